@@ -2,6 +2,7 @@ use anyhow::{bail, Context};
 use std::env;
 use std::io::ErrorKind;
 use std::process::{Command, Stdio};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 fn main() -> anyhow::Result<()> {
     println!("cargo:rerun-if-changed=.git/HEAD");
@@ -14,9 +15,67 @@ fn main() -> anyhow::Result<()> {
         }
         None => println!("cargo:rustc-env=VERSION_WITH_GIT={pkg_version}"),
     }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rustc-env=BUILD_DATE={}", get_build_date()?);
+    println!("cargo:rustc-env=RUSTC_VERSION={}", get_rustc_version()?);
+    println!(
+        "cargo:rustc-env=BUILD_FEATURES={}",
+        get_features().join(",")
+    );
     Ok(())
 }
 
+/// Return the build timestamp in RFC 3339 format.  If `SOURCE_DATE_EPOCH` is
+/// set (for reproducible builds), it is used instead of the current time.
+fn get_build_date() -> anyhow::Result<String> {
+    let now = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(s) => {
+            let epoch = s
+                .trim()
+                .parse::<i64>()
+                .context("SOURCE_DATE_EPOCH is not an integer")?;
+            OffsetDateTime::from_unix_timestamp(epoch)
+                .context("SOURCE_DATE_EPOCH is out of range")?
+        }
+        Err(_) => OffsetDateTime::now_utc()
+            .replace_nanosecond(0)
+            .context("failed to truncate build timestamp")?,
+    };
+    now.format(&Rfc3339)
+        .context("failed to format build timestamp")
+}
+
+/// Return the output of `$RUSTC --version`
+fn get_rustc_version() -> anyhow::Result<String> {
+    let rustc = getenv("RUSTC")?;
+    let output = Command::new(&rustc)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("failed to run `{rustc} --version`"))?;
+    if !output.status.success() {
+        bail!(
+            "`{rustc} --version` command was not successful: {}",
+            output.status
+        );
+    }
+    Ok(std::str::from_utf8(&output.stdout)
+        .context("`rustc --version` output was not UTF-8")?
+        .trim()
+        .to_owned())
+}
+
+/// Return the names of the Cargo features enabled for the build, sorted
+fn get_features() -> Vec<String> {
+    let mut features = env::vars()
+        .filter_map(|(k, _)| {
+            k.strip_prefix("CARGO_FEATURE_")
+                .map(|f| f.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+    features
+}
+
 fn get_commit_hash() -> anyhow::Result<Option<String>> {
     let manifest_dir = getenv("CARGO_MANIFEST_DIR")?;
     match Command::new("git")
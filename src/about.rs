@@ -0,0 +1,315 @@
+//! Tracking of the API & metadata schema versions reported by the primary
+//! Archive instance, and the `/about` endpoint reporting them alongside
+//! `dandidav`'s own version
+use crate::consts::{JSON_CONTENT_TYPE, KNOWN_SCHEMA_MAJOR_VERSIONS};
+use crate::dandi::{DandiClient, DandiError, InstanceInfo};
+use crate::version::VersionInfo;
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, response::Response},
+    response::IntoResponse,
+};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// A record of the software & metadata schema versions most recently reported
+/// by the primary Archive instance's `/info/` endpoint, refreshed
+/// periodically so that breaking changes to the upstream API are noticed
+/// early
+#[derive(Debug)]
+pub(crate) struct UpstreamVersions {
+    dandi: DandiClient,
+    latest: Mutex<Option<UpstreamProbe>>,
+}
+
+impl UpstreamVersions {
+    pub(crate) fn new(dandi: DandiClient) -> UpstreamVersions {
+        UpstreamVersions {
+            dandi,
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// Record the result of a request to the Archive's `/info/` endpoint.  If
+    /// the request failed, the versions from the last successful request (if
+    /// any) are retained.
+    pub(crate) fn record(&self, result: Result<&InstanceInfo, DandiError>) {
+        let mut latest = self.lock();
+        let versions = match result {
+            Ok(info) => {
+                let versions = ApiVersions::new(info);
+                if !versions.schema_known {
+                    tracing::warn!(
+                        schema_version = versions.schema_version.as_deref(),
+                        api_version = versions.api_version,
+                        "Archive reports a metadata schema version with an unknown major version; dandidav may not be compatible with it",
+                    );
+                }
+                Some(versions)
+            }
+            Err(_) => latest.as_ref().and_then(|p| p.versions.clone()),
+        };
+        *latest = Some(UpstreamProbe {
+            checked_at: OffsetDateTime::now_utc(),
+            versions,
+            error: result.err().map(|e| format!("{:#}", anyhow::Error::new(e))),
+        });
+    }
+
+    /// Fetch the Archive's `/info/` endpoint and record the result
+    pub(crate) async fn probe(&self) {
+        match self.dandi.instance_info().await {
+            Ok(info) => self.record(Ok(&info)),
+            Err(e) => {
+                tracing::warn!(error = ?e, "Failed to fetch Archive API & schema versions");
+                self.record(Err(e));
+            }
+        }
+    }
+
+    /// Spawn a task that calls [`UpstreamVersions::probe()`] every `period`
+    pub(crate) fn install_periodic_probe(self: &Arc<Self>, period: Duration) {
+        let versions = Arc::clone(self);
+        let mut schedule = tokio::time::interval(period);
+        schedule.reset(); // Don't tick immediately
+        schedule.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        tokio::spawn(async move {
+            loop {
+                schedule.tick().await;
+                versions.probe().await;
+            }
+        });
+    }
+
+    /// Returns `true` if the Archive has reported a metadata schema version
+    /// whose major version is not one that `dandidav` is known to work with.
+    /// If no versions have been successfully retrieved yet, this is `false`.
+    pub(crate) fn schema_unknown(&self) -> bool {
+        self.lock()
+            .as_ref()
+            .and_then(|p| p.versions.as_ref())
+            .is_some_and(|v| !v.schema_known)
+    }
+
+    /// Return the schema version most recently reported by the Archive, if
+    /// any
+    pub(crate) fn schema_version(&self) -> Option<String> {
+        self.lock()
+            .as_ref()
+            .and_then(|p| p.versions.as_ref())
+            .and_then(|v| v.schema_version.clone())
+    }
+
+    /// Respond to a request for `/about` with a JSON document describing the
+    /// versions of `dandidav` and of the primary Archive instance
+    pub(crate) fn to_response(&self) -> Response<Body> {
+        let about = About {
+            dandidav: VersionInfo::current(),
+            archive: ArchiveAbout {
+                api_url: self.dandi.api_url().as_str(),
+                probe: self.lock().clone(),
+            },
+        };
+        let body = serde_json::to_string(&about).expect("serializing About should not fail");
+        // Note: This response should not have WebDAV headers (DAV, Allow)
+        ([(CONTENT_TYPE, JSON_CONTENT_TYPE)], body).into_response()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<UpstreamProbe>> {
+        self.latest
+            .lock()
+            .expect("UpstreamVersions mutex should not be poisoned")
+    }
+}
+
+/// The result of the most recent request to the Archive's `/info/` endpoint
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct UpstreamProbe {
+    /// The time at which the request was made
+    #[serde(with = "time::serde::rfc3339")]
+    checked_at: OffsetDateTime,
+
+    /// The versions reported by the most recent successful request, if any
+    #[serde(flatten)]
+    versions: Option<ApiVersions>,
+
+    /// A description of the error that occurred, if the request failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Software & metadata schema versions reported by an Archive instance
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct ApiVersions {
+    /// The version of the Archive software
+    api_version: String,
+
+    /// The version of the DANDI metadata schema used by the Archive, if
+    /// reported
+    schema_version: Option<String>,
+
+    /// Whether the major version of `schema_version` is one that `dandidav`
+    /// is known to work with
+    schema_known: bool,
+}
+
+impl ApiVersions {
+    fn new(info: &InstanceInfo) -> ApiVersions {
+        let schema_known = info
+            .schema_version
+            .as_deref()
+            .and_then(schema_major_version)
+            .is_some_and(|major| KNOWN_SCHEMA_MAJOR_VERSIONS.contains(&major));
+        ApiVersions {
+            api_version: info.version.clone(),
+            schema_version: info.schema_version.clone(),
+            schema_known,
+        }
+    }
+}
+
+/// Return the major version of a schema version string of the form
+/// `MAJOR.MINOR.PATCH`
+fn schema_major_version(s: &str) -> Option<u64> {
+    let (major, _) = s.split_once('.')?;
+    major.parse().ok()
+}
+
+/// The body of a response to a request for `/about`
+#[derive(Clone, Debug, Serialize)]
+struct About<'a> {
+    /// Version & build information about `dandidav`
+    dandidav: VersionInfo,
+
+    /// Information about the primary Archive instance
+    archive: ArchiveAbout<'a>,
+}
+
+/// Information about the primary Archive instance reported by `/about`
+#[derive(Clone, Debug, Serialize)]
+struct ArchiveAbout<'a> {
+    /// The Archive's API URL
+    api_url: &'a str,
+
+    /// The result of the most recent request for the Archive's versions, if
+    /// any have been made yet
+    #[serde(flatten)]
+    probe: Option<UpstreamProbe>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dandi::{ApiCacheConfig, ZarrLookup};
+    use crate::httputil::{FakeTransport, HttpConfig, RetryPolicy};
+    use crate::paths::ZarrExtensions;
+    use crate::s3::S3Config;
+    use rstest::rstest;
+    use serde_json::json;
+
+    static INFO_URL: &str = "https://api.example.com/api/info/";
+
+    fn upstream(transport: FakeTransport) -> UpstreamVersions {
+        let dandi = DandiClient::new(
+            "https://api.example.com/api".parse().unwrap(),
+            ApiCacheConfig {
+                max_entries: 0,
+                ttl: Duration::from_secs(1),
+            },
+            RetryPolicy::default(),
+            S3Config {
+                connect_timeout: Duration::from_secs(1),
+                read_timeout: Duration::from_secs(1),
+                max_attempts: 1,
+                list_concurrency: 1,
+            },
+            HttpConfig::default(),
+            ZarrLookup {
+                extensions: ZarrExtensions::default(),
+                max_candidates: 1,
+                exhaustive: false,
+            },
+            0,
+            None,
+        )
+        .unwrap()
+        .with_transport(Arc::new(transport));
+        UpstreamVersions::new(dandi)
+    }
+
+    async fn about(upstream: &UpstreamVersions) -> serde_json::Value {
+        let body = axum::body::to_bytes(upstream.to_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn known_schema() {
+        let upstream = upstream(FakeTransport::new().json(
+            INFO_URL,
+            &json!({"version": "0.11.0", "schema_version": "0.6.9"}),
+        ));
+        upstream.probe().await;
+        assert!(!upstream.schema_unknown());
+        assert_eq!(upstream.schema_version().as_deref(), Some("0.6.9"));
+        let about = about(&upstream).await;
+        assert_eq!(about["dandidav"]["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(about["archive"]["api_url"], "https://api.example.com/api");
+        assert_eq!(about["archive"]["api_version"], "0.11.0");
+        assert_eq!(about["archive"]["schema_version"], "0.6.9");
+        assert_eq!(about["archive"]["schema_known"], true);
+        assert!(about["archive"]["checked_at"].is_string());
+        assert!(about["archive"].get("error").is_none());
+    }
+
+    #[rstest]
+    #[case(json!({"version": "1.0.0", "schema_version": "1.0.0"}))]
+    #[case(json!({"version": "1.0.0", "schema_version": "garbage"}))]
+    #[case(json!({"version": "1.0.0"}))]
+    #[tokio::test]
+    async fn unknown_schema(#[case] info: serde_json::Value) {
+        let upstream = upstream(FakeTransport::new().json(INFO_URL, &info));
+        upstream.probe().await;
+        assert!(upstream.schema_unknown());
+        assert_eq!(about(&upstream).await["archive"]["schema_known"], false);
+    }
+
+    #[tokio::test]
+    async fn failure_retains_versions() {
+        let upstream = upstream(FakeTransport::new());
+        let info = serde_json::from_value::<InstanceInfo>(
+            json!({"version": "0.11.0", "schema_version": "0.6.9"}),
+        )
+        .unwrap();
+        upstream.record(Ok(&info));
+        upstream.probe().await;
+        assert_eq!(upstream.schema_version().as_deref(), Some("0.6.9"));
+        let about = about(&upstream).await;
+        assert_eq!(about["archive"]["api_version"], "0.11.0");
+        assert!(about["archive"]["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn never_probed() {
+        let upstream = upstream(FakeTransport::new());
+        assert!(!upstream.schema_unknown());
+        assert_eq!(upstream.schema_version(), None);
+        let about = about(&upstream).await;
+        assert_eq!(about["archive"]["api_url"], "https://api.example.com/api");
+        assert!(about["archive"].get("checked_at").is_none());
+    }
+
+    #[rstest]
+    #[case("0.6.9", Some(0))]
+    #[case("1.0.0", Some(1))]
+    #[case("10.2", Some(10))]
+    #[case("0", None)]
+    #[case("v0.6.9", None)]
+    #[case("", None)]
+    fn test_schema_major_version(#[case] s: &str, #[case] major: Option<u64>) {
+        assert_eq!(schema_major_version(s), major);
+    }
+}
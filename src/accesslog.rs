@@ -0,0 +1,286 @@
+//! Structured access logging with per-request summaries of upstream activity
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header::USER_AGENT, response::Response},
+    middleware::Next,
+};
+use bytes::Bytes;
+use http_body::{Frame, SizeHint};
+use pin_project::pin_project;
+use serde::Serialize;
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::task::{Context, Poll};
+use tokio::time::Instant;
+
+tokio::task_local! {
+    /// The activity of the request currently being handled by the task, if
+    /// access logging is enabled
+    static ACTIVITY: Arc<RequestActivity>;
+}
+
+/// Record that the current request made an HTTP request to an upstream
+/// service.  This is a no-op if access logging is disabled or if called
+/// outside of a request's task.
+pub(crate) fn note_upstream_call() {
+    let _ = ACTIVITY.try_with(|a| a.upstream_calls.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Record that the current request looked up a value in a cache.  This is a
+/// no-op if access logging is disabled or if called outside of a request's
+/// task.
+pub(crate) fn note_cache_lookup(hit: bool) {
+    let _ = ACTIVITY.try_with(|a| {
+        if hit {
+            a.cache_hits.fetch_add(1, Ordering::Relaxed)
+        } else {
+            a.cache_misses.fetch_add(1, Ordering::Relaxed)
+        }
+    });
+}
+
+/// A response extension giving the type of the request path (e.g.,
+/// `"dandiset"` or `"zarr-path"`), as used in metrics labels, for inclusion
+/// in the access log
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PathKind(pub(crate) &'static str);
+
+/// Counts of the upstream activity performed while handling a request
+#[derive(Debug, Default)]
+struct RequestActivity {
+    upstream_calls: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+/// Log a single JSON record for each request (in the `access_json` field of a
+/// log line with `access_event` set to `"request"`) once its response body
+/// has been sent or dropped.
+///
+/// Each record contains the request method & `User-Agent`, the type of the
+/// request path, the response status, the number of response body bytes
+/// produced, the time from receipt of the request until the body was
+/// finished, and the numbers of upstream HTTP requests, cache hits, and cache
+/// misses performed by the request's task (including while producing a
+/// streamed body).  Work done by background tasks spawned on the request's
+/// behalf (e.g., prefetching of pages) is not counted.
+///
+/// This middleware must be applied outside of `handle_head` so that `HEAD`
+/// requests are recorded with their actual method.
+pub(crate) async fn log_access(request: Request<Body>, next: Next) -> Response<Body> {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(ToOwned::to_owned);
+    let activity = Arc::new(RequestActivity::default());
+    let resp = ACTIVITY
+        .scope(Arc::clone(&activity), next.run(request))
+        .await;
+    let record = AccessRecord {
+        method,
+        path_kind: resp.extensions().get::<PathKind>().map_or("other", |k| k.0),
+        status: resp.status().as_u16(),
+        user_agent,
+        start,
+        bytes: 0,
+        activity,
+    };
+    resp.map(|inner| Body::new(LoggedBody { inner, record }))
+}
+
+/// The in-progress access log record for a request, which is logged when
+/// dropped
+#[derive(Debug)]
+struct AccessRecord {
+    method: String,
+    path_kind: &'static str,
+    status: u16,
+    user_agent: Option<String>,
+    start: Instant,
+    bytes: u64,
+    activity: Arc<RequestActivity>,
+}
+
+impl Drop for AccessRecord {
+    fn drop(&mut self) {
+        let entry = AccessLogEntry {
+            method: &self.method,
+            path_kind: self.path_kind,
+            status: self.status,
+            user_agent: self.user_agent.as_deref(),
+            bytes: self.bytes,
+            duration_secs: self.start.elapsed().as_secs_f64(),
+            upstream_calls: self.activity.upstream_calls.load(Ordering::Relaxed),
+            cache_hits: self.activity.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.activity.cache_misses.load(Ordering::Relaxed),
+        };
+        entry.log();
+    }
+}
+
+/// A single record in the access log
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct AccessLogEntry<'a> {
+    method: &'a str,
+    path_kind: &'static str,
+    status: u16,
+    user_agent: Option<&'a str>,
+    bytes: u64,
+    duration_secs: f64,
+    upstream_calls: u64,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+
+impl AccessLogEntry<'_> {
+    fn log(&self) {
+        match serde_json::to_string(self) {
+            Ok(access_json) => tracing::info!(
+                access_event = "request",
+                %access_json,
+                "{} request completed with status {}",
+                self.method,
+                self.status,
+            ),
+            Err(e) => tracing::warn!(error = %e, "Failed to serialize access log record"),
+        }
+    }
+}
+
+/// A response body that counts the bytes it produces in an [`AccessRecord`]
+/// and that polls the inner body with the request's [`RequestActivity`] in
+/// scope, so that upstream requests made while streaming are counted
+#[pin_project]
+struct LoggedBody {
+    #[pin]
+    inner: Body,
+    record: AccessRecord,
+}
+
+impl http_body::Body for LoggedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let this = self.project();
+        let activity = Arc::clone(&this.record.activity);
+        let inner = this.inner;
+        let r = ACTIVITY.sync_scope(activity, || inner.poll_frame(cx));
+        if let Poll::Ready(Some(Ok(ref frame))) = r {
+            if let Some(data) = frame.data_ref() {
+                this.record.bytes = this
+                    .record
+                    .bytes
+                    .saturating_add(u64::try_from(data.len()).unwrap_or(u64::MAX));
+            }
+        }
+        r
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, middleware, response::IntoResponse, routing::get, Router};
+    use futures_util::stream;
+    use http_body::Body as _;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn counts_activity() {
+        let activity = Arc::new(RequestActivity::default());
+        ACTIVITY
+            .scope(Arc::clone(&activity), async {
+                note_upstream_call();
+                note_upstream_call();
+                note_cache_lookup(true);
+                note_cache_lookup(false);
+                note_cache_lookup(true);
+            })
+            .await;
+        // Outside of a request, nothing is counted (and nothing panics):
+        note_upstream_call();
+        assert_eq!(activity.upstream_calls.load(Ordering::Relaxed), 2);
+        assert_eq!(activity.cache_hits.load(Ordering::Relaxed), 2);
+        assert_eq!(activity.cache_misses.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn counts_bytes() {
+        let record = AccessRecord {
+            method: "GET".into(),
+            path_kind: "root",
+            status: 200,
+            user_agent: None,
+            start: Instant::now(),
+            bytes: 0,
+            activity: Arc::new(RequestActivity::default()),
+        };
+        let chunks = stream::iter(["foo", "quux"].map(Ok::<_, std::io::Error>));
+        let body = LoggedBody {
+            inner: Body::from_stream(chunks),
+            record,
+        };
+        let mut body = std::pin::pin!(body);
+        while std::future::poll_fn(|cx| body.as_mut().poll_frame(cx))
+            .await
+            .is_some()
+        {}
+        assert_eq!(body.record.bytes, 7);
+    }
+
+    #[tokio::test]
+    async fn preserves_response() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    let mut resp = "Hello".into_response();
+                    resp.extensions_mut().insert(PathKind("root"));
+                    resp
+                }),
+            )
+            .route(
+                "/streamed",
+                get(|| async {
+                    note_upstream_call();
+                    let chunks = stream::iter(["foo", "bar"].map(Ok::<_, std::io::Error>));
+                    Body::from_stream(chunks)
+                }),
+            )
+            .layer(middleware::from_fn(log_access));
+        let resp = app
+            .clone()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.body().size_hint().exact(), Some(5));
+        let resp = app
+            .oneshot(Request::get("/streamed").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"foobar");
+    }
+}
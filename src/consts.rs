@@ -12,6 +12,32 @@ pub(crate) static USER_AGENT: &str = concat!(
     ")",
 );
 
+/// The default value of the `--max-retries` command-line option
+pub(crate) const DEFAULT_MAX_RETRIES: u32 = 4;
+
+/// The default value of the `--retry-backoff` command-line option, in
+/// milliseconds
+pub(crate) const DEFAULT_RETRY_BACKOFF_MS: u64 = 1000;
+
+/// The maximum delay before retrying a failed outgoing HTTP request
+pub(crate) const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The default value of the `--retry-budget` command-line option, in percent
+pub(crate) const DEFAULT_RETRY_BUDGET_PERCENT: u32 = 20;
+
+/// The number of retries of outgoing HTTP requests that may be made by a
+/// client regardless of how many requests it has sent, i.e., the size of the
+/// client's retry budget when it is first created
+pub(crate) const RETRY_BUDGET_RESERVE: u64 = 10;
+
+/// The default value of the `--circuit-breaker-threshold` command-line
+/// option
+pub(crate) const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+
+/// The default value of the `--circuit-breaker-cooldown` command-line
+/// option, in seconds
+pub(crate) const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS: u64 = 30;
+
 /// The "Server" value returned in all responses from dandidav
 pub(crate) static SERVER_VALUE: &str =
     concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
@@ -19,48 +45,438 @@ pub(crate) static SERVER_VALUE: &str =
 /// The default value of the `--api-url` command-line option
 pub(crate) static DEFAULT_API_URL: &str = "https://api.dandiarchive.org/api";
 
-/// File extensions (case sensitive) for Zarrs, including the leading periods
+/// The default file extensions (matched case-insensitively) for Zarrs,
+/// including the leading periods
 pub(crate) static ZARR_EXTENSIONS: [&str; 2] = [".zarr", ".ngff"];
 
 /// The maximum number of S3 clients cached at once by `DandiClient`
 pub(crate) const S3CLIENT_CACHE_SIZE: u64 = 8;
 
+/// If a cached S3 client's requests fail this many times in a row, the client
+/// is evicted from `DandiClient`'s cache and rebuilt
+pub(crate) const S3CLIENT_MAX_CONSECUTIVE_FAILURES: u32 = 5;
+
+/// The default value of the `--s3-connect-timeout` command-line option
+pub(crate) const DEFAULT_S3_CONNECT_TIMEOUT_SECS: u64 = 5;
+
+/// The default value of the `--s3-read-timeout` command-line option
+pub(crate) const DEFAULT_S3_READ_TIMEOUT_SECS: u64 = 30;
+
+/// The region assumed for buckets on custom S3-compatible endpoints given via
+/// `--s3-endpoint`, as expected by MinIO and most other S3-compatible stores
+pub(crate) static CUSTOM_S3_ENDPOINT_REGION: &str = "us-east-1";
+
+/// The default value of the `--s3-list-concurrency` command-line option
+pub(crate) const DEFAULT_S3_LIST_CONCURRENCY: usize = 1;
+
+/// The default value of the `--s3-max-attempts` command-line option
+pub(crate) const DEFAULT_S3_MAX_ATTEMPTS: u32 = 3;
+
+/// The "Cache-Control" value for responses describing resources that never
+/// change, i.e., published Dandiset versions & immutable Zarr paths.  The
+/// `immutable` directive is not used, as the rendering of such resources can
+/// still change when `dandidav` is upgraded or reconfigured.
+pub(crate) static IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=604800";
+
+/// The "Cache-Control" value for responses describing resources that may
+/// change, requiring caches to revalidate them before reuse
+pub(crate) static MUTABLE_CACHE_CONTROL: &str = "no-cache";
+
+/// The "Cache-Control" value that replaces the others in responses to
+/// requests made with client-supplied Archive API tokens, so that shared
+/// caches do not store them
+pub(crate) static PRIVATE_CACHE_CONTROL: &str = "private, no-cache";
+
 /// The "Content-Type" value for HTML responses to `GET` requests for
 /// collections
 pub(crate) static HTML_CONTENT_TYPE: &str = "text/html; charset=utf-8";
 
+/// The "Content-Type" value for JSON responses to `GET` requests for
+/// collections
+pub(crate) static JSON_CONTENT_TYPE: &str = "application/json";
+
 /// The "Content-Type" value for the stylesheet
 pub(crate) static CSS_CONTENT_TYPE: &str = "text/css; charset=utf-8";
 
+/// The "Content-Type" value for the script for sorting & filtering collection
+/// tables
+pub(crate) static JAVASCRIPT_CONTENT_TYPE: &str = "text/javascript; charset=utf-8";
+
 /// The "Content-Type" value (reported in both `GET` and `PROPFIND` responses)
 /// for virtual `dandiset.yaml` files
 pub(crate) static YAML_CONTENT_TYPE: &str = "text/yaml; charset=utf-8";
 
+/// The "Content-Type" value (reported in both `GET` and `PROPFIND` responses)
+/// for virtual asset metadata sidecar files
+pub(crate) static SIDECAR_CONTENT_TYPE: &str = "application/json";
+
+/// The "Content-Type" value (reported in both `GET` and `PROPFIND` responses)
+/// for virtual `assets.jsonl` files
+pub(crate) static JSONL_CONTENT_TYPE: &str = "application/jsonl";
+
+/// The "Content-Type" value (reported in both `GET` and `PROPFIND` responses)
+/// for virtual `releases.tsv` files
+pub(crate) static TSV_CONTENT_TYPE: &str = "text/tab-separated-values; charset=utf-8";
+
+/// The suffix appended to an asset's path to form the path of its virtual
+/// metadata sidecar file when `--asset-sidecars` is in effect
+pub(crate) static ASSET_SIDECAR_SUFFIX: &str = ".dandi.json";
+
+/// The filename extensions of the assets & Zarr entries that are served
+/// inline as plain text when `--inline-preview-max-bytes` is in effect
+pub(crate) static INLINE_PREVIEW_EXTENSIONS: &[&str] = &[".json", ".tsv", ".yaml", ".yml"];
+
 /// The "Content-Type" value given in `PROPFIND` responses for blob assets with
 /// no `encodingFormat` set and also for Zarr entries
 pub(crate) static DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
 
+/// The "Content-Type" value for `/.well-known/security.txt`, `/humans.txt`,
+/// and assets served inline for previewing
+pub(crate) static TEXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+/// The "Content-Type" value for responses to requests for `/metrics`
+pub(crate) static METRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
 /// The "Content-Type" value for `PROPFIND` XML responses
 pub(crate) static DAV_XML_CONTENT_TYPE: &str = "text/xml; charset=utf-8";
 
 /// The XML namespace for standard WebDAV elements
 pub(crate) static DAV_XMLNS: &str = "DAV:";
 
+/// The XML namespace for WebDAV properties specific to `dandidav`
+pub(crate) static DANDIDAV_XMLNS: &str = "https://github.com/dandi/dandidav";
+
 /// The display format for timestamps shown in collections' HTML views (after
 /// converting to UTC)
 pub(crate) static HTML_TIMESTAMP_FORMAT: &[FormatItem<'_>] =
     format_description!("[year]-[month]-[day] [hour]:[minute]:[second]Z");
 
+/// The default display format for timestamps shown in collections' HTML views
+/// when converting to a UTC offset other than zero
+pub(crate) static HTML_OFFSET_TIMESTAMP_FORMAT: &[FormatItem<'_>] = format_description!(
+    "[year]-[month]-[day] [hour]:[minute]:[second][offset_hour sign:mandatory]:[offset_minute]"
+);
+
+/// The format for UTC offsets given on the command line
+pub(crate) static UTC_OFFSET_FORMAT: &[FormatItem<'_>] =
+    format_description!("[offset_hour sign:mandatory]:[offset_minute]");
+
 /// If a client makes a request for a resource with one of these names as a
 /// component (case insensitive), assume it doesn't exist without bothering to
-/// check the backend.
+/// check the backend.  Further names can be added with the `--fast-not-exist`
+/// and `--fast-not-exist-file` command-line options.
 ///
-/// This list must be kept in sorted order; this is enforced by a test below.
-pub(crate) static FAST_NOT_EXIST: &[&str] = &[".bzr", ".git", ".nols", ".svn"];
+/// This list must be kept in sorted order and in lowercase; this is enforced
+/// by a test below.
+pub(crate) static FAST_NOT_EXIST: &[&str] = &[
+    ".bzr",
+    ".ds_store",
+    ".git",
+    ".nols",
+    ".svn",
+    "desktop.ini",
+    "lost+found",
+    "thumbs.db",
+];
+
+/// The default value of the `--max-infinite-depth-resources` command-line
+/// option
+pub(crate) const DEFAULT_MAX_INFINITE_DEPTH_RESOURCES: usize = 10_000;
+
+/// The default value of the `--max-redirects` command-line option
+pub(crate) const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// The default value of the `--pool-idle-timeout` command-line option, in
+/// seconds
+pub(crate) const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+
+/// The default value of the `--tcp-keepalive` command-line option, in
+/// seconds
+pub(crate) const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 60;
+
+/// The default value of the `--listing-timeout` command-line option, in
+/// seconds
+pub(crate) const DEFAULT_LISTING_TIMEOUT_SECS: u64 = 120;
+
+/// The default value of the `--redirect-timeout` command-line option, in
+/// seconds
+pub(crate) const DEFAULT_REDIRECT_TIMEOUT_SECS: u64 = 30;
+
+/// The default value of the `--stream-timeout` command-line option, in
+/// seconds
+pub(crate) const DEFAULT_STREAM_TIMEOUT_SECS: u64 = 600;
+
+/// The default value of the `--max-zarr-candidates` command-line option
+pub(crate) const DEFAULT_MAX_ZARR_CANDIDATES: usize = 8;
+
+/// The default value of the `--page-prefetch` command-line option
+pub(crate) const DEFAULT_PAGE_PREFETCH: usize = 1;
+
+/// The maximum number of collections whose children are fetched at once when
+/// responding to a `PROPFIND` request with an infinite `Depth`
+pub(crate) const INFINITE_DEPTH_CONCURRENCY: usize = 8;
+
+/// The maximum number of times to list the children of a resource in a draft
+/// Dandiset version in response to a `PROPFIND` request with a `Depth` of 1
+/// when the version is modified while the children are being listed
+pub(crate) const DRAFT_LISTING_ATTEMPTS: usize = 2;
+
+/// The maximum total number of members across all snapshots of Dandiset
+/// versions retained for answering `sync-collection` reports with previously
+/// issued sync tokens
+pub(crate) const SYNC_SNAPSHOT_CACHE_SIZE: u64 = 1_000_000;
+
+/// The maximum number of bytes of a resource that are downloaded in a single
+/// request when copying the resource to another server in response to a
+/// `COPY` request
+pub(crate) const COPY_CHUNK_SIZE: u64 = 16 << 20;
+
+/// The name of the request header whose value is sent as the `Authorization`
+/// header when uploading a resource to the destination of a `COPY` request
+pub(crate) static DESTINATION_AUTHORIZATION_HEADER: &str = "x-destination-authorization";
+
+/// The default value of the `--api-cache-size` command-line option
+pub(crate) const DEFAULT_API_CACHE_SIZE: u64 = 1000;
+
+/// The default value of the `--api-cache-ttl` command-line option, in seconds
+pub(crate) const DEFAULT_API_CACHE_TTL_SECS: u64 = 60;
+
+/// The default value of the `--published-cache-size` command-line option
+pub(crate) const DEFAULT_PUBLISHED_CACHE_SIZE: u64 = 100_000;
+
+/// The maximum time for which a failed lookup of a path in a Dandiset
+/// version is cached
+pub(crate) const MISSING_PATH_CACHE_TTL: Duration = Duration::from_secs(15);
+
+/// Interval between periodic logging of the Archive API response caches' hit &
+/// miss counts
+pub(crate) const API_CACHE_STATS_PERIOD: Duration = Duration::from_secs(3600);
+
+/// The maximum amount of time to wait for each upstream service to respond
+/// when handling a request for `/readyz`
+pub(crate) const READINESS_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Interval between periodic logging of the Zarr manifest cache's contents
 pub(crate) const ZARR_MANIFEST_CACHE_DUMP_PERIOD: Duration = Duration::from_secs(3600);
 
+/// The maximum amount of time that a Zarr manifest fetch will wait in the
+/// queue for one of the `--zarrman-max-concurrent-fetches` slots before
+/// failing
+pub(crate) const ZARR_MANIFEST_FETCH_QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval between rereadings of the `--zarrman-pin-file` to update the set
+/// of pinned Zarr manifests
+pub(crate) const ZARR_MANIFEST_PIN_REFRESH_PERIOD: Duration = Duration::from_secs(600);
+
+/// The maximum (and default) number of entries per page in paginated HTML
+/// collection listings.  This is also the largest page size supported by the
+/// Archive API.
+pub(crate) const MAX_PER_PAGE: usize = 1000;
+
+/// The maximum size (in bytes) of the compiled form of a regular expression
+/// given in the `match` query parameter of a request, so that clients cannot
+/// make the server compile arbitrarily large patterns
+pub(crate) const MATCH_REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// The maximum value of the `depth` query parameter for JSON tree exports of
+/// collections; larger values are lowered to this
+pub(crate) const MAX_TREE_DEPTH: u32 = 10;
+
+/// The maximum number of resources that may be included in a JSON tree export
+/// of a collection
+pub(crate) const MAX_TREE_RESOURCES: usize = 10_000;
+
+/// The maximum number of collections whose children are fetched at once when
+/// building a JSON tree export of a collection
+pub(crate) const TREE_CONCURRENCY: usize = 8;
+
+/// The default value of the `--maintenance-retry-after` command-line option,
+/// in seconds
+pub(crate) const DEFAULT_MAINTENANCE_RETRY_AFTER_SECS: u64 = 300;
+
+/// The default value of the `--rate-limit-burst` command-line option
+pub(crate) const DEFAULT_RATE_LIMIT_BURST: u32 = 50;
+
+/// Interval between periodic removal of idle clients' state from the rate
+/// limiter
+pub(crate) const RATE_LIMITER_CLEANUP_PERIOD: Duration = Duration::from_secs(60);
+
+/// Interval between checks for whether the day covered by the aggregated
+/// usage statistics has ended and the statistics should be logged
+pub(crate) const USAGE_STATS_CHECK_PERIOD: Duration = Duration::from_secs(60);
+
+/// The maximum number of distinct clients tracked per day by the usage
+/// statistics.  Once this many have been seen, the day's count of unique
+/// clients is reported as a lower bound.
+pub(crate) const USAGE_MAX_CLIENTS: usize = 100_000;
+
+/// Error messages recorded for `/admin/errors` are truncated to this many
+/// characters
+pub(crate) const ERROR_LOG_MESSAGE_LIMIT: usize = 1000;
+
+/// The name of the virtual collection beneath each Dandiset version path that
+/// lists the version's assets matching a search query
+pub(crate) const SEARCH_COLLECTION_NAME: &str = ".search";
+
+/// The maximum number of assets listed in the results of a search of a
+/// Dandiset version; further matches are omitted
+pub(crate) const MAX_SEARCH_RESULTS: usize = 1000;
+
+/// The name of the virtual collection beneath each Dandiset version path that
+/// lists the BIDS datasets detected in the version
+pub(crate) const BIDS_COLLECTION_NAME: &str = ".bids";
+
+/// The name of the file whose presence in a folder marks the folder as the
+/// root of a BIDS dataset
+pub(crate) const BIDS_DESCRIPTION_FILE: &str = "dataset_description.json";
+
+/// When detecting BIDS datasets in a Dandiset version, only folders at most
+/// this many levels below the root of the version are examined
+pub(crate) const BIDS_MAX_DEPTH: usize = 2;
+
+/// When detecting BIDS datasets in a Dandiset version, at most this many
+/// folder listings are requested from the Archive; any further folders are
+/// not examined
+pub(crate) const BIDS_MAX_LISTINGS: usize = 200;
+
+/// How long browsers may cache the responses to CORS preflight requests
+pub(crate) const CORS_MAX_AGE: Duration = Duration::from_secs(86400);
+
+/// The default value of the `--jobs` option of the `mirror` subcommand
+pub(crate) const DEFAULT_MIRROR_JOBS: usize = 4;
+
+/// The maximum number of bytes of a file that the `mirror` subcommand
+/// downloads in a single request
+pub(crate) const MIRROR_CHUNK_SIZE: u64 = 64 << 20;
+
+/// The name of the manifest of mirrored files that the `mirror` subcommand
+/// writes in its output directory
+pub(crate) static MIRROR_MANIFEST_NAME: &str = ".dandidav-mirror.jsonl";
+
+/// The suffix appended to the names of files being downloaded by the `mirror`
+/// subcommand until they are complete
+pub(crate) static MIRROR_PARTIAL_SUFFIX: &str = ".dandidav-part";
+
+/// The maximum amount of time to wait for a client to complete a TLS
+/// handshake when serving HTTPS
+pub(crate) const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The maximum number of TLS connections that may have completed their
+/// handshakes without yet being picked up by the server
+pub(crate) const TLS_ACCEPT_QUEUE_SIZE: usize = 64;
+
+/// The major versions of the DANDI metadata schema that `dandidav` is known
+/// to work with.  If the primary Archive instance reports a schema version
+/// with any other major version, a warning is logged and `/readyz` reports a
+/// degraded status.
+pub(crate) static KNOWN_SCHEMA_MAJOR_VERSIONS: [u64; 1] = [0];
+
+/// Interval between requests to the primary Archive instance's `/info/`
+/// endpoint to check for changes to its API & schema versions
+pub(crate) const UPSTREAM_PROBE_PERIOD: Duration = Duration::from_secs(15 * 60);
+
+/// The "Content-Type" values reported for files served from local mounts,
+/// keyed by (lowercase) filename extension.  Files with other extensions are
+/// reported as [`DEFAULT_CONTENT_TYPE`].
+pub(crate) static LOCAL_CONTENT_TYPES: &[(&str, &str)] = &[
+    (".csv", "text/csv; charset=utf-8"),
+    (".gif", "image/gif"),
+    (".htm", "text/html; charset=utf-8"),
+    (".html", "text/html; charset=utf-8"),
+    (".jpeg", "image/jpeg"),
+    (".jpg", "image/jpeg"),
+    (".json", "application/json"),
+    (".jsonl", "application/jsonl"),
+    (".md", "text/markdown; charset=utf-8"),
+    (".nwb", "application/x-nwb"),
+    (".pdf", "application/pdf"),
+    (".png", "image/png"),
+    (".svg", "image/svg+xml"),
+    (".tsv", "text/tab-separated-values; charset=utf-8"),
+    (".txt", "text/plain; charset=utf-8"),
+    (".yaml", "text/yaml; charset=utf-8"),
+    (".yml", "text/yaml; charset=utf-8"),
+    (".zip", "application/zip"),
+];
+
+/// The maximum number of bytes read from a local file at a time when serving
+/// it from a local mount
+pub(crate) const LOCAL_READ_CHUNK_SIZE: usize = 64 << 10;
+
+/// The "Content-Type" value for `/sitemap.xml` and its pages
+pub(crate) static SITEMAP_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+
+/// The maximum number of URLs listed in a single sitemap file, per the
+/// [sitemaps protocol](https://www.sitemaps.org/protocol.html)
+pub(crate) const SITEMAP_MAX_URLS: usize = 50_000;
+
+/// How long a generated list of sitemap URLs is reused before being
+/// regenerated from the Archive's Dandiset listing
+pub(crate) const SITEMAP_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum number of Dandisets whose version lists are fetched at once
+/// when generating the sitemap
+pub(crate) const SITEMAP_CONCURRENCY: usize = 8;
+
+/// Route for the stylesheet used by HTML views
+pub(crate) const STYLESHEET_ROUTE: &str = "/.static/styles.css";
+
+/// Route for the script used by HTML views of collections
+pub(crate) const TABLE_SCRIPT_ROUTE: &str = "/.static/table.js";
+
+/// Route for the `security.txt` file
+pub(crate) const SECURITY_TXT_ROUTE: &str = "/.well-known/security.txt";
+
+/// Route for the `humans.txt` file
+pub(crate) const HUMANS_TXT_ROUTE: &str = "/humans.txt";
+
+/// Route for the `robots.txt` file
+pub(crate) const ROBOTS_TXT_ROUTE: &str = "/robots.txt";
+
+/// Route for the sitemap index
+pub(crate) const SITEMAP_INDEX_ROUTE: &str = "/sitemap.xml";
+
+/// Route for the individual pages of the sitemap
+pub(crate) const SITEMAP_PAGE_ROUTE: &str = "/sitemaps/{page}";
+
+/// Route for redirects from asset IDs to the assets' locations in the WebDAV
+/// hierarchy.  The route is also served with a trailing slash.
+pub(crate) const ASSET_REDIRECT_ROUTE: &str = "/assets/{asset_id}";
+
+/// Route for the liveness check
+pub(crate) const HEALTHZ_ROUTE: &str = "/healthz";
+
+/// Route for the readiness check
+pub(crate) const READYZ_ROUTE: &str = "/readyz";
+
+/// Route for information about the server & its upstreams
+pub(crate) const ABOUT_ROUTE: &str = "/about";
+
+/// Route for the log of recent error responses
+pub(crate) const ADMIN_ERRORS_ROUTE: &str = "/admin/errors";
+
+/// Route for Prometheus metrics
+pub(crate) const METRICS_ROUTE: &str = "/metrics";
+
+/// All routes served by something other than the WebDAV handler.  The first
+/// components of these routes cannot be used as the names of local mounts.
+pub(crate) const NON_DAV_ROUTES: [&str; 13] = [
+    STYLESHEET_ROUTE,
+    TABLE_SCRIPT_ROUTE,
+    SECURITY_TXT_ROUTE,
+    HUMANS_TXT_ROUTE,
+    ROBOTS_TXT_ROUTE,
+    SITEMAP_INDEX_ROUTE,
+    SITEMAP_PAGE_ROUTE,
+    ASSET_REDIRECT_ROUTE,
+    HEALTHZ_ROUTE,
+    READYZ_ROUTE,
+    ABOUT_ROUTE,
+    ADMIN_ERRORS_ROUTE,
+    METRICS_ROUTE,
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,4 +486,9 @@ mod tests {
     fn test_fast_not_exist_is_sorted() {
         assert!(FAST_NOT_EXIST.iter().tuple_windows().all(|(a, b)| a < b));
     }
+
+    #[test]
+    fn test_fast_not_exist_is_lowercase() {
+        assert!(FAST_NOT_EXIST.iter().all(|s| *s == s.to_ascii_lowercase()));
+    }
 }
@@ -0,0 +1,228 @@
+//! Cross-origin resource sharing (CORS) support, so that browser-based
+//! WebDAV clients served from other origins can make requests to `dandidav`
+use crate::consts::CORS_MAX_AGE;
+use axum::http::{
+    header::{
+        HeaderName, HeaderValue, ACCEPT_RANGES, AUTHORIZATION, CONTENT_LENGTH, CONTENT_RANGE,
+        CONTENT_TYPE, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE,
+    },
+    Method,
+};
+use std::str::FromStr;
+use thiserror::Error;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use url::Url;
+
+/// The methods that cross-origin requests may use
+static ALLOWED_METHODS: [&str; 6] = ["GET", "HEAD", "OPTIONS", "PROPFIND", "REPORT", "COPY"];
+
+/// The WebDAV-specific request headers (in addition to the standard headers
+/// listed in [`cors_layer()`]) that cross-origin requests may include
+static ALLOWED_DAV_HEADERS: [&str; 3] = ["depth", "destination", "overwrite"];
+
+/// The WebDAV-specific response headers (in addition to the standard headers
+/// listed in [`cors_layer()`]) that browsers should make available to
+/// scripts making cross-origin requests
+static EXPOSED_DAV_HEADERS: [&str; 2] = ["dav", "allow"];
+
+/// Construct a layer that answers CORS preflight requests and adds CORS
+/// headers to responses.
+///
+/// If `origins` is empty, requests from any origin are allowed, and responses
+/// carry `Access-Control-Allow-Origin: *`.  Otherwise, only requests from the
+/// given origins are allowed.
+pub(crate) fn cors_layer(origins: Vec<CorsOrigin>) -> CorsLayer {
+    let allow_origin = if origins.is_empty() {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.into_iter().map(|o| o.0))
+    };
+    let methods = ALLOWED_METHODS
+        .into_iter()
+        .map(|m| Method::from_bytes(m.as_bytes()).expect("CORS methods should be valid"))
+        .collect::<Vec<_>>();
+    let allow_headers = [
+        AUTHORIZATION,
+        CONTENT_TYPE,
+        IF_MATCH,
+        IF_MODIFIED_SINCE,
+        IF_NONE_MATCH,
+        RANGE,
+    ]
+    .into_iter()
+    .chain(ALLOWED_DAV_HEADERS.into_iter().map(HeaderName::from_static))
+    .collect::<Vec<_>>();
+    let expose_headers = [
+        ACCEPT_RANGES,
+        CONTENT_LENGTH,
+        CONTENT_RANGE,
+        ETAG,
+        LAST_MODIFIED,
+    ]
+    .into_iter()
+    .chain(EXPOSED_DAV_HEADERS.into_iter().map(HeaderName::from_static))
+    .collect::<Vec<_>>();
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(methods)
+        .allow_headers(allow_headers)
+        .expose_headers(expose_headers)
+        .max_age(CORS_MAX_AGE)
+}
+
+/// A web origin (a scheme, host, and optional port, e.g.,
+/// `https://example.com`) from which cross-origin requests are allowed
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct CorsOrigin(HeaderValue);
+
+impl FromStr for CorsOrigin {
+    type Err = ParseCorsOriginError;
+
+    /// Parse an HTTP(S) origin.  A trailing slash is permitted, but the
+    /// origin must not include a path, query string, or fragment.
+    fn from_str(s: &str) -> Result<CorsOrigin, ParseCorsOriginError> {
+        let url = Url::parse(s).map_err(|_| ParseCorsOriginError)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(ParseCorsOriginError);
+        }
+        let origin = url.origin().ascii_serialization();
+        if !s
+            .strip_suffix('/')
+            .unwrap_or(s)
+            .eq_ignore_ascii_case(&origin)
+        {
+            return Err(ParseCorsOriginError);
+        }
+        HeaderValue::from_str(&origin)
+            .map(CorsOrigin)
+            .map_err(|_| ParseCorsOriginError)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid CORS origin: expected an HTTP(S) URL with no path, e.g., https://example.com")]
+pub(crate) struct ParseCorsOriginError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{
+            header::{
+                ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+                ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+                ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+            },
+            response::Response,
+            StatusCode,
+        },
+        Router,
+    };
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    fn app(origins: Vec<CorsOrigin>) -> Router {
+        Router::new()
+            .fallback(|| async { ([("DAV", "1, 3")], "Hello\n") })
+            .layer(cors_layer(origins))
+    }
+
+    async fn request(
+        app: Router,
+        method: &str,
+        origin: &str,
+        headers: &[(&str, &str)],
+    ) -> Response<Body> {
+        let mut req = Request::builder()
+            .method(method)
+            .uri("/dandisets/")
+            .header(ORIGIN, origin);
+        for &(name, value) in headers {
+            req = req.header(name, value);
+        }
+        app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    fn header_list(resp: &Response<Body>, name: HeaderName) -> Vec<String> {
+        resp.headers()
+            .get_all(name)
+            .iter()
+            .flat_map(|v| v.to_str().unwrap().split(','))
+            .map(|s| s.trim().to_ascii_lowercase())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn preflight() {
+        let resp = request(
+            app(Vec::new()),
+            "OPTIONS",
+            "https://example.com",
+            &[
+                (ACCESS_CONTROL_REQUEST_METHOD.as_str(), "PROPFIND"),
+                (ACCESS_CONTROL_REQUEST_HEADERS.as_str(), "depth"),
+            ],
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        let methods = header_list(&resp, ACCESS_CONTROL_ALLOW_METHODS);
+        assert!(methods.contains(&"propfind".to_owned()));
+        assert!(methods.contains(&"report".to_owned()));
+        let headers = header_list(&resp, ACCESS_CONTROL_ALLOW_HEADERS);
+        assert!(headers.contains(&"depth".to_owned()));
+        assert!(headers.contains(&"range".to_owned()));
+        assert_eq!(resp.headers()[ACCESS_CONTROL_MAX_AGE], "86400");
+    }
+
+    #[tokio::test]
+    async fn exposes_headers() {
+        let resp = request(app(Vec::new()), "GET", "https://example.com", &[]).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        let exposed = header_list(&resp, ACCESS_CONTROL_EXPOSE_HEADERS);
+        for name in ["dav", "etag", "content-length"] {
+            assert!(exposed.contains(&name.to_owned()), "{name} not exposed");
+        }
+    }
+
+    #[rstest]
+    #[case("https://example.com", Some("https://example.com"))]
+    #[case("http://localhost:3000", Some("http://localhost:3000"))]
+    #[case("https://example.org", None)]
+    #[tokio::test]
+    async fn restricted_origins(#[case] origin: &str, #[case] allowed: Option<&str>) {
+        let origins = vec![
+            "https://example.com".parse().unwrap(),
+            "http://localhost:3000/".parse().unwrap(),
+        ];
+        let resp = request(app(origins), "GET", origin, &[]).await;
+        assert_eq!(
+            resp.headers()
+                .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+                .map(|v| v.to_str().unwrap()),
+            allowed
+        );
+    }
+
+    #[rstest]
+    #[case("https://example.com", "https://example.com")]
+    #[case("https://example.com/", "https://example.com")]
+    #[case("http://localhost:8080", "http://localhost:8080")]
+    #[case("HTTPS://EXAMPLE.COM", "https://example.com")]
+    fn parse_origin(#[case] s: &str, #[case] origin: &str) {
+        assert_eq!(s.parse::<CorsOrigin>().unwrap().0, origin);
+    }
+
+    #[rstest]
+    #[case("example.com")]
+    #[case("ftp://example.com")]
+    #[case("https://example.com/dav/")]
+    #[case("https://example.com?q=1")]
+    #[case("*")]
+    fn parse_bad_origin(#[case] s: &str) {
+        assert_eq!(s.parse::<CorsOrigin>(), Err(ParseCorsOriginError));
+    }
+}
@@ -0,0 +1,279 @@
+//! Caching of responses from the Archive API
+use crate::metrics::metrics;
+use moka::future::{Cache, CacheBuilder};
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+/// Settings for the caches of Archive API responses kept by
+/// [`DandiClient`][super::DandiClient]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct ApiCacheConfig {
+    /// The maximum number of responses to store in each cache.  A value of 0
+    /// disables caching.
+    pub(crate) max_entries: u64,
+
+    /// How long after being fetched a cached response expires.  A value of
+    /// zero disables caching.
+    pub(crate) ttl: Duration,
+}
+
+impl ApiCacheConfig {
+    /// Returns `true` if these settings disable caching
+    fn is_disabled(&self) -> bool {
+        self.max_entries == 0 || self.ttl.is_zero()
+    }
+}
+
+/// A cache of values computed from Archive API responses that keeps track of
+/// how often it is hit & missed
+///
+/// Only successful fetches made via [`ApiCache::get_or_fetch()`] are cached;
+/// errors are always returned to the caller as-is and the next lookup of the
+/// same key will fetch again.
+#[derive(Clone, Debug)]
+pub(super) struct ApiCache<K, V>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// The name of the cache, used in log messages
+    name: &'static str,
+
+    /// The inner cache, or `None` if caching is disabled
+    cache: Option<Cache<K, V>>,
+
+    /// The number of lookups that were served from the cache
+    hits: Arc<AtomicU64>,
+
+    /// The number of lookups that had to fetch a fresh value
+    misses: Arc<AtomicU64>,
+}
+
+impl<K, V> ApiCache<K, V>
+where
+    K: fmt::Debug + Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Construct a new `ApiCache` with the given name and settings
+    pub(super) fn new(name: &'static str, config: ApiCacheConfig) -> Self {
+        let cache = (!config.is_disabled()).then(|| {
+            CacheBuilder::new(config.max_entries)
+                .name(name)
+                .time_to_live(config.ttl)
+                .build()
+        });
+        ApiCache {
+            name,
+            cache,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Return the cached value for `key` if there is one.  Otherwise, await
+    /// `fetch`, cache its output if successful, and return it.
+    pub(super) async fn get_or_fetch<E, Fut>(&self, key: K, fetch: Fut) -> Result<V, E>
+    where
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let Some(ref cache) = self.cache else {
+            return fetch.await;
+        };
+        if let Some(value) = self.get(&key).await {
+            return Ok(value);
+        }
+        let value = fetch.await?;
+        cache.insert(key, value.clone()).await;
+        Ok(value)
+    }
+
+    /// Return the cached value for `key`, if any, recording the lookup as a
+    /// hit or miss
+    pub(super) async fn get(&self, key: &K) -> Option<V> {
+        let cache = self.cache.as_ref()?;
+        if let Some(value) = cache.get(key).await {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            metrics().record_cache_lookup(self.name, true);
+            tracing::debug!(
+                cache_event = "hit",
+                cache = self.name,
+                ?key,
+                "Fetched Archive API response from cache",
+            );
+            Some(value)
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            metrics().record_cache_lookup(self.name, false);
+            tracing::debug!(
+                cache_event = "miss",
+                cache = self.name,
+                ?key,
+                "Cache miss for Archive API response; about to fetch from Archive",
+            );
+            None
+        }
+    }
+
+    /// Cache `value` for `key`
+    pub(super) async fn insert(&self, key: K, value: V) {
+        if let Some(ref cache) = self.cache {
+            cache.insert(key, value).await;
+        }
+    }
+
+    /// Remove the cached value for `key`, if any
+    pub(super) async fn invalidate(&self, key: &K) {
+        if let Some(ref cache) = self.cache {
+            cache.invalidate(key).await;
+        }
+    }
+
+    /// Return the number of cache hits & misses so far
+    pub(super) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Log the number of cache hits & misses so far along with the hit rate
+    pub(super) fn log_stats(&self) {
+        let Some(ref cache) = self.cache else {
+            return;
+        };
+        let stats = self.stats();
+        tracing::info!(
+            cache_event = "stats",
+            cache = self.name,
+            hits = stats.hits,
+            misses = stats.misses,
+            hit_rate = stats.hit_rate(),
+            approx_cache_len = cache.entry_count(),
+            "Archive API response cache statistics",
+        );
+    }
+}
+
+/// Counts of hits & misses for an [`ApiCache`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(super) struct CacheStats {
+    pub(super) hits: u64,
+    pub(super) misses: u64,
+}
+
+impl CacheStats {
+    /// Return the fraction of lookups that were cache hits, or `None` if there
+    /// have not been any lookups
+    pub(super) fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits.saturating_add(self.misses);
+        #[allow(clippy::cast_precision_loss)]
+        (total > 0).then(|| self.hits as f64 / total as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn config() -> ApiCacheConfig {
+        ApiCacheConfig {
+            max_entries: 10,
+            ttl: Duration::from_secs(60),
+        }
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn fetch(counter: &AtomicUsize, value: &str) -> Result<String, String> {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Ok(value.to_owned())
+    }
+
+    #[tokio::test]
+    async fn hit_after_miss() {
+        let cache = ApiCache::<u32, String>::new("test", config());
+        let counter = AtomicUsize::new(0);
+        let r = cache.get_or_fetch(1, fetch(&counter, "foo")).await;
+        assert_eq!(r.as_deref(), Ok("foo"));
+        let r = cache.get_or_fetch(1, fetch(&counter, "bar")).await;
+        assert_eq!(r.as_deref(), Ok("foo"));
+        let r = cache.get_or_fetch(2, fetch(&counter, "baz")).await;
+        assert_eq!(r.as_deref(), Ok("baz"));
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+        let stats = cache.stats();
+        assert_eq!(stats, CacheStats { hits: 1, misses: 2 });
+        assert_eq!(stats.hit_rate(), Some(1.0 / 3.0));
+    }
+
+    #[tokio::test]
+    async fn errors_not_cached() {
+        let cache = ApiCache::<u32, String>::new("test", config());
+        let r = cache
+            .get_or_fetch(1, async { Err::<String, _>(String::from("oops")) })
+            .await;
+        assert_eq!(r, Err(String::from("oops")));
+        let counter = AtomicUsize::new(0);
+        let r = cache.get_or_fetch(1, fetch(&counter, "foo")).await;
+        assert_eq!(r.as_deref(), Ok("foo"));
+        assert_eq!(counter.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 2 });
+    }
+
+    #[tokio::test]
+    async fn expired() {
+        let cache = ApiCache::<u32, String>::new(
+            "test",
+            ApiCacheConfig {
+                max_entries: 10,
+                ttl: Duration::from_millis(50),
+            },
+        );
+        let counter = AtomicUsize::new(0);
+        let r = cache.get_or_fetch(1, fetch(&counter, "foo")).await;
+        assert_eq!(r.as_deref(), Ok("foo"));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let r = cache.get_or_fetch(1, fetch(&counter, "bar")).await;
+        assert_eq!(r.as_deref(), Ok("bar"));
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let cache = ApiCache::<u32, String>::new(
+            "test",
+            ApiCacheConfig {
+                max_entries: 0,
+                ttl: Duration::from_secs(60),
+            },
+        );
+        let counter = AtomicUsize::new(0);
+        let r = cache.get_or_fetch(1, fetch(&counter, "foo")).await;
+        assert_eq!(r.as_deref(), Ok("foo"));
+        let r = cache.get_or_fetch(1, fetch(&counter, "bar")).await;
+        assert_eq!(r.as_deref(), Ok("bar"));
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[tokio::test]
+    async fn insert_and_invalidate() {
+        let cache = ApiCache::<u32, String>::new("test", config());
+        assert_eq!(cache.get(&1).await, None);
+        cache.insert(1, String::from("foo")).await;
+        assert_eq!(cache.get(&1).await.as_deref(), Some("foo"));
+        cache.invalidate(&1).await;
+        assert_eq!(cache.get(&1).await, None);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+    }
+
+    #[test]
+    fn no_hit_rate() {
+        assert_eq!(CacheStats::default().hit_rate(), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Memoization of asset path lookups within a single request
+use super::{AtAssetPath, DandiError, DandisetId, VersionId};
+use crate::paths::PurePath;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// The key identifying an asset path lookup: a Dandiset ID, version ID, and
+/// path within the version
+type PathKey = (DandisetId, VersionId, PurePath);
+
+/// A record of the results of looking up paths in Dandiset versions' file
+/// hierarchies, shared by all [`VersionEndpoint`][super::VersionEndpoint]s
+/// used while handling a single request so that each distinct path is looked
+/// up at most once per request.
+///
+/// Unlike the client-wide cache of path lookups, this memo is not subject to
+/// any size limit or expiry (it is discarded when the request is done), and
+/// concurrent lookups of the same path share a single fetch.  Failed lookups
+/// are not memoized.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PathMemo(Arc<Mutex<HashMap<PathKey, Arc<OnceCell<AtAssetPath>>>>>);
+
+impl PathMemo {
+    /// Return the memoized result for `key` if there is one.  Otherwise,
+    /// await `fetch`, memoize its output if successful, and return it.  If a
+    /// lookup of `key` is already in progress, wait for it to complete
+    /// instead of awaiting `fetch`.
+    pub(super) async fn get_or_fetch<Fut>(
+        &self,
+        key: PathKey,
+        fetch: Fut,
+    ) -> Result<AtAssetPath, DandiError>
+    where
+        Fut: Future<Output = Result<AtAssetPath, DandiError>>,
+    {
+        let cell = {
+            let mut map = self
+                .0
+                .lock()
+                .expect("PathMemo mutex should not be poisoned");
+            Arc::clone(map.entry(key).or_default())
+        };
+        cell.get_or_try_init(|| fetch).await.cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dandi::AssetFolder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn key(path: &str) -> PathKey {
+        (
+            "000027".parse().unwrap(),
+            VersionId::Draft,
+            path.parse().unwrap(),
+        )
+    }
+
+    async fn fetch(counter: &AtomicUsize, path: &str) -> Result<AtAssetPath, DandiError> {
+        counter.fetch_add(1, Ordering::Relaxed);
+        tokio::task::yield_now().await;
+        Ok(AtAssetPath::Folder(AssetFolder {
+            path: format!("{path}/").parse().unwrap(),
+            total_size: None,
+            total_assets: None,
+        }))
+    }
+
+    #[tokio::test]
+    async fn fetch_once() {
+        let memo = PathMemo::default();
+        let counter = AtomicUsize::new(0);
+        let (r1, r2) = tokio::join!(
+            memo.get_or_fetch(key("foo"), fetch(&counter, "foo")),
+            memo.get_or_fetch(key("foo"), fetch(&counter, "foo")),
+        );
+        assert_eq!(r1.unwrap(), r2.unwrap());
+        let r3 = memo
+            .clone()
+            .get_or_fetch(key("foo"), fetch(&counter, "foo"))
+            .await;
+        assert!(r3.is_ok());
+        assert_eq!(counter.load(Ordering::Relaxed), 1);
+        let r4 = memo.get_or_fetch(key("bar"), fetch(&counter, "bar")).await;
+        assert!(r4.is_ok());
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn errors_not_memoized() {
+        let memo = PathMemo::default();
+        let r = memo
+            .get_or_fetch(key("foo"), async {
+                Err(DandiError::PathNotFound {
+                    path: "foo".parse().unwrap(),
+                })
+            })
+            .await;
+        assert!(r.is_err());
+        let counter = AtomicUsize::new(0);
+        let r = memo.get_or_fetch(key("foo"), fetch(&counter, "foo")).await;
+        assert!(r.is_ok());
+        assert_eq!(counter.load(Ordering::Relaxed), 1);
+    }
+}
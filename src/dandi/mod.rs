@@ -1,25 +1,57 @@
 //! The implementation of the data source for the `/dandisets/` hierarchy
+mod cache;
 mod dandiset_id;
+mod memo;
 mod streams;
+mod token;
 mod types;
 mod version_id;
+use self::cache::ApiCache;
+pub(crate) use self::cache::ApiCacheConfig;
 pub(crate) use self::dandiset_id::*;
-use self::streams::Paginate;
+pub(crate) use self::memo::PathMemo;
+use self::streams::{Page, Paginate};
+pub(crate) use self::token::*;
 pub(crate) use self::types::*;
 pub(crate) use self::version_id::*;
-use crate::consts::S3CLIENT_CACHE_SIZE;
-use crate::dav::ErrorClass;
-use crate::httputil::{BuildClientError, Client, HttpError, HttpUrl};
-use crate::paths::{ParsePureDirPathError, PureDirPath, PurePath};
+use crate::consts::{
+    ASSET_SIDECAR_SUFFIX, BIDS_DESCRIPTION_FILE, MISSING_PATH_CACHE_TTL, S3CLIENT_CACHE_SIZE,
+    S3CLIENT_MAX_CONSECUTIVE_FAILURES,
+};
+use crate::dav::{ErrorClass, Instance};
+use crate::httputil::{
+    Authorization, BuildClientError, Client, HttpConfig, HttpError, HttpUrl, RedirectError,
+    RetryPolicy,
+};
+use crate::paths::{ParsePureDirPathError, PureDirPath, PurePath, ZarrExtensions};
 use crate::s3::{
-    BucketSpec, GetBucketRegionError, PrefixedS3Client, S3Client, S3Error, S3Location,
+    BucketSpec, GetBucketRegionError, PrefixedS3Client, S3Client, S3Config, S3Entry, S3Error,
+    S3Location,
+};
+use axum::http::{
+    header::{HeaderMap, HeaderValue, RANGE},
+    StatusCode,
+};
+use bytes::{Bytes, BytesMut};
+use futures_util::{future::ready, stream, stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use moka::{
+    future::{Cache, CacheBuilder},
+    ops::compute::Op,
 };
-use futures_util::{Stream, TryStreamExt};
-use moka::future::{Cache, CacheBuilder};
 use serde::de::DeserializeOwned;
 use smartstring::alias::CompactString;
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use time::OffsetDateTime;
+
+/// A [`futures_util::Stream`] of the immediate child resources of a folder in
+/// a Dandiset version's file hierarchy, as returned by
+/// [`VersionEndpoint::get_root_children()`] and
+/// [`VersionEndpoint::get_resource_with_child_stream()`]
+pub(crate) type ChildStream = BoxStream<'static, Result<DandiResource, DandiError>>;
 
 /// A client for fetching data about Dandisets, their versions, and their
 /// assets from a DANDI Archive instance
@@ -28,9 +60,26 @@ pub(crate) struct DandiClient {
     /// The HTTP client used for making requests to the Archive instance's API
     inner: Client,
 
+    /// Which of the Archive instances served by `dandidav` this client is
+    /// for, determining where in the hierarchy the instance's Dandisets are
+    /// served
+    instance: Instance,
+
+    /// The name of the top-level collection under which the instance's
+    /// Dandisets are served, i.e., `instance.index_name()`
+    index_name: Arc<str>,
+
     /// The base API URL of the Archive instance
     api_url: HttpUrl,
 
+    /// The API token supplied by the client on whose behalf requests are
+    /// being made, if any.  If this is `None`, requests are authenticated
+    /// with the server-wide token (if any).
+    ///
+    /// Cached responses are keyed by this token so that responses fetched
+    /// using one user's credentials are never served to another user.
+    token: Option<ApiToken>,
+
     /// A cache of [`S3Client`] instances that are used for listing Zarr
     /// entries on the Archive's S3 bucket.
     ///
@@ -40,28 +89,306 @@ pub(crate) struct DandiClient {
     /// once they are needed.  Each bucket needs an `S3Client` to access it,
     /// and as construction of the inner `aws_sdk_s3::Client` is expensive, we
     /// cache them.
+    ///
+    /// If a cached client's requests start failing consistently (e.g., due to
+    /// the bucket moving regions), the client is evicted and rebuilt; see
+    /// [`DandiClient::get_s3client()`].
     s3clients: Cache<BucketSpec, Arc<S3Client>>,
+
+    /// Settings for the S3 clients in `s3clients`
+    s3config: S3Config,
+
+    /// Settings for locating Zarr assets within paths; see
+    /// [`VersionEndpoint::get_resource_with_s3()`]
+    zarr_lookup: ZarrLookup,
+
+    /// The maximum number of pages of results from paginated API endpoints to
+    /// fetch in the background ahead of the page currently being consumed;
+    /// see [`Paginate`]
+    page_prefetch: usize,
+
+    /// A cache of Dandiset information, keyed by client token & Dandiset ID
+    dandisets: ApiCache<(Option<ApiToken>, DandisetId), Dandiset>,
+
+    /// A cache of Dandiset version information, keyed by client token,
+    /// Dandiset ID, and version ID
+    versions: ApiCache<(Option<ApiToken>, DandisetId, VersionId), DandisetVersion>,
+
+    /// A cache of the results of looking up paths in Dandiset versions' file
+    /// hierarchies, keyed by client token, Dandiset ID, version ID, and path
+    paths: ApiCache<(Option<ApiToken>, DandisetId, VersionId, PurePath), AtAssetPath>,
+
+    /// A cache of paths recently found not to exist in Dandiset versions'
+    /// file hierarchies, keyed like `paths`.  Each value is the `modified`
+    /// timestamp of the version at the time of the lookup; an entry is only
+    /// used while the version's timestamp (as cached in `versions`) is
+    /// unchanged.  Entries expire after at most [`MISSING_PATH_CACHE_TTL`],
+    /// which bounds how long a newly-added path can be reported as missing
+    /// when `versions` still holds a stale timestamp.
+    missing_paths: ApiCache<(Option<ApiToken>, DandisetId, VersionId, PurePath), OffsetDateTime>,
+
+    /// A cache of the buckets & key prefixes on S3 at which Zarr assets'
+    /// entries are stored, keyed by client token & asset ID
+    zarr_locations: ApiCache<(Option<ApiToken>, String), (BucketSpec, PureDirPath)>,
+
+    /// A cache of the results of looking up entry paths within Zarr assets on
+    /// S3, keyed by client token, asset ID, and entry path.  `None` values
+    /// record that there is no entry at the path.
+    zarr_entries: ApiCache<(Option<ApiToken>, String, PurePath), Option<S3Entry>>,
+}
+
+/// The result of resolving a path in a Dandiset version's file hierarchy with
+/// [`VersionEndpoint::locate()`]
+#[derive(Clone, Debug)]
+enum Located {
+    /// The path points to the given asset or folder
+    AtPath(AtAssetPath),
+
+    /// The path points to `entry_path` within the Zarr asset `zarr`, which
+    /// is located at `zarr_path`
+    InZarr {
+        zarr: ZarrAsset,
+        zarr_path: PurePath,
+        entry_path: PurePath,
+    },
+}
+
+/// Settings for locating Zarr assets within paths in Dandiset versions; see
+/// [`VersionEndpoint::get_resource_with_s3()`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ZarrLookup {
+    /// The extensions that mark a path component as a potential Zarr
+    pub(crate) extensions: ZarrExtensions,
+
+    /// The maximum number of potential Zarr path components that will be
+    /// looked up when resolving a single path
+    pub(crate) max_candidates: usize,
+
+    /// Whether to look up every leading path component of a path that could
+    /// not otherwise be found, in order to find Zarrs whose paths lack any of
+    /// `extensions`
+    pub(crate) exhaustive: bool,
 }
 
 impl DandiClient {
     /// Construct a new `DandiClient` for the Archive instance with the given
-    /// base API URL
+    /// base API URL, caching API responses according to `cache_config`,
+    /// retrying failed requests according to `retry`, configuring S3 clients
+    /// with `s3config`, configuring HTTP connections & redirect handling with
+    /// `http_config`, locating Zarrs within paths according to `zarr_lookup`,
+    /// and prefetching up to `page_prefetch` pages of paginated results.  If
+    /// `token` is non-`None`, it is used to authenticate requests to the API
+    /// that are not made with a client-supplied token (see
+    /// [`DandiClient::with_token()`]).
+    ///
+    /// Repeated and trailing forward slashes in the path of `api_url` are
+    /// removed (see [`HttpUrl::normalize_path()`]) so that URLs for API
+    /// endpoints are formed correctly.
     ///
     /// # Errors
     ///
     /// Returns an error if construction of the inner `reqwest::Client` fails
-    pub(crate) fn new(api_url: HttpUrl) -> Result<Self, BuildClientError> {
-        let inner = Client::new()?;
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        mut api_url: HttpUrl,
+        cache_config: ApiCacheConfig,
+        retry: RetryPolicy,
+        s3config: S3Config,
+        http_config: HttpConfig,
+        zarr_lookup: ZarrLookup,
+        page_prefetch: usize,
+        token: Option<ApiToken>,
+    ) -> Result<Self, BuildClientError> {
+        api_url.normalize_path();
+        let auth = Authorization::new(&api_url, token.map(|t| t.header_value()));
+        let inner = Client::new(retry, http_config, Some(auth))?;
         let s3clients = CacheBuilder::new(S3CLIENT_CACHE_SIZE)
             .name("s3clients")
+            .eviction_listener(
+                |bucket_spec: Arc<BucketSpec>, client: Arc<S3Client>, cause| {
+                    tracing::debug!(
+                        cache_event = "evict",
+                        cache = "s3clients",
+                        bucket = %bucket_spec.bucket,
+                        region = bucket_spec.region,
+                        consecutive_failures = client.consecutive_failures(),
+                        ?cause,
+                        "S3 client evicted from cache",
+                    );
+                },
+            )
             .build();
         Ok(DandiClient {
             inner,
+            index_name: Instance::Primary.index_name().into(),
+            instance: Instance::Primary,
             api_url,
+            token: None,
             s3clients,
+            s3config,
+            zarr_lookup,
+            page_prefetch,
+            dandisets: ApiCache::new("dandi-dandisets", cache_config),
+            versions: ApiCache::new("dandi-versions", cache_config),
+            paths: ApiCache::new("dandi-paths", cache_config),
+            missing_paths: ApiCache::new(
+                "dandi-missing-paths",
+                ApiCacheConfig {
+                    ttl: cache_config.ttl.min(MISSING_PATH_CACHE_TTL),
+                    ..cache_config
+                },
+            ),
+            zarr_locations: ApiCache::new("dandi-zarr-locations", cache_config),
+            zarr_entries: ApiCache::new("dandi-zarr-entries", cache_config),
         })
     }
 
+    /// Spawn a task that logs the hit & miss counts of the Archive API
+    /// response caches every `period`
+    pub(crate) fn install_periodic_cache_stats(&self, period: Duration) {
+        let this = self.clone();
+        let mut schedule = tokio::time::interval(period);
+        schedule.reset(); // Don't tick immediately
+        schedule.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // Distinguish the statistics of different Archive instances' clients
+        let span = tracing::info_span!("api_cache_stats", api_url = %this.api_url);
+        tokio::spawn({
+            async move {
+                loop {
+                    schedule.tick().await;
+                    let _guard = span.enter();
+                    this.dandisets.log_stats();
+                    this.versions.log_stats();
+                    this.paths.log_stats();
+                    this.missing_paths.log_stats();
+                    this.zarr_locations.log_stats();
+                    this.zarr_entries.log_stats();
+                }
+            }
+        });
+    }
+
+    /// Return the URL to redirect a client to in order to download a resource
+    /// from `url`.
+    ///
+    /// If an API token was supplied and `url` is an Archive API URL (e.g., an
+    /// asset's `/download/` endpoint), the client will not have the
+    /// credentials needed to access it if the asset is embargoed, so an
+    /// authenticated request is made to `url`, and the (signed) URL that it
+    /// redirects to is returned instead.  Otherwise, `url` is returned
+    /// unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the authenticated request fails or does not
+    /// redirect anywhere.
+    pub(crate) async fn authorize_download(&self, url: &HttpUrl) -> Result<HttpUrl, DandiError> {
+        if self.inner.authenticates(url.as_url()) {
+            Ok(self.inner.get_redirect_location(url.clone()).await?)
+        } else {
+            Ok(url.clone())
+        }
+    }
+
+    /// Download the bytes of the resource at `url` (as returned by
+    /// [`DandiClient::authorize_download()`]) within the given range of
+    /// offsets.
+    ///
+    /// The range is requested with a `Range` header; if the server ignores it
+    /// and returns the entire resource, the requested portion is extracted
+    /// from the response as it is streamed, and the rest of the response is
+    /// not read.  The returned data may be shorter than requested if the
+    /// resource ends before `range.end`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the response has a 4xx or 5xx
+    /// status.
+    pub(crate) async fn download_range(
+        &self,
+        url: &HttpUrl,
+        range: Range<u64>,
+    ) -> Result<Bytes, DandiError> {
+        let Some(last) = range.end.checked_sub(1).filter(|&last| last >= range.start) else {
+            return Ok(Bytes::new());
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            RANGE,
+            HeaderValue::try_from(format!("bytes={}-{last}", range.start))
+                .expect("byte range should be a valid header value"),
+        );
+        let r = self.inner.get_with_headers(url.clone(), headers).await?;
+        // Offsets of the wanted bytes relative to the start of the response
+        // body
+        let wanted = if r.status == StatusCode::PARTIAL_CONTENT {
+            0..(range.end - range.start)
+        } else {
+            range
+        };
+        let mut body = r.body;
+        let mut buf = BytesMut::new();
+        let mut offset = 0;
+        while offset < wanted.end {
+            let Some(chunk) = body.try_next().await? else {
+                break;
+            };
+            let chunk_start = offset;
+            offset += u64::try_from(chunk.len()).unwrap_or(u64::MAX);
+            if offset > wanted.start {
+                let lo =
+                    usize::try_from(wanted.start.saturating_sub(chunk_start)).unwrap_or(usize::MAX);
+                let hi =
+                    usize::try_from(wanted.end.min(offset) - chunk_start).unwrap_or(usize::MAX);
+                buf.extend_from_slice(&chunk[lo..hi]);
+            }
+        }
+        Ok(buf.freeze())
+    }
+
+    /// Mark the client as being for the Archive instance `instance` rather
+    /// than for the primary instance
+    pub(crate) fn with_instance(mut self, instance: Instance) -> DandiClient {
+        self.index_name = instance.index_name().into();
+        self.instance = instance;
+        self
+    }
+
+    /// Return the Archive instance that the client is for
+    pub(crate) fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// Return the name of the top-level collection under which the client's
+    /// Archive instance's Dandisets are served
+    pub(crate) fn index_name(&self) -> &str {
+        &self.index_name
+    }
+
+    /// Return a copy of the client that authenticates its requests to the
+    /// Archive with the API token `token` supplied by a `dandidav` client
+    /// instead of with the server-wide token
+    pub(crate) fn with_token(&self, token: ApiToken) -> DandiClient {
+        DandiClient {
+            inner: self.inner.with_credentials(token.header_value()),
+            token: Some(token),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of the client that sends its HTTP requests via
+    /// `transport`
+    #[cfg(test)]
+    pub(crate) fn with_transport(
+        &self,
+        transport: Arc<dyn crate::httputil::HttpTransport>,
+    ) -> DandiClient {
+        DandiClient {
+            inner: self.inner.with_transport(transport),
+            ..self.clone()
+        }
+    }
+
     /// Return the URL formed by appending the given path segments and a
     /// trailing slash to the path of the API base URL
     fn get_url<I>(&self, segments: I) -> HttpUrl
@@ -74,6 +401,18 @@ impl DandiClient {
         url
     }
 
+    /// Fetch identifying information about the asset with the given ID,
+    /// regardless of which Dandiset it belongs to
+    pub(crate) async fn get_asset_info(&self, asset_id: &str) -> Result<AssetInfo, DandiError> {
+        self.get(self.get_url(["assets", asset_id, "info"])).await
+    }
+
+    /// Return the URL of the Archive endpoint that redirects to the content
+    /// of the asset with the given ID
+    pub(crate) fn asset_download_url(&self, asset_id: &str) -> HttpUrl {
+        self.get_url(["assets", asset_id, "download"])
+    }
+
     /// Perform a `GET` request to the given URL and return the deserialized
     /// JSON response body
     async fn get<T: DeserializeOwned>(&self, url: HttpUrl) -> Result<T, DandiError> {
@@ -83,7 +422,7 @@ impl DandiClient {
     /// Return a [`futures_util::Stream`] that makes paginated `GET` requests
     /// to the given URL and its subsequent pages and yields a `Result<T,
     /// DandiError>` value for each item deserialized from the responses
-    fn paginate<T: DeserializeOwned + 'static>(&self, url: HttpUrl) -> Paginate<T> {
+    fn paginate<T: DeserializeOwned + Send + 'static>(&self, url: HttpUrl) -> Paginate<T> {
         Paginate::new(self, url)
     }
 
@@ -98,39 +437,37 @@ impl DandiClient {
     /// slash appended to the key prefix if one isn't already present), with
     /// the assumption that the Zarr's entries are laid out under the given key
     /// prefix on the given bucket using the same names & directory structure
-    /// as the actual Zarr.
+    /// as the actual Zarr.  The bucket & key prefix for each asset are cached.
     async fn get_s3client_for_zarr(
         &self,
         zarr: &ZarrAsset,
     ) -> Result<PrefixedS3Client, DandiError> {
-        let Some(S3Location {
-            bucket_spec,
-            mut key,
-        }) = zarr.s3location()
-        else {
-            return Err(DandiError::ZarrToS3Error {
-                asset_id: zarr.asset_id.clone(),
-                source: ZarrToS3Error::NoS3Url,
-            });
+        let locate = async {
+            let Some(S3Location {
+                bucket_spec,
+                mut key,
+            }) = zarr.s3location()
+            else {
+                return Err(DandiError::ZarrToS3Error {
+                    asset_id: zarr.asset_id.clone(),
+                    source: ZarrToS3Error::NoS3Url,
+                });
+            };
+            if !key.ends_with('/') {
+                key.push('/');
+            }
+            let prefix =
+                PureDirPath::try_from(key).map_err(|source| DandiError::ZarrToS3Error {
+                    asset_id: zarr.asset_id.clone(),
+                    source: ZarrToS3Error::BadS3Key(source),
+                })?;
+            Ok((bucket_spec, prefix))
         };
-        if !key.ends_with('/') {
-            key.push('/');
-        }
-        let prefix = PureDirPath::try_from(key).map_err(|source| DandiError::ZarrToS3Error {
-            asset_id: zarr.asset_id.clone(),
-            source: ZarrToS3Error::BadS3Key(source),
-        })?;
-        match self
-            .s3clients
-            .try_get_with_by_ref(
-                &bucket_spec,
-                // Box the future passed to moka in order to minimize the size
-                // of the moka future (cf.
-                // <https://github.com/moka-rs/moka/issues/212>):
-                Box::pin(async { bucket_spec.clone().into_s3client().await.map(Arc::new) }),
-            )
-            .await
-        {
+        let (bucket_spec, prefix) = self
+            .zarr_locations
+            .get_or_fetch((self.token.clone(), zarr.asset_id.clone()), locate)
+            .await?;
+        match self.get_s3client(&bucket_spec).await {
             Ok(client) => Ok(client.with_prefix(prefix)),
             Err(source) => Err(DandiError::ZarrToS3Error {
                 asset_id: zarr.asset_id.clone(),
@@ -142,6 +479,164 @@ impl DandiClient {
         }
     }
 
+    /// Look up the entry at `entry_path` within the Zarr asset `zarr` using
+    /// `s3`, a client for the Zarr's key prefix, possibly from a cache.
+    /// Returns `None` if there is no such entry.
+    async fn get_zarr_entry(
+        &self,
+        zarr: &ZarrAsset,
+        s3: &PrefixedS3Client,
+        entry_path: &PurePath,
+    ) -> Result<Option<S3Entry>, S3Error> {
+        self.zarr_entries
+            .get_or_fetch(
+                (
+                    self.token.clone(),
+                    zarr.asset_id.clone(),
+                    entry_path.clone(),
+                ),
+                s3.get_path(entry_path),
+            )
+            .await
+    }
+
+    /// Return the cached [`S3Client`] for the given bucket, constructing one
+    /// if there is no cached client.
+    ///
+    /// If the cached client's last [`S3CLIENT_MAX_CONSECUTIVE_FAILURES`]
+    /// requests have all failed, it is replaced in the cache with a newly
+    /// constructed client (unless another task has already done so), thereby
+    /// picking up any changes in the bucket's region and the like.
+    async fn get_s3client(
+        &self,
+        bucket_spec: &BucketSpec,
+    ) -> Result<Arc<S3Client>, Arc<GetBucketRegionError>> {
+        let client = self
+            .s3clients
+            .try_get_with_by_ref(
+                bucket_spec,
+                // Box the future passed to moka in order to minimize the size
+                // of the moka future (cf.
+                // <https://github.com/moka-rs/moka/issues/212>):
+                Box::pin(async {
+                    bucket_spec
+                        .clone()
+                        .into_s3client(&self.inner, self.s3config)
+                        .await
+                        .map(Arc::new)
+                }),
+            )
+            .await?;
+        let failures = client.consecutive_failures();
+        if failures < S3CLIENT_MAX_CONSECUTIVE_FAILURES {
+            return Ok(client);
+        }
+        tracing::warn!(
+            bucket = %bucket_spec.bucket,
+            region = bucket_spec.region,
+            consecutive_failures = failures,
+            "Cached S3 client keeps failing; replacing it with a new client",
+        );
+        let result = self
+            .s3clients
+            .entry_by_ref(bucket_spec)
+            .and_try_compute_with(|entry| {
+                Box::pin(async move {
+                    match entry {
+                        Some(e) if !Arc::ptr_eq(e.value(), &client) => Ok(Op::Nop),
+                        _ => bucket_spec
+                            .clone()
+                            .into_s3client(&self.inner, self.s3config)
+                            .await
+                            .map(|c| Op::Put(Arc::new(c))),
+                    }
+                })
+            })
+            .await
+            .map_err(Arc::new)?;
+        Ok(result
+            .into_entry()
+            .expect("computing an S3 client should never leave the entry empty")
+            .into_value())
+    }
+
+    /// Return the base API URL of the Archive instance
+    pub(crate) fn api_url(&self) -> &HttpUrl {
+        &self.api_url
+    }
+
+    /// Check that the Archive instance is reachable by making a request to
+    /// its lightweight `/info/` endpoint
+    pub(crate) async fn check(&self) -> Result<(), HttpError> {
+        self.inner.get(self.get_url(["info"])).await.map(|_| ())
+    }
+
+    /// Retrieve information about the Archive instance itself, such as its
+    /// name & software version, from its `/info/` endpoint
+    pub(crate) async fn instance_info(&self) -> Result<InstanceInfo, DandiError> {
+        self.get(self.get_url(["info"])).await
+    }
+
+    /// Check that the configured API URL is that of a DANDI Archive API by
+    /// requesting the first page of its Dandisets listing.
+    ///
+    /// If the server's response shows that the URL is not an Archive API URL
+    /// (i.e., a 404, a non-JSON response, or a redirect to another origin),
+    /// an error is returned; if appending `/api` to the URL yields a working
+    /// API URL, the error suggests that instead.  All other failures (e.g.,
+    /// network errors, 5xx responses, or 4xx responses like 403 and 429) are
+    /// merely logged, as they may just mean that the Archive is temporarily
+    /// unavailable or is refusing requests for reasons of its own, and
+    /// `dandidav` should still start up in that case.
+    ///
+    /// # Errors
+    ///
+    /// See above.
+    pub(crate) async fn validate_api_url(&self) -> Result<(), ApiUrlError> {
+        let Err(e) = self.probe(&self.api_url).await else {
+            return Ok(());
+        };
+        let wrong_url = matches!(
+            e,
+            HttpError::NotFound { .. }
+                | HttpError::ParseJson { .. }
+                | HttpError::Redirect {
+                    source: RedirectError::CrossOrigin { .. },
+                    ..
+                }
+        );
+        if !wrong_url {
+            let e = anyhow::Error::from(e);
+            tracing::warn!(error = ?e, api_url = %self.api_url, "Could not verify Archive API URL; continuing anyway");
+            return Ok(());
+        }
+        let mut suggestion = None;
+        if !self.api_url.path_ends_with("api") {
+            let mut candidate = self.api_url.clone();
+            candidate.push("api");
+            if self.probe(&candidate).await.is_ok() {
+                suggestion = Some(candidate);
+            }
+        }
+        Err(ApiUrlError {
+            api_url: self.api_url.clone(),
+            suggestion,
+            source: e,
+        })
+    }
+
+    /// Request the first Dandiset from the API at `api_url`
+    async fn probe(&self, api_url: &HttpUrl) -> Result<(), HttpError> {
+        let mut url = api_url.clone();
+        url.push("dandisets")
+            .ensure_dirpath()
+            .append_query_param("page_size", "1");
+        self.inner
+            .get_json::<Page<serde::de::IgnoredAny>>(url)
+            .await
+            .map(|_| ())
+    }
+
     /// Return a [`futures_util::Stream`] that yields a `Dandiset` for each
     /// Dandiset on the Archive instance
     pub(crate) fn get_all_dandisets(
@@ -194,15 +689,21 @@ impl<'a> DandisetEndpoint<'a> {
         VersionEndpoint::new(self, version_id)
     }
 
-    /// Retrieve information about the Dandiset
+    /// Retrieve information about the Dandiset, possibly from a cache
     pub(crate) async fn get(&self) -> Result<Dandiset, DandiError> {
+        let fetch = async {
+            self.client
+                .get::<RawDandiset>(
+                    self.client
+                        .get_url(["dandisets", self.dandiset_id.as_ref()]),
+                )
+                .await
+                .map(|ds| ds.with_metadata_urls(self.client))
+        };
         self.client
-            .get::<RawDandiset>(
-                self.client
-                    .get_url(["dandisets", self.dandiset_id.as_ref()]),
-            )
+            .dandisets
+            .get_or_fetch((self.client.token.clone(), self.dandiset_id.clone()), fetch)
             .await
-            .map(|ds| ds.with_metadata_urls(self.client))
     }
 
     /// Return a [`futures_util::Stream`] that yields a `DandisetVersion` for
@@ -226,6 +727,12 @@ impl<'a> DandisetEndpoint<'a> {
 }
 
 /// An object for making requests relating to a specific version of a Dandiset
+///
+/// Paths in the version's file hierarchy are resolved using only the
+/// Archive's core `assets/?path=` and `assets/paths/` endpoints, which are
+/// available on all Archive deployments, rather than any WebDAV-specific
+/// endpoints (e.g., `webdav/assets/atpath/`) that older or third-party
+/// deployments may lack.
 #[derive(Clone, Debug)]
 pub(crate) struct VersionEndpoint<'a> {
     /// Client for the Archive instance
@@ -236,6 +743,14 @@ pub(crate) struct VersionEndpoint<'a> {
 
     /// The ID of the version this instance operates on
     version_id: VersionId,
+
+    /// Memo of path lookups, shared with the other `VersionEndpoint`s used
+    /// for the same request
+    memo: PathMemo,
+
+    /// Whether each asset in the version should be accompanied by a virtual
+    /// metadata sidecar file; see [`VersionEndpoint::with_sidecars()`]
+    sidecars: bool,
 }
 
 impl<'a> VersionEndpoint<'a> {
@@ -245,11 +760,46 @@ impl<'a> VersionEndpoint<'a> {
             client: upper.client,
             dandiset_id: upper.dandiset_id,
             version_id,
+            memo: PathMemo::default(),
+            sidecars: false,
         }
     }
 
-    /// Retrieve information about the version
+    /// Make the endpoint record its path lookups in (and reuse results from)
+    /// `memo`, which should be shared by all `VersionEndpoint`s used while
+    /// handling a single request
+    pub(crate) fn with_memo(self, memo: PathMemo) -> Self {
+        Self { memo, ..self }
+    }
+
+    /// Set whether each asset in the version should be accompanied by a
+    /// virtual metadata sidecar file at the asset's path plus
+    /// [`ASSET_SIDECAR_SUFFIX`], containing the asset's full metadata as
+    /// JSON.  When enabled, sidecars are listed after their assets among the
+    /// children of folders, and they can be retrieved by path.  If the
+    /// version contains a real asset or folder at a sidecar's path, the real
+    /// resource takes precedence when that path is requested.
+    pub(crate) fn with_sidecars(self, sidecars: bool) -> Self {
+        Self { sidecars, ..self }
+    }
+
+    /// Retrieve information about the version, possibly from a cache
     pub(crate) async fn get(&self) -> Result<DandisetVersion, DandiError> {
+        self.client
+            .versions
+            .get_or_fetch(
+                (
+                    self.client.token.clone(),
+                    self.dandiset_id.clone(),
+                    self.version_id.clone(),
+                ),
+                self.fetch(),
+            )
+            .await
+    }
+
+    /// Retrieve information about the version, bypassing the cache
+    pub(crate) async fn fetch(&self) -> Result<DandisetVersion, DandiError> {
         self.client
             .get::<RawDandisetVersion>(self.client.get_url([
                 "dandisets",
@@ -290,26 +840,39 @@ impl<'a> VersionEndpoint<'a> {
         &self,
         path: &PurePath,
     ) -> Result<DandiResourceWithChildren, DandiError> {
+        match self.get_resource_with_child_stream(path).await? {
+            DandiResourceWithChildStream::Folder { folder, children } => {
+                let children = children.try_collect::<Vec<_>>().await?;
+                Ok(DandiResourceWithChildren::Folder { folder, children })
+            }
+            DandiResourceWithChildStream::Other(r) => Ok(*r),
+        }
+    }
+
+    /// Get details on the resource at the given `path` in the version's file
+    /// hierarchy (treating Zarrs as directories of their entries) along with
+    /// its immediate child resources (if any).  If the resource is a folder,
+    /// its children are returned as a [`ChildStream`] that fetches them from
+    /// the Archive as it is polled, so that folders with very many children
+    /// can be processed incrementally.
+    ///
+    /// Although `path` is a `PurePath`, the resulting resource may be a
+    /// collection.
+    pub(crate) async fn get_resource_with_child_stream(
+        &self,
+        path: &PurePath,
+    ) -> Result<DandiResourceWithChildStream, DandiError> {
         match self.get_resource_with_s3(path).await? {
             DandiResourceWithS3::Folder(folder) => {
-                let mut children = Vec::new();
-                let mut stream = self.get_folder_entries(&folder);
-                while let Some(child) = stream.try_next().await? {
-                    let child = match child {
-                        FolderEntry::Folder(subf) => DandiResource::Folder(subf),
-                        FolderEntry::Asset { id, path } => match self.get_asset_by_id(&id).await {
-                            Ok(asset) => DandiResource::Asset(asset),
-                            Err(DandiError::Http(HttpError::NotFound { .. })) => {
-                                return Err(DandiError::DisappearingAsset { asset_id: id, path })
-                            }
-                            Err(e) => return Err(e),
-                        },
-                    };
-                    children.push(child);
-                }
-                Ok(DandiResourceWithChildren::Folder { folder, children })
+                let children = self.get_children(Some(&folder.path));
+                Ok(DandiResourceWithChildStream::Folder { folder, children })
+            }
+            DandiResourceWithS3::Asset(Asset::Blob(r)) => {
+                Ok(DandiResourceWithChildren::Blob(r).into())
+            }
+            DandiResourceWithS3::AssetSidecar(r) => {
+                Ok(DandiResourceWithChildren::AssetSidecar(r).into())
             }
-            DandiResourceWithS3::Asset(Asset::Blob(r)) => Ok(DandiResourceWithChildren::Blob(r)),
             DandiResourceWithS3::Asset(Asset::Zarr(zarr)) => {
                 let s3 = self.client.get_s3client_for_zarr(&zarr).await?;
                 let children = s3
@@ -317,7 +880,7 @@ impl<'a> VersionEndpoint<'a> {
                     .map_ok(|child| zarr.make_resource(child))
                     .try_collect::<Vec<_>>()
                     .await?;
-                Ok(DandiResourceWithChildren::Zarr { zarr, children })
+                Ok(DandiResourceWithChildren::Zarr { zarr, children }.into())
             }
             DandiResourceWithS3::ZarrFolder { folder, s3 } => {
                 let children = s3
@@ -325,30 +888,253 @@ impl<'a> VersionEndpoint<'a> {
                     .map_ok(|child| folder.make_resource(child))
                     .try_collect::<Vec<_>>()
                     .await?;
-                Ok(DandiResourceWithChildren::ZarrFolder { folder, children })
+                Ok(DandiResourceWithChildren::ZarrFolder { folder, children }.into())
             }
-            DandiResourceWithS3::ZarrEntry(r) => Ok(DandiResourceWithChildren::ZarrEntry(r)),
+            DandiResourceWithS3::ZarrEntry(r) => Ok(DandiResourceWithChildren::ZarrEntry(r).into()),
         }
     }
 
     /// Return a [`futures_util::Stream`] that yields the resources at the root
     /// of the version's file hierarchy
-    pub(crate) fn get_root_children(
+    pub(crate) fn get_root_children(&self) -> ChildStream {
+        self.get_children(None)
+    }
+
+    /// Fetch page number `page` (counting from 1) of the resources at the
+    /// root of the version's file hierarchy, with `per_page` resources per
+    /// page
+    pub(crate) async fn get_root_children_page(
         &self,
-    ) -> impl Stream<Item = Result<DandiResource, DandiError>> + '_ {
-        self.get_entries_under_path(None)
-            .and_then(move |entry| async move {
+        page: usize,
+        per_page: usize,
+    ) -> Result<ChildPage, DandiError> {
+        self.get_children_page(None, page, per_page).await
+    }
+
+    /// Fetch page number `page` (counting from 1) of the immediate child
+    /// resources of `folder`, with `per_page` resources per page
+    pub(crate) async fn get_folder_children_page(
+        &self,
+        folder: &AssetFolder,
+        page: usize,
+        per_page: usize,
+    ) -> Result<ChildPage, DandiError> {
+        self.get_children_page(Some(&folder.path), page, per_page)
+            .await
+    }
+
+    /// Return a [`futures_util::Stream`] that yields every asset in the
+    /// version in lexicographic order of their paths, fetched page by page
+    /// from the Archive as the stream is polled.
+    ///
+    /// The stream holds its own copy of the client rather than borrowing
+    /// `self` so that it can be used in the body of a streaming response.
+    pub(crate) fn get_all_assets(&self) -> BoxStream<'static, Result<Asset, DandiError>> {
+        let mut url = self.client.get_url([
+            "dandisets",
+            self.dandiset_id.as_ref(),
+            "versions",
+            self.version_id.as_ref(),
+            "assets",
+        ]);
+        url.append_query_param("metadata", "1");
+        url.append_query_param("order", "path");
+        let client = Arc::new(self.client.clone());
+        let dandiset_id = self.dandiset_id.clone();
+        let version_id = self.version_id.clone();
+        self.client
+            .paginate::<RawAsset>(url)
+            .and_then(move |asset| {
+                let endpoint = client
+                    .dandiset(dandiset_id.clone())
+                    .version(version_id.clone());
+                ready(asset.try_into_asset(&endpoint).map_err(DandiError::from))
+            })
+            .boxed()
+    }
+
+    /// Search the version for assets whose paths match `query`, returning at
+    /// most `limit` of them in lexicographic order of their paths.
+    ///
+    /// If `query` contains any of the glob metacharacters `*`, `?`, or `[`, it
+    /// is treated as a glob pattern that must match an asset's entire path;
+    /// otherwise, it matches any asset whose path contains it as a substring.
+    /// The matching itself is performed by the Archive's asset listing
+    /// endpoint.
+    pub(crate) async fn search_assets(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<DandiResource>, DandiError> {
+        let mut url = self.client.get_url([
+            "dandisets",
+            self.dandiset_id.as_ref(),
+            "versions",
+            self.version_id.as_ref(),
+            "assets",
+        ]);
+        if query.contains(['*', '?', '[']) {
+            url.append_query_param("glob", query);
+        } else {
+            url.append_query_param("glob", &format!("*{query}*"));
+        }
+        url.append_query_param("metadata", "1");
+        url.append_query_param("order", "path");
+        let mut stream = self.client.paginate::<RawAsset>(url);
+        let mut results = Vec::new();
+        while results.len() < limit {
+            let Some(asset) = stream.try_next().await? else {
+                break;
+            };
+            results.push(DandiResource::Asset(asset.try_into_asset(self)?));
+        }
+        Ok(results)
+    }
+
+    /// Detect the BIDS datasets in the version, i.e., the folders (including
+    /// the root of the file hierarchy) that directly contain a
+    /// `dataset_description.json` asset.
+    ///
+    /// The file hierarchy is examined breadth-first via its folder listings,
+    /// descending at most `max_depth` levels below the root and requesting at
+    /// most `max_listings` listings from the Archive.  As BIDS subject folders
+    /// never contain datasets of their own, folders whose names start with
+    /// `sub-` are not descended into.
+    pub(crate) async fn find_bids_datasets(
+        &self,
+        max_depth: usize,
+        max_listings: usize,
+    ) -> Result<BidsDatasets, DandiError> {
+        let mut datasets = BidsDatasets::default();
+        let mut queue = VecDeque::from([None::<AssetFolder>]);
+        let mut listings = 0;
+        while let Some(folder) = queue.pop_front() {
+            if listings >= max_listings {
+                tracing::warn!(
+                    dandiset = %self.dandiset_id,
+                    version = %self.version_id,
+                    max_listings,
+                    "Too many folders to examine while detecting BIDS datasets; results will be incomplete",
+                );
+                break;
+            }
+            listings += 1;
+            let path = folder.as_ref().map(|f| &f.path);
+            let depth = path.map_or(0, |p| p.component_strs().count());
+            let mut entries = self.get_entries_under_path(path);
+            let mut is_dataset = false;
+            while let Some(entry) = entries.try_next().await? {
                 match entry {
-                    FolderEntry::Folder(subf) => Ok(DandiResource::Folder(subf)),
-                    FolderEntry::Asset { id, path } => match self.get_asset_by_id(&id).await {
-                        Ok(asset) => Ok(DandiResource::Asset(asset)),
-                        Err(DandiError::Http(HttpError::NotFound { .. })) => {
-                            Err(DandiError::DisappearingAsset { asset_id: id, path })
+                    FolderEntry::Asset { path, .. } => {
+                        if path.name_str() == BIDS_DESCRIPTION_FILE {
+                            is_dataset = true;
+                        }
+                    }
+                    FolderEntry::Folder(sub) => {
+                        if depth < max_depth && !sub.path.name_str().starts_with("sub-") {
+                            queue.push_back(Some(sub));
                         }
-                        Err(e) => Err(e),
-                    },
+                    }
+                }
+            }
+            if is_dataset {
+                match folder {
+                    Some(f) => datasets.folders.push(f),
+                    None => datasets.root = true,
+                }
+            }
+        }
+        datasets
+            .folders
+            .sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Ok(datasets)
+    }
+
+    /// Return a [`ChildStream`] that yields the immediate child resources of
+    /// the folder at `path` in the version's file hierarchy, treating Zarrs as
+    /// non-collections.  If `path` is `None`, the resources at the root of the
+    /// file hierarchy are yielded.
+    ///
+    /// The stream holds its own copy of the client rather than borrowing
+    /// `self` so that it can be used in the body of a streaming response.
+    fn get_children(&self, path: Option<&PureDirPath>) -> ChildStream {
+        let client = Arc::new(self.client.clone());
+        let dandiset_id = self.dandiset_id.clone();
+        let version_id = self.version_id.clone();
+        let sidecars = self.sidecars;
+        self.get_entries_under_path(path)
+            .and_then(move |entry| {
+                let client = Arc::clone(&client);
+                let dandiset_id = dandiset_id.clone();
+                let version_id = version_id.clone();
+                async move {
+                    client
+                        .dandiset(dandiset_id)
+                        .version(version_id)
+                        .resolve_entry(entry)
+                        .await
                 }
             })
+            .map_ok(move |res| stream::iter(with_sidecar(res, sidecars).map(Ok)))
+            .try_flatten()
+            .boxed()
+    }
+
+    /// Fetch page number `page` (counting from 1) of the immediate child
+    /// resources of the folder at `path` in the version's file hierarchy,
+    /// treating Zarrs as non-collections, with `per_page` resources per page.
+    /// If `path` is `None`, the resources at the root of the file hierarchy
+    /// are paged through.
+    ///
+    /// Only the requested page is fetched from the Archive.  Pages past the
+    /// last page are returned as empty.
+    async fn get_children_page(
+        &self,
+        path: Option<&PureDirPath>,
+        page: usize,
+        per_page: usize,
+    ) -> Result<ChildPage, DandiError> {
+        let mut url = self.entries_url(path);
+        url.append_query_param("page", &page.to_string());
+        url.append_query_param("page_size", &per_page.to_string());
+        let entries = match self.client.get::<Page<FolderEntry>>(url).await {
+            Ok(entries) => entries,
+            // The Archive responds to requests for pages past the end with
+            // 404
+            Err(DandiError::Http(HttpError::NotFound { .. })) if page > 1 => {
+                return Ok(ChildPage {
+                    children: Vec::new(),
+                    has_next: false,
+                })
+            }
+            Err(e) => return Err(e),
+        };
+        let mut children = Vec::with_capacity(entries.results.len());
+        for entry in entries.results {
+            children.extend(with_sidecar(
+                self.resolve_entry(entry).await?,
+                self.sidecars,
+            ));
+        }
+        Ok(ChildPage {
+            children,
+            has_next: entries.next.is_some(),
+        })
+    }
+
+    /// Convert a [`FolderEntry`] into a [`DandiResource`], fetching the
+    /// details of the asset if it is one
+    async fn resolve_entry(&self, entry: FolderEntry) -> Result<DandiResource, DandiError> {
+        match entry {
+            FolderEntry::Folder(subf) => Ok(DandiResource::Folder(subf)),
+            FolderEntry::Asset { id, path } => match self.get_asset_by_id(&id).await {
+                Ok(asset) => Ok(DandiResource::Asset(asset)),
+                Err(DandiError::Http(HttpError::NotFound { .. })) => {
+                    Err(DandiError::DisappearingAsset { asset_id: id, path })
+                }
+                Err(e) => Err(e),
+            },
+        }
     }
 
     /// Get details on the resource at the given `path` in the version's file
@@ -360,19 +1146,130 @@ impl<'a> VersionEndpoint<'a> {
     /// <https://github.com/dandi/dandi-webdav/issues/5> and
     /// <https://github.com/dandi/dandidav/issues/10>).
     ///
-    /// - For each non-final component in `path` from left to right that has a
-    ///   `.zarr` or `.ngff` extension (case sensitive), query the asset path
-    ///   up through that component.  If 404, return 404.  If blob asset,
-    ///   return 404.  If folder, go to next candidate.  Otherwise, we have a
-    ///   Zarr asset, and the rest of the original path is the Zarr entry path.
+    /// - For each non-final component in `path` from left to right that has
+    ///   one of the configured Zarr extensions (by default, `.zarr` or
+    ///   `.ngff`, case insensitive), query the asset path up through that
+    ///   component.  If 404, return 404.  If blob asset, return 404.  If
+    ///   folder, go to next candidate.  Otherwise, we have a Zarr asset, and
+    ///   the rest of the original path is the Zarr entry path.
     ///
     /// - If all components are exhausted without erroring or finding a Zarr,
     ///   treat the entirety of `path` as an asset/folder path.
+    ///
+    /// - If exhaustive lookup is enabled and nothing exists at `path`, repeat
+    ///   the first step for all of the non-final components that were not
+    ///   already queried, in order to find Zarrs without a recognized
+    ///   extension.
+    ///
+    /// In order to keep crafted paths from causing an excessive number of
+    /// requests to the Archive, if more than `max_candidates` candidate
+    /// components would need to be queried in either pass, a "not found"
+    /// error is returned instead.
     async fn get_resource_with_s3(
         &self,
         path: &PurePath,
     ) -> Result<DandiResourceWithS3, DandiError> {
-        for (zarr_path, entry_path) in path.split_zarr_candidates() {
+        let located = match self.locate(path).await {
+            Err(DandiError::PathNotFound { .. }) if self.sidecars => {
+                return self
+                    .get_sidecar(path)
+                    .await
+                    .map(DandiResourceWithS3::AssetSidecar)
+            }
+            r => r?,
+        };
+        match located {
+            Located::AtPath(r) => Ok(r.into()),
+            Located::InZarr {
+                zarr,
+                zarr_path,
+                entry_path,
+            } => {
+                let s3 = self.client.get_s3client_for_zarr(&zarr).await?;
+                match self.client.get_zarr_entry(&zarr, &s3, &entry_path).await? {
+                    Some(entry) => Ok(zarr.make_resource(entry).with_s3(s3)),
+                    None => Err(DandiError::ZarrEntryNotFound {
+                        zarr_path,
+                        entry_path,
+                    }),
+                }
+            }
+        }
+    }
+
+    /// If `path` is the path to a Zarr asset in the version's file hierarchy
+    /// or to a resource within one, return the Zarr asset along with the path
+    /// within it (or `None` if `path` is the path to the Zarr itself).  If
+    /// `path` is the path to any other resource or to nothing, return `None`.
+    ///
+    /// Zarrs are located using the same algorithm as
+    /// [`VersionEndpoint::get_resource()`], but the Zarr's entries are not
+    /// looked up on S3.
+    pub(crate) async fn get_zarr_at_path(
+        &self,
+        path: &PurePath,
+    ) -> Result<Option<(ZarrAsset, Option<PurePath>)>, DandiError> {
+        match self.locate(path).await {
+            Ok(Located::InZarr {
+                zarr, entry_path, ..
+            }) => Ok(Some((zarr, Some(entry_path)))),
+            Ok(Located::AtPath(AtAssetPath::Asset(Asset::Zarr(zarr)))) => Ok(Some((zarr, None))),
+            Ok(Located::AtPath(_))
+            | Err(
+                DandiError::PathNotFound { .. }
+                | DandiError::PathUnderBlob { .. }
+                | DandiError::TooManyZarrCandidates { .. },
+            ) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Determine whether `path` points to a resource within a Zarr asset or
+    /// to an asset or folder, using the algorithm described for
+    /// [`VersionEndpoint::get_resource_with_s3()`]
+    async fn locate(&self, path: &PurePath) -> Result<Located, DandiError> {
+        let lookup = &self.client.zarr_lookup;
+        let candidates = path.split_zarr_candidates(&lookup.extensions);
+        if let Some(located) = self.find_zarr(path, candidates).await? {
+            return Ok(located);
+        }
+        match self.get_path(path).await {
+            Err(DandiError::PathNotFound { .. }) if lookup.exhaustive => {
+                let candidates = path
+                    .split_prefixes()
+                    .filter(|(zp, _)| !lookup.extensions.matches(zp.name_str()));
+                self.find_zarr(path, candidates)
+                    .await?
+                    .ok_or_else(|| DandiError::PathNotFound { path: path.clone() })
+            }
+            r => r.map(Located::AtPath),
+        }
+    }
+
+    /// Query each of the `(zarr_path, entry_path)` pairs in `candidates` in
+    /// turn until a Zarr asset is found.  Returns `None` if every candidate
+    /// is a folder.
+    async fn find_zarr<I>(
+        &self,
+        path: &PurePath,
+        candidates: I,
+    ) -> Result<Option<Located>, DandiError>
+    where
+        I: Iterator<Item = (PurePath, PurePath)>,
+    {
+        let max = self.client.zarr_lookup.max_candidates;
+        for (i, (zarr_path, entry_path)) in candidates.enumerate() {
+            if i >= max {
+                tracing::warn!(
+                    %path,
+                    max_zarr_candidates = max,
+                    "Path contains too many potential Zarr components; not resolving",
+                );
+                return Err(DandiError::TooManyZarrCandidates {
+                    path: path.clone(),
+                    max,
+                });
+            }
             match self.get_path(&zarr_path).await? {
                 AtAssetPath::Folder(_) => continue,
                 AtAssetPath::Asset(Asset::Blob(_)) => {
@@ -382,18 +1279,43 @@ impl<'a> VersionEndpoint<'a> {
                     })
                 }
                 AtAssetPath::Asset(Asset::Zarr(zarr)) => {
-                    let s3 = self.client.get_s3client_for_zarr(&zarr).await?;
-                    return match s3.get_path(&entry_path).await? {
-                        Some(entry) => Ok(zarr.make_resource(entry).with_s3(s3)),
-                        None => Err(DandiError::ZarrEntryNotFound {
-                            zarr_path,
-                            entry_path,
-                        }),
-                    };
+                    return Ok(Some(Located::InZarr {
+                        zarr,
+                        zarr_path,
+                        entry_path,
+                    }))
                 }
             }
         }
-        self.get_path(path).await.map(Into::into)
+        Ok(None)
+    }
+
+    /// If `path` is the path to the virtual metadata sidecar file of an asset
+    /// in the version, return the sidecar with its content fetched from the
+    /// Archive.  Otherwise, fail with [`DandiError::PathNotFound`].
+    async fn get_sidecar(&self, path: &PurePath) -> Result<AssetSidecar, DandiError> {
+        let not_found = || DandiError::PathNotFound { path: path.clone() };
+        let asset_path = path
+            .as_ref()
+            .strip_suffix(ASSET_SIDECAR_SUFFIX)
+            .and_then(|p| p.parse::<PurePath>().ok())
+            .ok_or_else(not_found)?;
+        match self.get_path(&asset_path).await {
+            Ok(AtAssetPath::Asset(asset)) => {
+                let mut sidecar = asset.sidecar();
+                let metadata = self
+                    .client
+                    .get::<serde_json::Value>(sidecar.metadata_url.clone())
+                    .await?;
+                sidecar.content = Some(
+                    serde_json::to_vec_pretty(&metadata)
+                        .expect("serializing JSON metadata should not fail"),
+                );
+                Ok(sidecar)
+            }
+            Ok(AtAssetPath::Folder(_)) | Err(DandiError::PathNotFound { .. }) => Err(not_found()),
+            Err(e) => Err(e),
+        }
     }
 
     /// Return the URL for the version's metadata
@@ -435,12 +1357,75 @@ impl<'a> VersionEndpoint<'a> {
 
     /// Get details on the resource (an asset or folder) at the given `path` in
     /// the version's file hierarchy, treating Zarrs as non-collections.
+    /// Results are memoized for the duration of the request and also cached
+    /// across requests; see [`VersionEndpoint::fetch_path()`] for how they are
+    /// computed.
+    async fn get_path(&self, path: &PurePath) -> Result<AtAssetPath, DandiError> {
+        // Box the cache lookup in order to keep the size of the future
+        // returned by this method down:
+        let cached = Box::pin(self.get_cached_path(path));
+        self.memo
+            .get_or_fetch(
+                (
+                    self.dandiset_id.clone(),
+                    self.version_id.clone(),
+                    path.clone(),
+                ),
+                cached,
+            )
+            .await
+    }
+
+    /// Get details on the resource (an asset or folder) at the given `path` in
+    /// the version's file hierarchy, treating Zarrs as non-collections, from
+    /// the cross-request caches if possible.
+    ///
+    /// Paths found not to exist are recorded in a short-lived negative cache
+    /// so that repeated probes for nonexistent paths (e.g., `index.html` or
+    /// dotfiles requested by WebDAV clients) do not each require a round
+    /// trip to the Archive.  Negative entries are disregarded once the
+    /// version's `modified` timestamp changes; as the timestamp is itself
+    /// read from the `versions` cache, a change may go unnoticed until that
+    /// cache expires, so negative entries also expire after at most
+    /// [`MISSING_PATH_CACHE_TTL`].
+    async fn get_cached_path(&self, path: &PurePath) -> Result<AtAssetPath, DandiError> {
+        let key = (
+            self.client.token.clone(),
+            self.dandiset_id.clone(),
+            self.version_id.clone(),
+            path.clone(),
+        );
+        if let Some(modified) = self.client.missing_paths.get(&key).await {
+            if self.get().await.is_ok_and(|v| v.modified == modified) {
+                return Err(DandiError::PathNotFound { path: path.clone() });
+            }
+            self.client.missing_paths.invalidate(&key).await;
+        }
+        match self
+            .client
+            .paths
+            .get_or_fetch(key.clone(), self.fetch_path(path))
+            .await
+        {
+            Err(DandiError::PathNotFound { path }) => {
+                if let Ok(v) = self.get().await {
+                    self.client.missing_paths.insert(key, v.modified).await;
+                }
+                Err(DandiError::PathNotFound { path })
+            }
+            r => r,
+        }
+    }
+
+    /// Get details on the resource (an asset or folder) at the given `path` in
+    /// the version's file hierarchy, treating Zarrs as non-collections,
+    /// bypassing the cache.
     ///
     /// This method paginates over all assets in the version whose paths start
     /// with `path`, sorted by asset paths in lexicographic order.  If an exact
     /// match is found, that asset is returned.  If an asset is found whose
     /// path is a descendant of `path`, then `path` is a folder.
-    async fn get_path(&self, path: &PurePath) -> Result<AtAssetPath, DandiError> {
+    async fn fetch_path(&self, path: &PurePath) -> Result<AtAssetPath, DandiError> {
         let mut url = self.client.get_url([
             "dandisets",
             self.dandiset_id.as_ref(),
@@ -457,7 +1442,11 @@ impl<'a> VersionEndpoint<'a> {
             if &asset.path == path {
                 return Ok(AtAssetPath::Asset(asset.try_into_asset(self)?));
             } else if asset.path.is_strictly_under(&dirpath) {
-                return Ok(AtAssetPath::Folder(AssetFolder { path: dirpath }));
+                return Ok(AtAssetPath::Folder(AssetFolder {
+                    path: dirpath,
+                    total_size: None,
+                    total_assets: None,
+                }));
             } else if asset.path.as_ref() > dirpath.as_ref() {
                 break;
             }
@@ -471,6 +1460,13 @@ impl<'a> VersionEndpoint<'a> {
     /// non-collections.  If `path` is `None`, the resources at the root of the
     /// file hierarchy are yielded.
     fn get_entries_under_path(&self, path: Option<&PureDirPath>) -> Paginate<FolderEntry> {
+        self.client.paginate(self.entries_url(path))
+    }
+
+    /// Return the URL for listing the immediate child resources of the folder
+    /// at `path` in the version's file hierarchy (or of the root of the
+    /// hierarchy if `path` is `None`)
+    fn entries_url(&self, path: Option<&PureDirPath>) -> HttpUrl {
         let mut url = self.client.get_url([
             "dandisets",
             self.dandiset_id.as_ref(),
@@ -482,18 +1478,70 @@ impl<'a> VersionEndpoint<'a> {
         if let Some(path) = path {
             url.append_query_param("path_prefix", path.as_ref());
         }
-        self.client.paginate(url)
+        url
     }
+}
 
-    /// Return a [`futures_util::Stream`] that yields a [`FolderEntry`] object
-    /// for each immediate child resource (both assets and folders) of the
-    /// folder at `path` in the version's file hierarchy, treating Zarrs as
-    /// non-collections.
-    fn get_folder_entries(&self, path: &AssetFolder) -> Paginate<FolderEntry> {
-        self.get_entries_under_path(Some(&path.path))
+/// Return `res` followed by its virtual metadata sidecar file if `sidecars`
+/// is `true` and `res` is an asset; otherwise, return just `res`
+fn with_sidecar(res: DandiResource, sidecars: bool) -> impl Iterator<Item = DandiResource> {
+    let sidecar = match res {
+        DandiResource::Asset(ref asset) if sidecars => {
+            Some(DandiResource::AssetSidecar(asset.sidecar()))
+        }
+        _ => None,
+    };
+    std::iter::once(res).chain(sidecar)
+}
+
+/// A page of the immediate child resources of a folder in a Dandiset version's
+/// file hierarchy, as returned by [`VersionEndpoint::get_root_children_page()`]
+/// and [`VersionEndpoint::get_folder_children_page()`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ChildPage {
+    /// The resources on the page
+    pub(crate) children: Vec<DandiResource>,
+
+    /// Whether there are further pages
+    pub(crate) has_next: bool,
+}
+
+/// The result of [`VersionEndpoint::get_resource_with_child_stream()`]
+pub(crate) enum DandiResourceWithChildStream {
+    /// A folder, with a stream of its immediate child resources
+    Folder {
+        folder: AssetFolder,
+        children: ChildStream,
+    },
+
+    /// Any other resource, with its child resources (if any) already fetched
+    Other(Box<DandiResourceWithChildren>),
+}
+
+impl From<DandiResourceWithChildren> for DandiResourceWithChildStream {
+    fn from(value: DandiResourceWithChildren) -> DandiResourceWithChildStream {
+        DandiResourceWithChildStream::Other(Box::new(value))
     }
 }
 
+/// Error returned by [`DandiClient::validate_api_url()`] when the configured
+/// API URL is not that of a DANDI Archive API
+#[derive(Debug, Error)]
+#[error(
+    "{api_url} does not appear to be a DANDI Archive API URL{}; check the --api-url option",
+    .suggestion.as_ref().map(|u| format!(" (did you mean {u}?)")).unwrap_or_default()
+)]
+pub(crate) struct ApiUrlError {
+    /// The configured API URL
+    api_url: HttpUrl,
+
+    /// A similar URL that does point to an Archive API, if one was found
+    suggestion: Option<HttpUrl>,
+
+    /// The error that occurred when probing the API URL
+    source: HttpError,
+}
+
 #[derive(Debug, Error)]
 pub(crate) enum DandiError {
     #[error(transparent)]
@@ -502,6 +1550,8 @@ pub(crate) enum DandiError {
     PathNotFound { path: PurePath },
     #[error("path {path:?} points nowhere as leading portion {blob_path:?} points to a blob")]
     PathUnderBlob { path: PurePath, blob_path: PurePath },
+    #[error("path {path:?} contains more than {max} components that could be Zarrs")]
+    TooManyZarrCandidates { path: PurePath, max: usize },
     #[error("entry {entry_path:?} in Zarr {zarr_path:?} not found")]
     ZarrEntryNotFound {
         zarr_path: PurePath,
@@ -527,10 +1577,12 @@ impl DandiError {
             DandiError::Http(source) => source.class(),
             DandiError::PathNotFound { .. }
             | DandiError::PathUnderBlob { .. }
+            | DandiError::TooManyZarrCandidates { .. }
             | DandiError::ZarrEntryNotFound { .. } => ErrorClass::NotFound,
-            DandiError::DisappearingAsset { .. } => ErrorClass::BadGateway,
+            DandiError::DisappearingAsset { .. } | DandiError::AssetType(_) => {
+                ErrorClass::BadGateway
+            }
             DandiError::ZarrToS3Error { source, .. } => source.class(),
-            DandiError::AssetType(_) => ErrorClass::BadGateway,
             DandiError::S3(source) => source.class(),
         }
     }
@@ -553,8 +1605,7 @@ impl ZarrToS3Error {
     /// Classify the general type of error
     pub(crate) fn class(&self) -> ErrorClass {
         match self {
-            ZarrToS3Error::NoS3Url => ErrorClass::BadGateway,
-            ZarrToS3Error::BadS3Key(_) => ErrorClass::BadGateway,
+            ZarrToS3Error::NoS3Url | ZarrToS3Error::BadS3Key(_) => ErrorClass::BadGateway,
             ZarrToS3Error::LocateBucket { source, .. } => {
                 let class = source.class();
                 if class == ErrorClass::NotFound {
@@ -582,10 +1633,313 @@ fn dump_json_as_yaml(data: serde_json::Value) -> String {
 
 #[cfg(test)]
 mod tests {
+    use super::cache::CacheStats;
     use super::*;
+    use crate::httputil::FakeTransport;
+    use crate::s3::{S3Config, TryFromCommonPrefixError};
+    use assert_matches::assert_matches;
     use indoc::indoc;
     use serde_json::json;
 
+    /// Construct a client for the Archive API at `api_url` with the given
+    /// cache & Zarr lookup configurations
+    fn client_with(
+        api_url: &str,
+        cache_config: ApiCacheConfig,
+        zarr_lookup: ZarrLookup,
+    ) -> DandiClient {
+        DandiClient::new(
+            api_url.parse().unwrap(),
+            cache_config,
+            RetryPolicy::default(),
+            S3Config {
+                connect_timeout: Duration::from_secs(1),
+                read_timeout: Duration::from_secs(1),
+                max_attempts: 1,
+                list_concurrency: 1,
+            },
+            HttpConfig::default(),
+            zarr_lookup,
+            0,
+            None,
+        )
+        .unwrap()
+    }
+
+    /// Cache configuration that disables caching
+    const NO_CACHE: ApiCacheConfig = ApiCacheConfig {
+        max_entries: 0,
+        ttl: Duration::from_secs(1),
+    };
+
+    /// The Zarr lookup configuration used by test clients unless a test
+    /// needs otherwise
+    fn default_zarr_lookup() -> ZarrLookup {
+        ZarrLookup {
+            extensions: ZarrExtensions::default(),
+            max_candidates: 1,
+            exhaustive: false,
+        }
+    }
+
+    /// Construct a client for the Archive API at `api_url` with caching
+    /// disabled
+    fn client(api_url: &str) -> DandiClient {
+        client_with(api_url, NO_CACHE, default_zarr_lookup())
+    }
+
+    /// Construct a client for a fake Archive API with caching enabled
+    fn caching_client() -> DandiClient {
+        client_with(
+            FAKE_API_URL,
+            ApiCacheConfig {
+                max_entries: 10,
+                ttl: Duration::from_secs(60),
+            },
+            default_zarr_lookup(),
+        )
+    }
+
+    /// The base URL of the fake Archive API used by `FakeTransport`-backed
+    /// tests
+    const FAKE_API_URL: &str = "https://api.example.com/api";
+
+    /// The URL of the draft version of Dandiset 000001 on the fake Archive
+    const VERSION_URL: &str = "https://api.example.com/api/dandisets/000001/versions/draft";
+
+    /// The metadata of the draft version of Dandiset 000001 on the fake
+    /// Archive, last modified at `modified`
+    fn version(modified: &str) -> serde_json::Value {
+        json!({
+            "version": "draft",
+            "name": "Test Dandiset",
+            "asset_count": 1,
+            "size": 42,
+            "status": "Valid",
+            "created": "2024-01-01T00:00:00Z",
+            "modified": modified,
+        })
+    }
+
+    /// The metadata of an asset at `path`, either a Zarr or a blob, as
+    /// returned by the Archive
+    fn asset(path: &str, zarr: bool) -> serde_json::Value {
+        let (blob, zarr_id, encoding, digest) = if zarr {
+            (
+                None,
+                Some("89ab-cdef"),
+                "application/x-zarr",
+                json!({"dandi:dandi-zarr-checksum": "abc-1--42"}),
+            )
+        } else {
+            (
+                Some("89ab-cdef"),
+                None,
+                "application/x-nwb",
+                json!({"dandi:dandi-etag": "abc-1"}),
+            )
+        };
+        json!({
+            "asset_id": "0123-4567",
+            "blob": blob,
+            "zarr": zarr_id,
+            "path": path,
+            "size": 42,
+            "created": "2024-01-01T00:00:00Z",
+            "modified": "2024-01-02T00:00:00Z",
+            "metadata": {
+                "encodingFormat": encoding,
+                "contentUrl": [],
+                "digest": digest,
+            },
+        })
+    }
+
+    /// Add to `transport` a response to a lookup of the assets at & under
+    /// `path` in the draft version of Dandiset 000001
+    fn lookup(
+        transport: FakeTransport,
+        path: &str,
+        results: &[serde_json::Value],
+    ) -> FakeTransport {
+        transport.json(
+            &format!(
+                "{VERSION_URL}/assets/?path={}&metadata=1&order=path",
+                path.replace('/', "%2F")
+            ),
+            &json!({"next": null, "results": results}),
+        )
+    }
+
+    /// A Zarr asset stored under `zarr/abc/` in the `dandiarchive` bucket
+    fn zarr_asset() -> ZarrAsset {
+        ZarrAsset {
+            asset_id: "0123".into(),
+            zarr_id: "abc".into(),
+            path: "foo.zarr".parse().unwrap(),
+            size: 42,
+            created: time::macros::datetime!(2024-01-01 00:00:00 UTC),
+            modified: time::macros::datetime!(2024-01-01 00:00:00 UTC),
+            metadata: serde_json::from_value(json!({
+                "encodingFormat": "application/x-zarr",
+                "contentUrl": [
+                    "https://api.example.com/api/assets/0123/download/",
+                    "https://dandiarchive.s3.us-east-2.amazonaws.com/zarr/abc",
+                ],
+                "digest": {},
+            }))
+            .unwrap(),
+            metadata_url:
+                "https://api.example.com/api/dandisets/000001/versions/draft/assets/0123/"
+                    .parse()
+                    .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn zarr_location_cached() {
+        let client = caching_client();
+        let zarr = zarr_asset();
+        for _ in 0..2 {
+            client.get_s3client_for_zarr(&zarr).await.unwrap();
+        }
+        assert_eq!(
+            client.zarr_locations.stats(),
+            CacheStats { hits: 1, misses: 1 }
+        );
+        let (bucket_spec, prefix) = client
+            .zarr_locations
+            .get_or_fetch((None, "0123".into()), async { Err::<_, ()>(()) })
+            .await
+            .unwrap();
+        assert_eq!(bucket_spec.bucket, "dandiarchive");
+        assert_eq!(bucket_spec.region.as_deref(), Some("us-east-2"));
+        assert_eq!(prefix, "zarr/abc/");
+    }
+
+    #[tokio::test]
+    async fn zarr_location_cached_per_token() {
+        let client = caching_client();
+        let zarr = zarr_asset();
+        let alice = client.with_token("alice-token".parse().unwrap());
+        let bob = client.with_token("bob-token".parse().unwrap());
+        alice.get_s3client_for_zarr(&zarr).await.unwrap();
+        bob.get_s3client_for_zarr(&zarr).await.unwrap();
+        client.get_s3client_for_zarr(&zarr).await.unwrap();
+        assert_eq!(
+            client.zarr_locations.stats(),
+            CacheStats { hits: 0, misses: 3 }
+        );
+        alice.get_s3client_for_zarr(&zarr).await.unwrap();
+        assert_eq!(
+            client.zarr_locations.stats(),
+            CacheStats { hits: 1, misses: 3 }
+        );
+    }
+
+    #[test]
+    fn test_dandi_error_class() {
+        let url = "https://api.dandiarchive.org/api/dandisets/000027/"
+            .parse::<HttpUrl>()
+            .unwrap();
+        let cases = [
+            (
+                DandiError::Http(HttpError::NotFound { url }),
+                ErrorClass::NotFound,
+            ),
+            (
+                DandiError::PathNotFound {
+                    path: "foo/bar.nwb".parse().unwrap(),
+                },
+                ErrorClass::NotFound,
+            ),
+            (
+                DandiError::PathUnderBlob {
+                    path: "foo/bar.nwb/baz".parse().unwrap(),
+                    blob_path: "foo/bar.nwb".parse().unwrap(),
+                },
+                ErrorClass::NotFound,
+            ),
+            (
+                DandiError::TooManyZarrCandidates {
+                    path: "a.zarr/b.zarr/c.zarr/d".parse().unwrap(),
+                    max: 2,
+                },
+                ErrorClass::NotFound,
+            ),
+            (
+                DandiError::ZarrEntryNotFound {
+                    zarr_path: "foo.zarr".parse().unwrap(),
+                    entry_path: "0/0/0".parse().unwrap(),
+                },
+                ErrorClass::NotFound,
+            ),
+            (
+                DandiError::DisappearingAsset {
+                    asset_id: "0123".into(),
+                    path: "foo/bar.nwb".parse().unwrap(),
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                DandiError::ZarrToS3Error {
+                    asset_id: "0123".into(),
+                    source: ZarrToS3Error::NoS3Url,
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                DandiError::AssetType(AssetTypeError::Neither {
+                    asset_id: "0123".into(),
+                }),
+                ErrorClass::BadGateway,
+            ),
+            (
+                DandiError::S3(S3Error::BadPrefix {
+                    bucket: "dandiarchive".into(),
+                    prefix: "zarr/".into(),
+                    source: TryFromCommonPrefixError::NoPrefix,
+                }),
+                ErrorClass::BadGateway,
+            ),
+        ];
+        for (e, class) in cases {
+            assert_eq!(e.class(), class, "wrong class for {e:?}");
+        }
+    }
+
+    #[test]
+    fn test_zarr_to_s3_error_class() {
+        let url = "https://nonexistent.s3.amazonaws.com"
+            .parse::<HttpUrl>()
+            .unwrap();
+        let cases = [
+            (ZarrToS3Error::NoS3Url, ErrorClass::BadGateway),
+            (
+                ZarrToS3Error::BadS3Key(PureDirPath::try_from(String::from("/zarr/")).unwrap_err()),
+                ErrorClass::BadGateway,
+            ),
+            // A nonexistent bucket is the Archive's fault, not the client's
+            (
+                ZarrToS3Error::LocateBucket {
+                    bucket: "nonexistent".into(),
+                    source: Arc::new(GetBucketRegionError::Http(HttpError::NotFound { url })),
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                ZarrToS3Error::LocateBucket {
+                    bucket: "dandiarchive".into(),
+                    source: Arc::new(GetBucketRegionError::NoHeader),
+                },
+                ErrorClass::BadGateway,
+            ),
+        ];
+        for (e, class) in cases {
+            assert_eq!(e.class(), class, "wrong class for {e:?}");
+        }
+    }
+
     #[test]
     fn test_dump_json_as_yaml() {
         let data = json! ({
@@ -619,4 +1973,554 @@ mod tests {
         "}
         );
     }
+
+    mod validate_api_url {
+        use super::*;
+        use axum::{routing::get, Router};
+        use reqwest::StatusCode;
+        use rstest::rstest;
+
+        /// Serve a fake Archive whose API is at `/api` (and that serves HTML
+        /// at `/`) on a random local port and return the base URL of the
+        /// server
+        async fn serve() -> String {
+            let app = Router::new()
+                .route(
+                    "/api/dandisets/",
+                    get(|| async { axum::Json(json!({"next": null, "results": [{}]})) }),
+                )
+                .route("/dandisets/", get(|| async { "<html></html>" }));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            format!("http://{addr}")
+        }
+
+        #[tokio::test]
+        async fn valid() {
+            let base = serve().await;
+            for api_url in [
+                format!("{base}/api"),
+                format!("{base}/api/"),
+                format!("{base}/api//"),
+            ] {
+                let client = client(&api_url);
+                assert_eq!(client.api_url.as_str(), format!("{base}/api"));
+                client.validate_api_url().await.unwrap();
+            }
+        }
+
+        #[tokio::test]
+        async fn missing_api() {
+            let base = serve().await;
+            let e = client(&base).validate_api_url().await.unwrap_err();
+            assert_matches!(e.source, HttpError::ParseJson { .. });
+            assert_eq!(
+                e.suggestion.as_ref().map(HttpUrl::as_str),
+                Some(format!("{base}/api").as_str())
+            );
+            assert_eq!(
+                e.to_string(),
+                format!("{base}/ does not appear to be a DANDI Archive API URL (did you mean {base}/api?); check the --api-url option")
+            );
+        }
+
+        #[tokio::test]
+        async fn wrong_path() {
+            let base = serve().await;
+            let e = client(&format!("{base}/nowhere"))
+                .validate_api_url()
+                .await
+                .unwrap_err();
+            assert_matches!(e.source, HttpError::NotFound { .. });
+            assert!(e.suggestion.is_none());
+        }
+
+        #[tokio::test]
+        async fn unreachable() {
+            // Bind & immediately release a port so that nothing is listening
+            // on it
+            let addr = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap();
+            let client = client(&format!("http://{addr}/api"));
+            client.validate_api_url().await.unwrap();
+        }
+
+        #[rstest]
+        #[case(StatusCode::NOT_FOUND, false)]
+        #[case(StatusCode::FORBIDDEN, true)]
+        #[case(StatusCode::TOO_MANY_REQUESTS, true)]
+        #[case(StatusCode::INTERNAL_SERVER_ERROR, true)]
+        #[case(StatusCode::SERVICE_UNAVAILABLE, true)]
+        #[tokio::test]
+        async fn error_status(#[case] status: StatusCode, #[case] ok: bool) {
+            let transport = FakeTransport::new().respond(
+                "https://api.example.com/api/dandisets/?page_size=1",
+                status,
+                &[],
+                "",
+            );
+            let client = client("https://api.example.com/api").with_transport(Arc::new(transport));
+            assert_eq!(client.validate_api_url().await.is_ok(), ok);
+        }
+    }
+
+    #[tokio::test]
+    async fn instance_info() {
+        let transport = Arc::new(FakeTransport::new().json(
+            "https://api.example.com/api/info/",
+            &json!({
+                "version": "0.11.0",
+                "instance_config": {
+                    "instance_name": "DANDI-EXAMPLE",
+                    "instance_url": "https://example.com",
+                },
+            }),
+        ));
+        let client = client("https://api.example.com/api").with_transport(transport.clone());
+        let info = client.instance_info().await.unwrap();
+        assert_eq!(info.version, "0.11.0");
+        assert_eq!(info.name(), Some("DANDI-EXAMPLE"));
+        assert_eq!(
+            transport.requests(),
+            [(
+                reqwest::Method::GET,
+                "https://api.example.com/api/info/".to_owned()
+            )]
+        );
+    }
+
+    mod sidecars {
+        use super::*;
+        use rstest::rstest;
+
+        fn fake_client(transport: FakeTransport) -> (DandiClient, Arc<FakeTransport>) {
+            let transport = Arc::new(transport);
+            let client = client(FAKE_API_URL).with_transport(transport.clone());
+            (client, transport)
+        }
+
+        #[tokio::test]
+        async fn get_sidecar() {
+            let transport = lookup(FakeTransport::new(), "foo.nwb.dandi.json", &[]);
+            let transport = lookup(transport, "foo.nwb", &[asset("foo.nwb", false)]).json(
+                &format!("{VERSION_URL}/assets/0123-4567/"),
+                &json!({"id": "dandiasset:0123-4567", "path": "foo.nwb"}),
+            );
+            let (client, _) = fake_client(transport);
+            let res = client
+                .dandiset("000001".parse().unwrap())
+                .version(VersionId::Draft)
+                .with_sidecars(true)
+                .get_resource(&"foo.nwb.dandi.json".parse().unwrap())
+                .await
+                .unwrap();
+            let DandiResource::AssetSidecar(sidecar) = res else {
+                panic!("expected a sidecar, got {res:?}");
+            };
+            assert_eq!(sidecar.path, "foo.nwb.dandi.json");
+            assert_eq!(
+                sidecar.metadata_url.as_str(),
+                format!("{VERSION_URL}/assets/0123-4567/")
+            );
+            let content =
+                serde_json::from_slice::<serde_json::Value>(&sidecar.content.unwrap()).unwrap();
+            assert_eq!(
+                content,
+                json!({"id": "dandiasset:0123-4567", "path": "foo.nwb"})
+            );
+        }
+
+        #[tokio::test]
+        async fn real_path_wins() {
+            let transport = lookup(
+                FakeTransport::new(),
+                "foo.nwb.dandi.json",
+                &[asset("foo.nwb.dandi.json", false)],
+            );
+            let (client, transport) = fake_client(transport);
+            let res = client
+                .dandiset("000001".parse().unwrap())
+                .version(VersionId::Draft)
+                .with_sidecars(true)
+                .get_resource(&"foo.nwb.dandi.json".parse().unwrap())
+                .await
+                .unwrap();
+            assert_matches!(res, DandiResource::Asset(Asset::Blob(_)));
+            assert_eq!(transport.requests().len(), 1);
+        }
+
+        #[rstest]
+        #[case(false, "foo.nwb.dandi.json")]
+        #[case(true, "qux.nwb.dandi.json")]
+        #[case(true, "bar.dandi.json")]
+        #[tokio::test]
+        async fn not_found(#[case] sidecars: bool, #[case] path: &str) {
+            let transport = lookup(FakeTransport::new(), path, &[]);
+            let transport = lookup(transport, "foo.nwb", &[asset("foo.nwb", false)]);
+            let transport = lookup(transport, "qux.nwb", &[]);
+            let transport = lookup(transport, "bar", &[asset("bar/baz.nwb", false)]);
+            let (client, _) = fake_client(transport);
+            let r = client
+                .dandiset("000001".parse().unwrap())
+                .version(VersionId::Draft)
+                .with_sidecars(sidecars)
+                .get_resource(&path.parse().unwrap())
+                .await;
+            assert_matches!(r, Err(DandiError::PathNotFound { .. }));
+        }
+
+        #[rstest]
+        #[case(false, &["foo.nwb"])]
+        #[case(true, &["foo.nwb", "foo.nwb.dandi.json"])]
+        #[tokio::test]
+        async fn listed(#[case] sidecars: bool, #[case] names: &[&str]) {
+            let transport = FakeTransport::new()
+                .json(
+                    &format!("{VERSION_URL}/assets/paths/?page=1&page_size=10"),
+                    &json!({
+                        "next": null,
+                        "results": [{"path": "foo.nwb", "asset": {"asset_id": "0123-4567"}}],
+                    }),
+                )
+                .json(
+                    &format!("{VERSION_URL}/assets/0123-4567/info/"),
+                    &asset("foo.nwb", false),
+                );
+            let (client, _) = fake_client(transport);
+            let page = client
+                .dandiset("000001".parse().unwrap())
+                .version(VersionId::Draft)
+                .with_sidecars(sidecars)
+                .get_root_children_page(1, 10)
+                .await
+                .unwrap();
+            let listed = page
+                .children
+                .iter()
+                .map(|r| match r {
+                    DandiResource::Asset(Asset::Blob(blob)) => blob.path.to_string(),
+                    DandiResource::AssetSidecar(sidecar) => {
+                        assert_eq!(sidecar.content, None);
+                        sidecar.path.to_string()
+                    }
+                    r => panic!("unexpected resource: {r:?}"),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(listed, names);
+        }
+    }
+
+    mod download_range {
+        use super::*;
+        use rstest::rstest;
+
+        const BLOB_URL: &str = "https://blobs.example.com/blobs/abc";
+
+        fn blob() -> Vec<u8> {
+            (0..5000u32)
+                .map(|i| u8::try_from(i % 251).unwrap())
+                .collect()
+        }
+
+        #[rstest]
+        #[case(0..10)]
+        #[case(1500..2600)]
+        #[case(4990..6000)]
+        #[case(6000..7000)]
+        #[tokio::test]
+        async fn range_ignored(#[case] range: Range<u64>) {
+            let transport = FakeTransport::new().respond(BLOB_URL, StatusCode::OK, &[], blob());
+            let client = client("https://api.example.com/api").with_transport(Arc::new(transport));
+            let data = client
+                .download_range(&BLOB_URL.parse().unwrap(), range.clone())
+                .await
+                .unwrap();
+            let blob = blob();
+            let start = usize::try_from(range.start).unwrap().min(blob.len());
+            let end = usize::try_from(range.end).unwrap().min(blob.len());
+            assert_eq!(data, blob[start..end]);
+        }
+
+        #[tokio::test]
+        async fn partial_content() {
+            let blob = blob();
+            let transport = FakeTransport::new().respond(
+                BLOB_URL,
+                StatusCode::PARTIAL_CONTENT,
+                &[("content-range", "bytes 1000-3999/5000")],
+                blob[1000..4000].to_vec(),
+            );
+            let client = client("https://api.example.com/api").with_transport(Arc::new(transport));
+            let data = client
+                .download_range(&BLOB_URL.parse().unwrap(), 1000..4000)
+                .await
+                .unwrap();
+            assert_eq!(data, blob[1000..4000]);
+        }
+    }
+
+    mod missing_paths {
+        use super::*;
+
+        #[tokio::test]
+        async fn cached_until_modified() {
+            let transport = Arc::new(lookup(FakeTransport::new(), "index.html", &[]).json(
+                &format!("{VERSION_URL}/info/"),
+                &version("2024-01-02T00:00:00Z"),
+            ));
+            let client = caching_client().with_transport(transport.clone());
+            let path = "index.html".parse::<PurePath>().unwrap();
+            let lookup = || async {
+                client
+                    .dandiset("000001".parse().unwrap())
+                    .version(VersionId::Draft)
+                    .get_resource(&path)
+                    .await
+            };
+            let lookups = |transport: &FakeTransport| {
+                transport
+                    .requests()
+                    .into_iter()
+                    .filter(|(_, url)| url.contains("/assets/?"))
+                    .count()
+            };
+
+            assert_matches!(lookup().await, Err(DandiError::PathNotFound { .. }));
+            assert_eq!(lookups(&transport), 1);
+            assert_matches!(lookup().await, Err(DandiError::PathNotFound { .. }));
+            assert_eq!(lookups(&transport), 1);
+            assert_eq!(
+                client.missing_paths.stats(),
+                CacheStats { hits: 1, misses: 1 }
+            );
+
+            // Simulate a change to the draft version:
+            let mut modified = client
+                .dandiset("000001".parse().unwrap())
+                .version(VersionId::Draft)
+                .get()
+                .await
+                .unwrap();
+            modified.modified = time::macros::datetime!(2024-01-03 00:00:00 UTC);
+            client
+                .versions
+                .insert(
+                    (None, "000001".parse().unwrap(), VersionId::Draft),
+                    modified,
+                )
+                .await;
+            assert_matches!(lookup().await, Err(DandiError::PathNotFound { .. }));
+            assert_eq!(lookups(&transport), 2);
+        }
+    }
+
+    mod zarr_lookup {
+        use super::*;
+        use rstest::rstest;
+
+        async fn get_zarr_at_path(
+            transport: FakeTransport,
+            exhaustive: bool,
+            path: &str,
+        ) -> Option<(ZarrAsset, Option<PurePath>)> {
+            let client = client_with(
+                FAKE_API_URL,
+                NO_CACHE,
+                ZarrLookup {
+                    extensions: ZarrExtensions::default(),
+                    max_candidates: 5,
+                    exhaustive,
+                },
+            )
+            .with_transport(Arc::new(transport));
+            client
+                .dandiset("000001".parse().unwrap())
+                .version(VersionId::Draft)
+                .get_zarr_at_path(&path.parse().unwrap())
+                .await
+                .unwrap()
+        }
+
+        #[rstest]
+        #[case(false)]
+        #[case(true)]
+        #[tokio::test]
+        async fn uppercase_extension(#[case] exhaustive: bool) {
+            let transport = lookup(
+                FakeTransport::new(),
+                "sub-01",
+                &[asset("sub-01/Data.ZARR", true)],
+            );
+            let transport = lookup(
+                transport,
+                "sub-01/Data.ZARR",
+                &[asset("sub-01/Data.ZARR", true)],
+            );
+            let (zarr, entry_path) =
+                get_zarr_at_path(transport, exhaustive, "sub-01/Data.ZARR/0/0")
+                    .await
+                    .unwrap();
+            assert_eq!(zarr.path, "sub-01/Data.ZARR");
+            assert_eq!(entry_path.unwrap(), "0/0");
+        }
+
+        #[rstest]
+        #[case(false, None)]
+        #[case(true, Some("0/0"))]
+        #[tokio::test]
+        async fn unrecognized_extension(#[case] exhaustive: bool, #[case] entry: Option<&str>) {
+            let transport = lookup(FakeTransport::new(), "sub-01/data.n5/0/0", &[]);
+            let transport = lookup(transport, "sub-01", &[asset("sub-01/data.n5", true)]);
+            let transport = lookup(
+                transport,
+                "sub-01/data.n5",
+                &[asset("sub-01/data.n5", true)],
+            );
+            let r = get_zarr_at_path(transport, exhaustive, "sub-01/data.n5/0/0").await;
+            assert_eq!(
+                r.and_then(|(_, ep)| ep).as_ref().map(PurePath::as_ref),
+                entry
+            );
+        }
+
+        #[rstest]
+        #[case(1)]
+        #[case(3)]
+        #[tokio::test]
+        async fn too_many_candidates(#[case] max_candidates: usize) {
+            let path = "a.zarr/b.zarr/c.zarr/d.zarr/e.zarr/f.zarr/0";
+            let leaf = asset(path, false);
+            let transport = path
+                .match_indices('/')
+                .fold(FakeTransport::new(), |transport, (i, _)| {
+                    lookup(transport, &path[..i], std::slice::from_ref(&leaf))
+                });
+            let transport = Arc::new(transport);
+            let client = client_with(
+                FAKE_API_URL,
+                NO_CACHE,
+                ZarrLookup {
+                    extensions: ZarrExtensions::default(),
+                    max_candidates,
+                    exhaustive: false,
+                },
+            )
+            .with_transport(transport.clone());
+            let r = client
+                .dandiset("000001".parse().unwrap())
+                .version(VersionId::Draft)
+                .get_resource_with_s3(&path.parse().unwrap())
+                .await;
+            let e = r.unwrap_err();
+            assert_eq!(e.class(), ErrorClass::NotFound);
+            assert_matches!(e, DandiError::TooManyZarrCandidates { max, .. } if max == max_candidates);
+            let lookups = transport
+                .requests()
+                .into_iter()
+                .filter(|(_, url)| url.contains("/assets/?path="))
+                .count();
+            assert_eq!(lookups, max_candidates);
+        }
+
+        #[tokio::test]
+        async fn exhaustive_not_found() {
+            let transport = lookup(FakeTransport::new(), "sub-01/data.nwb/0", &[]);
+            let transport = lookup(transport, "sub-01", &[asset("sub-01/data.nwb", false)]);
+            let transport = lookup(
+                transport,
+                "sub-01/data.nwb",
+                &[asset("sub-01/data.nwb", false)],
+            );
+            assert_eq!(
+                get_zarr_at_path(transport, true, "sub-01/data.nwb/0").await,
+                None
+            );
+        }
+    }
+
+    mod paginate {
+        use super::*;
+        use futures_util::StreamExt;
+        use rstest::rstest;
+
+        const PAGE_URL: &str = "https://api.example.com/api/things/";
+
+        fn page_url(n: u32) -> String {
+            format!("{PAGE_URL}?page={n}")
+        }
+
+        /// Return a transport serving three pages of two numbers each at
+        /// `PAGE_URL`
+        fn pages() -> FakeTransport {
+            FakeTransport::new()
+                .json(PAGE_URL, &json!({"next": page_url(2), "results": [1, 2]}))
+                .json(
+                    &page_url(2),
+                    &json!({"next": page_url(3), "results": [3, 4]}),
+                )
+                .json(&page_url(3), &json!({"next": null, "results": [5, 6]}))
+        }
+
+        fn paginate(
+            transport: FakeTransport,
+            prefetch: usize,
+        ) -> (Paginate<u32>, Arc<FakeTransport>) {
+            let transport = Arc::new(transport);
+            let client = DandiClient {
+                page_prefetch: prefetch,
+                ..client("https://api.example.com/api").with_transport(transport.clone())
+            };
+            (client.paginate(PAGE_URL.parse().unwrap()), transport)
+        }
+
+        /// Let any spawned page-fetching tasks run to completion
+        async fn settle() {
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        #[rstest]
+        #[case(0, 1)]
+        #[case(1, 2)]
+        #[case(2, 3)]
+        #[case(5, 3)]
+        #[tokio::test]
+        async fn prefetch_pages(#[case] prefetch: usize, #[case] requested: usize) {
+            let (mut stream, transport) = paginate(pages(), prefetch);
+            for expected in [1, 2] {
+                assert_eq!(stream.next().await.unwrap().unwrap(), expected);
+                settle().await;
+            }
+            assert_eq!(transport.requests().len(), requested);
+            let rest = stream.map(Result::unwrap).collect::<Vec<_>>().await;
+            assert_eq!(rest, [3, 4, 5, 6]);
+            assert_eq!(
+                transport
+                    .requests()
+                    .into_iter()
+                    .map(|(_, url)| url)
+                    .collect::<Vec<_>>(),
+                [PAGE_URL.to_owned(), page_url(2), page_url(3)]
+            );
+        }
+
+        #[rstest]
+        #[case(0)]
+        #[case(2)]
+        #[tokio::test]
+        async fn error_after_earlier_results(#[case] prefetch: usize) {
+            let transport = FakeTransport::new()
+                .json(PAGE_URL, &json!({"next": page_url(2), "results": [1, 2]}));
+            let (stream, _) = paginate(transport, prefetch);
+            let items = stream.collect::<Vec<_>>().await;
+            assert_eq!(items.len(), 3);
+            assert_matches!(items[0], Ok(1));
+            assert_matches!(items[1], Ok(2));
+            assert_matches!(items[2], Err(DandiError::Http(HttpError::NotFound { .. })));
+        }
+    }
 }
@@ -1,84 +1,145 @@
 use super::{DandiClient, DandiError};
 use crate::httputil::{Client, HttpError, HttpUrl};
-use futures_util::{future::BoxFuture, FutureExt, Stream};
+use futures_util::Stream;
 use pin_project::pin_project;
 use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::VecDeque;
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{ready, Context, Poll};
+use tokio::task::JoinHandle;
 
 // Implementing paginate() as a manually-implemented Stream instead of via
 // async_stream lets us save about 4700 bytes on dandidav's top-level Futures.
+/// A [`Stream`] of the results from a paginated Archive API endpoint.
+///
+/// Up to `prefetch` pages beyond the one currently being yielded are fetched
+/// in background tasks so that consumers do not have to wait a full round
+/// trip for each page.  As each page gives the URL of the next, pages are
+/// fetched one at a time, and the next fetch is started when the stream is
+/// polled after the previous one completes.  A `prefetch` of zero fetches
+/// each page only once the previous page's results have all been yielded.
 #[pin_project]
 #[must_use = "streams do nothing unless polled"]
 pub(super) struct Paginate<T> {
     client: Client,
-    state: PaginateState<T>,
-}
 
-enum PaginateState<T> {
-    Requesting(BoxFuture<'static, Result<Page<T>, HttpError>>),
-    Yielding {
-        results: std::vec::IntoIter<T>,
-        next: Option<HttpUrl>,
-    },
-    Done,
+    /// The maximum number of pages to fetch ahead of the current page
+    prefetch: usize,
+
+    /// The remaining results from the current page
+    results: std::vec::IntoIter<T>,
+
+    /// The results of pages that have been fetched but not yet started on
+    buffered: VecDeque<Vec<T>>,
+
+    /// The task fetching the next page, if any
+    inflight: Option<PageTask<T>>,
+
+    /// The URL of the next page to fetch once `inflight` is `None`
+    next: Option<HttpUrl>,
+
+    /// An error encountered while fetching a page, to be yielded once the
+    /// results from earlier pages are exhausted
+    error: Option<HttpError>,
 }
 
 impl<T> Paginate<T> {
     pub(super) fn new(client: &DandiClient, url: HttpUrl) -> Self {
         Paginate {
             client: client.inner.clone(),
-            state: PaginateState::Yielding {
-                results: Vec::new().into_iter(),
-                next: Some(url),
-            },
+            prefetch: client.page_prefetch,
+            results: Vec::new().into_iter(),
+            buffered: VecDeque::new(),
+            inflight: None,
+            next: Some(url),
+            error: None,
         }
     }
 }
 
 impl<T> Stream for Paginate<T>
 where
-    T: DeserializeOwned + 'static,
+    T: DeserializeOwned + Send + 'static,
 {
     type Item = Result<T, DandiError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
         loop {
-            match this.state {
-                PaginateState::Requesting(ref mut fut) => match ready!(fut.as_mut().poll(cx)) {
-                    Ok(page) => {
-                        *this.state = PaginateState::Yielding {
-                            results: page.results.into_iter(),
-                            next: page.next,
+            if let Some(task) = this.inflight {
+                if let Poll::Ready(r) = Pin::new(task).poll(cx) {
+                    *this.inflight = None;
+                    match r {
+                        Ok(page) => {
+                            this.buffered.push_back(page.results);
+                            *this.next = page.next;
                         }
+                        Err(e) => *this.error = Some(e),
                     }
-                    Err(e) => {
-                        *this.state = PaginateState::Done;
-                        return Some(Err(DandiError::from(e))).into();
-                    }
-                },
-                PaginateState::Yielding {
-                    ref mut results,
-                    ref mut next,
-                } => {
-                    if let Some(item) = results.next() {
-                        return Some(Ok(item)).into();
-                    } else if let Some(url) = next.take() {
-                        *this.state =
-                            PaginateState::Requesting(this.client.get_json::<Page<T>>(url).boxed());
-                    } else {
-                        *this.state = PaginateState::Done;
-                    }
                 }
-                PaginateState::Done => return None.into(),
             }
+            let idle = this.results.as_slice().is_empty() && this.buffered.is_empty();
+            if this.inflight.is_none()
+                && this.error.is_none()
+                && (idle || this.buffered.len() < *this.prefetch)
+            {
+                if let Some(url) = this.next.take() {
+                    *this.inflight = Some(PageTask::spawn(this.client, url));
+                    // Poll the new task so that we're woken when it's done
+                    continue;
+                }
+            }
+            if let Some(item) = this.results.next() {
+                return Some(Ok(item)).into();
+            } else if let Some(results) = this.buffered.pop_front() {
+                *this.results = results.into_iter();
+            } else if let Some(e) = this.error.take() {
+                return Some(Err(DandiError::from(e))).into();
+            } else if this.inflight.is_some() {
+                return Poll::Pending;
+            } else {
+                return None.into();
+            }
+        }
+    }
+}
+
+/// A background task fetching a single page of results.  The task is aborted
+/// if this handle is dropped before the task completes.
+struct PageTask<T>(JoinHandle<Result<Page<T>, HttpError>>);
+
+impl<T: DeserializeOwned + Send + 'static> PageTask<T> {
+    fn spawn(client: &Client, url: HttpUrl) -> Self {
+        PageTask(tokio::spawn(client.get_json::<Page<T>>(url)))
+    }
+}
+
+impl<T> Future for PageTask<T> {
+    type Output = Result<Page<T>, HttpError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match ready!(Pin::new(&mut self.0).poll(cx)) {
+            Ok(r) => Poll::Ready(r),
+            // The task is only aborted when `self` is dropped, so this can
+            // only be a panic:
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
         }
     }
 }
 
+impl<T> Drop for PageTask<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// A single page of results from a paginated Archive API endpoint
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
-struct Page<T> {
-    next: Option<HttpUrl>,
-    results: Vec<T>,
+pub(super) struct Page<T> {
+    /// The URL of the next page of results, if any
+    pub(super) next: Option<HttpUrl>,
+
+    /// The results on this page
+    pub(super) results: Vec<T>,
 }
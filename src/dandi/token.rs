@@ -0,0 +1,65 @@
+use reqwest::header::HeaderValue;
+use std::fmt;
+use thiserror::Error;
+
+/// An API token for a DANDI Archive instance, used to authenticate requests
+/// in order to gain access to embargoed Dandisets
+///
+/// The token's `Debug` representation does not include the token itself so
+/// that it does not end up in logs.
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub(crate) struct ApiToken(String);
+
+impl ApiToken {
+    /// Return the value of the `Authorization` header to send to the Archive
+    /// in order to authenticate with this token
+    pub(crate) fn header_value(&self) -> HeaderValue {
+        HeaderValue::try_from(format!("token {}", self.0))
+            .expect("API token should have been validated on construction")
+    }
+}
+
+impl fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiToken(<redacted>)")
+    }
+}
+
+impl std::str::FromStr for ApiToken {
+    type Err = ParseApiTokenError;
+
+    fn from_str(s: &str) -> Result<ApiToken, ParseApiTokenError> {
+        let s = s.trim();
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_graphic()) {
+            Ok(ApiToken(s.to_owned()))
+        } else {
+            Err(ParseApiTokenError)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("API tokens must be nonempty and consist of printable ASCII characters")]
+pub(crate) struct ParseApiTokenError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn parse_token() {
+        let token = " 0123456789abcdef\n".parse::<ApiToken>().unwrap();
+        assert_eq!(token.header_value(), "token 0123456789abcdef");
+        assert_eq!(format!("{token:?}"), "ApiToken(<redacted>)");
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("  ")]
+    #[case("abc def")]
+    #[case("abc\u{e9}")]
+    fn parse_bad_token(#[case] s: &str) {
+        assert_eq!(s.parse::<ApiToken>(), Err(ParseApiTokenError));
+    }
+}
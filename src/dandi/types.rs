@@ -1,8 +1,10 @@
 use super::{DandisetId, VersionId};
+use crate::consts::ASSET_SIDECAR_SUFFIX;
 use crate::httputil::HttpUrl;
 use crate::paths::{PureDirPath, PurePath};
 use crate::s3::{PrefixedS3Client, S3Entry, S3Folder, S3Location, S3Object};
-use serde::Deserialize;
+use serde::{ser::Serializer, Deserialize, Serialize};
+use std::collections::BTreeMap;
 use thiserror::Error;
 use time::OffsetDateTime;
 
@@ -15,15 +17,19 @@ pub(super) struct RawDandiset {
     modified: OffsetDateTime,
     //contact_person: String,
     //embargo_status: ...,
-    draft_version: RawDandisetVersion,
+    // This is usually present, but it can be null or absent for degenerate
+    // Dandisets (e.g., ones whose versions are all hidden from us due to
+    // embargo), and such Dandisets shouldn't break listings.
+    #[serde(default)]
+    draft_version: Option<RawDandisetVersion>,
     most_recent_published_version: Option<RawDandisetVersion>,
 }
 
 impl RawDandiset {
     pub(super) fn with_metadata_urls(self, client: &super::DandiClient) -> Dandiset {
-        let draft_version = self
-            .draft_version
-            .with_metadata_url(client.version_metadata_url(&self.identifier, &VersionId::Draft));
+        let draft_version = self.draft_version.map(|v| {
+            v.with_metadata_url(client.version_metadata_url(&self.identifier, &VersionId::Draft))
+        });
         let most_recent_published_version = self.most_recent_published_version.map(|v| {
             let url = client.version_metadata_url(&self.identifier, &v.version);
             v.with_metadata_url(url)
@@ -43,7 +49,7 @@ pub(crate) struct Dandiset {
     pub(crate) identifier: DandisetId,
     pub(crate) created: OffsetDateTime,
     pub(crate) modified: OffsetDateTime,
-    pub(crate) draft_version: DandisetVersion,
+    pub(crate) draft_version: Option<DandisetVersion>,
     pub(crate) most_recent_published_version: Option<DandisetVersion>,
 }
 
@@ -51,7 +57,8 @@ pub(crate) struct Dandiset {
 pub(super) struct RawDandisetVersion {
     pub(super) version: VersionId,
     //name: String,
-    //asset_count: u64,
+    #[serde(default)]
+    asset_count: Option<u64>,
     size: i64,
     //status: ...,
     #[serde(with = "time::serde::rfc3339")]
@@ -65,6 +72,7 @@ impl RawDandisetVersion {
         DandisetVersion {
             version: self.version,
             size: self.size,
+            asset_count: self.asset_count,
             created: self.created,
             modified: self.modified,
             metadata_url,
@@ -76,6 +84,8 @@ impl RawDandisetVersion {
 pub(crate) struct DandisetVersion {
     pub(crate) version: VersionId,
     pub(crate) size: i64,
+    /// The number of assets in the version, if reported by the Archive
+    pub(crate) asset_count: Option<u64>,
     pub(crate) created: OffsetDateTime,
     pub(crate) modified: OffsetDateTime,
     pub(crate) metadata_url: HttpUrl,
@@ -96,6 +106,45 @@ impl From<VersionMetadata> for Vec<u8> {
     }
 }
 
+/// Information about an Archive instance, as returned by the API's `/info/`
+/// endpoint
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub(crate) struct InstanceInfo {
+    /// The version of the Archive software running on the instance
+    pub(crate) version: String,
+
+    /// The version of the DANDI metadata schema used by the instance.  This
+    /// is not provided by very old versions of the Archive software.
+    #[serde(default)]
+    pub(crate) schema_version: Option<String>,
+
+    /// Details identifying the instance.  This is not provided by older
+    /// versions of the Archive software.
+    #[serde(default)]
+    pub(crate) instance_config: Option<InstanceConfig>,
+}
+
+impl InstanceInfo {
+    /// Return the instance's human-readable name, if known
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.instance_config
+            .as_ref()
+            .map(|cfg| cfg.instance_name.as_str())
+    }
+
+    /// Return the URL of the instance's web interface, if known
+    pub(crate) fn url(&self) -> Option<&HttpUrl> {
+        self.instance_config.as_ref()?.instance_url.as_ref()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub(crate) struct InstanceConfig {
+    pub(crate) instance_name: String,
+    #[serde(default)]
+    pub(crate) instance_url: Option<HttpUrl>,
+}
+
 // Item in a `/dandisets/{dandiset_id}/versions/{version_id}/assets/paths/`
 // response
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
@@ -108,6 +157,26 @@ pub(crate) enum FolderEntry {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) struct AssetFolder {
     pub(crate) path: PureDirPath,
+
+    /// The total size of all assets beneath the folder, if known.  This is
+    /// only known for folders listed as children of other folders.
+    pub(crate) total_size: Option<i64>,
+
+    /// The number of assets beneath the folder, if known.  This is only known
+    /// for folders listed as children of other folders.
+    pub(crate) total_assets: Option<u64>,
+}
+
+/// The BIDS datasets detected in a Dandiset version by
+/// [`VersionEndpoint::find_bids_datasets()`][super::VersionEndpoint::find_bids_datasets]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct BidsDatasets {
+    /// Whether the root of the version's file hierarchy is itself a BIDS
+    /// dataset
+    pub(crate) root: bool,
+
+    /// The folders that are BIDS datasets, sorted by path
+    pub(crate) folders: Vec<AssetFolder>,
 }
 
 impl From<RawFolderEntry> for FolderEntry {
@@ -120,6 +189,8 @@ impl From<RawFolderEntry> for FolderEntry {
         } else {
             FolderEntry::Folder(AssetFolder {
                 path: entry.path.to_dir_path(),
+                total_size: entry.total_size,
+                total_assets: entry.total_assets,
             })
         }
     }
@@ -131,6 +202,10 @@ impl From<RawFolderEntry> for FolderEntry {
 struct RawFolderEntry {
     path: PurePath,
     asset: Option<RawFolderEntryAsset>,
+    #[serde(default, alias = "aggregate_size")]
+    total_size: Option<i64>,
+    #[serde(default, alias = "aggregate_files")]
+    total_assets: Option<u64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
@@ -151,6 +226,51 @@ pub(crate) enum Asset {
     Zarr(ZarrAsset),
 }
 
+impl Asset {
+    /// Return the asset's path within its Dandiset version
+    pub(crate) fn path(&self) -> &PurePath {
+        match self {
+            Asset::Blob(blob) => &blob.path,
+            Asset::Zarr(zarr) => &zarr.path,
+        }
+    }
+
+    /// Return the size of the asset's content in bytes
+    pub(crate) fn size(&self) -> i64 {
+        match self {
+            Asset::Blob(blob) => blob.size,
+            Asset::Zarr(zarr) => zarr.size,
+        }
+    }
+
+    /// Return the checksums of the asset's content as recorded in its
+    /// metadata
+    pub(crate) fn digests(&self) -> &AssetDigests {
+        match self {
+            Asset::Blob(blob) => &blob.metadata.digest,
+            Asset::Zarr(zarr) => &zarr.metadata.digest,
+        }
+    }
+
+    /// Return the virtual metadata sidecar file for the asset, without its
+    /// content
+    pub(crate) fn sidecar(&self) -> AssetSidecar {
+        let (path, created, modified, metadata_url) = match self {
+            Asset::Blob(blob) => (&blob.path, blob.created, blob.modified, &blob.metadata_url),
+            Asset::Zarr(zarr) => (&zarr.path, zarr.created, zarr.modified, &zarr.metadata_url),
+        };
+        AssetSidecar {
+            path: format!("{path}{ASSET_SIDECAR_SUFFIX}")
+                .parse::<PurePath>()
+                .expect("appending a suffix to a path should produce a valid path"),
+            created,
+            modified,
+            metadata_url: metadata_url.clone(),
+            content: None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) struct BlobAsset {
     pub(crate) asset_id: String,
@@ -169,7 +289,7 @@ impl BlobAsset {
     }
 
     pub(crate) fn etag(&self) -> Option<&str> {
-        self.metadata.digest.dandi_etag.as_deref()
+        self.metadata.digest.dandi_etag()
     }
 
     pub(crate) fn archive_url(&self) -> Option<&HttpUrl> {
@@ -200,6 +320,13 @@ pub(crate) struct ZarrAsset {
 }
 
 impl ZarrAsset {
+    pub(crate) fn s3_url(&self) -> Option<&HttpUrl> {
+        self.metadata
+            .content_url
+            .iter()
+            .find(|url| S3Location::parse_url(url.as_url()).is_ok())
+    }
+
     pub(crate) fn s3location(&self) -> Option<S3Location> {
         self.metadata
             .content_url
@@ -207,6 +334,11 @@ impl ZarrAsset {
             .find_map(|url| S3Location::parse_url(url.as_url()).ok())
     }
 
+    /// Return the Zarr's DANDI Zarr checksum, if known
+    pub(crate) fn checksum(&self) -> Option<&str> {
+        self.metadata.digest.dandi_zarr_checksum()
+    }
+
     pub(crate) fn make_resource(&self, value: S3Entry) -> DandiResource {
         match value {
             S3Entry::Folder(folder) => DandiResource::ZarrFolder(self.make_folder(folder)),
@@ -236,6 +368,29 @@ impl ZarrAsset {
     }
 }
 
+/// A virtual file alongside an asset in a Dandiset version, containing the
+/// asset's full metadata as JSON
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct AssetSidecar {
+    /// The path to the sidecar file, i.e., the asset's path with
+    /// [`ASSET_SIDECAR_SUFFIX`] appended
+    pub(crate) path: PurePath,
+
+    /// The timestamp at which the asset was created
+    pub(crate) created: OffsetDateTime,
+
+    /// The timestamp at which the asset was last modified
+    pub(crate) modified: OffsetDateTime,
+
+    /// The Archive URL from which the asset's metadata is retrieved
+    pub(crate) metadata_url: HttpUrl,
+
+    /// The asset's metadata serialized as JSON.  This is only fetched when
+    /// the sidecar itself is requested, not when it is listed as a child of
+    /// a collection.
+    pub(crate) content: Option<Vec<u8>>,
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct AssetMetadata {
@@ -244,10 +399,43 @@ pub(crate) struct AssetMetadata {
     digest: AssetDigests,
 }
 
+/// The checksums of an asset's content, keyed by digest type (e.g.,
+/// `dandi:dandi-etag` or `dandi:sha2-256`)
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
-pub(crate) struct AssetDigests {
-    #[serde(rename = "dandi:dandi-etag")]
-    dandi_etag: Option<String>,
+#[serde(transparent)]
+pub(crate) struct AssetDigests(BTreeMap<String, serde_json::Value>);
+
+impl AssetDigests {
+    /// Return the asset's DANDI ETag, if known
+    fn dandi_etag(&self) -> Option<&str> {
+        self.0
+            .get("dandi:dandi-etag")
+            .and_then(serde_json::Value::as_str)
+    }
+
+    /// Return the Zarr asset's DANDI Zarr checksum, if known
+    fn dandi_zarr_checksum(&self) -> Option<&str> {
+        self.0
+            .get("dandi:dandi-zarr-checksum")
+            .and_then(serde_json::Value::as_str)
+    }
+}
+
+impl Serialize for AssetDigests {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// Identifying information about an asset, as returned by the Archive's
+/// `/assets/{asset_id}/info/` endpoint, which does not require knowing the
+/// Dandiset that the asset belongs to
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub(crate) struct AssetInfo {
+    pub(crate) asset_id: String,
+    pub(crate) blob: Option<String>,
+    pub(crate) zarr: Option<String>,
+    pub(crate) path: PurePath,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
@@ -313,6 +501,7 @@ pub(crate) enum AssetTypeError {
 pub(crate) enum DandiResource {
     Folder(AssetFolder),
     Asset(Asset),
+    AssetSidecar(AssetSidecar),
     ZarrFolder(ZarrFolder),
     ZarrEntry(ZarrEntry),
 }
@@ -322,6 +511,7 @@ impl DandiResource {
         match self {
             DandiResource::Folder(r) => DandiResourceWithS3::Folder(r),
             DandiResource::Asset(r) => DandiResourceWithS3::Asset(r),
+            DandiResource::AssetSidecar(r) => DandiResourceWithS3::AssetSidecar(r),
             DandiResource::ZarrFolder(folder) => DandiResourceWithS3::ZarrFolder { folder, s3 },
             DandiResource::ZarrEntry(r) => DandiResourceWithS3::ZarrEntry(r),
         }
@@ -375,6 +565,7 @@ pub(crate) struct ZarrEntry {
 pub(super) enum DandiResourceWithS3 {
     Folder(AssetFolder),
     Asset(Asset),
+    AssetSidecar(AssetSidecar),
     ZarrFolder {
         folder: ZarrFolder,
         s3: PrefixedS3Client,
@@ -396,6 +587,7 @@ impl From<DandiResourceWithS3> for DandiResource {
         match value {
             DandiResourceWithS3::Folder(r) => DandiResource::Folder(r),
             DandiResourceWithS3::Asset(r) => DandiResource::Asset(r),
+            DandiResourceWithS3::AssetSidecar(r) => DandiResource::AssetSidecar(r),
             DandiResourceWithS3::ZarrFolder { folder, .. } => DandiResource::ZarrFolder(folder),
             DandiResourceWithS3::ZarrEntry(r) => DandiResource::ZarrEntry(r),
         }
@@ -409,6 +601,7 @@ pub(crate) enum DandiResourceWithChildren {
         children: Vec<DandiResource>,
     },
     Blob(BlobAsset),
+    AssetSidecar(AssetSidecar),
     Zarr {
         zarr: ZarrAsset,
         children: Vec<DandiResource>,
@@ -419,3 +612,152 @@ pub(crate) enum DandiResourceWithChildren {
     },
     ZarrEntry(ZarrEntry),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use rstest::rstest;
+
+    #[test]
+    fn raw_dandiset_with_draft() {
+        let ds = serde_json::from_str::<RawDandiset>(
+            r#"{
+                "identifier": "000001",
+                "created": "2020-03-15T22:56:55.655000Z",
+                "modified": "2020-03-15T22:56:55.655000Z",
+                "contact_person": "Jane Doe",
+                "embargo_status": "OPEN",
+                "draft_version": {
+                    "version": "draft",
+                    "name": "Example Dandiset",
+                    "asset_count": 1,
+                    "size": 42,
+                    "status": "Valid",
+                    "created": "2020-03-15T22:56:55.655000Z",
+                    "modified": "2020-03-16T12:00:00.000000Z"
+                },
+                "most_recent_published_version": null
+            }"#,
+        )
+        .unwrap();
+        assert_matches!(
+            ds.draft_version,
+            Some(RawDandisetVersion {
+                version: VersionId::Draft,
+                asset_count: Some(1),
+                size: 42,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn raw_dandiset_null_draft() {
+        let ds = serde_json::from_str::<RawDandiset>(
+            r#"{
+                "identifier": "000002",
+                "created": "2020-03-15T22:56:55.655000Z",
+                "modified": "2020-03-15T22:56:55.655000Z",
+                "contact_person": "Jane Doe",
+                "embargo_status": "EMBARGOED",
+                "draft_version": null,
+                "most_recent_published_version": null
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(ds.draft_version, None);
+        assert_eq!(ds.most_recent_published_version, None);
+    }
+
+    #[test]
+    fn instance_info() {
+        let info = serde_json::from_str::<InstanceInfo>(
+            r#"{
+                "schema_version": "0.6.9",
+                "schema_url": "https://raw.githubusercontent.com/dandi/schema/master/releases/0.6.9/dandiset.json",
+                "version": "0.11.0",
+                "cli-minimal-version": "0.60.0",
+                "cli-bad-versions": [],
+                "services": {
+                    "api": {"url": "https://api.dandiarchive.org/api"},
+                    "webui": {"url": "https://dandiarchive.org"}
+                },
+                "instance_config": {
+                    "instance_name": "DANDI",
+                    "instance_identifier": "RRID:SCR_017571",
+                    "instance_url": "https://dandiarchive.org",
+                    "doi_prefix": "10.48324"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(info.version, "0.11.0");
+        assert_eq!(info.schema_version.as_deref(), Some("0.6.9"));
+        assert_eq!(info.name(), Some("DANDI"));
+        assert_eq!(
+            info.url().map(HttpUrl::as_str),
+            Some("https://dandiarchive.org/")
+        );
+    }
+
+    #[test]
+    fn instance_info_sans_config() {
+        let info = serde_json::from_str::<InstanceInfo>(
+            r#"{
+                "schema_version": "0.6.4",
+                "version": "0.3.80",
+                "services": {
+                    "api": {"url": "https://api.dandiarchive.org/api"}
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(info.version, "0.3.80");
+        assert_eq!(info.name(), None);
+        assert_eq!(info.url(), None);
+    }
+
+    #[rstest]
+    #[case(r#"{"path": "sub-01", "asset": null, "total_size": 1024, "total_assets": 3}"#)]
+    #[case(r#"{"path": "sub-01", "asset": null, "aggregate_size": 1024, "aggregate_files": 3}"#)]
+    fn folder_entry_totals(#[case] src: &str) {
+        let entry = serde_json::from_str::<FolderEntry>(src).unwrap();
+        assert_eq!(
+            entry,
+            FolderEntry::Folder(AssetFolder {
+                path: "sub-01/".parse().unwrap(),
+                total_size: Some(1024),
+                total_assets: Some(3),
+            })
+        );
+    }
+
+    #[test]
+    fn folder_entry_sans_totals() {
+        let entry =
+            serde_json::from_str::<FolderEntry>(r#"{"path": "sub-01", "asset": null}"#).unwrap();
+        assert_matches!(
+            entry,
+            FolderEntry::Folder(AssetFolder {
+                total_size: None,
+                total_assets: None,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn raw_dandiset_missing_versions() {
+        let ds = serde_json::from_str::<RawDandiset>(
+            r#"{
+                "identifier": "000003",
+                "created": "2020-03-15T22:56:55.655000Z",
+                "modified": "2020-03-15T22:56:55.655000Z"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(ds.draft_version, None);
+        assert_eq!(ds.most_recent_published_version, None);
+    }
+}
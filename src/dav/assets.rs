@@ -0,0 +1,114 @@
+//! Resolution of assets identified only by their asset IDs
+use super::util::{client_token, not_found, unauthorized, ErrorDetails};
+use super::{DandiDav, DavError, ErrorClass};
+use axum::{
+    body::Body,
+    http::{HeaderMap, Response},
+    response::{IntoResponse, Redirect},
+};
+use std::sync::Arc;
+
+impl DandiDav {
+    /// Respond to a request for `/assets/{asset_id}` by looking up the asset
+    /// with the given ID on the primary Archive instance and redirecting to a
+    /// location at which it can be accessed.
+    ///
+    /// As the Archive does not report which Dandisets an asset belongs to,
+    /// Zarr assets are redirected to their latest manifests in the `/zarrs/`
+    /// hierarchy, and blob assets are redirected to the Archive's download
+    /// endpoint for them.
+    ///
+    /// If the client supplied its own Archive API token in `headers`, that
+    /// token is used to look up the asset.
+    pub(crate) async fn redirect_asset(
+        self: &Arc<Self>,
+        asset_id: &str,
+        headers: &HeaderMap,
+    ) -> Response<Body> {
+        if uuid::Uuid::try_parse(asset_id).is_err() {
+            return not_found();
+        }
+        let this = self.for_request(client_token(headers));
+        let dandi = this.primary_dandi();
+        match dandi.get_asset_info(asset_id).await {
+            Ok(info) => match info.zarr {
+                Some(zarr_id) => Redirect::temporary(&format!("/zarrs/{zarr_id}/")).into_response(),
+                None => Redirect::temporary(dandi.asset_download_url(&info.asset_id).as_str())
+                    .into_response(),
+            },
+            Err(e) => {
+                let e = DavError::from(e);
+                let class = e.class();
+                let e = anyhow::Error::from(e);
+                tracing::info!(error = ?e, status = class.to_status().as_u16(), "Error resolving asset ID");
+                let message = format!("{e:?}");
+                let mut resp = match class {
+                    ErrorClass::NotFound => not_found(),
+                    ErrorClass::Unauthorized => unauthorized(),
+                    _ => (class.to_status(), message.clone()).into_response(),
+                };
+                resp.extensions_mut()
+                    .insert(ErrorDetails { class, message });
+                resp
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::dav_with_transport;
+    use super::*;
+    use crate::httputil::FakeTransport;
+    use axum::http::{header::LOCATION, StatusCode};
+    use rstest::rstest;
+    use serde_json::json;
+
+    const ASSET_ID: &str = "0123abcd-4567-89ab-cdef-0123456789ab";
+
+    async fn redirect(zarr: Option<&str>, asset_id: &str) -> Response<Body> {
+        let transport = FakeTransport::new().json(
+            &format!("http://127.0.0.1:9/api/assets/{ASSET_ID}/info/"),
+            &json!({
+                "asset_id": ASSET_ID,
+                "blob": zarr.is_none().then_some("89ab-cdef"),
+                "zarr": zarr,
+                "path": "sub-01/sub-01_ecephys.nwb",
+                "size": 42,
+                "created": "2024-01-01T00:00:00Z",
+                "modified": "2024-01-02T00:00:00Z",
+            }),
+        );
+        let dav = dav_with_transport(transport);
+        Arc::new(dav)
+            .redirect_asset(asset_id, &HeaderMap::new())
+            .await
+    }
+
+    #[tokio::test]
+    async fn blob() {
+        let resp = redirect(None, ASSET_ID).await;
+        assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            resp.headers()[LOCATION],
+            format!("http://127.0.0.1:9/api/assets/{ASSET_ID}/download/")
+        );
+    }
+
+    #[tokio::test]
+    async fn zarr() {
+        let zarr_id = "fedcba98-7654-3210-fedc-ba9876543210";
+        let resp = redirect(Some(zarr_id), ASSET_ID).await;
+        assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(resp.headers()[LOCATION], format!("/zarrs/{zarr_id}/"));
+    }
+
+    #[rstest]
+    #[case("not-a-uuid")]
+    #[case("11111111-2222-3333-4444-555555555555")]
+    #[tokio::test]
+    async fn not_found(#[case] asset_id: &str) {
+        let resp = redirect(None, asset_id).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,161 @@
+//! In-process caching of resources belonging to published Dandiset versions
+use super::instance::Instance;
+use super::path::{DavPath, VersionSpec};
+use super::types::{DavCollection, DavResource, DavResourceWithChildren};
+use super::DavError;
+use crate::dandi::{DandisetId, PublishedVersionId};
+use crate::metrics::metrics;
+use crate::paths::PurePath;
+use futures_util::{stream, stream::BoxStream, StreamExt};
+use moka::future::{Cache, CacheBuilder};
+use std::future::Future;
+
+/// The name under which lookups in the [`PublishedCache`] are logged &
+/// counted
+const PUBLISHED_CACHE_NAME: &str = "published-resources";
+
+/// A cache of resources (along with their immediate child resources) in
+/// published Dandiset versions.
+///
+/// As published versions never change, entries are never expired; they are
+/// only evicted when the cache is full.  The size of the cache is measured in
+/// resources, with each entry weighing one plus its number of children.
+#[derive(Clone, Debug)]
+pub(crate) struct PublishedCache(Cache<PublishedKey, DavResourceWithChildren>);
+
+impl PublishedCache {
+    /// Construct a cache holding up to `max_resources` resources.  Returns
+    /// `None` if `max_resources` is zero, in which case caching is disabled.
+    pub(crate) fn new(max_resources: u64) -> Option<PublishedCache> {
+        (max_resources > 0).then(|| {
+            PublishedCache(
+                CacheBuilder::new(max_resources)
+                    .name(PUBLISHED_CACHE_NAME)
+                    .weigher(|_, res: &DavResourceWithChildren| {
+                        let children = match res {
+                            DavResourceWithChildren::Collection { children, .. } => children.len(),
+                            DavResourceWithChildren::Item(_) => 0,
+                        };
+                        u32::try_from(children.saturating_add(1)).unwrap_or(u32::MAX)
+                    })
+                    .build(),
+            )
+        })
+    }
+
+    /// Return the cached value for `key`, if any
+    pub(super) async fn get(&self, key: &PublishedKey) -> Option<DavResourceWithChildren> {
+        let value = self.0.get(key).await;
+        metrics().record_cache_lookup(PUBLISHED_CACHE_NAME, value.is_some());
+        tracing::debug!(
+            cache_event = if value.is_some() { "hit" } else { "miss" },
+            cache = PUBLISHED_CACHE_NAME,
+            ?key,
+            "Looked up published version resource in cache",
+        );
+        value
+    }
+
+    /// Cache `value` under `key`
+    pub(super) async fn insert(&self, key: PublishedKey, value: DavResourceWithChildren) {
+        self.0.insert(key, value).await;
+    }
+
+    /// Return the cached value for `key` if there is one.  Otherwise, await
+    /// `fetch`, cache its output if successful, and return it.
+    pub(super) async fn get_or_fetch<Fut>(
+        &self,
+        key: PublishedKey,
+        fetch: Fut,
+    ) -> Result<DavResourceWithChildren, DavError>
+    where
+        Fut: Future<Output = Result<DavResourceWithChildren, DavError>>,
+    {
+        if let Some(value) = self.get(&key).await {
+            return Ok(value);
+        }
+        let value = fetch.await?;
+        self.insert(key, value.clone()).await;
+        Ok(value)
+    }
+
+    /// Wrap a stream of the children of the collection `col` so that, once
+    /// the stream has been fully & successfully consumed, the collection and
+    /// its children are cached under `key`.  Nothing is cached if the stream
+    /// yields an error or is dropped before completion.
+    pub(super) fn tee(
+        &self,
+        key: PublishedKey,
+        col: DavCollection,
+        children: BoxStream<'static, Result<DavResource, DavError>>,
+    ) -> BoxStream<'static, Result<DavResource, DavError>> {
+        stream::unfold(
+            (children, Vec::new(), Some((self.clone(), key, col))),
+            |(mut children, mut seen, mut pending)| async move {
+                match children.next().await {
+                    Some(Ok(res)) => {
+                        seen.push(res.clone());
+                        Some((Ok(res), (children, seen, pending)))
+                    }
+                    Some(Err(e)) => {
+                        pending = None;
+                        Some((Err(e), (children, seen, pending)))
+                    }
+                    None => {
+                        if let Some((cache, key, col)) = pending {
+                            let value = DavResourceWithChildren::Collection {
+                                col,
+                                children: seen,
+                            };
+                            cache.insert(key, value).await;
+                        }
+                        None
+                    }
+                }
+            },
+        )
+        .boxed()
+    }
+}
+
+/// The key under which a resource is stored in a [`PublishedCache`]: the
+/// Dandiset & published version that the resource belongs to plus the
+/// resource's path within the version (or `None` for the version itself)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(super) struct PublishedKey {
+    instance: Instance,
+    dandiset_id: DandisetId,
+    version: PublishedVersionId,
+    path: Option<PurePath>,
+}
+
+impl PublishedKey {
+    /// Return the key for the resource at `path` if it is a published
+    /// Dandiset version or a resource within one.  Returns `None` for all
+    /// other paths, including those for virtual files & searches.
+    pub(super) fn for_path(path: &DavPath) -> Option<PublishedKey> {
+        let (instance, dandiset_id, version, path) = match path {
+            DavPath::Version {
+                instance,
+                dandiset_id,
+                version,
+            } => (instance, dandiset_id, version, None),
+            DavPath::DandiResource {
+                instance,
+                dandiset_id,
+                version,
+                path,
+            } => (instance, dandiset_id, version, Some(path)),
+            _ => return None,
+        };
+        let VersionSpec::Published(version) = version else {
+            return None;
+        };
+        Some(PublishedKey {
+            instance: instance.clone(),
+            dandiset_id: dandiset_id.clone(),
+            version: version.clone(),
+            path: path.cloned(),
+        })
+    }
+}
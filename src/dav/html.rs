@@ -1,18 +1,48 @@
 //! Rendering resource listings as HTML documents
-use super::util::Href;
-use super::{DavCollection, DavItem, DavResource, ResourceKind};
-use crate::consts::HTML_TIMESTAMP_FORMAT;
+use super::util::{Href, MatchFilter, PageRequest, SortKey, SortRequest};
+use super::{DavCollection, DavItem, DavResource, Instance, ResourceKind};
+use crate::consts::{HTML_OFFSET_TIMESTAMP_FORMAT, HTML_TIMESTAMP_FORMAT, UTC_OFFSET_FORMAT};
+use crate::dandi::InstanceInfo;
 use crate::paths::Component;
+use futures_util::{future::ready, stream, Stream, StreamExt};
 use humansize::{format_size_i, BINARY};
 use serde::{ser::Serializer, Serialize};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
 use tera::{Context, Error, Filter, Tera, Value};
 use thiserror::Error;
-use time::OffsetDateTime;
+use time::{
+    error::InvalidFormatDescription,
+    format_description::{self, OwnedFormatItem},
+    OffsetDateTime, PrimitiveDateTime, UtcOffset,
+};
 
-/// The [Tera](https://keats.github.io/tera/) template for HTML collection
-/// views
-static COLLECTION_TEMPLATE: &str = include_str!("templates/collection.html.tera");
+/// The [Tera](https://keats.github.io/tera/) templates for HTML collection
+/// views, keyed by template name.  A collection view consists of the output of
+/// `collection-head.html`, followed by the output of `collection-row.html` for
+/// each row of the table of resources, followed by the output of
+/// `collection-foot.html`.  Paginated views also include
+/// `collection-nav.html` in the head & foot.
+static COLLECTION_TEMPLATES: [(&str, &str); 4] = [
+    (
+        "collection-nav.html",
+        include_str!("templates/collection-nav.html.tera"),
+    ),
+    (
+        "collection-head.html",
+        include_str!("templates/collection-head.html.tera"),
+    ),
+    (
+        "collection-row.html",
+        include_str!("templates/collection-row.html.tera"),
+    ),
+    (
+        "collection-foot.html",
+        include_str!("templates/collection-foot.html.tera"),
+    ),
+];
 
 /// A template manager
 pub(crate) struct Templater {
@@ -21,75 +51,250 @@ pub(crate) struct Templater {
 
     /// Site title to display in HTML responses
     title: String,
+
+    /// Labels for the Archive instances being served, to display in the
+    /// footers of HTML responses
+    archives: Vec<ArchiveLabel>,
+
+    /// How timestamps are displayed in HTML responses
+    timestamps: TimestampStyle,
+
+    /// A hash of `title`, `archives`, & `timestamps`, which together with the
+    /// request determine the contents of rendered HTML documents
+    fingerprint: u64,
 }
 
 impl Templater {
-    /// Create a new templater with site title `title` and load all templates
-    /// into it
+    /// Create a new templater with site title `title` and Archive instance
+    /// labels `archives` and load all templates into it
     ///
     /// # Errors
     ///
     /// If any template fails to load, a [`TemplateError::Load`] is returned.
-    pub(crate) fn new(title: String) -> Result<Self, TemplateError> {
+    pub(crate) fn new(title: String, archives: Vec<ArchiveLabel>) -> Result<Self, TemplateError> {
         let mut engine = Tera::default();
         engine.register_filter("formatsize", FormatSizeFilter);
-        engine
-            .add_raw_template("collection.html", COLLECTION_TEMPLATE)
-            .map_err(|source| TemplateError::Load {
-                template_name: "collection.html",
-                source,
-            })?;
-        Ok(Templater { engine, title })
+        let timestamps = TimestampStyle::default();
+        engine.register_filter("formattime", FormatTimeFilter(timestamps.clone()));
+        for (template_name, template) in COLLECTION_TEMPLATES {
+            engine
+                .add_raw_template(template_name, template)
+                .map_err(|source| TemplateError::Load {
+                    template_name,
+                    source,
+                })?;
+        }
+        let mut templater = Templater {
+            engine,
+            title,
+            archives,
+            timestamps,
+            fingerprint: 0,
+        };
+        templater.refingerprint();
+        Ok(templater)
+    }
+
+    /// Display timestamps in rendered documents in the given style instead
+    /// of the default
+    pub(crate) fn with_timestamps(mut self, timestamps: TimestampStyle) -> Self {
+        self.engine
+            .register_filter("formattime", FormatTimeFilter(timestamps.clone()));
+        self.timestamps = timestamps;
+        self.refingerprint();
+        self
+    }
+
+    /// Recompute the templater's fingerprint from its configuration
+    fn refingerprint(&mut self) {
+        let mut hasher = DefaultHasher::new();
+        self.title.hash(&mut hasher);
+        self.archives.hash(&mut hasher);
+        self.timestamps.hash(&mut hasher);
+        self.fingerprint = hasher.finish();
+    }
+
+    /// Return a hash of the templater's configuration.  Two templaters with
+    /// the same fingerprint render identical documents for identical inputs.
+    pub(super) fn fingerprint(&self) -> u64 {
+        self.fingerprint
     }
 
     /// Render an HTML document containing a table listing the resources in
-    /// `entries`.  `pathparts` contains the individual components of the
-    /// request URL path.
+    /// `entries`, sorted in the order requested by `sort` (or by name if
+    /// `sort` is `None`).  `pathparts` contains the individual components of
+    /// the request URL path.  If `entries` is a single page of the
+    /// collection's resources, links to the neighboring pages should be
+    /// passed as `nav`.
+    /// If `entries` has been filtered by a [`MatchFilter`], that filter
+    /// should be passed as `filter` so that it is preserved in the links for
+    /// sorting the table.
     pub(super) fn render_collection(
         &self,
         entries: Vec<DavResource>,
         pathparts: Vec<Component>,
+        nav: Option<PageNav>,
+        sort: Option<SortRequest>,
+        filter: Option<&MatchFilter>,
     ) -> Result<String, TemplateError> {
-        let template_name = "collection.html";
-        let colctx = self.collection_context(entries, pathparts);
-        let context =
-            Context::from_serialize(colctx).map_err(|source| TemplateError::MakeContext {
-                template_name,
-                source,
-            })?;
-        self.engine
-            .render(template_name, &context)
-            .map_err(|source| TemplateError::Render {
-                template_name,
-                source,
-            })
+        let sort = sort.unwrap_or_default();
+        let mut rows = entries.into_iter().map(ColRow::from).collect::<Vec<_>>();
+        sort_rows(&mut rows, sort);
+        self.render_rows(&rows, pathparts, nav, Some(sort), filter)
     }
 
-    /// Construct the context for displaying the given `entries`.  `pathparts`
-    /// contains the individual components of the request URL path.
-    fn collection_context(
+    /// Render an HTML document containing a table listing the resources on
+    /// page `page` of `entries` after sorting in the order requested by
+    /// `sort` (or by name if `sort` is `None`), along with links to the
+    /// neighboring pages.  `pathparts` and `filter` are as for
+    /// [`Templater::render_collection()`].
+    pub(super) fn render_collection_page(
         &self,
         entries: Vec<DavResource>,
         pathparts: Vec<Component>,
-    ) -> CollectionContext {
+        page: PageRequest,
+        sort: Option<SortRequest>,
+        filter: Option<&MatchFilter>,
+    ) -> Result<String, TemplateError> {
         let mut rows = entries.into_iter().map(ColRow::from).collect::<Vec<_>>();
-        rows.sort_unstable();
-        if let Some((_, pp)) = pathparts.split_last() {
-            rows.insert(
-                0,
-                ColRow::parentdir(Href::from_path(&abs_dir_from_components(pp))),
-            );
+        sort_rows(&mut rows, sort.unwrap_or_default());
+        let start = page.offset().min(rows.len());
+        let end = start.saturating_add(page.per_page).min(rows.len());
+        let nav = PageNav::new(page, end < rows.len(), sort, filter);
+        self.render_rows(
+            &rows[start..end],
+            pathparts,
+            Some(nav),
+            Some(sort.unwrap_or_default()),
+            filter,
+        )
+    }
+
+    /// Render an HTML document containing a table with the given rows (after
+    /// the row for the parent collection, if any).  `sort` is the order in
+    /// which the rows are sorted, if any.  `filter` is the filter applied to
+    /// the rows, if any.
+    fn render_rows(
+        &self,
+        rows: &[ColRow],
+        pathparts: Vec<Component>,
+        nav: Option<PageNav>,
+        sort: Option<SortRequest>,
+        filter: Option<&MatchFilter>,
+    ) -> Result<String, TemplateError> {
+        let mut html = self.render_head(pathparts, nav.clone(), sort, filter)?;
+        for r in rows {
+            html.push_str(&self.render_row(r)?);
         }
+        html.push_str(&self.render_foot(nav)?);
+        Ok(html)
+    }
+
+    /// Return a [`futures_util::Stream`] that yields successive pieces of an
+    /// HTML document containing a table listing the resources yielded by
+    /// `entries`, for use as a streaming response body when listing
+    /// collections with very many children.  Unlike
+    /// [`Templater::render_collection()`], the rows of the table are output
+    /// in the order in which `entries` yields them.
+    ///
+    /// `pathparts` and `filter` are as for [`Templater::render_collection()`].
+    ///
+    /// If `entries` yields an error or a row fails to render, the error is
+    /// yielded and the stream ends.
+    pub(super) fn render_collection_stream<S, E>(
+        self: Arc<Self>,
+        entries: S,
+        pathparts: Vec<Component>,
+        filter: Option<&MatchFilter>,
+    ) -> Result<impl Stream<Item = Result<String, E>> + Send + 'static, TemplateError>
+    where
+        S: Stream<Item = Result<DavResource, E>> + Send + 'static,
+        E: From<TemplateError> + Send + 'static,
+    {
+        let head = self.render_head(pathparts, None, None, filter)?;
+        let foot = self.render_foot(None)?;
+        let rows = entries.map(move |r| r.and_then(|res| Ok(self.render_row(&ColRow::from(res))?)));
+        Ok(stream::once(ready(Ok(head)))
+            .chain(rows)
+            .chain(stream::once(ready(Ok(foot))))
+            .scan(false, |failed, r| {
+                // Stop after the first error
+                let item = (!*failed).then(|| {
+                    *failed = r.is_err();
+                    r
+                });
+                ready(item)
+            }))
+    }
+
+    /// Render the beginning of a collection view, up through the row for the
+    /// parent collection (if any).  `pathparts` contains the individual
+    /// components of the request URL path.  `nav` contains
+    /// the links to neighboring pages if the view is paginated.  `sort` is the
+    /// order in which the rows of the table are sorted, if any.  `filter` is
+    /// the filter applied to the rows, if any, which is preserved in the
+    /// links for sorting the table.
+    fn render_head(
+        &self,
+        pathparts: Vec<Component>,
+        nav: Option<PageNav>,
+        sort: Option<SortRequest>,
+        filter: Option<&MatchFilter>,
+    ) -> Result<String, TemplateError> {
+        let parent = pathparts
+            .split_last()
+            .map(|(_, pp)| ColRow::parentdir(Href::from_path(&abs_dir_from_components(pp))));
         let title_path = abs_dir_from_components(&pathparts);
         let title = format!("{} \u{2014} {}", self.title, title_path);
-        CollectionContext {
+        let headctx = HeadContext {
             title,
             breadcrumbs: self.make_breadcrumbs(pathparts),
-            rows,
+            sort: SortLinks::new(sort, nav.as_ref().map(|n| n.per_page), filter),
+            nav,
+        };
+        let mut html = self.render("collection-head.html", headctx)?;
+        if let Some(parent) = parent {
+            html.push_str(&self.render_row(&parent)?);
+        }
+        Ok(html)
+    }
+
+    /// Render a single row of the table in a collection view
+    fn render_row(&self, row: &ColRow) -> Result<String, TemplateError> {
+        self.render("collection-row.html", RowContext { r: row })
+    }
+
+    /// Render the end of a collection view, following the last row of the
+    /// table.  `nav` contains the links to neighboring pages if the view is
+    /// paginated.
+    fn render_foot(&self, nav: Option<PageNav>) -> Result<String, TemplateError> {
+        let footctx = FootContext {
+            nav,
             package_url: env!("CARGO_PKG_REPOSITORY"),
             package_version: env!("CARGO_PKG_VERSION"),
             package_commit: option_env!("GIT_COMMIT"),
-        }
+            archives: &self.archives,
+        };
+        self.render("collection-foot.html", footctx)
+    }
+
+    /// Render the template `template_name` with the given context
+    fn render<C: Serialize>(
+        &self,
+        template_name: &'static str,
+        ctx: C,
+    ) -> Result<String, TemplateError> {
+        let context =
+            Context::from_serialize(ctx).map_err(|source| TemplateError::MakeContext {
+                template_name,
+                source,
+            })?;
+        self.engine
+            .render(template_name, &context)
+            .map_err(|source| TemplateError::Render {
+                template_name,
+                source,
+            })
     }
 
     /// Create breadcrumbs for the given request URL path components
@@ -112,17 +317,36 @@ impl Templater {
     }
 }
 
-/// Context to provide to the `collection.html` template
+/// Context to provide to the `collection-head.html` template
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
-struct CollectionContext {
+struct HeadContext {
     /// Page title
     title: String,
 
     /// Breadcrumb links
     breadcrumbs: Vec<Link>,
 
-    /// Rows of the table
-    rows: Vec<ColRow>,
+    /// Links to neighboring pages, if the view is paginated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nav: Option<PageNav>,
+
+    /// Links for sorting the table by its sortable columns
+    sort: SortLinks,
+}
+
+/// Context to provide to the `collection-row.html` template
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct RowContext<'a> {
+    /// The row to render
+    r: &'a ColRow,
+}
+
+/// Context to provide to the `collection-foot.html` template
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct FootContext<'a> {
+    /// Links to neighboring pages, if the view is paginated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nav: Option<PageNav>,
 
     /// URL to link "dandidav" in the page's footer to
     package_url: &'static str,
@@ -133,6 +357,50 @@ struct CollectionContext {
     /// Current `dandidav` commit hash (if known)
     #[serde(skip_serializing_if = "Option::is_none")]
     package_commit: Option<&'static str>,
+
+    /// Labels for the Archive instances being served
+    archives: &'a [ArchiveLabel],
+}
+
+/// A label identifying an Archive instance being served, as displayed in the
+/// footer of HTML collection views
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct ArchiveLabel {
+    /// The human-readable name of the instance
+    name: String,
+
+    /// The version of the Archive software running on the instance, without
+    /// any leading "v"
+    version: String,
+
+    /// The URL of the instance's web interface, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<Href>,
+
+    /// A link to the collection under which the instance's Dandisets are
+    /// served
+    index: Href,
+}
+
+impl ArchiveLabel {
+    /// Construct a label for `instance` from the information returned by its
+    /// `/info/` endpoint.  If the endpoint did not report the instance's
+    /// name, the primary instance is labelled "DANDI Archive", and other
+    /// instances are labelled with their configured names.
+    pub(crate) fn new(instance: &Instance, info: &InstanceInfo) -> ArchiveLabel {
+        let name = match (info.name(), instance) {
+            (Some(name), _) => name.to_owned(),
+            (None, Instance::Primary) => String::from("DANDI Archive"),
+            (None, Instance::Named(name)) => name.to_string(),
+        };
+        let version = info.version.strip_prefix('v').unwrap_or(&info.version);
+        ArchiveLabel {
+            name,
+            version: version.to_owned(),
+            url: info.url().map(Href::from),
+            index: Href::from_path(&format!("/{}/", instance.index_name())),
+        }
+    }
 }
 
 /// A hyperlink to display in an HTML document
@@ -145,6 +413,138 @@ struct Link {
     href: Href,
 }
 
+/// Links to the pages before & after a page of a paginated collection view,
+/// provided to the `collection-nav.html` template
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(super) struct PageNav {
+    /// The number of the current page, counting from 1
+    page: usize,
+
+    /// Query string for the previous page, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prev: Option<String>,
+
+    /// Query string for the next page, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
+
+    /// The number of entries per page
+    #[serde(skip)]
+    per_page: usize,
+}
+
+impl PageNav {
+    /// Construct the links for the page requested by `req`.  `has_next`
+    /// indicates whether there are any entries after the requested page.
+    /// `sort` and `filter` are the sort order and filter requested by the
+    /// client, if any, which are preserved in the links.
+    pub(super) fn new(
+        req: PageRequest,
+        has_next: bool,
+        sort: Option<SortRequest>,
+        filter: Option<&MatchFilter>,
+    ) -> PageNav {
+        let query_for = |page| {
+            let mut query = req.query_for(page);
+            if let Some(sort) = sort {
+                query.push('&');
+                query.push_str(&sort.query());
+            }
+            if let Some(filter) = filter {
+                query.push('&');
+                query.push_str(&filter.query());
+            }
+            query
+        };
+        PageNav {
+            page: req.page,
+            prev: (req.page > 1).then(|| query_for(req.page - 1)),
+            next: has_next.then(|| query_for(req.page.saturating_add(1))),
+            per_page: req.per_page,
+        }
+    }
+}
+
+/// Links for sorting a collection view by each of its sortable columns,
+/// provided to the `collection-head.html` template
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct SortLinks {
+    /// Link for the "Name" column
+    name: SortLink,
+
+    /// Link for the "Size" column
+    size: SortLink,
+
+    /// Link for the "Modified" column
+    modified: SortLink,
+}
+
+impl SortLinks {
+    /// Construct the links for a view whose rows are sorted by `current` (or
+    /// in no particular order if `current` is `None`).  If the view is
+    /// paginated, `per_page` is the number of entries per page, which is
+    /// preserved in the links, as is `filter` (if any).
+    fn new(
+        current: Option<SortRequest>,
+        per_page: Option<usize>,
+        filter: Option<&MatchFilter>,
+    ) -> SortLinks {
+        let link = |key| SortLink::new(key, current, per_page, filter);
+        SortLinks {
+            name: link(SortKey::Name),
+            size: link(SortKey::Size),
+            modified: link(SortKey::Modified),
+        }
+    }
+}
+
+/// A link in a column header for sorting a collection view by that column
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct SortLink {
+    /// Query string for sorting by the column in ascending order, or in
+    /// descending order if the view is already sorted by the column in
+    /// ascending order.  Paginated views are sent back to the first page.
+    href: String,
+
+    /// The value for the column header's `aria-sort` attribute, if the view
+    /// is sorted by the column
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aria_sort: Option<&'static str>,
+}
+
+impl SortLink {
+    fn new(
+        key: SortKey,
+        current: Option<SortRequest>,
+        per_page: Option<usize>,
+        filter: Option<&MatchFilter>,
+    ) -> SortLink {
+        let current = current.filter(|sr| sr.key == key);
+        let req = SortRequest {
+            key,
+            descending: current.is_some_and(|sr| !sr.descending),
+        };
+        let mut href = match per_page {
+            Some(per_page) => format!("?{}&per_page={per_page}", req.query()),
+            None => format!("?{}", req.query()),
+        };
+        if let Some(filter) = filter {
+            href.push('&');
+            href.push_str(&filter.query());
+        }
+        SortLink {
+            href,
+            aria_sort: current.map(|sr| {
+                if sr.descending {
+                    "descending"
+                } else {
+                    "ascending"
+                }
+            }),
+        }
+    }
+}
+
 /// A row of a table listing the resources within a collection
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 struct ColRow {
@@ -164,6 +564,11 @@ struct ColRow {
     #[serde(skip_serializing_if = "Option::is_none")]
     size: Option<i64>,
 
+    /// The number of assets within the resource, if it is a directory in a
+    /// Dandiset version and the number is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asset_count: Option<u64>,
+
     /// The timestamp at which the resource was created
     #[serde(
         skip_serializing_if = "Option::is_none",
@@ -194,6 +599,7 @@ impl ColRow {
             is_dir: true,
             kind: ResourceKind::Parent,
             size: None,
+            asset_count: None,
             created: None,
             modified: None,
             metadata_url: None,
@@ -218,6 +624,7 @@ impl From<DavCollection> for ColRow {
             is_dir: true,
             kind: col.kind,
             size: col.size,
+            asset_count: col.asset_count,
             created: col.created,
             modified: col.modified,
             metadata_url: col.metadata_url.map(Into::into),
@@ -233,6 +640,7 @@ impl From<DavItem> for ColRow {
             is_dir: false,
             kind: item.kind,
             size: item.size,
+            asset_count: None,
             created: item.created,
             modified: item.modified,
             metadata_url: item.metadata_url.map(Into::into),
@@ -240,6 +648,32 @@ impl From<DavItem> for ColRow {
     }
 }
 
+/// Sort `rows` in the order requested by `sort`.  When sorting by size or
+/// modification time, rows without a value for the column always come last,
+/// and ties are broken by name.
+fn sort_rows(rows: &mut [ColRow], sort: SortRequest) {
+    fn cmp_present<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) if descending => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    match sort.key {
+        SortKey::Name if sort.descending => rows.sort_unstable_by(|a, b| b.cmp(a)),
+        SortKey::Name => rows.sort_unstable(),
+        SortKey::Size => rows.sort_unstable_by(|a, b| {
+            cmp_present(a.size, b.size, sort.descending).then_with(|| a.cmp(b))
+        }),
+        SortKey::Modified => rows.sort_unstable_by(|a, b| {
+            cmp_present(a.modified, b.modified, sort.descending).then_with(|| a.cmp(b))
+        }),
+    }
+}
+
 #[derive(Debug, Error)]
 pub(crate) enum TemplateError {
     /// Failed to load a template
@@ -273,7 +707,7 @@ fn maybe_timestamp<S: Serializer>(
     match ts.as_ref() {
         Some(ts) => {
             let s = ts
-                .to_offset(time::UtcOffset::UTC)
+                .to_offset(UtcOffset::UTC)
                 .format(&HTML_TIMESTAMP_FORMAT)
                 .expect("timestamp formatting should not fail");
             serializer.serialize_some(&s)
@@ -323,10 +757,96 @@ fn formatsize(size: i64) -> String {
     format_size_i(size, BINARY)
 }
 
+/// How to display timestamps in HTML views: the UTC offset to convert them
+/// to and the format to display them in
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct TimestampStyle {
+    /// The display format, or `None` for the default format
+    format: Option<OwnedFormatItem>,
+
+    /// The UTC offset to convert timestamps to before display
+    offset: UtcOffset,
+}
+
+impl TimestampStyle {
+    /// Create a new `TimestampStyle` that converts timestamps to `offset` and
+    /// displays them in `format`.  If `format` is `None`, timestamps are
+    /// displayed as, e.g., `2024-01-02 03:04:05Z` when `offset` is UTC and as
+    /// `2024-01-02 03:04:05+02:00` otherwise.
+    pub(crate) fn new(format: Option<OwnedFormatItem>, offset: UtcOffset) -> TimestampStyle {
+        TimestampStyle { format, offset }
+    }
+
+    /// Format `ts` in this style
+    fn format(&self, ts: OffsetDateTime) -> Result<String, time::error::Format> {
+        let ts = ts.to_offset(self.offset);
+        match self.format {
+            Some(ref fmt) => ts.format(fmt),
+            None if self.offset.is_utc() => ts.format(&HTML_TIMESTAMP_FORMAT),
+            None => ts.format(&HTML_OFFSET_TIMESTAMP_FORMAT),
+        }
+    }
+}
+
+impl Default for TimestampStyle {
+    fn default() -> TimestampStyle {
+        TimestampStyle::new(None, UtcOffset::UTC)
+    }
+}
+
+impl Hash for TimestampStyle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // `OwnedFormatItem` does not implement `Hash`, but its `Debug`
+        // representation fully describes it.
+        self.format
+            .as_ref()
+            .map(|fmt| format!("{fmt:?}"))
+            .hash(state);
+        self.offset.hash(state);
+    }
+}
+
+/// Parse a timestamp format description for `--html-timestamp-format` in the
+/// [syntax of the `time` crate][syntax]
+///
+/// [syntax]: https://time-rs.github.io/book/api/format-description.html
+pub(crate) fn parse_timestamp_format(s: &str) -> Result<OwnedFormatItem, InvalidFormatDescription> {
+    format_description::parse_owned::<2>(s)
+}
+
+/// Parse a UTC offset for `--html-utc-offset` of the form `+HH:MM` or
+/// `-HH:MM`; `Z` and `UTC` (case insensitive) are also accepted as synonyms
+/// for `+00:00`
+pub(crate) fn parse_utc_offset(s: &str) -> Result<UtcOffset, time::error::Parse> {
+    if s.eq_ignore_ascii_case("Z") || s.eq_ignore_ascii_case("UTC") {
+        Ok(UtcOffset::UTC)
+    } else {
+        UtcOffset::parse(s, &UTC_OFFSET_FORMAT)
+    }
+}
+
+/// A custom Tera filter for displaying timestamps, as passed to templates in
+/// [`HTML_TIMESTAMP_FORMAT`], in a configured [`TimestampStyle`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct FormatTimeFilter(TimestampStyle);
+
+impl Filter for FormatTimeFilter {
+    fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> Result<Value, Error> {
+        let ts = value
+            .as_str()
+            .and_then(|s| PrimitiveDateTime::parse(s, &HTML_TIMESTAMP_FORMAT).ok())
+            .ok_or_else(|| Error::msg("Input to formattime filter must be a timestamp"))?;
+        self.0
+            .format(ts.assume_utc())
+            .map(Value::from)
+            .map_err(|e| Error::chain("Failed to format timestamp", e))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dav::types::Redirect;
+    use crate::dav::types::{Redirect, ZarrVersion};
     use rstest::rstest;
 
     #[rstest]
@@ -344,23 +864,95 @@ mod tests {
         assert_eq!(formatsize(size), s);
     }
 
+    #[rstest]
+    #[case(None, "+00:00", "2021-01-01 01:23:45Z")]
+    #[case(None, "+02:00", "2021-01-01 03:23:45+02:00")]
+    #[case(None, "-05:30", "2020-12-31 19:53:45-05:30")]
+    #[case(
+        Some("[day]/[month]/[year] [hour]:[minute]"),
+        "+01:00",
+        "01/01/2021 02:23"
+    )]
+    #[case(
+        Some("[month repr:short] [day padding:none], [year] [hour repr:12]:[minute] [period]"),
+        "Z",
+        "Jan 1, 2021 01:23 AM"
+    )]
+    fn test_formattime(#[case] format: Option<&str>, #[case] offset: &str, #[case] s: &str) {
+        let style = TimestampStyle::new(
+            format.map(|f| parse_timestamp_format(f).unwrap()),
+            parse_utc_offset(offset).unwrap(),
+        );
+        let filter = FormatTimeFilter(style);
+        let value = filter
+            .filter(&Value::from("2021-01-01 01:23:45Z"), &HashMap::new())
+            .unwrap();
+        assert_eq!(value, Value::from(s));
+    }
+
+    #[test]
+    fn test_formattime_bad_input() {
+        let filter = FormatTimeFilter(TimestampStyle::default());
+        assert!(filter.filter(&Value::from(42), &HashMap::new()).is_err());
+        assert!(filter
+            .filter(&Value::from("2021-01-01T01:23:45Z"), &HashMap::new())
+            .is_err());
+    }
+
+    #[rstest]
+    #[case("Z", UtcOffset::UTC)]
+    #[case("utc", UtcOffset::UTC)]
+    #[case("+00:00", UtcOffset::UTC)]
+    #[case("+02:00", UtcOffset::from_hms(2, 0, 0).unwrap())]
+    #[case("-09:30", UtcOffset::from_hms(-9, -30, 0).unwrap())]
+    fn test_parse_utc_offset(#[case] s: &str, #[case] offset: UtcOffset) {
+        assert_eq!(parse_utc_offset(s).unwrap(), offset);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("02:00")]
+    #[case("+2")]
+    #[case("+0200")]
+    #[case("EST")]
+    fn test_parse_bad_utc_offset(#[case] s: &str) {
+        assert!(parse_utc_offset(s).is_err());
+    }
+
+    #[test]
+    fn timestamps_change_fingerprint() {
+        let t1 = Templater::new("dandidav".to_owned(), Vec::new()).unwrap();
+        let t2 = Templater::new("dandidav".to_owned(), Vec::new())
+            .unwrap()
+            .with_timestamps(TimestampStyle::default());
+        assert_eq!(t1.fingerprint(), t2.fingerprint());
+        let t3 = Templater::new("dandidav".to_owned(), Vec::new())
+            .unwrap()
+            .with_timestamps(TimestampStyle::new(
+                None,
+                UtcOffset::from_hms(2, 0, 0).unwrap(),
+            ));
+        assert_ne!(t1.fingerprint(), t3.fingerprint());
+    }
+
     mod render_collection {
         use super::*;
-        use crate::dav::{DavContent, DavResourceWithChildren};
+        use crate::dav::{DavContent, DavResourceWithChildren, Instance};
         use pretty_assertions::assert_eq;
         use std::borrow::Cow;
         use time::macros::datetime;
 
         #[test]
         fn basic() {
-            let templater = Templater::new("Dandidav Test".to_owned()).unwrap();
+            let templater = Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap();
             let entries = vec![
                 DavResource::Collection(DavCollection {
                     path: Some("foo/bar/baz/a.zarr/".parse().unwrap()),
                     created: Some(datetime!(2021-01-01 01:23:45 UTC)),
                     modified: Some(datetime!(2023-12-31 12:34:56 UTC)),
                     size: Some(1234567890),
-                    kind: ResourceKind::Zarr,
+                    asset_count: None,
+                    kind: ResourceKind::Zarr { version: None },
                     metadata_url: None,
                 }),
                 DavResource::Collection(DavCollection {
@@ -368,6 +960,7 @@ mod tests {
                     created: None,
                     modified: None,
                     size: None,
+                    asset_count: None,
                     kind: ResourceKind::Directory,
                     metadata_url: None,
                 }),
@@ -429,6 +1022,9 @@ mod tests {
                         "bar".parse().unwrap(),
                         "baz".parse().unwrap(),
                     ],
+                    None,
+                    None,
+                    None,
                 )
                 .unwrap();
             let commit_str = match option_env!("GIT_COMMIT") {
@@ -451,13 +1047,18 @@ mod tests {
 
         #[test]
         fn root() {
-            let templater = Templater::new("Dandidav Test".to_owned()).unwrap();
+            let templater = Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap();
             let DavResourceWithChildren::Collection { children, .. } =
-                DavResourceWithChildren::root()
+                DavResourceWithChildren::root([
+                    DavCollection::dandiset_index(&Instance::Primary),
+                    DavCollection::zarr_index(),
+                ])
             else {
                 panic!("DavResourceWithChildren::root() should be a Collection");
             };
-            let rendered = templater.render_collection(children, Vec::new()).unwrap();
+            let rendered = templater
+                .render_collection(children, Vec::new(), None, None, None)
+                .unwrap();
             let commit_str = match option_env!("GIT_COMMIT") {
                 Some(s) => Cow::from(format!(", commit {s}")),
                 None => Cow::from(""),
@@ -475,5 +1076,406 @@ mod tests {
             .replacen("{commit}", &commit_str, 1);
             assert_eq!(rendered, expected);
         }
+
+        #[test]
+        fn zarr() {
+            let templater = Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap();
+            let entries = vec![
+                DavResource::Collection(DavCollection {
+                    path: Some("dandisets/000027/draft/v2.zarr/".parse().unwrap()),
+                    created: None,
+                    modified: Some(datetime!(2024-02-14 22:13:35 UTC)),
+                    size: Some(1234),
+                    asset_count: None,
+                    kind: ResourceKind::Zarr {
+                        version: Some(ZarrVersion::V2),
+                    },
+                    metadata_url: None,
+                }),
+                DavResource::Collection(DavCollection {
+                    path: Some("dandisets/000027/draft/v3.zarr/".parse().unwrap()),
+                    created: None,
+                    modified: Some(datetime!(2024-02-14 22:13:35 UTC)),
+                    size: Some(5678),
+                    asset_count: None,
+                    kind: ResourceKind::Zarr {
+                        version: Some(ZarrVersion::V3),
+                    },
+                    metadata_url: None,
+                }),
+                DavResource::Collection(DavCollection {
+                    path: Some("dandisets/000027/draft/unknown.zarr/".parse().unwrap()),
+                    created: None,
+                    modified: Some(datetime!(2024-02-14 22:13:35 UTC)),
+                    size: Some(42),
+                    asset_count: None,
+                    kind: ResourceKind::Zarr { version: None },
+                    metadata_url: None,
+                }),
+            ];
+            let rendered = templater
+                .render_collection(
+                    entries,
+                    vec![
+                        "dandisets".parse().unwrap(),
+                        "000027".parse().unwrap(),
+                        "draft".parse().unwrap(),
+                    ],
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            let commit_str = match option_env!("GIT_COMMIT") {
+                Some(s) => Cow::from(format!(", commit {s}")),
+                None => Cow::from(""),
+            };
+            let expected = include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/testdata/render-collection/zarr.html"
+            ))
+            .replacen(
+                "{package_url}",
+                &env!("CARGO_PKG_REPOSITORY").replace('/', "&#x2F;"),
+                1,
+            )
+            .replacen("{version}", env!("CARGO_PKG_VERSION"), 1)
+            .replacen("{commit}", &commit_str, 1);
+            assert_eq!(rendered, expected);
+        }
+
+        fn info(version: &str, name: Option<&str>, url: Option<&str>) -> InstanceInfo {
+            InstanceInfo {
+                version: version.to_owned(),
+                schema_version: None,
+                instance_config: name.map(|name| crate::dandi::InstanceConfig {
+                    instance_name: name.to_owned(),
+                    instance_url: url.map(|u| u.parse().unwrap()),
+                }),
+            }
+        }
+
+        #[test]
+        fn archive_label() {
+            let staging = Instance::Named("staging".parse().unwrap());
+            let templater = Templater::new(
+                "Dandidav Test".to_owned(),
+                vec![ArchiveLabel::new(
+                    &Instance::Primary,
+                    &info("0.11.0", Some("DANDI"), Some("https://dandiarchive.org")),
+                )],
+            )
+            .unwrap();
+            let html = templater
+                .render_collection(Vec::new(), Vec::new(), None, None, None)
+                .unwrap();
+            assert!(html.contains(
+                r#" &middot; serving <a href="https:&#x2F;&#x2F;dandiarchive.org&#x2F;">DANDI</a> v0.11.0
+    </footer>"#
+            ));
+            let templater = Templater::new(
+                "Dandidav Test".to_owned(),
+                vec![
+                    ArchiveLabel::new(&Instance::Primary, &info("v0.11.0", None, None)),
+                    ArchiveLabel::new(&staging, &info("0.12.0", Some("DANDI-STAGING"), None)),
+                ],
+            )
+            .unwrap();
+            let html = templater
+                .render_collection(Vec::new(), Vec::new(), None, None, None)
+                .unwrap();
+            assert!(html.contains(concat!(
+                r#" &middot; serving DANDI Archive v0.11.0 (<a href="&#x2F;dandisets&#x2F;">&#x2F;dandisets&#x2F;</a>),"#,
+                r#" DANDI-STAGING v0.12.0 (<a href="&#x2F;staging-dandisets&#x2F;">&#x2F;staging-dandisets&#x2F;</a>)"#,
+                "\n    </footer>",
+            )));
+        }
+    }
+
+    #[rstest]
+    #[case(Some(3), Some("(3 assets)"))]
+    #[case(Some(1), Some("(1 asset)"))]
+    #[case(None, None)]
+    fn render_asset_count(#[case] asset_count: Option<u64>, #[case] label: Option<&str>) {
+        let templater = Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap();
+        let entries = vec![DavResource::Collection(DavCollection {
+            path: Some("dandisets/000001/draft/sub-01/".parse().unwrap()),
+            created: None,
+            modified: None,
+            size: Some(1024),
+            asset_count,
+            kind: ResourceKind::Directory,
+            metadata_url: None,
+        })];
+        let rendered = templater
+            .render_collection(
+                entries,
+                vec![
+                    "dandisets".parse().unwrap(),
+                    "000001".parse().unwrap(),
+                    "draft".parse().unwrap(),
+                ],
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(rendered.contains(r#"<td class="size" data-sort-value="1024">1 KiB</td>"#));
+        match label {
+            Some(label) => assert!(rendered.contains(&format!(
+                r#"Directory <span class="asset-count">{label}</span>"#
+            ))),
+            None => assert!(!rendered.contains("asset-count")),
+        }
+    }
+
+    mod render_collection_stream {
+        use super::*;
+        use crate::dav::{DavContent, DavError};
+        use futures_util::TryStreamExt;
+        use pretty_assertions::assert_eq;
+        use std::time::Duration;
+
+        fn entries() -> Vec<DavResource> {
+            vec![
+                DavResource::Collection(DavCollection {
+                    path: Some("foo/bar/code/".parse().unwrap()),
+                    created: None,
+                    modified: None,
+                    size: None,
+                    asset_count: None,
+                    kind: ResourceKind::Directory,
+                    metadata_url: None,
+                }),
+                DavResource::Item(DavItem {
+                    path: "foo/bar/readme.txt".parse().unwrap(),
+                    created: None,
+                    modified: None,
+                    content_type: "text/plain".into(),
+                    size: Some(42),
+                    etag: None,
+                    kind: ResourceKind::Blob,
+                    content: DavContent::Redirect(Redirect::Direct(
+                        "https://dandiarchive-test.s3.amazonaws.com/blobs/readme.txt"
+                            .parse()
+                            .unwrap(),
+                    )),
+                    metadata_url: None,
+                }),
+            ]
+        }
+
+        fn pathparts() -> Vec<Component> {
+            vec!["foo".parse().unwrap(), "bar".parse().unwrap()]
+        }
+
+        #[tokio::test]
+        async fn same_as_render_collection() {
+            let templater =
+                Arc::new(Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap());
+            let rows = entries().into_iter().map(ColRow::from).collect::<Vec<_>>();
+            let expected = templater
+                .render_rows(&rows, pathparts(), None, None, None)
+                .unwrap();
+            let rendered = Arc::clone(&templater)
+                .render_collection_stream(
+                    stream::iter(entries().into_iter().map(Ok::<_, DavError>)),
+                    pathparts(),
+                    None,
+                )
+                .unwrap()
+                .try_collect::<String>()
+                .await
+                .unwrap();
+            assert_eq!(rendered, expected);
+        }
+
+        #[tokio::test]
+        async fn arrival_order() {
+            let templater =
+                Arc::new(Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap());
+            let chunks = templater
+                .render_collection_stream(
+                    stream::iter(entries().into_iter().rev().map(Ok::<_, DavError>)),
+                    pathparts(),
+                    None,
+                )
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(chunks.len(), 4);
+            assert!(chunks[0].contains("<title>"));
+            assert!(chunks[0].contains("../"));
+            assert!(chunks[1].contains("readme.txt"));
+            assert!(chunks[2].contains("code/"));
+            assert!(chunks[3].ends_with("</html>\n"));
+        }
+
+        #[tokio::test]
+        async fn error_ends_stream() {
+            let templater =
+                Arc::new(Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap());
+            let mut items = entries().into_iter().map(Ok).collect::<Vec<_>>();
+            items.insert(
+                1,
+                Err(DavError::Timeout {
+                    timeout: Duration::from_secs(1),
+                }),
+            );
+            let results = templater
+                .render_collection_stream(stream::iter(items), pathparts(), None)
+                .unwrap()
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(results.len(), 3);
+            assert!(results[0].is_ok());
+            assert!(results[1].is_ok());
+            assert!(results[2].is_err());
+        }
+    }
+
+    mod render_collection_page {
+        use super::*;
+        use crate::dav::{DavResourceWithChildren, Instance};
+
+        fn render(page: usize) -> String {
+            let templater = Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap();
+            let DavResourceWithChildren::Collection { children, .. } =
+                DavResourceWithChildren::root([
+                    DavCollection::dandiset_index(&Instance::Primary),
+                    DavCollection::zarr_index(),
+                ])
+            else {
+                panic!("DavResourceWithChildren::root() should be a Collection");
+            };
+            templater
+                .render_collection_page(
+                    children,
+                    Vec::new(),
+                    PageRequest { page, per_page: 1 },
+                    None,
+                    None,
+                )
+                .unwrap()
+        }
+
+        #[test]
+        fn first() {
+            let html = render(1);
+            assert!(html.contains(r#"href="&#x2F;dandisets&#x2F;""#));
+            assert!(!html.contains(r#"href="&#x2F;zarrs&#x2F;""#));
+            assert!(html.contains("<span class=\"page\">Page 1</span>"));
+            assert!(!html.contains("Previous"));
+            assert!(html.contains(r#"<a href="?page=2&amp;per_page=1">Next &#x2192;</a>"#));
+        }
+
+        #[test]
+        fn last() {
+            let html = render(2);
+            assert!(!html.contains(r#"href="&#x2F;dandisets&#x2F;""#));
+            assert!(html.contains(r#"href="&#x2F;zarrs&#x2F;""#));
+            assert!(html.contains(r#"<a href="?page=1&amp;per_page=1">&#x2190; Previous</a>"#));
+            assert!(!html.contains("Next"));
+        }
+
+        #[test]
+        fn past_end() {
+            let html = render(5);
+            assert!(!html.contains(r#"href="&#x2F;dandisets&#x2F;""#));
+            assert!(!html.contains(r#"href="&#x2F;zarrs&#x2F;""#));
+            assert!(html.contains(r#"<a href="?page=4&amp;per_page=1">&#x2190; Previous</a>"#));
+            assert!(!html.contains("Next"));
+        }
+    }
+
+    mod sort_rows {
+        use super::*;
+        use time::macros::datetime;
+
+        fn rows() -> Vec<ColRow> {
+            let row = |name: &str, size, modified| ColRow {
+                name: name.to_owned(),
+                href: Href::from_path(&format!("/{name}")),
+                is_dir: false,
+                kind: ResourceKind::Blob,
+                size,
+                asset_count: None,
+                created: None,
+                modified,
+                metadata_url: None,
+            };
+            vec![
+                row("b.txt", Some(10), Some(datetime!(2024-01-01 00:00:00 UTC))),
+                row("a.txt", Some(300), None),
+                row("d.txt", None, Some(datetime!(2025-06-01 00:00:00 UTC))),
+                row("c.txt", Some(10), Some(datetime!(2023-03-01 00:00:00 UTC))),
+            ]
+        }
+
+        #[rstest]
+        #[case(SortKey::Name, false, ["a.txt", "b.txt", "c.txt", "d.txt"])]
+        #[case(SortKey::Name, true, ["d.txt", "c.txt", "b.txt", "a.txt"])]
+        #[case(SortKey::Size, false, ["b.txt", "c.txt", "a.txt", "d.txt"])]
+        #[case(SortKey::Size, true, ["a.txt", "b.txt", "c.txt", "d.txt"])]
+        #[case(SortKey::Modified, false, ["c.txt", "b.txt", "d.txt", "a.txt"])]
+        #[case(SortKey::Modified, true, ["d.txt", "b.txt", "c.txt", "a.txt"])]
+        fn test_sort_rows(
+            #[case] key: SortKey,
+            #[case] descending: bool,
+            #[case] names: [&str; 4],
+        ) {
+            let mut rows = rows();
+            sort_rows(&mut rows, SortRequest { key, descending });
+            let sorted = rows.iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+            assert_eq!(sorted, names);
+        }
+
+        #[test]
+        fn sorted_page() {
+            let templater = Templater::new("Dandidav Test".to_owned(), Vec::new()).unwrap();
+            let entries = rows()
+                .into_iter()
+                .map(|r| {
+                    DavResource::Item(DavItem {
+                        path: r.name.parse().unwrap(),
+                        created: None,
+                        modified: r.modified,
+                        content_type: "text/plain".into(),
+                        size: r.size,
+                        etag: None,
+                        kind: ResourceKind::Blob,
+                        content: crate::dav::DavContent::Blob(Vec::new()),
+                        metadata_url: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let html = templater
+                .render_collection_page(
+                    entries,
+                    Vec::new(),
+                    PageRequest {
+                        page: 1,
+                        per_page: 2,
+                    },
+                    Some(SortRequest {
+                        key: SortKey::Size,
+                        descending: true,
+                    }),
+                    None,
+                )
+                .unwrap();
+            let a = html.find(r#"data-sort-value="a.txt""#).unwrap();
+            let b = html.find(r#"data-sort-value="b.txt""#).unwrap();
+            assert!(a < b);
+            assert!(!html.contains("c.txt"));
+            assert!(html.contains(
+                r#"<th data-sort-type="number" aria-sort="descending"><a href="?sort=size&amp;per_page=2">Size</a></th>"#
+            ));
+            assert!(html.contains(r#"<th><a href="?sort=name&amp;per_page=2">Name</a></th>"#));
+            assert!(html.contains(
+                r#"<a href="?page=2&amp;per_page=2&amp;sort=size&amp;order=desc">Next &#x2192;</a>"#
+            ));
+        }
     }
 }
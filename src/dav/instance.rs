@@ -0,0 +1,197 @@
+//! Identifying the DANDI Archive instances served by `dandidav`
+use crate::httputil::{HttpUrl, ParseHttpUrlError};
+use smartstring::alias::CompactString;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The suffix of the names of the top-level collections under which the
+/// Dandisets of each Archive instance are served
+const INDEX_SUFFIX: &str = "dandisets";
+
+/// A DANDI Archive instance whose Dandisets are served by `dandidav`
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Instance {
+    /// The instance given by `--api-url`, whose Dandisets are served under
+    /// `/dandisets/`
+    #[default]
+    Primary,
+
+    /// An additional instance given by `--instance`, whose Dandisets are
+    /// served under `/{name}-dandisets/`
+    Named(InstanceName),
+}
+
+impl Instance {
+    /// Return the name of the top-level collection under which the
+    /// instance's Dandisets are served
+    pub(crate) fn index_name(&self) -> String {
+        match self {
+            Instance::Primary => INDEX_SUFFIX.to_owned(),
+            Instance::Named(name) => format!("{name}-{INDEX_SUFFIX}"),
+        }
+    }
+
+    /// Parse the name of a top-level collection as returned by
+    /// [`Instance::index_name()`].  Matching is case-insensitive.
+    ///
+    /// Returns `None` if `s` is not of the form `dandisets` or
+    /// `{name}-dandisets` for a valid instance name.  Note that the returned
+    /// instance need not actually be configured.
+    pub(super) fn from_index_name(s: &str) -> Option<Instance> {
+        let s = s.to_ascii_lowercase();
+        if s == INDEX_SUFFIX {
+            Some(Instance::Primary)
+        } else {
+            s.strip_suffix(INDEX_SUFFIX)?
+                .strip_suffix('-')?
+                .parse::<InstanceName>()
+                .ok()
+                .map(Instance::Named)
+        }
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instance::Primary => write!(f, "primary"),
+            Instance::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The name of an additional Archive instance, consisting of one or more
+/// hyphen-separated runs of lowercase ASCII letters & digits
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct InstanceName(CompactString);
+
+fn validate(s: &str) -> Result<(), ParseInstanceNameError> {
+    if !s.is_empty()
+        && s.split('-').all(|run| {
+            !run.is_empty()
+                && run
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+    {
+        Ok(())
+    } else {
+        Err(ParseInstanceNameError)
+    }
+}
+
+validstr!(
+    InstanceName,
+    ParseInstanceNameError,
+    validate,
+    "an Archive instance name"
+);
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("instance names must be hyphen-separated runs of lowercase ASCII letters & digits")]
+pub(crate) struct ParseInstanceNameError;
+
+/// An additional Archive instance to serve, as given on the command line in
+/// the form `NAME=URL`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct InstanceSpec {
+    /// The name of the instance
+    pub(crate) name: InstanceName,
+
+    /// The instance's API URL
+    pub(crate) api_url: HttpUrl,
+}
+
+impl FromStr for InstanceSpec {
+    type Err = ParseInstanceSpecError;
+
+    fn from_str(s: &str) -> Result<InstanceSpec, ParseInstanceSpecError> {
+        let (name, url) = s.split_once('=').ok_or(ParseInstanceSpecError::NoEquals)?;
+        Ok(InstanceSpec {
+            name: name.parse()?,
+            api_url: url.parse()?,
+        })
+    }
+}
+
+/// Error returned when parsing an invalid [`InstanceSpec`] string
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub(crate) enum ParseInstanceSpecError {
+    #[error("expected an argument of the form NAME=URL")]
+    NoEquals,
+    #[error("invalid instance name")]
+    Name(#[from] ParseInstanceNameError),
+    #[error("invalid API URL")]
+    Url(#[from] ParseHttpUrlError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("dandisets", Some(Instance::Primary))]
+    #[case("DANDISETS", Some(Instance::Primary))]
+    #[case("staging-dandisets", Some(Instance::Named("staging".parse().unwrap())))]
+    #[case("Staging-Dandisets", Some(Instance::Named("staging".parse().unwrap())))]
+    #[case("dev-2-dandisets", Some(Instance::Named("dev-2".parse().unwrap())))]
+    #[case("-dandisets", None)]
+    #[case("staging--dandisets", None)]
+    #[case("stagingdandisets", None)]
+    #[case("staging_dandisets", None)]
+    #[case("zarrs", None)]
+    fn test_from_index_name(#[case] s: &str, #[case] instance: Option<Instance>) {
+        assert_eq!(Instance::from_index_name(s), instance);
+    }
+
+    #[rstest]
+    #[case(Instance::Primary, "dandisets")]
+    #[case(Instance::Named("staging".parse().unwrap()), "staging-dandisets")]
+    fn test_index_name(#[case] instance: Instance, #[case] name: &str) {
+        assert_eq!(instance.index_name(), name);
+        assert_eq!(Instance::from_index_name(name), Some(instance));
+    }
+
+    #[rstest]
+    #[case("staging")]
+    #[case("sandbox-2")]
+    #[case("a-b-c")]
+    fn test_good_instance_name(#[case] s: &str) {
+        assert!(s.parse::<InstanceName>().is_ok());
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("-staging")]
+    #[case("staging-")]
+    #[case("a--b")]
+    #[case("Staging")]
+    #[case("stag ing")]
+    #[case("stäging")]
+    fn test_bad_instance_name(#[case] s: &str) {
+        assert!(s.parse::<InstanceName>().is_err());
+    }
+
+    #[test]
+    fn parse_instance_spec() {
+        let spec = "staging=https://api-staging.dandiarchive.org/api"
+            .parse::<InstanceSpec>()
+            .unwrap();
+        assert_eq!(spec.name, "staging");
+        assert_eq!(
+            spec.api_url.as_str(),
+            "https://api-staging.dandiarchive.org/api"
+        );
+    }
+
+    #[rstest]
+    #[case("https://api-staging.dandiarchive.org/api")]
+    #[case("Staging=https://api-staging.dandiarchive.org/api")]
+    #[case("staging=api-staging.dandiarchive.org")]
+    fn parse_bad_instance_spec(#[case] s: &str) {
+        assert_matches!(s.parse::<InstanceSpec>(), Err(_));
+    }
+}
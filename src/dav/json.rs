@@ -0,0 +1,532 @@
+//! Machine-readable JSON listings of collections
+use super::types::*;
+use super::util::{format_creationdate, Href};
+use crate::dandi::{Asset, AssetDigests, DandisetId, DandisetVersion, VersionId};
+use crate::paths::{PureDirPath, PurePath};
+use serde::{ser::Serializer, Serialize};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+/// A JSON listing of a collection's entries, returned in response to `GET`
+/// requests for collections that ask for JSON instead of HTML
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(super) struct JsonListing {
+    /// The absolute URL path at which the collection is served
+    url: Href,
+
+    /// Type of the collection
+    kind: ResourceKind,
+
+    /// If the collection is a Zarr, its Zarr format version, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zarr_version: Option<ZarrVersion>,
+
+    /// The collection's immediate children
+    entries: Vec<JsonEntry>,
+}
+
+impl JsonListing {
+    /// Construct a `JsonListing` for the collection `col` with the given
+    /// `children`.
+    ///
+    /// `prefer_s3_redirects` determines which URL is reported as the download
+    /// URL for resources that can be downloaded from either S3 or the Archive.
+    pub(super) fn new(
+        col: DavCollection,
+        children: Vec<DavResource>,
+        prefer_s3_redirects: bool,
+    ) -> JsonListing {
+        let mut entries = children
+            .into_iter()
+            .map(|r| JsonEntry::new(r, prefer_s3_redirects))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        JsonListing {
+            url: col.href(),
+            kind: col.kind,
+            zarr_version: col.zarr_version(),
+            entries,
+        }
+    }
+}
+
+/// A nested JSON tree of a resource and its descendants down to a requested
+/// depth, returned in response to `GET` requests with a `tree=json` query
+/// parameter
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(super) struct JsonTree {
+    /// The resource itself
+    #[serde(flatten)]
+    entry: JsonEntry,
+
+    /// The resource's immediate children, sorted by name.  This is only
+    /// present for collections less than the requested depth beneath the
+    /// root of the tree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<JsonTree>>,
+}
+
+impl JsonTree {
+    /// Construct a `JsonTree` rooted at `root` from `descendants`, the
+    /// resources (in any order) at most `depth` levels beneath it, as yielded
+    /// by [`DandiDav::walk()`][super::DandiDav::walk].
+    ///
+    /// `prefer_s3_redirects` is as for [`JsonListing::new()`].
+    pub(super) fn new<I>(
+        root: DavResource,
+        descendants: I,
+        depth: u32,
+        prefer_s3_redirects: bool,
+    ) -> JsonTree
+    where
+        I: IntoIterator<Item = DavResource>,
+    {
+        let mut by_parent = HashMap::<String, Vec<JsonEntry>>::new();
+        for res in descendants {
+            let entry = JsonEntry::new(res, prefer_s3_redirects);
+            if let Some(parent) = parent_href(entry.url.as_ref()) {
+                by_parent.entry(parent.to_owned()).or_default().push(entry);
+            }
+        }
+        JsonTree::build(
+            JsonEntry::new(root, prefer_s3_redirects),
+            depth,
+            &mut by_parent,
+        )
+    }
+
+    /// Construct a `JsonTree` for `entry`, taking its descendants down to
+    /// `depth` levels beneath it from `by_parent`, a map from collection
+    /// hrefs to their children
+    fn build(
+        entry: JsonEntry,
+        depth: u32,
+        by_parent: &mut HashMap<String, Vec<JsonEntry>>,
+    ) -> JsonTree {
+        let children = (entry.is_collection && depth > 0).then(|| {
+            let mut children = by_parent.remove(entry.url.as_ref()).unwrap_or_default();
+            children.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            children
+                .into_iter()
+                .map(|e| JsonTree::build(e, depth - 1, by_parent))
+                .collect()
+        });
+        JsonTree { entry, children }
+    }
+}
+
+/// Return the href of the collection containing the resource with href
+/// `href`, or `None` if `href` is the root
+fn parent_href(href: &str) -> Option<&str> {
+    let i = href.trim_end_matches('/').rfind('/')?;
+    Some(&href[..=i])
+}
+
+/// An estimate of the total size of a Dandiset version's assets, returned in
+/// response to `GET` requests for versions with an `estimate=size` query
+/// parameter
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(super) struct JsonSizeEstimate {
+    /// The absolute URL path at which the version is served
+    url: Href,
+
+    /// The ID of the Dandiset
+    dandiset_id: String,
+
+    /// The ID of the version.  For `latest/`, this is the ID of the version
+    /// it resolved to.
+    version: VersionId,
+
+    /// The total size in bytes of the version's assets, as reported by the
+    /// Archive.  This includes the total size of each Zarr's entries.
+    size: i64,
+
+    /// The number of assets in the version, if reported by the Archive
+    asset_count: Option<u64>,
+}
+
+impl JsonSizeEstimate {
+    /// Construct a `JsonSizeEstimate` for the version `v` of the Dandiset
+    /// `dandiset_id`, served at `path`
+    pub(super) fn new(
+        path: &PureDirPath,
+        dandiset_id: &DandisetId,
+        v: DandisetVersion,
+    ) -> JsonSizeEstimate {
+        JsonSizeEstimate {
+            url: Href::from_path(&format!("/{path}")),
+            dandiset_id: dandiset_id.to_string(),
+            version: v.version,
+            size: v.size,
+            asset_count: v.asset_count,
+        }
+    }
+}
+
+/// A description of a single asset in a Dandiset version, serialized as one
+/// line of the version's `assets.jsonl` file
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(super) struct JsonAssetRecord<'a> {
+    /// The asset's path within the version
+    path: &'a PurePath,
+
+    /// The size of the asset in bytes
+    size: i64,
+
+    /// The checksums of the asset's content, keyed by digest type
+    checksums: &'a AssetDigests,
+
+    /// A URL from which the asset's content can be downloaded.  For Zarrs,
+    /// this is the S3 URL of the Zarr's entries, if known.
+    download_url: Option<Href>,
+}
+
+impl<'a> JsonAssetRecord<'a> {
+    /// Construct a `JsonAssetRecord` for `asset`.  If `prefer_s3_redirects`
+    /// is true, blob assets' S3 URLs are used as their download URLs in
+    /// preference to Archive download URLs.
+    pub(super) fn new(asset: &'a Asset, prefer_s3_redirects: bool) -> JsonAssetRecord<'a> {
+        let download_url = match asset {
+            Asset::Blob(blob) if prefer_s3_redirects => {
+                blob.s3_url().or_else(|| blob.archive_url())
+            }
+            Asset::Blob(blob) => blob.archive_url().or_else(|| blob.s3_url()),
+            Asset::Zarr(zarr) => zarr.s3_url(),
+        };
+        JsonAssetRecord {
+            path: asset.path(),
+            size: asset.size(),
+            checksums: asset.digests(),
+            download_url: download_url.map(Into::into),
+        }
+    }
+
+    /// Serialize the record as a line of JSON, including the terminating
+    /// newline
+    pub(super) fn to_line(&self) -> String {
+        let mut line =
+            serde_json::to_string(self).expect("serializing a JsonAssetRecord should not fail");
+        line.push('\n');
+        line
+    }
+}
+
+/// An entry in a [`JsonListing`]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct JsonEntry {
+    /// Resource basename
+    name: String,
+
+    /// Type of resource
+    kind: ResourceKind,
+
+    /// `true` iff the resource is a collection
+    is_collection: bool,
+
+    /// The size of the resource
+    size: Option<i64>,
+
+    /// The timestamp at which the resource was created, in RFC 3339 format
+    #[serde(serialize_with = "maybe_rfc3339")]
+    created: Option<OffsetDateTime>,
+
+    /// The timestamp at which the resource was last modified, in RFC 3339
+    /// format
+    #[serde(serialize_with = "maybe_rfc3339")]
+    modified: Option<OffsetDateTime>,
+
+    /// The absolute URL path at which the resource is served by `dandidav`
+    url: Href,
+
+    /// A URL from which the resource's content can be downloaded.  This is
+    /// `None` for collections and for resources without a known download
+    /// URL.
+    download_url: Option<Href>,
+
+    /// A URL for retrieving the resource's associated metadata (if any) from
+    /// the Archive instance
+    metadata_url: Option<Href>,
+}
+
+impl JsonEntry {
+    /// Construct a `JsonEntry` for the given resource
+    fn new(res: DavResource, prefer_s3_redirects: bool) -> JsonEntry {
+        match res {
+            DavResource::Collection(col) => JsonEntry {
+                name: col.name().unwrap_or("/").to_owned(),
+                kind: col.kind,
+                is_collection: true,
+                size: col.size,
+                created: col.created,
+                modified: col.modified,
+                url: col.href(),
+                download_url: None,
+                metadata_url: col.metadata_url.map(Into::into),
+            },
+            DavResource::Item(item) => {
+                let url = item.href();
+                let download_url = match item.content {
+                    DavContent::Blob(_) | DavContent::AssetManifest | DavContent::LocalFile(_) => {
+                        Some(url.clone())
+                    }
+                    DavContent::Redirect(ref redir) => {
+                        Some(redir.get_url(prefer_s3_redirects).into())
+                    }
+                    DavContent::Missing => None,
+                };
+                JsonEntry {
+                    name: item.name().to_owned(),
+                    kind: item.kind,
+                    is_collection: false,
+                    size: item.size,
+                    created: item.created,
+                    modified: item.modified,
+                    url,
+                    download_url,
+                    metadata_url: item.metadata_url.map(Into::into),
+                }
+            }
+        }
+    }
+}
+
+/// Serialize an optional timestamp in RFC 3339 format
+fn maybe_rfc3339<S: Serializer>(
+    ts: &Option<OffsetDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match ts {
+        Some(ts) => serializer.serialize_some(&format_creationdate(*ts)),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{DEFAULT_CONTENT_TYPE, YAML_CONTENT_TYPE};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    #[test]
+    fn listing() {
+        let col = DavCollection {
+            path: Some("dandisets/000001/draft/".parse().unwrap()),
+            created: Some(datetime!(2024-01-01 12:00:00 UTC)),
+            modified: Some(datetime!(2024-01-02 12:00:00 UTC)),
+            size: Some(42),
+            asset_count: None,
+            kind: ResourceKind::Version,
+            metadata_url: None,
+        };
+        let children = vec![
+            DavResource::Item(DavItem {
+                path: "dandisets/000001/draft/dandiset.yaml".parse().unwrap(),
+                created: None,
+                modified: None,
+                content_type: YAML_CONTENT_TYPE.to_owned(),
+                size: Some(10),
+                etag: None,
+                kind: ResourceKind::VersionMetadata,
+                content: DavContent::Blob(b"name: foo\n".to_vec()),
+                metadata_url: None,
+            }),
+            DavResource::Collection(DavCollection {
+                path: Some("dandisets/000001/draft/sub-01/".parse().unwrap()),
+                created: None,
+                modified: None,
+                size: None,
+                asset_count: None,
+                kind: ResourceKind::Directory,
+                metadata_url: None,
+            }),
+            DavResource::Item(DavItem {
+                path: "dandisets/000001/draft/data file.nwb".parse().unwrap(),
+                created: Some(datetime!(2024-01-01 13:00:00 UTC)),
+                modified: Some(datetime!(2024-01-01 14:00:00 -05:00)),
+                content_type: DEFAULT_CONTENT_TYPE.to_owned(),
+                size: Some(32),
+                etag: None,
+                kind: ResourceKind::Blob,
+                content: DavContent::Redirect(Redirect::Alt {
+                    s3: "https://bucket.s3.amazonaws.com/blobs/abc".parse().unwrap(),
+                    archive: "https://api.example.com/assets/1234/download/"
+                        .parse()
+                        .unwrap(),
+                }),
+                metadata_url: Some(
+                    "https://api.example.com/dandisets/000001/versions/draft/assets/1234/"
+                        .parse()
+                        .unwrap(),
+                ),
+            }),
+        ];
+        let listing = JsonListing::new(col, children, false);
+        assert_eq!(
+            serde_json::to_value(listing).unwrap(),
+            json!({
+                "url": "/dandisets/000001/draft/",
+                "kind": "Dandiset version",
+                "entries": [
+                    {
+                        "name": "dandiset.yaml",
+                        "kind": "Version metadata",
+                        "is_collection": false,
+                        "size": 10,
+                        "created": null,
+                        "modified": null,
+                        "url": "/dandisets/000001/draft/dandiset.yaml",
+                        "download_url": "/dandisets/000001/draft/dandiset.yaml",
+                        "metadata_url": null,
+                    },
+                    {
+                        "name": "data file.nwb",
+                        "kind": "Blob asset",
+                        "is_collection": false,
+                        "size": 32,
+                        "created": "2024-01-01T13:00:00Z",
+                        "modified": "2024-01-01T14:00:00-05:00",
+                        "url": "/dandisets/000001/draft/data%20file.nwb",
+                        "download_url": "https://api.example.com/assets/1234/download/",
+                        "metadata_url": "https://api.example.com/dandisets/000001/versions/draft/assets/1234/",
+                    },
+                    {
+                        "name": "sub-01",
+                        "kind": "Directory",
+                        "is_collection": true,
+                        "size": null,
+                        "created": null,
+                        "modified": null,
+                        "url": "/dandisets/000001/draft/sub-01/",
+                        "download_url": null,
+                        "metadata_url": null,
+                    },
+                ],
+            })
+        );
+    }
+
+    fn folder(path: &str, kind: ResourceKind) -> DavResource {
+        DavResource::Collection(DavCollection {
+            path: Some(path.parse().unwrap()),
+            created: None,
+            modified: None,
+            size: None,
+            asset_count: None,
+            kind,
+            metadata_url: None,
+        })
+    }
+
+    fn blob(path: &str) -> DavResource {
+        DavResource::Item(DavItem {
+            path: path.parse().unwrap(),
+            created: None,
+            modified: None,
+            content_type: DEFAULT_CONTENT_TYPE.to_owned(),
+            size: Some(1),
+            etag: None,
+            kind: ResourceKind::Blob,
+            content: DavContent::Missing,
+            metadata_url: None,
+        })
+    }
+
+    #[test]
+    fn tree() {
+        let root = folder("dandisets/000001/draft/", ResourceKind::Version);
+        let descendants = vec![
+            folder("dandisets/000001/draft/sub-02/", ResourceKind::Directory),
+            folder("dandisets/000001/draft/sub-01/", ResourceKind::Directory),
+            blob("dandisets/000001/draft/sub-01/b.nwb"),
+            folder(
+                "dandisets/000001/draft/sub-01/ses 1/",
+                ResourceKind::Directory,
+            ),
+            blob("dandisets/000001/draft/sub-01/a.nwb"),
+            blob("dandisets/000001/draft/README.md"),
+        ];
+        let tree = serde_json::to_value(JsonTree::new(root, descendants, 2, false)).unwrap();
+        let names = |v: &serde_json::Value| {
+            v["children"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|c| c["name"].as_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tree["url"], "/dandisets/000001/draft/");
+        assert_eq!(tree["is_collection"], true);
+        assert_eq!(names(&tree), ["README.md", "sub-01", "sub-02"]);
+        let readme = &tree["children"][0];
+        assert_eq!(readme["url"], "/dandisets/000001/draft/README.md");
+        assert!(readme.get("children").is_none());
+        let sub01 = &tree["children"][1];
+        assert_eq!(names(sub01), ["a.nwb", "b.nwb", "ses 1"]);
+        // Collections at the maximum depth are not expanded:
+        let ses1 = &sub01["children"][2];
+        assert_eq!(ses1["url"], "/dandisets/000001/draft/sub-01/ses%201/");
+        assert!(ses1.get("children").is_none());
+        // Expanded collections without children get an empty list:
+        assert_eq!(tree["children"][2]["children"], json!([]));
+    }
+
+    #[test]
+    fn tree_of_item() {
+        let tree = JsonTree::new(blob("zarrs/foo.txt"), Vec::new(), 3, false);
+        assert_eq!(
+            serde_json::to_value(tree).unwrap(),
+            json!({
+                "name": "foo.txt",
+                "kind": "Blob asset",
+                "is_collection": false,
+                "size": 1,
+                "created": null,
+                "modified": null,
+                "url": "/zarrs/foo.txt",
+                "download_url": null,
+                "metadata_url": null,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("/", None)]
+    #[case("/dandisets/", Some("/"))]
+    #[case("/dandisets/000001/draft/", Some("/dandisets/000001/"))]
+    #[case("/dandisets/000001/draft/foo.nwb", Some("/dandisets/000001/draft/"))]
+    fn test_parent_href(#[case] href: &str, #[case] parent: Option<&str>) {
+        assert_eq!(parent_href(href), parent);
+    }
+
+    #[test]
+    fn size_estimate() {
+        let v = DandisetVersion {
+            version: VersionId::Published("0.210831.2033".parse().unwrap()),
+            size: 18_792_700_000,
+            asset_count: Some(14),
+            created: datetime!(2021-08-31 20:33:00 UTC),
+            modified: datetime!(2021-08-31 20:33:00 UTC),
+            metadata_url: "https://api.example.com/dandisets/000027/versions/0.210831.2033/"
+                .parse()
+                .unwrap(),
+        };
+        let estimate = JsonSizeEstimate::new(
+            &"dandisets/000027/latest/".parse().unwrap(),
+            &"000027".parse().unwrap(),
+            v,
+        );
+        assert_eq!(
+            serde_json::to_value(estimate).unwrap(),
+            json!({
+                "url": "/dandisets/000027/latest/",
+                "dandiset_id": "000027",
+                "version": "0.210831.2033",
+                "size": 18_792_700_000_i64,
+                "asset_count": 14,
+            })
+        );
+    }
+}
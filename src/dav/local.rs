@@ -0,0 +1,699 @@
+//! Serving directory trees on the local filesystem via `--local-mount`
+use super::path::DavPath;
+use super::source::DavDataSource;
+use super::types::{
+    DavCollection, DavContent, DavItem, DavResource, DavResourceWithChildren, ResourceKind,
+};
+use super::util::{with_body_size, ByteRange};
+use super::{DandiDav, DavError, ErrorClass};
+use crate::consts::{
+    DEFAULT_CONTENT_TYPE, LOCAL_CONTENT_TYPES, LOCAL_READ_CHUNK_SIZE, NON_DAV_ROUTES,
+};
+use crate::paths::{Component, PureDirPath, PurePath};
+use axum::{
+    body::Body,
+    http::{
+        header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE},
+        Response, StatusCode,
+    },
+    response::IntoResponse,
+};
+use bytes::Bytes;
+use futures_util::{
+    future::BoxFuture,
+    stream::{self, BoxStream},
+    FutureExt, StreamExt,
+};
+use smartstring::alias::CompactString;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+
+/// The name of the top-level collection at which a local directory tree is
+/// served, consisting of one or more hyphen-separated runs of lowercase ASCII
+/// letters & digits.  Names used by `dandidav`'s other hierarchies
+/// (`dandisets`, `{name}-dandisets`, and `zarrs`) and by its non-WebDAV
+/// endpoints (see [`NON_DAV_ROUTES`]) are not allowed.
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct MountName(CompactString);
+
+/// Names of the top-level collections of the other WebDAV hierarchies
+const DAV_HIERARCHY_NAMES: [&str; 2] = ["dandisets", "zarrs"];
+
+/// Returns `true` if `s` is the name of another WebDAV hierarchy or the first
+/// component of a route served by something other than the WebDAV handler
+fn is_reserved(s: &str) -> bool {
+    DAV_HIERARCHY_NAMES.contains(&s)
+        || s.ends_with("-dandisets")
+        || NON_DAV_ROUTES
+            .iter()
+            .any(|route| route.trim_start_matches('/').split('/').next() == Some(s))
+}
+
+fn validate(s: &str) -> Result<(), ParseMountNameError> {
+    if s.is_empty()
+        || !s.split('-').all(|run| {
+            !run.is_empty()
+                && run
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+    {
+        Err(ParseMountNameError::Invalid)
+    } else if is_reserved(s) {
+        Err(ParseMountNameError::Reserved)
+    } else {
+        Ok(())
+    }
+}
+
+validstr!(
+    MountName,
+    ParseMountNameError,
+    validate,
+    "a local mount name"
+);
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+pub(crate) enum ParseMountNameError {
+    #[error("mount names must be hyphen-separated runs of lowercase ASCII letters & digits")]
+    Invalid,
+    #[error("mount name is reserved for another hierarchy")]
+    Reserved,
+}
+
+/// A local directory tree to serve, as given on the command line in the form
+/// `NAME=DIR`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct LocalMountSpec {
+    /// The name of the top-level collection at which to serve the directory
+    pub(crate) name: MountName,
+
+    /// The directory to serve
+    pub(crate) dir: PathBuf,
+}
+
+impl FromStr for LocalMountSpec {
+    type Err = ParseLocalMountSpecError;
+
+    fn from_str(s: &str) -> Result<LocalMountSpec, ParseLocalMountSpecError> {
+        let (name, dir) = s
+            .split_once('=')
+            .ok_or(ParseLocalMountSpecError::NoEquals)?;
+        if dir.is_empty() {
+            return Err(ParseLocalMountSpecError::EmptyDir);
+        }
+        Ok(LocalMountSpec {
+            name: name.parse()?,
+            dir: PathBuf::from(dir),
+        })
+    }
+}
+
+/// Error returned when parsing an invalid [`LocalMountSpec`] string
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub(crate) enum ParseLocalMountSpecError {
+    #[error("expected an argument of the form NAME=DIR")]
+    NoEquals,
+    #[error("invalid mount name")]
+    Name(#[from] ParseMountNameError),
+    #[error("directory path is empty")]
+    EmptyDir,
+}
+
+/// A read-only view of a directory tree on the local filesystem, served at a
+/// top-level collection of the `dandidav` hierarchy.
+///
+/// Files & directories whose names begin with a period are not served, nor
+/// are files that are not valid path components (e.g., non-UTF-8 names),
+/// symlinks pointing outside of the tree, or anything other than regular
+/// files & directories (e.g., FIFOs, sockets, and device files).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct LocalSource {
+    /// The name of the top-level collection at which the tree is served
+    name: MountName,
+
+    /// The canonicalized path to the root of the directory tree
+    root: PathBuf,
+}
+
+impl LocalSource {
+    /// Serve the directory given by `spec`, failing if it is not a
+    /// directory
+    pub(crate) async fn new(spec: LocalMountSpec) -> Result<LocalSource, LocalMountError> {
+        let root = tokio::fs::canonicalize(&spec.dir).await.map_err(|source| {
+            LocalMountError::Resolve {
+                dir: spec.dir.clone(),
+                source,
+            }
+        })?;
+        let is_dir = tokio::fs::metadata(&root)
+            .await
+            .map_err(|source| LocalMountError::Resolve {
+                dir: spec.dir.clone(),
+                source,
+            })?
+            .is_dir();
+        if !is_dir {
+            return Err(LocalMountError::NotADirectory { dir: spec.dir });
+        }
+        Ok(LocalSource {
+            name: spec.name,
+            root,
+        })
+    }
+
+    /// Return the directory path at which the directory at `path` (relative
+    /// to the root of the tree, or the root itself if `None`) is served
+    fn web_dir(&self, path: Option<&PurePath>) -> PureDirPath {
+        let base = PureDirPath::from(
+            Component::try_from(String::from(&self.name))
+                .expect("mount name should be a valid path component"),
+        );
+        match path {
+            Some(p) => base.join_dir(&p.to_dir_path()),
+            None => base,
+        }
+    }
+
+    /// Find the file or directory at `path` (relative to the root of the
+    /// tree, or the root itself if `None`) and return its filesystem path &
+    /// metadata.  Anything that is not a regular file or directory is
+    /// treated as nonexistent, as reading it could block or have side
+    /// effects.
+    async fn locate(&self, path: Option<&PurePath>) -> Result<(PathBuf, Metadata), LocalError> {
+        let mut fspath = self.root.clone();
+        if let Some(p) = path {
+            if p.component_strs().any(|c| c.starts_with('.')) {
+                return Err(LocalError::NotFound { path: p.clone() });
+            }
+            fspath.extend(p.component_strs());
+        }
+        let not_found = || LocalError::NotFound {
+            path: path.cloned().unwrap_or_else(|| {
+                PurePath::try_from(String::from(&self.name))
+                    .expect("mount name should be a valid path")
+            }),
+        };
+        let fspath = match tokio::fs::canonicalize(&fspath).await {
+            Ok(p) => p,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(not_found()),
+            Err(source) => {
+                return Err(LocalError::Io {
+                    path: fspath,
+                    source,
+                })
+            }
+        };
+        if !fspath.starts_with(&self.root) {
+            return Err(not_found());
+        }
+        match tokio::fs::metadata(&fspath).await {
+            Ok(md) if md.is_file() || md.is_dir() => Ok((fspath, md)),
+            Ok(_) => Err(not_found()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(not_found()),
+            Err(source) => Err(LocalError::Io {
+                path: fspath,
+                source,
+            }),
+        }
+    }
+
+    /// Construct the resource for the file or directory served at `path`
+    /// (relative to the root of the tree, or the root itself if `None`)
+    /// with filesystem path `fspath` & metadata `md`
+    fn make_resource(
+        &self,
+        path: Option<&PurePath>,
+        fspath: PathBuf,
+        md: &Metadata,
+    ) -> DavResource {
+        let created = md.created().ok().map(OffsetDateTime::from);
+        let modified = md.modified().ok().map(OffsetDateTime::from);
+        match path {
+            Some(p) if !md.is_dir() => {
+                let size = md.len();
+                let etag = modified.map(|m| {
+                    let nanos = m.unix_timestamp_nanos();
+                    format!("\"{size:x}-{nanos:x}\"")
+                });
+                DavResource::Item(DavItem {
+                    path: self.web_dir(None).join(p),
+                    created,
+                    modified,
+                    content_type: content_type(p.name_str()).to_owned(),
+                    size: i64::try_from(size).ok(),
+                    etag,
+                    kind: ResourceKind::LocalFile,
+                    content: DavContent::LocalFile(fspath),
+                    metadata_url: None,
+                })
+            }
+            _ => DavResource::Collection(DavCollection {
+                path: Some(self.web_dir(path)),
+                created,
+                modified,
+                size: None,
+                asset_count: None,
+                kind: if path.is_some() {
+                    ResourceKind::Directory
+                } else {
+                    ResourceKind::LocalMount
+                },
+                metadata_url: None,
+            }),
+        }
+    }
+
+    /// Get details on the file or directory at `path` (relative to the root
+    /// of the tree, or the root itself if `None`)
+    async fn get(&self, path: Option<&PurePath>) -> Result<DavResource, LocalError> {
+        let (fspath, md) = self.locate(path).await?;
+        Ok(self.make_resource(path, fspath, &md))
+    }
+
+    /// Get details on the file or directory at `path` (relative to the root
+    /// of the tree, or the root itself if `None`) along with its immediate
+    /// children (if any), sorted by name
+    async fn get_with_children(
+        &self,
+        path: Option<&PurePath>,
+    ) -> Result<DavResourceWithChildren, LocalError> {
+        let (fspath, md) = self.locate(path).await?;
+        let col = match self.make_resource(path, fspath.clone(), &md) {
+            DavResource::Collection(col) => col,
+            DavResource::Item(item) => return Ok(DavResourceWithChildren::Item(item)),
+        };
+        let io_error = |source| LocalError::Io {
+            path: fspath.clone(),
+            source,
+        };
+        let mut entries = tokio::fs::read_dir(&fspath).await.map_err(io_error)?;
+        let mut children = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(io_error)? {
+            let Some(name) = entry
+                .file_name()
+                .to_str()
+                .filter(|s| !s.starts_with('.'))
+                .and_then(|s| s.parse::<Component>().ok())
+            else {
+                continue;
+            };
+            let child_path = match path {
+                Some(p) => p.join_one(&name),
+                None => PurePath::from(name),
+            };
+            // Use `locate()` so that symlinks are followed & checked:
+            match self.locate(Some(&child_path)).await {
+                Ok((child_fspath, child_md)) => {
+                    children.push(self.make_resource(Some(&child_path), child_fspath, &child_md));
+                }
+                Err(LocalError::NotFound { .. }) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        children.sort_unstable_by(|a, b| a.name().cmp(&b.name()));
+        Ok(DavResourceWithChildren::Collection { col, children })
+    }
+}
+
+impl DavDataSource for LocalSource {
+    fn mount_name(&self) -> &str {
+        &self.name
+    }
+
+    fn mount_collection(&self) -> DavCollection {
+        DavCollection {
+            path: Some(self.web_dir(None)),
+            created: None,
+            modified: None,
+            size: None,
+            asset_count: None,
+            kind: ResourceKind::LocalMount,
+            metadata_url: None,
+        }
+    }
+
+    fn get_resource<'a>(
+        &'a self,
+        _dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResource, DavError>> {
+        async move {
+            let DavPath::LocalPath { path, .. } = path else {
+                unreachable!("non-local path {path:?} should not be routed to a local mount");
+            };
+            Ok(self.get(path.as_ref()).await?)
+        }
+        .boxed()
+    }
+
+    fn get_resource_with_children<'a>(
+        &'a self,
+        _dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResourceWithChildren, DavError>> {
+        async move {
+            let DavPath::LocalPath { path, .. } = path else {
+                unreachable!("non-local path {path:?} should not be routed to a local mount");
+            };
+            Ok(self.get_with_children(path.as_ref()).await?)
+        }
+        .boxed()
+    }
+}
+
+/// Return the "Content-Type" value for a local file named `name`, based on
+/// its extension
+fn content_type(name: &str) -> &'static str {
+    let name = name.to_ascii_lowercase();
+    LOCAL_CONTENT_TYPES
+        .iter()
+        .find(|(ext, _)| name.ends_with(ext))
+        .map_or(DEFAULT_CONTENT_TYPE, |&(_, ct)| ct)
+}
+
+/// Open the local file at `fspath` for reading and return it along with its
+/// size
+pub(super) async fn open_file(fspath: &Path) -> Result<(tokio::fs::File, u64), LocalError> {
+    let io_error = |source| LocalError::Io {
+        path: fspath.to_owned(),
+        source,
+    };
+    let file = tokio::fs::File::open(fspath).await.map_err(io_error)?;
+    let len = file.metadata().await.map_err(io_error)?.len();
+    Ok((file, len))
+}
+
+/// Return a stream of the content read from `reader` in chunks of at most
+/// [`LOCAL_READ_CHUNK_SIZE`] bytes
+pub(super) fn read_chunks<R>(reader: R) -> BoxStream<'static, io::Result<Bytes>>
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    stream::try_unfold(reader, |mut reader| async move {
+        let mut buf = vec![0; LOCAL_READ_CHUNK_SIZE];
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            Ok(None)
+        } else {
+            buf.truncate(n);
+            Ok(Some((Bytes::from(buf), reader)))
+        }
+    })
+    .boxed()
+}
+
+/// Generate a response streaming the contents of the local file at `fspath`
+/// with the given `content_type`, restricted to `range` if it is non-`None`.
+/// Satisfiable ranges produce a 206 response, and unsatisfiable ones produce
+/// a 416 response.
+pub(super) async fn file_response(
+    content_type: String,
+    fspath: &Path,
+    range: Option<ByteRange>,
+) -> Result<Response<Body>, LocalError> {
+    let (mut file, len) = open_file(fspath).await?;
+    let (status, start, count, content_range) = match range {
+        None => (StatusCode::OK, 0, len, None),
+        Some(range) => match range.resolve(len) {
+            Some((start, end)) => (
+                StatusCode::PARTIAL_CONTENT,
+                start,
+                end - start + 1,
+                Some(format!("bytes {start}-{end}/{len}")),
+            ),
+            None => {
+                return Ok((
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(CONTENT_RANGE, format!("bytes */{len}"))],
+                )
+                    .into_response())
+            }
+        },
+    };
+    if start > 0 {
+        file.seek(io::SeekFrom::Start(start))
+            .await
+            .map_err(|source| LocalError::Io {
+                path: fspath.to_owned(),
+                source,
+            })?;
+    }
+    let mut resp = (
+        status,
+        [
+            (CONTENT_TYPE, content_type),
+            (ACCEPT_RANGES, "bytes".into()),
+            (CONTENT_LENGTH, count.to_string()),
+        ],
+        Body::from_stream(read_chunks(file.take(count))),
+    )
+        .into_response();
+    if let Some(content_range) = content_range {
+        resp.headers_mut().insert(
+            CONTENT_RANGE,
+            content_range
+                .parse()
+                .expect("Content-Range value should be a valid header value"),
+        );
+    }
+    Ok(with_body_size(
+        resp,
+        usize::try_from(count).unwrap_or(usize::MAX),
+    ))
+}
+
+/// Error returned by [`LocalSource::new()`]
+#[derive(Debug, Error)]
+pub(crate) enum LocalMountError {
+    #[error("failed to resolve local mount directory {}", .dir.display())]
+    Resolve { dir: PathBuf, source: io::Error },
+    #[error("local mount path {} is not a directory", .dir.display())]
+    NotADirectory { dir: PathBuf },
+}
+
+/// Error returned when serving a resource from a local mount fails
+#[derive(Debug, Error)]
+pub(crate) enum LocalError {
+    #[error("no such local resource: {path:?}")]
+    NotFound { path: PurePath },
+    #[error("failed to read local file {}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl LocalError {
+    /// Classify the general type of error
+    pub(crate) fn class(&self) -> ErrorClass {
+        match self {
+            LocalError::NotFound { .. } => ErrorClass::NotFound,
+            LocalError::Io { .. } => ErrorClass::Internal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::dav_with_transport;
+    use super::*;
+    use crate::dav::PathConfig;
+    use crate::httputil::FakeTransport;
+    use axum::{
+        extract::Request,
+        http::{
+            header::{ETAG, RANGE},
+            Method,
+        },
+    };
+    use rstest::rstest;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[rstest]
+    #[case("local")]
+    #[case("docs-2024")]
+    #[case("derived-data")]
+    fn test_valid_mount_name(#[case] s: &str) {
+        assert_eq!(s.parse::<MountName>().unwrap(), s);
+    }
+
+    #[rstest]
+    #[case("", ParseMountNameError::Invalid)]
+    #[case("Local", ParseMountNameError::Invalid)]
+    #[case("local-", ParseMountNameError::Invalid)]
+    #[case("local--docs", ParseMountNameError::Invalid)]
+    #[case("local_docs", ParseMountNameError::Invalid)]
+    #[case("zarrs", ParseMountNameError::Reserved)]
+    #[case("dandisets", ParseMountNameError::Reserved)]
+    #[case("staging-dandisets", ParseMountNameError::Reserved)]
+    #[case("assets", ParseMountNameError::Reserved)]
+    #[case("admin", ParseMountNameError::Reserved)]
+    #[case("about", ParseMountNameError::Reserved)]
+    #[case("sitemaps", ParseMountNameError::Reserved)]
+    #[case("healthz", ParseMountNameError::Reserved)]
+    #[case("readyz", ParseMountNameError::Reserved)]
+    #[case("metrics", ParseMountNameError::Reserved)]
+    fn test_invalid_mount_name(#[case] s: &str, #[case] err: ParseMountNameError) {
+        assert_eq!(s.parse::<MountName>(), Err(err));
+    }
+
+    #[test]
+    fn test_non_dav_routes_reserved() {
+        for route in NON_DAV_ROUTES {
+            let name = route.trim_start_matches('/').split('/').next().unwrap();
+            assert!(name.parse::<MountName>().is_err(), "{name:?} accepted");
+        }
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let spec = "docs=/srv/docs".parse::<LocalMountSpec>().unwrap();
+        assert_eq!(spec.name, "docs");
+        assert_eq!(spec.dir, Path::new("/srv/docs"));
+    }
+
+    #[rstest]
+    #[case("docs", ParseLocalMountSpecError::NoEquals)]
+    #[case("docs=", ParseLocalMountSpecError::EmptyDir)]
+    #[case(
+        "zarrs=/srv/docs",
+        ParseLocalMountSpecError::Name(ParseMountNameError::Reserved)
+    )]
+    fn test_parse_bad_spec(#[case] s: &str, #[case] err: ParseLocalMountSpecError) {
+        assert_eq!(s.parse::<LocalMountSpec>(), Err(err));
+    }
+
+    #[rstest]
+    #[case("README.md", "text/markdown; charset=utf-8")]
+    #[case("table.TSV", "text/tab-separated-values; charset=utf-8")]
+    #[case("records.jsonl", "application/jsonl")]
+    #[case("data.bin", "application/octet-stream")]
+    #[case("Makefile", "application/octet-stream")]
+    fn test_content_type(#[case] name: &str, #[case] ct: &str) {
+        assert_eq!(content_type(name), ct);
+    }
+
+    /// Create a directory tree to serve and a `DandiDav` serving it under
+    /// `/local/`.  The tree is rooted at the `tree` subdirectory of the
+    /// returned `TempDir`, next to a file that should not be reachable.  On
+    /// Unix, the tree also contains a socket, which should not be served.
+    async fn local_dav() -> (TempDir, Arc<DandiDav>) {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let tree = tmpdir.path().join("tree");
+        std::fs::create_dir_all(tree.join("docs")).unwrap();
+        std::fs::write(tree.join("data.txt"), "Hello, world!\n").unwrap();
+        std::fs::write(tree.join("docs").join("README.md"), "# Docs\n").unwrap();
+        std::fs::write(tree.join(".hidden"), "secret\n").unwrap();
+        std::fs::write(tmpdir.path().join("outside.txt"), "secret\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(tmpdir.path().join("outside.txt"), tree.join("escape.txt"))
+            .unwrap();
+        #[cfg(unix)]
+        std::os::unix::net::UnixListener::bind(tree.join("socket")).unwrap();
+        let source = LocalSource::new(LocalMountSpec {
+            name: "local".parse().unwrap(),
+            dir: tree,
+        })
+        .await
+        .unwrap();
+        let mut dav = dav_with_transport(FakeTransport::new());
+        dav.local_mounts.push(source);
+        dav.path_config = Arc::new(PathConfig {
+            local_mounts: vec!["local".parse().unwrap()],
+            ..PathConfig::default()
+        });
+        (tmpdir, Arc::new(dav))
+    }
+
+    async fn request(dav: &Arc<DandiDav>, req: Request<Body>) -> (Response<Body>, Bytes) {
+        let resp = dav.handle_request(req).await.unwrap();
+        let (parts, body) = resp.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (Response::from_parts(parts, Body::empty()), body)
+    }
+
+    async fn get(dav: &Arc<DandiDav>, uri: &str) -> (Response<Body>, Bytes) {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .header("Accept", "application/json")
+            .body(Body::empty())
+            .unwrap();
+        request(dav, req).await
+    }
+
+    fn entry_names(body: &[u8]) -> Vec<String> {
+        let listing = serde_json::from_slice::<Value>(body).unwrap();
+        listing["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["name"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn get_file() {
+        let (_tmpdir, dav) = local_dav().await;
+        let (resp, body) = get(&dav, "/local/data.txt").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/plain; charset=utf-8");
+        assert_eq!(resp.headers()[CONTENT_LENGTH], "14");
+        let etag = resp.headers()[ETAG].to_str().unwrap();
+        assert!(etag.starts_with("\"e-"));
+        assert!(etag.ends_with('"'));
+        assert_eq!(body, "Hello, world!\n");
+    }
+
+    #[tokio::test]
+    async fn get_file_range() {
+        let (_tmpdir, dav) = local_dav().await;
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/local/data.txt")
+            .header(RANGE, "bytes=7-11")
+            .body(Body::empty())
+            .unwrap();
+        let (resp, body) = request(&dav, req).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[CONTENT_RANGE], "bytes 7-11/14");
+        assert_eq!(body, "world");
+    }
+
+    #[tokio::test]
+    async fn list_mount() {
+        let (_tmpdir, dav) = local_dav().await;
+        let (resp, body) = get(&dav, "/local/").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(entry_names(&body), ["data.txt", "docs"]);
+        let (resp, body) = get(&dav, "/local/docs/").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(entry_names(&body), ["README.md"]);
+    }
+
+    #[tokio::test]
+    async fn root_lists_mount() {
+        let (_tmpdir, dav) = local_dav().await;
+        let (resp, body) = get(&dav, "/").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(entry_names(&body).contains(&"local".to_owned()));
+    }
+
+    #[rstest]
+    #[case("/local/.hidden")]
+    #[case("/local/nonexistent.txt")]
+    #[case("/local/docs/../../outside.txt")]
+    #[cfg_attr(unix, case("/local/escape.txt"))]
+    #[cfg_attr(unix, case("/local/socket"))]
+    #[tokio::test]
+    async fn not_found(#[case] uri: &str) {
+        let (_tmpdir, dav) = local_dav().await;
+        let (resp, _) = get(&dav, uri).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
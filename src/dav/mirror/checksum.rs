@@ -0,0 +1,197 @@
+//! Verification of downloaded content against MD5 digests & DANDI ETags
+use md5::{Digest, Md5};
+
+/// The default size of the parts that a blob is split into when computing its
+/// DANDI ETag
+const DANDI_ETAG_DEFAULT_PART_SIZE: u64 = 64 << 20;
+
+/// The maximum number of parts that a blob is split into when computing its
+/// DANDI ETag
+const DANDI_ETAG_MAX_PARTS: u64 = 10_000;
+
+/// The expected checksum of a file's content
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) enum Checksum {
+    /// The MD5 digest of the content as a lowercase hex string, as used in
+    /// the S3 ETags of Zarr entries
+    Md5(String),
+
+    /// A DANDI ETag, as used to identify the content of blob assets: the MD5
+    /// digest of the concatenated MD5 digests of the content's parts (as
+    /// determined by [`dandi_etag_part_size()`]), followed by a hyphen and the
+    /// number of parts
+    DandiEtag(String),
+}
+
+impl Checksum {
+    /// Return the checksum as a string
+    pub(super) fn as_str(&self) -> &str {
+        match self {
+            Checksum::Md5(s) | Checksum::DandiEtag(s) => s,
+        }
+    }
+
+    /// Return a hasher for computing the same kind of checksum for content
+    /// of `size` bytes
+    pub(super) fn hasher(&self, size: u64) -> ChecksumHasher {
+        match self {
+            Checksum::Md5(_) => ChecksumHasher::new(None),
+            Checksum::DandiEtag(_) => ChecksumHasher::new(Some(dandi_etag_part_size(size))),
+        }
+    }
+}
+
+/// Return the size of the parts (other than the last) that content of `size`
+/// bytes is split into when computing its DANDI ETag.
+///
+/// This follows `dandischema.digests.dandietag`: parts are 64 MiB unless that
+/// would result in more than 10,000 parts, in which case the part size is
+/// increased to the smallest size that results in 10,000 parts.
+fn dandi_etag_part_size(size: u64) -> u64 {
+    if size.div_ceil(DANDI_ETAG_DEFAULT_PART_SIZE) > DANDI_ETAG_MAX_PARTS {
+        size.div_ceil(DANDI_ETAG_MAX_PARTS)
+    } else {
+        DANDI_ETAG_DEFAULT_PART_SIZE
+    }
+}
+
+/// Incremental computation of a [`Checksum`] of content fed to it in chunks
+#[derive(Clone, Debug)]
+pub(super) struct ChecksumHasher {
+    /// The size of the parts the content is split into, or `None` if only a
+    /// plain MD5 digest is being computed
+    part_size: Option<u64>,
+
+    /// The MD5 hasher for the current part (or for the entire content if
+    /// `part_size` is `None`)
+    part: Md5,
+
+    /// The number of bytes fed into `part` so far
+    part_len: u64,
+
+    /// The MD5 hasher for the digests of completed parts
+    digests: Md5,
+
+    /// The number of completed parts
+    parts: u64,
+}
+
+impl ChecksumHasher {
+    fn new(part_size: Option<u64>) -> ChecksumHasher {
+        ChecksumHasher {
+            part_size,
+            part: Md5::new(),
+            part_len: 0,
+            digests: Md5::new(),
+            parts: 0,
+        }
+    }
+
+    /// Feed the next chunk of content into the hasher
+    pub(super) fn update(&mut self, mut data: &[u8]) {
+        let Some(part_size) = self.part_size else {
+            self.part.update(data);
+            return;
+        };
+        while !data.is_empty() {
+            let room =
+                usize::try_from(part_size.saturating_sub(self.part_len)).unwrap_or(usize::MAX);
+            let (head, tail) = data.split_at(room.min(data.len()));
+            self.part.update(head);
+            self.part_len = self
+                .part_len
+                .saturating_add(u64::try_from(head.len()).unwrap_or(u64::MAX));
+            if self.part_len >= part_size {
+                self.finish_part();
+            }
+            data = tail;
+        }
+    }
+
+    fn finish_part(&mut self) {
+        let digest = std::mem::take(&mut self.part).finalize();
+        self.digests.update(digest);
+        self.parts = self.parts.saturating_add(1);
+        self.part_len = 0;
+    }
+
+    /// Return the checksum of the content fed into the hasher, in the same
+    /// format as the string of the [`Checksum`] that the hasher was obtained
+    /// from
+    pub(super) fn finalize(mut self) -> String {
+        if self.part_size.is_none() {
+            return format!("{:x}", self.part.finalize());
+        }
+        if self.part_len > 0 {
+            self.finish_part();
+        }
+        format!("{:x}-{}", self.digests.finalize(), self.parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, DANDI_ETAG_DEFAULT_PART_SIZE)]
+    #[case(1 << 30, DANDI_ETAG_DEFAULT_PART_SIZE)]
+    #[case(DANDI_ETAG_DEFAULT_PART_SIZE * DANDI_ETAG_MAX_PARTS, DANDI_ETAG_DEFAULT_PART_SIZE)]
+    #[case(
+        DANDI_ETAG_DEFAULT_PART_SIZE * DANDI_ETAG_MAX_PARTS + 1,
+        DANDI_ETAG_DEFAULT_PART_SIZE + 1
+    )]
+    #[case(1 << 40, 109_951_163)]
+    fn test_dandi_etag_part_size(#[case] size: u64, #[case] part_size: u64) {
+        assert_eq!(dandi_etag_part_size(size), part_size);
+    }
+
+    #[test]
+    fn md5() {
+        let checksum = Checksum::Md5("ignored".into());
+        let mut hasher = checksum.hasher(11);
+        hasher.update(b"Hello, ");
+        hasher.update(b"");
+        hasher.update(b"World!");
+        assert_eq!(hasher.finalize(), "65a8e27d8879283831b664bd8b7f0ad4");
+    }
+
+    #[test]
+    fn dandi_etag_empty() {
+        let checksum = Checksum::DandiEtag("ignored".into());
+        let hasher = checksum.hasher(0);
+        assert_eq!(hasher.finalize(), "d41d8cd98f00b204e9800998ecf8427e-0");
+    }
+
+    #[test]
+    fn dandi_etag_single_part() {
+        // The digest of the MD5 digest of "Hello, World!"
+        let checksum = Checksum::DandiEtag("ignored".into());
+        let mut hasher = checksum.hasher(13);
+        hasher.update(b"Hello, World!");
+        let part = Md5::digest(b"Hello, World!");
+        let expected = format!("{:x}-1", Md5::digest(part));
+        assert_eq!(hasher.finalize(), expected);
+    }
+
+    #[test]
+    fn multipart_chunking_independent() {
+        let data = (0..2500u32)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect::<Vec<_>>();
+        let mut whole = ChecksumHasher::new(Some(1000));
+        whole.update(&data);
+        let mut chunked = ChecksumHasher::new(Some(1000));
+        for chunk in data.chunks(333) {
+            chunked.update(chunk);
+        }
+        let mut digests = Md5::new();
+        for part in data.chunks(1000) {
+            digests.update(Md5::digest(part));
+        }
+        let expected = format!("{:x}-3", digests.finalize());
+        assert_eq!(whole.finalize(), expected);
+        assert_eq!(chunked.finalize(), expected);
+    }
+}
@@ -0,0 +1,595 @@
+//! Downloading of resource hierarchies to local directories for the `mirror`
+//! subcommand
+mod checksum;
+use self::checksum::Checksum;
+use super::path::split_uri_path;
+use super::types::{DavContent, DavItem, DavResource, HasProperties, ResourceKind};
+use super::walk::WalkLimits;
+use super::{DandiDav, DavError, DavPath, Instance};
+use crate::consts::{MIRROR_CHUNK_SIZE, MIRROR_MANIFEST_NAME, MIRROR_PARTIAL_SUFFIX};
+use crate::dandi::{DandiClient, DandiError};
+use crate::httputil::HttpUrl;
+use crate::paths::{PureDirPath, PurePath};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+impl DandiDav {
+    /// Download the resource at `path` (a path as served by `dandidav`, e.g.,
+    /// `dandisets/000027/draft/sub-RAT123/`) and all of its descendants into
+    /// the local directory `outdir`, downloading up to `jobs` files at once
+    /// (with 0 treated as 1).
+    ///
+    /// Files are downloaded from the same URLs that `dandidav` would redirect
+    /// `GET` requests to, in chunks of [`MIRROR_CHUNK_SIZE`] bytes.  Each
+    /// file is first written to a partial file (with a
+    /// [`MIRROR_PARTIAL_SUFFIX`] suffix) that is renamed into place once the
+    /// download is complete and the file's content has been verified against
+    /// the blob's DANDI ETag or the Zarr entry's MD5 digest.
+    ///
+    /// Each file that is downloaded or found to be up to date is recorded in
+    /// a JSON Lines manifest named [`MIRROR_MANIFEST_NAME`] in `outdir`.  When
+    /// a mirror is rerun after being interrupted, files recorded in the
+    /// manifest with the same size & checksum as they have upstream are
+    /// skipped, and downloads of partial files are resumed from where they
+    /// left off.  Once the mirror completes, the manifest is rewritten to
+    /// only list the files that were mirrored.
+    ///
+    /// Virtual files generated by `dandidav` (e.g., `dandiset.yaml`) are
+    /// written as-is; `assets.jsonl` files and resources without download
+    /// URLs are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` is invalid, if fetching any resource or
+    /// downloading any file fails, if a downloaded file fails verification,
+    /// or if an I/O error occurs.  Files that were downloaded before the
+    /// error occurred are kept & recorded in the manifest.
+    pub(crate) async fn mirror(
+        self: &Arc<Self>,
+        path: &str,
+        outdir: &Path,
+        jobs: usize,
+    ) -> Result<MirrorReport, MirrorError> {
+        let jobs = jobs.max(1);
+        let davpath = split_uri_path(path, &self.path_config)
+            .and_then(|parts| DavPath::from_components(parts, &self.path_config))
+            .ok_or_else(|| MirrorError::InvalidPath(path.to_owned()))?;
+        let dandi = self
+            .dandi(davpath.instance().unwrap_or(&Instance::Primary))
+            .map_err(DavError::from)?
+            .clone();
+        create_dir_all(outdir).await?;
+        let mut manifest = Manifest::load(outdir.join(MIRROR_MANIFEST_NAME)).await?;
+        let previous = std::mem::take(&mut manifest.previous);
+        let mut walk = Box::pin(self.walk(
+            &davpath,
+            WalkLimits {
+                max_depth: None,
+                max_resources: None,
+                concurrency: jobs,
+            },
+        ));
+        let (base, resources) = match walk.next().await {
+            Some(Ok(DavResource::Collection(col))) => (col.path, walk.boxed()),
+            Some(Ok(DavResource::Item(item))) => (
+                item.path.parent(),
+                stream::iter([Ok(DavResource::Item(item))]).boxed(),
+            ),
+            Some(Err(e)) => return Err(e.into()),
+            None => return Ok(MirrorReport::default()),
+        };
+        let mut items = std::pin::pin!(resources
+            .map_err(MirrorError::from)
+            .try_filter_map(|res| {
+                let base = base.clone();
+                async move {
+                    match res {
+                        DavResource::Collection(col) => {
+                            if let Some(rel) = col
+                                .path
+                                .as_ref()
+                                .and_then(|p| relative_dir(base.as_ref(), p))
+                            {
+                                create_dir_all(&outdir.join(rel.as_ref())).await?;
+                            }
+                            Ok(None)
+                        }
+                        DavResource::Item(item) => {
+                            Ok(relative_path(base.as_ref(), &item.path).map(|rel| (rel, item)))
+                        }
+                    }
+                }
+            })
+            .map_ok(|(rel, item)| {
+                let prev = previous.get(rel.as_ref()).cloned();
+                let dandi = dandi.clone();
+                async move { self.mirror_item(&dandi, item, rel, outdir, prev).await }
+            })
+            .try_buffer_unordered(jobs));
+        let mut report = MirrorReport::default();
+        while let Some(outcome) = items.try_next().await? {
+            report.add(&outcome);
+            if let Some(entry) = outcome.into_entry() {
+                manifest.record(entry).await?;
+            }
+        }
+        manifest.finish().await?;
+        Ok(report)
+    }
+
+    /// Mirror the single non-collection resource `item` to the path `rel`
+    /// beneath `outdir`.  `prev` is the record for the file in the manifest
+    /// from a previous run, if any.
+    async fn mirror_item(
+        &self,
+        dandi: &DandiClient,
+        item: DavItem,
+        rel: PurePath,
+        outdir: &Path,
+        prev: Option<ManifestEntry>,
+    ) -> Result<Outcome, MirrorError> {
+        let dest = outdir.join(rel.as_ref());
+        match item.content {
+            DavContent::Blob(ref blob) => {
+                write_file(&dest, blob).await?;
+                Ok(Outcome::Generated(ManifestEntry {
+                    path: rel.to_string(),
+                    size: u64::try_from(blob.len()).ok(),
+                    checksum: None,
+                    url: None,
+                }))
+            }
+            DavContent::LocalFile(ref fspath) => {
+                let size =
+                    tokio::fs::copy(fspath, &dest)
+                        .await
+                        .map_err(|source| MirrorError::Io {
+                            path: dest.clone(),
+                            source,
+                        })?;
+                Ok(Outcome::Generated(ManifestEntry {
+                    path: rel.to_string(),
+                    size: Some(size),
+                    checksum: None,
+                    url: None,
+                }))
+            }
+            DavContent::AssetManifest => Ok(Outcome::Skipped),
+            DavContent::Missing => {
+                tracing::warn!(path = %item.path, "No download URL known for resource; skipping");
+                Ok(Outcome::Skipped)
+            }
+            DavContent::Redirect(ref redir) => {
+                let size = item.size.and_then(|sz| u64::try_from(sz).ok());
+                let checksum = expected_checksum(&item);
+                let url = redir.get_url(self.prefer_s3_redirects);
+                let entry = ManifestEntry {
+                    path: rel.to_string(),
+                    size,
+                    checksum: checksum.as_ref().map(|c| c.as_str().to_owned()),
+                    url: Some(url.to_string()),
+                };
+                if prev.as_ref().is_some_and(|p| p.describes(&entry))
+                    && file_size(&dest).await? == size
+                {
+                    tracing::debug!(path = %rel, "File is up to date; not downloading");
+                    return Ok(Outcome::UpToDate(entry));
+                }
+                let url = dandi.authorize_download(url).await?;
+                let downloaded = download(dandi, &url, &dest, size, checksum.as_ref()).await?;
+                tracing::info!(path = %rel, bytes = downloaded, "Downloaded file");
+                Ok(Outcome::Downloaded { entry, downloaded })
+            }
+        }
+    }
+}
+
+/// Download the file at `url` (of `size` bytes, if known) to `dest`, resuming
+/// from a partial file left by a previous run if there is one, and verify it
+/// against `checksum` (if known).  Returns the number of bytes downloaded.
+async fn download(
+    dandi: &DandiClient,
+    url: &HttpUrl,
+    dest: &Path,
+    size: Option<u64>,
+    checksum: Option<&Checksum>,
+) -> Result<u64, MirrorError> {
+    let partial = partial_path(dest);
+    let mut hasher = checksum.map(|c| c.hasher(size.unwrap_or_default()));
+    let mut offset = match (size, file_size(&partial).await?) {
+        (Some(size), Some(len)) if len <= size => len,
+        _ => 0,
+    };
+    let io_err = |source| MirrorError::Io {
+        path: partial.clone(),
+        source,
+    };
+    let mut fp = tokio::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(offset == 0)
+        .open(&partial)
+        .await
+        .map_err(io_err)?;
+    if offset > 0 {
+        tracing::debug!(path = %partial.display(), offset, "Resuming partial download");
+        if let Some(ref mut hasher) = hasher {
+            let mut buf = vec![0; 1 << 20];
+            loop {
+                let n = fp.read(&mut buf).await.map_err(io_err)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(buf.get(..n).unwrap_or_default());
+            }
+        }
+    }
+    let mut downloaded = 0u64;
+    loop {
+        let end = match size {
+            Some(size) if offset >= size => break,
+            Some(size) => offset.saturating_add(MIRROR_CHUNK_SIZE).min(size),
+            // The size is not known, so download everything in one go:
+            None if offset > 0 => break,
+            None => u64::MAX,
+        };
+        let chunk = dandi.download_range(url, offset..end).await?;
+        if chunk.is_empty() {
+            if size.is_none() {
+                break;
+            }
+            return Err(MirrorError::Truncated {
+                url: url.to_string(),
+                offset,
+            });
+        }
+        fp.write_all(&chunk).await.map_err(io_err)?;
+        if let Some(ref mut hasher) = hasher {
+            hasher.update(&chunk);
+        }
+        let len = u64::try_from(chunk.len()).unwrap_or(u64::MAX);
+        offset = offset.saturating_add(len);
+        downloaded = downloaded.saturating_add(len);
+    }
+    fp.flush().await.map_err(io_err)?;
+    drop(fp);
+    if let (Some(checksum), Some(hasher)) = (checksum, hasher) {
+        let actual = hasher.finalize();
+        if actual != checksum.as_str() {
+            // Remove the partial file so that the next run starts afresh
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(MirrorError::ChecksumMismatch {
+                path: dest.to_owned(),
+                expected: checksum.as_str().to_owned(),
+                actual,
+            });
+        }
+    }
+    tokio::fs::rename(&partial, dest)
+        .await
+        .map_err(|source| MirrorError::Io {
+            path: dest.to_owned(),
+            source,
+        })?;
+    Ok(downloaded)
+}
+
+/// Return the checksum that the content of `item` is expected to have, if
+/// known: the DANDI ETag for blob assets, or the MD5 digest for Zarr entries
+/// that were not uploaded in multiple parts
+fn expected_checksum(item: &DavItem) -> Option<Checksum> {
+    if item.kind == ResourceKind::Blob {
+        let etag = item.etag.as_deref()?.trim_matches('"');
+        let (digest, parts) = etag.split_once('-')?;
+        (digest.len() == 32
+            && digest.bytes().all(|b| b.is_ascii_hexdigit())
+            && parts.parse::<u64>().is_ok())
+        .then(|| Checksum::DandiEtag(etag.to_ascii_lowercase()))
+    } else {
+        item.checksum().map(Checksum::Md5)
+    }
+}
+
+/// Return the path of `path` relative to the directory `base` (or `path`
+/// itself if `base` is the root)
+fn relative_path(base: Option<&PureDirPath>, path: &PurePath) -> Option<PurePath> {
+    match base {
+        Some(b) => path.relative_to(b),
+        None => Some(path.clone()),
+    }
+}
+
+/// Return the path of the directory `path` relative to the directory `base`
+/// (or `path` itself if `base` is the root).  Returns `None` if `path` equals
+/// `base` or is not beneath it.
+fn relative_dir(base: Option<&PureDirPath>, path: &PureDirPath) -> Option<PureDirPath> {
+    match base {
+        Some(b) if b == path => None,
+        Some(b) => path.relative_to(b),
+        None => Some(path.clone()),
+    }
+}
+
+/// Return the path at which the partial download of `dest` is stored
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut s = dest.as_os_str().to_owned();
+    s.push(MIRROR_PARTIAL_SUFFIX);
+    PathBuf::from(s)
+}
+
+/// Return the size of the file at `path`, or `None` if it does not exist
+async fn file_size(path: &Path) -> Result<Option<u64>, MirrorError> {
+    match tokio::fs::metadata(path).await {
+        Ok(md) if md.is_file() => Ok(Some(md.len())),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(MirrorError::Io {
+            path: path.to_owned(),
+            source,
+        }),
+    }
+}
+
+async fn create_dir_all(path: &Path) -> Result<(), MirrorError> {
+    tokio::fs::create_dir_all(path)
+        .await
+        .map_err(|source| MirrorError::Io {
+            path: path.to_owned(),
+            source,
+        })
+}
+
+async fn write_file(path: &Path, content: &[u8]) -> Result<(), MirrorError> {
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|source| MirrorError::Io {
+            path: path.to_owned(),
+            source,
+        })
+}
+
+/// The result of mirroring a single non-collection resource
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Outcome {
+    /// The file was downloaded, with `downloaded` bytes fetched in this run
+    Downloaded {
+        entry: ManifestEntry,
+        downloaded: u64,
+    },
+
+    /// The file was already present and recorded in the manifest
+    UpToDate(ManifestEntry),
+
+    /// The file is a virtual file generated by `dandidav` and was written
+    /// directly
+    Generated(ManifestEntry),
+
+    /// The resource has no content to download
+    Skipped,
+}
+
+impl Outcome {
+    fn into_entry(self) -> Option<ManifestEntry> {
+        match self {
+            Outcome::Downloaded { entry, .. }
+            | Outcome::UpToDate(entry)
+            | Outcome::Generated(entry) => Some(entry),
+            Outcome::Skipped => None,
+        }
+    }
+}
+
+/// Summary of the work done by [`DandiDav::mirror()`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct MirrorReport {
+    /// The number of files downloaded
+    pub(crate) downloaded: u64,
+
+    /// The number of bytes downloaded
+    pub(crate) downloaded_bytes: u64,
+
+    /// The number of files that were already up to date
+    pub(crate) up_to_date: u64,
+
+    /// The number of files whose downloads could not be verified due to the
+    /// file's checksum not being known
+    pub(crate) unverified: u64,
+
+    /// The number of virtual files written
+    pub(crate) generated: u64,
+
+    /// The number of resources skipped for lack of content
+    pub(crate) skipped: u64,
+}
+
+impl MirrorReport {
+    fn add(&mut self, outcome: &Outcome) {
+        match outcome {
+            Outcome::Downloaded { entry, downloaded } => {
+                self.downloaded = self.downloaded.saturating_add(1);
+                self.downloaded_bytes = self.downloaded_bytes.saturating_add(*downloaded);
+                if entry.checksum.is_none() {
+                    self.unverified = self.unverified.saturating_add(1);
+                }
+            }
+            Outcome::UpToDate(_) => self.up_to_date = self.up_to_date.saturating_add(1),
+            Outcome::Generated(_) => self.generated = self.generated.saturating_add(1),
+            Outcome::Skipped => self.skipped = self.skipped.saturating_add(1),
+        }
+    }
+}
+
+impl fmt::Display for MirrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Downloaded {} files ({} bytes, {} unverified); {} files already up to date; {} virtual files written; {} resources skipped",
+            self.downloaded,
+            self.downloaded_bytes,
+            self.unverified,
+            self.up_to_date,
+            self.generated,
+            self.skipped
+        )
+    }
+}
+
+/// A record of a mirrored file in the manifest
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct ManifestEntry {
+    /// The path to the file relative to the mirror's root directory
+    path: String,
+
+    /// The size of the file, if known
+    size: Option<u64>,
+
+    /// The DANDI ETag or MD5 digest that the file's content was verified
+    /// against, if known
+    checksum: Option<String>,
+
+    /// The URL that the file was downloaded from (absent for virtual files)
+    url: Option<String>,
+}
+
+impl ManifestEntry {
+    /// Returns `true` if the two entries describe the same file content
+    fn describes(&self, other: &ManifestEntry) -> bool {
+        self.path == other.path
+            && self.size == other.size
+            && self.checksum.is_some()
+            && self.checksum == other.checksum
+    }
+}
+
+/// The manifest of files mirrored into a directory, stored as JSON Lines
+#[derive(Debug)]
+struct Manifest {
+    /// The path to the manifest file
+    path: PathBuf,
+
+    /// The entries read from the manifest file at the start of the run,
+    /// keyed by path
+    previous: BTreeMap<String, ManifestEntry>,
+
+    /// The entries recorded during the current run
+    recorded: BTreeMap<String, ManifestEntry>,
+
+    /// The manifest file, opened for appending once the first entry of the
+    /// run is recorded
+    file: Option<tokio::fs::File>,
+}
+
+impl Manifest {
+    /// Read the manifest at `path`, if it exists.  If an entry for a path
+    /// occurs more than once, the last one wins.
+    async fn load(path: PathBuf) -> Result<Manifest, MirrorError> {
+        let mut previous = BTreeMap::new();
+        match tokio::fs::read_to_string(&path).await {
+            Ok(s) => {
+                for (i, line) in s.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let entry = serde_json::from_str::<ManifestEntry>(line).map_err(|source| {
+                        MirrorError::Manifest {
+                            path: path.clone(),
+                            line: i + 1,
+                            source,
+                        }
+                    })?;
+                    previous.insert(entry.path.clone(), entry);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(source) => return Err(MirrorError::Io { path, source }),
+        }
+        Ok(Manifest {
+            path,
+            previous,
+            recorded: BTreeMap::new(),
+            file: None,
+        })
+    }
+
+    /// Append `entry` to the manifest file
+    async fn record(&mut self, entry: ManifestEntry) -> Result<(), MirrorError> {
+        let io_err = |source| MirrorError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let mut line = serde_json::to_string(&entry).expect("manifest entry should serialize");
+        line.push('\n');
+        let fp = match self.file {
+            Some(ref mut fp) => fp,
+            None => self.file.insert(
+                tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .await
+                    .map_err(io_err)?,
+            ),
+        };
+        fp.write_all(line.as_bytes()).await.map_err(io_err)?;
+        fp.flush().await.map_err(io_err)?;
+        self.recorded.insert(entry.path.clone(), entry);
+        Ok(())
+    }
+
+    /// Rewrite the manifest file so that it lists only the entries recorded
+    /// during the current run, sorted by path
+    async fn finish(mut self) -> Result<(), MirrorError> {
+        drop(self.file.take());
+        let mut s = String::new();
+        for entry in self.recorded.values() {
+            s.push_str(&serde_json::to_string(entry).expect("manifest entry should serialize"));
+            s.push('\n');
+        }
+        let tmp = partial_path(&self.path);
+        write_file(&tmp, s.as_bytes()).await?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .map_err(|source| MirrorError::Io {
+                path: self.path.clone(),
+                source,
+            })
+    }
+}
+
+/// Error returned by [`DandiDav::mirror()`]
+#[derive(Debug, Error)]
+pub(crate) enum MirrorError {
+    #[error("invalid or nonexistent path {0:?}")]
+    InvalidPath(String),
+    #[error(transparent)]
+    Dav(#[from] DavError),
+    #[error("failed to download file")]
+    Download(#[from] DandiError),
+    #[error("download from {url} ended early at offset {offset}")]
+    Truncated { url: String, offset: u64 },
+    #[error("checksum mismatch for {}: expected {expected}, got {actual}", path.display())]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    #[error("failed to parse line {line} of manifest {}", path.display())]
+    Manifest {
+        path: PathBuf,
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("I/O error on {}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
@@ -1,30 +1,77 @@
 //! The WebDAV component of `dandidav`
+mod assets;
+mod cache;
+mod copy;
 mod html;
+mod instance;
+mod json;
+mod local;
+mod mirror;
 mod path;
+mod source;
+mod sync;
 mod types;
 mod util;
+mod walk;
 mod xml;
-pub(crate) use self::html::Templater;
+pub(crate) use self::cache::PublishedCache;
+use self::cache::PublishedKey;
+pub(crate) use self::copy::CopyConfig;
 use self::html::*;
+pub(crate) use self::html::{
+    parse_timestamp_format, parse_utc_offset, ArchiveLabel, Templater, TimestampStyle,
+};
+pub(crate) use self::instance::{Instance, InstanceSpec};
+use self::json::{JsonAssetRecord, JsonListing, JsonSizeEstimate, JsonTree};
+use self::local::LocalError;
+pub(crate) use self::local::{LocalMountSpec, LocalSource};
+pub(crate) use self::path::PathConfig;
 use self::path::*;
+use self::source::DavDataSource;
+pub(crate) use self::sync::SyncSnapshots;
 use self::types::*;
 use self::util::*;
+pub(crate) use self::util::{
+    BodySize, DandisetFilter, ErrorDetails, RedirectTargetSize, RequestTimeouts, RootBehavior,
+};
+use self::walk::WalkLimits;
 use self::xml::*;
-use crate::consts::{DAV_XML_CONTENT_TYPE, HTML_CONTENT_TYPE};
+use crate::accesslog::PathKind;
+use crate::consts::{
+    BIDS_MAX_DEPTH, BIDS_MAX_LISTINGS, DAV_XML_CONTENT_TYPE, DRAFT_LISTING_ATTEMPTS,
+    HTML_CONTENT_TYPE, INFINITE_DEPTH_CONCURRENCY, INLINE_PREVIEW_EXTENSIONS, JSONL_CONTENT_TYPE,
+    JSON_CONTENT_TYPE, MAX_SEARCH_RESULTS, MAX_TREE_RESOURCES, PRIVATE_CACHE_CONTROL,
+    TEXT_CONTENT_TYPE, TREE_CONCURRENCY,
+};
 use crate::dandi::*;
+use crate::httputil::HttpError;
+use crate::metrics::metrics;
 use crate::paths::Component;
 use crate::paths::PurePath;
 use crate::zarrman::*;
 use axum::{
     body::Body,
     extract::Request,
-    http::{header::CONTENT_TYPE, response::Response, StatusCode},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, VARY, X_CONTENT_TYPE_OPTIONS},
+        response::Response,
+        HeaderValue, Method, StatusCode,
+    },
     response::{IntoResponse, Redirect},
     RequestExt,
 };
-use futures_util::TryStreamExt;
+use bytes::Bytes;
+use futures_util::{future::ready, stream, stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use http_body::Body as _;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
 use std::convert::Infallible;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::time::Instant;
 
 /// HTTP headers to include in all responses for WebDAV resources
 const WEBDAV_RESPONSE_HEADERS: [(&str, &str); 2] = [
@@ -33,17 +80,53 @@ const WEBDAV_RESPONSE_HEADERS: [(&str, &str); 2] = [
     ("DAV", "1, 3"),
 ];
 
+/// HTTP headers to include in responses to `OPTIONS` requests for
+/// collections.  Collections are rendered as HTML or JSON listings, for which
+/// byte ranges are not supported.
+const COLLECTION_OPTIONS_HEADERS: [(&str, &str); 3] = [
+    ("Allow", "GET, HEAD, OPTIONS, PROPFIND"),
+    ("DAV", "1, 3"),
+    ("Accept-Ranges", "none"),
+];
+
+/// HTTP headers to include in responses to `OPTIONS` requests for Dandiset
+/// versions, which additionally support `sync-collection` reports
+const VERSION_OPTIONS_HEADERS: [(&str, &str); 3] = [
+    ("Allow", "GET, HEAD, OPTIONS, PROPFIND, REPORT"),
+    ("DAV", "1, 3"),
+    ("Accept-Ranges", "none"),
+];
+
+/// HTTP headers to include in responses to `OPTIONS` requests for
+/// non-collection resources, which are either served directly (with support
+/// for byte ranges) or redirected to a download URL that supports byte
+/// ranges
+const ITEM_OPTIONS_HEADERS: [(&str, &str); 3] = [
+    ("Allow", "GET, HEAD, OPTIONS, PROPFIND"),
+    ("DAV", "1, 3"),
+    ("Accept-Ranges", "bytes"),
+];
+
+/// HTTP headers to include in responses to `OPTIONS` requests for
+/// non-collection resources when `COPY` requests are enabled
+const COPYABLE_ITEM_OPTIONS_HEADERS: [(&str, &str); 3] = [
+    ("Allow", "GET, HEAD, OPTIONS, PROPFIND, COPY"),
+    ("DAV", "1, 3"),
+    ("Accept-Ranges", "bytes"),
+];
+
 /// Manager for handling WebDAV requests
 pub(crate) struct DandiDav {
-    /// A client for fetching data from the Dandi Archive
-    pub(crate) dandi: DandiClient,
+    /// Clients for fetching data from each of the DANDI Archive instances
+    /// being served.  This must include the [`Instance::Primary`] instance.
+    pub(crate) archives: BTreeMap<Instance, DandiClient>,
 
     /// A client for fetching data from
     /// <https://github.com/dandi/zarr-manifests>
     pub(crate) zarrman: ZarrManClient,
 
     /// Manager for templating of HTML responses
-    pub(crate) templater: Templater,
+    pub(crate) templater: Arc<Templater>,
 
     /// Whether `GET` requests for blob assets should be responded to with
     /// redirects to S3 (`true`) or to Archive download URLs that then redirect
@@ -54,6 +137,66 @@ pub(crate) struct DandiDav {
     /// do not support multi-step redirects, so setting this to `true` is
     /// necessary to allow such clients to download from `dandidav`.
     pub(crate) prefer_s3_redirects: bool,
+
+    /// How to respond to `GET` requests for the root collection.  This does
+    /// not affect `PROPFIND` requests.
+    pub(crate) root_behavior: RootBehavior,
+
+    /// Whether each asset in a Dandiset version should be accompanied by a
+    /// virtual `{path}.dandi.json` file containing the asset's metadata
+    pub(crate) asset_sidecars: bool,
+
+    /// The maximum size in bytes of text-like assets & Zarr entries (as
+    /// determined by [`INLINE_PREVIEW_EXTENSIONS`]) that `GET` requests are
+    /// answered for by fetching the resource's content and serving it as
+    /// plain text rather than by redirecting, so that such files can be
+    /// viewed directly in a browser.  If this is `None`, all such requests
+    /// are redirected.
+    pub(crate) inline_preview_max_bytes: Option<u64>,
+
+    /// The maximum number of resources to report in response to a `PROPFIND`
+    /// request with an infinite `Depth`.  If this is `None`, such requests
+    /// are refused with a 403 response.
+    pub(crate) infinite_depth_limit: Option<usize>,
+
+    /// Time limits for handling requests, by the kind of request
+    pub(crate) timeouts: RequestTimeouts,
+
+    /// A memo of the asset path lookups made while handling the current
+    /// request, so that no path is looked up more than once per request.
+    /// [`DandiDav::handle_request()`] gives each request a fresh memo.
+    pub(crate) path_memo: PathMemo,
+
+    /// A cache of listings of published Dandiset versions and the resources
+    /// within them, or `None` if such caching is disabled.  The cache is not
+    /// consulted when handling requests made with client-supplied Archive API
+    /// tokens.
+    pub(crate) published_cache: Option<PublishedCache>,
+
+    /// Where & how non-collection resources may be copied to external
+    /// WebDAV servers in response to `COPY` requests, or `None` if such
+    /// requests are refused
+    pub(crate) copy: Option<CopyConfig>,
+
+    /// Snapshots of Dandiset versions as of the sync tokens issued for them
+    /// in responses to `sync-collection` reports
+    pub(crate) sync_snapshots: SyncSnapshots,
+
+    /// Whether the contents of Zarr assets in Dandiset versions on the
+    /// primary Archive instance should be served from the Zarrs' manifests
+    /// (when present in the manifest tree) rather than by listing S3
+    pub(crate) zarrs_via_manifests: bool,
+
+    /// Directory trees on the local filesystem to serve (read-only) at
+    /// top-level collections alongside the Archive hierarchies
+    pub(crate) local_mounts: Vec<LocalSource>,
+
+    /// Configuration for parsing request paths.  Its `local_mounts` must list
+    /// the names of the above `local_mounts`.
+    pub(crate) path_config: Arc<PathConfig>,
+
+    /// Which Dandisets are served
+    pub(crate) dandiset_filter: Arc<DandisetFilter>,
 }
 
 impl DandiDav {
@@ -64,32 +207,230 @@ impl DandiDav {
     /// header & request body are parsed & extracted and then passed to the
     /// appropriate method for the request's verb for dedicated handling.
     ///
+    /// If the request's [`TimeoutClass`] has a time limit in `timeouts` and
+    /// handling the request takes longer than that, a 504 response is
+    /// returned.  For responses whose bodies are streamed (i.e., whose sizes
+    /// are not known in advance), the limit also applies to producing the
+    /// body, which is aborted if it is not completed in time.
+    ///
     /// Any errors returned are logged and converted to 4xx or 5xx responses,
-    /// as appropriate.  The final response also has
-    /// [`WEBDAV_RESPONSE_HEADERS`] added.
+    /// as appropriate.  The final response also has any
+    /// [`WEBDAV_RESPONSE_HEADERS`] that it does not already have added.
     pub(crate) async fn handle_request(
-        &self,
+        self: &Arc<Self>,
         req: Request<Body>,
     ) -> Result<Response<Body>, Infallible> {
-        let resp = match req.extract::<DavRequest, _>().await {
-            Ok(DavRequest::Get { path, pathparts }) => self.get(&path, pathparts).await,
-            Ok(DavRequest::Propfind { path, depth, query }) => {
-                self.propfind(&path, depth, query).await
+        let method = match *req.method() {
+            Method::GET => "GET",
+            Method::OPTIONS => "OPTIONS",
+            ref m if m.as_str().eq_ignore_ascii_case("PROPFIND") => "PROPFIND",
+            ref m if m.as_str().eq_ignore_ascii_case("COPY") => "COPY",
+            ref m if m.as_str().eq_ignore_ascii_case("REPORT") => "REPORT",
+            _ => "other",
+        };
+        // Handle the request with a `DandiDav` that has a fresh memo of path
+        // lookups and, if the client supplied its own Archive API token,
+        // `DandiClient`s that use that token.
+        let token = client_token(req.headers());
+        let private = token.is_some();
+        let request_size = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.parse::<u64>().ok());
+        let this = Arc::new(self.for_request(token));
+        let trailing_slash = req.uri().path().ends_with('/');
+        let start = Instant::now();
+        let extracted = req
+            .extract_with_state::<DavRequest, _, _>(&*this.path_config)
+            .await;
+        let timeout_class = extracted
+            .as_ref()
+            .ok()
+            .and_then(|r| r.timeout_class(trailing_slash));
+        let timeout = timeout_class.and_then(|c| this.timeouts.get(c));
+        let path_kind = match extracted {
+            Ok(
+                DavRequest::Get { ref path, .. }
+                | DavRequest::Propfind { ref path, .. }
+                | DavRequest::Copy { ref path, .. }
+                | DavRequest::Report { ref path, .. }
+                | DavRequest::Options {
+                    path: Some(ref path),
+                },
+            ) => path.kind(),
+            Ok(DavRequest::Options { path: None }) => "any",
+            Err(_) => "invalid",
+        };
+        let propfind_path = match extracted {
+            Ok(DavRequest::Propfind { ref path, .. }) => Some(path.clone()),
+            _ => None,
+        };
+        if let Some(size) = request_size {
+            metrics().record_request_size(method, path_kind, size);
+        }
+        let handled = async {
+            match extracted {
+                Ok(DavRequest::Get {
+                    path,
+                    pathparts,
+                    range,
+                    conditions,
+                    format,
+                    page,
+                    sort,
+                    filter,
+                    tree: None,
+                    estimate: false,
+                    disposition,
+                }) => {
+                    this.get(
+                        &path,
+                        pathparts,
+                        range,
+                        &conditions,
+                        format,
+                        page,
+                        sort,
+                        filter.as_ref(),
+                        disposition,
+                    )
+                    .await
+                }
+                Ok(DavRequest::Get {
+                    path,
+                    tree: Some(tree),
+                    estimate: false,
+                    ..
+                }) => this.get_tree(&path, tree).await,
+                Ok(DavRequest::Get {
+                    path,
+                    estimate: true,
+                    ..
+                }) => this.estimate_size(&path).await,
+                Ok(DavRequest::Propfind {
+                    path,
+                    depth,
+                    query,
+                    conditions,
+                    filter,
+                }) => {
+                    this.propfind(&path, depth, query, &conditions, filter)
+                        .await
+                }
+                Ok(DavRequest::Copy {
+                    path,
+                    destination,
+                    overwrite,
+                    credentials,
+                }) => this.copy(&path, destination, overwrite, credentials).await,
+                Ok(DavRequest::Report { path, query }) => this.report(&path, query).await,
+                Ok(DavRequest::Options { path }) => this.options(path.as_ref()).await,
+                Err(r) => Ok(r),
+            }
+        };
+        let resp = match timeout {
+            Some(timeout) => {
+                let deadline = start + timeout;
+                match tokio::time::timeout_at(deadline, handled).await {
+                    Ok(Ok(r)) if r.body().size_hint().exact().is_none() => {
+                        Ok(with_body_deadline(r, deadline))
+                    }
+                    Ok(r) => r,
+                    Err(_) => Err(DavError::Timeout { timeout }),
+                }
             }
-            Ok(DavRequest::Options) => Ok(StatusCode::NO_CONTENT.into_response()),
-            Err(r) => Ok(r),
+            None => handled.await,
         };
         let resp = resp.unwrap_or_else(|e| {
                 let class = e.class();
+                let condition = propfind_path
+                    .as_ref()
+                    .and_then(|path| error_condition(&e, path));
                 let e = anyhow::Error::from(e);
                 tracing::info!(error = ?e, status = class.to_status().as_u16(), "Error processing request");
-                if class == ErrorClass::NotFound {
+                let message = format!("{e:?}");
+                let mut resp = if let Some(body) = condition {
+                    (
+                        StatusCode::NOT_FOUND,
+                        [(CONTENT_TYPE, DAV_XML_CONTENT_TYPE)],
+                        body,
+                    )
+                        .into_response()
+                } else if class == ErrorClass::NotFound {
                     not_found()
+                } else if class == ErrorClass::Unauthorized {
+                    unauthorized()
                 } else {
-                    (class.to_status(), format!("{e:?}")).into_response()
-                }
+                    (class.to_status(), message.clone()).into_response()
+                };
+                resp.extensions_mut().insert(ErrorDetails { class, message });
+                resp
             });
-        Ok((WEBDAV_RESPONSE_HEADERS, resp).into_response())
+        metrics().record_request(method, path_kind, resp.status().as_u16());
+        let mut resp = record_response_size(resp, path_kind);
+        resp.extensions_mut().insert(PathKind(path_kind));
+        if resp.headers().contains_key(CACHE_CONTROL) {
+            // Any request may carry a client token that changes what the
+            // response describes, so shared caches must not serve a response
+            // to one client for another client's request.
+            resp.headers_mut()
+                .append(VARY, HeaderValue::from_static("Authorization"));
+            if private {
+                // Responses obtained with a client's own token may describe
+                // resources that other clients cannot access.
+                resp.headers_mut().insert(
+                    CACHE_CONTROL,
+                    HeaderValue::from_static(PRIVATE_CACHE_CONTROL),
+                );
+            }
+        }
+        for (name, value) in WEBDAV_RESPONSE_HEADERS {
+            resp.headers_mut()
+                .entry(name)
+                .or_insert_with(|| HeaderValue::from_static(value));
+        }
+        Ok(resp)
+    }
+
+    /// Return a copy of `self` for handling a single request, with an empty
+    /// `path_memo`.  If `token` is non-`None`, the copy makes requests to the
+    /// Archive instances using the client-supplied API token `token` and does
+    /// not use the `published_cache`.
+    fn for_request(&self, token: Option<ApiToken>) -> DandiDav {
+        DandiDav {
+            archives: self
+                .archives
+                .iter()
+                .map(|(instance, client)| {
+                    let client = match token {
+                        Some(ref token) => client.with_token(token.clone()),
+                        None => client.clone(),
+                    };
+                    (instance.clone(), client)
+                })
+                .collect(),
+            zarrman: self.zarrman.clone(),
+            templater: Arc::clone(&self.templater),
+            prefer_s3_redirects: self.prefer_s3_redirects,
+            root_behavior: self.root_behavior.clone(),
+            asset_sidecars: self.asset_sidecars,
+            inline_preview_max_bytes: self.inline_preview_max_bytes,
+            infinite_depth_limit: self.infinite_depth_limit,
+            timeouts: self.timeouts,
+            path_memo: PathMemo::default(),
+            published_cache: if token.is_some() {
+                None
+            } else {
+                self.published_cache.clone()
+            },
+            copy: self.copy.clone(),
+            sync_snapshots: self.sync_snapshots.clone(),
+            zarrs_via_manifests: self.zarrs_via_manifests,
+            local_mounts: self.local_mounts.clone(),
+            path_config: Arc::clone(&self.path_config),
+            dandiset_filter: Arc::clone(&self.dandiset_filter),
+        }
     }
 
     /// Handle a `GET` request for the given `path`.
@@ -97,140 +438,991 @@ impl DandiDav {
     /// `pathparts` contains the individual components of the request URL path
     /// prior to parsing into `path`.  It is needed for things like breadcrumbs
     /// in HTML views of collection resources.
+    ///
+    /// `range` is the byte range requested via the `Range` header, if any; it
+    /// is only honored for resources whose content is served directly.
+    ///
+    /// `conditions` contains the request's conditional headers; if they
+    /// indicate that the client's copy of a non-collection resource is up to
+    /// date, a 304 response is returned.  Listings of collections are given
+    /// validators by [`DandiDav::validators()`] where possible, and if the
+    /// client already has a listing with a matching entity tag, a 304
+    /// response is returned; for resources that never change, this happens
+    /// before anything is fetched from upstream.  Validators are only
+    /// computed once the resource has been resolved, and never for
+    /// redirects, so that requests for nonexistent resources and redirects
+    /// do not incur extra upstream lookups.  Responses other than redirects
+    /// are also given `Cache-Control` headers allowing caches to reuse them
+    /// for resources that never change and requiring revalidation otherwise.
+    ///
+    /// `format` determines whether collections are listed as HTML pages or
+    /// as JSON documents.
+    ///
+    /// `page` is the page of a collection's entries to show in an HTML
+    /// listing, if pagination was requested.  For Dandiset versions and
+    /// folders within them, only the requested page of entries is fetched
+    /// from the Archive; for other collections, all entries are fetched and
+    /// then paginated.
+    ///
+    /// `sort` is the order in which to list a collection's entries in an HTML
+    /// listing, if one was requested.  As sorting requires all of the
+    /// entries, a sorted listing is never streamed, and only the requested
+    /// page of it is shown after fetching & sorting all entries.
+    ///
+    /// `filter` is a filter on the names of a collection's entries to list,
+    /// if one was requested.  When combined with `page`, all of the entries
+    /// are fetched & filtered before paginating.
+    ///
+    /// `disposition` is how the client should present a non-collection
+    /// resource, if requested.  Resources generated by `dandidav` are served
+    /// with a corresponding `Content-Disposition` header, while redirects to
+    /// blob assets go to Archive download URLs that set the header.
+    #[allow(clippy::too_many_arguments)]
     async fn get(
         &self,
         path: &DavPath,
         pathparts: Vec<Component>,
+        range: Option<ByteRange>,
+        conditions: &Conditions,
+        format: ListingFormat,
+        page: Option<PageRequest>,
+        sort: Option<SortRequest>,
+        filter: Option<&MatchFilter>,
+        disposition: Option<Disposition>,
     ) -> Result<Response<Body>, DavError> {
-        match self.get_resource_with_children(path).await? {
-            DavResourceWithChildren::Collection { children, .. } => {
-                let html = self.templater.render_collection(children, pathparts)?;
-                Ok(([(CONTENT_TYPE, HTML_CONTENT_TYPE)], html).into_response())
-            }
-            DavResourceWithChildren::Item(DavItem {
-                content_type,
-                content: DavContent::Blob(blob),
-                ..
-            }) => Ok(([(CONTENT_TYPE, content_type)], blob).into_response()),
-            DavResourceWithChildren::Item(DavItem {
-                content: DavContent::Redirect(redir),
-                ..
-            }) => Ok(
-                Redirect::temporary(redir.get_url(self.prefer_s3_redirects).as_str())
-                    .into_response(),
-            ),
-            DavResourceWithChildren::Item(DavItem {
-                content: DavContent::Missing,
-                ..
-            }) => {
+        if *path == DavPath::Root {
+            match self.root_behavior {
+                RootBehavior::Render => (),
+                RootBehavior::Redirect(ref url) => {
+                    return Ok(Redirect::temporary(url).into_response())
+                }
+                RootBehavior::NotFound => return Ok(not_found()),
+            }
+        }
+        // Everything about the request that affects the representation of a
+        // listing
+        let variant = (format, page, sort, filter);
+        if Self::is_immutable(path) {
+            // The validators of resources that never change are computed
+            // without contacting upstream, so a conditional request for a
+            // listing of one can be answered before fetching anything.
+            let validators = self.validators(path, variant).await?;
+            if validators.is_listed_in(conditions) {
+                return Ok(validators.not_modified());
+            }
+        }
+        let res = match format {
+            ListingFormat::Html if sort.is_none() && (filter.is_none() || page.is_none()) => {
+                match self.get_listing(path, page).await? {
+                    Listing::Streamed { children, .. } => {
+                        let validators = self.validators(path, variant).await?;
+                        if validators.is_listed_in(conditions) {
+                            return Ok(validators.not_modified());
+                        }
+                        let children = match filter.cloned() {
+                            Some(filter) => children
+                                .try_filter(move |r| ready(filter.matches_resource(r)))
+                                .boxed(),
+                            None => children,
+                        };
+                        let mut resp = self.stream_html_listing(children, pathparts, filter)?;
+                        validators.insert(resp.headers_mut());
+                        return Ok(resp);
+                    }
+                    Listing::Paged {
+                        children,
+                        page,
+                        has_next,
+                    } => {
+                        let validators = self.validators(path, variant).await?;
+                        if validators.is_listed_in(conditions) {
+                            return Ok(validators.not_modified());
+                        }
+                        // Archive-side pagination is only used when there's no
+                        // filter.
+                        let html = self.templater.render_collection(
+                            children,
+                            pathparts,
+                            Some(PageNav::new(page, has_next, None, None)),
+                            None,
+                            None,
+                        )?;
+                        let mut resp = html_listing_response(html);
+                        validators.insert(resp.headers_mut());
+                        return Ok(resp);
+                    }
+                    Listing::Fetched(res) => res,
+                }
+            }
+            ListingFormat::Html | ListingFormat::Json => {
+                self.get_resource_with_children(path).await?
+            }
+        };
+        let res = match (res, filter) {
+            (DavResourceWithChildren::Collection { col, mut children }, Some(filter)) => {
+                children.retain(|r| filter.matches_resource(r));
+                DavResourceWithChildren::Collection { col, children }
+            }
+            (res, _) => res,
+        };
+        let item = match res {
+            DavResourceWithChildren::Collection { col, children } => {
+                let validators = self.validators(path, variant).await?;
+                if validators.is_listed_in(conditions) {
+                    return Ok(validators.not_modified());
+                }
+                let mut resp = match format {
+                    ListingFormat::Html => {
+                        let html = match page {
+                            Some(page) => self
+                                .templater
+                                .render_collection_page(children, pathparts, page, sort, filter)?,
+                            None => self
+                                .templater
+                                .render_collection(children, pathparts, None, sort, filter)?,
+                        };
+                        html_listing_response(html)
+                    }
+                    ListingFormat::Json => {
+                        let listing = JsonListing::new(col, children, self.prefer_s3_redirects);
+                        let body = serde_json::to_string(&listing)
+                            .expect("serializing a JsonListing should not fail");
+                        let size = body.len();
+                        with_body_size(
+                            ([(CONTENT_TYPE, JSON_CONTENT_TYPE), (VARY, "Accept")], body)
+                                .into_response(),
+                            size,
+                        )
+                    }
+                };
+                validators.insert(resp.headers_mut());
+                return Ok(resp);
+            }
+            DavResourceWithChildren::Item(item) => item,
+        };
+        let DavItem {
+            path: item_path,
+            mut content_type,
+            content,
+            size,
+            etag,
+            modified,
+            ..
+        } = item;
+        let (content, preview) = match content {
+            DavContent::Missing => {
                 // TODO: Log something
-                Ok(not_found())
+                return Ok(not_found());
+            }
+            DavContent::Redirect(redir) => {
+                // Zarr manifest entries are not associated with an instance,
+                // but their download URLs are never Archive API URLs, so the
+                // choice of client does not matter for them.
+                let client = self.dandi(path.instance().unwrap_or(&Instance::Primary))?;
+                let preview = if disposition == Some(Disposition::Attachment) {
+                    None
+                } else {
+                    self.fetch_preview(client, item_path.name_str(), &redir, size)
+                        .await
+                };
+                let Some(blob) = preview else {
+                    // Redirects carry no validators, so none are computed for
+                    // them.
+                    let url = client
+                        .authorize_download(
+                            &redir.get_url_with_disposition(self.prefer_s3_redirects, disposition),
+                        )
+                        .await?;
+                    let mut resp = Redirect::temporary(url.as_str()).into_response();
+                    if let Some(size) = size.and_then(|sz| u64::try_from(sz).ok()) {
+                        resp.extensions_mut().insert(RedirectTargetSize(size));
+                    }
+                    return Ok(resp);
+                };
+                content_type = TEXT_CONTENT_TYPE.to_owned();
+                (DavContent::Blob(blob), true)
+            }
+            content => (content, false),
+        };
+        let validators = self
+            .item_validators(path, format, etag.as_deref(), modified)
+            .await?;
+        if conditions.is_not_modified(validators.etag.as_deref(), validators.modified) {
+            return Ok(validators.not_modified());
+        }
+        let mut resp = match content {
+            DavContent::AssetManifest => self.assets_jsonl_response(path, range).await?,
+            DavContent::Blob(blob) => blob_response(content_type, blob, range),
+            DavContent::LocalFile(fspath) => {
+                local::file_response(content_type, &fspath, range).await?
+            }
+            DavContent::Missing | DavContent::Redirect(_) => unreachable!("handled above"),
+        };
+        validators.insert(resp.headers_mut());
+        insert_disposition(resp.headers_mut(), disposition, item_path.name_str());
+        if preview {
+            // Previews are untrusted content, so browsers must not sniff them
+            // as anything other than plain text.
+            resp.headers_mut()
+                .insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        }
+        Ok(resp)
+    }
+
+    /// If inline previews are enabled and the non-collection resource named
+    /// `name` of `size` bytes that redirects to `redir` is a small enough
+    /// text-like file, fetch & return its content.  Returns `None` if the
+    /// resource should be redirected to instead, including when fetching its
+    /// content fails.
+    async fn fetch_preview(
+        &self,
+        client: &DandiClient,
+        name: &str,
+        redir: &types::Redirect,
+        size: Option<i64>,
+    ) -> Option<Vec<u8>> {
+        let max_bytes = self.inline_preview_max_bytes?;
+        let size = size
+            .and_then(|sz| u64::try_from(sz).ok())
+            .filter(|&sz| sz <= max_bytes)?;
+        let name_lower = name.to_ascii_lowercase();
+        if !INLINE_PREVIEW_EXTENSIONS
+            .iter()
+            .any(|ext| name_lower.ends_with(ext))
+        {
+            return None;
+        }
+        let r = async {
+            let url = client
+                .authorize_download(redir.get_url(self.prefer_s3_redirects))
+                .await?;
+            client.download_range(&url, 0..size).await
+        }
+        .await;
+        match r {
+            Ok(blob) => Some(blob.to_vec()),
+            Err(e) => {
+                tracing::warn!(
+                    error = ?anyhow::Error::from(e),
+                    name,
+                    "Failed to fetch content for inline preview; redirecting instead",
+                );
+                None
             }
         }
     }
 
+    /// Handle a `GET` request for a JSON tree of the resource at `path` and
+    /// its descendants down to `tree.depth` levels beneath it.
+    ///
+    /// The hierarchy is walked with the children of up to
+    /// [`TREE_CONCURRENCY`] collections fetched at once.  If the tree would
+    /// contain more than [`MAX_TREE_RESOURCES`] resources, the request fails
+    /// with a 403 instead.
+    async fn get_tree(
+        self: &Arc<Self>,
+        path: &DavPath,
+        tree: TreeRequest,
+    ) -> Result<Response<Body>, DavError> {
+        if *path == DavPath::Root && !matches!(self.root_behavior, RootBehavior::Render) {
+            return Ok(not_found());
+        }
+        let limits = WalkLimits {
+            max_depth: Some(tree.depth),
+            max_resources: Some(MAX_TREE_RESOURCES),
+            concurrency: TREE_CONCURRENCY,
+        };
+        let mut resources = self
+            .walk(path, limits)
+            .try_collect::<Vec<_>>()
+            .await?
+            .into_iter();
+        let Some(root) = resources.next() else {
+            return Ok(not_found());
+        };
+        let tree = JsonTree::new(root, resources, tree.depth, self.prefer_s3_redirects);
+        let body = serde_json::to_string(&tree).expect("serializing a JsonTree should not fail");
+        let size = body.len();
+        Ok(with_body_size(
+            ([(CONTENT_TYPE, JSON_CONTENT_TYPE)], body).into_response(),
+            size,
+        ))
+    }
+
+    /// Handle a `GET` request for an estimate of the total size of the
+    /// Dandiset version at `path`, so that users can see how much they would
+    /// be downloading before starting a recursive download.  The estimate is
+    /// taken from the version information reported by the Archive, so the
+    /// version's assets are not enumerated.
+    ///
+    /// Requests for paths other than Dandiset versions receive a 400
+    /// response.
+    async fn estimate_size(&self, path: &DavPath) -> Result<Response<Body>, DavError> {
+        let DavPath::Version {
+            instance,
+            dandiset_id,
+            version,
+        } = path
+        else {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                "Size estimates are only available for Dandiset versions\n",
+            )
+                .into_response());
+        };
+        let handler = self
+            .get_version_handler(instance, dandiset_id, version)
+            .await?;
+        let v = handler.endpoint.get().await?;
+        let estimate = JsonSizeEstimate::new(
+            &version_path(instance, dandiset_id, version),
+            dandiset_id,
+            v,
+        );
+        let body = serde_json::to_string(&estimate)
+            .expect("serializing a JsonSizeEstimate should not fail");
+        let size = body.len();
+        Ok(with_body_size(
+            ([(CONTENT_TYPE, JSON_CONTENT_TYPE)], body).into_response(),
+            size,
+        ))
+    }
+
+    /// Return a response containing an HTML listing of a collection in which
+    /// the rows for the collection's children are streamed to the client as
+    /// they are yielded by `children`, in the order yielded.
+    /// `pathparts` and `filter` are as for [`DandiDav::get()`]; `children`
+    /// must already have been filtered.
+    ///
+    /// As the status line has already been sent by the time `children` is
+    /// polled, any errors it yields are logged and cause the response body to
+    /// be aborted, so that clients do not mistake a partial listing for a
+    /// complete one.
+    fn stream_html_listing(
+        &self,
+        children: BoxStream<'static, Result<DavResource, DavError>>,
+        pathparts: Vec<Component>,
+        filter: Option<&MatchFilter>,
+    ) -> Result<Response<Body>, TemplateError> {
+        let chunks = Arc::clone(&self.templater)
+            .render_collection_stream(children, pathparts, filter)?
+            .map_err(|e: DavError| {
+                let e = anyhow::Error::from(e);
+                tracing::info!(error = ?e, "Error while streaming HTML collection listing; aborting response");
+                e
+            });
+        Ok((
+            [(CONTENT_TYPE, HTML_CONTENT_TYPE), (VARY, "Accept")],
+            Body::from_stream(chunks),
+        )
+            .into_response())
+    }
+
+    /// Return a response containing the `assets.jsonl` file for the Dandiset
+    /// version at `path`, restricted to `range` if it is non-`None`, in which
+    /// the records for the version's assets are streamed to the client as
+    /// they are fetched from the Archive.
+    ///
+    /// See [`generated_file_response()`] for how ranges and errors are
+    /// handled.
+    async fn assets_jsonl_response(
+        &self,
+        path: &DavPath,
+        range: Option<ByteRange>,
+    ) -> Result<Response<Body>, DavError> {
+        let DavPath::AssetsJsonl {
+            instance,
+            dandiset_id,
+            version,
+        } = path
+        else {
+            return Ok(not_found());
+        };
+        let handler = self
+            .get_version_handler(instance, dandiset_id, version)
+            .await?;
+        let prefer_s3_redirects = self.prefer_s3_redirects;
+        let generate = || {
+            handler
+                .endpoint
+                .get_all_assets()
+                .map_ok(move |asset| {
+                    Bytes::from(JsonAssetRecord::new(&asset, prefer_s3_redirects).to_line())
+                })
+                .map_err(DavError::from)
+        };
+        generated_file_response(JSONL_CONTENT_TYPE, generate, range).await
+    }
+
+    /// Returns `true` if the resource at `path` never changes, i.e., if
+    /// `path` is a published Dandiset version, a path within one, or a path
+    /// to or within a Zarr manifest
+    fn is_immutable(path: &DavPath) -> bool {
+        match path {
+            DavPath::ZarrPath { path } => ZarrManClient::is_immutable(path),
+            _ => path
+                .version()
+                .is_some_and(|(_, _, v)| matches!(v, VersionSpec::Published(_))),
+        }
+    }
+
+    /// Compute the validators for responses describing the resource at
+    /// `path`.  `variant` identifies the kind of response (e.g., the listing
+    /// format & query parameters or the `PROPFIND` query), so that different
+    /// kinds of responses for the same resource receive different entity
+    /// tags.
+    ///
+    /// Resources that never change (as determined by
+    /// [`DandiDav::is_immutable()`]) receive entity tags computed solely from
+    /// `path`, `variant`, the version of `dandidav`, and the server's
+    /// configuration, so that conditional requests for them can be answered
+    /// without contacting any upstream services.  Resources belonging to a
+    /// draft or "latest" Dandiset version receive entity tags that also
+    /// incorporate the version's modification time, which is also returned as
+    /// the resources' modification time; as any change to a version's
+    /// metadata or assets updates the version's modification time, this
+    /// suffices to detect changes.  All other resources receive no
+    /// validators.
+    async fn validators<T: Hash>(
+        &self,
+        path: &DavPath,
+        variant: T,
+    ) -> Result<Validators, DavError> {
+        if Self::is_immutable(path) {
+            return Ok(Validators {
+                etag: Some(self.entity_tag(path, variant, None)),
+                modified: None,
+                immutable: true,
+            });
+        }
+        let Some((instance, dandiset_id, version_spec)) = path.version() else {
+            return Ok(Validators::default());
+        };
+        let modified = self
+            .get_version_handler(instance, dandiset_id, version_spec)
+            .await?
+            .endpoint
+            .get()
+            .await?
+            .modified;
+        Ok(Validators {
+            etag: Some(self.entity_tag(path, variant, Some(modified))),
+            modified: Some(modified),
+            immutable: false,
+        })
+    }
+
+    /// Compute the validators for a response serving the content of the
+    /// non-collection resource at `path`, whose own entity tag & modification
+    /// time (if known) are `etag` and `modified`.  The resource's Dandiset
+    /// version is only looked up (via [`DandiDav::validators()`]) if the
+    /// resource lacks either of its own validators or never changes.
+    async fn item_validators<T: Hash>(
+        &self,
+        path: &DavPath,
+        variant: T,
+        etag: Option<&str>,
+        modified: Option<OffsetDateTime>,
+    ) -> Result<Validators, DavError> {
+        if etag.is_some() && modified.is_some() && !Self::is_immutable(path) {
+            return Ok(Validators {
+                etag: etag.map(String::from),
+                modified,
+                immutable: false,
+            });
+        }
+        Ok(self
+            .validators(path, variant)
+            .await?
+            .for_item(etag, modified))
+    }
+
+    /// Compute an entity tag from `path`, `variant`, the modification time of
+    /// the resource's Dandiset version (if relevant), the version of
+    /// `dandidav`, and the server's configuration (including which Dandisets
+    /// & local directories are served).
+    ///
+    /// If `modified` is `None` (i.e., the resource never changes), the
+    /// representation is fully determined by the hashed inputs, and so a
+    /// strong entity tag is returned.  Otherwise, the tag is weak, as the
+    /// representation may differ in minor ways (e.g., the order of a
+    /// folder's entries) between requests.
+    fn entity_tag<T: Hash>(
+        &self,
+        path: &DavPath,
+        variant: T,
+        modified: Option<OffsetDateTime>,
+    ) -> String {
+        let mut hasher = DefaultHasher::new();
+        env!("VERSION_WITH_GIT").hash(&mut hasher);
+        self.templater.fingerprint().hash(&mut hasher);
+        self.prefer_s3_redirects.hash(&mut hasher);
+        self.asset_sidecars.hash(&mut hasher);
+        self.inline_preview_max_bytes.hash(&mut hasher);
+        self.zarrs_via_manifests.hash(&mut hasher);
+        self.dandiset_filter.hash(&mut hasher);
+        self.local_mounts.hash(&mut hasher);
+        path.hash(&mut hasher);
+        variant.hash(&mut hasher);
+        modified.hash(&mut hasher);
+        let tag = hasher.finish();
+        if modified.is_some() {
+            format!("W/\"{tag:016x}\"")
+        } else {
+            format!("\"{tag:016x}\"")
+        }
+    }
+
+    /// Handle an `OPTIONS` request for the given `path`, or for the server as
+    /// a whole if `path` is `None` (as for `OPTIONS *`).
+    ///
+    /// The resource at `path` is looked up so that requests for nonexistent
+    /// resources receive 404 responses, and the headers of the response are
+    /// tailored to whether the resource is a collection, as some WebDAV
+    /// clients use `OPTIONS` responses for capability detection before
+    /// mounting.
+    async fn options(&self, path: Option<&DavPath>) -> Result<Response<Body>, DavError> {
+        let Some(path) = path else {
+            return Ok(StatusCode::NO_CONTENT.into_response());
+        };
+        let headers = match self.get_resource(path).await? {
+            DavResource::Collection(_) if matches!(path, DavPath::Version { .. }) => {
+                VERSION_OPTIONS_HEADERS
+            }
+            DavResource::Collection(_) => COLLECTION_OPTIONS_HEADERS,
+            DavResource::Item(DavItem {
+                content: DavContent::Missing,
+                ..
+            }) => return Ok(not_found()),
+            DavResource::Item(_) if self.copy.is_some() => COPYABLE_ITEM_OPTIONS_HEADERS,
+            DavResource::Item(_) => ITEM_OPTIONS_HEADERS,
+        };
+        Ok((StatusCode::NO_CONTENT, headers).into_response())
+    }
+
     /// Handle a `PROPFIND` request for the given `path`.  `depth` is the value
     /// of the `Depth` header, and `query` is the parsed request body (with an
-    /// empty body already defaulted to "allprop" as per the RFC).
+    /// empty body already defaulted to "allprop" as per the RFC).  If
+    /// `filter` is non-`None`, only those resources below `path` whose names
+    /// match it are reported on; the resource at `path` is always reported.
+    ///
+    /// Responses for finite depths are given validators by
+    /// [`DandiDav::validators()`] (distinguished by depth & query) and
+    /// `Cache-Control` headers, and if `conditions` lists a matching entity
+    /// tag, a 304 response is returned before walking the hierarchy.
+    ///
+    /// Responses with a `Depth` of 1 for collections outside of draft
+    /// Dandiset versions are streamed to the client as the collection's
+    /// children are fetched, so that listings of collections with very many
+    /// children start arriving quickly without being held in memory in full.
+    /// Listings in draft versions are instead assembled in full first so that
+    /// they can be checked for consistency; see
+    /// [`DandiDav::walk_draft_snapshot()`].
     async fn propfind(
-        &self,
+        self: &Arc<Self>,
         path: &DavPath,
-        depth: FiniteDepth,
+        depth: Depth,
         query: PropFind,
+        conditions: &Conditions,
+        filter: Option<MatchFilter>,
     ) -> Result<Response<Body>, DavError> {
-        let resources = match depth {
-            FiniteDepth::Zero => vec![self.get_resource(path).await?],
-            FiniteDepth::One => self.get_resource_with_children(path).await?.into_vec(),
+        let limits = match depth {
+            Depth::Zero => WalkLimits::depth(0),
+            Depth::One => WalkLimits::depth(1),
+            Depth::Infinity => {
+                let Some(max_resources) = self.infinite_depth_limit else {
+                    return Ok(infinite_depth_forbidden());
+                };
+                return self
+                    .propfind_infinite(path, max_resources, query, filter)
+                    .await;
+            }
+        };
+        let validators = self.validators(path, (depth, &query)).await?;
+        if validators
+            .etag
+            .as_deref()
+            .is_some_and(|etag| conditions.lists_etag(etag))
+        {
+            return Ok(validators.not_modified());
+        }
+        let in_draft = matches!(path.version(), Some((_, _, VersionSpec::Draft)));
+        if depth == Depth::One && !in_draft {
+            let resources = match self.get_listing(path, None).await? {
+                Listing::Streamed { col, children } => {
+                    stream::once(ready(Ok(DavResource::Collection(col))))
+                        .chain(children)
+                        .boxed()
+                }
+                Listing::Fetched(DavResourceWithChildren::Collection { col, children }) => {
+                    stream::iter(
+                        std::iter::once(DavResource::Collection(col))
+                            .chain(children)
+                            .map(Ok),
+                    )
+                    .boxed()
+                }
+                Listing::Fetched(DavResourceWithChildren::Item(item)) => {
+                    stream::iter([Ok(DavResource::Item(item))]).boxed()
+                }
+                Listing::Paged { .. } => {
+                    unreachable!("get_listing() should not page when no page is requested")
+                }
+            };
+            let mut resp = stream_multistatus(resources, query, filter)?;
+            validators.insert(resp.headers_mut());
+            return Ok(resp);
+        }
+        let snapshot = if depth == Depth::One {
+            self.walk_draft_snapshot(path, limits).await?
+        } else {
+            Snapshot {
+                resources: self.walk(path, limits).try_collect::<Vec<_>>().await?,
+                modified: None,
+                unstable: false,
+            }
         };
-        let response = resources
+        let mut response = snapshot
+            .resources
             .into_iter()
-            .map(|r| query.find(&r))
+            .enumerate()
+            .filter(|(i, r)| *i == 0 || filter.as_ref().map_or(true, |f| f.matches_resource(r)))
+            .map(|(_, r)| query.find(&r))
             .collect::<Vec<_>>();
-        Ok((
+        if snapshot.unstable {
+            if let Some(r) = response.first_mut() {
+                r.add_property(Property::ListingUnstable, PropValue::Empty);
+            }
+        }
+        let mut resp = (
             StatusCode::MULTI_STATUS,
             [(CONTENT_TYPE, DAV_XML_CONTENT_TYPE)],
-            (Multistatus { response }).to_xml()?,
+            (Multistatus {
+                response,
+                sync_token: None,
+            })
+            .to_xml()?,
         )
-            .into_response())
+            .into_response();
+        if !snapshot.unstable
+            && snapshot
+                .modified
+                .map_or(true, |m| validators.modified == Some(m))
+        {
+            validators.insert(resp.headers_mut());
+        } else {
+            // The validators were computed from an earlier state of the
+            // version than the one listed, so they must not be used.
+            Validators::default().insert(resp.headers_mut());
+        }
+        Ok(resp)
+    }
+
+    /// Walk the hierarchy at `path` with the given limits, collecting the
+    /// resources into a [`Snapshot`].
+    ///
+    /// If `path` is in a draft Dandiset version, which may be modified while
+    /// its resources are being paginated through, the version's modification
+    /// time is fetched (bypassing the cache) before and after the walk.  If
+    /// it changed, the walk is retried (with a fresh memo of asset path
+    /// lookups) up to a total of [`DRAFT_LISTING_ATTEMPTS`] times, after
+    /// which the last walk's resources are returned with the snapshot marked
+    /// unstable.
+    async fn walk_draft_snapshot(
+        self: &Arc<Self>,
+        path: &DavPath,
+        limits: WalkLimits,
+    ) -> Result<Snapshot, DavError> {
+        let Some((instance, dandiset_id, version_spec @ VersionSpec::Draft)) = path.version()
+        else {
+            return Ok(Snapshot {
+                resources: self.walk(path, limits).try_collect::<Vec<_>>().await?,
+                modified: None,
+                unstable: false,
+            });
+        };
+        let handler = self
+            .get_version_handler(instance, dandiset_id, version_spec)
+            .await?;
+        let mut before = handler.fetch_modified().await?;
+        let mut dav = Arc::clone(self);
+        let mut attempt = 1;
+        loop {
+            let resources = dav.walk(path, limits).try_collect::<Vec<_>>().await?;
+            let after = handler.fetch_modified().await?;
+            if after == before {
+                return Ok(Snapshot {
+                    resources,
+                    modified: Some(after),
+                    unstable: false,
+                });
+            } else if attempt >= DRAFT_LISTING_ATTEMPTS {
+                tracing::info!(
+                    ?path,
+                    "Draft version kept changing while being listed; marking listing unstable",
+                );
+                return Ok(Snapshot {
+                    resources,
+                    modified: Some(after),
+                    unstable: true,
+                });
+            }
+            tracing::info!(?path, "Draft version changed while being listed; retrying",);
+            before = after;
+            dav = Arc::new(self.for_request(None));
+            attempt += 1;
+        }
+    }
+
+    /// Handle a `PROPFIND` request with an infinite `Depth` for the given
+    /// `path`, reporting on at most `max_resources` resources.  `filter` is as
+    /// for [`DandiDav::propfind()`]; resources that do not match it still
+    /// count towards `max_resources`, as they must still be traversed.
+    ///
+    /// The resource at `path` is fetched before responding so that errors in
+    /// fetching it can be reported via the response status.  After that, the
+    /// multistatus document is streamed to the client as the hierarchy is
+    /// walked.  As the status line has already been sent by that point, any
+    /// later errors (including exceeding `max_resources`) are logged and cause
+    /// the response body to be aborted, so that clients do not mistake a
+    /// partial listing for a complete one.
+    async fn propfind_infinite(
+        self: &Arc<Self>,
+        path: &DavPath,
+        max_resources: usize,
+        query: PropFind,
+        filter: Option<MatchFilter>,
+    ) -> Result<Response<Body>, DavError> {
+        let limits = WalkLimits {
+            max_depth: None,
+            max_resources: Some(max_resources),
+            concurrency: INFINITE_DEPTH_CONCURRENCY,
+        };
+        let mut resources = Box::pin(self.walk(path, limits));
+        let first = resources.try_next().await?;
+        let resources = stream::iter(first.map(Ok)).chain(resources);
+        Ok(stream_multistatus(resources, query, filter)?)
+    }
+
+    /// Return the data sources whose hierarchies are mounted beneath the root
+    /// collection, in the order in which they are listed in the root
+    fn sources(&self) -> impl Iterator<Item = &dyn DavDataSource> {
+        let zarrman: &dyn DavDataSource = &self.zarrman;
+        self.archives
+            .values()
+            .map(|client| -> &dyn DavDataSource { client })
+            .chain(std::iter::once(zarrman))
+            .chain(
+                self.local_mounts
+                    .iter()
+                    .map(|local| -> &dyn DavDataSource { local }),
+            )
+    }
+
+    /// Return the data source whose hierarchy contains `path`, failing if no
+    /// registered source is mounted at the path's top-level collection.
+    /// `path` must not be [`DavPath::Root`].
+    fn source_for(&self, path: &DavPath) -> Result<&dyn DavDataSource, UnknownMountError> {
+        let name = path
+            .mount_name()
+            .expect("root path should not be routed to a data source");
+        self.sources()
+            .find(|source| source.mount_name() == name)
+            .ok_or(UnknownMountError(name))
+    }
+
+    /// Fail if `path` is beneath a Dandiset that is excluded from being
+    /// served by the `dandiset_filter`
+    fn check_dandiset(&self, path: &DavPath) -> Result<(), HiddenDandisetError> {
+        match path.dandiset_id() {
+            Some(dandiset_id) if !self.dandiset_filter.allows(dandiset_id) => {
+                Err(HiddenDandisetError(dandiset_id.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Return the client for the primary Archive instance
+    pub(crate) fn primary_dandi(&self) -> &DandiClient {
+        self.archives
+            .get(&Instance::Primary)
+            .expect("primary Archive instance should always be served")
+    }
+
+    /// Return the client for the given Archive instance, failing if the
+    /// instance is not being served
+    fn dandi(&self, instance: &Instance) -> Result<&DandiClient, UnknownInstanceError> {
+        self.archives
+            .get(instance)
+            .ok_or_else(|| UnknownInstanceError(instance.clone()))
     }
 
     /// Obtain a handler for fetching resources for the given version of the
-    /// given Dandiset.  If `version` is `VersionSpec::Latest`, the most recent
+    /// given Dandiset on the given Archive instance.  If `version` is
+    /// `VersionSpec::Latest` or `VersionSpec::LatestRelease`, the most recent
     /// published version of the Dandiset is used.
     async fn get_version_handler<'a>(
         &'a self,
+        instance: &'a Instance,
         dandiset_id: &'a DandisetId,
         version_spec: &'a VersionSpec,
     ) -> Result<VersionHandler<'a>, DavError> {
-        let d = self.dandi.dandiset(dandiset_id.clone());
+        if !self.dandiset_filter.allows(dandiset_id) {
+            return Err(HiddenDandisetError(dandiset_id.clone()).into());
+        }
+        let d = self.dandi(instance)?.dandiset(dandiset_id.clone());
         let endpoint = match version_spec {
             VersionSpec::Draft => d.version(VersionId::Draft),
             VersionSpec::Published(v) => d.version(VersionId::Published(v.clone())),
-            VersionSpec::Latest => match d.get().await?.most_recent_published_version {
-                Some(DandisetVersion { version, .. }) => d.version(version),
-                None => {
-                    return Err(DavError::NoLatestVersion {
-                        dandiset_id: dandiset_id.clone(),
-                    })
+            VersionSpec::Latest | VersionSpec::LatestRelease => {
+                match d.get().await?.most_recent_published_version {
+                    Some(DandisetVersion { version, .. }) => d.version(version),
+                    None => {
+                        return Err(DavError::NoLatestVersion {
+                            dandiset_id: dandiset_id.clone(),
+                        })
+                    }
                 }
-            },
-        };
+            }
+        }
+        .with_memo(self.path_memo.clone())
+        .with_sidecars(self.asset_sidecars);
+        // Zarr manifests are only generated for the primary Archive instance
+        let zarrman =
+            (self.zarrs_via_manifests && *instance == Instance::Primary).then_some(&self.zarrman);
         Ok(VersionHandler {
+            instance,
             dandiset_id,
             version_spec,
             endpoint,
+            zarrman,
         })
     }
 
+    /// Construct the virtual `releases.tsv` file listing the published
+    /// versions of the given Dandiset.  It is an error for the Dandiset to
+    /// not have any published versions.
+    async fn get_releases_tsv(
+        &self,
+        instance: &Instance,
+        dandiset_id: &DandisetId,
+    ) -> Result<DavItem, DavError> {
+        let versions = self
+            .dandi(instance)?
+            .dandiset(dandiset_id.clone())
+            .get_all_versions()
+            .try_filter(|v| ready(matches!(v.version, VersionId::Published(_))))
+            .try_collect::<Vec<_>>()
+            .await?;
+        if versions.is_empty() {
+            return Err(DavError::NoReleases {
+                dandiset_id: dandiset_id.clone(),
+            });
+        }
+        Ok(DavItem::releases_tsv(instance, dandiset_id, versions))
+    }
+
     /// Get details on the resource at the given `path`
     async fn get_resource(&self, path: &DavPath) -> Result<DavResource, DavError> {
         match path {
             DavPath::Root => Ok(DavResource::root()),
-            DavPath::DandisetIndex => Ok(DavResource::Collection(DavCollection::dandiset_index())),
-            DavPath::Dandiset { dandiset_id } => {
-                let ds = self.dandi.dandiset(dandiset_id.clone()).get().await?;
-                Ok(DavResource::Collection(ds.into()))
-            }
-            DavPath::DandisetReleases { dandiset_id } => {
-                // TODO: Should this return a 404 when the Dandiset doesn't
-                // have any published releases?
-                Ok(DavResource::Collection(DavCollection::dandiset_releases(
-                    dandiset_id,
-                )))
+            path => {
+                self.check_dandiset(path)?;
+                self.source_for(path)?.get_resource(self, path).await
+            }
+        }
+    }
+
+    /// Get details on the resource at the given `path` along with its
+    /// immediate child resources (if any) for display in an HTML listing.
+    ///
+    /// If `path` points to a Dandiset version or to a folder within one, the
+    /// children are returned as a stream that fetches them from the Archive
+    /// as it is polled, as such collections can have very many children.
+    /// For a Dandiset version, the virtual `dandiset.yaml` and `assets.jsonl`
+    /// files are yielded at the end of the stream, except for those at whose
+    /// paths the version contains a real asset or folder.  Other resources' children are fetched by
+    /// [`DandiDav::get_resource_with_children()`].
+    ///
+    /// If `page` is non-`None`, then for Dandiset versions and folders within
+    /// them, only the requested page of children is fetched instead.
+    ///
+    /// Complete listings of published Dandiset versions and of resources
+    /// within them are served from the `published_cache` when present there;
+    /// otherwise, they are added to the cache once fully streamed.
+    async fn get_listing(
+        &self,
+        path: &DavPath,
+        page: Option<PageRequest>,
+    ) -> Result<Listing, DavError> {
+        if page.is_none() {
+            if let (Some(cache), Some(key)) = (&self.published_cache, PublishedKey::for_path(path))
+            {
+                // Cached collections are streamed so that they are rendered
+                // the same as when fetched from the Archive:
+                match cache.get(&key).await {
+                    Some(DavResourceWithChildren::Collection { col, children }) => {
+                        return Ok(Listing::Streamed {
+                            col,
+                            children: stream::iter(children.into_iter().map(Ok)).boxed(),
+                        });
+                    }
+                    Some(res) => return Ok(Listing::Fetched(res)),
+                    None => (),
+                }
+                return match self.fetch_listing(path, None).await? {
+                    Listing::Streamed { col, children } => Ok(Listing::Streamed {
+                        children: cache.tee(key, col.clone(), children),
+                        col,
+                    }),
+                    Listing::Fetched(res) => {
+                        cache.insert(key, res.clone()).await;
+                        Ok(Listing::Fetched(res))
+                    }
+                    listing @ Listing::Paged { .. } => Ok(listing),
+                };
             }
+        }
+        self.fetch_listing(path, page).await
+    }
+
+    /// Get details on the resource at the given `path` along with its
+    /// immediate child resources (if any) for display in an HTML listing,
+    /// bypassing the `published_cache`.  See [`DandiDav::get_listing()`].
+    async fn fetch_listing(
+        &self,
+        path: &DavPath,
+        page: Option<PageRequest>,
+    ) -> Result<Listing, DavError> {
+        match path {
             DavPath::Version {
+                instance,
                 dandiset_id,
                 version,
-            } => self
-                .get_version_handler(dandiset_id, version)
-                .await?
-                .get()
-                .await
-                .map(DavResource::Collection),
-            DavPath::DandisetYaml {
-                dandiset_id,
-                version,
-            } => self
-                .get_version_handler(dandiset_id, version)
-                .await?
-                .get_dandiset_yaml()
-                .await
-                .map(DavResource::Item),
+            } => {
+                let handler = self
+                    .get_version_handler(instance, dandiset_id, version)
+                    .await?;
+                match page {
+                    Some(page) => handler.get_listing_page(page).await,
+                    None => handler.get_listing().await,
+                }
+            }
             DavPath::DandiResource {
+                instance,
                 dandiset_id,
                 version,
                 path,
             } => {
-                self.get_version_handler(dandiset_id, version)
+                self.get_version_handler(instance, dandiset_id, version)
                     .await?
-                    .get_resource(path)
+                    .get_resource_listing(path, page)
                     .await
             }
-            DavPath::ZarrIndex => Ok(DavResource::Collection(DavCollection::zarr_index())),
-            DavPath::ZarrPath { path } => {
-                let res = self.zarrman.get_resource(path).await?;
-                Ok(DavResource::from(res))
-            }
+            _ => self
+                .get_resource_with_children(path)
+                .await
+                .map(Listing::Fetched),
         }
     }
 
@@ -238,106 +1430,42 @@ impl DandiDav {
     /// immediate child resources (if any).
     ///
     /// If `path` points to a Dandiset version, the child resources will
-    /// include `dandiset.yaml` as a virtual asset.
+    /// include `dandiset.yaml` and `assets.jsonl` as virtual files, except
+    /// for those at whose paths the version contains a real asset or folder.
+    ///
+    /// Resources in published Dandiset versions are served from & stored in
+    /// the `published_cache`, if any.
     async fn get_resource_with_children(
         &self,
         path: &DavPath,
     ) -> Result<DavResourceWithChildren, DavError> {
-        match path {
-            DavPath::Root => Ok(DavResourceWithChildren::root()),
-            DavPath::DandisetIndex => {
-                let col = DavCollection::dandiset_index();
-                let children = self
-                    .dandi
-                    .get_all_dandisets()
-                    .map_ok(|ds| DavResource::Collection(ds.into()))
-                    .try_collect::<Vec<_>>()
-                    .await?;
-                Ok(DavResourceWithChildren::Collection { col, children })
-            }
-            DavPath::Dandiset { dandiset_id } => {
-                let mut ds = self.dandi.dandiset(dandiset_id.clone()).get().await?;
-                let draft = DavResource::Collection(DavCollection::dandiset_version(
-                    ds.draft_version.clone(),
-                    version_path(dandiset_id, &VersionSpec::Draft),
-                ));
-                let children = match ds.most_recent_published_version.take() {
-                    Some(v) => {
-                        let latest = DavCollection::dandiset_version(
-                            v,
-                            version_path(dandiset_id, &VersionSpec::Latest),
-                        );
-                        let latest = DavResource::Collection(latest);
-                        let releases =
-                            DavResource::Collection(DavCollection::dandiset_releases(dandiset_id));
-                        vec![draft, latest, releases]
-                    }
-                    None => vec![draft],
-                };
-                let col = DavCollection::from(ds);
-                Ok(DavResourceWithChildren::Collection { col, children })
-            }
-            DavPath::DandisetReleases { dandiset_id } => {
-                // TODO: Should this return a 404 when the Dandiset doesn't
-                // have any published releases?
-                let col = DavCollection::dandiset_releases(dandiset_id);
-                let mut children = Vec::new();
-                let endpoint = self.dandi.dandiset(dandiset_id.clone());
-                let mut stream = endpoint.get_all_versions();
-                while let Some(v) = stream.try_next().await? {
-                    if let VersionId::Published(ref pvid) = v.version {
-                        let path = version_path(dandiset_id, &VersionSpec::Published(pvid.clone()));
-                        children.push(DavResource::Collection(DavCollection::dandiset_version(
-                            v, path,
-                        )));
-                    }
-                }
-                Ok(DavResourceWithChildren::Collection { col, children })
-            }
-            DavPath::Version {
-                dandiset_id,
-                version,
-            } => {
-                let handler = self.get_version_handler(dandiset_id, version).await?;
-                let col = handler.get().await?;
-                let mut children = handler.get_root_children().await?;
-                children.push(handler.get_dandiset_yaml().await.map(DavResource::Item)?);
-                Ok(DavResourceWithChildren::Collection { col, children })
+        // Box the fetches in order to keep the size of the future returned by
+        // this method down:
+        match (&self.published_cache, PublishedKey::for_path(path)) {
+            (Some(cache), Some(key)) => {
+                Box::pin(cache.get_or_fetch(key, self.fetch_resource_with_children(path))).await
             }
-            DavPath::DandisetYaml {
-                dandiset_id,
-                version,
-            } => self
-                .get_version_handler(dandiset_id, version)
-                .await?
-                .get_dandiset_yaml()
-                .await
-                .map(DavResourceWithChildren::Item),
-            DavPath::DandiResource {
-                dandiset_id,
-                version,
-                path,
-            } => {
-                self.get_version_handler(dandiset_id, version)
-                    .await?
-                    .get_resource_with_children(path)
+            _ => Box::pin(self.fetch_resource_with_children(path)).await,
+        }
+    }
+
+    /// Get details on the resource at the given `path` along with its
+    /// immediate child resources (if any), bypassing the `published_cache`.
+    /// See [`DandiDav::get_resource_with_children()`].
+    async fn fetch_resource_with_children(
+        &self,
+        path: &DavPath,
+    ) -> Result<DavResourceWithChildren, DavError> {
+        match path {
+            DavPath::Root => Ok(DavResourceWithChildren::root(
+                self.sources().map(DavDataSource::mount_collection),
+            )),
+            path => {
+                self.check_dandiset(path)?;
+                self.source_for(path)?
+                    .get_resource_with_children(self, path)
                     .await
             }
-            DavPath::ZarrIndex => {
-                let col = DavCollection::zarr_index();
-                let children = self
-                    .zarrman
-                    .get_top_level_dirs()
-                    .await?
-                    .into_iter()
-                    .map(DavResource::from)
-                    .collect();
-                Ok(DavResourceWithChildren::Collection { col, children })
-            }
-            DavPath::ZarrPath { path } => {
-                let res = self.zarrman.get_resource_with_children(path).await?;
-                Ok(DavResourceWithChildren::from(res))
-            }
         }
     }
 }
@@ -348,41 +1476,250 @@ impl DandiDav {
 /// with the path to the Dandiset & version.
 #[derive(Clone, Debug)]
 struct VersionHandler<'a> {
+    instance: &'a Instance,
     dandiset_id: &'a DandisetId,
     version_spec: &'a VersionSpec,
     endpoint: VersionEndpoint<'a>,
+
+    /// The client to use for serving the contents of Zarr assets from their
+    /// manifests, or `None` if Zarr contents are to be listed from S3
+    zarrman: Option<&'a ZarrManClient>,
 }
 
 impl VersionHandler<'_> {
     /// Get details on the version itself as a collection sans children
     async fn get(&self) -> Result<DavCollection, DavError> {
         let v = self.endpoint.get().await?;
-        let path = version_path(self.dandiset_id, self.version_spec);
+        let path = version_path(self.instance, self.dandiset_id, self.version_spec);
         Ok(DavCollection::dandiset_version(v, path))
     }
 
     /// Get details on all resources at the root of the version's file tree
-    /// (not including the `dandiset.yaml` file)
+    /// (not including the virtual files)
     async fn get_root_children(&self) -> Result<Vec<DavResource>, DandiError> {
         self.endpoint
             .get_root_children()
             .map_ok(|res| {
-                DavResource::from(res).under_version_path(self.dandiset_id, self.version_spec)
+                DavResource::from(res).under_version_path(
+                    self.instance,
+                    self.dandiset_id,
+                    self.version_spec,
+                )
             })
             .try_collect::<Vec<_>>()
             .await
     }
 
-    /// Get the version's `dandiset.yaml` file
+    /// Get details on the version itself as a collection along with a stream
+    /// of the resources at the root of the version's file tree, ending with
+    /// the virtual files that no root resource shadows
+    async fn get_listing(&self) -> Result<Listing, DavError> {
+        let col = self.get().await?;
+        // Fetch the virtual files now so that errors in doing so can be
+        // reported via the response status:
+        let mut virtuals = Vec::with_capacity(VirtualFile::ALL.len());
+        for file in VirtualFile::ALL {
+            virtuals.push(self.get_virtual_file(file).await?);
+        }
+        let children = self.place_children(self.endpoint.get_root_children());
+        let children = stream::unfold(
+            (children.fuse(), VecDeque::from(virtuals)),
+            |(mut children, mut virtuals)| async move {
+                match children.next().await {
+                    Some(r) => {
+                        if let Ok(res) = &r {
+                            virtuals.retain(|v| res.name() != Some(v.name()));
+                        }
+                        Some((r, (children, virtuals)))
+                    }
+                    None => virtuals
+                        .pop_front()
+                        .map(|v| (Ok(DavResource::Item(v)), (children, virtuals))),
+                }
+            },
+        )
+        .boxed();
+        Ok(Listing::Streamed { col, children })
+    }
+
+    /// Get details on the version itself as a collection along with the given
+    /// page of the resources at the root of the version's file tree.  The
+    /// first page also includes each virtual file for which the version does
+    /// not contain a real asset or folder at the same path.
+    async fn get_listing_page(&self, page: PageRequest) -> Result<Listing, DavError> {
+        let mut children = self.place_page(
+            self.endpoint
+                .get_root_children_page(page.page, page.per_page)
+                .await?,
+        );
+        if page.page == 1 {
+            for file in VirtualFile::ALL {
+                if let DavResource::Item(item) = self.get_virtual_file_resource(file).await? {
+                    if item.kind == file.kind() {
+                        children.children.push(DavResource::Item(item));
+                    }
+                }
+            }
+        }
+        Ok(Listing::Paged {
+            children: children.children,
+            page,
+            has_next: children.has_next,
+        })
+    }
+
+    /// Get details on the version's virtual collection of search results as a
+    /// collection sans children.  The version's details are fetched so that
+    /// searches of nonexistent versions fail.
+    async fn get_search(&self) -> Result<DavCollection, DavError> {
+        self.endpoint.get().await?;
+        let path = version_path(self.instance, self.dandiset_id, self.version_spec);
+        Ok(DavCollection::version_search(&path))
+    }
+
+    /// Search the version for assets whose paths match `query`, returning at
+    /// most [`MAX_SEARCH_RESULTS`] of them.  The resources are located at the
+    /// assets' actual paths in the version, not beneath the search
+    /// collection.
+    async fn search(&self, query: &str) -> Result<Vec<DavResource>, DavError> {
+        let results = self
+            .endpoint
+            .search_assets(query, MAX_SEARCH_RESULTS)
+            .await?;
+        Ok(results
+            .into_iter()
+            .map(|res| {
+                DavResource::from(res).under_version_path(
+                    self.instance,
+                    self.dandiset_id,
+                    self.version_spec,
+                )
+            })
+            .collect())
+    }
+
+    /// Get details on the version's virtual collection of BIDS datasets as a
+    /// collection sans children.  The version's details are fetched so that
+    /// requests for nonexistent versions fail.
+    async fn get_bids(&self) -> Result<DavCollection, DavError> {
+        self.endpoint.get().await?;
+        let path = version_path(self.instance, self.dandiset_id, self.version_spec);
+        Ok(DavCollection::version_bids(&path))
+    }
+
+    /// Detect the BIDS datasets in the version, returning the folders that
+    /// are datasets (preceded by the version itself if its root is a
+    /// dataset).  The resources are located at their actual paths in the
+    /// version, not beneath the BIDS collection.
+    async fn bids_datasets(&self) -> Result<Vec<DavResource>, DavError> {
+        let datasets = self
+            .endpoint
+            .find_bids_datasets(BIDS_MAX_DEPTH, BIDS_MAX_LISTINGS)
+            .await?;
+        let mut resources = Vec::with_capacity(datasets.folders.len() + 1);
+        if datasets.root {
+            resources.push(DavResource::Collection(self.get().await?));
+        }
+        resources.extend(datasets.folders.into_iter().map(|folder| {
+            DavResource::from(DandiResource::Folder(folder)).under_version_path(
+                self.instance,
+                self.dandiset_id,
+                self.version_spec,
+            )
+        }));
+        Ok(resources)
+    }
+
+    /// Get the version's virtual `dandiset.yaml` file
     async fn get_dandiset_yaml(&self) -> Result<DavItem, DavError> {
         let md = self.endpoint.get_metadata().await?;
-        Ok(DavItem::from(md).under_version_path(self.dandiset_id, self.version_spec))
+        Ok(
+            DavItem::from(md).under_version_path(
+                self.instance,
+                self.dandiset_id,
+                self.version_spec,
+            ),
+        )
+    }
+
+    /// Get the version's virtual `assets.jsonl` file
+    async fn get_assets_jsonl(&self) -> Result<DavItem, DavError> {
+        let v = self.endpoint.get().await?;
+        Ok(DavItem::assets_jsonl(v.modified).under_version_path(
+            self.instance,
+            self.dandiset_id,
+            self.version_spec,
+        ))
+    }
+
+    /// Get the given virtual file of the version
+    async fn get_virtual_file(&self, file: VirtualFile) -> Result<DavItem, DavError> {
+        match file {
+            VirtualFile::DandisetYaml => self.get_dandiset_yaml().await,
+            VirtualFile::AssetsJsonl => self.get_assets_jsonl().await,
+        }
+    }
+
+    /// Get details on the resource at the path of the virtual file `file`.
+    /// If the version contains a real asset or folder at that path, it is
+    /// returned; otherwise, the virtual file is returned.
+    async fn get_virtual_file_resource(&self, file: VirtualFile) -> Result<DavResource, DavError> {
+        match self.get_resource(&file.path()).await {
+            Err(DavError::Dandi(DandiError::PathNotFound { .. })) => {
+                self.get_virtual_file(file).await.map(DavResource::Item)
+            }
+            r => r,
+        }
+    }
+
+    /// Get details on the resource at the path of the virtual file `file`
+    /// along with its immediate child resources (if any).  If the version
+    /// contains a real asset or folder at that path, it is returned;
+    /// otherwise, the virtual file is returned.
+    async fn get_virtual_file_resource_with_children(
+        &self,
+        file: VirtualFile,
+    ) -> Result<DavResourceWithChildren, DavError> {
+        match self.get_resource_with_children(&file.path()).await {
+            Err(DavError::Dandi(DandiError::PathNotFound { .. })) => self
+                .get_virtual_file(file)
+                .await
+                .map(DavResourceWithChildren::Item),
+            r => r,
+        }
+    }
+
+    /// Fetch the current modification time of the version, bypassing the
+    /// cache, in order to detect changes to the version while its resources
+    /// are being listed
+    async fn fetch_modified(&self) -> Result<OffsetDateTime, DavError> {
+        Ok(self.endpoint.fetch().await?.modified)
     }
 
     /// Get details on the resource at the given `path`
     async fn get_resource(&self, path: &PurePath) -> Result<DavResource, DavError> {
+        if let Some(ManifestZarr {
+            zarrman,
+            zarr,
+            manifest_path,
+            entry_path: Some(entry_path),
+        }) = self.find_manifest_zarr(path).await?
+        {
+            let res = zarrman
+                .get_zarr_resource(manifest_path, zarr.path.to_dir_path(), Some(entry_path))
+                .await?;
+            return Ok(DavResource::from(res).under_version_path(
+                self.instance,
+                self.dandiset_id,
+                self.version_spec,
+            ));
+        }
         let res = self.endpoint.get_resource(path).await?;
-        Ok(DavResource::from(res).under_version_path(self.dandiset_id, self.version_spec))
+        Ok(DavResource::from(res).under_version_path(
+            self.instance,
+            self.dandiset_id,
+            self.version_spec,
+        ))
     }
 
     /// Get details on the resource at the given `path` along with its
@@ -391,12 +1728,359 @@ impl VersionHandler<'_> {
         &self,
         path: &PurePath,
     ) -> Result<DavResourceWithChildren, DavError> {
+        if let Some(mz) = self.find_manifest_zarr(path).await? {
+            return self.get_manifest_zarr_with_children(mz).await;
+        }
         let res = self.endpoint.get_resource_with_children(path).await?;
-        Ok(DavResourceWithChildren::from(res)
-            .under_version_path(self.dandiset_id, self.version_spec))
+        Ok(DavResourceWithChildren::from(res).under_version_path(
+            self.instance,
+            self.dandiset_id,
+            self.version_spec,
+        ))
+    }
+
+    /// Get details on the resource at the given `path` along with its
+    /// immediate child resources (if any) for display in an HTML listing.  If
+    /// the resource is a folder, its children are returned as a stream, or,
+    /// if `page` is non-`None`, only the requested page of its children is
+    /// fetched.
+    async fn get_resource_listing(
+        &self,
+        path: &PurePath,
+        page: Option<PageRequest>,
+    ) -> Result<Listing, DavError> {
+        if let Some(mz) = self.find_manifest_zarr(path).await? {
+            return Ok(Listing::Fetched(
+                self.get_manifest_zarr_with_children(mz).await?,
+            ));
+        }
+        match self.endpoint.get_resource_with_child_stream(path).await? {
+            DandiResourceWithChildStream::Folder { folder, children } => {
+                let Some(page) = page else {
+                    return Ok(Listing::Streamed {
+                        col: DavCollection::from(folder).under_version_path(
+                            self.instance,
+                            self.dandiset_id,
+                            self.version_spec,
+                        ),
+                        children: self.place_children(children),
+                    });
+                };
+                let children = self.place_page(
+                    self.endpoint
+                        .get_folder_children_page(&folder, page.page, page.per_page)
+                        .await?,
+                );
+                Ok(Listing::Paged {
+                    children: children.children,
+                    page,
+                    has_next: children.has_next,
+                })
+            }
+            DandiResourceWithChildStream::Other(res) => Ok(Listing::Fetched(
+                DavResourceWithChildren::from(*res).under_version_path(
+                    self.instance,
+                    self.dandiset_id,
+                    self.version_spec,
+                ),
+            )),
+        }
+    }
+
+    /// If Zarr contents are served from manifests and `path` is the path to a
+    /// Zarr asset or to a resource within one, and if the manifest tree
+    /// contains a manifest for the Zarr's current checksum, return the
+    /// details needed to serve the resource from the manifest.  Otherwise,
+    /// return `None`, in which case the resource should be looked up via the
+    /// Archive & S3 as usual.
+    async fn find_manifest_zarr(
+        &self,
+        path: &PurePath,
+    ) -> Result<Option<ManifestZarr<'_>>, DavError> {
+        let Some(zarrman) = self.zarrman else {
+            return Ok(None);
+        };
+        let Some((zarr, entry_path)) = self.endpoint.get_zarr_at_path(path).await? else {
+            return Ok(None);
+        };
+        let Some(manifest_path) = zarr
+            .checksum()
+            .and_then(|checksum| ManifestPath::for_zarr(&zarr.zarr_id, checksum))
+        else {
+            return Ok(None);
+        };
+        if !zarrman.has_manifest(&manifest_path).await? {
+            tracing::debug!(
+                asset_id = zarr.asset_id,
+                %manifest_path,
+                "Manifest for Zarr asset not found; listing Zarr on S3 instead",
+            );
+            return Ok(None);
+        }
+        Ok(Some(ManifestZarr {
+            zarrman,
+            zarr,
+            manifest_path,
+            entry_path,
+        }))
+    }
+
+    /// Get details on a resource in a Zarr (or on the Zarr itself) along with
+    /// its immediate child resources (if any) from the Zarr's manifest
+    async fn get_manifest_zarr_with_children(
+        &self,
+        mz: ManifestZarr<'_>,
+    ) -> Result<DavResourceWithChildren, DavError> {
+        let ManifestZarr {
+            zarrman,
+            zarr,
+            manifest_path,
+            entry_path,
+        } = mz;
+        let res = zarrman
+            .get_zarr_resource_with_children(manifest_path, zarr.path.to_dir_path(), entry_path)
+            .await?;
+        let res = match res {
+            // Describe the Zarr itself using the asset's details from the
+            // Archive
+            ZarrManResourceWithChildren::Manifest { children, .. } => {
+                DavResourceWithChildren::Collection {
+                    col: DavCollection::from(zarr),
+                    children: children.into_iter().map(DavResource::from).collect(),
+                }
+            }
+            res => DavResourceWithChildren::from(res),
+        };
+        Ok(res.under_version_path(self.instance, self.dandiset_id, self.version_spec))
+    }
+
+    /// Convert the resources in `page` to `DavResource`s with their paths
+    /// prefixed with the path to the Dandiset & version
+    fn place_page(&self, page: ChildPage) -> DavChildPage {
+        DavChildPage {
+            children: page
+                .children
+                .into_iter()
+                .map(|res| {
+                    DavResource::from(res).under_version_path(
+                        self.instance,
+                        self.dandiset_id,
+                        self.version_spec,
+                    )
+                })
+                .collect(),
+            has_next: page.has_next,
+        }
+    }
+
+    /// Convert the resources yielded by `children` to `DavResource`s with
+    /// their paths prefixed with the path to the Dandiset & version
+    fn place_children(
+        &self,
+        children: ChildStream,
+    ) -> BoxStream<'static, Result<DavResource, DavError>> {
+        let instance = self.instance.clone();
+        let dandiset_id = self.dandiset_id.clone();
+        let version_spec = self.version_spec.clone();
+        children
+            .map_ok(move |res| {
+                DavResource::from(res).under_version_path(&instance, &dandiset_id, &version_spec)
+            })
+            .map_err(DavError::from)
+            .boxed()
+    }
+}
+
+/// The result of [`DandiDav::walk_draft_snapshot()`]
+#[derive(Clone, Debug)]
+struct Snapshot {
+    /// The resources encountered by the walk
+    resources: Vec<DavResource>,
+
+    /// The modification time of the resources' draft version as of the end
+    /// of the walk, or `None` if the resources are not in a draft version
+    modified: Option<OffsetDateTime>,
+
+    /// Whether the resources' draft version was modified during every
+    /// attempted walk
+    unstable: bool,
+}
+
+/// Construct a "207 Multi-Status" response whose body is a `multistatus`
+/// document reporting the properties in `query` for each resource yielded by
+/// `resources`, serialized incrementally as the stream is polled.  The first
+/// resource is always reported; the rest are only reported if they match
+/// `filter` (if any).
+///
+/// As the status line will already have been sent by the time any error
+/// occurs in `resources`, such errors are logged and cause the response body
+/// to be aborted, so that clients do not mistake a partial listing for a
+/// complete one.
+fn stream_multistatus<S>(
+    resources: S,
+    query: PropFind,
+    filter: Option<MatchFilter>,
+) -> Result<Response<Body>, ToXmlError>
+where
+    S: Stream<Item = Result<DavResource, DavError>> + Send + 'static,
+{
+    let mut first = true;
+    let responses = resources
+        .try_filter(move |r| {
+            let is_first = std::mem::take(&mut first);
+            ready(is_first || filter.as_ref().map_or(true, |f| f.matches_resource(r)))
+        })
+        .map_ok(move |r| query.find(&r));
+    let (writer, start) = MultistatusWriter::start()?;
+    let chunks = stream::unfold(Some((Box::pin(responses), writer)), |state| async move {
+        let (mut responses, mut writer) = state?;
+        let (r, state) = match responses.next().await {
+            Some(Ok(resp)) => match writer.write_response(&resp) {
+                Ok(chunk) => (Ok(chunk), Some((responses, writer))),
+                Err(e) => (Err(DavError::from(e)), None),
+            },
+            Some(Err(e)) => (Err(e), None),
+            None => (writer.finish().map_err(DavError::from), None),
+        };
+        let r = r.map_err(|e| {
+            let e = anyhow::Error::from(e);
+            tracing::info!(error = ?e, "Error while streaming PROPFIND response; aborting response");
+            e
+        });
+        Some((r, state))
+    });
+    let body = Body::from_stream(stream::once(async { Ok(start) }).chain(chunks));
+    Ok((
+        StatusCode::MULTI_STATUS,
+        [(CONTENT_TYPE, DAV_XML_CONTENT_TYPE)],
+        body,
+    )
+        .into_response())
+}
+
+/// The result of [`DandiDav::get_listing()`]
+enum Listing {
+    /// A collection whose immediate child resources are fetched as the stream
+    /// is polled
+    Streamed {
+        col: DavCollection,
+        children: BoxStream<'static, Result<DavResource, DavError>>,
+    },
+
+    /// A collection along with a single page of its immediate child resources
+    Paged {
+        children: Vec<DavResource>,
+        page: PageRequest,
+        has_next: bool,
+    },
+
+    /// A resource along with its immediate child resources (if any), already
+    /// fetched
+    Fetched(DavResourceWithChildren),
+}
+
+/// A [`ChildPage`] with its resources converted to `DavResource`s
+struct DavChildPage {
+    /// The resources on the page
+    children: Vec<DavResource>,
+
+    /// Whether there are further pages
+    has_next: bool,
+}
+
+/// A virtual file served at the root of each Dandiset version's file
+/// hierarchy unless shadowed by a real asset or folder of the same name
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum VirtualFile {
+    /// `dandiset.yaml`, containing the version's metadata
+    DandisetYaml,
+
+    /// `assets.jsonl`, listing the version's assets
+    AssetsJsonl,
+}
+
+impl VirtualFile {
+    /// All virtual files, in the order in which they are listed
+    const ALL: [VirtualFile; 2] = [VirtualFile::DandisetYaml, VirtualFile::AssetsJsonl];
+
+    /// Return the file's name
+    fn name(self) -> &'static str {
+        match self {
+            VirtualFile::DandisetYaml => "dandiset.yaml",
+            VirtualFile::AssetsJsonl => "assets.jsonl",
+        }
+    }
+
+    /// Return the file's name as a `PurePath`
+    fn path(self) -> PurePath {
+        self.name()
+            .parse::<PurePath>()
+            .expect("virtual file names should be valid paths")
+    }
+
+    /// Return the [`ResourceKind`] of the virtual file
+    fn kind(self) -> ResourceKind {
+        match self {
+            VirtualFile::DandisetYaml => ResourceKind::VersionMetadata,
+            VirtualFile::AssetsJsonl => ResourceKind::AssetManifest,
+        }
+    }
+}
+
+/// A Zarr asset in a Dandiset version whose contents are served from the
+/// Zarr's manifest, along with the path to the requested resource within it
+#[derive(Clone, Debug)]
+struct ManifestZarr<'a> {
+    zarrman: &'a ZarrManClient,
+    zarr: ZarrAsset,
+    manifest_path: ManifestPath,
+    entry_path: Option<PurePath>,
+}
+
+/// If `e` is an error from a request for the resource at `path` that failed
+/// because a leading portion of the path points to a blob asset or to a Zarr
+/// lacking the rest of the path, return a `DAV:error` document naming the
+/// blocking asset
+fn error_condition(e: &DavError, path: &DavPath) -> Option<String> {
+    let DavPath::DandiResource {
+        instance,
+        dandiset_id,
+        version,
+        ..
+    } = path
+    else {
+        return None;
+    };
+    let prefix = version_path(instance, dandiset_id, version);
+    let condition = match e {
+        DavError::Dandi(DandiError::PathUnderBlob { blob_path, .. }) => {
+            ErrorCondition::PathUnderBlob {
+                blob: Href::from_path(&format!("/{prefix}{blob_path}")),
+            }
+        }
+        DavError::Dandi(DandiError::ZarrEntryNotFound { zarr_path, .. }) => {
+            ErrorCondition::ZarrEntryNotFound {
+                zarr: Href::from_path(&format!("/{prefix}{zarr_path}/")),
+            }
+        }
+        _ => return None,
+    };
+    match condition.to_xml() {
+        Ok(body) => Some(body),
+        Err(e) => {
+            tracing::warn!(error = ?anyhow::Error::from(e), "Failed to serialize DAV:error document");
+            None
+        }
     }
 }
 
+/// Returns `true` if any of `children` (the resources at the root of a
+/// Dandiset version's file hierarchy) has the same name as `file` and thus
+/// takes precedence over the virtual file
+fn shadows_virtual_file(children: &[DavResource], file: VirtualFile) -> bool {
+    children.iter().any(|r| r.name() == Some(file.name()))
+}
+
 #[derive(Debug, Error)]
 pub(crate) enum DavError {
     #[error("failed to fetch data from Archive")]
@@ -407,10 +2091,26 @@ pub(crate) enum DavError {
         "latest version was requested for Dandiset {dandiset_id}, but it has not been published"
     )]
     NoLatestVersion { dandiset_id: DandisetId },
+    #[error("Dandiset {dandiset_id} has no published versions")]
+    NoReleases { dandiset_id: DandisetId },
+    #[error(transparent)]
+    UnknownInstance(#[from] UnknownInstanceError),
+    #[error(transparent)]
+    UnknownMount(#[from] UnknownMountError),
+    #[error(transparent)]
+    HiddenDandiset(#[from] HiddenDandisetError),
+    #[error("failed to serve local file")]
+    Local(#[from] LocalError),
+    #[error("request would enumerate more than {limit} resources")]
+    TooManyResources { limit: usize },
+    #[error("request could not be handled within {timeout:?}")]
+    Timeout { timeout: Duration },
     #[error(transparent)]
     Template(#[from] TemplateError),
     #[error(transparent)]
     Xml(#[from] ToXmlError),
+    #[error("failed to upload resource to COPY destination")]
+    Copy(#[source] HttpError),
 }
 
 impl DavError {
@@ -419,23 +2119,66 @@ impl DavError {
         match self {
             DavError::Dandi(e) => e.class(),
             DavError::ZarrMan(e) => e.class(),
-            DavError::NoLatestVersion { .. } => ErrorClass::NotFound,
+            DavError::Local(e) => e.class(),
+            DavError::NoLatestVersion { .. }
+            | DavError::NoReleases { .. }
+            | DavError::UnknownInstance(_)
+            | DavError::UnknownMount(_)
+            | DavError::HiddenDandiset(_) => ErrorClass::NotFound,
+            DavError::TooManyResources { .. } => ErrorClass::Forbidden,
+            DavError::Timeout { .. } => ErrorClass::GatewayTimeout,
+            DavError::Copy(_) => ErrorClass::BadGateway,
             DavError::Template(_) | DavError::Xml(_) => ErrorClass::Internal,
         }
     }
 }
 
+/// Error returned when a request path refers to an Archive instance that is
+/// not being served
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("Archive instance {0} is not being served")]
+pub(crate) struct UnknownInstanceError(Instance);
+
+/// Error returned when a request path is beneath a top-level collection at
+/// which no data source is mounted
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("no hierarchy is being served at /{0}/")]
+pub(crate) struct UnknownMountError(String);
+
+/// Error returned when a request path refers to a Dandiset that is excluded
+/// from being served by the `--include-dandisets` or `--exclude-dandisets`
+/// options
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("Dandiset {0} is not being served")]
+pub(crate) struct HiddenDandisetError(DandisetId);
+
 /// A classification of a `DavError` for use in determining the HTTP status
 /// code to reply with
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum ErrorClass {
     /// The error was ultimately caused by something not being found
     NotFound,
 
+    /// The request was refused because fulfilling it would exceed a
+    /// server-side limit
+    Forbidden,
+
+    /// The Archive rejected the credentials (or lack thereof) used to make a
+    /// request on the client's behalf
+    Unauthorized,
+
     /// The error was ultimately caused by an upstream server returning an
     /// error or invalid response
     BadGateway,
 
+    /// The error was ultimately caused by a request to an upstream server
+    /// timing out or by the request as a whole taking too long to handle
+    GatewayTimeout,
+
+    /// `dandidav` is too busy to handle the request at the moment
+    Unavailable,
+
     /// The error was ultimately caused by something going wrong in `dandidav`
     Internal,
 }
@@ -445,8 +2188,3049 @@ impl ErrorClass {
     fn to_status(self) -> StatusCode {
         match self {
             ErrorClass::NotFound => StatusCode::NOT_FOUND,
+            ErrorClass::Forbidden => StatusCode::FORBIDDEN,
+            ErrorClass::Unauthorized => StatusCode::UNAUTHORIZED,
             ErrorClass::BadGateway => StatusCode::BAD_GATEWAY,
+            ErrorClass::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorClass::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
             ErrorClass::Internal => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::SYNC_SNAPSHOT_CACHE_SIZE;
+    use crate::httputil::{FakeTransport, HttpConfig, HttpUrl, RetryPolicy};
+    use crate::paths::ZarrExtensions;
+    use crate::s3::S3Config;
+    use rstest::rstest;
+
+    /// Construct a `DandiDav` whose Archive client points at a closed port,
+    /// for testing requests that can be handled without contacting the
+    /// Archive
+    pub(super) fn dav() -> Arc<DandiDav> {
+        let dandi = DandiClient::new(
+            "http://127.0.0.1:9/api".parse().unwrap(),
+            ApiCacheConfig {
+                max_entries: 0,
+                ttl: Duration::from_secs(1),
+            },
+            RetryPolicy::default(),
+            S3Config {
+                connect_timeout: Duration::from_secs(1),
+                read_timeout: Duration::from_secs(1),
+                max_attempts: 1,
+                list_concurrency: 1,
+            },
+            HttpConfig::default(),
+            ZarrLookup {
+                extensions: ZarrExtensions::default(),
+                max_candidates: 1,
+                exhaustive: false,
+            },
+            0,
+            None,
+        )
+        .unwrap();
+        let zarrman = ZarrManClient::new(
+            ManifestFetcher::new(0, RetryPolicy::default(), HttpConfig::default()).unwrap(),
+        );
+        Arc::new(DandiDav {
+            archives: BTreeMap::from([(Instance::Primary, dandi)]),
+            zarrman,
+            templater: Arc::new(Templater::new("dandidav".to_owned(), Vec::new()).unwrap()),
+            prefer_s3_redirects: false,
+            root_behavior: RootBehavior::Render,
+            asset_sidecars: false,
+            inline_preview_max_bytes: None,
+            infinite_depth_limit: None,
+            timeouts: RequestTimeouts {
+                listing: None,
+                redirect: None,
+                stream: None,
+            },
+            path_memo: PathMemo::default(),
+            published_cache: None,
+            copy: None,
+            sync_snapshots: SyncSnapshots::new(SYNC_SNAPSHOT_CACHE_SIZE),
+            zarrs_via_manifests: false,
+            local_mounts: Vec::new(),
+            path_config: Arc::default(),
+            dandiset_filter: Arc::default(),
+        })
+    }
+
+    /// Construct a `DandiDav` like [`dav()`] whose Archive clients send their
+    /// requests to `transport`
+    pub(super) fn dav_with_transport(transport: impl Into<Arc<FakeTransport>>) -> DandiDav {
+        let mut dav = Arc::into_inner(dav()).expect("fresh DandiDav should not be shared");
+        let transport = transport.into();
+        for client in dav.archives.values_mut() {
+            *client = client.with_transport(transport.clone());
+        }
+        dav
+    }
+
+    /// The Archive API URL for the draft version of Dandiset 000027 as seen by
+    /// [`dav()`]
+    const DRAFT_VERSION_URL: &str = "http://127.0.0.1:9/api/dandisets/000027/versions/draft";
+
+    /// The Archive API URL for a published version of Dandiset 000027 as seen
+    /// by [`dav()`]
+    const PUBLISHED_VERSION_URL: &str =
+        "http://127.0.0.1:9/api/dandisets/000027/versions/0.210831.2033";
+
+    /// Return the Archive's description of the Dandiset version `version`
+    /// (as returned by the version's `info/` endpoint), created and last
+    /// modified at `modified`
+    pub(super) fn version_info(version: &str, modified: &str) -> serde_json::Value {
+        serde_json::json!({
+            "version": version,
+            "name": "Test Dandiset",
+            "asset_count": 1,
+            "size": 42,
+            "status": "Valid",
+            "created": modified,
+            "modified": modified,
+        })
+    }
+
+    /// Return the Archive's metadata for a Dandiset version (as returned by
+    /// the version's endpoint)
+    pub(super) fn version_metadata() -> serde_json::Value {
+        serde_json::json!({"name": "Test Dandiset"})
+    }
+
+    #[rstest]
+    #[case(ErrorClass::NotFound, StatusCode::NOT_FOUND)]
+    #[case(ErrorClass::Forbidden, StatusCode::FORBIDDEN)]
+    #[case(ErrorClass::Unauthorized, StatusCode::UNAUTHORIZED)]
+    #[case(ErrorClass::BadGateway, StatusCode::BAD_GATEWAY)]
+    #[case(ErrorClass::GatewayTimeout, StatusCode::GATEWAY_TIMEOUT)]
+    #[case(ErrorClass::Unavailable, StatusCode::SERVICE_UNAVAILABLE)]
+    #[case(ErrorClass::Internal, StatusCode::INTERNAL_SERVER_ERROR)]
+    fn test_error_class_to_status(#[case] class: ErrorClass, #[case] status: StatusCode) {
+        assert_eq!(class.to_status(), status);
+    }
+
+    mod shadows_virtual_file {
+        use super::*;
+        use time::macros::datetime;
+
+        fn blob(path: &str) -> DavResource {
+            DavResource::Item(DavItem {
+                path: path.parse().unwrap(),
+                created: Some(datetime!(2024-02-14 22:13:22 UTC)),
+                modified: Some(datetime!(2024-02-14 22:13:35 UTC)),
+                content_type: "text/yaml".into(),
+                size: Some(42),
+                etag: None,
+                kind: ResourceKind::Blob,
+                content: DavContent::Missing,
+                metadata_url: None,
+            })
+        }
+
+        fn folder(path: &str) -> DavResource {
+            DavResource::Collection(DavCollection {
+                path: Some(path.parse().unwrap()),
+                created: None,
+                modified: None,
+                size: None,
+                asset_count: None,
+                kind: ResourceKind::Directory,
+                metadata_url: None,
+            })
+        }
+
+        #[test]
+        fn no_collision() {
+            let children = vec![
+                blob("dandisets/000123/draft/dandiset.yml"),
+                blob("dandisets/000123/draft/Dandiset.yaml"),
+                folder("dandisets/000123/draft/sub-01/"),
+            ];
+            assert!(!shadows_virtual_file(&children, VirtualFile::DandisetYaml));
+        }
+
+        #[test]
+        fn empty() {
+            assert!(!shadows_virtual_file(&[], VirtualFile::DandisetYaml));
+        }
+
+        #[test]
+        fn blob_collision() {
+            let children = vec![
+                folder("dandisets/000123/draft/sub-01/"),
+                blob("dandisets/000123/draft/dandiset.yaml"),
+            ];
+            assert!(shadows_virtual_file(&children, VirtualFile::DandisetYaml));
+        }
+
+        #[test]
+        fn folder_collision() {
+            let children = vec![folder("dandisets/000123/draft/dandiset.yaml/")];
+            assert!(shadows_virtual_file(&children, VirtualFile::DandisetYaml));
+        }
+    }
+
+    #[test]
+    fn test_dav_error_class() {
+        let url = "https://api.dandiarchive.org/api/dandisets/000027/"
+            .parse::<HttpUrl>()
+            .unwrap();
+        let cases = [
+            (
+                DavError::Dandi(DandiError::PathNotFound {
+                    path: "foo/bar.nwb".parse().unwrap(),
+                }),
+                ErrorClass::NotFound,
+            ),
+            (
+                DavError::ZarrMan(ZarrManError::Http(HttpError::NotFound { url })),
+                ErrorClass::NotFound,
+            ),
+            (
+                DavError::NoLatestVersion {
+                    dandiset_id: "000027".parse().unwrap(),
+                },
+                ErrorClass::NotFound,
+            ),
+            (
+                DavError::TooManyResources { limit: 1000 },
+                ErrorClass::Forbidden,
+            ),
+            (
+                DavError::Template(TemplateError::Render {
+                    template_name: "collection.html",
+                    source: tera::Error::msg("test"),
+                }),
+                ErrorClass::Internal,
+            ),
+            (
+                DavError::Xml(ToXmlError::Decode(
+                    String::from_utf8(vec![0xFF]).unwrap_err().utf8_error(),
+                )),
+                ErrorClass::Internal,
+            ),
+        ];
+        for (e, class) in cases {
+            assert_eq!(e.class(), class, "wrong class for {e:?}");
+        }
+    }
+
+    mod options {
+        use super::*;
+        use axum::http::header::ACCEPT_RANGES;
+
+        async fn options(uri: &str) -> Response<Body> {
+            let req = Request::builder()
+                .method(Method::OPTIONS)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            dav().handle_request(req).await.unwrap()
+        }
+
+        #[tokio::test]
+        async fn collection() {
+            let resp = options("/").await;
+            assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            assert_eq!(resp.headers()["Allow"], "GET, HEAD, OPTIONS, PROPFIND");
+            assert_eq!(resp.headers()["DAV"], "1, 3");
+            assert_eq!(resp.headers()[ACCEPT_RANGES], "none");
+        }
+
+        #[tokio::test]
+        async fn server() {
+            let resp = options("*").await;
+            assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            assert_eq!(resp.headers()["Allow"], "GET, HEAD, OPTIONS, PROPFIND");
+            assert_eq!(resp.headers()["DAV"], "1, 3");
+            assert!(!resp.headers().contains_key(ACCEPT_RANGES));
+        }
+
+        #[tokio::test]
+        async fn dandiset_index() {
+            let resp = options("/dandisets/").await;
+            assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            assert_eq!(resp.headers()[ACCEPT_RANGES], "none");
+        }
+
+        #[tokio::test]
+        async fn unknown_instance() {
+            let resp = options("/staging-dandisets/").await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn nonexistent() {
+            let resp = options("/nonexistent/").await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert!(!resp.headers().contains_key(ACCEPT_RANGES));
+        }
+    }
+
+    mod match_filter {
+        use super::*;
+
+        async fn request(method: &str, uri: &str, accept: &str) -> (StatusCode, String) {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Accept", accept)
+                .header("Depth", "1")
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav().handle_request(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn get_json() {
+            let (status, body) = request("GET", "/?match=zarr*", "application/json").await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body.contains(r#""name":"zarrs""#));
+            assert!(!body.contains(r#""name":"dandisets""#));
+        }
+
+        #[tokio::test]
+        async fn get_html_regex() {
+            let (status, body) = request("GET", "/?match=re:%5Edandi&sort=size", "text/html").await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body.contains(r#"href="&#x2F;dandisets&#x2F;""#));
+            assert!(!body.contains(r#"href="&#x2F;zarrs&#x2F;""#));
+            assert!(body.contains("?sort=name&amp;match=re%3A%5Edandi"));
+        }
+
+        #[tokio::test]
+        async fn propfind() {
+            let (status, body) = request("PROPFIND", "/?match=zarrs", "*/*").await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(body.contains("<href>/</href>"));
+            assert!(body.contains("<href>/zarrs/</href>"));
+            assert!(!body.contains("<href>/dandisets/</href>"));
+        }
+
+        #[rstest]
+        #[case("/?match=")]
+        #[case("/?match=re:(")]
+        #[case("/?match=%5B")]
+        #[tokio::test]
+        async fn invalid(#[case] uri: &str) {
+            let (status, body) = request("GET", uri, "text/html").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(body.starts_with("Invalid \"match\" query parameter: "));
+        }
+    }
+
+    mod tree {
+        use super::*;
+
+        async fn get(uri: &str) -> (StatusCode, String) {
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav().handle_request(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn root() {
+            let (status, body) = get("/?tree=json&depth=1").await;
+            assert_eq!(status, StatusCode::OK);
+            let tree = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+            assert_eq!(tree["url"], "/");
+            let children = tree["children"].as_array().unwrap();
+            assert_eq!(children.len(), 2);
+            assert_eq!(children[0]["url"], "/dandisets/");
+            assert_eq!(children[1]["url"], "/zarrs/");
+            assert!(children.iter().all(|c| c.get("children").is_none()));
+        }
+
+        #[tokio::test]
+        async fn depth_zero() {
+            let (status, body) = get("/?tree=json&depth=0").await;
+            assert_eq!(status, StatusCode::OK);
+            let tree = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+            assert_eq!(tree["url"], "/");
+            assert!(tree.get("children").is_none());
+        }
+
+        #[rstest]
+        #[case("/?tree=xml")]
+        #[case("/?tree=json&depth=all")]
+        #[tokio::test]
+        async fn invalid(#[case] uri: &str) {
+            let (status, body) = get(uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body, "Invalid \"tree\" or \"depth\" query parameter\n");
+        }
+    }
+
+    mod estimate {
+        use super::*;
+        use serde_json::{json, Value};
+
+        async fn get(transport: FakeTransport, uri: &str) -> (StatusCode, String) {
+            let dav = dav_with_transport(transport);
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn version() {
+            let transport = FakeTransport::new().json(
+                &format!("{DRAFT_VERSION_URL}/info/"),
+                &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+            );
+            let (status, body) = get(transport, "/dandisets/000027/draft/?estimate=size").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<Value>(&body).unwrap(),
+                json!({
+                    "url": "/dandisets/000027/draft/",
+                    "dandiset_id": "000027",
+                    "version": "draft",
+                    "size": 42,
+                    "asset_count": 1,
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn not_a_version() {
+            let (status, _) = get(FakeTransport::new(), "/dandisets/000027/?estimate=size").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn invalid() {
+            let (status, body) = get(
+                FakeTransport::new(),
+                "/dandisets/000027/draft/?estimate=files",
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body, "Invalid \"estimate\" query parameter\n");
+        }
+    }
+
+    mod search {
+        use super::*;
+        use serde_json::{json, Value};
+
+        fn transport() -> FakeTransport {
+            FakeTransport::new().json(
+                &format!("{DRAFT_VERSION_URL}/info/"),
+                &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+            )
+        }
+
+        fn asset(path: &str) -> Value {
+            json!({
+                "asset_id": "0123-4567",
+                "blob": "89ab-cdef",
+                "zarr": null,
+                "path": path,
+                "size": 42,
+                "created": "2024-01-01T00:00:00Z",
+                "modified": "2024-01-02T00:00:00Z",
+                "metadata": {
+                    "encodingFormat": "application/x-nwb",
+                    "contentUrl": [],
+                    "digest": {"dandi:dandi-etag": "abc-1"},
+                },
+            })
+        }
+
+        async fn get(transport: FakeTransport, uri: &str) -> (StatusCode, Value) {
+            let dav = dav_with_transport(transport);
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .header("Accept", "application/json")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+        }
+
+        fn entry_urls(listing: &Value) -> Vec<&str> {
+            listing["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["url"].as_str().unwrap())
+                .collect()
+        }
+
+        #[rstest]
+        #[case("sub-01", "*sub-01*")]
+        #[case("sub-*%2F*.nwb", "sub-*%2F*.nwb")]
+        #[tokio::test]
+        async fn matches(#[case] q: &str, #[case] glob: &str) {
+            let transport = transport().json(
+                &format!("{DRAFT_VERSION_URL}/assets/?glob={glob}&metadata=1&order=path"),
+                &json!({
+                    "next": null,
+                    "results": [
+                        asset("sub-01/sub-01_ses-1.nwb"),
+                        asset("sub-01/sub-01_ses-2.nwb"),
+                    ],
+                }),
+            );
+            let (status, listing) =
+                get(transport, &format!("/dandisets/000027/draft/.search?q={q}")).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(listing["url"], "/dandisets/000027/draft/.search/");
+            assert_eq!(listing["kind"], "Search results");
+            assert_eq!(
+                entry_urls(&listing),
+                [
+                    "/dandisets/000027/draft/sub-01/sub-01_ses-1.nwb",
+                    "/dandisets/000027/draft/sub-01/sub-01_ses-2.nwb",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn no_query() {
+            let (status, listing) = get(transport(), "/dandisets/000027/draft/.search/").await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(entry_urls(&listing).is_empty());
+        }
+
+        #[tokio::test]
+        async fn nonexistent_version() {
+            let (status, _) = get(
+                FakeTransport::new(),
+                "/dandisets/000027/draft/.search?q=foo",
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    mod bids {
+        use super::*;
+        use serde_json::{json, Value};
+
+        fn transport() -> FakeTransport {
+            FakeTransport::new().json(
+                &format!("{DRAFT_VERSION_URL}/info/"),
+                &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+            )
+        }
+
+        fn listing(prefix: Option<&str>, entries: Value) -> (String, Value) {
+            let url = match prefix {
+                Some(p) => format!("{DRAFT_VERSION_URL}/assets/paths/?path_prefix={p}%2F"),
+                None => format!("{DRAFT_VERSION_URL}/assets/paths/"),
+            };
+            (url, json!({"next": null, "results": entries}))
+        }
+
+        fn description(folder: &str) -> Value {
+            let path = if folder.is_empty() {
+                "dataset_description.json".to_owned()
+            } else {
+                format!("{folder}/dataset_description.json")
+            };
+            json!({"path": path, "asset": {"asset_id": "0123-4567"}})
+        }
+
+        fn folder(path: &str) -> Value {
+            json!({"path": path, "aggregate_files": 1, "aggregate_size": 42})
+        }
+
+        async fn get(transport: FakeTransport, uri: &str) -> (StatusCode, Value) {
+            let dav = dav_with_transport(transport);
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .header("Accept", "application/json")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+        }
+
+        fn entry_urls(listing: &Value) -> Vec<&str> {
+            listing["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["url"].as_str().unwrap())
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn detects_datasets() {
+            let mut transport = transport();
+            for (url, body) in [
+                listing(
+                    None,
+                    json!([folder("derivatives"), folder("rawdata"), folder("sub-01")]),
+                ),
+                listing(
+                    Some("derivatives"),
+                    json!([folder("derivatives/fmriprep"), folder("derivatives/notes")]),
+                ),
+                listing(
+                    Some("derivatives%2Ffmriprep"),
+                    json!([description("derivatives/fmriprep")]),
+                ),
+                listing(Some("derivatives%2Fnotes"), json!([])),
+                listing(
+                    Some("rawdata"),
+                    json!([description("rawdata"), folder("rawdata/sub-01")]),
+                ),
+            ] {
+                transport = transport.json(&url, &body);
+            }
+            let (status, listing) = get(transport, "/dandisets/000027/draft/.bids/").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(listing["url"], "/dandisets/000027/draft/.bids/");
+            assert_eq!(listing["kind"], "BIDS datasets");
+            assert_eq!(
+                entry_urls(&listing),
+                [
+                    "/dandisets/000027/draft/derivatives/fmriprep/",
+                    "/dandisets/000027/draft/rawdata/",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn root_dataset() {
+            let (url, body) = listing(None, json!([description(""), folder("sub-01")]));
+            let (status, listing) = get(
+                transport().json(&url, &body),
+                "/dandisets/000027/draft/.bids/",
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(entry_urls(&listing), ["/dandisets/000027/draft/"]);
+        }
+
+        #[tokio::test]
+        async fn nonexistent_version() {
+            let (status, _) = get(FakeTransport::new(), "/dandisets/000027/draft/.bids/").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    mod error_conditions {
+        use super::*;
+        use assert_matches::assert_matches;
+        use axum::http::header::HeaderMap;
+        use serde_json::json;
+
+        fn parse(path: &str) -> DavPath {
+            DavPath::from_components(
+                path.split('/')
+                    .filter(|c| !c.is_empty())
+                    .map(|c| c.parse::<Component>().unwrap())
+                    .collect(),
+                &PathConfig::default(),
+            )
+            .unwrap()
+        }
+
+        fn transport() -> FakeTransport {
+            FakeTransport::new()
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/info/"),
+                    &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+                )
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/assets/?path=data.zarr&metadata=1&order=path"),
+                    &json!({
+                        "next": null,
+                        "results": [{
+                            "asset_id": "0123-4567",
+                            "blob": "89ab-cdef",
+                            "zarr": null,
+                            "path": "data.zarr",
+                            "size": 42,
+                            "created": "2024-01-01T00:00:00Z",
+                            "modified": "2024-01-02T00:00:00Z",
+                            "metadata": {
+                                "encodingFormat": "application/octet-stream",
+                                "contentUrl": [
+                                    "https://api.dandiarchive.org/api/assets/0123-4567/download/",
+                                    "https://dandiarchive.s3.amazonaws.com/blobs/89a/bcd/89abcdef",
+                                ],
+                                "digest": {
+                                    "dandi:dandi-etag": "abc-1",
+                                    "dandi:sha2-256": "def",
+                                },
+                            },
+                        }],
+                    }),
+                )
+        }
+
+        async fn request(method: &str, uri: &str) -> (StatusCode, HeaderMap, String) {
+            let dav = dav_with_transport(transport());
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Depth", "0")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, headers, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[rstest]
+        #[case("/dandisets/000027/draft/data.zarr/0")]
+        #[case("/dandisets/000027/draft/data.zarr/0/0")]
+        #[tokio::test]
+        async fn propfind_path_under_blob(#[case] uri: &str) {
+            let (status, headers, body) = request("PROPFIND", uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(headers[CONTENT_TYPE], DAV_XML_CONTENT_TYPE);
+            assert!(body.contains("<dandidav:path-under-blob>"));
+            assert!(body.contains("<href>/dandisets/000027/draft/data.zarr</href>"));
+        }
+
+        #[tokio::test]
+        async fn get_path_under_blob() {
+            let (status, _, body) = request("GET", "/dandisets/000027/draft/data.zarr/0").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(body, "404\n");
+        }
+
+        #[test]
+        fn zarr_entry_not_found() {
+            let e = DavError::Dandi(DandiError::ZarrEntryNotFound {
+                zarr_path: "sub-1/sample.zarr".parse().unwrap(),
+                entry_path: "0/0/0".parse().unwrap(),
+            });
+            let path = parse("/dandisets/000027/releases/0.210831.2033/sub-1/sample.zarr/0/0/0");
+            assert_matches!(error_condition(&e, &path), Some(body) => {
+                assert!(body.contains("<dandidav:zarr-entry-not-found>"));
+                assert!(body.contains(
+                    "<href>/dandisets/000027/releases/0.210831.2033/sub-1/sample.zarr/</href>"
+                ));
+            });
+        }
+
+        #[test]
+        fn other_errors() {
+            let e = DavError::Dandi(DandiError::PathNotFound {
+                path: "sub-1/missing.nwb".parse().unwrap(),
+            });
+            let path = parse("/dandisets/000027/draft/sub-1/missing.nwb");
+            assert_eq!(error_condition(&e, &path), None);
+        }
+    }
+
+    mod published_cache {
+        use super::*;
+        use serde_json::json;
+
+        fn transport() -> Arc<FakeTransport> {
+            Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/info/"),
+                        &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                    )
+                    .json(&format!("{PUBLISHED_VERSION_URL}/"), &version_metadata())
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/assets/paths/"),
+                        &json!({"next": null, "results": []}),
+                    ),
+            )
+        }
+
+        fn cached_dav(transport: &Arc<FakeTransport>) -> Arc<DandiDav> {
+            let mut dav = dav_with_transport(transport.clone());
+            dav.published_cache = PublishedCache::new(100);
+            Arc::new(dav)
+        }
+
+        async fn get(
+            dav: &Arc<DandiDav>,
+            accept: &str,
+            token: Option<&str>,
+        ) -> (StatusCode, String) {
+            let mut req =
+                Request::get("/dandisets/000027/releases/0.210831.2033/").header("Accept", accept);
+            if let Some(token) = token {
+                req = req.header("Authorization", format!("token {token}"));
+            }
+            let resp = Arc::clone(dav)
+                .handle_request(req.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[test]
+        fn test_key_for_path() {
+            let key = |s: &str| {
+                let path = DavPath::from_components(
+                    s.split('/')
+                        .filter(|c| !c.is_empty())
+                        .map(|c| c.parse::<Component>().unwrap())
+                        .collect(),
+                    &PathConfig::default(),
+                )
+                .unwrap();
+                PublishedKey::for_path(&path)
+            };
+            assert!(key("/dandisets/000027/releases/0.210831.2033/").is_some());
+            assert!(key("/dandisets/000027/releases/0.210831.2033/sub-RAT123/").is_some());
+            assert_ne!(
+                key("/dandisets/000027/releases/0.210831.2033/"),
+                key("/dandisets/000027/releases/0.210831.2033/sub-RAT123/")
+            );
+            assert!(key("/dandisets/000027/releases/0.210831.2033/dandiset.yaml").is_none());
+            assert!(key("/dandisets/000027/draft/").is_none());
+            assert!(key("/dandisets/000027/draft/sub-RAT123/").is_none());
+            assert!(key("/dandisets/000027/latest/").is_none());
+            assert!(key("/dandisets/000027/releases/").is_none());
+        }
+
+        // HTML listings are streamed and only cached once complete, while JSON
+        // listings are fetched in full up front.
+        #[rstest]
+        #[case("text/html")]
+        #[case("application/json")]
+        #[tokio::test]
+        async fn serves_from_cache(#[case] accept: &str) {
+            let transport = transport();
+            let dav = cached_dav(&transport);
+            let (status, body) = get(&dav, accept, None).await;
+            assert_eq!(status, StatusCode::OK);
+            let fetched = transport.requests().len();
+            assert!(fetched > 0);
+            let (status, cached_body) = get(&dav, accept, None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(cached_body, body);
+            assert_eq!(transport.requests().len(), fetched);
+        }
+
+        #[tokio::test]
+        async fn client_token_bypasses_cache() {
+            let transport = transport();
+            let dav = cached_dav(&transport);
+            let (status, _) = get(&dav, "application/json", None).await;
+            assert_eq!(status, StatusCode::OK);
+            let fetched = transport.requests().len();
+            let (status, _) = get(&dav, "application/json", Some("0123456789abcdef")).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(transport.requests().len() > fetched);
+        }
+    }
+
+    mod assets_jsonl {
+        use super::*;
+        use crate::consts::MUTABLE_CACHE_CONTROL;
+        use axum::http::header::{HeaderMap, CONTENT_DISPOSITION};
+        use serde_json::{json, Value};
+
+        fn transport() -> FakeTransport {
+            FakeTransport::new()
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/info/"),
+                    &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+                )
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/assets/?path=assets.jsonl&metadata=1&order=path"),
+                    &json!({"next": null, "results": []}),
+                )
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/assets/?metadata=1&order=path"),
+                    &json!({
+                        "next": null,
+                        "results": [
+                            {
+                                "asset_id": "0123-4567",
+                                "blob": "89ab-cdef",
+                                "zarr": null,
+                                "path": "sub-01/sub-01_ses-1.nwb",
+                                "size": 42,
+                                "created": "2024-01-01T00:00:00Z",
+                                "modified": "2024-01-02T00:00:00Z",
+                                "metadata": {
+                                    "encodingFormat": "application/x-nwb",
+                                    "contentUrl": [
+                                        "https://api.dandiarchive.org/api/assets/0123-4567/download/",
+                                        "https://dandiarchive.s3.amazonaws.com/blobs/89a/bcd/89abcdef",
+                                    ],
+                                    "digest": {
+                                        "dandi:dandi-etag": "abc-1",
+                                        "dandi:sha2-256": "def",
+                                    },
+                                },
+                            },
+                            {
+                                "asset_id": "7654-3210",
+                                "blob": null,
+                                "zarr": "fedc-ba98",
+                                "path": "sub-01/sub-01_ses-2.zarr",
+                                "size": 1192,
+                                "created": "2024-01-01T00:00:00Z",
+                                "modified": "2024-01-02T00:00:00Z",
+                                "metadata": {
+                                    "encodingFormat": "application/x-zarr",
+                                    "contentUrl": [
+                                        "https://api.dandiarchive.org/api/assets/7654-3210/download/",
+                                        "https://dandiarchive.s3.amazonaws.com/zarr/fedc-ba98/",
+                                    ],
+                                    "digest": {"dandi:dandi-zarr-checksum": "0123-7--1192"},
+                                },
+                            },
+                        ],
+                    }),
+                )
+        }
+
+        async fn get(
+            transport: FakeTransport,
+            uri: &str,
+            range: Option<&str>,
+        ) -> (StatusCode, HeaderMap, String) {
+            let dav = dav_with_transport(transport);
+            let mut req = Request::builder().method(Method::GET).uri(uri);
+            if let Some(range) = range {
+                req = req.header("Range", range);
+            }
+            let req = req.body(Body::empty()).unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, headers, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn lists_assets() {
+            let (status, headers, body) =
+                get(transport(), "/dandisets/000027/draft/assets.jsonl", None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(headers[CONTENT_TYPE], JSONL_CONTENT_TYPE);
+            assert!(!headers.contains_key(CONTENT_DISPOSITION));
+            assert!(headers.contains_key("ETag"));
+            assert_eq!(headers["Last-Modified"], "Mon, 16 Mar 2020 12:00:00 GMT");
+            assert_eq!(headers[CACHE_CONTROL], MUTABLE_CACHE_CONTROL);
+            let records = body
+                .lines()
+                .map(|ln| serde_json::from_str::<Value>(ln).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                records,
+                [
+                    json!({
+                        "path": "sub-01/sub-01_ses-1.nwb",
+                        "size": 42,
+                        "checksums": {
+                            "dandi:dandi-etag": "abc-1",
+                            "dandi:sha2-256": "def",
+                        },
+                        "download_url": "https://api.dandiarchive.org/api/assets/0123-4567/download/",
+                    }),
+                    json!({
+                        "path": "sub-01/sub-01_ses-2.zarr",
+                        "size": 1192,
+                        "checksums": {"dandi:dandi-zarr-checksum": "0123-7--1192"},
+                        "download_url": "https://dandiarchive.s3.amazonaws.com/zarr/fedc-ba98/",
+                    }),
+                ]
+            );
+            assert!(body.ends_with('\n'));
+        }
+
+        #[tokio::test]
+        async fn download() {
+            let (status, headers, _) = get(
+                transport(),
+                "/dandisets/000027/draft/assets.jsonl?download=1",
+                None,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                headers[CONTENT_DISPOSITION],
+                "attachment; filename=\"assets.jsonl\"; filename*=UTF-8''assets.jsonl"
+            );
+        }
+
+        #[tokio::test]
+        async fn nonexistent_version() {
+            let (status, _, _) = get(
+                FakeTransport::new(),
+                "/dandisets/000027/draft/assets.jsonl",
+                None,
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn range() {
+            let uri = "/dandisets/000027/draft/assets.jsonl";
+            let (_, headers, full) = get(transport(), uri, None).await;
+            assert_eq!(headers["Accept-Ranges"], "bytes");
+            let len = full.len();
+            let (status, headers, body) = get(transport(), uri, Some("bytes=10-99")).await;
+            assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+            assert_eq!(headers["Content-Range"], format!("bytes 10-99/{len}"));
+            assert_eq!(headers[CONTENT_TYPE], JSONL_CONTENT_TYPE);
+            assert!(headers.contains_key("ETag"));
+            assert_eq!(body, full[10..100]);
+        }
+
+        #[tokio::test]
+        async fn suffix_range() {
+            let uri = "/dandisets/000027/draft/assets.jsonl";
+            let (_, _, full) = get(transport(), uri, None).await;
+            let len = full.len();
+            let (status, headers, body) = get(transport(), uri, Some("bytes=-50")).await;
+            assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+            assert_eq!(
+                headers["Content-Range"],
+                format!("bytes {}-{}/{len}", len - 50, len - 1)
+            );
+            assert_eq!(body, full[(len - 50)..]);
+        }
+
+        #[tokio::test]
+        async fn unsatisfiable_range() {
+            let uri = "/dandisets/000027/draft/assets.jsonl";
+            let (_, _, full) = get(transport(), uri, None).await;
+            let len = full.len();
+            let (status, headers, _) = get(transport(), uri, Some(&format!("bytes={len}-"))).await;
+            assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(headers["Content-Range"], format!("bytes */{len}"));
+        }
+
+        #[tokio::test]
+        async fn paginated_in_path_order() {
+            let page2 = format!("{DRAFT_VERSION_URL}/assets/?metadata=1&order=path&page=2");
+            let asset = |id: &str, path: &str| {
+                json!({
+                    "asset_id": id,
+                    "blob": "89ab-cdef",
+                    "zarr": null,
+                    "path": path,
+                    "size": 42,
+                    "created": "2024-01-01T00:00:00Z",
+                    "modified": "2024-01-02T00:00:00Z",
+                    "metadata": {
+                        "encodingFormat": "application/x-nwb",
+                        "contentUrl": [],
+                        "digest": {"dandi:dandi-etag": "abc-1"},
+                    },
+                })
+            };
+            let transport = transport()
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/assets/?metadata=1&order=path"),
+                    &json!({
+                        "next": page2,
+                        "results": [asset("0001", "a.nwb"), asset("0002", "b/c.nwb")],
+                    }),
+                )
+                .json(
+                    &page2,
+                    &json!({"next": null, "results": [asset("0003", "d.nwb")]}),
+                );
+            let (status, _, body) =
+                get(transport, "/dandisets/000027/draft/assets.jsonl", None).await;
+            assert_eq!(status, StatusCode::OK);
+            let paths = body
+                .lines()
+                .map(|ln| serde_json::from_str::<Value>(ln).unwrap()["path"].clone())
+                .collect::<Vec<_>>();
+            assert_eq!(paths, ["a.nwb", "b/c.nwb", "d.nwb"]);
+        }
+    }
+
+    mod validators {
+        use super::*;
+        use crate::consts::{IMMUTABLE_CACHE_CONTROL, MUTABLE_CACHE_CONTROL};
+        use assert_matches::assert_matches;
+
+        fn parse(path: &str) -> DavPath {
+            DavPath::from_components(
+                path.split('/')
+                    .filter(|c| !c.is_empty())
+                    .map(|c| c.parse::<Component>().unwrap())
+                    .collect(),
+                &PathConfig::default(),
+            )
+            .unwrap()
+        }
+
+        /// Return the validator variant for a `GET` request for a listing in
+        /// the given format with no query parameters
+        fn listing(
+            format: ListingFormat,
+        ) -> (
+            ListingFormat,
+            Option<PageRequest>,
+            Option<SortRequest>,
+            Option<&'static MatchFilter>,
+        ) {
+            (format, None, None, None)
+        }
+
+        #[rstest]
+        #[case("/dandisets/000027/releases/0.210831.2033/", true)]
+        #[case("/dandisets/000027/releases/0.210831.2033/sub-RAT123/", true)]
+        #[case("/dandisets/000027/releases/0.210831.2033/dandiset.yaml", true)]
+        #[case("/dandisets/000027/draft/", false)]
+        #[case("/dandisets/000027/draft/sub-RAT123/", false)]
+        #[case("/dandisets/000027/draft/dandiset.yaml", false)]
+        #[case("/dandisets/000027/latest/", false)]
+        #[case("/dandisets/000027/releases/latest/", false)]
+        #[case("/dandisets/000027/releases/latest/sub-RAT123/", false)]
+        #[case("/dandisets/000027/", false)]
+        #[case("/dandisets/", false)]
+        #[case("/zarrs/001/e3b/", false)]
+        #[case("/zarrs/001/e3b/001e3b6d-26fb-463f-af28-520a25680ab4/", false)]
+        #[case(
+            "/zarrs/001/e3b/001e3b6d-26fb-463f-af28-520a25680ab4/326273bcc8730474323a66ea4e3daa49-113328--97037755426.zarr/",
+            true
+        )]
+        #[case(
+            "/zarrs/001/e3b/001e3b6d-26fb-463f-af28-520a25680ab4/326273bcc8730474323a66ea4e3daa49-113328--97037755426.zarr/0/",
+            true
+        )]
+        #[case("/zarrs/001e3b6d-26fb-463f-af28-520a25680ab4/", false)]
+        #[case("/zarrs/001e3b6d-26fb-463f-af28-520a25680ab4/0/", false)]
+        #[case("/zarrs/001/e3b/001e3b6d-26fb-463f-af28-520a25680ab4/latest/", false)]
+        #[case("/", false)]
+        fn test_is_immutable(#[case] path: &str, #[case] immutable: bool) {
+            assert_eq!(DandiDav::is_immutable(&parse(path)), immutable);
+        }
+
+        #[tokio::test]
+        async fn immutable() {
+            let path = parse("/dandisets/000027/releases/0.210831.2033/");
+            let validators = dav()
+                .validators(&path, listing(ListingFormat::Html))
+                .await
+                .unwrap();
+            assert!(validators.immutable);
+            assert_eq!(validators.modified, None);
+            assert_matches!(validators.etag, Some(etag) => {
+                assert!(etag.starts_with('"'));
+                assert!(etag.ends_with('"'));
+            });
+        }
+
+        #[tokio::test]
+        async fn unversioned() {
+            let validators = dav()
+                .validators(&parse("/dandisets/"), listing(ListingFormat::Html))
+                .await
+                .unwrap();
+            assert_eq!(validators, Validators::default());
+        }
+
+        #[tokio::test]
+        async fn distinct() {
+            let dav = dav();
+            let version = parse("/dandisets/000027/releases/0.210831.2033/");
+            let folder = parse("/dandisets/000027/releases/0.210831.2033/sub-RAT123/");
+            let etag = |path, variant| {
+                let dav = &dav;
+                async move { dav.validators(path, variant).await.unwrap().etag }
+            };
+            let html = etag(&version, listing(ListingFormat::Html)).await;
+            assert!(html.is_some());
+            assert_eq!(etag(&version, listing(ListingFormat::Html)).await, html);
+            assert_ne!(etag(&version, listing(ListingFormat::Json)).await, html);
+            assert_ne!(etag(&folder, listing(ListingFormat::Html)).await, html);
+        }
+
+        #[tokio::test]
+        async fn distinct_by_config() {
+            let path = &parse("/dandisets/000027/releases/0.210831.2033/sub-RAT123/");
+            let etag = |dav: DandiDav| async move {
+                dav.validators(path, listing(ListingFormat::Html))
+                    .await
+                    .unwrap()
+                    .etag
+            };
+            let fresh = || dav_with_transport(FakeTransport::new());
+            let base = etag(fresh()).await;
+            assert!(base.is_some());
+            let mut manifests = fresh();
+            manifests.zarrs_via_manifests = !manifests.zarrs_via_manifests;
+            assert_ne!(etag(manifests).await, base);
+            let mut preview = fresh();
+            preview.inline_preview_max_bytes = Some(1024);
+            assert_ne!(etag(preview).await, base);
+            let mut filtered = fresh();
+            filtered.dandiset_filter = Arc::new(DandisetFilter::new(
+                Vec::new(),
+                vec![glob::Pattern::new("0000*").unwrap()],
+            ));
+            assert_ne!(etag(filtered).await, base);
+            let tmpdir = tempfile::tempdir().unwrap();
+            let mut mounted = fresh();
+            mounted.local_mounts.push(
+                LocalSource::new(LocalMountSpec {
+                    name: "local".parse().unwrap(),
+                    dir: tmpdir.path().to_owned(),
+                })
+                .await
+                .unwrap(),
+            );
+            assert_ne!(etag(mounted).await, base);
+        }
+
+        #[rstest]
+        #[case("?page=2")]
+        #[case("?per_page=10")]
+        #[case("?sort=size")]
+        #[case("?order=desc")]
+        #[case("?match=sub-*")]
+        #[tokio::test]
+        async fn distinct_by_query(#[case] query: &str) {
+            let uri = "/dandisets/000027/releases/0.210831.2033/";
+            let dav = dav();
+            let etag = dav
+                .validators(&parse(uri), listing(ListingFormat::Html))
+                .await
+                .unwrap()
+                .etag
+                .unwrap();
+            let req = Request::get(format!("{uri}{query}"))
+                .header("If-None-Match", etag.as_str())
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav.handle_request(req).await.unwrap();
+            assert_ne!(resp.status(), StatusCode::NOT_MODIFIED);
+        }
+
+        #[rstest]
+        #[case("text/html", ListingFormat::Html)]
+        #[case("application/json", ListingFormat::Json)]
+        #[tokio::test]
+        async fn not_modified(#[case] accept: &str, #[case] format: ListingFormat) {
+            let uri = "/dandisets/000027/releases/0.210831.2033/";
+            let dav = dav();
+            let etag = dav
+                .validators(&parse(uri), listing(format))
+                .await
+                .unwrap()
+                .etag
+                .unwrap();
+            // The Archive client points to a closed port, so this only
+            // succeeds if nothing is fetched from upstream.
+            let req = Request::get(uri)
+                .header("Accept", accept)
+                .header("If-None-Match", format!("\"foo\", {etag}"))
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav.handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(resp.headers()["ETag"], etag.as_str());
+            assert_eq!(resp.headers()[CACHE_CONTROL], IMMUTABLE_CACHE_CONTROL);
+        }
+
+        #[tokio::test]
+        async fn private() {
+            let uri = "/dandisets/000027/releases/0.210831.2033/";
+            let dav = dav();
+            let etag = dav
+                .validators(&parse(uri), listing(ListingFormat::Html))
+                .await
+                .unwrap()
+                .etag
+                .unwrap();
+            let req = Request::get(uri)
+                .header("Authorization", "token 0123456789abcdef")
+                .header("If-None-Match", etag)
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav.handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(resp.headers()[CACHE_CONTROL], PRIVATE_CACHE_CONTROL);
+        }
+
+        #[tokio::test]
+        async fn propfind_not_modified() {
+            let uri = "/dandisets/000027/releases/0.210831.2033/";
+            let dav = dav();
+            let query = PropFind::AllProp {
+                include: Vec::new(),
+            };
+            let etag = dav
+                .validators(&parse(uri), (Depth::One, &query))
+                .await
+                .unwrap()
+                .etag
+                .unwrap();
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri(uri)
+                .header("Depth", "1")
+                .header("If-None-Match", etag.as_str())
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::clone(&dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(resp.headers()["ETag"], etag.as_str());
+            // The entity tag is specific to the depth.
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri(uri)
+                .header("Depth", "0")
+                .header("If-None-Match", etag.as_str())
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav.handle_request(req).await.unwrap();
+            assert_ne!(resp.status(), StatusCode::NOT_MODIFIED);
+        }
+
+        #[tokio::test]
+        async fn draft() {
+            let transport = Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{DRAFT_VERSION_URL}/info/"),
+                        &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+                    )
+                    .json(&format!("{DRAFT_VERSION_URL}/"), &version_metadata())
+                    .json(
+                        &format!("{DRAFT_VERSION_URL}/assets/paths/"),
+                        &serde_json::json!({"next": null, "results": []}),
+                    ),
+            );
+            let dav = dav_with_transport(transport.clone());
+            let uri = "/dandisets/000027/draft/";
+            let validators = dav
+                .validators(&parse(uri), listing(ListingFormat::Html))
+                .await
+                .unwrap();
+            assert!(!validators.immutable);
+            assert_eq!(
+                validators.modified,
+                Some(time::macros::datetime!(2020-03-16 12:00:00 UTC))
+            );
+            let etag = validators.etag.unwrap();
+            assert!(etag.starts_with("W/\""));
+            assert_ne!(
+                dav.validators(&parse(uri), listing(ListingFormat::Json))
+                    .await
+                    .unwrap()
+                    .etag,
+                Some(etag.clone())
+            );
+            // The draft is mutable, so its listing is resolved before the
+            // entity tag is compared.
+            let req = Request::get(uri)
+                .header("If-None-Match", etag.as_str())
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(resp.headers()["ETag"], etag.as_str());
+            assert_eq!(
+                resp.headers()["Last-Modified"],
+                "Mon, 16 Mar 2020 12:00:00 GMT"
+            );
+            assert_eq!(resp.headers()[CACHE_CONTROL], MUTABLE_CACHE_CONTROL);
+        }
+
+        #[rstest]
+        #[case(None, MUTABLE_CACHE_CONTROL)]
+        #[case(Some("token 0123456789abcdef"), PRIVATE_CACHE_CONTROL)]
+        #[tokio::test]
+        async fn vary_authorization(
+            #[case] authorization: Option<&str>,
+            #[case] cache_control: &str,
+        ) {
+            let transport = Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{DRAFT_VERSION_URL}/info/"),
+                        &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+                    )
+                    .json(&format!("{DRAFT_VERSION_URL}/"), &version_metadata())
+                    .json(
+                        &format!("{DRAFT_VERSION_URL}/assets/paths/"),
+                        &serde_json::json!({"next": null, "results": []}),
+                    ),
+            );
+            let dav = Arc::new(dav_with_transport(transport));
+            let mut req = Request::get("/dandisets/000027/draft/").header("Accept", "text/html");
+            if let Some(authorization) = authorization {
+                req = req.header("Authorization", authorization);
+            }
+            let resp = dav
+                .handle_request(req.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()[CACHE_CONTROL], cache_control);
+            let vary = resp
+                .headers()
+                .get_all(VARY)
+                .iter()
+                .map(|v| v.to_str().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(vary, ["Accept", "Authorization"]);
+        }
+
+        #[tokio::test]
+        async fn wildcard_not_short_circuited() {
+            let req = Request::get("/dandisets/000027/releases/0.210831.2033/")
+                .header("If-None-Match", "*")
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav().handle_request(req).await.unwrap();
+            assert_ne!(resp.status(), StatusCode::NOT_MODIFIED);
+        }
+    }
+
+    mod mirror {
+        use super::*;
+        use crate::consts::{MIRROR_MANIFEST_NAME, MIRROR_PARTIAL_SUFFIX};
+        use crate::dav::mirror::{MirrorError, MirrorReport};
+        use assert_matches::assert_matches;
+        use serde_json::json;
+        use std::path::Path;
+
+        const DOWNLOAD_URL: &str = "https://api.dandiarchive.org/api/assets/0123-4567/download/";
+
+        const MIRROR_PATH: &str = "dandisets/000027/releases/0.210831.2033/";
+
+        // The DANDI ETag of "Hello, World!"
+        const ETAG: &str = "567f9bb9cf051b9252b862c2a763e913-1";
+
+        fn transport(etag: &str) -> Arc<FakeTransport> {
+            Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/info/"),
+                        &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                    )
+                    .json(&format!("{PUBLISHED_VERSION_URL}/"), &version_metadata())
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/assets/paths/"),
+                        &json!({
+                            "next": null,
+                            "results": [{"path": "hello.txt", "asset": {"asset_id": "0123-4567"}}],
+                        }),
+                    )
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/assets/0123-4567/info/"),
+                        &json!({
+                            "asset_id": "0123-4567",
+                            "blob": "89ab-cdef",
+                            "zarr": null,
+                            "path": "hello.txt",
+                            "size": 13,
+                            "created": "2024-01-01T00:00:00Z",
+                            "modified": "2024-01-02T00:00:00Z",
+                            "metadata": {
+                                "encodingFormat": "text/plain",
+                                "contentUrl": [DOWNLOAD_URL],
+                                "digest": {"dandi:dandi-etag": etag},
+                            },
+                        }),
+                    )
+                    .respond(DOWNLOAD_URL, StatusCode::OK, &[], "Hello, World!"),
+            )
+        }
+
+        fn mirror_dav(transport: &Arc<FakeTransport>) -> Arc<DandiDav> {
+            let dav = dav_with_transport(transport.clone());
+            Arc::new(dav)
+        }
+
+        async fn run(
+            transport: &Arc<FakeTransport>,
+            outdir: &Path,
+        ) -> Result<MirrorReport, MirrorError> {
+            mirror_dav(transport).mirror(MIRROR_PATH, outdir, 1).await
+        }
+
+        async fn run_path(path: &str, outdir: &Path) -> Result<MirrorReport, MirrorError> {
+            mirror_dav(&transport(ETAG)).mirror(path, outdir, 1).await
+        }
+
+        fn downloads(transport: &FakeTransport) -> usize {
+            transport
+                .requests()
+                .into_iter()
+                .filter(|(_, url)| url == DOWNLOAD_URL)
+                .count()
+        }
+
+        #[tokio::test]
+        async fn mirrors_version() {
+            let tmpdir = tempfile::tempdir().unwrap();
+            let transport = transport(ETAG);
+            let report = run(&transport, tmpdir.path()).await.unwrap();
+            assert_eq!(
+                report,
+                MirrorReport {
+                    downloaded: 1,
+                    downloaded_bytes: 13,
+                    up_to_date: 0,
+                    unverified: 0,
+                    generated: 1,
+                    skipped: 1,
+                }
+            );
+            assert_eq!(
+                std::fs::read_to_string(tmpdir.path().join("hello.txt")).unwrap(),
+                "Hello, World!"
+            );
+            assert!(tmpdir.path().join("dandiset.yaml").exists());
+            assert!(!tmpdir.path().join("assets.jsonl").exists());
+            let manifest = std::fs::read_to_string(tmpdir.path().join(MIRROR_MANIFEST_NAME))
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(manifest.len(), 2);
+            assert_eq!(manifest[0]["path"], "dandiset.yaml");
+            assert_eq!(manifest[0]["checksum"], serde_json::Value::Null);
+            assert_eq!(manifest[1]["path"], "hello.txt");
+            assert_eq!(manifest[1]["size"], 13);
+            assert_eq!(manifest[1]["checksum"], ETAG);
+            assert_eq!(manifest[1]["url"], DOWNLOAD_URL);
+            assert_eq!(downloads(&transport), 1);
+
+            // Rerunning the mirror does not download anything again:
+            let report = run(&transport, tmpdir.path()).await.unwrap();
+            assert_eq!(report.downloaded, 0);
+            assert_eq!(report.up_to_date, 1);
+            assert_eq!(downloads(&transport), 1);
+        }
+
+        #[tokio::test]
+        async fn resumes_partial_download() {
+            let tmpdir = tempfile::tempdir().unwrap();
+            let partial = tmpdir
+                .path()
+                .join(format!("hello.txt{MIRROR_PARTIAL_SUFFIX}"));
+            std::fs::write(&partial, "Hello, ").unwrap();
+            let transport = transport(ETAG);
+            let report = run(&transport, tmpdir.path()).await.unwrap();
+            assert_eq!(report.downloaded, 1);
+            assert_eq!(report.downloaded_bytes, 6);
+            assert_eq!(
+                std::fs::read_to_string(tmpdir.path().join("hello.txt")).unwrap(),
+                "Hello, World!"
+            );
+            assert!(!partial.exists());
+            assert_eq!(
+                transport.request_headers().last().unwrap()["Range"],
+                "bytes=7-12"
+            );
+        }
+
+        #[tokio::test]
+        async fn checksum_mismatch() {
+            let tmpdir = tempfile::tempdir().unwrap();
+            let transport = transport("00000000000000000000000000000000-1");
+            let r = run(&transport, tmpdir.path()).await;
+            assert_matches!(r, Err(MirrorError::ChecksumMismatch { expected, actual, .. }) => {
+                assert_eq!(expected, "00000000000000000000000000000000-1");
+                assert_eq!(actual, ETAG);
+            });
+            assert!(!tmpdir.path().join("hello.txt").exists());
+            assert!(!tmpdir
+                .path()
+                .join(format!("hello.txt{MIRROR_PARTIAL_SUFFIX}"))
+                .exists());
+        }
+
+        #[tokio::test]
+        async fn invalid_path() {
+            let tmpdir = tempfile::tempdir().unwrap();
+            let r = run_path("dandisets/bad/", tmpdir.path()).await;
+            assert_matches!(r, Err(MirrorError::InvalidPath(p)) => {
+                assert_eq!(p, "dandisets/bad/");
+            });
+        }
+    }
+
+    mod inline_preview {
+        use super::*;
+        use axum::http::header::{HeaderMap, CONTENT_DISPOSITION, LOCATION};
+        use serde_json::json;
+
+        const DOWNLOAD_URL: &str = "https://api.dandiarchive.org/api/assets/0123-4567/download/";
+
+        const CONTENT: &str = "id\tage\nsub-01\t42\n";
+
+        fn transport() -> Arc<FakeTransport> {
+            Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/info/"),
+                        &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                    )
+                    .json(
+                        &format!(
+                            "{PUBLISHED_VERSION_URL}/assets/?path=participants.tsv&metadata=1&order=path"
+                        ),
+                        &asset("participants.tsv", 19),
+                    )
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/assets/?path=big.json&metadata=1&order=path"),
+                        &asset("big.json", 4096),
+                    )
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/assets/?path=sub-01.nwb&metadata=1&order=path"),
+                        &asset("sub-01.nwb", 19),
+                    )
+                    .respond(DOWNLOAD_URL, StatusCode::OK, &[], CONTENT),
+            )
+        }
+
+        fn asset(path: &str, size: u64) -> serde_json::Value {
+            json!({
+                "next": null,
+                "results": [{
+                    "asset_id": "0123-4567",
+                    "blob": "89ab-cdef",
+                    "zarr": null,
+                    "path": path,
+                    "size": size,
+                    "created": "2024-01-01T00:00:00Z",
+                    "modified": "2024-01-02T00:00:00Z",
+                    "metadata": {
+                        "encodingFormat": "text/tab-separated-values",
+                        "contentUrl": [DOWNLOAD_URL],
+                        "digest": {"dandi:dandi-etag": "abc-1"},
+                    },
+                }],
+            })
+        }
+
+        fn downloads(transport: &FakeTransport) -> usize {
+            transport
+                .requests()
+                .into_iter()
+                .filter(|(_, url)| url == DOWNLOAD_URL)
+                .count()
+        }
+
+        async fn get(
+            transport: &Arc<FakeTransport>,
+            max_bytes: Option<u64>,
+            uri: &str,
+        ) -> (StatusCode, HeaderMap, String) {
+            let mut dav = dav_with_transport(transport.clone());
+            dav.inline_preview_max_bytes = max_bytes;
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, headers, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn serves_inline() {
+            let transport = transport();
+            let (status, headers, body) = get(
+                &transport,
+                Some(1024),
+                "/dandisets/000027/releases/0.210831.2033/participants.tsv",
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(headers[CONTENT_TYPE], TEXT_CONTENT_TYPE);
+            assert_eq!(headers[X_CONTENT_TYPE_OPTIONS], "nosniff");
+            assert!(!headers.contains_key(CONTENT_DISPOSITION));
+            assert!(headers.contains_key("ETag"));
+            assert_eq!(body, CONTENT);
+            assert_eq!(downloads(&transport), 1);
+        }
+
+        #[tokio::test]
+        async fn serves_range() {
+            let transport = transport();
+            let mut dav = dav_with_transport(transport.clone());
+            dav.inline_preview_max_bytes = Some(1024);
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri("/dandisets/000027/releases/0.210831.2033/participants.tsv")
+                .header("Range", "bytes=0-1")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(&body[..], b"id");
+        }
+
+        #[tokio::test]
+        async fn inline_disposition() {
+            let transport = transport();
+            let (status, headers, body) = get(
+                &transport,
+                Some(1024),
+                "/dandisets/000027/releases/0.210831.2033/participants.tsv?inline=1",
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                headers[CONTENT_DISPOSITION],
+                "inline; filename=\"participants.tsv\"; filename*=UTF-8''participants.tsv"
+            );
+            assert_eq!(body, CONTENT);
+        }
+
+        #[rstest]
+        #[case(None, "participants.tsv")]
+        #[case(Some(1024), "participants.tsv?download=1")]
+        #[case(Some(1024), "big.json")]
+        #[case(Some(1024), "sub-01.nwb")]
+        #[case(Some(18), "participants.tsv")]
+        #[tokio::test]
+        async fn redirects(#[case] max_bytes: Option<u64>, #[case] path: &str) {
+            let transport = transport();
+            let (status, headers, _) = get(
+                &transport,
+                max_bytes,
+                &format!("/dandisets/000027/releases/0.210831.2033/{path}"),
+            )
+            .await;
+            assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+            assert!(headers[LOCATION]
+                .to_str()
+                .unwrap()
+                .starts_with(DOWNLOAD_URL));
+            assert!(!headers.contains_key("ETag"));
+            assert_eq!(downloads(&transport), 0);
+        }
+
+        #[tokio::test]
+        async fn html_not_sniffed() {
+            let html = "<html><script>alert(1)</script></html>";
+            let transport = Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/info/"),
+                        &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                    )
+                    .json(
+                        &format!(
+                            "{PUBLISHED_VERSION_URL}/assets/?path=page.json&metadata=1&order=path"
+                        ),
+                        &asset("page.json", html.len() as u64),
+                    )
+                    .respond(DOWNLOAD_URL, StatusCode::OK, &[], html),
+            );
+            let (status, headers, body) = get(
+                &transport,
+                Some(1024),
+                "/dandisets/000027/releases/0.210831.2033/page.json",
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(headers[CONTENT_TYPE], TEXT_CONTENT_TYPE);
+            assert_eq!(headers[X_CONTENT_TYPE_OPTIONS], "nosniff");
+            assert_eq!(body, html);
+        }
+
+        #[tokio::test]
+        async fn redirects_on_fetch_failure() {
+            let transport = Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/info/"),
+                        &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                    )
+                    .json(
+                        &format!(
+                            "{PUBLISHED_VERSION_URL}/assets/?path=participants.tsv&metadata=1&order=path"
+                        ),
+                        &asset("participants.tsv", 19),
+                    ),
+            );
+            let (status, headers, _) = get(
+                &transport,
+                Some(1024),
+                "/dandisets/000027/releases/0.210831.2033/participants.tsv",
+            )
+            .await;
+            assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+            assert_eq!(headers[LOCATION], DOWNLOAD_URL);
+            assert_eq!(downloads(&transport), 1);
+        }
+    }
+
+    mod draft_snapshot {
+        use super::*;
+        use crate::consts::MUTABLE_CACHE_CONTROL;
+        use serde_json::json;
+
+        fn transport() -> FakeTransport {
+            FakeTransport::new()
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/assets/paths/"),
+                    &json!({
+                        "next": null,
+                        "results": [{"path": "sub-01", "aggregate_files": 1, "aggregate_size": 42}],
+                    }),
+                )
+                .json(&format!("{DRAFT_VERSION_URL}/"), &version_metadata())
+        }
+
+        fn info_requests(transport: &FakeTransport) -> usize {
+            transport
+                .requests()
+                .into_iter()
+                .filter(|(_, url)| *url == format!("{DRAFT_VERSION_URL}/info/"))
+                .count()
+        }
+
+        async fn propfind(transport: &Arc<FakeTransport>) -> (Response<Body>, String) {
+            let dav = dav_with_transport(transport.clone());
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri("/dandisets/000027/draft/")
+                .header("Depth", "1")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let (parts, body) = resp.into_parts();
+            let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+            (
+                Response::from_parts(parts, Body::empty()),
+                String::from_utf8(body.to_vec()).unwrap(),
+            )
+        }
+
+        #[tokio::test]
+        async fn stable() {
+            let transport = Arc::new(transport().json(
+                &format!("{DRAFT_VERSION_URL}/info/"),
+                &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+            ));
+            let (resp, body) = propfind(&transport).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert!(resp.headers().contains_key("ETag"));
+            assert!(body.contains("sub-01"));
+            assert!(!body.contains("listing-unstable"));
+        }
+
+        #[tokio::test]
+        async fn retries_once() {
+            // The version is modified during the first walk but not during
+            // the second.
+            let before = version_info("draft", "2020-03-16T12:00:00.000000Z");
+            let after = version_info("draft", "2020-03-16T12:00:01.000000Z");
+            let transport = Arc::new(transport().json_sequence(
+                &format!("{DRAFT_VERSION_URL}/info/"),
+                &[
+                    before.clone(),
+                    before.clone(),
+                    before.clone(),
+                    before,
+                    after,
+                ],
+            ));
+            let (resp, body) = propfind(&transport).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert!(!body.contains("listing-unstable"));
+            // The validators were computed before the modification:
+            assert!(!resp.headers().contains_key("ETag"));
+            assert_eq!(resp.headers()[CACHE_CONTROL], MUTABLE_CACHE_CONTROL);
+            assert_eq!(
+                transport
+                    .requests()
+                    .into_iter()
+                    .filter(|(_, url)| *url == format!("{DRAFT_VERSION_URL}/assets/paths/"))
+                    .count(),
+                2
+            );
+        }
+
+        #[tokio::test]
+        async fn unstable() {
+            let infos = (0..20)
+                .map(|i| version_info("draft", &format!("2020-03-16T12:00:{i:02}.000000Z")))
+                .collect::<Vec<_>>();
+            let transport =
+                Arc::new(transport().json_sequence(&format!("{DRAFT_VERSION_URL}/info/"), &infos));
+            let (resp, body) = propfind(&transport).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert!(!resp.headers().contains_key("ETag"));
+            assert_eq!(body.matches("<listing-unstable").count(), 1);
+            assert!(body.contains("sub-01"));
+            assert_eq!(info_requests(&transport), 2 + 3 * DRAFT_LISTING_ATTEMPTS);
+        }
+
+        #[tokio::test]
+        async fn published_not_refetched() {
+            let transport = Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/info/"),
+                        &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                    )
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/assets/paths/"),
+                        &json!({"next": null, "results": []}),
+                    )
+                    .json(&format!("{PUBLISHED_VERSION_URL}/"), &version_metadata()),
+            );
+            let dav = dav_with_transport(transport.clone());
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri("/dandisets/000027/releases/0.210831.2033/")
+                .header("Depth", "1")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            // The listing is streamed, so the children are only fetched once
+            // the body is read:
+            axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(
+                transport
+                    .requests()
+                    .into_iter()
+                    .filter(|(_, url)| url.ends_with("/assets/paths/"))
+                    .count(),
+                1
+            );
+        }
+    }
+
+    mod streamed_propfind {
+        use super::*;
+        use serde_json::json;
+
+        fn transport(list_children: bool) -> FakeTransport {
+            let transport = FakeTransport::new()
+                .json(
+                    &format!("{PUBLISHED_VERSION_URL}/info/"),
+                    &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                )
+                .json(&format!("{PUBLISHED_VERSION_URL}/"), &version_metadata());
+            if list_children {
+                transport.json(
+                    &format!("{PUBLISHED_VERSION_URL}/assets/paths/"),
+                    &json!({
+                        "next": null,
+                        "results": [{"path": "sub-01", "aggregate_files": 1, "aggregate_size": 42}],
+                    }),
+                )
+            } else {
+                transport
+            }
+        }
+
+        async fn propfind(transport: FakeTransport) -> Response<Body> {
+            let dav = dav_with_transport(transport);
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri("/dandisets/000027/releases/0.210831.2033/")
+                .header("Depth", "1")
+                .body(Body::empty())
+                .unwrap();
+            Arc::new(dav).handle_request(req).await.unwrap()
+        }
+
+        #[tokio::test]
+        async fn lists_children() {
+            let resp = propfind(transport(true)).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert!(resp.headers().contains_key("ETag"));
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            assert!(body.starts_with("<?xml"));
+            assert!(body.ends_with("</multistatus>\n"));
+            for href in [
+                "/dandisets/000027/releases/0.210831.2033/",
+                "/dandisets/000027/releases/0.210831.2033/sub-01/",
+                "/dandisets/000027/releases/0.210831.2033/dandiset.yaml",
+                "/dandisets/000027/releases/0.210831.2033/assets.jsonl",
+            ] {
+                assert!(
+                    body.contains(&format!("<href>{href}</href>")),
+                    "{href} not listed"
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn error_aborts_body() {
+            let resp = propfind(transport(false)).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert!(axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .is_err());
+        }
+    }
+
+    mod copy_requests {
+        use super::*;
+        use crate::httputil::FakeUploader;
+        use axum::http::header::{AUTHORIZATION, IF_NONE_MATCH};
+        use serde_json::json;
+
+        const DOWNLOAD_URL: &str = "https://api.dandiarchive.org/api/assets/0123-4567/download/";
+
+        const CONTENT: &str = "Hello, WebDAV!\n";
+
+        const ASSET_URI: &str = "/dandisets/000027/releases/0.210831.2033/sub-01.nwb";
+
+        const DESTINATION: &str = "https://dav.example.com/storage/sub-01.nwb";
+
+        fn transport() -> Arc<FakeTransport> {
+            Arc::new(
+                FakeTransport::new()
+                    .json(
+                        &format!("{PUBLISHED_VERSION_URL}/info/"),
+                        &version_info("0.210831.2033", "2021-08-31T20:33:00.000000Z"),
+                    )
+                    .json(
+                        &format!(
+                            "{PUBLISHED_VERSION_URL}/assets/?path=sub-01.nwb&metadata=1&order=path"
+                        ),
+                        &json!({
+                            "next": null,
+                            "results": [{
+                                "asset_id": "0123-4567",
+                                "blob": "89ab-cdef",
+                                "zarr": null,
+                                "path": "sub-01.nwb",
+                                "size": CONTENT.len(),
+                                "created": "2024-01-01T00:00:00Z",
+                                "modified": "2024-01-02T00:00:00Z",
+                                "metadata": {
+                                    "encodingFormat": "application/x-nwb",
+                                    "contentUrl": [DOWNLOAD_URL],
+                                    "digest": {"dandi:dandi-etag": "abc-1"},
+                                },
+                            }],
+                        }),
+                    )
+                    .respond(DOWNLOAD_URL, StatusCode::OK, &[], CONTENT),
+            )
+        }
+
+        async fn copy(
+            uploader: Option<&Arc<FakeUploader>>,
+            uri: &str,
+            headers: &[(&str, &str)],
+        ) -> Response<Body> {
+            let transport = transport();
+            let mut dav = dav_with_transport(transport.clone());
+            dav.copy = uploader.map(|up| {
+                CopyConfig::with_uploader(
+                    vec!["https://dav.example.com/storage".parse().unwrap()],
+                    up.clone(),
+                )
+            });
+            let mut req = Request::builder().method("COPY").uri(uri);
+            for &(name, value) in headers {
+                req = req.header(name, value);
+            }
+            Arc::new(dav)
+                .handle_request(req.body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+        }
+
+        #[tokio::test]
+        async fn copies_asset() {
+            let uploader = Arc::new(FakeUploader::new(StatusCode::CREATED));
+            let resp = copy(
+                Some(&uploader),
+                ASSET_URI,
+                &[
+                    ("Destination", DESTINATION),
+                    ("Overwrite", "F"),
+                    ("X-Destination-Authorization", "Bearer hunter2"),
+                ],
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+            let uploads = uploader.uploads();
+            assert_eq!(uploads.len(), 1);
+            let (url, headers, body) = &uploads[0];
+            assert_eq!(url, DESTINATION);
+            assert_eq!(headers[CONTENT_TYPE], "application/x-nwb");
+            assert_eq!(headers[CONTENT_LENGTH], CONTENT.len().to_string().as_str());
+            assert_eq!(headers[IF_NONE_MATCH], "*");
+            assert_eq!(headers[AUTHORIZATION], "Bearer hunter2");
+            assert_eq!(body, CONTENT.as_bytes());
+        }
+
+        #[rstest]
+        #[case(StatusCode::OK, StatusCode::NO_CONTENT)]
+        #[case(StatusCode::NO_CONTENT, StatusCode::NO_CONTENT)]
+        #[case(StatusCode::PRECONDITION_FAILED, StatusCode::PRECONDITION_FAILED)]
+        #[case(StatusCode::FORBIDDEN, StatusCode::BAD_GATEWAY)]
+        #[case(StatusCode::INTERNAL_SERVER_ERROR, StatusCode::BAD_GATEWAY)]
+        #[tokio::test]
+        async fn maps_destination_status(
+            #[case] upstream: StatusCode,
+            #[case] expected: StatusCode,
+        ) {
+            let uploader = Arc::new(FakeUploader::new(upstream));
+            let resp = copy(Some(&uploader), ASSET_URI, &[("Destination", DESTINATION)]).await;
+            assert_eq!(resp.status(), expected);
+            let uploads = uploader.uploads();
+            assert_eq!(uploads.len(), 1);
+            assert!(!uploads[0].1.contains_key(IF_NONE_MATCH));
+            assert!(!uploads[0].1.contains_key(AUTHORIZATION));
+        }
+
+        #[tokio::test]
+        async fn disabled() {
+            let resp = copy(None, ASSET_URI, &[("Destination", DESTINATION)]).await;
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        #[rstest]
+        #[case("https://dav.example.com/elsewhere/sub-01.nwb")]
+        #[case("https://evil.example.com/storage/sub-01.nwb")]
+        #[tokio::test]
+        async fn disallowed_destination(#[case] destination: &str) {
+            let uploader = Arc::new(FakeUploader::new(StatusCode::CREATED));
+            let resp = copy(Some(&uploader), ASSET_URI, &[("Destination", destination)]).await;
+            assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+            assert!(uploader.uploads().is_empty());
+        }
+
+        #[rstest]
+        #[case(&[])]
+        #[case(&[("Destination", "/storage/sub-01.nwb")])]
+        #[case(&[("Destination", DESTINATION), ("Overwrite", "maybe")])]
+        #[tokio::test]
+        async fn bad_headers(#[case] headers: &[(&str, &str)]) {
+            let uploader = Arc::new(FakeUploader::new(StatusCode::CREATED));
+            let resp = copy(Some(&uploader), ASSET_URI, headers).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            assert!(uploader.uploads().is_empty());
+        }
+
+        #[tokio::test]
+        async fn collection() {
+            let uploader = Arc::new(FakeUploader::new(StatusCode::CREATED));
+            let resp = copy(
+                Some(&uploader),
+                "/dandisets/",
+                &[("Destination", DESTINATION)],
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+            assert!(uploader.uploads().is_empty());
+        }
+
+        #[tokio::test]
+        async fn options_advertises_copy() {
+            let transport = transport();
+            let mut dav = dav_with_transport(transport.clone());
+            dav.copy = Some(CopyConfig::with_uploader(
+                vec!["https://dav.example.com/storage".parse().unwrap()],
+                Arc::new(FakeUploader::new(StatusCode::CREATED)),
+            ));
+            let req = Request::builder()
+                .method(Method::OPTIONS)
+                .uri(ASSET_URI)
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            assert_eq!(
+                resp.headers()["Allow"],
+                "GET, HEAD, OPTIONS, PROPFIND, COPY"
+            );
+        }
+    }
+
+    mod sync_reports {
+        use super::*;
+        use serde_json::{json, Value};
+        use time::macros::datetime;
+
+        const VERSION_URI: &str = "/dandisets/000027/draft/";
+
+        fn asset(path: &str, asset_id: &str, modified: &str) -> Value {
+            json!({
+                "asset_id": asset_id,
+                "blob": "89ab-cdef",
+                "zarr": null,
+                "path": path,
+                "size": 42,
+                "created": "2020-03-15T22:56:55.655000Z",
+                "modified": modified,
+                "metadata": {
+                    "encodingFormat": "application/x-nwb",
+                    "contentUrl": ["https://api.dandiarchive.org/api/assets/0123-4567/download/"],
+                    "digest": {"dandi:dandi-etag": "abc-1"},
+                },
+            })
+        }
+
+        fn transport(infos: &[Value], listings: &[Vec<Value>]) -> Arc<FakeTransport> {
+            let listings = listings
+                .iter()
+                .map(|assets| json!({"next": null, "results": assets}))
+                .collect::<Vec<_>>();
+            Arc::new(
+                FakeTransport::new()
+                    .json_sequence(&format!("{DRAFT_VERSION_URL}/info/"), infos)
+                    .json_sequence(
+                        &format!("{DRAFT_VERSION_URL}/assets/?metadata=1&order=path"),
+                        &listings,
+                    )
+                    .json(&format!("{DRAFT_VERSION_URL}/"), &version_metadata()),
+            )
+        }
+
+        fn make_dav(transport: &Arc<FakeTransport>) -> Arc<DandiDav> {
+            let mut dav = dav_with_transport(transport.clone());
+            dav.infinite_depth_limit = Some(100);
+            Arc::new(dav)
+        }
+
+        fn sync_body(token: Option<&str>, level: &str, limit: Option<u64>) -> String {
+            let token = token.map_or_else(
+                || String::from("<D:sync-token/>"),
+                |t| format!("<D:sync-token>{t}</D:sync-token>"),
+            );
+            let limit = limit.map_or_else(String::new, |n| {
+                format!("<D:limit><D:nresults>{n}</D:nresults></D:limit>")
+            });
+            format!(
+                concat!(
+                    r#"<?xml version="1.0" encoding="utf-8" ?>"#,
+                    r#"<D:sync-collection xmlns:D="DAV:">{}"#,
+                    "<D:sync-level>{}</D:sync-level>{}",
+                    "<D:prop><D:getetag/></D:prop>",
+                    "</D:sync-collection>",
+                ),
+                token, level, limit
+            )
+        }
+
+        async fn report(
+            dav: &Arc<DandiDav>,
+            uri: &str,
+            depth: Option<&str>,
+            body: String,
+        ) -> (Response<Body>, String) {
+            let mut req = Request::builder().method("REPORT").uri(uri);
+            if let Some(depth) = depth {
+                req = req.header("Depth", depth);
+            }
+            let resp = dav
+                .handle_request(req.body(Body::from(body)).unwrap())
+                .await
+                .unwrap();
+            let (parts, body) = resp.into_parts();
+            let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+            (
+                Response::from_parts(parts, Body::empty()),
+                String::from_utf8(body.to_vec()).unwrap(),
+            )
+        }
+
+        fn hrefs(body: &str) -> Vec<&str> {
+            body.split("<href>")
+                .skip(1)
+                .filter_map(|s| s.split_once("</href>").map(|(href, _)| href))
+                .collect()
+        }
+
+        const T1: &str = "2020-03-16T12:00:00.000000Z";
+
+        const T2: &str = "2020-03-17T12:00:00.000000Z";
+
+        fn token1() -> String {
+            sync::sync_token(datetime!(2020-03-16 12:00:00 UTC))
+        }
+
+        fn token2() -> String {
+            sync::sync_token(datetime!(2020-03-17 12:00:00 UTC))
+        }
+
+        #[tokio::test]
+        async fn initial_sync() {
+            let transport = transport(
+                &[version_info("draft", T1)],
+                &[vec![
+                    asset("sub-01/a.nwb", "a1", T1),
+                    asset("sub-01/b.nwb", "b1", T1),
+                ]],
+            );
+            let dav = make_dav(&transport);
+            let (resp, body) = report(
+                &dav,
+                VERSION_URI,
+                Some("0"),
+                sync_body(None, "infinite", None),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert_eq!(
+                hrefs(&body),
+                [
+                    "/dandisets/000027/draft/assets.jsonl",
+                    "/dandisets/000027/draft/dandiset.yaml",
+                    "/dandisets/000027/draft/sub-01/",
+                    "/dandisets/000027/draft/sub-01/a.nwb",
+                    "/dandisets/000027/draft/sub-01/b.nwb",
+                ]
+            );
+            assert!(body.contains(&format!("<sync-token>{}</sync-token>", token1())));
+        }
+
+        #[tokio::test]
+        async fn level_one() {
+            let transport = transport(
+                &[version_info("draft", T1)],
+                &[vec![
+                    asset("sub-01/a.nwb", "a1", T1),
+                    asset("sub-01/b.nwb", "b1", T1),
+                ]],
+            );
+            let dav = make_dav(&transport);
+            let (resp, body) = report(&dav, VERSION_URI, None, sync_body(None, "1", None)).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert_eq!(
+                hrefs(&body),
+                [
+                    "/dandisets/000027/draft/assets.jsonl",
+                    "/dandisets/000027/draft/dandiset.yaml",
+                    "/dandisets/000027/draft/sub-01/",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn incremental_sync() {
+            let transport = transport(
+                &[
+                    version_info("draft", T1),
+                    version_info("draft", T1),
+                    version_info("draft", T1),
+                    version_info("draft", T2),
+                ],
+                &[
+                    vec![
+                        asset("sub-01/a.nwb", "a1", T1),
+                        asset("sub-01/b.nwb", "b1", T1),
+                        asset("sub-02/c.nwb", "c1", T1),
+                    ],
+                    vec![
+                        asset("sub-01/b.nwb", "b2", T2),
+                        asset("sub-02/c.nwb", "c1", T1),
+                    ],
+                ],
+            );
+            let dav = make_dav(&transport);
+            let (resp, _) =
+                report(&dav, VERSION_URI, None, sync_body(None, "infinite", None)).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            let (resp, body) = report(
+                &dav,
+                VERSION_URI,
+                None,
+                sync_body(Some(&token1()), "infinite", None),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert_eq!(
+                hrefs(&body),
+                [
+                    "/dandisets/000027/draft/assets.jsonl",
+                    "/dandisets/000027/draft/dandiset.yaml",
+                    "/dandisets/000027/draft/sub-01/",
+                    "/dandisets/000027/draft/sub-01/b.nwb",
+                    "/dandisets/000027/draft/sub-01/a.nwb",
+                ]
+            );
+            assert_eq!(
+                body.matches("<status>HTTP/1.1 404 Not Found</status>")
+                    .count(),
+                1
+            );
+            assert!(body.contains(&format!("<sync-token>{}</sync-token>", token2())));
+        }
+
+        #[tokio::test]
+        async fn current_token() {
+            let transport = transport(&[version_info("draft", T1)], &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let (resp, body) = report(
+                &dav,
+                VERSION_URI,
+                None,
+                sync_body(Some(&token1()), "1", None),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            assert!(hrefs(&body).is_empty());
+            assert!(body.contains(&format!("<sync-token>{}</sync-token>", token1())));
+            assert!(!transport
+                .requests()
+                .iter()
+                .any(|(_, url)| url.contains("/assets/")));
+        }
+
+        #[tokio::test]
+        async fn unknown_token() {
+            let transport = transport(&[version_info("draft", T2)], &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let (resp, body) = report(
+                &dav,
+                VERSION_URI,
+                None,
+                sync_body(Some(&token1()), "1", None),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+            assert!(body.contains("<valid-sync-token />"));
+        }
+
+        #[tokio::test]
+        async fn over_limit() {
+            let transport = transport(
+                &[version_info("draft", T1)],
+                &[vec![asset("sub-01/a.nwb", "a1", T1)]],
+            );
+            let dav = make_dav(&transport);
+            let (resp, body) = report(
+                &dav,
+                VERSION_URI,
+                None,
+                sync_body(None, "infinite", Some(2)),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+            assert!(body.contains("<number-of-matches-within-limits />"));
+        }
+
+        #[tokio::test]
+        async fn over_member_limit() {
+            let transport = transport(
+                &[version_info("draft", T1)],
+                &[vec![
+                    asset("sub-01/a.nwb", "a1", T1),
+                    asset("sub-01/b.nwb", "b1", T1),
+                ]],
+            );
+            let mut dav = Arc::into_inner(make_dav(&transport)).unwrap();
+            dav.infinite_depth_limit = Some(2);
+            let (resp, body) = report(
+                &Arc::new(dav),
+                VERSION_URI,
+                None,
+                sync_body(None, "1", None),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+            assert!(body.contains("<number-of-matches-within-limits />"));
+        }
+
+        #[tokio::test]
+        async fn infinite_depth_disallowed() {
+            let transport = transport(&[version_info("draft", T1)], &[Vec::new()]);
+            let mut dav = Arc::into_inner(make_dav(&transport)).unwrap();
+            dav.infinite_depth_limit = None;
+            let (resp, body) = report(
+                &Arc::new(dav),
+                VERSION_URI,
+                None,
+                sync_body(None, "1", None),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+            assert!(body.contains("<supported-report />"));
+            assert_eq!(transport.requests(), Vec::new());
+        }
+
+        #[tokio::test]
+        async fn unstable() {
+            let infos = (0..20)
+                .map(|i| version_info("draft", &format!("2020-03-16T12:00:{i:02}.000000Z")))
+                .collect::<Vec<_>>();
+            let transport = transport(&infos, &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let (resp, _) = report(&dav, VERSION_URI, None, sync_body(None, "1", None)).await;
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        #[tokio::test]
+        async fn not_a_version() {
+            let transport = transport(&[version_info("draft", T1)], &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let (resp, body) =
+                report(&dav, "/dandisets/000027/", None, sync_body(None, "1", None)).await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+            assert!(body.contains("<supported-report />"));
+        }
+
+        #[tokio::test]
+        async fn unsupported_report() {
+            let transport = transport(&[version_info("draft", T1)], &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let body = String::from(concat!(
+                r#"<?xml version="1.0" encoding="utf-8" ?>"#,
+                r#"<D:expand-property xmlns:D="DAV:"/>"#,
+            ));
+            let (resp, body) = report(&dav, VERSION_URI, None, body).await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+            assert!(body.contains("<supported-report />"));
+        }
+
+        #[rstest]
+        #[case(Some("1"), sync_body(None, "1", None))]
+        #[case(None, sync_body(None, "2", None))]
+        #[case(None, String::new())]
+        #[tokio::test]
+        async fn bad_request(#[case] depth: Option<&str>, #[case] body: String) {
+            let transport = transport(&[version_info("draft", T1)], &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let (resp, _) = report(&dav, VERSION_URI, depth, body).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn options_advertises_report() {
+            let transport = transport(&[version_info("draft", T1)], &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let req = Request::builder()
+                .method(Method::OPTIONS)
+                .uri(VERSION_URI)
+                .body(Body::empty())
+                .unwrap();
+            let resp = dav.handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            assert_eq!(
+                resp.headers()["Allow"],
+                "GET, HEAD, OPTIONS, PROPFIND, REPORT"
+            );
+        }
+
+        #[tokio::test]
+        async fn propfind_sync_token() {
+            let transport = transport(&[version_info("draft", T1)], &[Vec::new()]);
+            let dav = make_dav(&transport);
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri(VERSION_URI)
+                .header("Depth", "0")
+                .body(Body::from(concat!(
+                    r#"<?xml version="1.0" encoding="utf-8" ?>"#,
+                    r#"<D:propfind xmlns:D="DAV:"><D:prop><D:sync-token/></D:prop></D:propfind>"#,
+                )))
+                .unwrap();
+            let resp = dav.handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            assert!(body.contains(&format!("<sync-token>{}</sync-token>", token1())));
+        }
+    }
+
+    mod manifest_zarrs {
+        use super::*;
+        use assert_matches::assert_matches;
+        use axum::http::header::{HeaderMap, LOCATION};
+        use serde_json::{json, Value};
+
+        const ZARR_ID: &str = "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d";
+
+        const CHECKSUM: &str = "6ddc4625befef8d6f9796835648162be-509--710206390";
+
+        fn manifest_url() -> String {
+            format!("https://datasets.datalad.org/dandi/zarr-manifests/zarr-manifests-v2-sorted/128/4a1/{ZARR_ID}/{CHECKSUM}.json")
+        }
+
+        fn transport() -> FakeTransport {
+            FakeTransport::new()
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/info/"),
+                    &version_info("draft", "2020-03-16T12:00:00.000000Z"),
+                )
+                .json(
+                    &format!("{DRAFT_VERSION_URL}/assets/?path=data.zarr&metadata=1&order=path"),
+                    &json!({
+                        "next": null,
+                        "results": [{
+                            "asset_id": "7654-3210",
+                            "blob": null,
+                            "zarr": ZARR_ID,
+                            "path": "data.zarr",
+                            "size": 1793919,
+                            "created": "2024-01-01T00:00:00Z",
+                            "modified": "2024-01-02T00:00:00Z",
+                            "metadata": {
+                                "encodingFormat": "application/x-zarr",
+                                "contentUrl": [
+                                    "https://api.dandiarchive.org/api/assets/7654-3210/download/",
+                                    format!("https://dandiarchive.s3.amazonaws.com/zarr/{ZARR_ID}/"),
+                                ],
+                                "digest": {"dandi:dandi-zarr-checksum": CHECKSUM},
+                            },
+                        }],
+                    }),
+                )
+        }
+
+        fn manifest_transport() -> FakeTransport {
+            transport().json(
+                &manifest_url(),
+                &json!({
+                    "entries": {
+                        ".zattrs": ["v-root-attrs", "2022-06-27T23:07:47+00:00", 24, "e1"],
+                        ".zgroup": ["v-root-group", "2022-06-27T23:07:48+00:00", 24, "e2"],
+                        "0": {
+                            ".zarray": ["v-0-array", "2022-06-27T23:09:00+00:00", 446, "e3"],
+                            "0": ["v-0-0", "2022-06-27T23:10:00+00:00", 1793451, "e4"],
+                        },
+                    },
+                }),
+            )
+        }
+
+        fn manifest_dav(transport: &Arc<FakeTransport>) -> DandiDav {
+            let mut dav = dav_with_transport(transport.clone());
+            dav.zarrman = ZarrManClient::new(
+                ManifestFetcher::new(1_000_000, RetryPolicy::default(), HttpConfig::default())
+                    .unwrap()
+                    .with_transport(transport.clone()),
+            );
+            dav.zarrs_via_manifests = true;
+            dav
+        }
+
+        async fn get(transport: FakeTransport, uri: &str) -> (StatusCode, HeaderMap, Value) {
+            let dav = manifest_dav(&Arc::new(transport));
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .header("Accept", "application/json")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                status,
+                headers,
+                serde_json::from_slice(&body).unwrap_or(Value::Null),
+            )
+        }
+
+        #[tokio::test]
+        async fn lists_zarr() {
+            let (status, _, listing) =
+                get(manifest_transport(), "/dandisets/000027/draft/data.zarr/").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(listing["url"], "/dandisets/000027/draft/data.zarr/");
+            let urls = listing["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["url"].as_str().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                urls,
+                [
+                    "/dandisets/000027/draft/data.zarr/.zattrs",
+                    "/dandisets/000027/draft/data.zarr/.zgroup",
+                    "/dandisets/000027/draft/data.zarr/.zmetadata",
+                    "/dandisets/000027/draft/data.zarr/0/",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn lists_zarr_folder() {
+            let (status, _, listing) =
+                get(manifest_transport(), "/dandisets/000027/draft/data.zarr/0/").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(listing["url"], "/dandisets/000027/draft/data.zarr/0/");
+            let urls = listing["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["url"].as_str().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                urls,
+                [
+                    "/dandisets/000027/draft/data.zarr/0/.zarray",
+                    "/dandisets/000027/draft/data.zarr/0/0",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn redirects_entry() {
+            let (status, headers, _) = get(
+                manifest_transport(),
+                "/dandisets/000027/draft/data.zarr/0/0",
+            )
+            .await;
+            assert!(status.is_redirection());
+            assert_eq!(
+                headers[LOCATION],
+                format!("https://dandiarchive.s3.amazonaws.com/zarr/{ZARR_ID}/0/0?versionId=v-0-0")
+            );
+        }
+
+        #[tokio::test]
+        async fn missing_entry() {
+            let (status, _, _) = get(
+                manifest_transport(),
+                "/dandisets/000027/draft/data.zarr/1/0",
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn missing_manifest() {
+            let transport = Arc::new(transport());
+            let dav = manifest_dav(&transport);
+            let dandiset_id = "000027".parse::<DandisetId>().unwrap();
+            let handler = dav
+                .get_version_handler(&Instance::Primary, &dandiset_id, &VersionSpec::Draft)
+                .await
+                .unwrap();
+            let path = "data.zarr/0/0".parse::<PurePath>().unwrap();
+            assert_matches!(handler.find_manifest_zarr(&path).await, Ok(None));
+            assert!(transport
+                .requests()
+                .iter()
+                .any(|(_, url)| *url == manifest_url()));
+        }
+
+        #[tokio::test]
+        async fn disabled() {
+            let transport = Arc::new(manifest_transport());
+            let mut dav = manifest_dav(&transport);
+            dav.zarrs_via_manifests = false;
+            let dandiset_id = "000027".parse::<DandisetId>().unwrap();
+            let handler = dav
+                .get_version_handler(&Instance::Primary, &dandiset_id, &VersionSpec::Draft)
+                .await
+                .unwrap();
+            let path = "data.zarr/0/0".parse::<PurePath>().unwrap();
+            assert_matches!(handler.find_manifest_zarr(&path).await, Ok(None));
+            assert!(transport.requests().is_empty());
+        }
+    }
+
+    mod releases_latest {
+        use super::*;
+        use serde_json::json;
+
+        const DANDISET_URL: &str = "http://127.0.0.1:9/api/dandisets/000027";
+
+        fn transport(published: bool) -> FakeTransport {
+            let draft = version_info("draft", "2020-03-15T22:56:55.655000Z");
+            let older = version_info("0.210831.2033", "2021-08-31T20:33:00Z");
+            let newer = version_info("0.220126.1903", "2022-01-26T19:03:00Z");
+            let (versions, latest) = if published {
+                (vec![draft.clone(), newer.clone(), older], newer.clone())
+            } else {
+                (vec![draft.clone()], serde_json::Value::Null)
+            };
+            FakeTransport::new()
+                .json(
+                    &format!("{DANDISET_URL}/"),
+                    &json!({
+                        "identifier": "000027",
+                        "created": "2020-03-15T22:56:55.655000Z",
+                        "modified": "2022-01-26T19:03:00Z",
+                        "draft_version": draft,
+                        "most_recent_published_version": latest,
+                    }),
+                )
+                .json(
+                    &format!("{DANDISET_URL}/versions/"),
+                    &json!({"next": null, "results": versions}),
+                )
+                .json(
+                    &format!("{DANDISET_URL}/versions/0.220126.1903/info/"),
+                    &newer,
+                )
+        }
+
+        async fn propfind(uri: &str, depth: &str, published: bool) -> (StatusCode, String) {
+            let dav = dav_with_transport(transport(published));
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri(uri)
+                .header("Depth", depth)
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn releases_listing_includes_latest() {
+            let (status, body) = propfind("/dandisets/000027/releases/", "1", true).await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(body.contains("<href>/dandisets/000027/releases/0.210831.2033/</href>"));
+            assert!(body.contains("<href>/dandisets/000027/releases/0.220126.1903/</href>"));
+            assert!(body.contains("<href>/dandisets/000027/releases/latest/</href>"));
+            assert!(!body.contains("<href>/dandisets/000027/releases/draft/</href>"));
+        }
+
+        #[tokio::test]
+        async fn releases_listing_sans_published() {
+            let (status, body) = propfind("/dandisets/000027/releases/", "1", false).await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(!body.contains("<href>/dandisets/000027/releases/latest/</href>"));
+        }
+
+        #[tokio::test]
+        async fn get_releases_latest() {
+            let (status, body) = propfind("/dandisets/000027/releases/latest/", "0", true).await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(body.contains("<href>/dandisets/000027/releases/latest/</href>"));
+        }
+
+        #[tokio::test]
+        async fn get_releases_latest_sans_published() {
+            let (status, _) = propfind("/dandisets/000027/releases/latest/", "0", false).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn dandiset_listing_includes_releases_tsv() {
+            let (status, body) = propfind("/dandisets/000027/", "1", true).await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(body.contains("<href>/dandisets/000027/releases.tsv</href>"));
+            let (status, body) = propfind("/dandisets/000027/", "1", false).await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(!body.contains("releases.tsv"));
+        }
+
+        #[tokio::test]
+        async fn get_releases_tsv() {
+            let dav = dav_with_transport(transport(true));
+            let req = Request::builder()
+                .uri("/dandisets/000027/releases.tsv")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(
+                resp.headers()[CONTENT_TYPE],
+                "text/tab-separated-values; charset=utf-8"
+            );
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(
+                std::str::from_utf8(&body).unwrap(),
+                concat!(
+                    "version\tcreated\tmodified\tsize\tasset_count\n",
+                    "0.210831.2033\t2021-08-31T20:33:00Z\t2021-08-31T20:33:00Z\t42\t1\n",
+                    "0.220126.1903\t2022-01-26T19:03:00Z\t2022-01-26T19:03:00Z\t42\t1\n",
+                )
+            );
+        }
+
+        #[tokio::test]
+        async fn get_releases_tsv_sans_published() {
+            let (status, _) = propfind("/dandisets/000027/releases.tsv", "0", false).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    mod fast_not_exist {
+        use super::*;
+
+        #[rstest]
+        #[case("GET", "/dandisets/000001/draft/.DS_Store")]
+        #[case("GET", "/dandisets/000001/draft/sub-01/Thumbs.db")]
+        #[case("GET", "/dandisets/000001/draft/desktop.ini")]
+        #[case("GET", "/zarrs/.git/config")]
+        #[case("PROPFIND", "/dandisets/000001/draft/lost+found/")]
+        #[case("PROPFIND", "/dandisets/000001/releases/.svn/")]
+        #[tokio::test]
+        async fn not_found_without_upstream(#[case] method: &str, #[case] uri: &str) {
+            let transport = Arc::new(FakeTransport::new());
+            let dav = dav_with_transport(transport.clone());
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Depth", "1")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert_eq!(transport.requests(), Vec::new());
+        }
+
+        #[tokio::test]
+        async fn configured_not_found_without_upstream() {
+            let transport = Arc::new(FakeTransport::new());
+            let mut dav = dav_with_transport(transport.clone());
+            dav.path_config =
+                Arc::new(PathConfig::default().with_fast_not_exist([String::from("@eaDir")]));
+            let req = Request::builder()
+                .uri("/dandisets/000001/draft/@EADIR/thumb.jpg")
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert_eq!(transport.requests(), Vec::new());
+        }
+    }
+
+    mod dandiset_filter {
+        use super::*;
+        use serde_json::json;
+
+        fn dandiset(id: &str) -> serde_json::Value {
+            json!({
+                "identifier": id,
+                "created": "2020-03-15T22:56:55.655000Z",
+                "modified": "2020-03-15T22:56:55.655000Z",
+                "draft_version": version_info("draft", "2020-03-15T22:56:55.655000Z"),
+                "most_recent_published_version": null,
+            })
+        }
+
+        async fn propfind(uri: &str, depth: &str) -> (StatusCode, String) {
+            let ids = ["000027", "000108", "000123"];
+            let mut transport = FakeTransport::new().json(
+                "http://127.0.0.1:9/api/dandisets/",
+                &json!({
+                    "next": null,
+                    "results": ids.map(dandiset),
+                }),
+            );
+            for id in ids {
+                let url = format!("http://127.0.0.1:9/api/dandisets/{id}");
+                transport = transport.json(&format!("{url}/"), &dandiset(id)).json(
+                    &format!("{url}/versions/draft/info/"),
+                    &dandiset(id)["draft_version"],
+                );
+            }
+            let mut dav = dav_with_transport(transport);
+            dav.dandiset_filter = Arc::new(DandisetFilter::new(
+                vec![glob::Pattern::new("0001*").unwrap()],
+                vec![glob::Pattern::new("000108").unwrap()],
+            ));
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri(uri)
+                .header("Depth", depth)
+                .body(Body::empty())
+                .unwrap();
+            let resp = Arc::new(dav).handle_request(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn index_omits_hidden() {
+            let (status, body) = propfind("/dandisets/", "1").await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(body.contains("<href>/dandisets/000123/</href>"));
+            assert!(!body.contains("000027"));
+            assert!(!body.contains("000108"));
+        }
+
+        #[rstest]
+        #[case("/dandisets/000123/")]
+        #[case("/dandisets/000123/draft/")]
+        #[tokio::test]
+        async fn get_allowed(#[case] uri: &str) {
+            let (status, body) = propfind(uri, "0").await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            assert!(body.contains(&format!("<href>{uri}</href>")));
+        }
+
+        #[rstest]
+        #[case("/dandisets/000027/")]
+        #[case("/dandisets/000108/")]
+        #[case("/dandisets/000108/draft/")]
+        #[tokio::test]
+        async fn get_hidden(#[case] uri: &str) {
+            let (status, _) = propfind(uri, "0").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+}
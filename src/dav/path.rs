@@ -1,32 +1,94 @@
 //! Parsing request paths
-use crate::consts::FAST_NOT_EXIST;
+use super::instance::Instance;
+use super::local::MountName;
+use crate::consts::{BIDS_COLLECTION_NAME, FAST_NOT_EXIST, SEARCH_COLLECTION_NAME};
 use crate::dandi::{DandisetId, PublishedVersionId};
-use crate::paths::{Component, ParseComponentError, PurePath};
+use crate::paths::{canonicalize, Component, PureDirPath, PurePath};
+use std::collections::HashSet;
+
+/// Server configuration that affects how request paths are parsed
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct PathConfig {
+    /// The names of the top-level collections at which local directory trees
+    /// are served
+    pub(crate) local_mounts: Vec<MountName>,
+
+    /// Additional path component names (beyond those in [`FAST_NOT_EXIST`]),
+    /// in lowercase, that cause requests to be treated as being for
+    /// nonexistent resources without making any requests to outside services
+    pub(crate) fast_not_exist: HashSet<String>,
+}
+
+impl PathConfig {
+    /// Set the additional fast-not-exist names to `names`, which are matched
+    /// case-insensitively
+    pub(crate) fn with_fast_not_exist<I>(mut self, names: I) -> PathConfig
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.fast_not_exist = names.into_iter().map(|s| s.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// Returns `true` if the request path component `s` should be treated as
+    /// nonexistent without having to make any requests to outside services
+    fn is_fast_not_exist(&self, s: &str) -> bool {
+        let s = s.to_ascii_lowercase();
+        FAST_NOT_EXIST.binary_search(&&*s).is_ok() || self.fast_not_exist.contains(&s)
+    }
+
+    /// If `name` is the name of a configured local mount (compared
+    /// case-insensitively), return the mount's name
+    fn local_mount(&self, name: &str) -> Option<MountName> {
+        self.local_mounts
+            .iter()
+            .find(|mount| mount.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+}
 
 /// A parsed request path
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub(super) enum DavPath {
     /// The root of the hierarchy served by `dandidav`
     Root,
 
-    /// The list of Dandisets at `/dandisets/`
-    DandisetIndex,
+    /// The list of an Archive instance's Dandisets at `/dandisets/` (for the
+    /// primary instance) or `/{name}-dandisets/` (for other instances)
+    DandisetIndex { instance: Instance },
 
-    /// A listing for a Dandiset at `/dandiset/{dandiset_id}/`
-    Dandiset { dandiset_id: DandisetId },
+    /// A listing for a Dandiset at `/{index}/{dandiset_id}/`, where `{index}`
+    /// is the instance's Dandiset index
+    Dandiset {
+        instance: Instance,
+        dandiset_id: DandisetId,
+    },
 
     /// A listing for a Dandiset's published versions at
-    /// `/dandiset/{dandiset_id}/releases/`
-    DandisetReleases { dandiset_id: DandisetId },
+    /// `/{index}/{dandiset_id}/releases/`
+    DandisetReleases {
+        instance: Instance,
+        dandiset_id: DandisetId,
+    },
+
+    /// The `releases.tsv` file for a Dandiset, listing its published
+    /// versions, served at `/{index}/{dandiset_id}/releases.tsv`
+    ReleasesTsv {
+        instance: Instance,
+        dandiset_id: DandisetId,
+    },
 
     /// A listing of the top level of a Dandiset version's file hierarchy
     ///
     /// This corresponds to the following request paths:
     ///
-    /// - `/dandiset/{dandiset_id}/draft/`
-    /// - `/dandiset/{dandiset_id}/latest/`
-    /// - `/dandiset/{dandiset_id}/releases/{version_id}/`
+    /// - `/{index}/{dandiset_id}/draft/`
+    /// - `/{index}/{dandiset_id}/latest/`
+    /// - `/{index}/{dandiset_id}/releases/{version_id}/`
     Version {
+        /// The Archive instance
+        instance: Instance,
+
         /// The Dandiset ID
         dandiset_id: DandisetId,
 
@@ -37,6 +99,55 @@ pub(super) enum DavPath {
     /// The `dandiset.yaml` file for a given Dandiset version, served at the
     /// path `dandiset.yaml` immediately beneath each version path
     DandisetYaml {
+        /// The Archive instance
+        instance: Instance,
+
+        /// The Dandiset ID
+        dandiset_id: DandisetId,
+
+        /// The version specifier
+        version: VersionSpec,
+    },
+
+    /// The `assets.jsonl` file for a given Dandiset version, listing the
+    /// version's assets one per line, served at the path `assets.jsonl`
+    /// immediately beneath each version path
+    AssetsJsonl {
+        /// The Archive instance
+        instance: Instance,
+
+        /// The Dandiset ID
+        dandiset_id: DandisetId,
+
+        /// The version specifier
+        version: VersionSpec,
+    },
+
+    /// The virtual collection of search results for a given Dandiset version,
+    /// served at the path `.search` immediately beneath each version path.
+    /// The search query is taken from the `q` query parameter of `GET`
+    /// requests; without one, the collection is empty.
+    VersionSearch {
+        /// The Archive instance
+        instance: Instance,
+
+        /// The Dandiset ID
+        dandiset_id: DandisetId,
+
+        /// The version specifier
+        version: VersionSpec,
+
+        /// The search query, if any
+        query: Option<String>,
+    },
+
+    /// The virtual collection of BIDS datasets detected in a given Dandiset
+    /// version, served at the path `.bids` immediately beneath each version
+    /// path
+    VersionBids {
+        /// The Archive instance
+        instance: Instance,
+
         /// The Dandiset ID
         dandiset_id: DandisetId,
 
@@ -46,6 +157,9 @@ pub(super) enum DavPath {
 
     /// Any other path beneath a Dandiset version path
     DandiResource {
+        /// The Archive instance
+        instance: Instance,
+
         /// The Dandiset ID
         dandiset_id: DandisetId,
 
@@ -61,35 +175,169 @@ pub(super) enum DavPath {
 
     /// A path beneath `/zarrs/`
     ZarrPath { path: PurePath },
+
+    /// A path at or beneath a local mount's top-level collection
+    /// `/{mount}/`
+    LocalPath {
+        /// The name of the local mount
+        mount: MountName,
+
+        /// The portion of the path after the mount name, or `None` for the
+        /// mount's top-level collection
+        path: Option<PurePath>,
+    },
 }
 
 impl DavPath {
+    /// Return a short name for the variant, for use as a metrics label
+    pub(super) fn kind(&self) -> &'static str {
+        match self {
+            DavPath::Root => "root",
+            DavPath::DandisetIndex { .. } => "dandiset-index",
+            DavPath::Dandiset { .. } => "dandiset",
+            DavPath::DandisetReleases { .. } => "dandiset-releases",
+            DavPath::ReleasesTsv { .. } => "releases-tsv",
+            DavPath::Version { .. } => "version",
+            DavPath::DandisetYaml { .. } => "dandiset-yaml",
+            DavPath::AssetsJsonl { .. } => "assets-jsonl",
+            DavPath::VersionSearch { .. } => "version-search",
+            DavPath::VersionBids { .. } => "version-bids",
+            DavPath::DandiResource { .. } => "dandi-resource",
+            DavPath::ZarrIndex => "zarr-index",
+            DavPath::ZarrPath { .. } => "zarr-path",
+            DavPath::LocalPath { .. } => "local",
+        }
+    }
+
+    /// Return the name of the top-level collection that the path is beneath
+    /// (or is), i.e., the mount name of the data source that serves it.
+    /// Returns `None` for the root path.
+    pub(super) fn mount_name(&self) -> Option<String> {
+        match self {
+            DavPath::Root => None,
+            DavPath::ZarrIndex | DavPath::ZarrPath { .. } => Some("zarrs".to_owned()),
+            DavPath::LocalPath { mount, .. } => Some(String::from(mount)),
+            _ => self.instance().map(Instance::index_name),
+        }
+    }
+
+    /// Return the Archive instance that the path belongs to, if any
+    pub(super) fn instance(&self) -> Option<&Instance> {
+        match self {
+            DavPath::DandisetIndex { instance }
+            | DavPath::Dandiset { instance, .. }
+            | DavPath::DandisetReleases { instance, .. }
+            | DavPath::ReleasesTsv { instance, .. }
+            | DavPath::Version { instance, .. }
+            | DavPath::DandisetYaml { instance, .. }
+            | DavPath::AssetsJsonl { instance, .. }
+            | DavPath::VersionSearch { instance, .. }
+            | DavPath::VersionBids { instance, .. }
+            | DavPath::DandiResource { instance, .. } => Some(instance),
+            DavPath::Root
+            | DavPath::ZarrIndex
+            | DavPath::ZarrPath { .. }
+            | DavPath::LocalPath { .. } => None,
+        }
+    }
+
+    /// If the path is a Dandiset path or a path beneath one, return the
+    /// Dandiset's ID
+    pub(super) fn dandiset_id(&self) -> Option<&DandisetId> {
+        match self {
+            DavPath::Dandiset { dandiset_id, .. }
+            | DavPath::DandisetReleases { dandiset_id, .. }
+            | DavPath::ReleasesTsv { dandiset_id, .. } => Some(dandiset_id),
+            _ => self.version().map(|(_, dandiset_id, _)| dandiset_id),
+        }
+    }
+
+    /// If the path is a Dandiset version path or a path beneath one, return
+    /// the version's Archive instance, Dandiset ID, and version specifier
+    pub(super) fn version(&self) -> Option<(&Instance, &DandisetId, &VersionSpec)> {
+        match self {
+            DavPath::Version {
+                instance,
+                dandiset_id,
+                version,
+            }
+            | DavPath::DandisetYaml {
+                instance,
+                dandiset_id,
+                version,
+            }
+            | DavPath::AssetsJsonl {
+                instance,
+                dandiset_id,
+                version,
+            }
+            | DavPath::VersionSearch {
+                instance,
+                dandiset_id,
+                version,
+                ..
+            }
+            | DavPath::VersionBids {
+                instance,
+                dandiset_id,
+                version,
+            }
+            | DavPath::DandiResource {
+                instance,
+                dandiset_id,
+                version,
+                ..
+            } => Some((instance, dandiset_id, version)),
+            _ => None,
+        }
+    }
+
     /// Parse a sequence of request path components into a `DavPath`.
     ///
-    /// Returns `None` if the request path is invalid/does not exist.
-    pub(super) fn from_components(parts: Vec<Component>) -> Option<DavPath> {
+    /// Returns `None` if the request path is invalid/does not exist.  Paths
+    /// beneath `/{name}-dandisets/` are parsed for any valid instance name,
+    /// regardless of whether the instance is actually being served, while
+    /// other top-level names are only recognized if they are local mounts
+    /// listed in `config`.
+    pub(super) fn from_components(parts: Vec<Component>, config: &PathConfig) -> Option<DavPath> {
         let mut iter = parts.into_iter();
         let Some(p1) = iter.next() else {
             return Some(DavPath::Root);
         };
-        if p1.eq_ignore_ascii_case("dandisets") {
+        if let Some(instance) = Instance::from_index_name(&p1) {
             let Some(did) = iter.next() else {
-                return Some(DavPath::DandisetIndex);
+                return Some(DavPath::DandisetIndex { instance });
             };
             let Ok(dandiset_id) = did.parse::<DandisetId>() else {
                 return None;
             };
             let Some(p3) = iter.next() else {
-                return Some(DavPath::Dandiset { dandiset_id });
+                return Some(DavPath::Dandiset {
+                    instance,
+                    dandiset_id,
+                });
             };
+            if p3 == "releases.tsv" {
+                return iter.next().is_none().then_some(DavPath::ReleasesTsv {
+                    instance,
+                    dandiset_id,
+                });
+            }
             let version = if p3.eq_ignore_ascii_case("releases") {
                 let Some(v) = iter.next() else {
-                    return Some(DavPath::DandisetReleases { dandiset_id });
-                };
-                let Ok(pv) = v.parse::<PublishedVersionId>() else {
-                    return None;
+                    return Some(DavPath::DandisetReleases {
+                        instance,
+                        dandiset_id,
+                    });
                 };
-                VersionSpec::Published(pv)
+                if v.eq_ignore_ascii_case("latest") {
+                    VersionSpec::LatestRelease
+                } else {
+                    let Ok(pv) = v.parse::<PublishedVersionId>() else {
+                        return None;
+                    };
+                    VersionSpec::Published(pv)
+                }
             } else if p3.eq_ignore_ascii_case("latest") {
                 VersionSpec::Latest
             } else if p3.eq_ignore_ascii_case("draft") {
@@ -99,14 +347,33 @@ impl DavPath {
             };
             match PurePath::from_components(iter) {
                 None => Some(DavPath::Version {
+                    instance,
                     dandiset_id,
                     version,
                 }),
                 Some(p) if p == "dandiset.yaml" => Some(DavPath::DandisetYaml {
+                    instance,
+                    dandiset_id,
+                    version,
+                }),
+                Some(p) if p == "assets.jsonl" => Some(DavPath::AssetsJsonl {
+                    instance,
+                    dandiset_id,
+                    version,
+                }),
+                Some(p) if p == SEARCH_COLLECTION_NAME => Some(DavPath::VersionSearch {
+                    instance,
+                    dandiset_id,
+                    version,
+                    query: None,
+                }),
+                Some(p) if p == BIDS_COLLECTION_NAME => Some(DavPath::VersionBids {
+                    instance,
                     dandiset_id,
                     version,
                 }),
                 Some(path) => Some(DavPath::DandiResource {
+                    instance,
                     dandiset_id,
                     version,
                     path,
@@ -118,13 +385,59 @@ impl DavPath {
                 Some(path) => Some(DavPath::ZarrPath { path }),
             }
         } else {
-            None
+            config.local_mount(&p1).map(|mount| DavPath::LocalPath {
+                mount,
+                path: PurePath::from_components(iter),
+            })
+        }
+    }
+
+    /// If `self` is a [`DavPath::VersionSearch`], set its search query to the
+    /// last nonempty value of the `q` parameter in the request query string
+    /// `query`.  Other paths are returned unchanged.
+    pub(super) fn with_search_query(self, query: Option<&str>) -> DavPath {
+        match self {
+            DavPath::VersionSearch {
+                instance,
+                dandiset_id,
+                version,
+                ..
+            } => {
+                let query = query.and_then(|q| {
+                    url::form_urlencoded::parse(q.as_bytes())
+                        .filter(|(k, v)| k == "q" && !v.is_empty())
+                        .map(|(_, v)| v.into_owned())
+                        .last()
+                });
+                DavPath::VersionSearch {
+                    instance,
+                    dandiset_id,
+                    version,
+                    query,
+                }
+            }
+            path => path,
+        }
+    }
+
+    /// Parse the path at which `dandidav` serves a collection resource (sans
+    /// leading slash) back into a `DavPath`.  A `None` input denotes the root
+    /// collection.
+    ///
+    /// Returns `None` if the path is invalid/does not exist.
+    pub(super) fn from_dir_path(
+        path: Option<&PureDirPath>,
+        config: &PathConfig,
+    ) -> Option<DavPath> {
+        match path {
+            Some(p) => DavPath::from_components(p.components().collect(), config),
+            None => Some(DavPath::Root),
         }
     }
 }
 
 /// A Dandiset version as specified in a request path
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub(super) enum VersionSpec {
     /// Draft version
     Draft,
@@ -134,41 +447,30 @@ pub(super) enum VersionSpec {
 
     /// Most recent published version
     Latest,
+
+    /// Most recent published version, as accessed via the `latest` alias
+    /// within a Dandiset's releases collection
+    LatestRelease,
 }
 
 /// Given a request path `path`, percent-decode it as UTF-8 and split it into
 /// its path components/path segments.
 ///
 /// Splitting is performed on runs of forward slashes after stripping leading &
-/// trailing slashes.  Single-dot components are ignored.  Double-dot
-/// components are discarded along with the immediately preceding components.
+/// trailing slashes, and the resulting segments are then canonicalized by
+/// [`canonicalize()`].
 ///
 /// Returns `None` if the path is invalid (i.e., cannot be percent-decoded or
-/// contains a NUL character) or if any component is accepted by
-/// [`is_fast_not_exist()`].
-pub(super) fn split_uri_path(s: &str) -> Option<Vec<Component>> {
+/// contains a NUL character) or if any segment is accepted by
+/// [`PathConfig::is_fast_not_exist()`].
+pub(super) fn split_uri_path(s: &str, config: &PathConfig) -> Option<Vec<Component>> {
     // TODO: Convert decoding-failures into DavError:
     let path = percent_encoding::percent_decode_str(s).decode_utf8().ok()?;
-    let mut parts = Vec::new();
-    for p in SplitComponents::new(&path) {
-        if is_fast_not_exist(p) {
-            return None;
-        }
-        match p.parse::<Component>() {
-            Ok(c) => parts.push(c),
-            Err(ParseComponentError::Empty) => unreachable!("part should not be empty"),
-            Err(ParseComponentError::Slash) => {
-                unreachable!("part should not contain / after splitting on /")
-            }
-            // TODO: Report NULs as DavErrors:
-            Err(ParseComponentError::Nul) => return None,
-            Err(ParseComponentError::CurDir) => (),
-            Err(ParseComponentError::ParentDir) => {
-                let _ = parts.pop();
-            }
-        }
+    if SplitComponents::new(&path).any(|c| config.is_fast_not_exist(c)) {
+        return None;
     }
-    Some(parts)
+    // TODO: Report NULs as DavErrors:
+    canonicalize(SplitComponents::new(&path)).ok()
 }
 
 /// An iterator over the substrings of a given string, separated by runs of
@@ -202,13 +504,6 @@ impl<'a> Iterator for SplitComponents<'a> {
 
 impl std::iter::FusedIterator for SplitComponents<'_> {}
 
-/// Returns `true` if the request path component `s` should be treated as
-/// nonexistent without having to make any requests to outside services
-fn is_fast_not_exist(s: &str) -> bool {
-    let s = s.to_ascii_lowercase();
-    FAST_NOT_EXIST.binary_search(&&*s).is_ok()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -299,7 +594,7 @@ mod tests {
         #[case("/..")]
         #[case("/%2e.")]
         fn root(#[case] s: &str) {
-            let parts = split_uri_path(s).unwrap();
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
             assert!(parts.is_empty());
         }
 
@@ -309,7 +604,7 @@ mod tests {
         #[case("/foo/")]
         #[case("//foo//")]
         fn foo(#[case] s: &str) {
-            let parts = split_uri_path(s).unwrap();
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
             assert_eq!(parts, ["foo"]);
         }
 
@@ -321,7 +616,7 @@ mod tests {
         #[case("/foo/bar/")]
         #[case("//foo//bar//")]
         fn foo_bar(#[case] s: &str) {
-            let parts = split_uri_path(s).unwrap();
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
             assert_eq!(parts, ["foo", "bar"]);
         }
 
@@ -331,7 +626,7 @@ mod tests {
         #[case("/foo/bar/.")]
         #[case("/foo/bar/./")]
         fn curdir(#[case] s: &str) {
-            let parts = split_uri_path(s).unwrap();
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
             assert_eq!(parts, ["foo", "bar"]);
         }
 
@@ -341,7 +636,7 @@ mod tests {
         #[case("/foo/%2e%2e/bar")]
         #[case("/foo/.%2e/bar")]
         fn foo_parent_bar(#[case] s: &str) {
-            let parts = split_uri_path(s).unwrap();
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
             assert_eq!(parts, ["bar"]);
         }
 
@@ -352,7 +647,7 @@ mod tests {
         #[case("/foo/bar/.%2e")]
         #[case("/foo/bar/.%2E/")]
         fn foo_bar_parent(#[case] s: &str) {
-            let parts = split_uri_path(s).unwrap();
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
             assert_eq!(parts, ["foo"]);
         }
 
@@ -362,7 +657,7 @@ mod tests {
         #[case("/%2e%2e/foo/bar")]
         #[case("/.%2e/foo/bar")]
         fn parent_foo_bar(#[case] s: &str) {
-            let parts = split_uri_path(s).unwrap();
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
             assert_eq!(parts, ["foo", "bar"]);
         }
 
@@ -370,12 +665,12 @@ mod tests {
         #[case("/foo\0bar")]
         #[case("/foo%00bar")]
         fn nul(#[case] s: &str) {
-            assert_eq!(split_uri_path(s), None);
+            assert_eq!(split_uri_path(s, &PathConfig::default()), None);
         }
 
         #[test]
         fn non_utf8() {
-            assert_eq!(split_uri_path("/f%f6%f6"), None);
+            assert_eq!(split_uri_path("/f%f6%f6", &PathConfig::default()), None);
         }
 
         #[rstest]
@@ -390,8 +685,23 @@ mod tests {
         #[case("/foo/.Svn/trunk")]
         #[case("/foo/.NoLs/bar")]
         #[case("/foo/.Bzr/cathedral")]
+        #[case("/dandisets/000001/draft/.DS_Store")]
+        #[case("/dandisets/000001/draft/sub-01/Thumbs.db")]
+        #[case("/dandisets/000001/draft/Desktop.ini")]
+        #[case("/lost+found")]
+        #[case("/lost%2Bfound/foo")]
         fn fast_not_exist(#[case] s: &str) {
-            assert_eq!(split_uri_path(s), None);
+            assert_eq!(split_uri_path(s, &PathConfig::default()), None);
+        }
+
+        #[test]
+        fn configured_fast_not_exist() {
+            let config = PathConfig::default().with_fast_not_exist([String::from("@EADIR")]);
+            assert!(split_uri_path("/foo/@eaDir/bar", &PathConfig::default()).is_some());
+            assert_eq!(split_uri_path("/foo/@eaDir/bar", &config), None);
+            assert_eq!(split_uri_path("/foo/@eadir", &config), None);
+            assert_eq!(split_uri_path("/foo/.git", &config), None);
+            assert!(split_uri_path("/foo/eadir", &config).is_some());
         }
     }
 
@@ -400,15 +710,26 @@ mod tests {
         use assert_matches::assert_matches;
         use rstest::rstest;
 
+        fn staging() -> Instance {
+            Instance::Named("staging".parse().unwrap())
+        }
+
         #[rstest]
         #[case("/foo")]
         #[case("/dandisets/123")]
         #[case("/dandisets/draft")]
         #[case("/dandisets/000123/0.201234.1")]
         #[case("/dandisets/000123/releases/draft")]
+        #[case("/dandisets/000123/releases.tsv/foo")]
+        #[case("/dandisets/000123/Releases.TSV")]
+        #[case("/-dandisets/")]
+        #[case("/Staging_Dandisets/000123")]
         fn test_bad_uri_paths(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_eq!(DavPath::from_components(parts), None);
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_eq!(
+                DavPath::from_components(parts, &PathConfig::default()),
+                None
+            );
         }
 
         #[rstest]
@@ -416,8 +737,11 @@ mod tests {
         #[case("/")]
         #[case("//")]
         fn test_root(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_eq!(DavPath::from_components(parts), Some(DavPath::Root));
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_eq!(
+                DavPath::from_components(parts, &PathConfig::default()),
+                Some(DavPath::Root)
+            );
         }
 
         #[rstest]
@@ -428,10 +752,12 @@ mod tests {
         #[case("/Dandisets")]
         #[case("/DandiSets")]
         fn test_dandiset_index(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
             assert_eq!(
-                DavPath::from_components(parts),
-                Some(DavPath::DandisetIndex)
+                DavPath::from_components(parts, &PathConfig::default()),
+                Some(DavPath::DandisetIndex {
+                    instance: Instance::Primary
+                })
             );
         }
 
@@ -442,8 +768,8 @@ mod tests {
         #[case("/Dandisets/000123")]
         #[case("/DandiSets/000123")]
         fn test_dandiset(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::Dandiset {dandiset_id}) => {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::Dandiset {instance: Instance::Primary, dandiset_id}) => {
                 assert_eq!(dandiset_id, "000123");
             });
         }
@@ -454,8 +780,19 @@ mod tests {
         #[case("/Dandisets/000123/Releases")]
         #[case("/DandiSets/000123/ReLeAsEs/")]
         fn test_dandiset_releases(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::DandisetReleases {dandiset_id}) => {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::DandisetReleases {instance: Instance::Primary, dandiset_id}) => {
+                assert_eq!(dandiset_id, "000123");
+            });
+        }
+
+        #[rstest]
+        #[case("/dandisets/000123/releases.tsv")]
+        #[case("/dandisets/000123/releases.tsv/")]
+        #[case("/Dandisets/000123//releases.tsv")]
+        fn test_releases_tsv(#[case] path: &str) {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::ReleasesTsv {instance: Instance::Primary, dandiset_id}) => {
                 assert_eq!(dandiset_id, "000123");
             });
         }
@@ -466,8 +803,8 @@ mod tests {
         #[case("/Dandisets/000123/Draft")]
         #[case("/DandiSets/000123/dRaFt/")]
         fn test_dandiset_draft(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::Version {dandiset_id, version}) => {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::Version {instance: Instance::Primary, dandiset_id, version}) => {
                 assert_eq!(dandiset_id, "000123");
                 assert_eq!(version, VersionSpec::Draft);
             });
@@ -479,21 +816,34 @@ mod tests {
         #[case("/Dandisets/000123/Latest")]
         #[case("/DandiSets/000123/LaTeST/")]
         fn test_dandiset_latest(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::Version {dandiset_id, version}) => {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::Version {instance: Instance::Primary, dandiset_id, version}) => {
                 assert_eq!(dandiset_id, "000123");
                 assert_eq!(version, VersionSpec::Latest);
             });
         }
 
+        #[rstest]
+        #[case("/dandisets/000123/releases/latest")]
+        #[case("/dandisets/000123/releases/latest/")]
+        #[case("/Dandisets/000123/Releases/Latest")]
+        #[case("/DandiSets/000123/ReLeAsEs//LaTeST/")]
+        fn test_dandiset_releases_latest(#[case] path: &str) {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::Version {instance: Instance::Primary, dandiset_id, version}) => {
+                assert_eq!(dandiset_id, "000123");
+                assert_eq!(version, VersionSpec::LatestRelease);
+            });
+        }
+
         #[rstest]
         #[case("/dandisets/000123/releases/0.240123.42")]
         #[case("/dandisets/000123/releases/0.240123.42/")]
         #[case("/Dandisets/000123/Releases//0.240123.42")]
         #[case("/DandiSets/000123/ReLeAsEs/0.240123.42//")]
         fn test_dandiset_published_version(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::Version {dandiset_id, version}) => {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::Version {instance: Instance::Primary, dandiset_id, version}) => {
                 assert_eq!(dandiset_id, "000123");
                 assert_matches!(version, VersionSpec::Published(v) => {
                     assert_eq!(v, "0.240123.42");
@@ -507,16 +857,95 @@ mod tests {
         #[case("/Dandisets/000123/Draft/dandiset.yaml")]
         #[case("/DandiSets/000123/dRaFt/dandiset.yaml")]
         fn test_dandiset_draft_dandiset_yaml(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::DandisetYaml {dandiset_id, version}) => {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::DandisetYaml {instance: Instance::Primary, dandiset_id, version}) => {
+                assert_eq!(dandiset_id, "000123");
+                assert_eq!(version, VersionSpec::Draft);
+            });
+        }
+
+        #[rstest]
+        #[case("/dandisets/000123/draft/assets.jsonl")]
+        #[case("/dandisets/000123/draft/assets.jsonl/")]
+        #[case("/Dandisets/000123/Draft/assets.jsonl")]
+        #[case("/dandisets/000123/draft/foo/../assets.jsonl")]
+        fn test_dandiset_draft_assets_jsonl(#[case] path: &str) {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::AssetsJsonl {instance: Instance::Primary, dandiset_id, version}) => {
                 assert_eq!(dandiset_id, "000123");
                 assert_eq!(version, VersionSpec::Draft);
             });
         }
 
+        #[rstest]
+        #[case("/dandisets/000123/draft/.search")]
+        #[case("/dandisets/000123/draft/.search/")]
+        #[case("/Dandisets/000123/Draft/.search")]
+        #[case("/dandisets/000123/draft/foo/../.search")]
+        fn test_dandiset_draft_search(#[case] path: &str) {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::VersionSearch {instance: Instance::Primary, dandiset_id, version, query: None}) => {
+                assert_eq!(dandiset_id, "000123");
+                assert_eq!(version, VersionSpec::Draft);
+            });
+        }
+
+        #[rstest]
+        #[case("/dandisets/000123/draft/.Search", ".Search")]
+        #[case("/dandisets/000123/draft/.search/foo", ".search/foo")]
+        #[case("/dandisets/000123/draft/foo/.search", "foo/.search")]
+        fn test_not_search(#[case] s: &str, #[case] respath: &str) {
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::DandiResource {path, ..}) => {
+                assert_eq!(path, respath);
+            });
+        }
+
+        #[rstest]
+        #[case("/dandisets/000123/releases/0.210831.2033/.bids")]
+        #[case("/dandisets/000123/releases/0.210831.2033/.bids/")]
+        fn test_version_bids(#[case] path: &str) {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::VersionBids {instance: Instance::Primary, dandiset_id, version: VersionSpec::Published(v)}) => {
+                assert_eq!(dandiset_id, "000123");
+                assert_eq!(v, "0.210831.2033");
+            });
+        }
+
+        #[rstest]
+        #[case("/dandisets/000123/draft/.BIDS", ".BIDS")]
+        #[case("/dandisets/000123/draft/.bids/rawdata", ".bids/rawdata")]
+        fn test_not_bids(#[case] s: &str, #[case] respath: &str) {
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::DandiResource {path, ..}) => {
+                assert_eq!(path, respath);
+            });
+        }
+
+        #[rstest]
+        #[case(None, None)]
+        #[case(Some("q=sub-01"), Some("sub-01"))]
+        #[case(Some("format=json&q=%2A.nwb"), Some("*.nwb"))]
+        #[case(Some("q=foo&q=bar"), Some("bar"))]
+        #[case(Some("q=foo&q="), Some("foo"))]
+        #[case(Some("q="), None)]
+        #[case(Some("query=foo"), None)]
+        fn test_with_search_query(#[case] qs: Option<&str>, #[case] q: Option<&str>) {
+            let parts =
+                split_uri_path("/dandisets/000123/draft/.search", &PathConfig::default()).unwrap();
+            let path = DavPath::from_components(parts, &PathConfig::default())
+                .unwrap()
+                .with_search_query(qs);
+            assert_matches!(path, DavPath::VersionSearch {query, ..} => {
+                assert_eq!(query.as_deref(), q);
+            });
+        }
+
         #[rstest]
         #[case("/dandisets/000123/draft/Dandiset.yaml", "Dandiset.yaml")]
         #[case("/dandisets/000123/draft/dandiset.yml", "dandiset.yml")]
+        #[case("/dandisets/000123/draft/Assets.jsonl", "Assets.jsonl")]
+        #[case("/dandisets/000123/draft/foo/assets.jsonl", "foo/assets.jsonl")]
         #[case("/dandisets/000123/draft/foo", "foo")]
         #[case("/dandisets/000123/draft/foo/bar", "foo/bar")]
         #[case("/dandisets/000123/draft/foo%2fbar", "foo/bar")]
@@ -526,8 +955,8 @@ mod tests {
         #[case("/dandisets/000123/draft/foo/../bar", "bar")]
         #[case("/dandisets/000123/draft/foo/%2e%2e/bar", "bar")]
         fn test_dandiset_draft_resource(#[case] s: &str, #[case] respath: &str) {
-            let parts = split_uri_path(s).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::DandiResource {dandiset_id, version, path}) => {
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::DandiResource {instance: Instance::Primary, dandiset_id, version, path}) => {
                 assert_eq!(dandiset_id, "000123");
                 assert_eq!(version, VersionSpec::Draft);
                 assert_eq!(path, respath);
@@ -544,8 +973,8 @@ mod tests {
         #[case("/dandisets/000123/latest/foo/./bar", "foo/bar")]
         #[case("/dandisets/000123/latest//foo//bar/", "foo/bar")]
         fn test_dandiset_latest_resource(#[case] s: &str, #[case] respath: &str) {
-            let parts = split_uri_path(s).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::DandiResource {dandiset_id, version, path}) => {
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::DandiResource {instance: Instance::Primary, dandiset_id, version, path}) => {
                 assert_eq!(dandiset_id, "000123");
                 assert_eq!(version, VersionSpec::Latest);
                 assert_eq!(path, respath);
@@ -565,8 +994,8 @@ mod tests {
         #[case("/dandisets/000123/RELEASES/0.240123.42/foo/./bar", "foo/bar")]
         #[case("/dandisets/000123/releases/0.240123.42//foo//bar/", "foo/bar")]
         fn test_dandiset_publish_version_resource(#[case] s: &str, #[case] respath: &str) {
-            let parts = split_uri_path(s).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::DandiResource {dandiset_id, version, path}) => {
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::DandiResource {instance: Instance::Primary, dandiset_id, version, path}) => {
                 assert_eq!(dandiset_id, "000123");
                 assert_matches!(version, VersionSpec::Published(v) => {
                     assert_eq!(v, "0.240123.42");
@@ -575,6 +1004,21 @@ mod tests {
             });
         }
 
+        #[rstest]
+        #[case("/staging-dandisets/", DavPath::DandisetIndex { instance: staging() })]
+        #[case("/Staging-Dandisets/000123", DavPath::Dandiset { instance: staging(), dandiset_id: "000123".parse().unwrap() })]
+        #[case("/staging-dandisets/000123/releases/", DavPath::DandisetReleases { instance: staging(), dandiset_id: "000123".parse().unwrap() })]
+        #[case("/staging-dandisets/000123/draft/", DavPath::Version { instance: staging(), dandiset_id: "000123".parse().unwrap(), version: VersionSpec::Draft })]
+        #[case("/staging-dandisets/000123/draft/dandiset.yaml", DavPath::DandisetYaml { instance: staging(), dandiset_id: "000123".parse().unwrap(), version: VersionSpec::Draft })]
+        #[case("/staging-dandisets/000123/latest/foo/bar", DavPath::DandiResource { instance: staging(), dandiset_id: "000123".parse().unwrap(), version: VersionSpec::Latest, path: "foo/bar".parse().unwrap() })]
+        fn test_named_instance(#[case] path: &str, #[case] dp: DavPath) {
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_eq!(
+                DavPath::from_components(parts, &PathConfig::default()),
+                Some(dp)
+            );
+        }
+
         #[rstest]
         #[case("/zarrs")]
         #[case("/zarrs/")]
@@ -583,8 +1027,11 @@ mod tests {
         #[case("/Zarrs")]
         #[case("/ZARRS")]
         fn test_zarr_index(#[case] path: &str) {
-            let parts = split_uri_path(path).unwrap();
-            assert_eq!(DavPath::from_components(parts), Some(DavPath::ZarrIndex));
+            let parts = split_uri_path(path, &PathConfig::default()).unwrap();
+            assert_eq!(
+                DavPath::from_components(parts, &PathConfig::default()),
+                Some(DavPath::ZarrIndex)
+            );
         }
 
         #[rstest]
@@ -593,10 +1040,94 @@ mod tests {
         #[case("/zarrs/123/abc", "123/abc")]
         #[case("/ZARRS/123/ABC", "123/ABC")]
         fn test_zarr_path(#[case] s: &str, #[case] respath: &str) {
-            let parts = split_uri_path(s).unwrap();
-            assert_matches!(DavPath::from_components(parts), Some(DavPath::ZarrPath {path}) => {
+            let parts = split_uri_path(s, &PathConfig::default()).unwrap();
+            assert_matches!(DavPath::from_components(parts, &PathConfig::default()), Some(DavPath::ZarrPath {path}) => {
                 assert_eq!(path, respath);
             });
         }
+
+        #[rstest]
+        #[case("/local", None)]
+        #[case("/local/", None)]
+        #[case("/Local/docs", Some("docs"))]
+        #[case("/local/docs/README.md", Some("docs/README.md"))]
+        fn test_local_path(#[case] s: &str, #[case] respath: Option<&str>) {
+            let config = PathConfig {
+                local_mounts: vec!["local".parse().unwrap()],
+                ..PathConfig::default()
+            };
+            let parts = split_uri_path(s, &config).unwrap();
+            assert_matches!(DavPath::from_components(parts, &config), Some(DavPath::LocalPath {mount, path}) => {
+                assert_eq!(mount, "local");
+                assert_eq!(path.as_deref(), respath);
+            });
+        }
+
+        #[test]
+        fn test_unconfigured_local_path() {
+            let parts = split_uri_path("/local/docs", &PathConfig::default()).unwrap();
+            assert_eq!(
+                DavPath::from_components(parts, &PathConfig::default()),
+                None
+            );
+        }
+    }
+
+    mod dav_path_from_dir_path {
+        use super::*;
+        use assert_matches::assert_matches;
+
+        #[test]
+        fn root() {
+            assert_eq!(
+                DavPath::from_dir_path(None, &PathConfig::default()),
+                Some(DavPath::Root)
+            );
+        }
+
+        #[test]
+        fn dandiset_index() {
+            let p = "dandisets/".parse::<PureDirPath>().unwrap();
+            assert_eq!(
+                DavPath::from_dir_path(Some(&p), &PathConfig::default()),
+                Some(DavPath::DandisetIndex {
+                    instance: Instance::Primary
+                })
+            );
+        }
+
+        #[test]
+        fn dandi_resource() {
+            let p = "dandisets/000123/latest/foo/bar.zarr/"
+                .parse::<PureDirPath>()
+                .unwrap();
+            assert_matches!(DavPath::from_dir_path(Some(&p), &PathConfig::default()), Some(DavPath::DandiResource {instance: Instance::Primary, dandiset_id, version, path}) => {
+                assert_eq!(dandiset_id, "000123");
+                assert_eq!(version, VersionSpec::Latest);
+                assert_eq!(path, "foo/bar.zarr");
+            });
+        }
+
+        #[test]
+        fn named_instance_resource() {
+            let p = "staging-dandisets/000123/draft/foo/"
+                .parse::<PureDirPath>()
+                .unwrap();
+            assert_matches!(DavPath::from_dir_path(Some(&p), &PathConfig::default()), Some(DavPath::DandiResource {instance: Instance::Named(name), dandiset_id, version, path}) => {
+                assert_eq!(name, "staging");
+                assert_eq!(dandiset_id, "000123");
+                assert_eq!(version, VersionSpec::Draft);
+                assert_eq!(path, "foo");
+            });
+        }
+
+        #[test]
+        fn invalid() {
+            let p = "foo/bar/".parse::<PureDirPath>().unwrap();
+            assert_eq!(
+                DavPath::from_dir_path(Some(&p), &PathConfig::default()),
+                None
+            );
+        }
     }
 }
@@ -0,0 +1,455 @@
+//! The backends serving the hierarchies mounted beneath the root collection
+use super::path::DavPath;
+use super::types::{DavCollection, DavResource, DavResourceWithChildren};
+use super::util::version_path;
+use super::{shadows_virtual_file, DandiDav, DavError, VersionSpec, VirtualFile};
+use crate::dandi::{DandiClient, DandisetVersion, VersionId};
+use crate::zarrman::ZarrManClient;
+use futures_util::{
+    future::{ready, BoxFuture},
+    FutureExt, TryStreamExt,
+};
+use std::fmt;
+
+/// A backend serving a hierarchy of resources that is mounted at a top-level
+/// collection of the hierarchy served by `dandidav`.
+///
+/// [`DandiDav`] routes each request for a path other than the root to the
+/// data source whose [`mount_name()`][DavDataSource::mount_name] matches the
+/// first component of the path.  Request paths are parsed before routing,
+/// though, so serving a new hierarchy requires not only implementing this
+/// trait and registering the source in [`DandiDav::sources()`] but also
+/// teaching [`DavPath::from_components()`] to recognize paths beneath the
+/// mount (possibly with a new [`DavPath`] variant for them).  Sources may
+/// therefore assume that they are only given paths of the variants that
+/// `from_components()` produces for their mount.
+pub(super) trait DavDataSource: fmt::Debug + Send + Sync {
+    /// Return the name of the top-level collection at which the source's
+    /// hierarchy is mounted
+    fn mount_name(&self) -> &str;
+
+    /// Return the top-level collection at which the source's hierarchy is
+    /// mounted, for listing as a child of the root collection
+    fn mount_collection(&self) -> DavCollection;
+
+    /// Get details on the resource at the given `path` in the source's
+    /// hierarchy.  `dav` is the `DandiDav` handling the current request,
+    /// whose settings & per-request state the source may consult.
+    fn get_resource<'a>(
+        &'a self,
+        dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResource, DavError>>;
+
+    /// Get details on the resource at the given `path` in the source's
+    /// hierarchy along with its immediate child resources (if any)
+    fn get_resource_with_children<'a>(
+        &'a self,
+        dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResourceWithChildren, DavError>>;
+}
+
+impl DavDataSource for DandiClient {
+    fn mount_name(&self) -> &str {
+        self.index_name()
+    }
+
+    fn mount_collection(&self) -> DavCollection {
+        DavCollection::dandiset_index(self.instance())
+    }
+
+    fn get_resource<'a>(
+        &'a self,
+        dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResource, DavError>> {
+        async move {
+            match path {
+                DavPath::DandisetIndex { instance } => Ok(DavResource::Collection(
+                    DavCollection::dandiset_index(instance),
+                )),
+                DavPath::Dandiset {
+                    instance,
+                    dandiset_id,
+                } => {
+                    let ds = self.dandiset(dandiset_id.clone()).get().await?;
+                    Ok(DavResource::Collection(DavCollection::dandiset(
+                        ds, instance,
+                    )))
+                }
+                DavPath::DandisetReleases {
+                    instance,
+                    dandiset_id,
+                } => {
+                    // TODO: Should this return a 404 when the Dandiset doesn't
+                    // have any published releases?
+                    Ok(DavResource::Collection(DavCollection::dandiset_releases(
+                        instance,
+                        dandiset_id,
+                    )))
+                }
+                DavPath::ReleasesTsv {
+                    instance,
+                    dandiset_id,
+                } => dav
+                    .get_releases_tsv(instance, dandiset_id)
+                    .await
+                    .map(DavResource::Item),
+                DavPath::Version {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => dav
+                    .get_version_handler(instance, dandiset_id, version)
+                    .await?
+                    .get()
+                    .await
+                    .map(DavResource::Collection),
+                DavPath::DandisetYaml {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => {
+                    dav.get_version_handler(instance, dandiset_id, version)
+                        .await?
+                        .get_virtual_file_resource(VirtualFile::DandisetYaml)
+                        .await
+                }
+                DavPath::AssetsJsonl {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => {
+                    dav.get_version_handler(instance, dandiset_id, version)
+                        .await?
+                        .get_virtual_file_resource(VirtualFile::AssetsJsonl)
+                        .await
+                }
+                DavPath::VersionSearch {
+                    instance,
+                    dandiset_id,
+                    version,
+                    ..
+                } => dav
+                    .get_version_handler(instance, dandiset_id, version)
+                    .await?
+                    .get_search()
+                    .await
+                    .map(DavResource::Collection),
+                DavPath::VersionBids {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => dav
+                    .get_version_handler(instance, dandiset_id, version)
+                    .await?
+                    .get_bids()
+                    .await
+                    .map(DavResource::Collection),
+                DavPath::DandiResource {
+                    instance,
+                    dandiset_id,
+                    version,
+                    path,
+                } => {
+                    dav.get_version_handler(instance, dandiset_id, version)
+                        .await?
+                        .get_resource(path)
+                        .await
+                }
+                DavPath::Root
+                | DavPath::ZarrIndex
+                | DavPath::ZarrPath { .. }
+                | DavPath::LocalPath { .. } => {
+                    unreachable!("non-Archive path {path:?} should not be routed to an Archive")
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn get_resource_with_children<'a>(
+        &'a self,
+        dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResourceWithChildren, DavError>> {
+        async move {
+            match path {
+                DavPath::DandisetIndex { instance } => {
+                    let children = self
+                        .get_all_dandisets()
+                        .try_filter(|ds| ready(dav.dandiset_filter.allows(&ds.identifier)))
+                        .map_ok(|ds| DavResource::Collection(DavCollection::dandiset(ds, instance)))
+                        .try_collect::<Vec<_>>()
+                        .await?;
+                    let col = DavCollection::dandiset_index(instance);
+                    Ok(DavResourceWithChildren::Collection { col, children })
+                }
+                DavPath::Dandiset {
+                    instance,
+                    dandiset_id,
+                } => {
+                    let mut ds = self.dandiset(dandiset_id.clone()).get().await?;
+                    let mut children = Vec::with_capacity(4);
+                    if let Some(v) = ds.draft_version.take() {
+                        children.push(DavResource::Collection(DavCollection::dandiset_version(
+                            v,
+                            version_path(instance, dandiset_id, &VersionSpec::Draft),
+                        )));
+                    }
+                    if let Some(v) = ds.most_recent_published_version.take() {
+                        let latest = DavCollection::dandiset_version(
+                            v,
+                            version_path(instance, dandiset_id, &VersionSpec::Latest),
+                        );
+                        children.push(DavResource::Collection(latest));
+                        children.push(DavResource::Collection(DavCollection::dandiset_releases(
+                            instance,
+                            dandiset_id,
+                        )));
+                        children.push(DavResource::Item(
+                            dav.get_releases_tsv(instance, dandiset_id).await?,
+                        ));
+                    }
+                    let col = DavCollection::dandiset(ds, instance);
+                    Ok(DavResourceWithChildren::Collection { col, children })
+                }
+                DavPath::DandisetReleases {
+                    instance,
+                    dandiset_id,
+                } => {
+                    // TODO: Should this return a 404 when the Dandiset doesn't
+                    // have any published releases?
+                    let endpoint = self.dandiset(dandiset_id.clone());
+                    let col = DavCollection::dandiset_releases(instance, dandiset_id);
+                    let mut children = Vec::new();
+                    let mut latest: Option<DandisetVersion> = None;
+                    let mut stream = endpoint.get_all_versions();
+                    while let Some(v) = stream.try_next().await? {
+                        if let VersionId::Published(ref pvid) = v.version {
+                            let path = version_path(
+                                instance,
+                                dandiset_id,
+                                &VersionSpec::Published(pvid.clone()),
+                            );
+                            if latest.as_ref().map_or(true, |l| v.created > l.created) {
+                                latest = Some(v.clone());
+                            }
+                            children.push(DavResource::Collection(
+                                DavCollection::dandiset_version(v, path),
+                            ));
+                        }
+                    }
+                    if let Some(v) = latest {
+                        let path = version_path(instance, dandiset_id, &VersionSpec::LatestRelease);
+                        children.push(DavResource::Collection(DavCollection::dandiset_version(
+                            v, path,
+                        )));
+                    }
+                    Ok(DavResourceWithChildren::Collection { col, children })
+                }
+                DavPath::ReleasesTsv {
+                    instance,
+                    dandiset_id,
+                } => dav
+                    .get_releases_tsv(instance, dandiset_id)
+                    .await
+                    .map(DavResourceWithChildren::Item),
+                DavPath::Version {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => {
+                    let handler = dav
+                        .get_version_handler(instance, dandiset_id, version)
+                        .await?;
+                    let col = handler.get().await?;
+                    let mut children = handler.get_root_children().await?;
+                    for file in VirtualFile::ALL {
+                        if !shadows_virtual_file(&children, file) {
+                            children.push(DavResource::Item(handler.get_virtual_file(file).await?));
+                        }
+                    }
+                    Ok(DavResourceWithChildren::Collection { col, children })
+                }
+                DavPath::DandisetYaml {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => {
+                    dav.get_version_handler(instance, dandiset_id, version)
+                        .await?
+                        .get_virtual_file_resource_with_children(VirtualFile::DandisetYaml)
+                        .await
+                }
+                DavPath::AssetsJsonl {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => {
+                    dav.get_version_handler(instance, dandiset_id, version)
+                        .await?
+                        .get_virtual_file_resource_with_children(VirtualFile::AssetsJsonl)
+                        .await
+                }
+                DavPath::VersionSearch {
+                    instance,
+                    dandiset_id,
+                    version,
+                    query,
+                } => {
+                    let handler = dav
+                        .get_version_handler(instance, dandiset_id, version)
+                        .await?;
+                    let col = handler.get_search().await?;
+                    let children = match query {
+                        Some(q) => handler.search(q).await?,
+                        None => Vec::new(),
+                    };
+                    Ok(DavResourceWithChildren::Collection { col, children })
+                }
+                DavPath::VersionBids {
+                    instance,
+                    dandiset_id,
+                    version,
+                } => {
+                    let handler = dav
+                        .get_version_handler(instance, dandiset_id, version)
+                        .await?;
+                    let col = handler.get_bids().await?;
+                    let children = handler.bids_datasets().await?;
+                    Ok(DavResourceWithChildren::Collection { col, children })
+                }
+                DavPath::DandiResource {
+                    instance,
+                    dandiset_id,
+                    version,
+                    path,
+                } => {
+                    dav.get_version_handler(instance, dandiset_id, version)
+                        .await?
+                        .get_resource_with_children(path)
+                        .await
+                }
+                DavPath::Root
+                | DavPath::ZarrIndex
+                | DavPath::ZarrPath { .. }
+                | DavPath::LocalPath { .. } => {
+                    unreachable!("non-Archive path {path:?} should not be routed to an Archive")
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+impl DavDataSource for ZarrManClient {
+    fn mount_name(&self) -> &str {
+        "zarrs"
+    }
+
+    fn mount_collection(&self) -> DavCollection {
+        DavCollection::zarr_index()
+    }
+
+    fn get_resource<'a>(
+        &'a self,
+        _dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResource, DavError>> {
+        async move {
+            match path {
+                DavPath::ZarrIndex => Ok(DavResource::Collection(DavCollection::zarr_index())),
+                DavPath::ZarrPath { path } => {
+                    let res = ZarrManClient::get_resource(self, path).await?;
+                    Ok(DavResource::from(res))
+                }
+                _ => unreachable!(
+                    "non-Zarr path {path:?} should not be routed to the Zarr manifests"
+                ),
+            }
+        }
+        .boxed()
+    }
+
+    fn get_resource_with_children<'a>(
+        &'a self,
+        _dav: &'a DandiDav,
+        path: &'a DavPath,
+    ) -> BoxFuture<'a, Result<DavResourceWithChildren, DavError>> {
+        async move {
+            match path {
+                DavPath::ZarrIndex => {
+                    let col = DavCollection::zarr_index();
+                    let children = self
+                        .get_top_level_dirs()
+                        .await?
+                        .into_iter()
+                        .map(DavResource::from)
+                        .collect();
+                    Ok(DavResourceWithChildren::Collection { col, children })
+                }
+                DavPath::ZarrPath { path } => {
+                    let res = ZarrManClient::get_resource_with_children(self, path).await?;
+                    Ok(DavResourceWithChildren::from(res))
+                }
+                _ => unreachable!(
+                    "non-Zarr path {path:?} should not be routed to the Zarr manifests"
+                ),
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{dav, dav_with_transport};
+    use super::super::Instance;
+    use super::*;
+    use crate::httputil::FakeTransport;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn mount_names() {
+        let mut dav = dav_with_transport(FakeTransport::new());
+        let staging = Instance::Named("staging".parse().unwrap());
+        let client = dav.primary_dandi().clone().with_instance(staging.clone());
+        dav.archives.insert(staging, client);
+        let names = dav
+            .sources()
+            .map(DavDataSource::mount_name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["dandisets", "staging-dandisets", "zarrs"]);
+        for source in dav.sources() {
+            assert_eq!(source.mount_collection().name(), Some(source.mount_name()));
+        }
+    }
+
+    #[tokio::test]
+    async fn unmounted_instance() {
+        let dav = dav();
+        let path = DavPath::DandisetIndex {
+            instance: Instance::Named("staging".parse().unwrap()),
+        };
+        assert_matches!(
+            dav.get_resource(&path).await,
+            Err(DavError::UnknownMount(e)) => {
+                assert_eq!(e.to_string(), "no hierarchy is being served at /staging-dandisets/");
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn root_lists_mounts() {
+        let dav = dav();
+        assert_matches!(
+            dav.fetch_resource_with_children(&DavPath::Root).await,
+            Ok(DavResourceWithChildren::Collection { children, .. }) => {
+                let names = children.iter().map(|r| r.name().unwrap()).collect::<Vec<_>>();
+                assert_eq!(names, ["dandisets", "zarrs"]);
+            }
+        );
+    }
+}
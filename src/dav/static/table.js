@@ -0,0 +1,91 @@
+// Progressive enhancement for the tables in HTML collection views: clicking on
+// a column header that the server cannot sort by sorts the table by that
+// column (clicking again reverses the order), and a text box is added for
+// only showing entries whose names contain a given string.  The ".." row, if
+// any, always stays at the top.
+//
+// Headers of columns that the server can sort by contain links that request
+// the listing again sorted by that column; these are left alone so that the
+// sorting applies to the whole listing rather than just the current page.
+(function () {
+    "use strict";
+
+    const table = document.querySelector("table.collection");
+    if (table === null) {
+        return;
+    }
+    const tbody = table.tBodies[0];
+    const parentRow = tbody.querySelector("tr.parent");
+    const entryRows = Array.from(tbody.rows).filter((row) => row !== parentRow);
+
+    // Return the value to sort `row` by for the column at index `col`, or
+    // `null` if the cell is empty
+    function sortValue(row, col, numeric) {
+        const cell = row.cells[col];
+        if (cell === undefined || cell.classList.contains("null")) {
+            return null;
+        }
+        const value = cell.dataset.sortValue ?? cell.textContent.trim();
+        return numeric ? Number(value) : value;
+    }
+
+    function sortBy(th) {
+        const col = th.cellIndex;
+        const numeric = th.dataset.sortType === "number";
+        const descending = th.getAttribute("aria-sort") === "ascending";
+        const sign = descending ? -1 : 1;
+        for (const other of table.tHead.rows[0].cells) {
+            other.removeAttribute("aria-sort");
+        }
+        th.setAttribute("aria-sort", descending ? "descending" : "ascending");
+        const keyed = entryRows.map((row, i) => [sortValue(row, col, numeric), i, row]);
+        keyed.sort(([a, i], [b, j]) => {
+            // Empty cells always go last
+            if (a === null || b === null) {
+                if (a === b) {
+                    return i - j;
+                }
+                return a === null ? 1 : -1;
+            }
+            let cmp;
+            if (numeric) {
+                cmp = a - b;
+            } else {
+                cmp = a < b ? -1 : a > b ? 1 : 0;
+            }
+            return cmp === 0 ? i - j : sign * cmp;
+        });
+        for (const [, , row] of keyed) {
+            tbody.appendChild(row);
+        }
+    }
+
+    for (const th of table.tHead.rows[0].cells) {
+        if (th.querySelector("a") !== null) {
+            continue;
+        }
+        th.classList.add("sortable");
+        th.tabIndex = 0;
+        th.addEventListener("click", () => sortBy(th));
+        th.addEventListener("keydown", (ev) => {
+            if (ev.key === "Enter" || ev.key === " ") {
+                ev.preventDefault();
+                sortBy(th);
+            }
+        });
+    }
+
+    const filter = document.createElement("input");
+    filter.type = "search";
+    filter.className = "filter";
+    filter.placeholder = "Filter by name";
+    filter.setAttribute("aria-label", "Filter by name");
+    filter.addEventListener("input", () => {
+        const needle = filter.value.toLowerCase();
+        for (const row of entryRows) {
+            const name = (sortValue(row, 0, false) ?? "").toLowerCase();
+            row.hidden = !name.includes(needle);
+        }
+    });
+    table.parentNode.insertBefore(filter, table);
+})();
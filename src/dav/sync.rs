@@ -0,0 +1,393 @@
+//! Answering `sync-collection` reports (RFC 6578) on Dandiset versions
+use super::path::DavPath;
+use super::types::DavResource;
+use super::util::{version_path, Href};
+use super::xml::{
+    DavResponse, Multistatus, Precondition, PropFind, SyncCollection, SyncLevel, ToXmlError,
+};
+use super::{DandiDav, DavError, VersionHandler, VirtualFile};
+use crate::consts::{DANDIDAV_XMLNS, DAV_XML_CONTENT_TYPE, DRAFT_LISTING_ATTEMPTS};
+use crate::dandi::{Asset, AssetFolder, DandiResource};
+use crate::paths::PureDirPath;
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, response::Response, StatusCode},
+    response::IntoResponse,
+};
+use futures_util::TryStreamExt;
+use moka::future::{Cache, CacheBuilder};
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// The name under which the [`SyncSnapshots`] cache is reported to `moka`
+const SYNC_SNAPSHOTS_NAME: &str = "sync-snapshots";
+
+/// The status reported for members that have been removed from a collection
+/// since the state identified by a client's sync token
+const REMOVED_STATUS: &str = "HTTP/1.1 404 Not Found";
+
+/// Return the sync token identifying the state of a Dandiset version last
+/// modified at `modified`
+pub(super) fn sync_token(modified: OffsetDateTime) -> String {
+    format!("{DANDIDAV_XMLNS}/sync/{}", modified.unix_timestamp_nanos())
+}
+
+/// Return a response with the given status and a `DAV:error` body naming
+/// the precondition `cond`
+pub(super) fn precondition_failed(status: StatusCode, cond: Precondition) -> Response<Body> {
+    match cond.to_xml() {
+        Ok(body) => (status, [(CONTENT_TYPE, DAV_XML_CONTENT_TYPE)], body).into_response(),
+        Err(_) => status.into_response(),
+    }
+}
+
+/// A cache of the members of Dandiset versions as of the states identified by
+/// the sync tokens issued for them, used to determine which members have
+/// changed when a client makes a `sync-collection` report request with a
+/// previously-issued token.
+///
+/// Each snapshot maps the paths of the members relative to the version
+/// (with a trailing slash for collections) to fingerprints of their states.
+/// The size of the cache is measured in members, with each snapshot weighing
+/// one plus its number of members.
+#[derive(Clone, Debug)]
+pub(crate) struct SyncSnapshots(Cache<(PureDirPath, String), Arc<BTreeMap<String, u64>>>);
+
+impl SyncSnapshots {
+    /// Construct a cache holding snapshots with up to `max_members` members
+    /// in total
+    pub(crate) fn new(max_members: u64) -> SyncSnapshots {
+        SyncSnapshots(
+            CacheBuilder::new(max_members)
+                .name(SYNC_SNAPSHOTS_NAME)
+                .weigher(|_, snapshot: &Arc<BTreeMap<String, u64>>| {
+                    u32::try_from(snapshot.len().saturating_add(1)).unwrap_or(u32::MAX)
+                })
+                .build(),
+        )
+    }
+}
+
+/// A member of a Dandiset version as reported in a `sync-collection` report
+#[derive(Clone, Debug)]
+struct Member {
+    /// A hash of the member's state, which changes whenever the member or
+    /// (for folders) anything beneath it changes
+    fingerprint: u64,
+
+    /// The member itself
+    resource: DavResource,
+}
+
+/// Running totals for a folder in a Dandiset version, accumulated while
+/// listing the version's assets
+#[derive(Clone, Debug, Default)]
+struct FolderTally {
+    hasher: DefaultHasher,
+    total_size: i64,
+    total_assets: u64,
+}
+
+impl DandiDav {
+    /// Handle a `sync-collection` `REPORT` request for the given `path`,
+    /// which must be a Dandiset version.
+    ///
+    /// The version's sync token is derived from its modification time.  If
+    /// the client's token is already current, an empty report is returned
+    /// without listing the version; otherwise, the version's members are
+    /// listed and compared against the snapshot of the version taken when
+    /// the client's token was issued, and those that have changed or been
+    /// removed since then are reported.  Requests without a token report
+    /// all members.  Listings of draft versions that keep changing while
+    /// being listed are retried up to [`DRAFT_LISTING_ATTEMPTS`] times, after
+    /// which a 503 response is returned.
+    ///
+    /// As a report requires listing the entire version, reports are subject
+    /// to the same limit as infinite-depth `PROPFIND` requests: if
+    /// `infinite_depth_limit` is `None`, reports are refused with a 403
+    /// response with a `DAV:supported-report` error, and versions with more
+    /// members than the limit result in a 507 response with a
+    /// `DAV:number-of-matches-within-limits` error.
+    ///
+    /// Tokens whose snapshots are not known (including ones that have been
+    /// evicted from `sync_snapshots`) result in a 403 response with a
+    /// `DAV:valid-sync-token` error, and reports with more changes than the
+    /// client-specified limit result in a 507 response with a
+    /// `DAV:number-of-matches-within-limits` error, either of which tells
+    /// the client to start over with a fresh sync.
+    pub(super) async fn report(
+        &self,
+        path: &DavPath,
+        query: SyncCollection,
+    ) -> Result<Response<Body>, DavError> {
+        let DavPath::Version {
+            instance,
+            dandiset_id,
+            version,
+        } = path
+        else {
+            return Ok(precondition_failed(
+                StatusCode::FORBIDDEN,
+                Precondition::SupportedReport,
+            ));
+        };
+        let Some(max_members) = self.infinite_depth_limit else {
+            return Ok(precondition_failed(
+                StatusCode::FORBIDDEN,
+                Precondition::SupportedReport,
+            ));
+        };
+        let handler = self
+            .get_version_handler(instance, dandiset_id, version)
+            .await?;
+        let vpath = version_path(instance, dandiset_id, version);
+        let mut modified = handler.fetch_modified().await?;
+        let old = match query.sync_token {
+            Some(ref token) if *token == sync_token(modified) => {
+                return Ok(sync_response(Vec::new(), token.clone())?);
+            }
+            Some(ref token) => {
+                let key = (vpath.clone(), token.clone());
+                let Some(snapshot) = self.sync_snapshots.0.get(&key).await else {
+                    tracing::info!(?path, %token, "Received unknown sync token");
+                    return Ok(precondition_failed(
+                        StatusCode::FORBIDDEN,
+                        Precondition::ValidSyncToken,
+                    ));
+                };
+                Some(snapshot)
+            }
+            None => None,
+        };
+        let mut attempt = 1;
+        let members = loop {
+            let Some(members) = handler
+                .sync_members(modified, self.asset_sidecars, max_members)
+                .await?
+            else {
+                tracing::info!(
+                    ?path,
+                    max_members,
+                    "Version has too many members for sync report"
+                );
+                return Ok(precondition_failed(
+                    StatusCode::INSUFFICIENT_STORAGE,
+                    Precondition::NumberOfMatchesWithinLimits,
+                ));
+            };
+            let after = handler.fetch_modified().await?;
+            if after == modified {
+                break members;
+            } else if attempt >= DRAFT_LISTING_ATTEMPTS {
+                tracing::info!(
+                    ?path,
+                    "Draft version kept changing while being listed for sync report",
+                );
+                return Ok((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Dandiset version kept changing while being listed; try again later\n",
+                )
+                    .into_response());
+            }
+            tracing::info!(?path, "Draft version changed while being listed; retrying");
+            modified = after;
+            attempt += 1;
+        };
+        let token = sync_token(modified);
+        let props = PropFind::Prop(query.props);
+        let mut response = members
+            .iter()
+            .filter(|(key, m)| {
+                in_level(key, query.level)
+                    && old
+                        .as_ref()
+                        .map_or(true, |snap| snap.get(*key) != Some(&m.fingerprint))
+            })
+            .map(|(_, m)| props.find(&m.resource))
+            .collect::<Vec<_>>();
+        if let Some(ref snap) = old {
+            response.extend(
+                snap.keys()
+                    .filter(|key| in_level(key, query.level) && !members.contains_key(*key))
+                    .map(|key| DavResponse {
+                        href: Href::from_path(&format!("/{vpath}{key}")),
+                        propstat: Vec::new(),
+                        status: Some(REMOVED_STATUS.into()),
+                        location: None,
+                    }),
+            );
+        }
+        if query
+            .limit
+            .is_some_and(|limit| u64::try_from(response.len()).unwrap_or(u64::MAX) > limit)
+        {
+            return Ok(precondition_failed(
+                StatusCode::INSUFFICIENT_STORAGE,
+                Precondition::NumberOfMatchesWithinLimits,
+            ));
+        }
+        let snapshot = members
+            .into_iter()
+            .map(|(key, m)| (key, m.fingerprint))
+            .collect::<BTreeMap<_, _>>();
+        self.sync_snapshots
+            .0
+            .insert((vpath, token.clone()), Arc::new(snapshot))
+            .await;
+        Ok(sync_response(response, token)?)
+    }
+}
+
+impl VersionHandler<'_> {
+    /// List all members of the version — its assets, the folders containing
+    /// them, its virtual files, and (if `sidecars` is true) the assets'
+    /// metadata sidecar files — keyed by their paths relative to the version.
+    /// `modified` is the version's modification time, which is used as the
+    /// state of the virtual files.
+    ///
+    /// Returns `None` if the version has more than `max_members` members, in
+    /// which case listing is stopped as soon as the limit is exceeded.
+    async fn sync_members(
+        &self,
+        modified: OffsetDateTime,
+        sidecars: bool,
+        max_members: usize,
+    ) -> Result<Option<BTreeMap<String, Member>>, DavError> {
+        let mut members = BTreeMap::new();
+        let mut folders = BTreeMap::<PureDirPath, FolderTally>::new();
+        let mut assets = self.endpoint.get_all_assets();
+        while let Some(asset) = assets.try_next().await? {
+            let (key, asset_id, asset_modified) = match asset {
+                Asset::Blob(ref blob) => (blob.path.to_string(), &blob.asset_id, blob.modified),
+                Asset::Zarr(ref zarr) => (format!("{}/", zarr.path), &zarr.asset_id, zarr.modified),
+            };
+            let mut hasher = DefaultHasher::new();
+            asset_id.hash(&mut hasher);
+            asset_modified.hash(&mut hasher);
+            let fingerprint = hasher.finish();
+            let mut parent = asset.path().parent();
+            while let Some(dir) = parent {
+                parent = dir.parent();
+                let tally = folders.entry(dir).or_default();
+                key.hash(&mut tally.hasher);
+                fingerprint.hash(&mut tally.hasher);
+                tally.total_size = tally.total_size.saturating_add(asset.size());
+                tally.total_assets += 1;
+            }
+            if sidecars {
+                let sidecar = asset.sidecar();
+                members.insert(
+                    sidecar.path.to_string(),
+                    Member {
+                        fingerprint,
+                        resource: self.place(DandiResource::AssetSidecar(sidecar)),
+                    },
+                );
+            }
+            members.insert(
+                key,
+                Member {
+                    fingerprint,
+                    resource: self.place(DandiResource::Asset(asset)),
+                },
+            );
+            if members.len().saturating_add(folders.len()) > max_members {
+                return Ok(None);
+            }
+        }
+        for (path, tally) in folders {
+            let folder = AssetFolder {
+                path: path.clone(),
+                total_size: Some(tally.total_size),
+                total_assets: Some(tally.total_assets),
+            };
+            members.insert(
+                path.to_string(),
+                Member {
+                    fingerprint: tally.hasher.finish(),
+                    resource: self.place(DandiResource::Folder(folder)),
+                },
+            );
+        }
+        for file in VirtualFile::ALL {
+            let name = file.name();
+            if members.contains_key(name) || members.contains_key(&format!("{name}/")) {
+                continue;
+            }
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            modified.hash(&mut hasher);
+            members.insert(
+                name.to_owned(),
+                Member {
+                    fingerprint: hasher.finish(),
+                    resource: DavResource::Item(self.get_virtual_file(file).await?),
+                },
+            );
+        }
+        Ok((members.len() <= max_members).then_some(members))
+    }
+
+    /// Convert a resource in the version to a [`DavResource`] with its path
+    /// prefixed with the path to the version
+    fn place(&self, res: DandiResource) -> DavResource {
+        DavResource::from(res).under_version_path(
+            self.instance,
+            self.dandiset_id,
+            self.version_spec,
+        )
+    }
+}
+
+/// Returns `true` iff the member at relative path `key` is reported on for
+/// a `sync-collection` report with the given level
+fn in_level(key: &str, level: SyncLevel) -> bool {
+    match level {
+        SyncLevel::One => !key.trim_end_matches('/').contains('/'),
+        SyncLevel::Infinite => true,
+    }
+}
+
+/// Construct a `sync-collection` report response with the given member
+/// responses and new sync token
+fn sync_response(response: Vec<DavResponse>, token: String) -> Result<Response<Body>, ToXmlError> {
+    Ok((
+        StatusCode::MULTI_STATUS,
+        [(CONTENT_TYPE, DAV_XML_CONTENT_TYPE)],
+        (Multistatus {
+            response,
+            sync_token: Some(token),
+        })
+        .to_xml()?,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_sync_token() {
+        let modified = datetime!(2024-06-18 13:38:10.123456789 UTC);
+        assert_eq!(
+            sync_token(modified),
+            "https://github.com/dandi/dandidav/sync/1718717890123456789"
+        );
+    }
+
+    #[rstest]
+    #[case("foo.nwb", SyncLevel::One, true)]
+    #[case("sub-01/", SyncLevel::One, true)]
+    #[case("sample.zarr/", SyncLevel::One, true)]
+    #[case("sub-01/foo.nwb", SyncLevel::One, false)]
+    #[case("sub-01/ses-01/", SyncLevel::One, false)]
+    #[case("sub-01/foo.nwb", SyncLevel::Infinite, true)]
+    fn test_in_level(#[case] key: &str, #[case] level: SyncLevel, #[case] r: bool) {
+        assert_eq!(in_level(key, level), r);
+    }
+}
@@ -1,13 +1,21 @@
-use super::util::{format_creationdate, format_modifieddate, version_path, Href};
+use super::instance::Instance;
+use super::sync::sync_token;
+use super::util::{format_creationdate, format_modifieddate, version_path, Disposition, Href};
 use super::xml::{PropValue, Property};
 use super::VersionSpec;
-use crate::consts::{DEFAULT_CONTENT_TYPE, YAML_CONTENT_TYPE};
+use crate::consts::{
+    BIDS_COLLECTION_NAME, DEFAULT_CONTENT_TYPE, JSONL_CONTENT_TYPE, JSON_CONTENT_TYPE,
+    SEARCH_COLLECTION_NAME, SIDECAR_CONTENT_TYPE, TSV_CONTENT_TYPE, YAML_CONTENT_TYPE,
+};
 use crate::dandi::*;
 use crate::httputil::HttpUrl;
-use crate::paths::{PureDirPath, PurePath};
+use crate::paths::{Component, PureDirPath, PurePath};
 use crate::zarrman::*;
 use enum_dispatch::enum_dispatch;
 use serde::{ser::Serializer, Serialize};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::path::PathBuf;
 use time::OffsetDateTime;
 
 /// Trait for querying the values of WebDAV properties from WebDAV resources
@@ -49,8 +57,43 @@ pub(super) trait HasProperties {
     /// Return `true` iff this is a collection resource
     fn is_collection(&self) -> bool;
 
-    /// Return the value of the given property.  `Property::Custom` inputs will
-    /// always evaluate to `None`.
+    /// Return the value of the custom "zarrversion" property, i.e., the Zarr
+    /// format version of a Zarr collection whose version could be determined
+    fn zarrversion(&self) -> Option<String>;
+
+    /// Return the value of the custom "checksum" property, i.e., the MD5
+    /// digest (as a lowercase hex string) of a Zarr entry's content
+    fn checksum(&self) -> Option<String>;
+
+    /// Return the value of the custom "versionid" property, i.e., the S3
+    /// version ID of the Zarr entry that `dandidav` redirects to
+    fn versionid(&self) -> Option<String>;
+
+    /// Return the value of the custom "assetcount" property, i.e., the number
+    /// of assets beneath a directory in a Dandiset version
+    fn assetcount(&self) -> Option<i64>;
+
+    /// Return the value of the "sync-token" property, i.e., the token
+    /// representing the current state of a Dandiset version for use with
+    /// `sync-collection` reports
+    fn synctoken(&self) -> Option<String> {
+        None
+    }
+
+    /// Return the non-standard properties that may be defined on this
+    /// resource.  These are listed (along with any standard properties that
+    /// are defined) in responses to `<propname>` PROPFIND requests.
+    ///
+    /// The default implementation returns every custom property known to
+    /// `dandidav`; implementors should narrow this down, typically via
+    /// [`ResourceKind::custom_properties()`].
+    fn custom_properties(&self) -> Vec<Property> {
+        Property::iter_custom().collect()
+    }
+
+    /// Return the value of the given property.  `Property::Custom` and
+    /// `Property::ListingUnstable` inputs will always evaluate to `None`, as
+    /// the latter is a property of a listing rather than of a resource.
     fn property(&self, prop: &Property) -> Option<PropValue> {
         match prop {
             Property::CreationDate => self.creationdate().map(Into::into),
@@ -66,7 +109,12 @@ pub(super) trait HasProperties {
                     Some(PropValue::Empty)
                 }
             }
-            Property::Custom(_) => None,
+            Property::ZarrVersion => self.zarrversion().map(Into::into),
+            Property::Checksum => self.checksum().map(Into::into),
+            Property::VersionId => self.versionid().map(Into::into),
+            Property::AssetCount => self.assetcount().map(Into::into),
+            Property::SyncToken => self.synctoken().map(Into::into),
+            Property::ListingUnstable | Property::Custom(_) => None,
         }
     }
 }
@@ -87,21 +135,31 @@ impl DavResource {
         DavResource::Collection(DavCollection::root())
     }
 
+    /// Return the base name of the resource's path.  This is `None` iff the
+    /// resource is the root collection.
+    pub(super) fn name(&self) -> Option<&str> {
+        match self {
+            DavResource::Collection(col) => col.name(),
+            DavResource::Item(item) => Some(item.name()),
+        }
+    }
+
     /// Prefix the resource's path with the path at which `dandidav` serves the
-    /// given Dandiset & version under `/dandisets/`.
+    /// given Dandiset & version of the given Archive instance.
     ///
     /// See [`version_path()`] for more information.
     pub(super) fn under_version_path(
         self,
+        instance: &Instance,
         dandiset_id: &DandisetId,
         version: &VersionSpec,
     ) -> DavResource {
         match self {
             DavResource::Collection(col) => {
-                DavResource::Collection(col.under_version_path(dandiset_id, version))
+                DavResource::Collection(col.under_version_path(instance, dandiset_id, version))
             }
             DavResource::Item(item) => {
-                DavResource::Item(item.under_version_path(dandiset_id, version))
+                DavResource::Item(item.under_version_path(instance, dandiset_id, version))
             }
         }
     }
@@ -113,6 +171,7 @@ impl From<DandiResource> for DavResource {
             DandiResource::Folder(folder) => DavResource::Collection(folder.into()),
             DandiResource::Asset(Asset::Blob(blob)) => DavResource::Item(blob.into()),
             DandiResource::Asset(Asset::Zarr(zarr)) => DavResource::Collection(zarr.into()),
+            DandiResource::AssetSidecar(sidecar) => DavResource::Item(sidecar.into()),
             DandiResource::ZarrFolder(folder) => DavResource::Collection(folder.into()),
             DandiResource::ZarrEntry(entry) => DavResource::Item(entry.into()),
         }
@@ -126,6 +185,7 @@ impl From<ZarrManResource> for DavResource {
             ZarrManResource::Manifest(folder) => DavResource::Collection(folder.into()),
             ZarrManResource::ManFolder(folder) => DavResource::Collection(folder.into()),
             ZarrManResource::ManEntry(entry) => DavResource::Item(entry.into()),
+            ZarrManResource::ZMetadata(zmeta) => DavResource::Item(zmeta.into()),
         }
     }
 }
@@ -146,53 +206,56 @@ pub(super) enum DavResourceWithChildren {
 
 impl DavResourceWithChildren {
     /// Construct a `DavResourceWithChildren` representing the root of the
-    /// hierarchy served by `dandidav`
-    pub(super) fn root() -> Self {
+    /// hierarchy served by `dandidav`, with the given top-level collections
+    /// (at which the data sources' hierarchies are mounted) as its children
+    pub(super) fn root<I>(mounts: I) -> Self
+    where
+        I: IntoIterator<Item = DavCollection>,
+    {
         DavResourceWithChildren::Collection {
             col: DavCollection::root(),
-            children: vec![
-                DavResource::Collection(DavCollection::dandiset_index()),
-                DavResource::Collection(DavCollection::zarr_index()),
-            ],
+            children: mounts.into_iter().map(DavResource::Collection).collect(),
         }
     }
 
     /// Prefix the paths of the resource and its child resources with the path
-    /// at which `dandidav` serves the given Dandiset & version under
-    /// `/dandisets/`.
+    /// at which `dandidav` serves the given Dandiset & version of the given
+    /// Archive instance.
     ///
     /// See [`version_path()`] for more information.
     pub(super) fn under_version_path(
         self,
+        instance: &Instance,
         dandiset_id: &DandisetId,
         version: &VersionSpec,
     ) -> DavResourceWithChildren {
         match self {
             DavResourceWithChildren::Collection { col, children } => {
                 DavResourceWithChildren::Collection {
-                    col: col.under_version_path(dandiset_id, version),
+                    col: col.under_version_path(instance, dandiset_id, version),
                     children: children
                         .into_iter()
-                        .map(|r| r.under_version_path(dandiset_id, version))
+                        .map(|r| r.under_version_path(instance, dandiset_id, version))
                         .collect(),
                 }
             }
-            DavResourceWithChildren::Item(item) => {
-                DavResourceWithChildren::Item(item.under_version_path(dandiset_id, version))
-            }
+            DavResourceWithChildren::Item(item) => DavResourceWithChildren::Item(
+                item.under_version_path(instance, dandiset_id, version),
+            ),
         }
     }
+}
 
-    /// Convert to a `Vec` of all `DavResources`s represented within `self`
-    pub(super) fn into_vec(self) -> Vec<DavResource> {
-        match self {
-            DavResourceWithChildren::Collection { col, children } => {
-                let mut vec = Vec::with_capacity(children.len().saturating_add(1));
-                vec.push(DavResource::from(col));
-                vec.extend(children);
-                vec
-            }
-            DavResourceWithChildren::Item(item) => vec![DavResource::Item(item)],
+impl From<DavResource> for DavResourceWithChildren {
+    /// Convert a `DavResource` to a `DavResourceWithChildren` that, if it is a
+    /// collection, has no children
+    fn from(value: DavResource) -> DavResourceWithChildren {
+        match value {
+            DavResource::Collection(col) => DavResourceWithChildren::Collection {
+                col,
+                children: Vec::new(),
+            },
+            DavResource::Item(item) => DavResourceWithChildren::Item(item),
         }
     }
 }
@@ -216,10 +279,14 @@ impl From<DandiResourceWithChildren> for DavResourceWithChildren {
                 children: map_children(children),
             },
             Blob(blob) => DavResourceWithChildren::Item(blob.into()),
-            Zarr { zarr, children } => DavResourceWithChildren::Collection {
-                col: DavCollection::from(zarr),
-                children: map_children(children),
-            },
+            AssetSidecar(sidecar) => DavResourceWithChildren::Item(sidecar.into()),
+            Zarr { zarr, children } => {
+                let children = map_children(children);
+                DavResourceWithChildren::Collection {
+                    col: DavCollection::from(zarr).with_zarr_version(&children),
+                    children,
+                }
+            }
             ZarrFolder { folder, children } => DavResourceWithChildren::Collection {
                 col: DavCollection::from(folder),
                 children: map_children(children),
@@ -241,15 +308,19 @@ impl From<ZarrManResourceWithChildren> for DavResourceWithChildren {
                 col: DavCollection::from(folder),
                 children: map_children(children),
             },
-            Manifest { folder, children } => DavResourceWithChildren::Collection {
-                col: DavCollection::from(folder),
-                children: map_children(children),
-            },
+            Manifest { folder, children } => {
+                let children = map_children(children);
+                DavResourceWithChildren::Collection {
+                    col: DavCollection::from(folder).with_zarr_version(&children),
+                    children,
+                }
+            }
             ManFolder { folder, children } => DavResourceWithChildren::Collection {
                 col: DavCollection::from(folder),
                 children: map_children(children),
             },
             ManEntry(entry) => DavResourceWithChildren::Item(entry.into()),
+            ZMetadata(zmeta) => DavResourceWithChildren::Item(zmeta.into()),
         }
     }
 }
@@ -277,6 +348,10 @@ pub(super) struct DavCollection {
     /// non-collection resources within the collection.
     pub(super) size: Option<i64>,
 
+    /// The number of assets within the collection, if it is a directory in a
+    /// Dandiset version and the number is known
+    pub(super) asset_count: Option<u64>,
+
     /// The type of resource, for display in the "Type" column of HTML tables
     pub(super) kind: ResourceKind,
 
@@ -286,6 +361,23 @@ pub(super) struct DavCollection {
 }
 
 impl DavCollection {
+    /// If the collection is a Zarr, return its Zarr format version, if known
+    pub(super) fn zarr_version(&self) -> Option<ZarrVersion> {
+        match self.kind {
+            ResourceKind::Zarr { version } => version,
+            _ => None,
+        }
+    }
+
+    /// If the collection is a Zarr, set its Zarr format version to the version
+    /// detected from the names of its immediate `children`
+    fn with_zarr_version(mut self, children: &[DavResource]) -> DavCollection {
+        if let ResourceKind::Zarr { ref mut version } = self.kind {
+            *version = ZarrVersion::detect(children);
+        }
+        self
+    }
+
     /// Return the base name of the resource's path
     pub(super) fn name(&self) -> Option<&str> {
         self.path.as_ref().map(PureDirPath::name_str)
@@ -301,15 +393,16 @@ impl DavCollection {
     }
 
     /// Prefix the resource's path with the path at which `dandidav` serves the
-    /// given Dandiset & version under `/dandisets/`.
+    /// given Dandiset & version of the given Archive instance.
     ///
     /// See [`version_path()`] for more information.
     pub(super) fn under_version_path(
         mut self,
+        instance: &Instance,
         dandiset_id: &DandisetId,
         version: &VersionSpec,
     ) -> DavCollection {
-        let vpath = version_path(dandiset_id, version);
+        let vpath = version_path(instance, dandiset_id, version);
         let path = match self.path {
             Some(p) => vpath.join_dir(&p),
             None => vpath,
@@ -326,44 +419,64 @@ impl DavCollection {
             created: None,
             modified: None,
             size: None,
+            asset_count: None,
             kind: ResourceKind::Root,
             metadata_url: None,
         }
     }
 
-    /// Construct a `DavCollection` representing the list of Dandisets at
-    /// `/dandisets/`
-    pub(super) fn dandiset_index() -> Self {
+    /// Construct a `DavCollection` representing the list of the given Archive
+    /// instance's Dandisets at `/dandisets/` or `/{name}-dandisets/`
+    pub(super) fn dandiset_index(instance: &Instance) -> Self {
         DavCollection {
             path: Some(
-                "dandisets/"
-                    .parse::<PureDirPath>()
-                    .expect(r#""dandisets/" should be a valid dir path"#),
+                PureDirPath::try_from(format!("{}/", instance.index_name()))
+                    .expect("should be a valid dir path"),
             ),
             created: None,
             modified: None,
             size: None,
+            asset_count: None,
             kind: ResourceKind::DandisetIndex,
             metadata_url: None,
         }
     }
 
     /// Construct a `DavCollection` representing the listing for the given
-    /// Dandiset's published versions at `/dandiset/{dandiset_id}/releases/`
-    pub(super) fn dandiset_releases(dandiset_id: &DandisetId) -> Self {
+    /// Dandiset's published versions at `/{index}/{dandiset_id}/releases/`,
+    /// where `{index}` is the index name of the given Archive instance
+    pub(super) fn dandiset_releases(instance: &Instance, dandiset_id: &DandisetId) -> Self {
         DavCollection {
             path: Some(
-                PureDirPath::try_from(format!("dandisets/{dandiset_id}/releases/"))
+                PureDirPath::try_from(format!("{}/{dandiset_id}/releases/", instance.index_name()))
                     .expect("should be a valid dir path"),
             ),
             created: None,
             modified: None,
             size: None,
+            asset_count: None,
             kind: ResourceKind::DandisetReleases,
             metadata_url: None,
         }
     }
 
+    /// Construct a `DavCollection` representing the Dandiset `ds` of the given
+    /// Archive instance
+    pub(super) fn dandiset(ds: Dandiset, instance: &Instance) -> Self {
+        DavCollection {
+            path: Some(
+                PureDirPath::try_from(format!("{}/{}/", instance.index_name(), ds.identifier))
+                    .expect("should be a valid dir path"),
+            ),
+            created: Some(ds.created),
+            modified: Some(ds.modified),
+            size: None,
+            asset_count: None,
+            kind: ResourceKind::Dandiset,
+            metadata_url: None,
+        }
+    }
+
     /// Construct a `DavCollection` representing the Dandiset version `v`
     /// as served at path `path`
     pub(super) fn dandiset_version(v: DandisetVersion, path: PureDirPath) -> Self {
@@ -372,11 +485,46 @@ impl DavCollection {
             created: Some(v.created),
             modified: Some(v.modified),
             size: Some(v.size),
+            asset_count: None,
             kind: ResourceKind::Version,
             metadata_url: Some(v.metadata_url),
         }
     }
 
+    /// Construct a `DavCollection` representing the virtual collection of
+    /// search results for the Dandiset version served at `version_path`
+    pub(super) fn version_search(version_path: &PureDirPath) -> Self {
+        let name = SEARCH_COLLECTION_NAME
+            .parse::<Component>()
+            .expect("SEARCH_COLLECTION_NAME should be a valid path component");
+        DavCollection {
+            path: Some(version_path.join_one_dir(&name)),
+            created: None,
+            modified: None,
+            size: None,
+            asset_count: None,
+            kind: ResourceKind::VersionSearch,
+            metadata_url: None,
+        }
+    }
+
+    /// Construct a `DavCollection` representing the virtual collection of
+    /// BIDS datasets for the Dandiset version served at `version_path`
+    pub(super) fn version_bids(version_path: &PureDirPath) -> Self {
+        let name = BIDS_COLLECTION_NAME
+            .parse::<Component>()
+            .expect("BIDS_COLLECTION_NAME should be a valid path component");
+        DavCollection {
+            path: Some(version_path.join_one_dir(&name)),
+            created: None,
+            modified: None,
+            size: None,
+            asset_count: None,
+            kind: ResourceKind::VersionBids,
+            metadata_url: None,
+        }
+    }
+
     /// Construct a `DavCollection` representing the top of the Zarr manifest
     /// tree at `/zarrs/`
     pub(super) fn zarr_index() -> Self {
@@ -389,6 +537,7 @@ impl DavCollection {
             created: None,
             modified: None,
             size: None,
+            asset_count: None,
             kind: ResourceKind::ZarrIndex,
             metadata_url: None,
         }
@@ -427,31 +576,56 @@ impl HasProperties for DavCollection {
     fn is_collection(&self) -> bool {
         true
     }
-}
 
-impl From<Dandiset> for DavCollection {
-    fn from(ds: Dandiset) -> DavCollection {
-        DavCollection {
-            path: Some(
-                PureDirPath::try_from(format!("dandisets/{}/", ds.identifier))
-                    .expect("should be a valid dir path"),
-            ),
-            created: Some(ds.created),
-            modified: Some(ds.modified),
-            size: None,
-            kind: ResourceKind::Dandiset,
-            metadata_url: None,
+    fn zarrversion(&self) -> Option<String> {
+        self.zarr_version().map(|v| v.number().to_owned())
+    }
+
+    fn checksum(&self) -> Option<String> {
+        None
+    }
+
+    fn versionid(&self) -> Option<String> {
+        None
+    }
+
+    fn assetcount(&self) -> Option<i64> {
+        self.asset_count.and_then(|n| i64::try_from(n).ok())
+    }
+
+    fn synctoken(&self) -> Option<String> {
+        (self.kind == ResourceKind::Version)
+            .then_some(self.modified)
+            .flatten()
+            .map(sync_token)
+    }
+
+    fn custom_properties(&self) -> Vec<Property> {
+        let mut props = self.kind.custom_properties();
+        // Directories of all sorts share a `ResourceKind`, but only those in
+        // Dandiset versions have asset counts, and only when listed by their
+        // parents.
+        if self.asset_count.is_some() {
+            props.push(Property::AssetCount);
         }
+        props
     }
 }
 
 impl From<AssetFolder> for DavCollection {
-    fn from(AssetFolder { path }: AssetFolder) -> DavCollection {
+    fn from(
+        AssetFolder {
+            path,
+            total_size,
+            total_assets,
+        }: AssetFolder,
+    ) -> DavCollection {
         DavCollection {
             path: Some(path),
             created: None,
             modified: None,
-            size: None,
+            size: total_size,
+            asset_count: total_assets,
             kind: ResourceKind::Directory,
             metadata_url: None,
         }
@@ -465,7 +639,8 @@ impl From<ZarrAsset> for DavCollection {
             created: Some(zarr.created),
             modified: Some(zarr.modified),
             size: Some(zarr.size),
-            kind: ResourceKind::Zarr,
+            asset_count: None,
+            kind: ResourceKind::Zarr { version: None },
             metadata_url: Some(zarr.metadata_url),
         }
     }
@@ -478,6 +653,7 @@ impl From<ZarrFolder> for DavCollection {
             created: None,
             modified: None,
             size: None,
+            asset_count: None,
             kind: ResourceKind::Directory,
             metadata_url: None,
         }
@@ -491,6 +667,7 @@ impl From<WebFolder> for DavCollection {
             created: None,
             modified: None,
             size: None,
+            asset_count: None,
             kind: ResourceKind::Directory,
             metadata_url: None,
         }
@@ -498,13 +675,14 @@ impl From<WebFolder> for DavCollection {
 }
 
 impl From<Manifest> for DavCollection {
-    fn from(Manifest { path }: Manifest) -> DavCollection {
+    fn from(Manifest { web_path, .. }: Manifest) -> DavCollection {
         DavCollection {
-            path: Some(path.to_web_path()),
+            path: Some(web_path),
             created: None,
             modified: None,
             size: None,
-            kind: ResourceKind::Zarr,
+            asset_count: None,
+            kind: ResourceKind::Zarr { version: None },
             metadata_url: None,
         }
     }
@@ -517,6 +695,7 @@ impl From<ManifestFolder> for DavCollection {
             created: None,
             modified: None,
             size: None,
+            asset_count: None,
             kind: ResourceKind::Directory,
             metadata_url: None,
         }
@@ -574,15 +753,16 @@ impl DavItem {
     }
 
     /// Prefix the resource's path with the path at which `dandidav` serves the
-    /// given Dandiset & version under `/dandisets/`.
+    /// given Dandiset & version of the given Archive instance.
     ///
     /// See [`version_path()`] for more information.
     pub(super) fn under_version_path(
         mut self,
+        instance: &Instance,
         dandiset_id: &DandisetId,
         version: &VersionSpec,
     ) -> DavItem {
-        let path = version_path(dandiset_id, version).join(&self.path);
+        let path = version_path(instance, dandiset_id, version).join(&self.path);
         self.path = path;
         self
     }
@@ -620,6 +800,44 @@ impl HasProperties for DavItem {
     fn is_collection(&self) -> bool {
         false
     }
+
+    fn zarrversion(&self) -> Option<String> {
+        None
+    }
+
+    fn checksum(&self) -> Option<String> {
+        if self.kind != ResourceKind::ZarrEntry {
+            return None;
+        }
+        // S3 ETags are MD5 digests for objects that were not uploaded in
+        // multiple parts; ETags of multipart uploads contain a hyphen and are
+        // not digests of the content.
+        let etag = self.etag.as_deref()?.trim_matches('"');
+        (etag.len() == 32 && etag.bytes().all(|b| b.is_ascii_hexdigit()))
+            .then(|| etag.to_ascii_lowercase())
+    }
+
+    fn versionid(&self) -> Option<String> {
+        if self.kind != ResourceKind::ZarrEntry {
+            return None;
+        }
+        let DavContent::Redirect(ref redir) = self.content else {
+            return None;
+        };
+        redir
+            .get_url(true)
+            .as_url()
+            .query_pairs()
+            .find_map(|(k, v)| (k == "versionId").then(|| v.into_owned()))
+    }
+
+    fn assetcount(&self) -> Option<i64> {
+        None
+    }
+
+    fn custom_properties(&self) -> Vec<Property> {
+        self.kind.custom_properties()
+    }
 }
 
 impl From<VersionMetadata> for DavItem {
@@ -642,6 +860,89 @@ impl From<VersionMetadata> for DavItem {
     }
 }
 
+impl DavItem {
+    /// Construct a `DavItem` representing the `releases.tsv` file for the
+    /// Dandiset `dandiset_id` of the given Archive instance, listing the
+    /// published versions in `versions` in order of creation
+    pub(super) fn releases_tsv(
+        instance: &Instance,
+        dandiset_id: &DandisetId,
+        mut versions: Vec<DandisetVersion>,
+    ) -> DavItem {
+        versions.sort_by_key(|v| v.created);
+        let mut tsv = String::from("version\tcreated\tmodified\tsize\tasset_count\n");
+        for v in &versions {
+            writeln!(
+                tsv,
+                "{}\t{}\t{}\t{}\t{}",
+                v.version,
+                format_creationdate(v.created),
+                format_creationdate(v.modified),
+                v.size,
+                v.asset_count.map(|n| n.to_string()).unwrap_or_default(),
+            )
+            .expect("writing to a String shouldn't fail");
+        }
+        let blob = tsv.into_bytes();
+        DavItem {
+            path: format!("{}/{dandiset_id}/releases.tsv", instance.index_name())
+                .parse::<PurePath>()
+                .expect("releases.tsv path should be a valid path"),
+            created: None,
+            modified: versions.iter().map(|v| v.modified).max(),
+            content_type: TSV_CONTENT_TYPE.to_owned(),
+            size: i64::try_from(blob.len()).ok(),
+            etag: None,
+            kind: ResourceKind::ReleaseList,
+            content: DavContent::Blob(blob),
+            metadata_url: None,
+        }
+    }
+
+    /// Construct a `DavItem` representing the `assets.jsonl` file for a
+    /// Dandiset version last modified at `modified`
+    pub(super) fn assets_jsonl(modified: OffsetDateTime) -> DavItem {
+        DavItem {
+            path: "assets.jsonl"
+                .parse::<PurePath>()
+                .expect(r#""assets.jsonl" should be a valid path"#),
+            created: None,
+            modified: Some(modified),
+            content_type: JSONL_CONTENT_TYPE.to_owned(),
+            size: None,
+            etag: None,
+            kind: ResourceKind::AssetManifest,
+            content: DavContent::AssetManifest,
+            metadata_url: None,
+        }
+    }
+}
+
+impl From<AssetSidecar> for DavItem {
+    fn from(sidecar: AssetSidecar) -> DavItem {
+        let (size, content) = match sidecar.content {
+            Some(blob) => (i64::try_from(blob.len()).ok(), DavContent::Blob(blob)),
+            // Sidecars listed as children of collections are not fetched, so
+            // point clients that download from listings at the Archive.
+            None => (
+                None,
+                DavContent::Redirect(Redirect::Direct(sidecar.metadata_url.clone())),
+            ),
+        };
+        DavItem {
+            path: sidecar.path,
+            created: Some(sidecar.created),
+            modified: Some(sidecar.modified),
+            content_type: SIDECAR_CONTENT_TYPE.to_owned(),
+            size,
+            etag: None,
+            kind: ResourceKind::AssetMetadata,
+            content,
+            metadata_url: Some(sidecar.metadata_url),
+        }
+    }
+}
+
 impl From<BlobAsset> for DavItem {
     fn from(blob: BlobAsset) -> DavItem {
         // Call methods before moving out `path` field:
@@ -705,20 +1006,53 @@ impl From<ManifestEntry> for DavItem {
     }
 }
 
+impl From<ConsolidatedMetadata> for DavItem {
+    fn from(zmeta: ConsolidatedMetadata) -> DavItem {
+        let (size, content) = match zmeta.content {
+            Some(blob) => (i64::try_from(blob.len()).ok(), DavContent::Blob(blob)),
+            // Consolidated metadata listed as a child of its Zarr is not
+            // computed; it is linked to by path instead.
+            None => (None, DavContent::Missing),
+        };
+        DavItem {
+            path: zmeta.web_path,
+            created: None,
+            modified: zmeta.modified,
+            content_type: JSON_CONTENT_TYPE.to_owned(),
+            size,
+            etag: None,
+            kind: ResourceKind::ZarrMetadata,
+            content,
+            metadata_url: None,
+        }
+    }
+}
+
 /// The content of a non-collection resource or a link thereto
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(super) enum DavContent {
     /// The raw content to serve in response to a `GET` request for the
     /// resource.
     ///
-    /// This is only used for `dandiset.yaml` resources, for which the content
-    /// is automatically generated by `dandidav`.
+    /// This is only used for resources whose content is automatically
+    /// generated by `dandidav`: `dandiset.yaml` files, asset metadata
+    /// sidecars, and consolidated Zarr metadata files.
     Blob(Vec<u8>),
 
+    /// A Dandiset version's list of assets, generated by `dandidav` from the
+    /// Archive's asset listing as it is streamed in response to a `GET`
+    /// request.  This is only used for `assets.jsonl` files.
+    AssetManifest,
+
     /// A URL that `dandidav` should redirect to when a `GET` request is made
     /// for the resource
     Redirect(Redirect),
 
+    /// A file on the local filesystem whose content should be served in
+    /// response to a `GET` request for the resource.  This is only used for
+    /// resources beneath local mounts.
+    LocalFile(PathBuf),
+
     /// No download URL could be determined for the resource
     Missing,
 }
@@ -751,6 +1085,29 @@ impl Redirect {
             }
         }
     }
+
+    /// Resolve to a single URL at which the client will be served the
+    /// resource with the given `disposition`, if any.
+    ///
+    /// Only Archive download URLs can control how the resource is presented
+    /// (via their `content_disposition` query parameter), so if a disposition
+    /// is requested, `Alt` variants resolve to their `archive` field with
+    /// the parameter added regardless of `prefer_s3`.  `Direct` variants and
+    /// requests without a disposition resolve as for [`Redirect::get_url()`].
+    pub(super) fn get_url_with_disposition(
+        &self,
+        prefer_s3: bool,
+        disposition: Option<Disposition>,
+    ) -> Cow<'_, HttpUrl> {
+        match (self, disposition) {
+            (Redirect::Alt { archive, .. }, Some(d)) => {
+                let mut url = archive.clone();
+                url.append_query_param("content_disposition", d.as_str());
+                Cow::Owned(url)
+            }
+            _ => Cow::Borrowed(self.get_url(prefer_s3)),
+        }
+    }
 }
 
 /// An enumeration of resource types for use in the "Type" column of HTML views
@@ -762,14 +1119,15 @@ pub(super) enum ResourceKind {
     /// Link to parent directory
     Parent,
 
-    /// The list of Dandisets at `/dandisets/`
+    /// The list of an Archive instance's Dandisets at `/dandisets/` or
+    /// `/{name}-dandisets/`
     DandisetIndex,
 
-    /// A listing for a Dandiset at `/dandiset/{dandiset_id}/`
+    /// A listing for a Dandiset at `/{index}/{dandiset_id}/`
     Dandiset,
 
     /// A listing for a Dandiset's published versions at
-    /// `/dandiset/{dandiset_id}/releases/`
+    /// `/{index}/{dandiset_id}/releases/`
     DandisetReleases,
 
     /// A listing of the top level of a Dandiset version's file hierarchy
@@ -778,20 +1136,44 @@ pub(super) enum ResourceKind {
     /// The `dandiset.yaml` file for a Dandiset version
     VersionMetadata,
 
+    /// The `assets.jsonl` file for a Dandiset version
+    AssetManifest,
+
+    /// The `releases.tsv` file for a Dandiset
+    ReleaseList,
+
+    /// The virtual collection of search results for a Dandiset version
+    VersionSearch,
+
+    /// The virtual collection of BIDS datasets in a Dandiset version
+    VersionBids,
+
+    /// A virtual file containing an asset's metadata as JSON
+    AssetMetadata,
+
     /// A generic directory
     Directory,
 
     /// A blob asset
     Blob,
 
-    /// A Zarr asset
-    Zarr,
+    /// A Zarr asset, along with its Zarr format version if known
+    Zarr { version: Option<ZarrVersion> },
 
     /// A Zarr entry
     ZarrEntry,
 
+    /// A consolidated metadata file synthesized for a Zarr
+    ZarrMetadata,
+
     /// The top of the Zarr manifest tree at `/zarrs/`
     ZarrIndex,
+
+    /// The top of a directory tree served from the local filesystem
+    LocalMount,
+
+    /// A file served from the local filesystem
+    LocalFile,
 }
 
 impl ResourceKind {
@@ -805,15 +1187,90 @@ impl ResourceKind {
             ResourceKind::DandisetReleases => "Published versions",
             ResourceKind::Version => "Dandiset version",
             ResourceKind::VersionMetadata => "Version metadata",
+            ResourceKind::AssetManifest => "Asset manifest",
+            ResourceKind::ReleaseList => "Release list",
+            ResourceKind::VersionSearch => "Search results",
+            ResourceKind::VersionBids => "BIDS datasets",
+            ResourceKind::AssetMetadata => "Asset metadata",
             ResourceKind::Directory => "Directory",
             ResourceKind::Blob => "Blob asset",
-            ResourceKind::Zarr => "Zarr asset",
+            ResourceKind::Zarr { version: Some(v) } => v.as_str(),
+            ResourceKind::Zarr { version: None } => "Zarr asset",
             ResourceKind::ZarrEntry => "Zarr entry",
+            ResourceKind::ZarrMetadata => "Consolidated Zarr metadata",
             ResourceKind::ZarrIndex => "Zarrs",
+            ResourceKind::LocalMount => "Local files",
+            ResourceKind::LocalFile => "Local file",
+        }
+    }
+
+    /// Return the non-standard properties that resources of this kind may
+    /// define.  When a data source starts emitting a new custom property for
+    /// a kind of resource, the property should be registered here so that it
+    /// is listed in `<propname>` responses.
+    pub(super) fn custom_properties(&self) -> Vec<Property> {
+        match self {
+            ResourceKind::Zarr { .. } => vec![Property::ZarrVersion],
+            ResourceKind::ZarrEntry => vec![Property::Checksum, Property::VersionId],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A version of the Zarr storage format
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(super) enum ZarrVersion {
+    V2,
+    V3,
+}
+
+impl ZarrVersion {
+    /// Determine the format version of a Zarr from the names of the resources
+    /// at its top level: a `zarr.json` file indicates Zarr v3, while a
+    /// `.zgroup`, `.zarray`, or `.zattrs` file indicates Zarr v2.  Returns
+    /// `None` if neither is present.
+    pub(super) fn detect(children: &[DavResource]) -> Option<ZarrVersion> {
+        let mut v2 = false;
+        for child in children {
+            if let DavResource::Item(item) = child {
+                match item.name() {
+                    "zarr.json" => return Some(ZarrVersion::V3),
+                    ".zgroup" | ".zarray" | ".zattrs" => v2 = true,
+                    _ => (),
+                }
+            }
+        }
+        v2.then_some(ZarrVersion::V2)
+    }
+
+    /// Return the version number as a string, for use as the value of the
+    /// "zarrversion" property
+    pub(super) fn number(&self) -> &'static str {
+        match self {
+            ZarrVersion::V2 => "2",
+            ZarrVersion::V3 => "3",
+        }
+    }
+
+    /// Return a human-readable label for display in the "Type" column of HTML
+    /// views
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            ZarrVersion::V2 => "Zarr v2",
+            ZarrVersion::V3 => "Zarr v3",
         }
     }
 }
 
+impl Serialize for ZarrVersion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 impl Serialize for ResourceKind {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -822,3 +1279,342 @@ impl Serialize for ResourceKind {
         serializer.serialize_str(self.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dav::xml::PropFind;
+    use assert_matches::assert_matches;
+
+    mod zarr_version {
+        use super::*;
+        use rstest::rstest;
+
+        fn entry(name: &str) -> DavResource {
+            DavResource::Item(DavItem {
+                path: format!("zarrs/abc/def.zarr/{name}").parse().unwrap(),
+                created: None,
+                modified: None,
+                content_type: DEFAULT_CONTENT_TYPE.to_owned(),
+                size: Some(42),
+                etag: None,
+                kind: ResourceKind::ZarrEntry,
+                content: DavContent::Missing,
+                metadata_url: None,
+            })
+        }
+
+        fn folder(name: &str) -> DavResource {
+            DavResource::Collection(DavCollection {
+                path: Some(format!("zarrs/abc/def.zarr/{name}/").parse().unwrap()),
+                created: None,
+                modified: None,
+                size: None,
+                asset_count: None,
+                kind: ResourceKind::Directory,
+                metadata_url: None,
+            })
+        }
+
+        #[rstest]
+        #[case(vec![entry("zarr.json"), folder("0")], Some(ZarrVersion::V3))]
+        #[case(vec![entry(".zgroup"), entry(".zattrs"), folder("0")], Some(ZarrVersion::V2))]
+        #[case(vec![entry(".zarray"), entry("0.0")], Some(ZarrVersion::V2))]
+        #[case(vec![entry(".zattrs"), entry("zarr.json")], Some(ZarrVersion::V3))]
+        #[case(vec![folder("zarr.json"), folder(".zgroup")], None)]
+        #[case(vec![entry("data.bin")], None)]
+        #[case(Vec::new(), None)]
+        fn detect(#[case] children: Vec<DavResource>, #[case] version: Option<ZarrVersion>) {
+            assert_eq!(ZarrVersion::detect(&children), version);
+        }
+
+        #[test]
+        fn with_zarr_version() {
+            let children = vec![entry("zarr.json")];
+            let col = DavCollection {
+                path: Some("zarrs/abc/def.zarr/".parse().unwrap()),
+                created: None,
+                modified: None,
+                size: None,
+                asset_count: None,
+                kind: ResourceKind::Zarr { version: None },
+                metadata_url: None,
+            }
+            .with_zarr_version(&children);
+            assert_eq!(col.zarr_version(), Some(ZarrVersion::V3));
+            assert_eq!(
+                col.property(&Property::ZarrVersion),
+                Some(PropValue::String("3".into()))
+            );
+            let resp = PropFind::PropName.find(&col);
+            assert_eq!(
+                resp.propstat[0].prop.get(&Property::ZarrVersion),
+                Some(&PropValue::Empty)
+            );
+        }
+
+        #[test]
+        fn with_zarr_version_not_zarr() {
+            let children = vec![entry("zarr.json")];
+            let col = DavCollection {
+                path: Some("zarrs/abc/".parse().unwrap()),
+                created: None,
+                modified: None,
+                size: None,
+                asset_count: None,
+                kind: ResourceKind::Directory,
+                metadata_url: None,
+            }
+            .with_zarr_version(&children);
+            assert_eq!(col.zarr_version(), None);
+            assert_eq!(col.property(&Property::ZarrVersion), None);
+            let resp = PropFind::PropName.find(&col);
+            assert!(!resp.propstat[0].prop.contains_key(&Property::ZarrVersion));
+        }
+    }
+
+    mod asset_sidecar {
+        use super::*;
+        use time::macros::datetime;
+
+        fn sidecar(content: Option<&[u8]>) -> AssetSidecar {
+            AssetSidecar {
+                path: "sub-01/foo.nwb.dandi.json".parse().unwrap(),
+                created: datetime!(2024-01-01 00:00:00 UTC),
+                modified: datetime!(2024-01-02 00:00:00 UTC),
+                metadata_url:
+                    "https://api.example.com/api/dandisets/000001/versions/draft/assets/0123/"
+                        .parse()
+                        .unwrap(),
+                content: content.map(Vec::from),
+            }
+        }
+
+        #[test]
+        fn fetched() {
+            let item = DavItem::from(sidecar(Some(b"{}")));
+            assert_eq!(item.kind, ResourceKind::AssetMetadata);
+            assert_eq!(item.content_type, "application/json");
+            assert_eq!(item.size, Some(2));
+            assert_eq!(item.content, DavContent::Blob(b"{}".to_vec()));
+        }
+
+        #[test]
+        fn listed() {
+            let item = DavItem::from(sidecar(None));
+            assert_eq!(item.size, None);
+            assert_matches!(item.content, DavContent::Redirect(Redirect::Direct(url)) => {
+                assert_eq!(
+                    url.as_str(),
+                    "https://api.example.com/api/dandisets/000001/versions/draft/assets/0123/"
+                );
+            });
+        }
+    }
+
+    mod asset_count {
+        use super::*;
+
+        #[test]
+        fn from_asset_folder() {
+            let col = DavCollection::from(AssetFolder {
+                path: "sub-01/".parse().unwrap(),
+                total_size: Some(1024),
+                total_assets: Some(3),
+            });
+            assert_eq!(col.size, Some(1024));
+            assert_eq!(col.property(&Property::AssetCount), Some(PropValue::Int(3)));
+            let resp = PropFind::PropName.find(&col);
+            assert_eq!(
+                resp.propstat[0].prop.get(&Property::AssetCount),
+                Some(&PropValue::Empty)
+            );
+        }
+
+        #[test]
+        fn unknown() {
+            let col = DavCollection::from(AssetFolder {
+                path: "sub-01/".parse().unwrap(),
+                total_size: None,
+                total_assets: None,
+            });
+            assert_eq!(col.size, None);
+            assert_eq!(col.property(&Property::AssetCount), None);
+            let resp = PropFind::PropName.find(&col);
+            assert_eq!(resp.propstat[0].prop.get(&Property::AssetCount), None);
+        }
+    }
+
+    mod zarr_entry_properties {
+        use super::*;
+        use rstest::rstest;
+
+        fn entry(etag: &str, url: &str) -> DavItem {
+            DavItem {
+                path: "zarrs/abc/def.zarr/0/0".parse().unwrap(),
+                created: None,
+                modified: None,
+                content_type: DEFAULT_CONTENT_TYPE.to_owned(),
+                size: Some(42),
+                etag: Some(etag.to_owned()),
+                kind: ResourceKind::ZarrEntry,
+                content: DavContent::Redirect(Redirect::Direct(url.parse().unwrap())),
+                metadata_url: None,
+            }
+        }
+
+        #[rstest]
+        #[case(
+            "\"0123456789ABCDEF0123456789abcdef\"",
+            Some("0123456789abcdef0123456789abcdef")
+        )]
+        #[case(
+            "0123456789abcdef0123456789abcdef",
+            Some("0123456789abcdef0123456789abcdef")
+        )]
+        #[case("\"0123456789abcdef0123456789abcdef-3\"", None)]
+        #[case("0123456789abcdefg", None)]
+        fn checksum(#[case] etag: &str, #[case] expected: Option<&str>) {
+            let item = entry(etag, "https://dandiarchive.s3.amazonaws.com/zarr/abc/0/0");
+            assert_eq!(item.checksum().as_deref(), expected);
+            assert_eq!(
+                item.property(&Property::Checksum),
+                expected.map(|s| PropValue::String(s.into()))
+            );
+        }
+
+        #[test]
+        fn versionid() {
+            let item = entry(
+                "0123456789abcdef0123456789abcdef",
+                "https://dandiarchive.s3.amazonaws.com/zarr/abc/0/0?versionId=a%2Bb.c",
+            );
+            assert_eq!(item.versionid().as_deref(), Some("a+b.c"));
+            assert_eq!(
+                item.property(&Property::VersionId),
+                Some(PropValue::String("a+b.c".into()))
+            );
+        }
+
+        #[test]
+        fn no_versionid() {
+            let item = entry(
+                "0123456789abcdef0123456789abcdef",
+                "https://dandiarchive.s3.amazonaws.com/zarr/abc/0/0",
+            );
+            assert_eq!(item.versionid(), None);
+            assert_eq!(item.property(&Property::VersionId), None);
+        }
+
+        #[test]
+        fn not_zarr_entry() {
+            let item = DavItem {
+                kind: ResourceKind::Blob,
+                ..entry(
+                    "0123456789abcdef0123456789abcdef",
+                    "https://dandiarchive.s3.amazonaws.com/blobs/abc?versionId=xyz",
+                )
+            };
+            assert_eq!(item.checksum(), None);
+            assert_eq!(item.versionid(), None);
+        }
+
+        #[test]
+        fn propname() {
+            let item = entry(
+                "0123456789abcdef0123456789abcdef",
+                "https://dandiarchive.s3.amazonaws.com/zarr/abc/0/0?versionId=xyz",
+            );
+            let resp = PropFind::PropName.find(&item);
+            assert_eq!(resp.propstat.len(), 1);
+            assert_eq!(
+                resp.propstat[0].prop.keys().cloned().collect::<Vec<_>>(),
+                vec![
+                    Property::DisplayName,
+                    Property::GetContentLength,
+                    Property::GetContentType,
+                    Property::GetETag,
+                    Property::ResourceType,
+                    Property::Checksum,
+                    Property::VersionId,
+                ]
+            );
+            assert!(resp.propstat[0]
+                .prop
+                .values()
+                .all(|v| *v == PropValue::Empty));
+        }
+
+        #[test]
+        fn propname_not_zarr_entry() {
+            let item = DavItem {
+                kind: ResourceKind::Blob,
+                ..entry(
+                    "0123456789abcdef0123456789abcdef",
+                    "https://dandiarchive.s3.amazonaws.com/blobs/abc?versionId=xyz",
+                )
+            };
+            let resp = PropFind::PropName.find(&item);
+            assert!(!resp.propstat[0].prop.contains_key(&Property::Checksum));
+            assert!(!resp.propstat[0].prop.contains_key(&Property::VersionId));
+        }
+    }
+
+    mod redirect {
+        use super::*;
+        use rstest::rstest;
+
+        fn alt() -> Redirect {
+            Redirect::Alt {
+                s3: "https://dandiarchive.s3.amazonaws.com/blobs/abc/def/abcdef"
+                    .parse()
+                    .unwrap(),
+                archive: "https://api.dandiarchive.org/api/assets/0123/download/"
+                    .parse()
+                    .unwrap(),
+            }
+        }
+
+        #[rstest]
+        #[case(false, None, "https://api.dandiarchive.org/api/assets/0123/download/")]
+        #[case(
+            true,
+            None,
+            "https://dandiarchive.s3.amazonaws.com/blobs/abc/def/abcdef"
+        )]
+        #[case(
+            false,
+            Some(Disposition::Attachment),
+            "https://api.dandiarchive.org/api/assets/0123/download/?content_disposition=attachment"
+        )]
+        #[case(
+            true,
+            Some(Disposition::Inline),
+            "https://api.dandiarchive.org/api/assets/0123/download/?content_disposition=inline"
+        )]
+        fn alt_with_disposition(
+            #[case] prefer_s3: bool,
+            #[case] disposition: Option<Disposition>,
+            #[case] url: &str,
+        ) {
+            assert_eq!(
+                alt()
+                    .get_url_with_disposition(prefer_s3, disposition)
+                    .as_str(),
+                url
+            );
+        }
+
+        #[test]
+        fn direct_with_disposition() {
+            let url = "https://dandiarchive.s3.amazonaws.com/zarr/abc/0/0"
+                .parse::<HttpUrl>()
+                .unwrap();
+            let redir = Redirect::Direct(url.clone());
+            assert_eq!(
+                *redir.get_url_with_disposition(false, Some(Disposition::Attachment)),
+                url
+            );
+        }
+    }
+}
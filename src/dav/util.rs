@@ -1,29 +1,55 @@
-use super::path::{split_uri_path, DavPath};
-use super::xml::PropFind;
-use super::VersionSpec;
-use crate::consts::DAV_XML_CONTENT_TYPE;
-use crate::dandi::DandisetId;
-use crate::httputil::HttpUrl;
+use super::instance::Instance;
+use super::path::{split_uri_path, DavPath, PathConfig};
+use super::sync::precondition_failed;
+use super::xml::{Precondition, PropFind, ReportError, SyncCollection};
+use super::{DavError, DavResource, ErrorClass, VersionSpec};
+use crate::consts::{
+    DAV_XML_CONTENT_TYPE, DESTINATION_AUTHORIZATION_HEADER, HTML_CONTENT_TYPE,
+    IMMUTABLE_CACHE_CONTROL, JSONL_CONTENT_TYPE, JSON_CONTENT_TYPE, MATCH_REGEX_SIZE_LIMIT,
+    MAX_PER_PAGE, MAX_TREE_DEPTH, MUTABLE_CACHE_CONTROL,
+};
+use crate::dandi::{ApiToken, DandisetId};
+use crate::httputil::{HttpUrl, ParseHttpUrlError};
+use crate::metrics::metrics;
 use crate::paths::{Component, PureDirPath};
 use axum::{
     body::Body,
     extract::{FromRequest, FromRequestParts, Request},
-    http::{header::CONTENT_TYPE, request::Parts, response::Response, Method, StatusCode},
+    http::{
+        header::{
+            HeaderMap, HeaderValue, ACCEPT, ACCEPT_RANGES, AUTHORIZATION, CACHE_CONTROL,
+            CONTENT_DISPOSITION, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
+            IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE, VARY, WWW_AUTHENTICATE,
+        },
+        request::Parts,
+        response::Response,
+        Method, StatusCode,
+    },
     response::IntoResponse,
     RequestExt,
 };
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use bytes::Bytes;
+use futures_util::{future::ready, stream, Stream, StreamExt, TryStreamExt};
+use http_body::Body as _;
 use indoc::indoc;
 use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use serde::{ser::Serializer, Serialize};
 use std::fmt::{self, Write};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
 use time::{
     format_description::{well_known::Rfc3339, FormatItem},
     macros::format_description,
-    OffsetDateTime,
+    OffsetDateTime, PrimitiveDateTime,
 };
+use tokio::time::Instant;
 
 /// Timestamp format for display of the "getlastmodified" property in WebDAV
-/// XML documents
+/// XML documents, also used for HTTP dates in headers like `Last-Modified` &
+/// `If-Modified-Since`
 static RFC1123: &[FormatItem<'_>] = format_description!(
     "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
 );
@@ -39,6 +65,23 @@ static PERCENT_ESCAPED: &AsciiSet = &NON_ALPHANUMERIC
     .remove(b'_')
     .remove(b'~');
 
+/// The set of characters to percent-encode in `filename*` parameters of
+/// `Content-Disposition` headers, i.e., everything other than the
+/// `attr-char`s of RFC 8187
+static RFC5987_ESCAPED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
 /// Response body to return in reply to `PROPFIND` requests with missing or
 /// "infinite" `Depth` headers
 static INFINITE_DEPTH_RESPONSE: &str = indoc! {r#"
@@ -48,28 +91,39 @@ static INFINITE_DEPTH_RESPONSE: &str = indoc! {r#"
 </error>
 "#};
 
-/// Return the path at which `dandidav` serves the given Dandiset & version
-/// under `/dandisets/`.
+/// Return the path at which `dandidav` serves the given Dandiset & version of
+/// the given Archive instance.
 ///
-/// The returned value will have one of the following formats:
+/// The returned value will have one of the following formats, where
+/// `{index}` is the [index name][Instance::index_name] of the instance:
 ///
-/// - `dandiset/{dandiset_id}/draft/`
-/// - `dandiset/{dandiset_id}/latest/`
-/// - `dandiset/{dandiset_id}/releases/{version_id}/`
-pub(super) fn version_path(dandiset_id: &DandisetId, version: &VersionSpec) -> PureDirPath {
-    fn writer(s: &mut String, dandiset_id: &DandisetId, version: &VersionSpec) -> fmt::Result {
-        write!(s, "dandisets/{dandiset_id}/")?;
+/// - `{index}/{dandiset_id}/draft/`
+/// - `{index}/{dandiset_id}/latest/`
+/// - `{index}/{dandiset_id}/releases/{version_id}/`
+pub(super) fn version_path(
+    instance: &Instance,
+    dandiset_id: &DandisetId,
+    version: &VersionSpec,
+) -> PureDirPath {
+    fn writer(
+        s: &mut String,
+        instance: &Instance,
+        dandiset_id: &DandisetId,
+        version: &VersionSpec,
+    ) -> fmt::Result {
+        write!(s, "{}/{dandiset_id}/", instance.index_name())?;
         match version {
             VersionSpec::Draft => write!(s, "draft")?,
             VersionSpec::Published(v) => write!(s, "releases/{v}")?,
             VersionSpec::Latest => write!(s, "latest")?,
+            VersionSpec::LatestRelease => write!(s, "releases/latest")?,
         }
         write!(s, "/")?;
         Ok(())
     }
 
     let mut s = String::new();
-    writer(&mut s, dandiset_id, version).expect("writing to a String shouldn't fail");
+    writer(&mut s, instance, dandiset_id, version).expect("writing to a String shouldn't fail");
     PureDirPath::try_from(s).expect("should be a valid dir path")
 }
 
@@ -100,6 +154,43 @@ pub(super) enum DavRequest {
         /// `path`.  This is needed for things like breadcrumbs in HTML views
         /// of collection resources.
         pathparts: Vec<Component>,
+
+        /// The byte range requested via the `Range` header, if any.  This is
+        /// `None` if the header was absent, could not be parsed, specified
+        /// multiple ranges, or was accompanied by an `If-Range` header.
+        range: Option<ByteRange>,
+
+        /// The request's conditional headers
+        conditions: Conditions,
+
+        /// The format in which to list the entries of a collection
+        format: ListingFormat,
+
+        /// The page of a collection's entries to list in an HTML view, if
+        /// pagination was requested
+        page: Option<PageRequest>,
+
+        /// The order in which to list a collection's entries in an HTML view,
+        /// if one was requested
+        sort: Option<SortRequest>,
+
+        /// A filter on the names of the collection's entries to list, if one
+        /// was requested
+        filter: Option<MatchFilter>,
+
+        /// A request for a JSON tree of the resource and its descendants, if
+        /// one was made.  When present, `format`, `page`, `sort`, and
+        /// `filter` are ignored.
+        tree: Option<TreeRequest>,
+
+        /// Whether an estimate of the total size of a Dandiset version was
+        /// requested via an `estimate=size` query parameter.  When `true`,
+        /// all other query parameters are ignored.
+        estimate: bool,
+
+        /// How a non-collection resource should be presented by the client,
+        /// if requested via a `download=1` or `inline=1` query parameter
+        disposition: Option<Disposition>,
     },
 
     /// A `PROPFIND` request
@@ -108,78 +199,1445 @@ pub(super) enum DavRequest {
         path: DavPath,
 
         /// The value of the `Depth` header
-        depth: FiniteDepth,
+        depth: Depth,
 
         /// The parsed request body.  (Empty bodies are defaulted to "allprop"
         /// during parsing as per the RFC.)
         query: PropFind,
+
+        /// The request's conditional headers
+        conditions: Conditions,
+
+        /// A filter on the names of the resources below the request path to
+        /// report on, if one was requested
+        filter: Option<MatchFilter>,
+    },
+
+    /// A `COPY` request
+    Copy {
+        /// The request path
+        path: DavPath,
+
+        /// The URL given in the `Destination` header
+        destination: HttpUrl,
+
+        /// Whether a resource already at `destination` may be replaced, as
+        /// determined by the `Overwrite` header (which defaults to "T" as per
+        /// RFC 4918, section 10.6)
+        overwrite: bool,
+
+        /// The value of the [`DESTINATION_AUTHORIZATION_HEADER`] header, if
+        /// any, to send as the `Authorization` header when uploading to
+        /// `destination`
+        credentials: Option<HeaderValue>,
+    },
+
+    /// A `REPORT` request for a `sync-collection` report
+    Report {
+        /// The request path
+        path: DavPath,
+
+        /// The parsed request body
+        query: SyncCollection,
     },
 
     /// An `OPTIONS` request
-    Options,
+    Options {
+        /// The request path, or `None` for a request for the server as a
+        /// whole (i.e., `OPTIONS *`)
+        path: Option<DavPath>,
+    },
 }
 
-impl<S: Send + Sync> FromRequest<S> for DavRequest {
+impl FromRequest<PathConfig> for DavRequest {
     type Rejection = Response<Body>;
 
-    async fn from_request(req: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request(req: Request<Body>, state: &PathConfig) -> Result<Self, Self::Rejection> {
         let uri_path = req.uri().path();
         match req.method() {
             &Method::GET => {
-                let Some(pathparts) = split_uri_path(uri_path) else {
+                let Some(pathparts) = split_uri_path(uri_path, state) else {
+                    // TODO: Log something
+                    return Err(not_found());
+                };
+                let Some(path) = DavPath::from_components(pathparts.clone(), state) else {
                     // TODO: Log something
                     return Err(not_found());
                 };
-                let Some(path) = DavPath::from_components(pathparts.clone()) else {
+                let path = path.with_search_query(req.uri().query());
+                let range = if req.headers().contains_key(IF_RANGE) {
+                    // We can't validate If-Range values, so ignore the Range
+                    // header and serve the whole representation, as permitted
+                    // by RFC 9110, section 13.1.5.
+                    None
+                } else {
+                    req.headers()
+                        .get(RANGE)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(ByteRange::parse)
+                };
+                let conditions = Conditions::from_headers(req.headers());
+                let format = ListingFormat::from_request(req.uri().query(), req.headers());
+                let Ok(page) = PageRequest::from_query(req.uri().query()) else {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "Invalid \"page\" or \"per_page\" query parameter\n",
+                    )
+                        .into_response());
+                };
+                let Ok(sort) = SortRequest::from_query(req.uri().query()) else {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "Invalid \"sort\" or \"order\" query parameter\n",
+                    )
+                        .into_response());
+                };
+                let filter = match MatchFilter::from_query(req.uri().query()) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        return Err((
+                            StatusCode::BAD_REQUEST,
+                            format!("Invalid \"match\" query parameter: {e}\n"),
+                        )
+                            .into_response())
+                    }
+                };
+                let Ok(tree) = TreeRequest::from_query(req.uri().query()) else {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "Invalid \"tree\" or \"depth\" query parameter\n",
+                    )
+                        .into_response());
+                };
+                let Ok(estimate) = estimate_requested(req.uri().query()) else {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "Invalid \"estimate\" query parameter\n",
+                    )
+                        .into_response());
+                };
+                let Ok(disposition) = Disposition::from_query(req.uri().query()) else {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "Invalid \"download\" or \"inline\" query parameter\n",
+                    )
+                        .into_response());
+                };
+                Ok(DavRequest::Get {
+                    path,
+                    pathparts,
+                    range,
+                    conditions,
+                    format,
+                    page,
+                    sort,
+                    filter,
+                    tree,
+                    estimate,
+                    disposition,
+                })
+            }
+            &Method::OPTIONS => {
+                if uri_path == "*" {
+                    return Ok(DavRequest::Options { path: None });
+                }
+                let Some(path) = split_uri_path(uri_path, state)
+                    .and_then(|parts| DavPath::from_components(parts, state))
+                else {
                     // TODO: Log something
                     return Err(not_found());
                 };
-                Ok(DavRequest::Get { path, pathparts })
+                Ok(DavRequest::Options { path: Some(path) })
             }
-            &Method::OPTIONS => Ok(DavRequest::Options),
             m if m.as_str().eq_ignore_ascii_case("PROPFIND") => {
-                let Some(path) = split_uri_path(uri_path).and_then(DavPath::from_components) else {
+                let Some(path) = split_uri_path(uri_path, state)
+                    .and_then(|parts| DavPath::from_components(parts, state))
+                else {
                     // TODO: Log something
                     return Err(not_found());
                 };
+                let filter = match MatchFilter::from_query(req.uri().query()) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        return Err((
+                            StatusCode::BAD_REQUEST,
+                            format!("Invalid \"match\" query parameter: {e}\n"),
+                        )
+                            .into_response())
+                    }
+                };
+                let conditions = Conditions::from_headers(req.headers());
                 let (depth, query) = req
-                    .extract_with_state::<(FiniteDepth, PropFind), _, _>(state)
+                    .extract_with_state::<(Depth, PropFind), _, _>(state)
                     .await?;
-                Ok(DavRequest::Propfind { path, depth, query })
+                Ok(DavRequest::Propfind {
+                    path,
+                    depth,
+                    query,
+                    conditions,
+                    filter,
+                })
+            }
+            m if m.as_str().eq_ignore_ascii_case("COPY") => {
+                let Some(path) = split_uri_path(uri_path, state)
+                    .and_then(|parts| DavPath::from_components(parts, state))
+                else {
+                    // TODO: Log something
+                    return Err(not_found());
+                };
+                let Some(destination) = req
+                    .headers()
+                    .get("Destination")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|s| s.trim().parse::<HttpUrl>().ok())
+                else {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "Missing or invalid \"Destination\" header; an absolute HTTP(S) URL is required\n",
+                    )
+                        .into_response());
+                };
+                let overwrite = match req.headers().get("Overwrite").map(|v| v.to_str()) {
+                    Some(Ok("T")) | None => true,
+                    Some(Ok("F")) => false,
+                    _ => {
+                        return Err((StatusCode::BAD_REQUEST, "Invalid \"Overwrite\" header\n")
+                            .into_response())
+                    }
+                };
+                let credentials = req
+                    .headers()
+                    .get(DESTINATION_AUTHORIZATION_HEADER)
+                    .cloned()
+                    .map(|mut v| {
+                        v.set_sensitive(true);
+                        v
+                    });
+                Ok(DavRequest::Copy {
+                    path,
+                    destination,
+                    overwrite,
+                    credentials,
+                })
+            }
+            m if m.as_str().eq_ignore_ascii_case("REPORT") => {
+                let Some(path) = split_uri_path(uri_path, state)
+                    .and_then(|parts| DavPath::from_components(parts, state))
+                else {
+                    // TODO: Log something
+                    return Err(not_found());
+                };
+                // RFC 6578, section 3.2: the Depth header must be "0" if
+                // present.
+                if !matches!(
+                    req.headers().get("Depth").map(|v| v.to_str()),
+                    Some(Ok("0")) | None
+                ) {
+                    return Err(
+                        (StatusCode::BAD_REQUEST, "Invalid \"Depth\" header\n").into_response()
+                    );
+                }
+                let blob = Bytes::from_request(req, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                match SyncCollection::from_xml(blob) {
+                    Ok(query) => Ok(DavRequest::Report { path, query }),
+                    Err(ReportError::Unsupported(_)) => Err(precondition_failed(
+                        StatusCode::FORBIDDEN,
+                        Precondition::SupportedReport,
+                    )),
+                    Err(_) => {
+                        Err((StatusCode::BAD_REQUEST, "Invalid request body\n").into_response())
+                    }
+                }
             }
             _ => Err(StatusCode::METHOD_NOT_ALLOWED.into_response()),
         }
     }
 }
 
-/// A non-infinite `Depth` WebDAV header value
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub(super) enum FiniteDepth {
+impl DavRequest {
+    /// Determine which time limit applies to handling the request.
+    /// `trailing_slash` must be `true` iff the request URL's path ends with a
+    /// forward slash, which is taken as a sign that a `GET` request for a
+    /// path that could be either a collection or a non-collection is for a
+    /// collection.
+    ///
+    /// Returns `None` for requests that are not subject to a time limit.
+    pub(super) fn timeout_class(&self, trailing_slash: bool) -> Option<TimeoutClass> {
+        match self {
+            DavRequest::Get { tree: Some(_), .. } => Some(TimeoutClass::Listing),
+            DavRequest::Get { path, .. } => match path {
+                DavPath::DandisetYaml { .. } => Some(TimeoutClass::Redirect),
+                DavPath::AssetsJsonl { .. } => Some(TimeoutClass::Stream),
+                DavPath::LocalPath { path: Some(_), .. } if !trailing_slash => {
+                    Some(TimeoutClass::Stream)
+                }
+                DavPath::DandiResource { .. } | DavPath::ZarrPath { .. } if !trailing_slash => {
+                    Some(TimeoutClass::Redirect)
+                }
+                _ => Some(TimeoutClass::Listing),
+            },
+            DavRequest::Propfind {
+                depth: Depth::Infinity,
+                ..
+            } => Some(TimeoutClass::Stream),
+            DavRequest::Report { .. } => Some(TimeoutClass::Stream),
+            DavRequest::Propfind { .. } | DavRequest::Options { path: Some(_) } => {
+                Some(TimeoutClass::Listing)
+            }
+            // Copies of large resources can legitimately take a long time
+            DavRequest::Copy { .. } | DavRequest::Options { path: None } => None,
+        }
+    }
+}
+
+/// A `Depth` WebDAV header value
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub(super) enum Depth {
     Zero,
     One,
+    /// Either an explicit "infinity" value or (as per RFC 4918, section
+    /// 9.1) a missing `Depth` header
+    Infinity,
 }
 
-impl<S: Send + Sync> FromRequestParts<S> for FiniteDepth {
+impl<S: Send + Sync> FromRequestParts<S> for Depth {
     type Rejection = Response<Body>;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         match parts.headers.get("Depth").map(|v| v.to_str()) {
-            Some(Ok("0")) => Ok(FiniteDepth::Zero),
-            Some(Ok("1")) => Ok(FiniteDepth::One),
-            Some(Ok("infinity")) | None => Err((
-                StatusCode::FORBIDDEN,
-                [(CONTENT_TYPE, DAV_XML_CONTENT_TYPE)],
-                INFINITE_DEPTH_RESPONSE,
-            )
-                .into_response()),
+            Some(Ok("0")) => Ok(Depth::Zero),
+            Some(Ok("1")) => Ok(Depth::One),
+            Some(Ok("infinity")) | None => Ok(Depth::Infinity),
             _ => Err((StatusCode::BAD_REQUEST, "Invalid \"Depth\" header\n").into_response()),
         }
     }
 }
 
+/// A single byte range from a `Range` header, as specified in RFC 9110,
+/// section 14.1.2.  Requests for multiple ranges are not supported.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum ByteRange {
+    /// The range starting at byte offset `start` and ending at byte offset
+    /// `end` (inclusive) or at the end of the representation if `end` is
+    /// `None`
+    FromTo { start: u64, end: Option<u64> },
+
+    /// The final `len` bytes of the representation
+    Suffix { len: u64 },
+}
+
+impl ByteRange {
+    /// Parse a `Range` header value.  Returns `None` if the value is invalid,
+    /// uses a unit other than "bytes", or specifies more than one range.
+    pub(super) fn parse(s: &str) -> Option<ByteRange> {
+        let (unit, spec) = s.trim().split_once('=')?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+        let (first, last) = spec.trim().split_once('-')?;
+        let parse_int = |t: &str| {
+            if !t.is_empty() && t.bytes().all(|b| b.is_ascii_digit()) {
+                t.parse::<u64>().ok()
+            } else {
+                None
+            }
+        };
+        if first.is_empty() {
+            parse_int(last).map(|len| ByteRange::Suffix { len })
+        } else {
+            let start = parse_int(first)?;
+            let end = if last.is_empty() {
+                None
+            } else {
+                Some(parse_int(last)?)
+            };
+            if end.is_some_and(|e| e < start) {
+                return None;
+            }
+            Some(ByteRange::FromTo { start, end })
+        }
+    }
+
+    /// Resolve the range against a representation of `len` bytes, returning
+    /// the offsets of the first and last bytes (inclusive) in the range.
+    /// Returns `None` if the range is unsatisfiable.
+    pub(super) fn resolve(self, len: u64) -> Option<(u64, u64)> {
+        let last = len.checked_sub(1)?;
+        match self {
+            ByteRange::FromTo { start, end } => {
+                (start <= last).then(|| (start, end.map_or(last, |e| e.min(last))))
+            }
+            ByteRange::Suffix { len: 0 } => None,
+            ByteRange::Suffix { len: suffix } => Some((len.saturating_sub(suffix), last)),
+        }
+    }
+}
+
+/// A response extension attached to redirects for non-collection resources of
+/// known size, giving the size in bytes of the redirect target.
+///
+/// `HEAD` requests are handled by converting them to `GET` requests and then
+/// discarding the response body; when this extension is present on the
+/// response, the `HEAD` handler reports the target's size in the
+/// `Content-Length` header and advertises support for byte ranges so that
+/// clients can plan chunked downloads before following the redirect.  The
+/// headers are not added to responses to `GET` requests, as the body of a
+/// redirect is empty.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RedirectTargetSize(pub(crate) u64);
+
+/// A response extension giving the size in bytes of a response body generated
+/// by `dandidav` (e.g., an HTML or JSON collection listing or a
+/// `dandiset.yaml` file).
+///
+/// The `HEAD` handler uses this to report an accurate `Content-Length` without
+/// relying on the body's size hint, which is not exact for streamed bodies.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct BodySize(pub(crate) u64);
+
+/// A response extension describing the error that caused `dandidav` to
+/// return an error response, for use by middleware that keeps track of
+/// errors
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ErrorDetails {
+    /// The classification of the error
+    pub(crate) class: ErrorClass,
+
+    /// The error message, including the messages of the error's sources
+    pub(crate) message: String,
+}
+
+/// Attach a [`BodySize`] extension to `resp` recording that its body is `len`
+/// bytes long
+pub(super) fn with_body_size(mut resp: Response<Body>, len: usize) -> Response<Body> {
+    if let Ok(len) = u64::try_from(len) {
+        resp.extensions_mut().insert(BodySize(len));
+    }
+    resp
+}
+
+/// Generate a response containing an HTML collection listing
+pub(super) fn html_listing_response(html: String) -> Response<Body> {
+    let size = html.len();
+    with_body_size(
+        ([(CONTENT_TYPE, HTML_CONTENT_TYPE), (VARY, "Accept")], html).into_response(),
+        size,
+    )
+}
+
+/// Generate a response serving `blob` with the given `content_type`,
+/// restricted to `range` if it is non-`None`.  Satisfiable ranges produce a
+/// 206 response, and unsatisfiable ones produce a 416 response.
+pub(super) fn blob_response(
+    content_type: String,
+    blob: Vec<u8>,
+    range: Option<ByteRange>,
+) -> Response<Body> {
+    let Some(range) = range else {
+        let size = blob.len();
+        return with_body_size(
+            (
+                [
+                    (CONTENT_TYPE, content_type),
+                    (ACCEPT_RANGES, "bytes".into()),
+                ],
+                blob,
+            )
+                .into_response(),
+            size,
+        );
+    };
+    let len = u64::try_from(blob.len()).unwrap_or(u64::MAX);
+    match range.resolve(len) {
+        Some((start, end)) => {
+            let content_range = format!("bytes {start}-{end}/{len}");
+            let part = usize::try_from(start)
+                .ok()
+                .zip(usize::try_from(end).ok())
+                .and_then(|(s, e)| blob.get(s..=e))
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default();
+            let size = part.len();
+            with_body_size(
+                (
+                    StatusCode::PARTIAL_CONTENT,
+                    [
+                        (CONTENT_TYPE, content_type),
+                        (ACCEPT_RANGES, "bytes".into()),
+                        (CONTENT_RANGE, content_range),
+                    ],
+                    part,
+                )
+                    .into_response(),
+                size,
+            )
+        }
+        None => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(CONTENT_RANGE, format!("bytes */{len}"))],
+        )
+            .into_response(),
+    }
+}
+
+/// Generate a response serving a virtual file with the given `content_type`
+/// whose contents are produced on the fly as a stream of chunks by
+/// `generate`, restricted to `range` if it is non-`None`.
+///
+/// `generate` must yield the same bytes in the same order every time it is
+/// called, which makes the file offset-addressable without buffering it: a
+/// range request is answered by generating the file once to measure its
+/// length and then again, discarding the bytes before the range and stopping
+/// once the end of the range has been reached.  Satisfiable ranges produce a
+/// 206 response, and unsatisfiable ones produce a 416 response; without a
+/// range, the file is streamed in full.
+///
+/// Errors that occur while measuring the file are returned, while errors
+/// that occur after the status line has been sent are logged and cause the
+/// response body to be aborted.
+pub(super) async fn generated_file_response<F, S>(
+    content_type: &'static str,
+    generate: F,
+    range: Option<ByteRange>,
+) -> Result<Response<Body>, DavError>
+where
+    F: Fn() -> S,
+    S: Stream<Item = Result<Bytes, DavError>> + Send + 'static,
+{
+    let Some(range) = range else {
+        return Ok((
+            [(CONTENT_TYPE, content_type), (ACCEPT_RANGES, "bytes")],
+            Body::from_stream(generate().map_err(log_generation_error)),
+        )
+            .into_response());
+    };
+    let len = generate()
+        .try_fold(0u64, |len, chunk| {
+            ready(Ok(len.saturating_add(chunk_len(&chunk))))
+        })
+        .await?;
+    let Some((start, end)) = range.resolve(len) else {
+        return Ok((
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(CONTENT_RANGE, format!("bytes */{len}"))],
+        )
+            .into_response());
+    };
+    let chunks = stream::unfold(
+        (generate().boxed(), 0u64),
+        move |(mut chunks, offset)| async move {
+            if offset > end {
+                return None;
+            }
+            let (r, offset) = match chunks.next().await? {
+                Ok(chunk) => {
+                    let next = offset.saturating_add(chunk_len(&chunk));
+                    let clamp =
+                        |i: u64| usize::try_from(i).map_or(chunk.len(), |i| i.min(chunk.len()));
+                    let lo = clamp(start.saturating_sub(offset));
+                    let hi = clamp(end.saturating_add(1).saturating_sub(offset));
+                    (Ok(chunk.slice(lo..hi)), next)
+                }
+                Err(e) => (Err(e), offset),
+            };
+            Some((r, (chunks, offset)))
+        },
+    );
+    let mut resp = (
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (CONTENT_TYPE, content_type.to_owned()),
+            (ACCEPT_RANGES, "bytes".into()),
+            (CONTENT_RANGE, format!("bytes {start}-{end}/{len}")),
+        ],
+        Body::from_stream(chunks.map_err(log_generation_error)),
+    )
+        .into_response();
+    resp.extensions_mut().insert(BodySize(end - start + 1));
+    Ok(resp)
+}
+
+/// Return the length of `chunk` as a `u64`
+fn chunk_len(chunk: &Bytes) -> u64 {
+    u64::try_from(chunk.len()).unwrap_or(u64::MAX)
+}
+
+/// Log an error that occurred while streaming a generated file to the client
+fn log_generation_error(e: DavError) -> anyhow::Error {
+    let e = anyhow::Error::from(e);
+    tracing::info!(error = ?e, "Error while streaming generated file; aborting response");
+    e
+}
+
+/// The format in which to list a collection's entries in response to a `GET`
+/// request
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub(super) enum ListingFormat {
+    /// An HTML page containing a table of the entries
+    #[default]
+    Html,
+
+    /// A JSON document; see [`JsonListing`][super::json::JsonListing]
+    Json,
+}
+
+impl ListingFormat {
+    /// Determine the listing format requested by a `GET` request with the
+    /// given query string & headers.
+    ///
+    /// A `format` query parameter of "json" or "html" takes precedence.
+    /// Otherwise, JSON is selected if the `Accept` header lists
+    /// `application/json` but not `text/html`, so that browsers continue to
+    /// receive HTML.
+    pub(super) fn from_request(query: Option<&str>, headers: &HeaderMap) -> ListingFormat {
+        if let Some(query) = query {
+            for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+                if key == "format" {
+                    if value.eq_ignore_ascii_case("json") {
+                        return ListingFormat::Json;
+                    } else if value.eq_ignore_ascii_case("html") {
+                        return ListingFormat::Html;
+                    }
+                }
+            }
+        }
+        let mut json = false;
+        for value in headers.get_all(ACCEPT) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            for media_range in value.split(',') {
+                let media_type = media_range
+                    .split_once(';')
+                    .map_or(media_range, |(mt, _)| mt)
+                    .trim();
+                if media_type.eq_ignore_ascii_case("text/html") {
+                    return ListingFormat::Html;
+                } else if media_type.eq_ignore_ascii_case("application/json") {
+                    json = true;
+                }
+            }
+        }
+        if json {
+            ListingFormat::Json
+        } else {
+            ListingFormat::Html
+        }
+    }
+}
+
+/// A request for a single page of a collection's entries, made via the `page`
+/// and `per_page` query parameters of a `GET` request
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub(super) struct PageRequest {
+    /// The number of the requested page, counting from 1
+    pub(super) page: usize,
+
+    /// The number of entries per page, from 1 to [`MAX_PER_PAGE`]
+    pub(super) per_page: usize,
+}
+
+impl PageRequest {
+    /// Parse the `page` and `per_page` parameters from a `GET` request's query
+    /// string.  Returns `Ok(None)` if neither parameter is present.  If only
+    /// one is present, the other defaults to 1 (for `page`) or
+    /// [`MAX_PER_PAGE`] (for `per_page`).  Values of `per_page` greater than
+    /// `MAX_PER_PAGE` are lowered to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if either parameter is not a positive integer
+    pub(super) fn from_query(query: Option<&str>) -> Result<Option<PageRequest>, ()> {
+        let mut page = None;
+        let mut per_page = None;
+        if let Some(query) = query {
+            for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+                let slot = match &*key {
+                    "page" => &mut page,
+                    "per_page" => &mut per_page,
+                    _ => continue,
+                };
+                match value.parse::<usize>() {
+                    Ok(n) if n > 0 => *slot = Some(n),
+                    _ => return Err(()),
+                }
+            }
+        }
+        if page.is_none() && per_page.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(PageRequest {
+            page: page.unwrap_or(1),
+            per_page: per_page.unwrap_or(MAX_PER_PAGE).min(MAX_PER_PAGE),
+        }))
+    }
+
+    /// Return the index of the first entry on the page among all of the
+    /// collection's entries
+    pub(super) fn offset(&self) -> usize {
+        self.page.saturating_sub(1).saturating_mul(self.per_page)
+    }
+
+    /// Return the query string for requesting page number `page` with the
+    /// same number of entries per page
+    pub(super) fn query_for(&self, page: usize) -> String {
+        format!("?page={page}&per_page={}", self.per_page)
+    }
+}
+
+/// A request for a collection's entries to be listed in a given order in an
+/// HTML view, made via the `sort` and `order` query parameters of a `GET`
+/// request
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub(super) struct SortRequest {
+    /// The column to sort by
+    pub(super) key: SortKey,
+
+    /// Whether to sort in descending order
+    pub(super) descending: bool,
+}
+
+impl SortRequest {
+    /// Parse the `sort` and `order` parameters from a `GET` request's query
+    /// string.  Returns `Ok(None)` if neither parameter is present.  `sort`
+    /// defaults to `name`, and `order` defaults to `asc`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if `sort` is not one of `name`, `size`, or `modified` or
+    /// if `order` is not one of `asc` or `desc`
+    pub(super) fn from_query(query: Option<&str>) -> Result<Option<SortRequest>, ()> {
+        let mut key = None;
+        let mut descending = None;
+        if let Some(query) = query {
+            for (k, value) in url::form_urlencoded::parse(query.as_bytes()) {
+                match &*k {
+                    "sort" => key = Some(value.parse::<SortKey>()?),
+                    "order" => {
+                        descending = Some(match &*value {
+                            "asc" => false,
+                            "desc" => true,
+                            _ => return Err(()),
+                        });
+                    }
+                    _ => (),
+                }
+            }
+        }
+        if key.is_none() && descending.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(SortRequest {
+            key: key.unwrap_or_default(),
+            descending: descending.unwrap_or_default(),
+        }))
+    }
+
+    /// Return the query parameters for requesting this sort order, without a
+    /// leading `?` or `&`
+    pub(super) fn query(&self) -> String {
+        if self.descending {
+            format!("sort={}&order=desc", self.key.as_str())
+        } else {
+            format!("sort={}", self.key.as_str())
+        }
+    }
+}
+
+/// A request for a nested JSON tree of a resource and its descendants, made
+/// via the `tree` and `depth` query parameters of a `GET` request
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) struct TreeRequest {
+    /// The number of levels beneath the resource to include, from 0 to
+    /// [`MAX_TREE_DEPTH`]
+    pub(super) depth: u32,
+}
+
+impl TreeRequest {
+    /// Parse the `tree` and `depth` parameters from a `GET` request's query
+    /// string.  Returns `Ok(None)` if `tree` is not present (in which case
+    /// `depth` is ignored).  `depth` defaults to 1, and values greater than
+    /// [`MAX_TREE_DEPTH`] are lowered to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if `tree` is not `json` or if `depth` is not a
+    /// nonnegative integer
+    pub(super) fn from_query(query: Option<&str>) -> Result<Option<TreeRequest>, ()> {
+        let mut tree = false;
+        let mut depth = None;
+        if let Some(query) = query {
+            for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+                match &*key {
+                    "tree" if value == "json" => tree = true,
+                    "tree" => return Err(()),
+                    "depth" => depth = Some(value),
+                    _ => (),
+                }
+            }
+        }
+        if !tree {
+            return Ok(None);
+        }
+        let depth = match depth {
+            Some(d) => d.parse::<u32>().map_err(|_| ())?,
+            None => 1,
+        };
+        Ok(Some(TreeRequest {
+            depth: depth.min(MAX_TREE_DEPTH),
+        }))
+    }
+}
+
+/// Parse the `estimate` parameter from a `GET` request's query string,
+/// returning `true` if it is present with a value of `size`
+///
+/// # Errors
+///
+/// Returns `Err` if the parameter is present with any other value
+fn estimate_requested(query: Option<&str>) -> Result<bool, ()> {
+    let mut estimate = false;
+    if let Some(query) = query {
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            if key == "estimate" {
+                if value == "size" {
+                    estimate = true;
+                } else {
+                    return Err(());
+                }
+            }
+        }
+    }
+    Ok(estimate)
+}
+
+/// How a client should present a non-collection resource to the user, as
+/// requested via a `download=1` or `inline=1` query parameter of a `GET`
+/// request
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Disposition {
+    /// Save the resource as a file (`download=1`)
+    Attachment,
+
+    /// Display the resource in the browser (`inline=1`)
+    Inline,
+}
+
+impl Disposition {
+    /// Determine the disposition requested by the `download` and `inline`
+    /// query parameters in `query`.  Each parameter may be set to `1` to
+    /// request the respective disposition or to `0` to leave it unrequested.
+    ///
+    /// Returns `Err` if either parameter has any other value or if both are
+    /// set to `1`.
+    fn from_query(query: Option<&str>) -> Result<Option<Disposition>, ()> {
+        let mut disposition = None;
+        if let Some(query) = query {
+            for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+                let d = match &*key {
+                    "download" => Disposition::Attachment,
+                    "inline" => Disposition::Inline,
+                    _ => continue,
+                };
+                match &*value {
+                    "1" if disposition.is_none() || disposition == Some(d) => {
+                        disposition = Some(d);
+                    }
+                    "0" => (),
+                    _ => return Err(()),
+                }
+            }
+        }
+        Ok(disposition)
+    }
+
+    /// Return the disposition type as used in `Content-Disposition` headers
+    /// and in the `content_disposition` query parameter of Archive download
+    /// URLs
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Disposition::Attachment => "attachment",
+            Disposition::Inline => "inline",
+        }
+    }
+
+    /// Return a `Content-Disposition` header value presenting a file with
+    /// the given name in this manner.
+    ///
+    /// The name is given both as an ASCII-only `filename` parameter (with
+    /// non-ASCII characters, quotation marks, and backslashes replaced by
+    /// underscores) and, as per RFC 6266, as a UTF-8 `filename*` parameter.
+    pub(super) fn header_value(self, filename: &str) -> HeaderValue {
+        let fallback = filename
+            .chars()
+            .map(|c| {
+                if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let encoded = percent_encode(filename.as_bytes(), RFC5987_ESCAPED);
+        HeaderValue::try_from(format!(
+            "{}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}",
+            self.as_str()
+        ))
+        .expect("Content-Disposition value should be a valid header value")
+    }
+}
+
+/// A filter on the names of a collection's entries, given via the `match`
+/// query parameter of a `GET` or `PROPFIND` request.
+///
+/// A parameter value of the form `re:PATTERN` is a regular expression, which
+/// matches any name containing a match for it; any other value is a glob
+/// pattern (supporting `?`, `*`, and `[...]`), which must match the entire
+/// name.  Matching is case-sensitive.
+#[derive(Clone, Debug)]
+pub(super) struct MatchFilter {
+    /// The value of the `match` parameter
+    source: String,
+
+    /// The compiled pattern
+    pattern: MatchPattern,
+}
+
+#[derive(Clone, Debug)]
+enum MatchPattern {
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+
+impl MatchFilter {
+    /// Parse the `match` parameter from a request's query string.  Returns
+    /// `Ok(None)` if the parameter is not present.  If the parameter is given
+    /// more than once, the last occurrence is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the parameter is empty or is not a valid glob pattern
+    /// or regular expression
+    pub(super) fn from_query(query: Option<&str>) -> Result<Option<MatchFilter>, MatchFilterError> {
+        let Some(source) = query.and_then(|q| {
+            url::form_urlencoded::parse(q.as_bytes())
+                .filter(|(k, _)| k == "match")
+                .map(|(_, v)| v.into_owned())
+                .last()
+        }) else {
+            return Ok(None);
+        };
+        if source.is_empty() || source == "re:" {
+            return Err(MatchFilterError::Empty);
+        }
+        let pattern = match source.strip_prefix("re:") {
+            Some(re) => MatchPattern::Regex(
+                regex::RegexBuilder::new(re)
+                    .size_limit(MATCH_REGEX_SIZE_LIMIT)
+                    .build()?,
+            ),
+            None => MatchPattern::Glob(glob::Pattern::new(&source)?),
+        };
+        Ok(Some(MatchFilter { source, pattern }))
+    }
+
+    /// Test whether the given resource name is matched by the filter
+    pub(super) fn matches(&self, name: &str) -> bool {
+        match self.pattern {
+            MatchPattern::Glob(ref pat) => pat.matches(name),
+            MatchPattern::Regex(ref rgx) => rgx.is_match(name),
+        }
+    }
+
+    /// Test whether the name of the given resource is matched by the filter.
+    /// The root collection, which has no name, is never matched.
+    pub(super) fn matches_resource(&self, res: &DavResource) -> bool {
+        res.name().is_some_and(|name| self.matches(name))
+    }
+
+    /// Return the query parameter for requesting this filter, without a
+    /// leading `?` or `&`
+    pub(super) fn query(&self) -> String {
+        url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("match", &self.source)
+            .finish()
+    }
+}
+
+impl PartialEq for MatchFilter {
+    fn eq(&self, other: &MatchFilter) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for MatchFilter {}
+
+impl Hash for MatchFilter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+    }
+}
+
+/// Error returned by [`MatchFilter::from_query()`] on an invalid `match`
+/// parameter
+#[derive(Debug, Error)]
+pub(super) enum MatchFilterError {
+    #[error("empty pattern")]
+    Empty,
+    #[error("invalid glob pattern: {0}")]
+    Glob(#[from] glob::PatternError),
+    #[error("invalid regular expression: {0}")]
+    Regex(#[from] regex::Error),
+}
+
+/// A filter on which Dandisets are served, configured via the
+/// `--include-dandisets` and `--exclude-dandisets` options.
+///
+/// A Dandiset is served if its ID matches at least one of the `include`
+/// patterns (or if there are no `include` patterns) and does not match any of
+/// the `exclude` patterns.  Dandisets that are not served are omitted from
+/// Dandiset indices and treated as nonexistent when requested directly.  The
+/// filter applies to the Dandisets of every Archive instance being served.
+#[derive(Clone, Debug, Default, Hash)]
+pub(crate) struct DandisetFilter {
+    /// Glob patterns of which a Dandiset's ID must match at least one, if
+    /// nonempty
+    include: Vec<glob::Pattern>,
+
+    /// Glob patterns that a Dandiset's ID must not match
+    exclude: Vec<glob::Pattern>,
+}
+
+impl DandisetFilter {
+    pub(crate) fn new(include: Vec<glob::Pattern>, exclude: Vec<glob::Pattern>) -> DandisetFilter {
+        DandisetFilter { include, exclude }
+    }
+
+    /// Test whether the Dandiset with the given ID should be served
+    pub(crate) fn allows(&self, dandiset_id: &DandisetId) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(dandiset_id)))
+            && !self.exclude.iter().any(|p| p.matches(dandiset_id))
+    }
+}
+
+/// A column by which the entries in an HTML view of a collection can be
+/// sorted
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub(super) enum SortKey {
+    /// Sort by resource name
+    #[default]
+    Name,
+
+    /// Sort by resource size
+    Size,
+
+    /// Sort by modification timestamp
+    Modified,
+}
+
+impl SortKey {
+    /// Return the value of the `sort` query parameter that selects this key
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<SortKey, ()> {
+        match s {
+            "name" => Ok(SortKey::Name),
+            "size" => Ok(SortKey::Size),
+            "modified" => Ok(SortKey::Modified),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Extract the Archive API token, if any, supplied by the client in a
+/// request's `Authorization` header so that it can be used to make requests
+/// to the Archive on the client's behalf.
+///
+/// Both `Authorization: token <TOKEN>` (as used by the Archive API itself)
+/// and Basic authentication with the token as the password (and any
+/// username) are accepted.  Malformed headers are ignored.
+pub(super) fn client_token(headers: &HeaderMap) -> Option<ApiToken> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.trim().split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("token") {
+        credentials.parse().ok()
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        let decoded = BASE64_STANDARD.decode(credentials.trim()).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (_, password) = decoded.split_once(':')?;
+        password.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// The conditional headers of a `GET` or `PROPFIND` request that are
+/// evaluated by `dandidav`, as specified in RFC 9110, section 13.1
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(super) struct Conditions {
+    /// The raw value of the `If-None-Match` header, if any
+    if_none_match: Option<String>,
+
+    /// The parsed value of the `If-Modified-Since` header, if present & valid
+    if_modified_since: Option<OffsetDateTime>,
+}
+
+impl Conditions {
+    /// Extract the conditional headers from a request's headers
+    pub(super) fn from_headers(headers: &HeaderMap) -> Conditions {
+        let if_none_match = headers
+            .get(IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let if_modified_since = headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_http_date);
+        Conditions {
+            if_none_match,
+            if_modified_since,
+        }
+    }
+
+    /// Returns `true` if the conditions indicate that the client's cached
+    /// copy of a resource with the given entity tag & modification time is
+    /// up to date, in which case a 304 response should be returned.
+    ///
+    /// As per RFC 9110, section 13.2.2, `If-Modified-Since` is only evaluated
+    /// when `If-None-Match` is absent.  Entity tags are compared using weak
+    /// comparison.
+    pub(super) fn is_not_modified(
+        &self,
+        etag: Option<&str>,
+        modified: Option<OffsetDateTime>,
+    ) -> bool {
+        if let Some(ref inm) = self.if_none_match {
+            if inm.trim() == "*" {
+                return true;
+            }
+            etag.is_some_and(|etag| self.lists_etag(etag))
+        } else if let Some((since, modified)) = self.if_modified_since.zip(modified) {
+            // HTTP dates have a resolution of one second, so compare against
+            // the modification time as it would be reported in
+            // `Last-Modified`.
+            modified.replace_nanosecond(0).unwrap_or(modified) <= since
+        } else {
+            false
+        }
+    }
+
+    /// Returns `true` if the `If-None-Match` header lists an entity tag that
+    /// matches `etag` under weak comparison.
+    ///
+    /// Unlike [`Conditions::is_not_modified()`], this does not treat
+    /// `If-None-Match: *` as a match, and so it can be used to answer
+    /// conditional requests before it is known whether the resource exists.
+    pub(super) fn lists_etag(&self, etag: &str) -> bool {
+        let Some(ref inm) = self.if_none_match else {
+            return false;
+        };
+        let etag = etag.trim_start_matches("W/").trim_matches('"');
+        // Splitting on double quotes and taking every other piece yields the
+        // opaque tags of the list's entity tags, regardless of weakness
+        // markers.
+        inm.split('"').skip(1).step_by(2).any(|t| t == etag)
+    }
+}
+
+/// Parse an HTTP date in the preferred IMF-fixdate format.  The obsolete
+/// formats are not supported.
+fn parse_http_date(s: &str) -> Option<OffsetDateTime> {
+    PrimitiveDateTime::parse(s.trim(), &RFC1123)
+        .ok()
+        .map(PrimitiveDateTime::assume_utc)
+}
+
+/// Set the `ETag` and `Last-Modified` headers in `headers` to the given
+/// values, if defined
+pub(super) fn insert_validators(
+    headers: &mut HeaderMap,
+    etag: Option<&str>,
+    modified: Option<OffsetDateTime>,
+) {
+    if let Some(v) = etag.and_then(|e| HeaderValue::from_str(e).ok()) {
+        headers.insert(ETAG, v);
+    }
+    if let Some(v) = modified.and_then(|m| HeaderValue::try_from(format_modifieddate(m)).ok()) {
+        headers.insert(LAST_MODIFIED, v);
+    }
+}
+
+/// The validators for responses describing a resource, along with whether
+/// the resource ever changes, as computed by `DandiDav::validators()`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(super) struct Validators {
+    /// The resource's entity tag, if any
+    pub(super) etag: Option<String>,
+
+    /// The resource's modification time, if known
+    pub(super) modified: Option<OffsetDateTime>,
+
+    /// Whether the resource is known to never change
+    pub(super) immutable: bool,
+}
+
+impl Validators {
+    /// Return the validators for a non-collection resource with the given
+    /// entity tag & modification time (if any), falling back to those in
+    /// `self` for any that are not defined
+    pub(super) fn for_item(
+        &self,
+        etag: Option<&str>,
+        modified: Option<OffsetDateTime>,
+    ) -> Validators {
+        Validators {
+            etag: etag.or(self.etag.as_deref()).map(String::from),
+            modified: modified.or(self.modified),
+            immutable: self.immutable,
+        }
+    }
+
+    /// Set the `ETag`, `Last-Modified`, and `Cache-Control` headers in
+    /// `headers` as appropriate
+    pub(super) fn insert(&self, headers: &mut HeaderMap) {
+        insert_validators(headers, self.etag.as_deref(), self.modified);
+        let cache_control = if self.immutable {
+            IMMUTABLE_CACHE_CONTROL
+        } else {
+            MUTABLE_CACHE_CONTROL
+        };
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    }
+
+    /// Returns `true` if `conditions` lists the entity tag in `self` (see
+    /// [`Conditions::lists_etag()`])
+    pub(super) fn is_listed_in(&self, conditions: &Conditions) -> bool {
+        self.etag
+            .as_deref()
+            .is_some_and(|etag| conditions.lists_etag(etag))
+    }
+
+    /// Generate a 304 response for a resource with these validators
+    pub(super) fn not_modified(&self) -> Response<Body> {
+        let mut resp = StatusCode::NOT_MODIFIED.into_response();
+        self.insert(resp.headers_mut());
+        resp
+    }
+}
+
+/// If `disposition` is non-`None`, set the `Content-Disposition` header in
+/// `headers` to present a file with the given name in that manner
+pub(super) fn insert_disposition(
+    headers: &mut HeaderMap,
+    disposition: Option<Disposition>,
+    filename: &str,
+) {
+    if let Some(d) = disposition {
+        headers.insert(CONTENT_DISPOSITION, d.header_value(filename));
+    }
+}
+
+/// How to respond to `GET` requests for the root collection (`/`)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum RootBehavior {
+    /// Render the root collection as HTML like any other collection
+    Render,
+
+    /// Redirect to the given absolute URL or absolute URL path
+    Redirect(String),
+
+    /// Respond with a 404
+    NotFound,
+}
+
+impl FromStr for RootBehavior {
+    type Err = ParseRootBehaviorError;
+
+    /// Parse a `RootBehavior` from a string.  The string must be either
+    /// "render", "404", an absolute URL path (starting with a forward slash),
+    /// or an HTTP(S) URL.
+    fn from_str(s: &str) -> Result<RootBehavior, ParseRootBehaviorError> {
+        match s {
+            "render" => Ok(RootBehavior::Render),
+            "404" => Ok(RootBehavior::NotFound),
+            s if s.starts_with('/') && !s.starts_with("//") => {
+                Ok(RootBehavior::Redirect(s.to_owned()))
+            }
+            s => Ok(RootBehavior::Redirect(s.parse::<HttpUrl>()?.to_string())),
+        }
+    }
+}
+
+/// Error returned when parsing an invalid [`RootBehavior`] string
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error(r#"expected "render", "404", an absolute URL path, or an HTTP(S) URL"#)]
+pub(crate) struct ParseRootBehaviorError(#[source] ParseHttpUrlError);
+
+impl From<ParseHttpUrlError> for ParseRootBehaviorError {
+    fn from(e: ParseHttpUrlError) -> ParseRootBehaviorError {
+        ParseRootBehaviorError(e)
+    }
+}
+
+/// A category of WebDAV request with its own time limit
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum TimeoutClass {
+    /// A `GET` request for a collection or a `PROPFIND` request with a finite
+    /// `Depth`; the full listing must be generated within the time limit
+    Listing,
+
+    /// A `GET` request for a non-collection resource, usually answered with a
+    /// redirect
+    Redirect,
+
+    /// A `PROPFIND` request with an infinite `Depth` or a `GET` request for a
+    /// Dandiset version's `assets.jsonl` file or a file in a local mount,
+    /// whose response body is streamed; the entire body must be sent within
+    /// the time limit
+    Stream,
+}
+
+/// Time limits for handling WebDAV requests of each [`TimeoutClass`].  A value
+/// of `None` means that requests of the corresponding class have no time
+/// limit.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct RequestTimeouts {
+    pub(crate) listing: Option<Duration>,
+    pub(crate) redirect: Option<Duration>,
+    pub(crate) stream: Option<Duration>,
+}
+
+impl RequestTimeouts {
+    /// Return the time limit for requests of the given class
+    pub(super) fn get(&self, class: TimeoutClass) -> Option<Duration> {
+        match class {
+            TimeoutClass::Listing => self.listing,
+            TimeoutClass::Redirect => self.redirect,
+            TimeoutClass::Stream => self.stream,
+        }
+    }
+}
+
+/// Wrap the body of `resp` so that, if the body has not been fully produced
+/// by `deadline`, it is aborted, so that clients do not mistake a partial
+/// response for a complete one
+pub(super) fn with_body_deadline(resp: Response<Body>, deadline: Instant) -> Response<Body> {
+    resp.map(|body| {
+        let chunks = stream::unfold(Some(body.into_data_stream()), move |state| async move {
+            let mut chunks = state?;
+            match tokio::time::timeout_at(deadline, chunks.next()).await {
+                Ok(Some(r)) => Some((r, Some(chunks))),
+                Ok(None) => None,
+                Err(_) => {
+                    tracing::info!("Response body was not completed before the request's deadline; aborting response");
+                    let e = std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "response body was not completed in time",
+                    );
+                    Some((Err(axum::Error::new(e)), None))
+                }
+            }
+        });
+        Body::from_stream(chunks)
+    })
+}
+
+/// Classify `resp` by the kind of content it carries, for use as a label on
+/// the response size metrics
+pub(super) fn response_kind(resp: &Response<Body>) -> &'static str {
+    let status = resp.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return "not-modified";
+    } else if status.is_redirection() {
+        return "redirect";
+    } else if status.is_client_error() || status.is_server_error() {
+        return "error";
+    }
+    let Some(ctype) = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return "other";
+    };
+    if ctype == HTML_CONTENT_TYPE {
+        "html"
+    } else if ctype == DAV_XML_CONTENT_TYPE {
+        "multistatus"
+    } else if ctype == JSON_CONTENT_TYPE {
+        "json"
+    } else if ctype == JSONL_CONTENT_TYPE {
+        "jsonl"
+    } else {
+        "other"
+    }
+}
+
+/// Record the size of the body of `resp`, a response to a request for a path
+/// of type `path_kind`, in the response size metrics.
+///
+/// If the size of the body is known in advance, it is recorded immediately.
+/// Otherwise, the body is wrapped so that the number of bytes produced is
+/// recorded once the body is finished or dropped; for aborted responses, this
+/// is the number of bytes produced before the abort.
+pub(super) fn record_response_size(
+    resp: Response<Body>,
+    path_kind: &'static str,
+) -> Response<Body> {
+    let kind = response_kind(&resp);
+    let size = resp
+        .extensions()
+        .get::<BodySize>()
+        .map(|&BodySize(size)| size)
+        .or_else(|| resp.body().size_hint().exact());
+    if let Some(size) = size {
+        metrics().record_response_size(path_kind, kind, size);
+        return resp;
+    }
+    resp.map(|body| {
+        let mut counter = BodySizeCounter {
+            path_kind,
+            kind,
+            size: 0,
+        };
+        Body::from_stream(body.into_data_stream().inspect_ok(move |chunk| {
+            counter.size = counter
+                .size
+                .saturating_add(u64::try_from(chunk.len()).unwrap_or(u64::MAX));
+        }))
+    })
+}
+
+/// A running count of the bytes produced by a streamed response body that is
+/// recorded in the response size metrics when dropped
+#[derive(Debug)]
+struct BodySizeCounter {
+    path_kind: &'static str,
+    kind: &'static str,
+    size: u64,
+}
+
+impl Drop for BodySizeCounter {
+    fn drop(&mut self) {
+        metrics().record_response_size(self.path_kind, self.kind, self.size);
+    }
+}
+
 /// A percent-encoded URI or URI path, for use in the `href` attribute of an
 /// HTML `<a>` tag or in a `<DAV:href>` tag in a `PROPFIND` response
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub(super) struct Href(String);
 
 impl Href {
@@ -221,11 +1679,746 @@ pub(super) fn not_found() -> Response<Body> {
     (StatusCode::NOT_FOUND, "404\n").into_response()
 }
 
+/// Generate a 401 response challenging the client to supply an Archive API
+/// token
+pub(super) fn unauthorized() -> Response<Body> {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(WWW_AUTHENTICATE, r#"Basic realm="DANDI Archive API token""#)],
+        "401\n",
+    )
+        .into_response()
+}
+
+/// Generate a 403 response to a `PROPFIND` request with a missing or
+/// "infinite" `Depth` header when such requests are not enabled
+pub(super) fn infinite_depth_forbidden() -> Response<Body> {
+    (
+        StatusCode::FORBIDDEN,
+        [(CONTENT_TYPE, DAV_XML_CONTENT_TYPE)],
+        INFINITE_DEPTH_RESPONSE,
+    )
+        .into_response()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_matches::assert_matches;
+    use futures_util::stream::BoxStream;
+    use rstest::rstest;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
     use time::macros::datetime;
 
+    async fn extract_depth(value: Option<&str>) -> Result<Depth, StatusCode> {
+        let mut req = Request::builder().method("PROPFIND").uri("/");
+        if let Some(v) = value {
+            req = req.header("Depth", v);
+        }
+        let (mut parts, ()) = req.body(()).unwrap().into_parts();
+        Depth::from_request_parts(&mut parts, &())
+            .await
+            .map_err(|r| r.status())
+    }
+
+    #[rstest]
+    #[case(Some("0"), Depth::Zero)]
+    #[case(Some("1"), Depth::One)]
+    #[case(Some("infinity"), Depth::Infinity)]
+    #[case(None, Depth::Infinity)]
+    #[tokio::test]
+    async fn test_depth(#[case] value: Option<&str>, #[case] depth: Depth) {
+        assert_eq!(extract_depth(value).await, Ok(depth));
+    }
+
+    #[rstest]
+    #[case("2")]
+    #[case("Infinity")]
+    #[case("")]
+    #[tokio::test]
+    async fn test_bad_depth(#[case] value: &str) {
+        assert_eq!(
+            extract_depth(Some(value)).await,
+            Err(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[rstest]
+    #[case("bytes=0-499", ByteRange::FromTo { start: 0, end: Some(499) })]
+    #[case("bytes=500-", ByteRange::FromTo { start: 500, end: None })]
+    #[case("bytes=-500", ByteRange::Suffix { len: 500 })]
+    #[case("bytes=5-5", ByteRange::FromTo { start: 5, end: Some(5) })]
+    #[case("Bytes = 1-2 ", ByteRange::FromTo { start: 1, end: Some(2) })]
+    fn test_parse_byte_range(#[case] s: &str, #[case] range: ByteRange) {
+        assert_eq!(ByteRange::parse(s), Some(range));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("bytes")]
+    #[case("bytes=")]
+    #[case("bytes=-")]
+    #[case("bytes=5-4")]
+    #[case("bytes=0-1,3-4")]
+    #[case("bytes=a-b")]
+    #[case("bytes=+1-2")]
+    #[case("items=0-1")]
+    fn test_parse_bad_byte_range(#[case] s: &str) {
+        assert_eq!(ByteRange::parse(s), None);
+    }
+
+    #[rstest]
+    #[case(ByteRange::FromTo { start: 0, end: Some(4) }, 10, Some((0, 4)))]
+    #[case(ByteRange::FromTo { start: 5, end: None }, 10, Some((5, 9)))]
+    #[case(ByteRange::FromTo { start: 5, end: Some(100) }, 10, Some((5, 9)))]
+    #[case(ByteRange::FromTo { start: 10, end: None }, 10, None)]
+    #[case(ByteRange::FromTo { start: 0, end: None }, 0, None)]
+    #[case(ByteRange::Suffix { len: 3 }, 10, Some((7, 9)))]
+    #[case(ByteRange::Suffix { len: 30 }, 10, Some((0, 9)))]
+    #[case(ByteRange::Suffix { len: 0 }, 10, None)]
+    #[case(ByteRange::Suffix { len: 3 }, 0, None)]
+    fn test_resolve_byte_range(
+        #[case] range: ByteRange,
+        #[case] len: u64,
+        #[case] resolved: Option<(u64, u64)>,
+    ) {
+        assert_eq!(range.resolve(len), resolved);
+    }
+
+    #[test]
+    fn test_blob_response_full() {
+        let r = blob_response("text/plain".into(), b"0123456789".to_vec(), None);
+        assert_eq!(r.status(), StatusCode::OK);
+        assert_eq!(r.headers()[ACCEPT_RANGES], "bytes");
+        assert!(!r.headers().contains_key(CONTENT_RANGE));
+        assert_eq!(r.extensions().get::<BodySize>(), Some(&BodySize(10)));
+    }
+
+    #[tokio::test]
+    async fn test_blob_response_partial() {
+        let r = blob_response(
+            "text/plain".into(),
+            b"0123456789".to_vec(),
+            Some(ByteRange::FromTo {
+                start: 2,
+                end: Some(5),
+            }),
+        );
+        assert_eq!(r.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(r.headers()[CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(r.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(r.extensions().get::<BodySize>(), Some(&BodySize(4)));
+        let body = axum::body::to_bytes(r.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"2345");
+    }
+
+    #[rstest]
+    #[case(StatusCode::OK, Some(HTML_CONTENT_TYPE), "html")]
+    #[case(StatusCode::MULTI_STATUS, Some(DAV_XML_CONTENT_TYPE), "multistatus")]
+    #[case(StatusCode::OK, Some(JSON_CONTENT_TYPE), "json")]
+    #[case(StatusCode::OK, Some(JSONL_CONTENT_TYPE), "jsonl")]
+    #[case(StatusCode::OK, Some("text/yaml"), "other")]
+    #[case(StatusCode::OK, None, "other")]
+    #[case(StatusCode::TEMPORARY_REDIRECT, None, "redirect")]
+    #[case(StatusCode::NOT_MODIFIED, None, "not-modified")]
+    #[case(StatusCode::NOT_FOUND, Some(HTML_CONTENT_TYPE), "error")]
+    #[case(StatusCode::BAD_GATEWAY, None, "error")]
+    fn test_response_kind(
+        #[case] status: StatusCode,
+        #[case] ctype: Option<&str>,
+        #[case] kind: &str,
+    ) {
+        let mut resp = status.into_response();
+        if let Some(ctype) = ctype {
+            resp.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_str(ctype).unwrap());
+        }
+        assert_eq!(response_kind(&resp), kind);
+    }
+
+    #[tokio::test]
+    async fn test_record_response_size_streamed() {
+        let chunks = stream::iter(["foo", "bar"].map(Ok::<_, std::io::Error>));
+        let resp = Response::new(Body::from_stream(chunks));
+        let resp = record_response_size(resp, "dandiset");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"foobar");
+    }
+
+    #[test]
+    fn test_blob_response_unsatisfiable() {
+        let r = blob_response(
+            "text/plain".into(),
+            b"0123456789".to_vec(),
+            Some(ByteRange::FromTo {
+                start: 10,
+                end: None,
+            }),
+        );
+        assert_eq!(r.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(r.headers()[CONTENT_RANGE], "bytes */10");
+    }
+
+    /// Return a generator for a file consisting of the chunks "abc", "defg",
+    /// and "hi" that records the number of chunks polled in `polled`
+    fn generate_chunks(
+        polled: Arc<AtomicUsize>,
+    ) -> impl Fn() -> BoxStream<'static, Result<Bytes, DavError>> {
+        move || {
+            let polled = polled.clone();
+            stream::iter(["abc", "defg", "hi"])
+                .inspect(move |_| {
+                    polled.fetch_add(1, Ordering::Relaxed);
+                })
+                .map(Bytes::from)
+                .map(Ok)
+                .boxed()
+        }
+    }
+
+    #[rstest]
+    #[case(None, StatusCode::OK, None, "abcdefghi")]
+    #[case(
+        Some(ByteRange::FromTo { start: 2, end: Some(5) }),
+        StatusCode::PARTIAL_CONTENT,
+        Some("bytes 2-5/9"),
+        "cdef"
+    )]
+    #[case(
+        Some(ByteRange::FromTo { start: 4, end: None }),
+        StatusCode::PARTIAL_CONTENT,
+        Some("bytes 4-8/9"),
+        "efghi"
+    )]
+    #[case(
+        Some(ByteRange::FromTo { start: 0, end: Some(100) }),
+        StatusCode::PARTIAL_CONTENT,
+        Some("bytes 0-8/9"),
+        "abcdefghi"
+    )]
+    #[case(
+        Some(ByteRange::Suffix { len: 3 }),
+        StatusCode::PARTIAL_CONTENT,
+        Some("bytes 6-8/9"),
+        "ghi"
+    )]
+    #[tokio::test]
+    async fn test_generated_file_response(
+        #[case] range: Option<ByteRange>,
+        #[case] status: StatusCode,
+        #[case] content_range: Option<&str>,
+        #[case] body: &str,
+    ) {
+        let polled = Arc::new(AtomicUsize::new(0));
+        let r = generated_file_response("text/plain", generate_chunks(polled), range)
+            .await
+            .unwrap();
+        assert_eq!(r.status(), status);
+        assert_eq!(r.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(r.headers()[ACCEPT_RANGES], "bytes");
+        assert_eq!(
+            r.headers().get(CONTENT_RANGE).map(|v| v.to_str().unwrap()),
+            content_range
+        );
+        if range.is_some() {
+            assert_eq!(
+                r.extensions().get::<BodySize>(),
+                Some(&BodySize(body.len().try_into().unwrap()))
+            );
+        }
+        let data = axum::body::to_bytes(r.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&data[..], body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_generated_file_response_stops_after_range() {
+        let polled = Arc::new(AtomicUsize::new(0));
+        let r = generated_file_response(
+            "text/plain",
+            generate_chunks(polled.clone()),
+            Some(ByteRange::FromTo {
+                start: 1,
+                end: Some(2),
+            }),
+        )
+        .await
+        .unwrap();
+        let data = axum::body::to_bytes(r.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&data[..], b"bc");
+        // Three chunks to measure the file, then one to serve the range:
+        assert_eq!(polled.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn test_generated_file_response_unsatisfiable() {
+        let polled = Arc::new(AtomicUsize::new(0));
+        let r = generated_file_response(
+            "text/plain",
+            generate_chunks(polled),
+            Some(ByteRange::FromTo {
+                start: 9,
+                end: None,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(r.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(r.headers()[CONTENT_RANGE], "bytes */9");
+    }
+
+    mod conditions {
+        use super::*;
+
+        fn conditions(inm: Option<&str>, ims: Option<&str>) -> Conditions {
+            let mut headers = HeaderMap::new();
+            if let Some(v) = inm {
+                headers.insert(IF_NONE_MATCH, HeaderValue::from_str(v).unwrap());
+            }
+            if let Some(v) = ims {
+                headers.insert(IF_MODIFIED_SINCE, HeaderValue::from_str(v).unwrap());
+            }
+            Conditions::from_headers(&headers)
+        }
+
+        const ETAG: Option<&str> = Some(r#""0123456789abcdef""#);
+        const MODIFIED: Option<OffsetDateTime> = Some(datetime!(2024-02-14 22:13:35.123 UTC));
+
+        #[test]
+        fn none() {
+            assert!(!conditions(None, None).is_not_modified(ETAG, MODIFIED));
+        }
+
+        #[rstest]
+        #[case(r#""0123456789abcdef""#, true)]
+        #[case(r#"W/"0123456789abcdef""#, true)]
+        #[case(r#""foo", "0123456789abcdef""#, true)]
+        #[case("*", true)]
+        #[case(r#""foo""#, false)]
+        #[case(r#""0123456789ABCDEF""#, false)]
+        fn if_none_match(#[case] inm: &str, #[case] matches: bool) {
+            assert_eq!(
+                conditions(Some(inm), None).is_not_modified(ETAG, MODIFIED),
+                matches
+            );
+        }
+
+        #[rstest]
+        #[case(r#""0123456789abcdef""#, true)]
+        #[case(r#"W/"0123456789abcdef""#, true)]
+        #[case(r#""foo", W/"0123456789abcdef""#, true)]
+        #[case("*", false)]
+        #[case(r#""foo""#, false)]
+        fn lists_etag(#[case] inm: &str, #[case] matches: bool) {
+            assert_eq!(
+                conditions(Some(inm), None).lists_etag(r#"W/"0123456789abcdef""#),
+                matches
+            );
+            assert!(!conditions(None, None).lists_etag(r#"W/"0123456789abcdef""#));
+        }
+
+        #[test]
+        fn if_none_match_no_etag() {
+            assert!(!conditions(Some(r#""foo""#), None).is_not_modified(None, MODIFIED));
+            assert!(conditions(Some("*"), None).is_not_modified(None, MODIFIED));
+        }
+
+        #[rstest]
+        #[case("Wed, 14 Feb 2024 22:13:35 GMT", true)]
+        #[case("Thu, 15 Feb 2024 00:00:00 GMT", true)]
+        #[case("Wed, 14 Feb 2024 22:13:34 GMT", false)]
+        #[case("Wednesday, 14-Feb-24 22:13:35 GMT", false)]
+        #[case("garbage", false)]
+        fn if_modified_since(#[case] ims: &str, #[case] unmodified: bool) {
+            assert_eq!(
+                conditions(None, Some(ims)).is_not_modified(ETAG, MODIFIED),
+                unmodified
+            );
+        }
+
+        #[test]
+        fn if_modified_since_no_mtime() {
+            let c = conditions(None, Some("Wed, 14 Feb 2024 22:13:35 GMT"));
+            assert!(!c.is_not_modified(ETAG, None));
+        }
+
+        #[test]
+        fn if_none_match_overrides_if_modified_since() {
+            let c = conditions(Some(r#""foo""#), Some("Wed, 14 Feb 2024 22:13:35 GMT"));
+            assert!(!c.is_not_modified(ETAG, MODIFIED));
+        }
+
+        #[test]
+        fn not_modified_response() {
+            let validators = Validators {
+                etag: ETAG.map(String::from),
+                modified: MODIFIED,
+                immutable: false,
+            };
+            let r = validators.not_modified();
+            assert_eq!(r.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(r.headers()[super::ETAG], r#""0123456789abcdef""#);
+            assert_eq!(r.headers()[LAST_MODIFIED], "Wed, 14 Feb 2024 22:13:35 GMT");
+            assert_eq!(r.headers()[CACHE_CONTROL], "no-cache");
+        }
+
+        #[test]
+        fn for_item() {
+            let validators = Validators {
+                etag: Some(r#"W/"fedcba9876543210""#.into()),
+                modified: None,
+                immutable: true,
+            };
+            let item = validators.for_item(None, MODIFIED);
+            assert_eq!(item.etag.as_deref(), Some(r#"W/"fedcba9876543210""#));
+            assert_eq!(item.modified, MODIFIED);
+            assert!(item.immutable);
+            let item = validators.for_item(ETAG, None);
+            assert_eq!(item.etag.as_deref(), ETAG);
+            assert_eq!(item.modified, None);
+            let mut headers = HeaderMap::new();
+            item.insert(&mut headers);
+            assert_eq!(headers[CACHE_CONTROL], "public, max-age=604800");
+        }
+    }
+
+    #[rstest]
+    #[case(None, None, ListingFormat::Html)]
+    #[case(Some("format=json"), None, ListingFormat::Json)]
+    #[case(Some("foo=bar&format=JSON"), None, ListingFormat::Json)]
+    #[case(Some("format=json"), Some("text/html"), ListingFormat::Json)]
+    #[case(Some("format=html"), Some("application/json"), ListingFormat::Html)]
+    #[case(Some("format=xml"), None, ListingFormat::Html)]
+    #[case(None, Some("application/json"), ListingFormat::Json)]
+    #[case(None, Some("application/json; q=0.9, */*; q=0.1"), ListingFormat::Json)]
+    #[case(
+        None,
+        Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+        ListingFormat::Html
+    )]
+    #[case(None, Some("application/json, text/html"), ListingFormat::Html)]
+    #[case(None, Some("*/*"), ListingFormat::Html)]
+    fn test_listing_format(
+        #[case] query: Option<&str>,
+        #[case] accept: Option<&str>,
+        #[case] format: ListingFormat,
+    ) {
+        let mut headers = HeaderMap::new();
+        if let Some(v) = accept {
+            headers.insert(ACCEPT, HeaderValue::from_str(v).unwrap());
+        }
+        assert_eq!(ListingFormat::from_request(query, &headers), format);
+    }
+
+    #[rstest]
+    #[case(None, Ok(None))]
+    #[case(Some("format=json"), Ok(None))]
+    #[case(Some("page=3"), Ok(Some((3, MAX_PER_PAGE))))]
+    #[case(Some("per_page=50"), Ok(Some((1, 50))))]
+    #[case(Some("page=2&per_page=100&format=html"), Ok(Some((2, 100))))]
+    #[case(Some("page=1&per_page=1000000"), Ok(Some((1, MAX_PER_PAGE))))]
+    #[case(Some("page=0"), Err(()))]
+    #[case(Some("per_page=0"), Err(()))]
+    #[case(Some("page=two"), Err(()))]
+    #[case(Some("page=-1"), Err(()))]
+    #[case(Some("per_page="), Err(()))]
+    fn test_page_request(
+        #[case] query: Option<&str>,
+        #[case] expected: Result<Option<(usize, usize)>, ()>,
+    ) {
+        let expected =
+            expected.map(|opt| opt.map(|(page, per_page)| PageRequest { page, per_page }));
+        assert_eq!(PageRequest::from_query(query), expected);
+    }
+
+    #[rstest]
+    #[case(None, Ok(None))]
+    #[case(Some("format=json"), Ok(None))]
+    #[case(Some("depth=3"), Ok(None))]
+    #[case(Some("depth=deep"), Ok(None))]
+    #[case(Some("tree=json"), Ok(Some(1)))]
+    #[case(Some("tree=json&depth=0"), Ok(Some(0)))]
+    #[case(Some("depth=3&tree=json"), Ok(Some(3)))]
+    #[case(Some("tree=json&depth=1000"), Ok(Some(MAX_TREE_DEPTH)))]
+    #[case(Some("tree=xml"), Err(()))]
+    #[case(Some("tree="), Err(()))]
+    #[case(Some("tree=json&depth=-1"), Err(()))]
+    #[case(Some("tree=json&depth=deep"), Err(()))]
+    fn test_tree_request(#[case] query: Option<&str>, #[case] expected: Result<Option<u32>, ()>) {
+        let expected = expected.map(|opt| opt.map(|depth| TreeRequest { depth }));
+        assert_eq!(TreeRequest::from_query(query), expected);
+    }
+
+    #[rstest]
+    #[case(None, Ok(false))]
+    #[case(Some("format=json"), Ok(false))]
+    #[case(Some("estimate=size"), Ok(true))]
+    #[case(Some("format=json&estimate=size"), Ok(true))]
+    #[case(Some("estimate=count"), Err(()))]
+    #[case(Some("estimate="), Err(()))]
+    fn test_estimate_requested(#[case] query: Option<&str>, #[case] expected: Result<bool, ()>) {
+        assert_eq!(estimate_requested(query), expected);
+    }
+
+    #[rstest]
+    #[case(None, Ok(None))]
+    #[case(Some("format=json"), Ok(None))]
+    #[case(Some("download=1"), Ok(Some(Disposition::Attachment)))]
+    #[case(Some("inline=1"), Ok(Some(Disposition::Inline)))]
+    #[case(Some("download=0"), Ok(None))]
+    #[case(Some("download=0&inline=1"), Ok(Some(Disposition::Inline)))]
+    #[case(Some("download=1&download=1"), Ok(Some(Disposition::Attachment)))]
+    #[case(Some("download=1&inline=1"), Err(()))]
+    #[case(Some("download=yes"), Err(()))]
+    #[case(Some("inline="), Err(()))]
+    fn test_disposition_from_query(
+        #[case] query: Option<&str>,
+        #[case] expected: Result<Option<Disposition>, ()>,
+    ) {
+        assert_eq!(Disposition::from_query(query), expected);
+    }
+
+    #[rstest]
+    #[case(
+        Disposition::Attachment,
+        "dandiset.yaml",
+        "attachment; filename=\"dandiset.yaml\"; filename*=UTF-8''dandiset.yaml"
+    )]
+    #[case(
+        Disposition::Inline,
+        "sub-01_ses-1.nwb.dandi.json",
+        "inline; filename=\"sub-01_ses-1.nwb.dandi.json\"; filename*=UTF-8''sub-01_ses-1.nwb.dandi.json"
+    )]
+    #[case(
+        Disposition::Attachment,
+        "caf\u{e9} \"au\" lait.txt",
+        "attachment; filename=\"caf_ _au_ lait.txt\"; filename*=UTF-8''caf%C3%A9%20%22au%22%20lait.txt"
+    )]
+    fn test_disposition_header_value(
+        #[case] disposition: Disposition,
+        #[case] filename: &str,
+        #[case] value: &str,
+    ) {
+        assert_eq!(disposition.header_value(filename), value);
+    }
+
+    #[test]
+    fn test_page_request_offset() {
+        let pr = PageRequest {
+            page: 3,
+            per_page: 100,
+        };
+        assert_eq!(pr.offset(), 200);
+        assert_eq!(pr.query_for(4), "?page=4&per_page=100");
+    }
+
+    #[rstest]
+    #[case(None, Ok(None))]
+    #[case(Some("page=2"), Ok(None))]
+    #[case(Some("sort=size"), Ok(Some((SortKey::Size, false))))]
+    #[case(Some("sort=modified&order=desc"), Ok(Some((SortKey::Modified, true))))]
+    #[case(Some("order=desc&page=2"), Ok(Some((SortKey::Name, true))))]
+    #[case(Some("sort=name&order=asc"), Ok(Some((SortKey::Name, false))))]
+    #[case(Some("sort=type"), Err(()))]
+    #[case(Some("sort=Size"), Err(()))]
+    #[case(Some("sort=size&order=descending"), Err(()))]
+    #[case(Some("sort="), Err(()))]
+    fn test_sort_request(
+        #[case] query: Option<&str>,
+        #[case] expected: Result<Option<(SortKey, bool)>, ()>,
+    ) {
+        let expected =
+            expected.map(|opt| opt.map(|(key, descending)| SortRequest { key, descending }));
+        assert_eq!(SortRequest::from_query(query), expected);
+    }
+
+    #[rstest]
+    #[case(SortKey::Name, false, "sort=name")]
+    #[case(SortKey::Size, true, "sort=size&order=desc")]
+    #[case(SortKey::Modified, false, "sort=modified")]
+    fn test_sort_request_query(#[case] key: SortKey, #[case] descending: bool, #[case] q: &str) {
+        let sr = SortRequest { key, descending };
+        assert_eq!(sr.query(), q);
+        assert_eq!(SortRequest::from_query(Some(q)), Ok(Some(sr)));
+    }
+
+    #[rstest]
+    #[case("match=*.nwb", "sub-01.nwb", true)]
+    #[case("match=*.nwb", "sub-01.nwb.json", false)]
+    #[case("match=*.nwb", "SUB-01.NWB", false)]
+    #[case("match=sub-0%3F", "sub-01", true)]
+    #[case("match=sub-%5B12%5D", "sub-3", false)]
+    #[case("match=re:nwb", "sub-01.nwb.json", true)]
+    #[case("match=re:%5Esub-%5Cd%2B%24", "sub-42", true)]
+    #[case("match=re:%5Esub-%5Cd%2B%24", "sub-42.nwb", false)]
+    #[case("page=2&match=foo&match=bar", "bar", true)]
+    #[case("page=2&match=foo&match=bar", "foo", false)]
+    fn test_match_filter(#[case] query: &str, #[case] name: &str, #[case] matches: bool) {
+        let filter = MatchFilter::from_query(Some(query)).unwrap().unwrap();
+        assert_eq!(filter.matches(name), matches);
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some("page=2"))]
+    #[case(Some("matches=*.nwb"))]
+    fn test_match_filter_absent(#[case] query: Option<&str>) {
+        assert_matches!(MatchFilter::from_query(query), Ok(None));
+    }
+
+    #[rstest]
+    #[case("match=", "empty pattern")]
+    #[case("match=re:", "empty pattern")]
+    #[case("match=%5B", "invalid glob pattern")]
+    #[case("match=re:(", "invalid regular expression")]
+    fn test_bad_match_filter(#[case] query: &str, #[case] msg: &str) {
+        let e = MatchFilter::from_query(Some(query)).unwrap_err();
+        assert!(e.to_string().starts_with(msg), "{e}");
+    }
+
+    #[test]
+    fn test_match_filter_query() {
+        let filter = MatchFilter::from_query(Some("match=re:%5Esub-.*%26"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(filter.query(), "match=re%3A%5Esub-.*%26");
+        assert_eq!(
+            MatchFilter::from_query(Some(&filter.query())).unwrap(),
+            Some(filter)
+        );
+    }
+
+    #[rstest]
+    #[case(None, None)]
+    #[case(Some("token abc123"), Some("abc123"))]
+    #[case(Some("Token  abc123 "), Some("abc123"))]
+    #[case(Some("Basic dXNlcjphYmMxMjM="), Some("abc123"))]
+    #[case(Some("basic OmFiYzEyMw=="), Some("abc123"))]
+    #[case(Some("Basic YWJjMTIz"), None)]
+    #[case(Some("Basic dXNlcjo="), None)]
+    #[case(Some("Basic !!!"), None)]
+    #[case(Some("Bearer abc123"), None)]
+    #[case(Some("token"), None)]
+    fn test_client_token(#[case] authorization: Option<&str>, #[case] token: Option<&str>) {
+        let mut headers = HeaderMap::new();
+        if let Some(v) = authorization {
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(v).unwrap());
+        }
+        assert_eq!(
+            client_token(&headers),
+            token.map(|t| t.parse::<ApiToken>().unwrap())
+        );
+    }
+
+    #[rstest]
+    #[case("GET", "/", None, Some(TimeoutClass::Listing))]
+    #[case("GET", "/dandisets/000027/draft/", None, Some(TimeoutClass::Listing))]
+    #[case(
+        "GET",
+        "/dandisets/000027/draft/foo/",
+        None,
+        Some(TimeoutClass::Listing)
+    )]
+    #[case(
+        "GET",
+        "/dandisets/000027/draft/foo.nwb",
+        None,
+        Some(TimeoutClass::Redirect)
+    )]
+    #[case(
+        "GET",
+        "/dandisets/000027/draft/dandiset.yaml",
+        None,
+        Some(TimeoutClass::Redirect)
+    )]
+    #[case("GET", "/zarrs/abc/def.zarr/", None, Some(TimeoutClass::Listing))]
+    #[case("GET", "/zarrs/abc/def.zarr/0/0", None, Some(TimeoutClass::Redirect))]
+    #[case(
+        "PROPFIND",
+        "/zarrs/abc/def.zarr",
+        Some("1"),
+        Some(TimeoutClass::Listing)
+    )]
+    #[case("PROPFIND", "/dandisets/", Some("0"), Some(TimeoutClass::Listing))]
+    #[case("PROPFIND", "/dandisets/", None, Some(TimeoutClass::Stream))]
+    #[case("OPTIONS", "/dandisets/", None, Some(TimeoutClass::Listing))]
+    #[case("OPTIONS", "*", None, None)]
+    #[tokio::test]
+    async fn test_timeout_class(
+        #[case] method: &str,
+        #[case] path: &str,
+        #[case] depth: Option<&str>,
+        #[case] class: Option<TimeoutClass>,
+    ) {
+        let mut req = Request::builder().method(method).uri(path);
+        if let Some(d) = depth {
+            req = req.header("Depth", d);
+        }
+        let req = req.body(Body::empty()).unwrap();
+        let trailing_slash = req.uri().path().ends_with('/');
+        let dr = req
+            .extract_with_state::<DavRequest, _, _>(&PathConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(dr.timeout_class(trailing_slash), class);
+    }
+
+    #[tokio::test]
+    async fn test_body_deadline_met() {
+        let resp = Body::from("Hello, world!").into_response();
+        let resp = with_body_deadline(resp, Instant::now() + Duration::from_secs(5));
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"Hello, world!");
+    }
+
+    #[tokio::test]
+    async fn test_body_deadline_exceeded() {
+        let chunks = stream::once(async { Ok::<_, std::convert::Infallible>("Hello, ") })
+            .chain(stream::pending());
+        let resp = Body::from_stream(chunks).into_response();
+        let resp = with_body_deadline(resp, Instant::now() + Duration::from_millis(50));
+        let r = axum::body::to_bytes(resp.into_body(), usize::MAX).await;
+        assert!(r.is_err());
+    }
+
+    #[rstest]
+    #[case("render", RootBehavior::Render)]
+    #[case("404", RootBehavior::NotFound)]
+    #[case("/dandisets/", RootBehavior::Redirect("/dandisets/".into()))]
+    #[case(
+        "https://docs.dandiarchive.org",
+        RootBehavior::Redirect("https://docs.dandiarchive.org/".into())
+    )]
+    #[case(
+        "https://www.example.com/docs/dandidav.html",
+        RootBehavior::Redirect("https://www.example.com/docs/dandidav.html".into())
+    )]
+    fn test_parse_root_behavior(#[case] s: &str, #[case] behavior: RootBehavior) {
+        assert_eq!(s.parse::<RootBehavior>(), Ok(behavior));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("dandisets")]
+    #[case("//www.example.com/")]
+    #[case("ftp://www.example.com/")]
+    #[case("Render")]
+    fn test_parse_bad_root_behavior(#[case] s: &str) {
+        assert!(s.parse::<RootBehavior>().is_err());
+    }
+
     #[test]
     fn test_href_from_path() {
         let s = "/~cleesh/foo bar/baz_quux.gnusto/red&green?blue";
@@ -240,4 +2433,29 @@ mod tests {
         let dt = datetime!(1994-11-06 03:49:37 -5);
         assert_eq!(format_modifieddate(dt), "Sun, 06 Nov 1994 08:49:37 GMT");
     }
+
+    #[rstest]
+    #[case(&[], &[], "000027", true)]
+    #[case(&["000027"], &[], "000027", true)]
+    #[case(&["000027"], &[], "000108", false)]
+    #[case(&["0001*"], &[], "000108", true)]
+    #[case(&["0001*", "000027"], &[], "000027", true)]
+    #[case(&[], &["000027"], "000027", false)]
+    #[case(&[], &["000027"], "000108", true)]
+    #[case(&["0001*"], &["000108"], "000108", false)]
+    #[case(&["0001*"], &["000108"], "000123", true)]
+    fn test_dandiset_filter(
+        #[case] include: &[&str],
+        #[case] exclude: &[&str],
+        #[case] dandiset_id: &str,
+        #[case] allowed: bool,
+    ) {
+        let patterns = |pats: &[&str]| {
+            pats.iter()
+                .map(|p| glob::Pattern::new(p).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let filter = DandisetFilter::new(patterns(include), patterns(exclude));
+        assert_eq!(filter.allows(&dandiset_id.parse().unwrap()), allowed);
+    }
 }
@@ -0,0 +1,199 @@
+//! Recursive enumeration of resource hierarchies
+use super::{DandiDav, DavError, DavPath, DavResource, DavResourceWithChildren};
+use futures_util::{future::BoxFuture, stream::FuturesUnordered, FutureExt, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Limits on the traversal performed by [`DandiDav::walk()`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) struct WalkLimits {
+    /// Only enumerate resources at most this many levels beneath the starting
+    /// resource.  A value of 0 only enumerates the starting resource itself.
+    /// `None` means there is no limit.
+    pub(super) max_depth: Option<u32>,
+
+    /// Fail with [`DavError::TooManyResources`] if more than this many
+    /// resources (including the starting resource) would be enumerated.
+    /// `None` means there is no limit.
+    pub(super) max_resources: Option<usize>,
+
+    /// The maximum number of collections to fetch the children of at once
+    pub(super) concurrency: usize,
+}
+
+impl WalkLimits {
+    /// Return limits for enumerating resources at most `max_depth` levels
+    /// beneath the starting resource, with no limit on the number of
+    /// resources
+    pub(super) fn depth(max_depth: u32) -> WalkLimits {
+        WalkLimits {
+            max_depth: Some(max_depth),
+            max_resources: None,
+            concurrency: 1,
+        }
+    }
+}
+
+impl DandiDav {
+    /// Return a [`futures_util::Stream`] that yields the resource at `path`
+    /// followed by its descendants (subject to `limits`).
+    ///
+    /// The starting resource is always yielded first, followed by its
+    /// immediate children in the order returned by the backend; the order in
+    /// which deeper descendants are yielded is unspecified.
+    ///
+    /// Each descendant collection is fetched via a fresh call to
+    /// [`DandiDav::get_resource_with_children()`] with up to
+    /// `limits.concurrency` such calls in progress at once.  If any call
+    /// fails, the error is yielded and the stream ends.  Dropping the stream
+    /// cancels any calls in progress.
+    ///
+    /// The stream holds a reference to the `DandiDav` instance rather than
+    /// borrowing it so that it can be used as the body of a streaming
+    /// response.
+    pub(super) fn walk(
+        self: &Arc<Self>,
+        path: &DavPath,
+        limits: WalkLimits,
+    ) -> impl Stream<Item = Result<DavResource, DavError>> + Send + 'static {
+        let mut state = Walk {
+            dav: Arc::clone(self),
+            limits,
+            queue: VecDeque::from([(path.clone(), 0)]),
+            in_flight: FuturesUnordered::new(),
+            ready: VecDeque::new(),
+            yielded: 0,
+            done: false,
+        };
+        futures_util::stream::poll_fn(move |cx| state.poll_next(cx))
+    }
+}
+
+/// The state of a stream returned by [`DandiDav::walk()`]
+struct Walk {
+    /// The `DandiDav` instance doing the fetching
+    dav: Arc<DandiDav>,
+
+    /// Limits on the traversal
+    limits: WalkLimits,
+
+    /// Collections whose children have yet to be fetched, along with their
+    /// depths relative to the starting resource
+    queue: VecDeque<(DavPath, u32)>,
+
+    /// Fetches currently in progress
+    in_flight:
+        FuturesUnordered<BoxFuture<'static, (u32, Result<DavResourceWithChildren, DavError>)>>,
+
+    /// Resources that have been fetched but not yet yielded
+    ready: VecDeque<DavResource>,
+
+    /// The number of resources yielded so far
+    yielded: usize,
+
+    /// Set once the stream has yielded an error
+    done: bool,
+}
+
+impl Walk {
+    /// Advance the traversal, returning the next resource (or error) if one
+    /// is available
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<DavResource, DavError>>> {
+        loop {
+            if self.done {
+                return Poll::Ready(None);
+            }
+            if let Some(res) = self.ready.pop_front() {
+                if let Some(limit) = self.limits.max_resources {
+                    if self.yielded >= limit {
+                        self.done = true;
+                        return Poll::Ready(Some(Err(DavError::TooManyResources { limit })));
+                    }
+                }
+                self.yielded = self.yielded.saturating_add(1);
+                return Poll::Ready(Some(Ok(res)));
+            }
+            while self.in_flight.len() < self.limits.concurrency.max(1) {
+                let Some((path, depth)) = self.queue.pop_front() else {
+                    break;
+                };
+                self.in_flight.push(self.fetch(path, depth));
+            }
+            match self.in_flight.poll_next_unpin(cx) {
+                Poll::Ready(Some((depth, Ok(res)))) => self.enqueue(depth, res),
+                Poll::Ready(Some((_, Err(e)))) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Return a future that fetches the resource at `path` (which is at depth
+    /// `depth`) and, if it is to be expanded, its children
+    fn fetch(
+        &self,
+        path: DavPath,
+        depth: u32,
+    ) -> BoxFuture<'static, (u32, Result<DavResourceWithChildren, DavError>)> {
+        let dav = Arc::clone(&self.dav);
+        let expand = self.expands(depth);
+        async move {
+            let r = if expand {
+                dav.get_resource_with_children(&path).await
+            } else {
+                dav.get_resource(&path)
+                    .await
+                    .map(DavResourceWithChildren::from)
+            };
+            (depth, r)
+        }
+        .boxed()
+    }
+
+    /// Add the results of a fetch at depth `depth` to the queue of resources
+    /// to yield and the queue of collections to expand
+    fn enqueue(&mut self, depth: u32, res: DavResourceWithChildren) {
+        let children = match res {
+            DavResourceWithChildren::Collection { col, children } => {
+                if depth == 0 {
+                    self.ready.push_back(DavResource::Collection(col));
+                }
+                children
+            }
+            DavResourceWithChildren::Item(item) => {
+                if depth == 0 {
+                    self.ready.push_back(DavResource::Item(item));
+                }
+                return;
+            }
+        };
+        let child_depth = depth.saturating_add(1);
+        for child in children {
+            if let DavResource::Collection(ref col) = child {
+                if self.expands(child_depth) {
+                    if let Some(p) =
+                        DavPath::from_dir_path(col.path.as_ref(), &self.dav.path_config)
+                    {
+                        self.queue.push_back((p, child_depth));
+                    } else {
+                        tracing::warn!(
+                            path = ?col.path,
+                            "Could not parse path of collection back into a DavPath; not descending into it",
+                        );
+                    }
+                }
+            }
+            self.ready.push_back(child);
+        }
+    }
+
+    /// Returns `true` if the children of collections at depth `depth` should
+    /// be enumerated
+    fn expands(&self, depth: u32) -> bool {
+        self.limits.max_depth.map_or(true, |m| depth < m)
+    }
+}
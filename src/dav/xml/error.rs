@@ -0,0 +1,143 @@
+use super::multistatus::{ToXmlError, XmlWriter};
+use crate::consts::{DANDIDAV_XMLNS, DAV_XMLNS};
+use crate::dav::util::Href;
+
+/// A condition explaining why a request for a path that does not exist
+/// failed, serialized as the body of a `DAV:error` document.  Each condition
+/// names the deepest resource along the requested path that does exist and
+/// blocks the rest of the path from resolving.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(in crate::dav) enum ErrorCondition {
+    /// A leading portion of the requested path points to the blob asset at
+    /// `blob`, which cannot have any resources beneath it
+    PathUnderBlob { blob: Href },
+
+    /// A leading portion of the requested path points to the Zarr asset at
+    /// `zarr`, which does not contain an entry at the rest of the path
+    ZarrEntryNotFound { zarr: Href },
+}
+
+impl ErrorCondition {
+    /// Return the qualified name of the condition's element, which is in the
+    /// `dandidav` namespace
+    fn name(&self) -> &'static str {
+        match self {
+            ErrorCondition::PathUnderBlob { .. } => "dandidav:path-under-blob",
+            ErrorCondition::ZarrEntryNotFound { .. } => "dandidav:zarr-entry-not-found",
+        }
+    }
+
+    /// Return the path of the blocking resource
+    fn href(&self) -> &Href {
+        match self {
+            ErrorCondition::PathUnderBlob { blob } => blob,
+            ErrorCondition::ZarrEntryNotFound { zarr } => zarr,
+        }
+    }
+
+    pub(in crate::dav) fn to_xml(&self) -> Result<String, ToXmlError> {
+        let mut writer = XmlWriter::new();
+        writer.start_tag_ns_prefixed("error", DAV_XMLNS, "dandidav", DANDIDAV_XMLNS)?;
+        writer.start_tag(self.name())?;
+        writer.text_tag("href", self.href().as_ref())?;
+        writer.end_tag()?;
+        writer.end_tag()?;
+        let mut s = writer.into_string()?;
+        s.push('\n');
+        Ok(s)
+    }
+}
+
+/// A precondition from RFC 4918 or RFC 6578 that a request violated,
+/// serialized as the body of a `DAV:error` document
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(in crate::dav) enum Precondition {
+    /// The request was for a report that the resource does not support
+    SupportedReport,
+
+    /// The sync token in a `sync-collection` report request was not issued
+    /// by `dandidav` for the resource or has been forgotten
+    ValidSyncToken,
+
+    /// Responding to a `sync-collection` report request would report on more
+    /// members than the client-specified limit
+    NumberOfMatchesWithinLimits,
+}
+
+impl Precondition {
+    /// Return the name of the precondition's element, which is in the `DAV:`
+    /// namespace
+    fn name(self) -> &'static str {
+        match self {
+            Precondition::SupportedReport => "supported-report",
+            Precondition::ValidSyncToken => "valid-sync-token",
+            Precondition::NumberOfMatchesWithinLimits => "number-of-matches-within-limits",
+        }
+    }
+
+    pub(in crate::dav) fn to_xml(self) -> Result<String, ToXmlError> {
+        let mut writer = XmlWriter::new();
+        writer.start_tag_ns("error", DAV_XMLNS)?;
+        writer.empty_tag(self.name())?;
+        writer.end_tag()?;
+        let mut s = writer.into_string()?;
+        s.push('\n');
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn path_under_blob_to_xml() {
+        let cond = ErrorCondition::PathUnderBlob {
+            blob: Href::from_path("/dandisets/000027/draft/sub-RAT123/sub-RAT123.nwb"),
+        };
+        assert_eq!(
+            cond.to_xml().unwrap(),
+            indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <error xmlns="DAV:" xmlns:dandidav="https://github.com/dandi/dandidav">
+                <dandidav:path-under-blob>
+                    <href>/dandisets/000027/draft/sub-RAT123/sub-RAT123.nwb</href>
+                </dandidav:path-under-blob>
+            </error>
+            "#}
+        );
+    }
+
+    #[test]
+    fn zarr_entry_not_found_to_xml() {
+        let cond = ErrorCondition::ZarrEntryNotFound {
+            zarr: Href::from_path("/dandisets/000108/draft/sample 1.zarr/"),
+        };
+        assert_eq!(
+            cond.to_xml().unwrap(),
+            indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <error xmlns="DAV:" xmlns:dandidav="https://github.com/dandi/dandidav">
+                <dandidav:zarr-entry-not-found>
+                    <href>/dandisets/000108/draft/sample%201.zarr/</href>
+                </dandidav:zarr-entry-not-found>
+            </error>
+            "#}
+        );
+    }
+
+    #[test]
+    fn valid_sync_token_to_xml() {
+        assert_eq!(
+            Precondition::ValidSyncToken.to_xml().unwrap(),
+            indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <error xmlns="DAV:">
+                <valid-sync-token />
+            </error>
+            "#}
+        );
+    }
+}
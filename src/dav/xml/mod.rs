@@ -1,13 +1,17 @@
 //! Working with WebDAV XML documents
+mod error;
 mod multistatus;
 mod propfind;
+mod report;
+pub(super) use self::error::*;
 pub(super) use self::multistatus::*;
 pub(super) use self::propfind::*;
-use crate::consts::DAV_XMLNS;
+pub(super) use self::report::*;
+use crate::consts::{DANDIDAV_XMLNS, DAV_XMLNS};
 use std::fmt;
 use xml::writer::Error as WriteError;
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub(in crate::dav) enum Property {
     CreationDate,
     DisplayName,
@@ -17,8 +21,29 @@ pub(in crate::dav) enum Property {
     GetETag,
     GetLastModified,
     ResourceType,
+    /// The "sync-token" property from RFC 6578, giving the current
+    /// synchronization state of a Dandiset version.  It is only reported
+    /// when explicitly requested.
+    SyncToken,
     //LockDiscovery,
     //SupportedLock,
+    /// `dandidav`'s custom "zarrversion" property, giving the Zarr format
+    /// version of a Zarr collection
+    ZarrVersion,
+    /// `dandidav`'s custom "checksum" property, giving the MD5 digest of a
+    /// Zarr entry's content
+    Checksum,
+    /// `dandidav`'s custom "versionid" property, giving the S3 version ID of
+    /// a Zarr entry
+    VersionId,
+    /// `dandidav`'s custom "assetcount" property, giving the number of assets
+    /// beneath a directory in a Dandiset version
+    AssetCount,
+    /// `dandidav`'s custom "listing-unstable" property, reported for the
+    /// requested resource in a `PROPFIND` response if its draft Dandiset
+    /// version kept changing while the resource's children were being
+    /// listed, in which case the listing may be inconsistent
+    ListingUnstable,
     Custom(Tag),
 }
 
@@ -36,6 +61,18 @@ impl Property {
         .into_iter()
     }
 
+    /// Iterate over the non-standard properties (in the `dandidav` namespace)
+    /// that `dandidav` can emit for some resource
+    pub(in crate::dav) fn iter_custom() -> impl Iterator<Item = Property> {
+        [
+            Property::ZarrVersion,
+            Property::Checksum,
+            Property::VersionId,
+            Property::AssetCount,
+        ]
+        .into_iter()
+    }
+
     fn write_xml(&self, writer: &mut XmlWriter, value: &PropValue) -> Result<(), WriteError> {
         match self {
             Property::CreationDate => writer.start_tag("creationdate")?,
@@ -45,6 +82,14 @@ impl Property {
             Property::GetETag => writer.start_tag("getetag")?,
             Property::GetLastModified => writer.start_tag("getlastmodified")?,
             Property::ResourceType => writer.start_tag("resourcetype")?,
+            Property::SyncToken => writer.start_tag("sync-token")?,
+            Property::ZarrVersion => writer.start_tag_ns("zarrversion", DANDIDAV_XMLNS)?,
+            Property::Checksum => writer.start_tag_ns("checksum", DANDIDAV_XMLNS)?,
+            Property::VersionId => writer.start_tag_ns("versionid", DANDIDAV_XMLNS)?,
+            Property::AssetCount => writer.start_tag_ns("assetcount", DANDIDAV_XMLNS)?,
+            Property::ListingUnstable => {
+                writer.start_tag_ns("listing-unstable", DANDIDAV_XMLNS)?;
+            }
             Property::Custom(tag) => writer.start_tag_ns(&tag.name, &tag.namespace)?,
         }
         value.write_xml(writer)?;
@@ -63,7 +108,17 @@ impl From<Tag> for Property {
             Some("getetag") => Property::GetETag,
             Some("getlastmodified") => Property::GetLastModified,
             Some("resourcetype") => Property::ResourceType,
-            _ => Property::Custom(tag),
+            Some("sync-token") => Property::SyncToken,
+            Some(_) => Property::Custom(tag),
+            None if tag.namespace == DANDIDAV_XMLNS => match &*tag.name {
+                "zarrversion" => Property::ZarrVersion,
+                "checksum" => Property::Checksum,
+                "versionid" => Property::VersionId,
+                "assetcount" => Property::AssetCount,
+                "listing-unstable" => Property::ListingUnstable,
+                _ => Property::Custom(tag),
+            },
+            None => Property::Custom(tag),
         }
     }
 }
@@ -102,7 +157,7 @@ impl From<i64> for PropValue {
     }
 }
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub(super) struct Tag {
     pub(super) name: String,
     pub(super) namespace: String,
@@ -4,10 +4,16 @@ use std::collections::BTreeMap;
 use thiserror::Error;
 use xml::writer::{events::XmlEvent, EmitterConfig, Error as WriteError, EventWriter};
 
+/// The status of a `propstat` element listing properties that were found
+pub(super) const PROPSTAT_OK: &str = "HTTP/1.1 200 OK";
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(in crate::dav) struct Multistatus {
     pub(in crate::dav) response: Vec<DavResponse>,
     //responsedescription
+    /// The collection's new sync token, for responses to `sync-collection`
+    /// reports
+    pub(in crate::dav) sync_token: Option<String>,
 }
 
 impl Multistatus {
@@ -17,6 +23,9 @@ impl Multistatus {
             for r in &self.response {
                 r.write_xml(writer)?;
             }
+            if let Some(ref token) = self.sync_token {
+                writer.text_tag("sync-token", token)?;
+            }
             Ok(())
         })?;
         let mut s = writer.into_string()?;
@@ -25,21 +34,87 @@ impl Multistatus {
     }
 }
 
+/// A serializer for `multistatus` documents whose `response` elements are
+/// supplied one at a time, allowing the document to be streamed to the client
+/// as it is generated.
+///
+/// The concatenation of the outputs of [`MultistatusWriter::start()`], each
+/// call to [`MultistatusWriter::write_response()`], and
+/// [`MultistatusWriter::finish()`] is the same as the output of
+/// [`Multistatus::to_xml()`] for the same responses.
+pub(in crate::dav) struct MultistatusWriter(XmlWriter);
+
+impl MultistatusWriter {
+    /// Begin a new `multistatus` document, returning the writer along with
+    /// the initial portion of the serialized document
+    pub(in crate::dav) fn start() -> Result<(MultistatusWriter, Vec<u8>), ToXmlError> {
+        let mut writer = XmlWriter::new();
+        writer.start_tag_ns("multistatus", DAV_XMLNS)?;
+        let output = writer.take_output();
+        Ok((MultistatusWriter(writer), output))
+    }
+
+    /// Serialize `response` as the next `response` element in the document
+    /// and return the newly-serialized portion of the document
+    pub(in crate::dav) fn write_response(
+        &mut self,
+        response: &DavResponse,
+    ) -> Result<Vec<u8>, ToXmlError> {
+        response.write_xml(&mut self.0)?;
+        Ok(self.0.take_output())
+    }
+
+    /// Close the document and return the final portion of it
+    pub(in crate::dav) fn finish(mut self) -> Result<Vec<u8>, ToXmlError> {
+        self.0.end_tag()?;
+        let mut output = self.0.take_output();
+        output.push(b'\n');
+        Ok(output)
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(in crate::dav) struct DavResponse {
     pub(in crate::dav) href: Href,
-    // TODO: RFC 4918 says <response> can contain (href*, status) as an
-    // alternative to propstat.  When does that apply?
     pub(in crate::dav) propstat: Vec<PropStat>,
+    /// The status of the resource as a whole, used instead of `propstat` to
+    /// report members removed from a collection in `sync-collection` reports
+    pub(in crate::dav) status: Option<String>,
     //error
     //responsedescription
     pub(in crate::dav) location: Option<Href>,
 }
 
 impl DavResponse {
+    /// Report `prop` as having the value `value` in the response's "200 OK"
+    /// `propstat` (creating it if necessary), regardless of whether the
+    /// property was requested.  If the property was previously reported as
+    /// missing, it is removed from the `propstat` for missing properties.
+    pub(in crate::dav) fn add_property(&mut self, prop: Property, value: PropValue) {
+        for p in &mut self.propstat {
+            p.prop.remove(&prop);
+        }
+        self.propstat
+            .retain(|p| !p.prop.is_empty() || p.status == PROPSTAT_OK);
+        if let Some(p) = self.propstat.iter_mut().find(|p| p.status == PROPSTAT_OK) {
+            p.prop.insert(prop, value);
+        } else {
+            self.propstat.insert(
+                0,
+                PropStat {
+                    prop: BTreeMap::from([(prop, value)]),
+                    status: PROPSTAT_OK.into(),
+                },
+            );
+        }
+    }
+
     fn write_xml(&self, writer: &mut XmlWriter) -> Result<(), WriteError> {
         writer.tag("response", |writer| {
             writer.text_tag("href", self.href.as_ref())?;
+            if let Some(ref status) = self.status {
+                writer.text_tag("status", status)?;
+            }
             for p in &self.propstat {
                 p.write_xml(writer)?;
             }
@@ -77,7 +152,7 @@ impl PropStat {
 pub(in crate::dav) struct XmlWriter(EventWriter<Vec<u8>>);
 
 impl XmlWriter {
-    fn new() -> Self {
+    pub(super) fn new() -> Self {
         XmlWriter(
             EmitterConfig::new()
                 .indent_string("    ")
@@ -87,7 +162,12 @@ impl XmlWriter {
         )
     }
 
-    fn into_string(self) -> Result<String, std::str::Utf8Error> {
+    /// Remove & return the output written so far
+    fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(self.0.inner_mut())
+    }
+
+    pub(super) fn into_string(self) -> Result<String, std::str::Utf8Error> {
         let buf = self.0.into_inner();
         String::from_utf8(buf).map_err(|e| e.utf8_error())
     }
@@ -120,6 +200,22 @@ impl XmlWriter {
         self.0.write(XmlEvent::start_element(name).default_ns(ns))
     }
 
+    /// Start a tag in the default namespace `ns` that also binds `prefix` to
+    /// the namespace `prefix_ns` for use by its descendants
+    pub(super) fn start_tag_ns_prefixed(
+        &mut self,
+        name: &str,
+        ns: &str,
+        prefix: &str,
+        prefix_ns: &str,
+    ) -> Result<(), WriteError> {
+        self.0.write(
+            XmlEvent::start_element(name)
+                .default_ns(ns)
+                .ns(prefix, prefix_ns),
+        )
+    }
+
     pub(super) fn end_tag(&mut self) -> Result<(), WriteError> {
         self.0.write(XmlEvent::end_element())
     }
@@ -134,7 +230,7 @@ impl XmlWriter {
         self.0.write(XmlEvent::characters(text))
     }
 
-    fn text_tag(&mut self, name: &str, text: &str) -> Result<(), WriteError> {
+    pub(super) fn text_tag(&mut self, name: &str, text: &str) -> Result<(), WriteError> {
         self.start_tag(name)?;
         self.text(text)?;
         self.end_tag()?;
@@ -170,6 +266,7 @@ mod tests {
                         ]),
                         status: "HTTP/1.1 200 OK".into(),
                     }],
+                    status: None,
                     location: None,
                 },
                 DavResponse {
@@ -198,6 +295,7 @@ mod tests {
                         ]),
                         status: "HTTP/1.1 200 OK".into(),
                     }],
+                    status: None,
                     location: None,
                 },
                 DavResponse {
@@ -222,6 +320,7 @@ mod tests {
                         ]),
                         status: "HTTP/1.1 307 TEMPORARY REDIRECT".into(),
                     }],
+                    status: None,
                     location: Some(
                         "https://www.example.com/data/quux.dat"
                             .parse::<HttpUrl>()
@@ -230,6 +329,7 @@ mod tests {
                     ),
                 },
             ],
+            sync_token: None,
         };
 
         assert_eq!(
@@ -285,4 +385,158 @@ mod tests {
         "#}
         );
     }
+
+    #[test]
+    fn multistatus_writer_matches_to_xml() {
+        let value = Multistatus {
+            response: vec![
+                DavResponse {
+                    href: Href::from_path("/foo/"),
+                    propstat: vec![PropStat {
+                        prop: BTreeMap::from([
+                            (Property::ResourceType, PropValue::Collection),
+                            (Property::DisplayName, PropValue::String("foo".into())),
+                        ]),
+                        status: "HTTP/1.1 200 OK".into(),
+                    }],
+                    status: None,
+                    location: None,
+                },
+                DavResponse {
+                    href: Href::from_path("/foo/bar.txt"),
+                    propstat: vec![PropStat {
+                        prop: BTreeMap::from([
+                            (Property::DisplayName, PropValue::String("bar.txt".into())),
+                            (Property::GetContentLength, PropValue::Int(42)),
+                            (Property::ResourceType, PropValue::Empty),
+                        ]),
+                        status: "HTTP/1.1 200 OK".into(),
+                    }],
+                    status: None,
+                    location: None,
+                },
+            ],
+            sync_token: None,
+        };
+        let (mut writer, mut output) = MultistatusWriter::start().unwrap();
+        for r in &value.response {
+            output.extend(writer.write_response(r).unwrap());
+        }
+        output.extend(writer.finish().unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), value.to_xml().unwrap());
+    }
+
+    #[test]
+    fn multistatus_writer_empty() {
+        let (writer, mut output) = MultistatusWriter::start().unwrap();
+        output.extend(writer.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            (Multistatus {
+                response: vec![],
+                sync_token: None,
+            })
+            .to_xml()
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn add_property_moves_missing() {
+        let mut response = DavResponse {
+            href: Href::from_path("/foo/"),
+            propstat: vec![PropStat {
+                prop: BTreeMap::from([(Property::ListingUnstable, PropValue::Empty)]),
+                status: "HTTP/1.1 404 NOT FOUND".into(),
+            }],
+            status: None,
+            location: None,
+        };
+        response.add_property(Property::ListingUnstable, PropValue::Empty);
+        assert_eq!(
+            response.propstat,
+            [PropStat {
+                prop: BTreeMap::from([(Property::ListingUnstable, PropValue::Empty)]),
+                status: "HTTP/1.1 200 OK".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn add_property_to_found() {
+        let mut response = DavResponse {
+            href: Href::from_path("/foo/"),
+            propstat: vec![
+                PropStat {
+                    prop: BTreeMap::from([(Property::ResourceType, PropValue::Collection)]),
+                    status: "HTTP/1.1 200 OK".into(),
+                },
+                PropStat {
+                    prop: BTreeMap::from([(Property::Checksum, PropValue::Empty)]),
+                    status: "HTTP/1.1 404 NOT FOUND".into(),
+                },
+            ],
+            status: None,
+            location: None,
+        };
+        response.add_property(Property::ListingUnstable, PropValue::Empty);
+        assert_eq!(response.propstat.len(), 2);
+        assert_eq!(
+            response.propstat[0].prop,
+            BTreeMap::from([
+                (Property::ResourceType, PropValue::Collection),
+                (Property::ListingUnstable, PropValue::Empty),
+            ])
+        );
+        assert_eq!(response.propstat[1].prop.len(), 1);
+    }
+
+    #[test]
+    fn sync_multistatus_to_xml() {
+        let value = Multistatus {
+            response: vec![
+                DavResponse {
+                    href: Href::from_path("/foo/bar.txt"),
+                    propstat: vec![PropStat {
+                        prop: BTreeMap::from([(
+                            Property::GetETag,
+                            PropValue::String(r#""0123456789abcdef""#.into()),
+                        )]),
+                        status: "HTTP/1.1 200 OK".into(),
+                    }],
+                    status: None,
+                    location: None,
+                },
+                DavResponse {
+                    href: Href::from_path("/foo/gone.txt"),
+                    propstat: Vec::new(),
+                    status: Some("HTTP/1.1 404 Not Found".into()),
+                    location: None,
+                },
+            ],
+            sync_token: Some("https://github.com/dandi/dandidav/sync/1700000000000000000".into()),
+        };
+        assert_eq!(
+            value.to_xml().unwrap(),
+            indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <multistatus xmlns="DAV:">
+                <response>
+                    <href>/foo/bar.txt</href>
+                    <propstat>
+                        <prop>
+                            <getetag>"0123456789abcdef"</getetag>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+                <response>
+                    <href>/foo/gone.txt</href>
+                    <status>HTTP/1.1 404 Not Found</status>
+                </response>
+                <sync-token>https://github.com/dandi/dandidav/sync/1700000000000000000</sync-token>
+            </multistatus>
+        "#}
+        );
+    }
 }
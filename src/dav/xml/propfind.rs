@@ -1,4 +1,4 @@
-use super::multistatus::{DavResponse, PropStat};
+use super::multistatus::{DavResponse, PropStat, PROPSTAT_OK};
 use super::{PropValue, Property, Tag};
 use crate::dav::types::HasProperties;
 use axum::{
@@ -13,7 +13,7 @@ use std::fmt;
 use thiserror::Error;
 use xml::reader::{Error as XmlError, ParserConfig2, XmlEvent};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub(in crate::dav) enum PropFind {
     AllProp { include: Vec<Property> },
     Prop(Vec<Property>),
@@ -72,7 +72,7 @@ impl PropFind {
                 }
             }
             PropFind::PropName => {
-                for prop in Property::iter_standard() {
+                for prop in Property::iter_standard().chain(res.custom_properties()) {
                     if res.property(&prop).is_some() {
                         found.insert(prop, PropValue::Empty);
                     }
@@ -83,7 +83,7 @@ impl PropFind {
         if !found.is_empty() || missing.is_empty() {
             propstat.push(PropStat {
                 prop: found,
-                status: "HTTP/1.1 200 OK".into(),
+                status: PROPSTAT_OK.into(),
             });
         }
         if !missing.is_empty() {
@@ -95,6 +95,7 @@ impl PropFind {
         DavResponse {
             href: res.href(),
             propstat,
+            status: None,
             // TODO: Should `location` be set to redirect URLs?
             location: None,
         }
@@ -335,6 +336,7 @@ pub(crate) enum PropFindError {
 mod tests {
     use super::*;
     use indoc::indoc;
+    use rstest::rstest;
 
     #[test]
     fn parse_prop() {
@@ -373,6 +375,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_prop_zarrversion() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <D:propfind xmlns:D="DAV:">
+                <D:prop xmlns:dd="https://github.com/dandi/dandidav">
+                    <D:displayname/>
+                    <dd:zarrversion/>
+                    <D:zarrversion/>
+                </D:prop>
+            </D:propfind>
+        "#};
+        let propfind = PropFind::from_xml(Bytes::from(s)).unwrap();
+        assert_eq!(
+            propfind,
+            PropFind::Prop(vec![
+                Property::DisplayName,
+                Property::ZarrVersion,
+                Property::Custom(Tag {
+                    namespace: "DAV:".into(),
+                    name: "zarrversion".into()
+                }),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_prop_checksum_versionid() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <D:propfind xmlns:D="DAV:">
+                <D:prop xmlns:dd="https://github.com/dandi/dandidav">
+                    <D:getetag/>
+                    <dd:checksum/>
+                    <dd:versionid/>
+                    <dd:sha256/>
+                </D:prop>
+            </D:propfind>
+        "#};
+        let propfind = PropFind::from_xml(Bytes::from(s)).unwrap();
+        assert_eq!(
+            propfind,
+            PropFind::Prop(vec![
+                Property::GetETag,
+                Property::Checksum,
+                Property::VersionId,
+                Property::Custom(Tag {
+                    namespace: "https://github.com/dandi/dandidav".into(),
+                    name: "sha256".into()
+                }),
+            ])
+        );
+    }
+
     #[test]
     fn parse_prop_std() {
         let s = indoc! {r#"
@@ -565,4 +621,104 @@ mod tests {
         let r = PropFind::from_xml(Bytes::from(s));
         assert!(r.is_err());
     }
+
+    /// `PROPFIND` bodies as sent by real-world WebDAV clients
+    static CLIENT_BODIES: [(&str, &str); 6] = [
+        (
+            "allprop",
+            include_str!("../../testdata/propfind/allprop.xml"),
+        ),
+        (
+            "cyberduck",
+            include_str!("../../testdata/propfind/cyberduck.xml"),
+        ),
+        ("davfs2", include_str!("../../testdata/propfind/davfs2.xml")),
+        (
+            "macos-finder",
+            include_str!("../../testdata/propfind/macos-finder.xml"),
+        ),
+        (
+            "propname",
+            include_str!("../../testdata/propfind/propname.xml"),
+        ),
+        (
+            "windows",
+            include_str!("../../testdata/propfind/windows.xml"),
+        ),
+    ];
+
+    /// Fragments spliced into client bodies by `parse_mutated_bodies()`
+    static SPLICES: [&[u8]; 8] = [
+        b"<",
+        b"/>",
+        b"</D:prop>",
+        b"<D:include/>",
+        b"<!DOCTYPE propfind [<!ENTITY a \"&a;\">]>",
+        b"&a;",
+        b"<![CDATA[x]]>",
+        b"\xFF\xFE",
+    ];
+
+    #[test]
+    fn parse_client_bodies() {
+        for (client, body) in CLIENT_BODIES {
+            let r = PropFind::from_xml(Bytes::from(body));
+            assert!(r.is_ok(), "Failed to parse body from {client}: {r:?}");
+        }
+    }
+
+    /// Feed the parser a deterministic stream of mutations of the client
+    /// bodies (truncations, byte substitutions, and splices) and check that
+    /// it never panics
+    #[test]
+    fn parse_mutated_bodies() {
+        // xorshift64, so that failures are reproducible
+        let mut seed = 0x2545_F491_4F6C_DD1D_u64;
+        let mut rand = move |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            usize::try_from(seed % (bound as u64)).unwrap()
+        };
+        for (_, body) in CLIENT_BODIES {
+            let body = body.as_bytes();
+            for len in 0..body.len() {
+                let _ = PropFind::from_xml(Bytes::copy_from_slice(&body[..len]));
+            }
+            for _ in 0..2000 {
+                let mut mutated = body.to_vec();
+                for _ in 0..=rand(4) {
+                    let i = rand(mutated.len());
+                    match rand(3) {
+                        0 => mutated[i] = u8::try_from(rand(256)).unwrap(),
+                        1 => {
+                            mutated.remove(i);
+                        }
+                        _ => {
+                            let splice = SPLICES[rand(SPLICES.len())];
+                            mutated.splice(i..i, splice.iter().copied());
+                        }
+                    }
+                }
+                let _ = PropFind::from_xml(Bytes::from(mutated));
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(b"<propfind")]
+    #[case(b"<D:propfind xmlns:D=\"DAV:\"><D:prop>")]
+    #[case(b"<propfind xmlns=\"DAV:\"><prop><getetag/></prop></propfind><x/>")]
+    #[case(b"\xFF\xFE<\x00p\x00")]
+    #[case(b"not XML at all")]
+    #[tokio::test]
+    async fn extract_invalid_body(#[case] body: &'static [u8]) {
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = PropFind::from_request(req, &()).await.unwrap_err();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
 }
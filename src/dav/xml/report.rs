@@ -0,0 +1,291 @@
+use super::{Property, Tag};
+use bytes::{Buf, Bytes};
+use thiserror::Error;
+use xml::reader::{Error as XmlError, ParserConfig2, XmlEvent};
+
+/// A parsed `sync-collection` `REPORT` request body, as specified in RFC
+/// 6578, section 3.2
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(in crate::dav) struct SyncCollection {
+    /// The sync token returned by a previous report, or `None` for an initial
+    /// synchronization (i.e., if the `sync-token` element was empty)
+    pub(in crate::dav) sync_token: Option<String>,
+
+    /// Whether to report on just the immediate members of the collection or
+    /// on all of its descendants
+    pub(in crate::dav) level: SyncLevel,
+
+    /// The maximum number of changed members to report, if any
+    pub(in crate::dav) limit: Option<u64>,
+
+    /// The properties to report for each changed member
+    pub(in crate::dav) props: Vec<Property>,
+}
+
+impl SyncCollection {
+    /// Parse a `REPORT` request body.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ReportError::Unsupported`] if the body is a well-formed
+    /// request for a report other than `sync-collection`, and other
+    /// [`ReportError`]s if the body is not a valid `sync-collection` request
+    pub(in crate::dav) fn from_xml(blob: Bytes) -> Result<SyncCollection, ReportError> {
+        let reader = ParserConfig2::new()
+            .ignore_invalid_encoding_declarations(false)
+            .allow_multiple_root_elements(false)
+            .trim_whitespace(true)
+            .create_reader(blob.reader());
+        let mut parser = SyncCollectionParser::default();
+        for event in reader {
+            use XmlEvent::*;
+            match event? {
+                StartElement { name, .. } => {
+                    parser.start_tag(Tag::new(name.local_name, name.namespace))?;
+                }
+                EndElement { .. } => {
+                    parser.stack.pop();
+                }
+                Characters(s) => parser.text(&s)?,
+                StartDocument { .. } | EndDocument | Comment(..) | Whitespace(..) => (),
+                ProcessingInstruction { .. } | CData(..) => {
+                    return Err(ReportError::UnexpectedContent)
+                }
+            }
+        }
+        parser.finish()
+    }
+}
+
+/// The value of a `sync-level` element
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub(in crate::dav) enum SyncLevel {
+    /// Only the immediate members of the collection are reported on
+    One,
+
+    /// All resources beneath the collection are reported on
+    Infinite,
+}
+
+/// The elements of a `sync-collection` request body that
+/// [`SyncCollectionParser`] tracks
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Element {
+    SyncCollection,
+    SyncToken,
+    SyncLevel,
+    Limit,
+    NResults,
+    Prop,
+    Property,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct SyncCollectionParser {
+    stack: Vec<Element>,
+    seen_root: bool,
+    sync_token: Option<String>,
+    level: Option<String>,
+    nresults: Option<String>,
+    props: Vec<Property>,
+}
+
+impl SyncCollectionParser {
+    fn start_tag(&mut self, tag: Tag) -> Result<(), ReportError> {
+        let elem = match (self.stack.last(), tag.dav_name()) {
+            (None, Some("sync-collection")) if !self.seen_root => {
+                self.seen_root = true;
+                Element::SyncCollection
+            }
+            (None, _) => return Err(ReportError::Unsupported(tag.to_string())),
+            (Some(Element::SyncCollection), Some("sync-token")) => Element::SyncToken,
+            (Some(Element::SyncCollection), Some("sync-level")) => Element::SyncLevel,
+            (Some(Element::SyncCollection), Some("limit")) => Element::Limit,
+            (Some(Element::SyncCollection), Some("prop")) => Element::Prop,
+            (Some(Element::Limit), Some("nresults")) => Element::NResults,
+            (Some(Element::Prop), _) => {
+                self.props.push(Property::from(tag));
+                Element::Property
+            }
+            _ => return Err(ReportError::UnexpectedTag(tag.to_string())),
+        };
+        self.stack.push(elem);
+        Ok(())
+    }
+
+    fn text(&mut self, s: &str) -> Result<(), ReportError> {
+        let target = match self.stack.last() {
+            Some(Element::SyncToken) => &mut self.sync_token,
+            Some(Element::SyncLevel) => &mut self.level,
+            Some(Element::NResults) => &mut self.nresults,
+            _ => return Err(ReportError::UnexpectedContent),
+        };
+        target.get_or_insert_with(String::new).push_str(s);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<SyncCollection, ReportError> {
+        if !self.seen_root || !self.stack.is_empty() {
+            return Err(ReportError::Incomplete);
+        }
+        let level = match self.level.as_deref().map(str::trim) {
+            Some("1") => SyncLevel::One,
+            Some("infinite" | "infinity") => SyncLevel::Infinite,
+            Some(other) => return Err(ReportError::InvalidLevel(other.to_owned())),
+            None => return Err(ReportError::MissingLevel),
+        };
+        let limit = match self.nresults {
+            Some(s) => Some(
+                s.trim()
+                    .parse::<u64>()
+                    .map_err(|_| ReportError::InvalidLimit(s))?,
+            ),
+            None => None,
+        };
+        Ok(SyncCollection {
+            sync_token: self
+                .sync_token
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty()),
+            level,
+            limit,
+            props: self.props,
+        })
+    }
+}
+
+/// Error returned when a `REPORT` request body cannot be parsed as a
+/// `sync-collection` request
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub(crate) enum ReportError {
+    #[error("failed to parse XML")]
+    Xml(#[from] XmlError),
+    #[error("XML contained unexpected content")]
+    UnexpectedContent,
+    #[error("unsupported report {0}")]
+    Unsupported(String),
+    #[error("unexpected tag {0} in <sync-collection> document")]
+    UnexpectedTag(String),
+    #[error("document ended before <sync-collection> was complete")]
+    Incomplete,
+    #[error("<sync-collection> lacks a <sync-level>")]
+    MissingLevel,
+    #[error("invalid <sync-level> value {0:?}")]
+    InvalidLevel(String),
+    #[error("invalid <nresults> value {0:?}")]
+    InvalidLimit(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use indoc::indoc;
+
+    #[test]
+    fn parse_initial() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <D:sync-collection xmlns:D="DAV:">
+                <D:sync-token/>
+                <D:sync-level>1</D:sync-level>
+                <D:prop>
+                    <D:getetag/>
+                    <D:getcontentlength/>
+                </D:prop>
+            </D:sync-collection>
+        "#};
+        assert_eq!(
+            SyncCollection::from_xml(Bytes::from(s)).unwrap(),
+            SyncCollection {
+                sync_token: None,
+                level: SyncLevel::One,
+                limit: None,
+                props: vec![Property::GetETag, Property::GetContentLength],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_token_limit() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <sync-collection xmlns="DAV:">
+                <sync-token>https://github.com/dandi/dandidav/sync/1700000000000000000</sync-token>
+                <sync-level>infinite</sync-level>
+                <limit><nresults>100</nresults></limit>
+                <prop/>
+            </sync-collection>
+        "#};
+        assert_eq!(
+            SyncCollection::from_xml(Bytes::from(s)).unwrap(),
+            SyncCollection {
+                sync_token: Some(
+                    "https://github.com/dandi/dandidav/sync/1700000000000000000".into()
+                ),
+                level: SyncLevel::Infinite,
+                limit: Some(100),
+                props: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_unsupported() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <D:expand-property xmlns:D="DAV:"/>
+        "#};
+        assert_matches!(
+            SyncCollection::from_xml(Bytes::from(s)),
+            Err(ReportError::Unsupported(tag)) => {
+                assert_eq!(tag, "{DAV:}expand-property");
+            }
+        );
+    }
+
+    #[test]
+    fn parse_missing_level() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <D:sync-collection xmlns:D="DAV:">
+                <D:sync-token/>
+                <D:prop><D:getetag/></D:prop>
+            </D:sync-collection>
+        "#};
+        assert_eq!(
+            SyncCollection::from_xml(Bytes::from(s)),
+            Err(ReportError::MissingLevel)
+        );
+    }
+
+    #[test]
+    fn parse_invalid_level() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <D:sync-collection xmlns:D="DAV:">
+                <D:sync-token/>
+                <D:sync-level>2</D:sync-level>
+            </D:sync-collection>
+        "#};
+        assert_eq!(
+            SyncCollection::from_xml(Bytes::from(s)),
+            Err(ReportError::InvalidLevel("2".into()))
+        );
+    }
+
+    #[test]
+    fn parse_unexpected_tag() {
+        let s = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <D:sync-collection xmlns:D="DAV:">
+                <D:sync-level>1</D:sync-level>
+                <D:allprop/>
+            </D:sync-collection>
+        "#};
+        assert_eq!(
+            SyncCollection::from_xml(Bytes::from(s)),
+            Err(ReportError::UnexpectedTag("{DAV:}allprop".into()))
+        );
+    }
+}
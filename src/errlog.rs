@@ -0,0 +1,291 @@
+//! In-memory record of recent errors for inspection by operators
+use crate::consts::{ERROR_LOG_MESSAGE_LIMIT, JSON_CONTENT_TYPE};
+use crate::dav::{ErrorClass, ErrorDetails};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        response::Response,
+    },
+    middleware::Next,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+/// The request header from which request IDs are taken, as set by Heroku's
+/// router and many reverse proxies
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// A record of the most recent errors that occurred while handling requests,
+/// served as JSON at `/admin/errors` so that operators can investigate
+/// reports from users without searching through logs.
+///
+/// Only the latest `capacity` errors are kept.  Errors classified as "not
+/// found" are not recorded, as they are routine and would otherwise crowd out
+/// more interesting errors.  As the endpoint is not authenticated, the paths &
+/// messages of errors for requests that carried credentials (which may concern
+/// embargoed Dandisets) are not recorded.
+#[derive(Debug)]
+pub(crate) struct ErrorLog {
+    capacity: usize,
+    events: Mutex<VecDeque<ErrorEvent>>,
+}
+
+impl ErrorLog {
+    pub(crate) fn new(capacity: usize) -> ErrorLog {
+        ErrorLog {
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Record `event`, discarding the oldest recorded event if the log is
+    /// full
+    fn record(&self, event: ErrorEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.lock();
+        while events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// Return the recorded events, most recent first
+    fn events(&self) -> Vec<ErrorEvent> {
+        self.lock().iter().rev().cloned().collect()
+    }
+
+    /// Return a response to a request for `/admin/errors` listing the
+    /// recorded events as a JSON array, most recent first
+    pub(crate) fn to_response(&self) -> Response<Body> {
+        let body =
+            serde_json::to_string(&self.events()).expect("serializing ErrorEvents should not fail");
+        // Note: This response should not have WebDAV headers (DAV, Allow)
+        ([(CONTENT_TYPE, JSON_CONTENT_TYPE)], body).into_response()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<ErrorEvent>> {
+        self.events
+            .lock()
+            .expect("ErrorLog mutex should not be poisoned")
+    }
+}
+
+/// A single error recorded in an [`ErrorLog`]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct ErrorEvent {
+    /// The time at which the response was returned
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+
+    /// The value of the request's `X-Request-ID` header, if any
+    request_id: Option<String>,
+
+    /// The request method
+    method: String,
+
+    /// The request path, or `None` if the request carried credentials
+    path: Option<String>,
+
+    /// The classification of the error
+    class: ErrorClass,
+
+    /// The status code of the response
+    status: u16,
+
+    /// The error message, truncated to [`ERROR_LOG_MESSAGE_LIMIT`]
+    /// characters, or `None` if the request carried credentials
+    message: Option<String>,
+}
+
+/// Record each response marked as an error by an [`ErrorDetails`] extension
+/// in the given [`ErrorLog`].
+///
+/// This middleware should be applied outside of `handle_head` so that `HEAD`
+/// requests are recorded with their actual method.
+pub(crate) async fn record_errors(
+    State(log): State<Arc<ErrorLog>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(ToOwned::to_owned);
+    let method = request.method().to_string();
+    let redact = request.headers().contains_key(AUTHORIZATION);
+    let path = (!redact).then(|| request.uri().path().to_owned());
+    let resp = next.run(request).await;
+    if let Some(details) = resp.extensions().get::<ErrorDetails>() {
+        if details.class != ErrorClass::NotFound {
+            log.record(ErrorEvent {
+                timestamp: OffsetDateTime::now_utc(),
+                request_id,
+                method,
+                path,
+                class: details.class,
+                status: resp.status().as_u16(),
+                message: (!redact).then(|| truncate(&details.message, ERROR_LOG_MESSAGE_LIMIT)),
+            });
+        }
+    }
+    resp
+}
+
+/// If `s` is longer than `limit` characters, return its first `limit`
+/// characters followed by an ellipsis; otherwise, return `s` unchanged
+fn truncate(s: &str, limit: usize) -> String {
+    match s.char_indices().nth(limit) {
+        Some((i, _)) => format!("{}…", &s[..i]),
+        None => s.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, middleware, routing::get, Router};
+    use rstest::rstest;
+    use time::macros::datetime;
+    use tower::ServiceExt;
+
+    fn event(path: &str) -> ErrorEvent {
+        ErrorEvent {
+            timestamp: datetime!(2026-10-16 12:34:56 UTC),
+            request_id: None,
+            method: "GET".into(),
+            path: Some(path.into()),
+            class: ErrorClass::BadGateway,
+            status: 502,
+            message: Some("Something went wrong".into()),
+        }
+    }
+
+    #[rstest]
+    #[case("", 3, "")]
+    #[case("foo", 3, "foo")]
+    #[case("foobar", 3, "foo…")]
+    #[case("héllo wörld", 7, "héllo w…")]
+    fn test_truncate(#[case] s: &str, #[case] limit: usize, #[case] truncated: &str) {
+        assert_eq!(truncate(s, limit), truncated);
+    }
+
+    #[test]
+    fn keeps_latest() {
+        let log = ErrorLog::new(2);
+        log.record(event("/a"));
+        log.record(event("/b"));
+        log.record(event("/c"));
+        let paths = log
+            .events()
+            .into_iter()
+            .filter_map(|e| e.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/c", "/b"]);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let log = ErrorLog::new(0);
+        log.record(event("/a"));
+        assert!(log.events().is_empty());
+    }
+
+    #[test]
+    fn serialize_event() {
+        let mut e = event("/dandisets/000027/draft/");
+        e.request_id = Some("abc123".into());
+        assert_eq!(
+            serde_json::to_string(&e).unwrap(),
+            r#"{"timestamp":"2026-10-16T12:34:56Z","request_id":"abc123","method":"GET","path":"/dandisets/000027/draft/","class":"bad_gateway","status":502,"message":"Something went wrong"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn records_errors() {
+        let log = Arc::new(ErrorLog::new(10));
+        let failure = |class: ErrorClass, status: StatusCode| {
+            let mut resp = (status, "Error").into_response();
+            resp.extensions_mut().insert(ErrorDetails {
+                class,
+                message: "x".repeat(ERROR_LOG_MESSAGE_LIMIT + 10),
+            });
+            resp
+        };
+        let app = Router::new()
+            .route("/", get(|| async { "Hello" }))
+            .route(
+                "/missing",
+                get(move || async move { failure(ErrorClass::NotFound, StatusCode::NOT_FOUND) }),
+            )
+            .route(
+                "/broken",
+                get(
+                    move || async move { failure(ErrorClass::BadGateway, StatusCode::BAD_GATEWAY) },
+                ),
+            )
+            .layer(middleware::from_fn_with_state(
+                Arc::clone(&log),
+                record_errors,
+            ));
+        for path in ["/", "/missing", "/broken"] {
+            let request = Request::get(path)
+                .header("X-Request-ID", format!("req-{path}"))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+        let events = log.events();
+        assert_eq!(events.len(), 1);
+        let e = &events[0];
+        assert_eq!(e.request_id.as_deref(), Some("req-/broken"));
+        assert_eq!(e.method, "GET");
+        assert_eq!(e.path.as_deref(), Some("/broken"));
+        assert_eq!(e.class, ErrorClass::BadGateway);
+        assert_eq!(e.status, 502);
+        let message = e.message.as_deref().unwrap();
+        assert_eq!(message.chars().count(), ERROR_LOG_MESSAGE_LIMIT + 1);
+        assert!(message.ends_with('…'));
+    }
+
+    #[tokio::test]
+    async fn redacts_authenticated() {
+        let log = Arc::new(ErrorLog::new(10));
+        let app = Router::new()
+            .route(
+                "/dandisets/000123/draft/secret.nwb",
+                get(|| async {
+                    let mut resp = (StatusCode::FORBIDDEN, "Error").into_response();
+                    resp.extensions_mut().insert(ErrorDetails {
+                        class: ErrorClass::Forbidden,
+                        message: "cannot access secret.nwb".into(),
+                    });
+                    resp
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                Arc::clone(&log),
+                record_errors,
+            ));
+        let request = Request::get("/dandisets/000123/draft/secret.nwb")
+            .header("Authorization", "token 0123456789abcdef")
+            .header("X-Request-ID", "abc123")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap();
+        let events = log.events();
+        assert_eq!(events.len(), 1);
+        let e = &events[0];
+        assert_eq!(e.request_id.as_deref(), Some("abc123"));
+        assert_eq!(e.status, 403);
+        assert_eq!(e.path, None);
+        assert_eq!(e.message, None);
+    }
+}
@@ -0,0 +1,250 @@
+//! Liveness & readiness checks for load balancers and the like
+use crate::about::UpstreamVersions;
+use crate::consts::{JSON_CONTENT_TYPE, READINESS_CHECK_TIMEOUT};
+use crate::dav::DandiDav;
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, response::Response, StatusCode},
+    response::IntoResponse,
+};
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Respond to a request for `/healthz`.  This always succeeds as long as the
+/// server is able to handle requests at all.
+pub(crate) fn liveness() -> Response<Body> {
+    (StatusCode::OK, "OK\n").into_response()
+}
+
+/// Respond to a request for `/readyz` by checking whether the primary Archive
+/// instance's API and the Zarr manifest tree are reachable.  If either check fails or takes
+/// longer than [`READINESS_CHECK_TIMEOUT`], a 503 response is returned.  In
+/// all cases, the response body is a JSON document describing the results of
+/// the checks.
+///
+/// If the checks succeed but the Archive has most recently reported a
+/// metadata schema version with an unknown major version (as recorded in
+/// `upstream`), the report's status is "degraded", though a 200 response is
+/// still returned.
+pub(crate) async fn readiness(dav: &DandiDav, upstream: &UpstreamVersions) -> Response<Body> {
+    let (archive_api, zarr_manifests) = tokio::join!(
+        run_check(dav.primary_dandi().check(), READINESS_CHECK_TIMEOUT),
+        run_check(dav.zarrman.check(), READINESS_CHECK_TIMEOUT),
+    );
+    let api_schema = SchemaReport {
+        known: !upstream.schema_unknown(),
+        version: upstream.schema_version(),
+    };
+    let report = ReadinessReport::new(archive_api, zarr_manifests, api_schema);
+    let status = match report.status {
+        ReadinessStatus::Ready => StatusCode::OK,
+        ReadinessStatus::Degraded => {
+            tracing::warn!(
+                ?report,
+                "Archive reports an unknown metadata schema version"
+            );
+            StatusCode::OK
+        }
+        ReadinessStatus::Unavailable => {
+            tracing::warn!(?report, "Readiness check failed");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+    let body =
+        serde_json::to_string(&report).expect("serializing a ReadinessReport should not fail");
+    (status, [(CONTENT_TYPE, JSON_CONTENT_TYPE)], body).into_response()
+}
+
+/// The results of the checks performed by [`readiness()`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct ReadinessReport {
+    /// The overall status
+    status: ReadinessStatus,
+
+    /// The result of checking the Archive API
+    archive_api: CheckResult,
+
+    /// The result of checking the Zarr manifest tree
+    zarr_manifests: CheckResult,
+
+    /// The metadata schema version most recently reported by the Archive
+    api_schema: SchemaReport,
+}
+
+impl ReadinessReport {
+    fn new(
+        archive_api: CheckResult,
+        zarr_manifests: CheckResult,
+        api_schema: SchemaReport,
+    ) -> ReadinessReport {
+        let status = if !(archive_api.ok && zarr_manifests.ok) {
+            ReadinessStatus::Unavailable
+        } else if !api_schema.known {
+            ReadinessStatus::Degraded
+        } else {
+            ReadinessStatus::Ready
+        };
+        ReadinessReport {
+            status,
+            archive_api,
+            zarr_manifests,
+            api_schema,
+        }
+    }
+}
+
+/// The overall status reported by [`readiness()`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ReadinessStatus {
+    /// All checks succeeded
+    Ready,
+
+    /// All checks succeeded, but the Archive reports a metadata schema
+    /// version that `dandidav` is not known to work with
+    Degraded,
+
+    /// One or more checks failed
+    Unavailable,
+}
+
+/// The metadata schema version reported by the Archive
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct SchemaReport {
+    /// `false` iff the Archive's schema version has an unknown major version
+    known: bool,
+
+    /// The schema version, if known
+    version: Option<String>,
+}
+
+/// The result of checking a single upstream service
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct CheckResult {
+    /// `true` iff the check succeeded
+    ok: bool,
+
+    /// How long the check took, in seconds
+    duration_secs: f64,
+
+    /// A description of the error that occurred, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Await `check`, failing if it does not complete within `timeout`
+async fn run_check<F, E>(check: F, timeout: Duration) -> CheckResult
+where
+    F: Future<Output = Result<(), E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    let error = match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(format!("{:#}", anyhow::Error::new(e))),
+        Err(_) => Some(format!("timed out after {}", DisplaySecs(timeout))),
+    };
+    CheckResult {
+        ok: error.is_none(),
+        duration_secs: start.elapsed().as_secs_f64(),
+        error,
+    }
+}
+
+/// Display a [`Duration`] as a number of seconds
+struct DisplaySecs(Duration);
+
+impl fmt::Display for DisplaySecs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s", self.0.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[error("upstream is down")]
+    struct Down;
+
+    fn check(ok: bool) -> CheckResult {
+        CheckResult {
+            ok,
+            duration_secs: 0.1,
+            error: (!ok).then(|| "upstream is down".to_owned()),
+        }
+    }
+
+    #[rstest]
+    #[case(true, true, true, ReadinessStatus::Ready)]
+    #[case(true, true, false, ReadinessStatus::Degraded)]
+    #[case(false, true, true, ReadinessStatus::Unavailable)]
+    #[case(true, false, false, ReadinessStatus::Unavailable)]
+    fn report_status(
+        #[case] archive_ok: bool,
+        #[case] zarrs_ok: bool,
+        #[case] schema_known: bool,
+        #[case] status: ReadinessStatus,
+    ) {
+        let report = ReadinessReport::new(
+            check(archive_ok),
+            check(zarrs_ok),
+            SchemaReport {
+                known: schema_known,
+                version: Some("0.6.9".to_owned()),
+            },
+        );
+        assert_eq!(report.status, status);
+    }
+
+    #[test]
+    fn serialize_report() {
+        let report = ReadinessReport::new(
+            check(true),
+            check(true),
+            SchemaReport {
+                known: false,
+                version: Some("1.0.0".to_owned()),
+            },
+        );
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "status": "degraded",
+                "archive_api": {"ok": true, "duration_secs": 0.1},
+                "zarr_manifests": {"ok": true, "duration_secs": 0.1},
+                "api_schema": {"known": false, "version": "1.0.0"},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn check_ok() {
+        let r = run_check(async { Ok::<_, Down>(()) }, Duration::from_secs(1)).await;
+        assert!(r.ok);
+        assert_eq!(r.error, None);
+    }
+
+    #[tokio::test]
+    async fn check_err() {
+        let r = run_check(async { Err(Down) }, Duration::from_secs(1)).await;
+        assert!(!r.ok);
+        assert_eq!(r.error.as_deref(), Some("upstream is down"));
+    }
+
+    #[tokio::test]
+    async fn check_timeout() {
+        let r = run_check(
+            std::future::pending::<Result<(), Down>>(),
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(!r.ok);
+        assert_eq!(r.error.as_deref(), Some("timed out after 0.01s"));
+    }
+}
@@ -1,9 +1,19 @@
 //! HTTP utilities
-use crate::consts::USER_AGENT;
+use crate::consts::{
+    DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS, DEFAULT_CIRCUIT_BREAKER_THRESHOLD,
+    DEFAULT_MAX_REDIRECTS, DEFAULT_MAX_RETRIES, DEFAULT_POOL_IDLE_TIMEOUT_SECS,
+    DEFAULT_RETRY_BACKOFF_MS, DEFAULT_RETRY_BUDGET_PERCENT, DEFAULT_TCP_KEEPALIVE_SECS,
+    MAX_RETRY_BACKOFF, RETRY_BUDGET_RESERVE, USER_AGENT,
+};
 use crate::dav::ErrorClass;
-use reqwest::{Method, Request, Response, StatusCode};
+use crate::metrics::metrics;
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, LOCATION},
+    Method, Request, Response, StatusCode,
+};
 use reqwest_middleware::{Middleware, Next};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
 use serde::{
     de::{DeserializeOwned, Deserializer, Error as _},
     Deserialize,
@@ -11,38 +21,363 @@ use serde::{
 use std::fmt;
 use std::future::Future;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tracing::Instrument;
 use url::Url;
 
-/// An HTTP client that logs all requests and retries failed requests
+/// Settings for the connections made by [`Client`]s
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct HttpConfig {
+    /// The maximum number of redirects to follow per request
+    pub(crate) max_redirects: usize,
+
+    /// Whether to speak HTTP/2 to all servers without first negotiating it.
+    /// When false, HTTP/2 is still used with HTTPS servers that offer it.
+    pub(crate) http2_prior_knowledge: bool,
+
+    /// How long an idle connection is kept in the pool before being closed.
+    /// `None` keeps idle connections open indefinitely.
+    pub(crate) pool_idle_timeout: Option<Duration>,
+
+    /// The maximum number of idle connections kept in the pool per host.
+    /// `None` places no limit on the number.
+    pub(crate) pool_max_idle_per_host: Option<usize>,
+
+    /// The interval at which TCP keepalive probes are sent on open
+    /// connections.  `None` disables TCP keepalive.
+    pub(crate) tcp_keepalive: Option<Duration>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> HttpConfig {
+        HttpConfig {
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            http2_prior_knowledge: false,
+            pool_idle_timeout: Some(Duration::from_secs(DEFAULT_POOL_IDLE_TIMEOUT_SECS)),
+            pool_max_idle_per_host: None,
+            tcp_keepalive: Some(Duration::from_secs(DEFAULT_TCP_KEEPALIVE_SECS)),
+        }
+    }
+}
+
+/// Settings for retrying failed requests made by a [`Client`] and for the
+/// circuit breaker that makes the client fail fast during sustained outages
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct RetryPolicy {
+    /// The maximum number of times to retry an idempotent request that failed
+    /// due to a network error or a 408, 429, or 5xx response
+    pub(crate) max_retries: u32,
+
+    /// The delay before the first retry of a request.  The delay doubles with
+    /// each subsequent retry, up to [`MAX_RETRY_BACKOFF`].
+    pub(crate) backoff: Duration,
+
+    /// The number of retries, as a percentage of the number of requests
+    /// sent, that the client may make once it has used up its initial
+    /// reserve of [`RETRY_BUDGET_RESERVE`] retries
+    pub(crate) budget_percent: u32,
+
+    /// The number of consecutive requests that must fail (after any retries)
+    /// for the circuit breaker to open.  0 disables the circuit breaker.
+    pub(crate) breaker_threshold: u32,
+
+    /// How long the circuit breaker stays open, failing all requests without
+    /// sending them, before letting a single trial request through
+    pub(crate) breaker_cooldown: Duration,
+}
+
+impl RetryPolicy {
+    /// Return the delay before the retry of a request that follows `retries`
+    /// previous retries
+    fn backoff(&self, retries: u32) -> Duration {
+        self.backoff
+            .checked_mul(2u32.saturating_pow(retries))
+            .map_or(MAX_RETRY_BACKOFF, |d| d.min(MAX_RETRY_BACKOFF))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff: Duration::from_millis(DEFAULT_RETRY_BACKOFF_MS),
+            budget_percent: DEFAULT_RETRY_BUDGET_PERCENT,
+            breaker_threshold: DEFAULT_CIRCUIT_BREAKER_THRESHOLD,
+            breaker_cooldown: Duration::from_secs(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS),
+        }
+    }
+}
+
+/// The retry budget and circuit breaker of a [`Client`], shared between all
+/// clones of the client
+#[derive(Debug)]
+struct RetryState {
+    policy: RetryPolicy,
+
+    /// The number of retries that may currently be made, in hundredths of a
+    /// retry
+    budget: Mutex<u64>,
+
+    breaker: Mutex<Breaker>,
+}
+
+/// The state of a [`Client`]'s circuit breaker
+#[derive(Debug, Default)]
+struct Breaker {
+    /// The number of consecutive requests that have failed
+    failures: u32,
+
+    /// If the breaker is open, the time at which it will next let a trial
+    /// request through
+    open_until: Option<Instant>,
+}
+
+impl RetryState {
+    /// The maximum value of `budget`
+    const BUDGET_CAPACITY: u64 = 100 * RETRY_BUDGET_RESERVE;
+
+    fn new(policy: RetryPolicy) -> RetryState {
+        RetryState {
+            policy,
+            budget: Mutex::new(Self::BUDGET_CAPACITY),
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    /// Add the fraction of a retry earned by sending a request to the retry
+    /// budget
+    fn deposit(&self) {
+        let mut budget = self
+            .budget
+            .lock()
+            .expect("retry budget mutex should not be poisoned");
+        *budget = budget
+            .saturating_add(u64::from(self.policy.budget_percent))
+            .min(Self::BUDGET_CAPACITY);
+    }
+
+    /// Take one retry from the retry budget, returning `false` if the budget
+    /// is used up
+    fn withdraw(&self) -> bool {
+        let mut budget = self
+            .budget
+            .lock()
+            .expect("retry budget mutex should not be poisoned");
+        if *budget >= 100 {
+            *budget -= 100;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `true` if the circuit breaker permits sending a request.  Once
+    /// an open breaker's cooldown has elapsed, a single request is permitted
+    /// as a trial, and further requests are refused for another cooldown
+    /// period unless the trial succeeds.
+    fn admit(&self) -> bool {
+        if self.policy.breaker_threshold == 0 {
+            return true;
+        }
+        let mut breaker = self
+            .breaker
+            .lock()
+            .expect("circuit breaker mutex should not be poisoned");
+        match breaker.open_until {
+            Some(t) if Instant::now() < t => false,
+            Some(_) => {
+                breaker.open_until = Some(Instant::now() + self.policy.breaker_cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Record whether a request to `url` failed (after any retries) due to a
+    /// network error or an error response from the server, opening the
+    /// circuit breaker if too many consecutive requests have failed
+    fn record(&self, url: &HttpUrl, failed: bool) {
+        if self.policy.breaker_threshold == 0 {
+            return;
+        }
+        let mut breaker = self
+            .breaker
+            .lock()
+            .expect("circuit breaker mutex should not be poisoned");
+        if failed {
+            breaker.failures = breaker.failures.saturating_add(1);
+            if breaker.failures >= self.policy.breaker_threshold {
+                if breaker.open_until.is_none() {
+                    tracing::warn!(
+                        url = %url,
+                        failures = breaker.failures,
+                        cooldown = ?self.policy.breaker_cooldown,
+                        "Too many consecutive requests failed; opening circuit breaker",
+                    );
+                }
+                breaker.open_until = Some(Instant::now() + self.policy.breaker_cooldown);
+            }
+        } else {
+            if breaker.open_until.is_some() {
+                tracing::info!(url = %url, "Trial request succeeded; closing circuit breaker");
+            }
+            *breaker = Breaker::default();
+        }
+    }
+}
+
+/// Returns `true` if a response with the given status indicates a failure
+/// that may not recur if the request is retried
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// An HTTP client that logs all requests, retries failed idempotent requests
+/// within a retry budget, stops sending requests for a while after too many
+/// consecutive failures, and refuses to follow overly long or circular chains
+/// of redirects
 #[derive(Debug, Clone)]
-pub(crate) struct Client(reqwest_middleware::ClientWithMiddleware);
+pub(crate) struct Client {
+    /// The transport used to actually send requests
+    transport: Arc<dyn HttpTransport>,
+
+    /// The retry policy, retry budget, and circuit breaker shared by all
+    /// clones of the client
+    retry: Arc<RetryState>,
+
+    /// The URL base & default credentials (if any) for authenticated requests
+    auth: Option<Authorization>,
+
+    /// Credentials to use for this client's requests in place of the default
+    /// credentials in `auth`
+    credentials: Option<HeaderValue>,
+}
 
 impl Client {
-    /// Construct a new client
+    /// Construct a new client that retries failed requests according to
+    /// `retry` and whose connections & redirect handling are configured by
+    /// `config`.  If `auth` is non-`None`, credentials are attached to
+    /// requests to URLs that it applies to, and requests for such URLs are not
+    /// allowed to be redirected to a different origin.
     ///
     /// # Errors
     ///
-    /// Returns an error if construction of the inner `reqwest::Client` fails
-    pub(crate) fn new() -> Result<Client, BuildClientError> {
-        let retry_policy = ExponentialBackoff::builder()
-            .base(2)
-            .build_with_max_retries(4);
-        let client = reqwest_middleware::ClientBuilder::new(
-            reqwest::ClientBuilder::new()
-                .user_agent(USER_AGENT)
-                .build()?,
-        )
-        .with(SimpleReqwestLogger)
-        // Retry network errors and responses of 408, 429, or 5xx up to four
-        // times, sleeping for 1s/2s/4s/8s before each retry attempt.
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build();
-        Ok(Client(client))
+    /// Returns an error if construction of an inner `reqwest::Client` fails
+    pub(crate) fn new(
+        retry: RetryPolicy,
+        config: HttpConfig,
+        auth: Option<Authorization>,
+    ) -> Result<Client, BuildClientError> {
+        Ok(Client {
+            transport: Arc::new(ReqwestTransport::new(config, auth.clone())?),
+            auth,
+            credentials: None,
+            retry: Arc::new(RetryState::new(retry)),
+        })
+    }
+
+    /// Return a copy of the client that sends its requests via `transport`
+    #[cfg(test)]
+    pub(crate) fn with_transport(&self, transport: Arc<dyn HttpTransport>) -> Client {
+        Client {
+            transport,
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of the client that uses the given `Authorization` header
+    /// value in place of the default credentials for URLs that the client's
+    /// [`Authorization`] applies to
+    pub(crate) fn with_credentials(&self, mut value: HeaderValue) -> Client {
+        value.set_sensitive(true);
+        Client {
+            credentials: Some(value),
+            ..self.clone()
+        }
+    }
+
+    /// Returns `true` if requests made by this client to `url` are
+    /// authenticated
+    pub(crate) fn authenticates(&self, url: &Url) -> bool {
+        self.auth.as_ref().is_some_and(|auth| {
+            auth.applies_to(url) && (self.credentials.is_some() || auth.value.is_some())
+        })
+    }
+
+    /// Send a request with the given method & additional headers to the
+    /// given URL via the transport, following redirects iff
+    /// `follow_redirects` is true, and converting 401, 404, and other 4xx &
+    /// 5xx responses into [`HttpError::Unauthorized`],
+    /// [`HttpError::NotFound`], and [`HttpError::Status`], respectively.
+    ///
+    /// If the request is idempotent and fails due to a network error or a
+    /// 408, 429, or 5xx response, it is retried with exponential backoff as
+    /// long as the retry policy and retry budget allow.  If the circuit
+    /// breaker is open, [`HttpError::CircuitOpen`] is returned without
+    /// sending anything.
+    async fn send(
+        &self,
+        method: Method,
+        url: HttpUrl,
+        headers: HeaderMap,
+        follow_redirects: bool,
+    ) -> Result<HttpResponse, HttpError> {
+        if !self.retry.admit() {
+            return Err(HttpError::CircuitOpen { url });
+        }
+        self.retry.deposit();
+        let mut retries = 0;
+        let r = loop {
+            let r = self
+                .transport
+                .send(TransportRequest {
+                    method: method.clone(),
+                    url: url.clone(),
+                    headers: headers.clone(),
+                    follow_redirects,
+                    credentials: self.credentials.clone(),
+                })
+                .await;
+            let failure = match r {
+                Ok(ref resp) if is_transient(resp.status) => format!("status {}", resp.status),
+                Err(ref e @ HttpError::Send { .. }) => format!("error: {e}"),
+                _ => {
+                    self.retry.record(&url, false);
+                    break r;
+                }
+            };
+            if !method.is_idempotent()
+                || retries >= self.retry.policy.max_retries
+                || !self.retry.withdraw()
+            {
+                self.retry.record(&url, true);
+                break r;
+            }
+            let delay = self.retry.policy.backoff(retries);
+            retries += 1;
+            tracing::warn!(
+                url = %url,
+                retry = retries,
+                "Request failed with {failure}; retrying in {delay:?}",
+            );
+            tokio::time::sleep(delay).await;
+        }?;
+        match r.status {
+            StatusCode::NOT_FOUND => Err(HttpError::NotFound { url }),
+            StatusCode::UNAUTHORIZED => Err(HttpError::Unauthorized { url }),
+            status if status.is_client_error() || status.is_server_error() => {
+                Err(HttpError::Status { url, status })
+            }
+            _ => Ok(r),
+        }
     }
 
-    /// Perform an HTTP request with the given method to the given URL
+    /// Perform an HTTP request with the given method to the given URL,
+    /// retrying it as described for [`Client::send()`]
     ///
     /// # Errors
     ///
@@ -52,21 +387,8 @@ impl Client {
         &self,
         method: Method,
         url: HttpUrl,
-    ) -> Result<Response, HttpError> {
-        let r = self
-            .0
-            .request(method, Url::from(url.clone()))
-            .send()
-            .await
-            .map_err(|source| HttpError::Send {
-                url: url.clone(),
-                source,
-            })?;
-        if r.status() == StatusCode::NOT_FOUND {
-            return Err(HttpError::NotFound { url });
-        }
-        r.error_for_status()
-            .map_err(|source| HttpError::Status { url, source })
+    ) -> Result<HttpResponse, HttpError> {
+        self.send(method, url, HeaderMap::new(), true).await
     }
 
     /// Perform a `HEAD` request to the given URL
@@ -75,7 +397,7 @@ impl Client {
     ///
     /// If sending the request fails or the response has a 4xx or 5xx status,
     /// an error is returned.
-    pub(crate) async fn head(&self, url: HttpUrl) -> Result<Response, HttpError> {
+    pub(crate) async fn head(&self, url: HttpUrl) -> Result<HttpResponse, HttpError> {
         self.request(Method::HEAD, url).await
     }
 
@@ -85,10 +407,51 @@ impl Client {
     ///
     /// If sending the request fails or the response has a 4xx or 5xx status,
     /// an error is returned.
-    pub(crate) async fn get(&self, url: HttpUrl) -> Result<Response, HttpError> {
+    pub(crate) async fn get(&self, url: HttpUrl) -> Result<HttpResponse, HttpError> {
         self.request(Method::GET, url).await
     }
 
+    /// Perform a `GET` request to the given URL with the given additional
+    /// request headers
+    ///
+    /// # Errors
+    ///
+    /// If sending the request fails or the response has a 4xx or 5xx status,
+    /// an error is returned.
+    pub(crate) async fn get_with_headers(
+        &self,
+        url: HttpUrl,
+        headers: HeaderMap,
+    ) -> Result<HttpResponse, HttpError> {
+        self.send(Method::GET, url, headers, true).await
+    }
+
+    /// Perform a `GET` request to the given URL without following any
+    /// redirects and return the URL that the response redirects to
+    ///
+    /// # Errors
+    ///
+    /// If sending the request fails, the response has a 4xx or 5xx status, or
+    /// the response is not a redirect with a valid `Location` header, an
+    /// error is returned.
+    pub(crate) async fn get_redirect_location(&self, url: HttpUrl) -> Result<HttpUrl, HttpError> {
+        let r = self
+            .send(Method::GET, url.clone(), HeaderMap::new(), false)
+            .await?;
+        if !r.status.is_redirection() {
+            return Err(HttpError::NotRedirected {
+                url,
+                status: r.status,
+            });
+        }
+        r.headers
+            .get(LOCATION)
+            .and_then(|hv| hv.to_str().ok())
+            .and_then(|loc| r.url.join(loc).ok())
+            .and_then(|u| u.as_str().parse::<HttpUrl>().ok())
+            .ok_or(HttpError::BadLocation { url })
+    }
+
     /// Perform a `GET` request to the given URL and deserialize the response
     /// body as JSON into `T`
     ///
@@ -106,16 +469,430 @@ impl Client {
         // simplifying the Future's use by the Paginate stream.
         let client = self.clone();
         async move {
-            client
-                .get(url.clone())
-                .await?
-                .json::<T>()
-                .await
-                .map_err(move |source| HttpError::Deserialize { url, source })
+            let body = client.get(url.clone()).await?.bytes().await?;
+            serde_json::from_slice::<T>(&body)
+                .map_err(move |source| HttpError::ParseJson { url, source })
+        }
+    }
+}
+
+/// A means of sending individual HTTP requests, used by [`Client`] to do
+/// the actual communicating.  The implementation used outside of tests is
+/// [`ReqwestTransport`]; tests can substitute in-memory fakes.
+#[async_trait::async_trait]
+pub(crate) trait HttpTransport: fmt::Debug + Send + Sync {
+    /// Send the given request and return the response.  Responses with 4xx
+    /// or 5xx statuses are returned as `Ok`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request could not be sent or a chain of
+    /// redirects was rejected
+    async fn send(&self, req: TransportRequest) -> Result<HttpResponse, HttpError>;
+}
+
+/// A request to be sent by an [`HttpTransport`]
+#[derive(Clone, Debug)]
+pub(crate) struct TransportRequest {
+    /// The request method
+    pub(crate) method: Method,
+
+    /// The URL to send the request to
+    pub(crate) url: HttpUrl,
+
+    /// Headers to send with the request in addition to those set by the
+    /// transport
+    pub(crate) headers: HeaderMap,
+
+    /// Whether to follow any redirects returned in response to the request
+    pub(crate) follow_redirects: bool,
+
+    /// The `Authorization` header value to use in place of the transport's
+    /// default credentials for URLs that require authentication, if any
+    pub(crate) credentials: Option<HeaderValue>,
+}
+
+/// A response body, streamed in chunks as it is received
+pub(crate) type ResponseBody = BoxStream<'static, Result<Bytes, HttpError>>;
+
+/// A response received by an [`HttpTransport`].  The body is not read until
+/// it is consumed, so callers that do not need the whole body at once can
+/// process it in bounded memory.
+pub(crate) struct HttpResponse {
+    /// The URL of the response; if redirects were followed, this is the URL
+    /// at the end of the chain
+    pub(crate) url: Url,
+
+    /// The response's status code
+    pub(crate) status: StatusCode,
+
+    /// The response's headers
+    pub(crate) headers: HeaderMap,
+
+    /// The response body
+    pub(crate) body: ResponseBody,
+}
+
+impl HttpResponse {
+    /// Read the remainder of the response body into memory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the body fails
+    pub(crate) async fn bytes(self) -> Result<Bytes, HttpError> {
+        let mut body = self.body;
+        let Some(first) = body.try_next().await? else {
+            return Ok(Bytes::new());
+        };
+        let Some(second) = body.try_next().await? else {
+            return Ok(first);
+        };
+        let mut buf = BytesMut::from(first);
+        buf.extend_from_slice(&second);
+        while let Some(chunk) = body.try_next().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf.freeze())
+    }
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("url", &self.url)
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An [`HttpTransport`] that sends requests with `reqwest`, logging all
+/// requests, attaching credentials, and enforcing a [`redirect_policy()`]
+#[derive(Clone, Debug)]
+pub(crate) struct ReqwestTransport {
+    /// The client used for requests that follow redirects
+    inner: reqwest_middleware::ClientWithMiddleware,
+
+    /// The client used for requests that do not follow redirects
+    no_follow: reqwest_middleware::ClientWithMiddleware,
+}
+
+impl ReqwestTransport {
+    /// Construct a new transport whose connections & redirect handling are
+    /// configured by `config`.  `auth` is as for [`Client::new()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if construction of an inner `reqwest::Client` fails
+    pub(crate) fn new(
+        config: HttpConfig,
+        auth: Option<Authorization>,
+    ) -> Result<ReqwestTransport, BuildClientError> {
+        Ok(ReqwestTransport {
+            inner: build_client(
+                redirect_policy(config.max_redirects, auth.clone()),
+                config,
+                auth.clone(),
+            )?,
+            no_follow: build_client(reqwest::redirect::Policy::none(), config, auth)?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpTransport for ReqwestTransport {
+    async fn send(&self, req: TransportRequest) -> Result<HttpResponse, HttpError> {
+        let client = if req.follow_redirects {
+            &self.inner
+        } else {
+            &self.no_follow
+        };
+        let url = req.url;
+        let mut builder = client
+            .request(req.method, Url::from(url.clone()))
+            .headers(req.headers);
+        if let Some(value) = req.credentials {
+            builder = builder.with_extension(Credentials(value));
+        }
+        let r = builder
+            .send()
+            .await
+            .map_err(|source| match find_redirect_error(&source) {
+                Some(e) => {
+                    tracing::warn!(
+                        url = %url,
+                        chain = ?e.chain().iter().map(Url::as_str).collect::<Vec<_>>(),
+                        "Refusing to follow redirects: {e}",
+                    );
+                    HttpError::Redirect {
+                        url: url.clone(),
+                        source: e.clone(),
+                    }
+                }
+                None => HttpError::Send {
+                    url: url.clone(),
+                    source,
+                },
+            })?;
+        let final_url = r.url().clone();
+        let status = r.status();
+        let headers = r.headers().clone();
+        let body = r
+            .bytes_stream()
+            .map_err(move |source| HttpError::Send {
+                url: url.clone(),
+                source: reqwest_middleware::Error::Reqwest(source),
+            })
+            .boxed();
+        Ok(HttpResponse {
+            url: final_url,
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// A request body streamed in chunks by an [`Uploader`]
+pub(crate) type UploadBody =
+    BoxStream<'static, Result<Bytes, Box<dyn std::error::Error + Send + Sync>>>;
+
+/// A means of sending `PUT` requests with streamed bodies, used to upload
+/// resources to external servers.  The implementation used outside of tests
+/// is [`ReqwestUploader`]; tests can substitute in-memory fakes.
+#[async_trait::async_trait]
+pub(crate) trait Uploader: fmt::Debug + Send + Sync {
+    /// Upload `body` to `url` in a `PUT` request with the given headers and
+    /// return the response's status code.  Redirects are not followed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request could not be sent (including if
+    /// `body` yields an error)
+    async fn put(
+        &self,
+        url: &HttpUrl,
+        headers: HeaderMap,
+        body: UploadBody,
+    ) -> Result<StatusCode, HttpError>;
+}
+
+/// An [`Uploader`] that sends requests with `reqwest`, logging all requests.
+/// As streamed bodies cannot be replayed, failed requests are not retried.
+#[derive(Clone, Debug)]
+pub(crate) struct ReqwestUploader(reqwest_middleware::ClientWithMiddleware);
+
+impl ReqwestUploader {
+    /// # Errors
+    ///
+    /// Returns an error if construction of an inner `reqwest::Client` fails
+    pub(crate) fn new() -> Result<ReqwestUploader, BuildClientError> {
+        let client = reqwest::ClientBuilder::new()
+            .user_agent(USER_AGENT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(ReqwestUploader(
+            reqwest_middleware::ClientBuilder::new(client)
+                .with(SimpleReqwestLogger)
+                .build(),
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Uploader for ReqwestUploader {
+    async fn put(
+        &self,
+        url: &HttpUrl,
+        headers: HeaderMap,
+        body: UploadBody,
+    ) -> Result<StatusCode, HttpError> {
+        let r = self
+            .0
+            .put(Url::from(url.clone()))
+            .headers(headers)
+            .body(reqwest::Body::wrap_stream(body))
+            .send()
+            .await
+            .map_err(|source| HttpError::Send {
+                url: url.clone(),
+                source,
+            })?;
+        Ok(r.status())
+    }
+}
+
+/// Construct a `reqwest` client with the given redirect policy, connection
+/// settings, logging, and (if `auth` is non-`None`) authorization middleware
+fn build_client(
+    policy: reqwest::redirect::Policy,
+    config: HttpConfig,
+    auth: Option<Authorization>,
+) -> Result<reqwest_middleware::ClientWithMiddleware, BuildClientError> {
+    let mut client = reqwest::ClientBuilder::new()
+        .user_agent(USER_AGENT)
+        .redirect(policy)
+        .pool_idle_timeout(config.pool_idle_timeout)
+        .pool_max_idle_per_host(config.pool_max_idle_per_host.unwrap_or(usize::MAX))
+        .tcp_keepalive(config.tcp_keepalive);
+    if config.http2_prior_knowledge {
+        client = client.http2_prior_knowledge();
+    }
+    let mut builder = reqwest_middleware::ClientBuilder::new(client.build()?);
+    if let Some(auth) = auth {
+        builder = builder.with(auth);
+    }
+    Ok(builder.with(SimpleReqwestLogger).build())
+}
+
+/// Return a redirect policy that follows at most `max_redirects` redirects
+/// and fails on any redirect to a URL that was already visited in the same
+/// chain.  If `api` is non-`None`, the policy also fails on any redirect of a
+/// request for a URL that `api` applies to (i.e., an API endpoint) to a
+/// different origin, as such redirects usually mean that the API base URL is
+/// not the canonical one and would otherwise lead to credentials being
+/// dropped or non-API responses being received.
+fn redirect_policy(max_redirects: usize, api: Option<Authorization>) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        // `previous()` contains the URL of the original request plus those
+        // of all redirects followed so far, so its length equals the number
+        // of the redirect currently being attempted.
+        let visited = attempt.previous().iter().any(|u| u == attempt.url());
+        let too_many = attempt.previous().len() > max_redirects;
+        let cross_origin = api.as_ref().and_then(|api| {
+            let original = attempt.previous().first()?;
+            (api.applies_to(original) && original.origin() != attempt.url().origin())
+                .then(|| api.canonical_base(original, attempt.url()))
+        });
+        if let Some(canonical) = cross_origin {
+            let mut chain = attempt.previous().to_vec();
+            chain.push(attempt.url().clone());
+            attempt.error(RedirectError::CrossOrigin { chain, canonical })
+        } else if visited || too_many {
+            let mut chain = attempt.previous().to_vec();
+            chain.push(attempt.url().clone());
+            let e = if visited {
+                RedirectError::Loop { chain }
+            } else {
+                RedirectError::TooMany {
+                    max: max_redirects,
+                    chain,
+                }
+            };
+            attempt.error(e)
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// Search the chain of sources of a request error for a [`RedirectError`]
+/// produced by [`redirect_policy()`]
+fn find_redirect_error(e: &reqwest_middleware::Error) -> Option<&RedirectError> {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(err) = source {
+        if let Some(re) = err.downcast_ref::<RedirectError>() {
+            return Some(re);
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Middleware for attaching credentials (as an `Authorization` header) to
+/// requests for URLs under a given base URL.
+///
+/// The credentials used are those in the request's [`Credentials`] extension,
+/// if any, or else the default credentials (if any) configured on the
+/// `Authorization`.
+///
+/// When a request is redirected to a different host, `reqwest` strips the
+/// header, so the credentials are never sent to, say, S3.
+#[derive(Clone)]
+pub(crate) struct Authorization {
+    /// The base URL, with a trailing slash appended if it did not already
+    /// have one
+    base: String,
+
+    /// The default value of the `Authorization` header
+    value: Option<HeaderValue>,
+}
+
+impl Authorization {
+    /// Construct an `Authorization` that attaches credentials to requests for
+    /// `base` and URLs beneath it, using `value` as the default
+    /// `Authorization` header value
+    pub(crate) fn new(base: &HttpUrl, mut value: Option<HeaderValue>) -> Authorization {
+        let mut base = base.as_str().to_owned();
+        if !base.ends_with('/') {
+            base.push('/');
+        }
+        if let Some(ref mut v) = value {
+            v.set_sensitive(true);
         }
+        Authorization { base, value }
+    }
+
+    /// Returns `true` if the credentials should be attached to a request for
+    /// `url`
+    pub(crate) fn applies_to(&self, url: &Url) -> bool {
+        let url = url.as_str();
+        url.starts_with(&self.base) || Some(url) == self.base.strip_suffix('/')
+    }
+
+    /// Given a request for `original` (a URL under the base URL) that was
+    /// redirected to `target`, return the URL that the base URL appears to
+    /// have been moved to, if `target`'s path ends with the same components
+    /// that follow the base URL in `original`'s path
+    fn canonical_base(&self, original: &Url, target: &Url) -> Option<Url> {
+        let rest = original.as_str().strip_prefix(&self.base).unwrap_or("");
+        let rest = rest.split(['?', '#']).next().unwrap_or("");
+        let path = target.path().strip_suffix(rest)?;
+        let mut canonical = target.clone();
+        canonical.set_path(path);
+        canonical.set_query(None);
+        canonical.set_fragment(None);
+        Some(canonical)
+    }
+}
+
+impl fmt::Debug for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authorization")
+            .field("base", &self.base)
+            .field("value", &self.value.as_ref().map(|_| "<redacted>"))
+            .finish()
     }
 }
 
+#[async_trait::async_trait]
+impl Middleware for Authorization {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut axum::http::Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if self.applies_to(req.url()) {
+            let value = extensions
+                .get::<Credentials>()
+                .map(|c| &c.0)
+                .or(self.value.as_ref());
+            if let Some(value) = value {
+                req.headers_mut()
+                    .entry(AUTHORIZATION)
+                    .or_insert_with(|| value.clone());
+            }
+        }
+        next.run(req, extensions).await
+    }
+}
+
+/// A request extension containing the `Authorization` header value that an
+/// [`Authorization`] middleware should use in place of its default
+#[derive(Clone)]
+struct Credentials(HeaderValue);
+
 /// Middleware for a `reqwest::Client` that adds logging of HTTP requests and
 /// their responses
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -131,9 +908,13 @@ impl Middleware for SimpleReqwestLogger {
     ) -> reqwest_middleware::Result<Response> {
         let span =
             tracing::debug_span!("outgoing-request", url = %req.url(), method = %req.method());
+        let host = req.url().host_str().unwrap_or_default().to_owned();
+        let method = req.method().to_string();
         async move {
             tracing::debug!("Making HTTP request");
+            let start = Instant::now();
             let r = next.run(req, extensions).await;
+            metrics().record_upstream(host, method, start.elapsed());
             match r {
                 Ok(ref resp) => tracing::debug!(status = %resp.status(), "Response received"),
                 Err(ref e) => tracing::debug!(error = ?e, "Failed to receive response"),
@@ -160,22 +941,41 @@ pub(crate) enum HttpError {
         source: reqwest_middleware::Error,
     },
 
+    /// Following the redirects returned in response to the request was
+    /// aborted because they went on too long or formed a loop
+    #[error("failed to follow redirects from {url}")]
+    Redirect { url: HttpUrl, source: RedirectError },
+
+    /// A request that was expected to be redirected was not
+    #[error("request to {url} was not redirected; got status {status}")]
+    NotRedirected { url: HttpUrl, status: StatusCode },
+
+    /// A redirect response lacked a valid `Location` header
+    #[error("redirect response from {url} lacked a valid Location header")]
+    BadLocation { url: HttpUrl },
+
     /// The server returned a 404 response
     #[error("no such resource: {url}")]
     NotFound { url: HttpUrl },
 
-    /// The server returned a 4xx or 5xx response other than 404
-    #[error("request to {url} returned error")]
-    Status {
-        url: HttpUrl,
-        source: reqwest::Error,
-    },
+    /// The server returned a 401 response
+    #[error("request to {url} was not authorized")]
+    Unauthorized { url: HttpUrl },
+
+    /// The server returned a 4xx or 5xx response other than 401 or 404
+    #[error("request to {url} returned error status {status}")]
+    Status { url: HttpUrl, status: StatusCode },
+
+    /// The request was not sent because the circuit breaker is open after too
+    /// many consecutive requests failed
+    #[error("not sending request to {url}, as too many recent requests to the server failed")]
+    CircuitOpen { url: HttpUrl },
 
     /// Deserializing the response body as JSON failed
     #[error("failed to deserialize response body from {url}")]
-    Deserialize {
+    ParseJson {
         url: HttpUrl,
-        source: reqwest::Error,
+        source: serde_json::Error,
     },
 }
 
@@ -184,13 +984,62 @@ impl HttpError {
     pub(crate) fn class(&self) -> ErrorClass {
         match self {
             HttpError::NotFound { .. } => ErrorClass::NotFound,
-            _ => ErrorClass::BadGateway,
+            HttpError::CircuitOpen { .. } => ErrorClass::GatewayTimeout,
+            HttpError::Unauthorized { .. } => ErrorClass::Unauthorized,
+            HttpError::Send { .. }
+            | HttpError::Redirect { .. }
+            | HttpError::NotRedirected { .. }
+            | HttpError::BadLocation { .. }
+            | HttpError::Status { .. }
+            | HttpError::ParseJson { .. } => ErrorClass::BadGateway,
         }
     }
 }
 
+/// Error produced when a chain of redirects is rejected by a [`Client`]
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub(crate) enum RedirectError {
+    /// A redirect pointed to a URL that was already visited
+    #[error("redirect loop detected")]
+    Loop { chain: Vec<Url> },
+
+    /// More than the maximum number of redirects were encountered
+    #[error("exceeded maximum of {max} redirects")]
+    TooMany { max: usize, chain: Vec<Url> },
+
+    /// A request for an API endpoint was redirected to a different origin.
+    /// `canonical` is the URL that the API appears to have moved to, if it
+    /// could be determined.
+    #[error("API request was redirected to a different origin{}", canonical_hint(.canonical.as_ref()))]
+    CrossOrigin {
+        chain: Vec<Url>,
+        canonical: Option<Url>,
+    },
+}
+
+impl RedirectError {
+    /// Return the URLs visited, starting with the URL of the original request
+    /// and ending with the URL of the rejected redirect
+    pub(crate) fn chain(&self) -> &[Url] {
+        match self {
+            RedirectError::Loop { chain }
+            | RedirectError::TooMany { chain, .. }
+            | RedirectError::CrossOrigin { chain, .. } => chain,
+        }
+    }
+}
+
+/// Format the suggestion included in the message of a
+/// [`RedirectError::CrossOrigin`] error
+fn canonical_hint(canonical: Option<&Url>) -> String {
+    match canonical {
+        Some(url) => format!("; if the API has moved to {url}, use that as the --api-url"),
+        None => String::new(),
+    }
+}
+
 /// A wrapper around [`url::Url`] that enforces a scheme of "http" or "https"
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub(crate) struct HttpUrl(Url);
 
 impl HttpUrl {
@@ -248,6 +1097,37 @@ impl HttpUrl {
         self
     }
 
+    /// Remove any empty segments from the URL's path, thereby collapsing runs
+    /// of repeated forward slashes and removing any trailing slash (except
+    /// when the path is just `/`)
+    pub(crate) fn normalize_path(&mut self) -> &mut Self {
+        let segments = self
+            .0
+            .path_segments()
+            .map(|ps| {
+                ps.filter(|s| !s.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        {
+            let Ok(mut ps) = self.0.path_segments_mut() else {
+                unreachable!("HTTP(S) URLs should always be able to be a base");
+            };
+            ps.clear().extend(segments);
+        }
+        self
+    }
+
+    /// Returns `true` if the last component of the URL's path (ignoring any
+    /// trailing slash) equals `segment`
+    pub(crate) fn path_ends_with(&self, segment: &str) -> bool {
+        self.0
+            .path_segments()
+            .and_then(|ps| ps.rev().find(|s| !s.is_empty()))
+            == Some(segment)
+    }
+
     /// Append `"{key}={value}"` (after percent-encoding) to the URL's query
     /// parameters
     pub(crate) fn append_query_param(&mut self, key: &str, value: &str) -> &mut Self {
@@ -304,9 +1184,229 @@ pub(crate) enum ParseHttpUrlError {
     Url(#[from] url::ParseError),
 }
 
+/// An in-memory [`HttpTransport`] for use in tests that replies to requests
+/// with canned responses keyed by URL (or with 404 responses for unknown
+/// URLs) and records the requests it receives.  Redirects are never
+/// followed.  If a canned response has an `ETag` header and a request's
+/// `If-None-Match` header matches it, a 304 response is returned instead.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct FakeTransport {
+    /// Canned responses, keyed by request URL
+    responses: std::collections::HashMap<String, (StatusCode, HeaderMap, Bytes)>,
+
+    /// Sequences of JSON response bodies to reply with in turn, keyed by
+    /// request URL.  These take precedence over `responses`.
+    sequences:
+        Mutex<std::collections::HashMap<String, std::collections::VecDeque<serde_json::Value>>>,
+
+    /// The methods & URLs of the requests received so far
+    requests: Mutex<Vec<(Method, String)>>,
+
+    /// The additional headers of the requests received so far
+    request_headers: Mutex<Vec<HeaderMap>>,
+}
+
+#[cfg(test)]
+impl FakeTransport {
+    pub(crate) fn new() -> FakeTransport {
+        FakeTransport::default()
+    }
+
+    /// Reply to requests for `url` with the given status, headers, and body
+    pub(crate) fn respond<B: Into<Bytes>>(
+        mut self,
+        url: &str,
+        status: StatusCode,
+        headers: &[(&'static str, &str)],
+        body: B,
+    ) -> FakeTransport {
+        let headers = headers
+            .iter()
+            .map(|&(name, value)| {
+                (
+                    reqwest::header::HeaderName::from_static(name),
+                    HeaderValue::from_str(value).expect("header value should be valid"),
+                )
+            })
+            .collect();
+        self.responses
+            .insert(url.to_owned(), (status, headers, body.into()));
+        self
+    }
+
+    /// Reply to requests for `url` with a 200 response containing `value`
+    /// serialized as JSON
+    pub(crate) fn json(self, url: &str, value: &serde_json::Value) -> FakeTransport {
+        self.respond(
+            url,
+            StatusCode::OK,
+            &[("content-type", "application/json")],
+            value.to_string(),
+        )
+    }
+
+    /// Reply to successive requests for `url` with 200 responses containing
+    /// the successive elements of `values` serialized as JSON, replying with
+    /// the last element once the others are used up
+    pub(crate) fn json_sequence(self, url: &str, values: &[serde_json::Value]) -> FakeTransport {
+        self.sequences
+            .lock()
+            .expect("FakeTransport mutex should not be poisoned")
+            .insert(url.to_owned(), values.iter().cloned().collect());
+        self
+    }
+
+    /// Return the methods & URLs of the requests received so far
+    pub(crate) fn requests(&self) -> Vec<(Method, String)> {
+        self.requests
+            .lock()
+            .expect("FakeTransport mutex should not be poisoned")
+            .clone()
+    }
+
+    /// Return the additional headers of the requests received so far
+    pub(crate) fn request_headers(&self) -> Vec<HeaderMap> {
+        self.request_headers
+            .lock()
+            .expect("FakeTransport mutex should not be poisoned")
+            .clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl HttpTransport for FakeTransport {
+    async fn send(&self, req: TransportRequest) -> Result<HttpResponse, HttpError> {
+        self.requests
+            .lock()
+            .expect("FakeTransport mutex should not be poisoned")
+            .push((req.method.clone(), req.url.to_string()));
+        self.request_headers
+            .lock()
+            .expect("FakeTransport mutex should not be poisoned")
+            .push(req.headers.clone());
+        let next_in_sequence = self
+            .sequences
+            .lock()
+            .expect("FakeTransport mutex should not be poisoned")
+            .get_mut(req.url.as_str())
+            .and_then(|values| {
+                if values.len() > 1 {
+                    values.pop_front()
+                } else {
+                    values.front().cloned()
+                }
+            });
+        let (mut status, headers, mut body) = match next_in_sequence {
+            Some(value) => (
+                StatusCode::OK,
+                HeaderMap::from_iter([(
+                    reqwest::header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                )]),
+                Bytes::from(value.to_string()),
+            ),
+            None => self
+                .responses
+                .get(req.url.as_str())
+                .cloned()
+                .unwrap_or_else(|| (StatusCode::NOT_FOUND, HeaderMap::new(), Bytes::new())),
+        };
+        if status == StatusCode::OK
+            && headers
+                .get(reqwest::header::ETAG)
+                .is_some_and(|etag| req.headers.get(reqwest::header::IF_NONE_MATCH) == Some(etag))
+        {
+            status = StatusCode::NOT_MODIFIED;
+            body = Bytes::new();
+        }
+        Ok(HttpResponse {
+            url: req.url.into(),
+            status,
+            headers,
+            body: if req.method == Method::HEAD {
+                futures_util::stream::empty().boxed()
+            } else {
+                chunked_body(body)
+            },
+        })
+    }
+}
+
+/// The size of the chunks in which [`FakeTransport`] streams response bodies
+#[cfg(test)]
+pub(crate) const FAKE_BODY_CHUNK_SIZE: usize = 1024;
+
+/// Convert `body` into a [`ResponseBody`] that yields it in chunks of
+/// [`FAKE_BODY_CHUNK_SIZE`] bytes, so that tests exercise reading bodies that
+/// arrive in multiple pieces
+#[cfg(test)]
+fn chunked_body(body: Bytes) -> ResponseBody {
+    let chunks = (0..body.len())
+        .step_by(FAKE_BODY_CHUNK_SIZE)
+        .map(|i| body.slice(i..body.len().min(i + FAKE_BODY_CHUNK_SIZE)))
+        .collect::<Vec<_>>();
+    futures_util::stream::iter(chunks).map(Ok).boxed()
+}
+
+/// An in-memory [`Uploader`] for use in tests that reads each request body
+/// in full, records the request, and replies with a fixed status code
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct FakeUploader {
+    /// The status code to reply to every request with
+    status: StatusCode,
+
+    /// The URLs, headers, and bodies of the requests received so far
+    uploads: Mutex<Vec<(String, HeaderMap, Bytes)>>,
+}
+
+#[cfg(test)]
+impl FakeUploader {
+    pub(crate) fn new(status: StatusCode) -> FakeUploader {
+        FakeUploader {
+            status,
+            uploads: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Return the URLs, headers, and bodies of the requests received so far
+    pub(crate) fn uploads(&self) -> Vec<(String, HeaderMap, Bytes)> {
+        self.uploads
+            .lock()
+            .expect("FakeUploader mutex should not be poisoned")
+            .clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Uploader for FakeUploader {
+    async fn put(
+        &self,
+        url: &HttpUrl,
+        headers: HeaderMap,
+        body: UploadBody,
+    ) -> Result<StatusCode, HttpError> {
+        let chunks = body.try_collect::<Vec<_>>().await;
+        // Mimic a failed upload without constructing a `reqwest` error:
+        let Ok(chunks) = chunks else {
+            return Ok(StatusCode::BAD_REQUEST);
+        };
+        self.uploads
+            .lock()
+            .expect("FakeUploader mutex should not be poisoned")
+            .push((url.to_string(), headers, Bytes::from(chunks.concat())));
+        Ok(self.status)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_matches::assert_matches;
+    use axum::response::IntoResponse;
     use rstest::rstest;
 
     #[rstest]
@@ -355,6 +1455,103 @@ mod tests {
         assert_eq!(before.as_str(), after);
     }
 
+    #[rstest]
+    #[case("https://api.dandiarchive.org/api", "https://api.dandiarchive.org/api")]
+    #[case(
+        "https://api.dandiarchive.org/api/",
+        "https://api.dandiarchive.org/api"
+    )]
+    #[case(
+        "https://api.dandiarchive.org/api//",
+        "https://api.dandiarchive.org/api"
+    )]
+    #[case(
+        "https://api.dandiarchive.org//api/",
+        "https://api.dandiarchive.org/api"
+    )]
+    #[case("https://example.com/foo//bar", "https://example.com/foo/bar")]
+    #[case("https://api.dandiarchive.org", "https://api.dandiarchive.org/")]
+    #[case("https://api.dandiarchive.org//", "https://api.dandiarchive.org/")]
+    #[case(
+        "https://api.dandiarchive.org/api/?x=y",
+        "https://api.dandiarchive.org/api?x=y"
+    )]
+    fn normalize_path(#[case] mut before: HttpUrl, #[case] after: &str) {
+        before.normalize_path();
+        assert_eq!(before.as_str(), after);
+    }
+
+    #[rstest]
+    #[case("https://api.dandiarchive.org/api", true)]
+    #[case("https://api.dandiarchive.org/api/", true)]
+    #[case("https://api.dandiarchive.org/api//", true)]
+    #[case("https://api.dandiarchive.org/", false)]
+    #[case("https://api.dandiarchive.org/apiary", false)]
+    #[case("https://example.com/api/v1", false)]
+    fn path_ends_with(#[case] url: HttpUrl, #[case] ends: bool) {
+        assert_eq!(url.path_ends_with("api"), ends);
+    }
+
+    /// Construct an arbitrary `reqwest::Error` for use in testing
+    fn reqwest_error() -> reqwest::Error {
+        reqwest::Client::new()
+            .get("http://[::1")
+            .build()
+            .expect_err("building a request for an invalid URL should fail")
+    }
+
+    #[test]
+    fn http_error_class() {
+        let url = "https://api.example.com/foo".parse::<HttpUrl>().unwrap();
+        let cases = [
+            (
+                HttpError::Send {
+                    url: url.clone(),
+                    source: reqwest_middleware::Error::Reqwest(reqwest_error()),
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                HttpError::Unauthorized { url: url.clone() },
+                ErrorClass::Unauthorized,
+            ),
+            (
+                HttpError::Redirect {
+                    url: url.clone(),
+                    source: RedirectError::Loop {
+                        chain: vec![url.clone().into(), url.clone().into()],
+                    },
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                HttpError::NotFound { url: url.clone() },
+                ErrorClass::NotFound,
+            ),
+            (
+                HttpError::Status {
+                    url: url.clone(),
+                    status: StatusCode::SERVICE_UNAVAILABLE,
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                HttpError::CircuitOpen { url: url.clone() },
+                ErrorClass::GatewayTimeout,
+            ),
+            (
+                HttpError::ParseJson {
+                    url,
+                    source: serde_json::from_str::<u32>("x").unwrap_err(),
+                },
+                ErrorClass::BadGateway,
+            ),
+        ];
+        for (e, class) in cases {
+            assert_eq!(e.class(), class, "wrong class for {e:?}");
+        }
+    }
+
     #[test]
     fn append_query_param() {
         let mut url = "https://api.github.com/foo".parse::<HttpUrl>().unwrap();
@@ -372,4 +1569,492 @@ mod tests {
             "https://api.github.com/foo?bar=baz&quux=with+space&bar=rod"
         );
     }
+
+    mod retry {
+        use super::*;
+
+        const URL: &str = "https://api.example.com/api/info/";
+
+        fn policy() -> RetryPolicy {
+            RetryPolicy {
+                max_retries: 3,
+                backoff: Duration::ZERO,
+                budget_percent: DEFAULT_RETRY_BUDGET_PERCENT,
+                breaker_threshold: 2,
+                breaker_cooldown: Duration::from_secs(60),
+            }
+        }
+
+        #[rstest]
+        #[case(0, Duration::from_secs(1))]
+        #[case(1, Duration::from_secs(2))]
+        #[case(3, Duration::from_secs(8))]
+        #[case(5, MAX_RETRY_BACKOFF)]
+        #[case(100, MAX_RETRY_BACKOFF)]
+        fn test_backoff(#[case] retries: u32, #[case] delay: Duration) {
+            assert_eq!(RetryPolicy::default().backoff(retries), delay);
+        }
+
+        #[rstest]
+        #[case(StatusCode::OK, false)]
+        #[case(StatusCode::NOT_FOUND, false)]
+        #[case(StatusCode::REQUEST_TIMEOUT, true)]
+        #[case(StatusCode::TOO_MANY_REQUESTS, true)]
+        #[case(StatusCode::INTERNAL_SERVER_ERROR, true)]
+        #[case(StatusCode::BAD_GATEWAY, true)]
+        #[case(StatusCode::SERVICE_UNAVAILABLE, true)]
+        fn test_is_transient(#[case] status: StatusCode, #[case] transient: bool) {
+            assert_eq!(is_transient(status), transient);
+        }
+
+        #[test]
+        fn retry_budget() {
+            let state = RetryState::new(RetryPolicy {
+                budget_percent: 50,
+                ..policy()
+            });
+            for _ in 0..RETRY_BUDGET_RESERVE {
+                assert!(state.withdraw());
+            }
+            assert!(!state.withdraw());
+            // Each request sent earns half a retry:
+            state.deposit();
+            assert!(!state.withdraw());
+            state.deposit();
+            assert!(state.withdraw());
+            assert!(!state.withdraw());
+        }
+
+        #[test]
+        fn retry_budget_capped_at_reserve() {
+            let state = RetryState::new(RetryPolicy {
+                budget_percent: 100,
+                ..policy()
+            });
+            for _ in 0..5 {
+                state.deposit();
+            }
+            for _ in 0..RETRY_BUDGET_RESERVE {
+                assert!(state.withdraw());
+            }
+            assert!(!state.withdraw());
+        }
+
+        #[test]
+        fn circuit_breaker_opens() {
+            let state = RetryState::new(policy());
+            let url = URL.parse::<HttpUrl>().unwrap();
+            state.record(&url, true);
+            assert!(state.admit());
+            state.record(&url, true);
+            assert!(!state.admit());
+        }
+
+        #[test]
+        fn circuit_breaker_reset_by_success() {
+            let state = RetryState::new(policy());
+            let url = URL.parse::<HttpUrl>().unwrap();
+            for _ in 0..3 {
+                state.record(&url, true);
+                state.record(&url, false);
+            }
+            assert!(state.admit());
+        }
+
+        #[test]
+        fn circuit_breaker_trial_request() {
+            let state = RetryState::new(RetryPolicy {
+                breaker_threshold: 1,
+                breaker_cooldown: Duration::ZERO,
+                ..policy()
+            });
+            let url = URL.parse::<HttpUrl>().unwrap();
+            state.record(&url, true);
+            // With no cooldown, the next request is let through as a trial,
+            // and its success closes the breaker:
+            assert!(state.admit());
+            state.record(&url, false);
+            assert!(state.breaker.lock().unwrap().open_until.is_none());
+        }
+
+        #[test]
+        fn circuit_breaker_disabled() {
+            let state = RetryState::new(RetryPolicy {
+                breaker_threshold: 0,
+                ..policy()
+            });
+            let url = URL.parse::<HttpUrl>().unwrap();
+            for _ in 0..10 {
+                state.record(&url, true);
+            }
+            assert!(state.admit());
+        }
+    }
+
+    mod fake_transport {
+        use super::*;
+        use serde_json::json;
+
+        fn client(transport: FakeTransport) -> Client {
+            Client::new(RetryPolicy::default(), HttpConfig::default(), None)
+                .unwrap()
+                .with_transport(Arc::new(transport))
+        }
+
+        #[tokio::test]
+        async fn get_json() {
+            let client = client(
+                FakeTransport::new()
+                    .json("https://example.com/good", &json!({"answer": 42}))
+                    .respond("https://example.com/bad", StatusCode::OK, &[], "<html>"),
+            );
+            let value = client
+                .get_json::<serde_json::Value>("https://example.com/good".parse().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(value, json!({"answer": 42}));
+            let e = client
+                .get_json::<serde_json::Value>("https://example.com/bad".parse().unwrap())
+                .await
+                .unwrap_err();
+            assert_matches!(e, HttpError::ParseJson { .. });
+        }
+
+        #[rstest]
+        #[case(StatusCode::NOT_FOUND, ErrorClass::NotFound)]
+        #[case(StatusCode::UNAUTHORIZED, ErrorClass::Unauthorized)]
+        #[case(StatusCode::FORBIDDEN, ErrorClass::BadGateway)]
+        #[case(StatusCode::SERVICE_UNAVAILABLE, ErrorClass::BadGateway)]
+        #[tokio::test]
+        async fn error_status(#[case] status: StatusCode, #[case] class: ErrorClass) {
+            let client =
+                client(FakeTransport::new().respond("https://example.com/", status, &[], ""));
+            let e = client
+                .get("https://example.com/".parse().unwrap())
+                .await
+                .unwrap_err();
+            assert_eq!(e.class(), class);
+            if class == ErrorClass::BadGateway {
+                assert_eq!(
+                    e.to_string(),
+                    format!("request to https://example.com/ returned error status {status}")
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn get_redirect_location() {
+            let client = client(
+                FakeTransport::new()
+                    .respond(
+                        "https://example.com/download/",
+                        StatusCode::FOUND,
+                        &[("location", "../blobs/abc")],
+                        "",
+                    )
+                    .respond("https://example.com/nowhere", StatusCode::FOUND, &[], "")
+                    .respond("https://example.com/here", StatusCode::OK, &[], ""),
+            );
+            let url = client
+                .get_redirect_location("https://example.com/download/".parse().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(url.as_str(), "https://example.com/blobs/abc");
+            assert_matches!(
+                client
+                    .get_redirect_location("https://example.com/nowhere".parse().unwrap())
+                    .await,
+                Err(HttpError::BadLocation { .. })
+            );
+            assert_matches!(
+                client
+                    .get_redirect_location("https://example.com/here".parse().unwrap())
+                    .await,
+                Err(HttpError::NotRedirected {
+                    status: StatusCode::OK,
+                    ..
+                })
+            );
+        }
+    }
+
+    /// Serve an app on a random local port in a background task and return
+    /// the base URL of the server
+    async fn serve(app: axum::Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    /// Construct a client that follows at most `max_redirects` redirects per
+    /// request
+    fn redirect_client(max_redirects: usize, auth: Option<Authorization>) -> Client {
+        let config = HttpConfig {
+            max_redirects,
+            ..HttpConfig::default()
+        };
+        Client::new(RetryPolicy::default(), config, auth).unwrap()
+    }
+
+    /// An app in which `/hop/{n}` redirects to `/hop/{n-1}` and `/hop/0`
+    /// returns a 200, and `/ping` and `/pong` redirect to each other
+    fn redirect_app() -> axum::Router {
+        use axum::{extract::Path, response::Redirect, routing::get};
+        axum::Router::new()
+            .route(
+                "/hop/{n}",
+                get(|Path(n): Path<u32>| async move {
+                    match n.checked_sub(1) {
+                        Some(m) => Redirect::temporary(&format!("/hop/{m}")).into_response(),
+                        None => "done".into_response(),
+                    }
+                }),
+            )
+            .route("/ping", get(|| async { Redirect::temporary("/pong") }))
+            .route("/pong", get(|| async { Redirect::temporary("/ping") }))
+            .route("/api/whoami", get(whoami))
+            .route("/apiary/whoami", get(whoami))
+            .route("/whoami", get(whoami))
+            .route(
+                "/api/private",
+                get(|headers: HeaderMap| async move {
+                    if headers.contains_key(AUTHORIZATION) {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::UNAUTHORIZED
+                    }
+                }),
+            )
+            .route(
+                "/api/download",
+                get(|| async { Redirect::temporary("/whoami") }),
+            )
+            .route(
+                "/old-api/{*rest}",
+                get(|Path(rest): Path<String>| async move {
+                    Redirect::permanent(&format!("/api/{rest}"))
+                }),
+            )
+            .route(
+                "/moved-api/{*rest}",
+                get(|Path(rest): Path<String>, headers: HeaderMap| async move {
+                    // Redirect to the same server under a different host
+                    // name, which is a different origin
+                    let host = headers
+                        .get(axum::http::header::HOST)
+                        .and_then(|hv| hv.to_str().ok())
+                        .unwrap_or_default()
+                        .replace("127.0.0.1", "localhost");
+                    Redirect::permanent(&format!("http://{host}/api/{rest}"))
+                }),
+            )
+    }
+
+    /// Respond with the value of the request's `Authorization` header, if any
+    #[allow(clippy::unused_async)]
+    async fn whoami(headers: HeaderMap) -> String {
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|hv| hv.to_str().ok())
+            .unwrap_or("anonymous")
+            .to_owned()
+    }
+
+    #[tokio::test]
+    async fn follow_redirects_within_limit() {
+        let base = serve(redirect_app()).await;
+        let client = redirect_client(3, None);
+        let url = format!("{base}/hop/3").parse::<HttpUrl>().unwrap();
+        let r = client.get(url).await.unwrap();
+        assert_eq!(r.url.as_str(), format!("{base}/hop/0"));
+        assert_eq!(r.bytes().await.unwrap(), "done");
+    }
+
+    #[tokio::test]
+    async fn too_many_redirects() {
+        let base = serve(redirect_app()).await;
+        let client = redirect_client(2, None);
+        let url = format!("{base}/hop/3").parse::<HttpUrl>().unwrap();
+        let e = client.get(url.clone()).await.unwrap_err();
+        assert_eq!(e.class(), ErrorClass::BadGateway);
+        let HttpError::Redirect { url: eurl, source } = e else {
+            panic!("expected Redirect error, got {e:?}");
+        };
+        assert_eq!(eurl, url);
+        assert_matches!(source, RedirectError::TooMany { max: 2, .. });
+        let chain = source
+            .chain()
+            .iter()
+            .map(|u| u.path().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(chain, ["/hop/3", "/hop/2", "/hop/1", "/hop/0"]);
+    }
+
+    #[tokio::test]
+    async fn redirect_loop() {
+        let base = serve(redirect_app()).await;
+        let client = redirect_client(10, None);
+        let url = format!("{base}/ping").parse::<HttpUrl>().unwrap();
+        let e = client.get(url).await.unwrap_err();
+        let HttpError::Redirect { source, .. } = e else {
+            panic!("expected Redirect error, got {e:?}");
+        };
+        assert_matches!(source, RedirectError::Loop { .. });
+        let chain = source
+            .chain()
+            .iter()
+            .map(|u| u.path().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(chain, ["/ping", "/pong", "/ping"]);
+    }
+
+    #[rstest]
+    #[case("https://api.example.com/api", true)]
+    #[case("https://api.example.com/api/", true)]
+    #[case("https://api.example.com/api/dandisets/?page=2", true)]
+    #[case("https://api.example.com/apiary/", false)]
+    #[case("https://api.example.com/", false)]
+    #[case("http://api.example.com/api/", false)]
+    #[case("https://bucket.s3.amazonaws.com/api/", false)]
+    fn authorization_applies_to(#[case] url: &str, #[case] applies: bool) {
+        let base = "https://api.example.com/api".parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&base, Some(HeaderValue::from_static("token abc")));
+        assert_eq!(auth.applies_to(&url.parse::<Url>().unwrap()), applies);
+    }
+
+    #[tokio::test]
+    async fn authorization_only_sent_under_base() {
+        let base = serve(redirect_app()).await;
+        let api_url = format!("{base}/api").parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&api_url, Some(HeaderValue::from_static("token abc")));
+        let client = redirect_client(10, Some(auth));
+        for (path, expected) in [
+            ("/api/whoami", "token abc"),
+            ("/apiary/whoami", "anonymous"),
+            ("/whoami", "anonymous"),
+        ] {
+            let url = format!("{base}{path}").parse::<HttpUrl>().unwrap();
+            let r = client.get(url).await.unwrap();
+            assert_eq!(r.bytes().await.unwrap(), expected, "wrong auth for {path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn get_redirect_location() {
+        let base = serve(redirect_app()).await;
+        let api_url = format!("{base}/api").parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&api_url, Some(HeaderValue::from_static("token abc")));
+        let client = redirect_client(10, Some(auth));
+        let url = format!("{base}/api/download").parse::<HttpUrl>().unwrap();
+        let loc = client.get_redirect_location(url).await.unwrap();
+        assert_eq!(loc.as_str(), format!("{base}/whoami"));
+        let url = format!("{base}/whoami").parse::<HttpUrl>().unwrap();
+        let e = client.get_redirect_location(url).await.unwrap_err();
+        assert_matches!(e, HttpError::NotRedirected { status, .. } => {
+            assert_eq!(status, StatusCode::OK);
+        });
+    }
+
+    #[tokio::test]
+    async fn credentials_override_default() {
+        let base = serve(redirect_app()).await;
+        let api_url = format!("{base}/api").parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&api_url, None);
+        let client = redirect_client(10, Some(auth));
+        let whoami = format!("{base}/api/whoami").parse::<HttpUrl>().unwrap();
+        assert!(!client.authenticates(whoami.as_url()));
+        let r = client.get(whoami.clone()).await.unwrap();
+        assert_eq!(r.bytes().await.unwrap(), "anonymous");
+        let private = format!("{base}/api/private").parse::<HttpUrl>().unwrap();
+        let e = client.get(private.clone()).await.unwrap_err();
+        assert_matches!(e, HttpError::Unauthorized { .. });
+        let user_client = client.with_credentials(HeaderValue::from_static("token xyz"));
+        assert!(user_client.authenticates(whoami.as_url()));
+        let r = user_client.get(whoami).await.unwrap();
+        assert_eq!(r.bytes().await.unwrap(), "token xyz");
+        user_client.get(private).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn same_origin_api_redirect() {
+        let base = serve(redirect_app()).await;
+        let api_url = format!("{base}/old-api").parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&api_url, Some(HeaderValue::from_static("token abc")));
+        let client = redirect_client(10, Some(auth));
+        let url = format!("{base}/old-api/whoami").parse::<HttpUrl>().unwrap();
+        let r = client.get(url).await.unwrap();
+        assert_eq!(r.url.as_str(), format!("{base}/api/whoami"));
+        assert_eq!(r.bytes().await.unwrap(), "token abc");
+    }
+
+    #[tokio::test]
+    async fn cross_origin_api_redirect() {
+        let base = serve(redirect_app()).await;
+        let api_url = format!("{base}/moved-api").parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&api_url, Some(HeaderValue::from_static("token abc")));
+        let client = redirect_client(10, Some(auth));
+        let url = format!("{base}/moved-api/whoami?foo=bar")
+            .parse::<HttpUrl>()
+            .unwrap();
+        let e = client.get(url.clone()).await.unwrap_err();
+        assert_eq!(e.class(), ErrorClass::BadGateway);
+        let HttpError::Redirect { url: eurl, source } = e else {
+            panic!("expected Redirect error, got {e:?}");
+        };
+        assert_eq!(eurl, url);
+        let canonical = base.replace("127.0.0.1", "localhost") + "/api/";
+        assert_matches!(source, RedirectError::CrossOrigin { canonical: Some(ref u), .. } => {
+            assert_eq!(u.as_str(), canonical);
+        });
+        assert_eq!(
+            source.to_string(),
+            format!("API request was redirected to a different origin; if the API has moved to {canonical}, use that as the --api-url")
+        );
+        assert_eq!(source.chain().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cross_origin_redirect_outside_api() {
+        let base = serve(redirect_app()).await;
+        let api_url = format!("{base}/api").parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&api_url, Some(HeaderValue::from_static("token abc")));
+        let client = redirect_client(10, Some(auth));
+        let url = format!("{base}/moved-api/whoami")
+            .parse::<HttpUrl>()
+            .unwrap();
+        let r = client.get(url).await.unwrap();
+        assert_eq!(r.bytes().await.unwrap(), "anonymous");
+    }
+
+    #[rstest]
+    #[case(
+        "https://api.example.com/api/dandisets/?page=2",
+        "https://canonical.example.com/api/dandisets/",
+        Some("https://canonical.example.com/api/")
+    )]
+    #[case(
+        "https://api.example.com/api/dandisets/000027/",
+        "https://www.example.com/archive/api/dandisets/000027/?x=y",
+        Some("https://www.example.com/archive/api/")
+    )]
+    #[case(
+        "https://api.example.com/api/info/",
+        "https://www.example.com/login/",
+        None
+    )]
+    fn test_canonical_base(
+        #[case] original: Url,
+        #[case] target: Url,
+        #[case] canonical: Option<&str>,
+    ) {
+        let base = "https://api.example.com/api".parse::<HttpUrl>().unwrap();
+        let auth = Authorization::new(&base, None);
+        assert_eq!(
+            auth.canonical_base(&original, &target)
+                .as_ref()
+                .map(Url::as_str),
+            canonical
+        );
+    }
 }
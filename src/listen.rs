@@ -0,0 +1,301 @@
+//! Opening of the socket on which `dandidav` listens for connections
+use crate::tls::TlsListener;
+use axum::{
+    serve::{Listener, ListenerExt},
+    Router,
+};
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio_rustls::TlsAcceptor;
+
+/// The file descriptor of the first socket passed to a process by systemd
+/// socket activation (`SD_LISTEN_FDS_START` in `sd_listen_fds(3)`)
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: std::os::fd::RawFd = 3;
+
+/// A socket on which `dandidav` listens for connections
+#[derive(Debug)]
+pub(crate) enum ServerListener {
+    Tcp(TcpListener),
+    Tls(TlsListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl ServerListener {
+    /// Obtain the socket to listen on.
+    ///
+    /// If `dandidav` was started via systemd socket activation (as indicated
+    /// by `systemd_fds`, the return value of [`take_systemd_fds()`]), the
+    /// first socket passed by systemd is used.  Otherwise, if `uds` is non-`None`,
+    /// a Unix domain socket is bound at that path, replacing any socket left
+    /// there by a previous run.  Otherwise, a TCP socket is bound to `addr`.
+    pub(crate) async fn open(
+        systemd_fds: Option<u32>,
+        uds: Option<&Path>,
+        addr: SocketAddr,
+    ) -> Result<ServerListener, ListenError> {
+        if let Some(listener) = ServerListener::from_systemd(systemd_fds)? {
+            return Ok(listener);
+        }
+        match uds {
+            Some(path) => ServerListener::bind_unix(path),
+            None => TcpListener::bind(addr)
+                .await
+                .map(ServerListener::Tcp)
+                .map_err(|source| ListenError::BindTcp { addr, source }),
+        }
+    }
+
+    /// Serve HTTPS on the socket, performing TLS handshakes with `acceptor`.
+    /// It is an error to call this on a Unix domain socket.
+    pub(crate) fn with_tls(self, acceptor: TlsAcceptor) -> Result<ServerListener, ListenError> {
+        match self {
+            ServerListener::Tcp(listener) => TlsListener::new(listener, acceptor)
+                .map(ServerListener::Tls)
+                .map_err(ListenError::Tls),
+            ServerListener::Tls(_) => Ok(self),
+            #[cfg(unix)]
+            ServerListener::Unix(_) => Err(ListenError::TlsOverUnix),
+        }
+    }
+
+    /// Return the local port of the socket, if it is a TCP socket
+    pub(crate) fn port(&self) -> Option<u16> {
+        match self {
+            ServerListener::Tcp(listener) => listener.local_addr().ok().map(|addr| addr.port()),
+            ServerListener::Tls(listener) => listener.local_addr().ok().map(|addr| addr.port()),
+            #[cfg(unix)]
+            ServerListener::Unix(_) => None,
+        }
+    }
+
+    /// Serve `app` on the socket until an unrecoverable error occurs.
+    ///
+    /// Requests received over TCP are annotated with the address of the peer
+    /// (for use in rate limiting & logging); requests received over a Unix
+    /// domain socket are not, and so clients' addresses can then only be
+    /// determined from headers like `X-Forwarded-For` set by a reverse proxy.
+    pub(crate) async fn serve(self, app: Router) -> io::Result<()> {
+        match self {
+            ServerListener::Tcp(listener) => {
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+            }
+            // axum only provides `ConnectInfo` for custom listeners when
+            // they're wrapped with `tap_io()`.
+            ServerListener::Tls(listener) => {
+                axum::serve(
+                    listener.tap_io(|_| ()),
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+            }
+            #[cfg(unix)]
+            ServerListener::Unix(listener) => axum::serve(listener, app.into_make_service()).await,
+        }
+    }
+
+    /// If the process was passed `nfds` sockets by systemd socket
+    /// activation, return the first one
+    #[cfg(unix)]
+    fn from_systemd(nfds: Option<u32>) -> Result<Option<ServerListener>, ListenError> {
+        let Some(nfds) = nfds else {
+            return Ok(None);
+        };
+        if nfds > 1 {
+            tracing::warn!(
+                nfds,
+                "systemd passed multiple sockets; only the first will be used"
+            );
+        }
+        let listener = inherited_listener().map_err(ListenError::Inherited)?;
+        tracing::info!("Using socket passed by systemd");
+        Ok(Some(listener))
+    }
+
+    #[cfg(not(unix))]
+    fn from_systemd(_nfds: Option<u32>) -> Result<Option<ServerListener>, ListenError> {
+        Ok(None)
+    }
+
+    /// Bind a Unix domain socket at `path`, first removing any socket already
+    /// at that path.  It is an error for a non-socket file to exist at
+    /// `path`.
+    #[cfg(unix)]
+    fn bind_unix(path: &Path) -> Result<ServerListener, ListenError> {
+        use std::os::unix::fs::FileTypeExt;
+        match std::fs::symlink_metadata(path) {
+            Ok(md) if md.file_type().is_socket() => {
+                std::fs::remove_file(path).map_err(|source| ListenError::BindUnix {
+                    path: path.to_owned(),
+                    source,
+                })?;
+            }
+            Ok(_) => {
+                return Err(ListenError::NotASocket {
+                    path: path.to_owned(),
+                })
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(source) => {
+                return Err(ListenError::BindUnix {
+                    path: path.to_owned(),
+                    source,
+                })
+            }
+        }
+        UnixListener::bind(path)
+            .map(ServerListener::Unix)
+            .map_err(|source| ListenError::BindUnix {
+                path: path.to_owned(),
+                source,
+            })
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(_path: &Path) -> Result<ServerListener, ListenError> {
+        Err(ListenError::UdsUnsupported)
+    }
+}
+
+/// Return the number of sockets passed to the process by systemd socket
+/// activation, or `None` if none were passed to it, and remove the environment
+/// variables describing them so that they are not inherited by any child
+/// processes.
+///
+/// As modifying the environment is not thread-safe, this must be called at
+/// program startup, before the Tokio runtime or any other threads are started.
+#[cfg(unix)]
+pub(crate) fn take_systemd_fds() -> Option<u32> {
+    let nfds = listen_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )?;
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    Some(nfds)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn take_systemd_fds() -> Option<u32> {
+    None
+}
+
+/// Given the values of the `LISTEN_PID` and `LISTEN_FDS` environment
+/// variables, return the number of sockets passed to the process with ID
+/// `pid` by systemd socket activation, or `None` if none were passed to it
+#[cfg_attr(not(unix), allow(dead_code))]
+fn listen_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<u32> {
+    if listen_pid?.parse::<u32>().ok()? != pid {
+        return None;
+    }
+    listen_fds?.parse::<u32>().ok().filter(|&n| n > 0)
+}
+
+/// Take ownership of the first socket passed by systemd and convert it to a
+/// TCP or Unix domain socket listener, as appropriate
+#[cfg(unix)]
+#[allow(unsafe_code)]
+fn inherited_listener() -> io::Result<ServerListener> {
+    use std::os::fd::{FromRawFd, IntoRawFd};
+    // SAFETY: systemd guarantees that file descriptor 3 is an open socket
+    // when `LISTEN_FDS` is set for this process, and nothing else in
+    // `dandidav` takes ownership of it.
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    // Obtaining the local address of a non-IP socket fails, in which case the
+    // socket is assumed to be a Unix domain socket:
+    if listener.local_addr().is_ok() {
+        listener.set_nonblocking(true)?;
+        return TcpListener::from_std(listener).map(ServerListener::Tcp);
+    }
+    // SAFETY: The file descriptor was just released by `listener`.
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(listener.into_raw_fd()) };
+    listener.set_nonblocking(true)?;
+    UnixListener::from_std(listener).map(ServerListener::Unix)
+}
+
+/// Error returned by [`ServerListener::open()`] and
+/// [`ServerListener::with_tls()`]
+#[derive(Debug, Error)]
+pub(crate) enum ListenError {
+    #[error("failed to bind to {addr}")]
+    BindTcp { addr: SocketAddr, source: io::Error },
+    #[error("failed to bind Unix domain socket at {}", .path.display())]
+    BindUnix { path: PathBuf, source: io::Error },
+    #[error("cannot bind Unix domain socket at {}: path exists and is not a socket", .path.display())]
+    NotASocket { path: PathBuf },
+    #[error("failed to use socket passed by systemd")]
+    Inherited(#[source] io::Error),
+    #[error("failed to set up TLS listener")]
+    Tls(#[source] io::Error),
+    #[cfg(unix)]
+    #[error("TLS cannot be used with a Unix domain socket")]
+    TlsOverUnix,
+    #[cfg(not(unix))]
+    #[error("Unix domain sockets are not supported on this platform")]
+    UdsUnsupported,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Some("42"), Some("1"), Some(1))]
+    #[case(Some("42"), Some("3"), Some(3))]
+    #[case(Some("42"), Some("0"), None)]
+    #[case(Some("43"), Some("1"), None)]
+    #[case(None, Some("1"), None)]
+    #[case(Some("42"), None, None)]
+    #[case(Some("foo"), Some("1"), None)]
+    #[case(Some("42"), Some("foo"), None)]
+    fn test_listen_fds(
+        #[case] listen_pid: Option<&str>,
+        #[case] nfds: Option<&str>,
+        #[case] r: Option<u32>,
+    ) {
+        assert_eq!(listen_fds(listen_pid, nfds, 42), r);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bind_unix_replaces_stale_socket() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("dandidav.sock");
+        let listener = ServerListener::bind_unix(&path).unwrap();
+        assert_matches!(listener, ServerListener::Unix(_));
+        drop(listener);
+        // The socket file is left behind after the listener is dropped:
+        assert!(path.exists());
+        let listener = ServerListener::bind_unix(&path).unwrap();
+        assert_matches!(listener, ServerListener::Unix(_));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bind_unix_not_a_socket() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("dandidav.sock");
+        std::fs::write(&path, "Not a socket\n").unwrap();
+        assert_matches!(
+            ServerListener::bind_unix(&path),
+            Err(ListenError::NotASocket { path: p }) => {
+                assert_eq!(p, path);
+            }
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Not a socket\n");
+    }
+}
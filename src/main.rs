@@ -1,25 +1,61 @@
 #[macro_use]
 mod validstr;
 
+mod about;
+mod accesslog;
 mod consts;
+mod cors;
 mod dandi;
 mod dav;
+mod errlog;
+mod health;
 mod httputil;
+mod listen;
+mod maintenance;
+mod metrics;
 mod paths;
+mod ratelimit;
 mod s3;
+mod sitemap;
 mod streamutil;
+mod tls;
+mod usage;
+mod version;
+mod wellknown;
 mod zarrman;
+use crate::about::UpstreamVersions;
+use crate::accesslog::log_access;
 use crate::consts::*;
-use crate::dandi::DandiClient;
-use crate::dav::{DandiDav, Templater};
-use crate::httputil::HttpUrl;
-use crate::zarrman::{ManifestFetcher, ZarrManClient};
+use crate::cors::{cors_layer, CorsOrigin};
+use crate::dandi::{ApiCacheConfig, ApiToken, DandiClient, PathMemo, ZarrLookup};
+use crate::dav::{
+    parse_timestamp_format, parse_utc_offset, ArchiveLabel, BodySize, CopyConfig, DandiDav,
+    DandisetFilter, Instance, InstanceSpec, LocalMountSpec, LocalSource, PathConfig,
+    PublishedCache, RedirectTargetSize, RequestTimeouts, RootBehavior, SyncSnapshots, Templater,
+    TimestampStyle,
+};
+use crate::errlog::{record_errors, ErrorLog};
+use crate::httputil::{HttpConfig, HttpUrl, RetryPolicy};
+use crate::listen::{take_systemd_fds, ServerListener};
+use crate::maintenance::{shed_agents, Maintenance, UserAgentRules};
+use crate::metrics::metrics;
+use crate::paths::{ZarrExtension, ZarrExtensions};
+use crate::ratelimit::{
+    limit_rate, log_rejections, parse_client_net, ClientIpSource, RateLimit, RateLimits,
+};
+use crate::s3::{set_custom_endpoints, S3Config, S3Endpoint};
+use crate::sitemap::Sitemap;
+use crate::tls::{load_tls_config, serve_https_redirects};
+use crate::usage::{record_usage, UsageStats};
+use crate::version::VersionInfo;
+use crate::wellknown::{parse_timestamp, RobotsPolicy, RobotsTxt, SecurityContact, SecurityTxt};
+use crate::zarrman::{read_pin_file, ManifestDiskCache, ManifestFetcher, ZarrManClient};
 use anyhow::Context;
 use axum::{
     body::Body,
-    extract::Request,
+    extract::{Path, Request},
     http::{
-        header::{HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_LENGTH, CONTENT_TYPE, SERVER},
+        header::{HeaderMap, HeaderValue, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_TYPE, SERVER},
         response::Response,
         Method,
     },
@@ -27,11 +63,16 @@ use axum::{
     routing::get,
     Router,
 };
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use http_body::Body as _;
+use ipnet::IpNet;
+use std::collections::BTreeMap;
 use std::fmt;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use time::{format_description::OwnedFormatItem, OffsetDateTime, UtcOffset};
 use tower::service_fn;
 use tower_http::{set_header::response::SetResponseHeaderLayer, trace::TraceLayer};
 use tracing::Level;
@@ -40,20 +81,278 @@ use tracing_subscriber::{filter::Targets, fmt::time::OffsetTime, prelude::*};
 /// The content of the CSS stylesheet to serve at `/.static/styles.css`
 static STYLESHEET: &str = include_str!("dav/static/styles.css");
 
+/// The content of the script for sorting & filtering collection tables to
+/// serve at `/.static/table.js`
+static TABLE_SCRIPT: &str = include_str!("dav/static/table.js");
+
 /// WebDAV view to DANDI Archive
 ///
 /// See <https://github.com/dandi/dandidav> for more information.
 #[derive(Clone, Debug, Eq, Parser, PartialEq)]
-#[command(version = env!("VERSION_WITH_GIT"))]
+#[command(version = env!("VERSION_WITH_GIT"), disable_version_flag = true)]
 struct Arguments {
-    /// API URL of the DANDI Archive instance to serve
+    /// Log a JSON record for each request summarizing its method, path type,
+    /// status, response size, duration, and numbers of upstream requests &
+    /// cache lookups
+    #[arg(long)]
+    access_log: bool,
+
+    /// Respond to `PROPFIND` requests with an infinite or missing `Depth`
+    /// header by recursively enumerating the requested resource's descendants
+    #[arg(long)]
+    allow_infinite_depth: bool,
+
+    /// Cache up to this many Dandiset, version, and asset path lookups from
+    /// the Archive API and Zarr location & entry lookups on S3 (each).  0
+    /// disables caching.
+    #[arg(long, default_value_t = DEFAULT_API_CACHE_SIZE, value_name = "INT")]
+    api_cache_size: u64,
+
+    /// Expire cached Archive API & Zarr entry lookups this many seconds after
+    /// they were fetched.  0 disables caching.
+    #[arg(long, default_value_t = DEFAULT_API_CACHE_TTL_SECS, value_name = "SECONDS")]
+    api_cache_ttl: u64,
+
+    /// API token for authenticating with the Archive, granting access to any
+    /// embargoed Dandisets that the token's owner can access
+    #[arg(
+        long,
+        env = "DANDI_API_KEY",
+        hide_env_values = true,
+        value_name = "TOKEN"
+    )]
+    api_token: Option<ApiToken>,
+
+    /// API URL of the primary DANDI Archive instance to serve, whose Dandisets
+    /// are served under `/dandisets/`
     #[arg(long, default_value = DEFAULT_API_URL, value_name = "URL")]
     api_url: HttpUrl,
 
+    /// Serve `/assets/{asset_id}`, which redirects to the Archive download
+    /// URL for the blob asset with the given ID or to the `/zarrs/` hierarchy
+    /// entry for the Zarr asset with the given ID.  As the Archive does not
+    /// report which Dandisets an asset belongs to, this cannot be combined
+    /// with `--include-dandisets` or `--exclude-dandisets`.
+    #[arg(long, conflicts_with_all = ["include_dandisets", "exclude_dandisets"])]
+    asset_redirects: bool,
+
+    /// After the circuit breaker for the Archive or the Zarr manifest tree
+    /// opens (see `--circuit-breaker-threshold`), fail requests to it
+    /// immediately for this many seconds before trying it again
+    #[arg(long, default_value_t = DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS, value_name = "SECONDS")]
+    circuit_breaker_cooldown: u64,
+
+    /// Open the circuit breaker for the Archive or the Zarr manifest tree
+    /// after this many consecutive requests to it fail despite retrying,
+    /// causing further requests to fail fast with a 504 response.  0 disables
+    /// the circuit breaker.
+    #[arg(long, default_value_t = DEFAULT_CIRCUIT_BREAKER_THRESHOLD, value_name = "INT")]
+    circuit_breaker_threshold: u32,
+
+    /// Accompany each asset `{path}` in a Dandiset version with a virtual
+    /// `{path}.dandi.json` file containing the asset's metadata
+    #[arg(long)]
+    asset_sidecars: bool,
+
+    /// Limit each client IP address to an average of this many requests per
+    /// second for static assets & non-collection resources, separately from
+    /// other requests.  Defaults to the `--rate-limit` value.
+    #[arg(long, value_name = "INT", value_parser = clap::value_parser!(u32).range(1..))]
+    cheap_rate_limit: Option<u32>,
+
+    /// Allow each client IP address to make this many requests for static
+    /// assets & non-collection resources in a burst before the
+    /// `--cheap-rate-limit` applies.  Defaults to the `--rate-limit-burst`
+    /// value.
+    #[arg(long, value_name = "INT", value_parser = clap::value_parser!(u32).range(1..))]
+    cheap_rate_limit_burst: Option<u32>,
+
+    /// Allow `COPY` requests to upload non-collection resources to URLs
+    /// beginning with the given prefix on an external WebDAV server.  Can be
+    /// given multiple times.  `COPY` requests are refused unless this option
+    /// is given.
+    #[arg(long, value_name = "URL")]
+    copy_destination: Vec<HttpUrl>,
+
+    /// Only allow cross-origin requests from browser-based clients served
+    /// from the given origin (e.g., `https://example.com`).  Can be given
+    /// multiple times.  If not given, cross-origin requests are allowed from
+    /// any origin.
+    #[arg(long, value_name = "ORIGIN")]
+    cors_origin: Vec<CorsOrigin>,
+
+    /// Keep the last this many errors that occurred while handling requests
+    /// (other than "not found" errors) in memory and serve them as JSON at
+    /// `/admin/errors`.  0 (the default) disables this.
+    #[arg(long, default_value_t = 0, value_name = "INT")]
+    error_log_size: usize,
+
+    /// Do not serve Dandisets whose IDs match any of the given glob patterns.
+    /// Can be given multiple times and/or as a comma-separated list.
+    #[arg(long, value_name = "PATTERN", value_delimiter = ',')]
+    exclude_dandisets: Vec<glob::Pattern>,
+
+    /// When a path under a Dandiset version cannot be found, look up each of
+    /// its leading path components in order to find Zarrs whose names lack a
+    /// recognized Zarr extension.  This is always correct but can require
+    /// many more requests to the Archive.
+    #[arg(long)]
+    exhaustive_zarr_lookup: bool,
+
+    /// Limit each client IP address to an average of this many `PROPFIND`
+    /// requests with a nonzero `Depth`, `tree` listing requests, `COPY`
+    /// requests, and `REPORT` requests per second, separately from other
+    /// requests.  Defaults to the `--rate-limit` value.
+    #[arg(long, value_name = "INT", value_parser = clap::value_parser!(u32).range(1..))]
+    expensive_rate_limit: Option<u32>,
+
+    /// Allow each client IP address to make this many `PROPFIND` requests
+    /// with a nonzero `Depth`, `tree` listing requests, `COPY` requests, and
+    /// `REPORT` requests in a burst before the `--expensive-rate-limit`
+    /// applies.  Defaults to the
+    /// `--rate-limit-burst` value.
+    #[arg(long, value_name = "INT", value_parser = clap::value_parser!(u32).range(1..))]
+    expensive_rate_limit_burst: Option<u32>,
+
+    /// Treat requests for paths with a component of the given name (compared
+    /// case-insensitively) as being for nonexistent resources without
+    /// contacting the Archive, in addition to a built-in list of names like
+    /// `.git` and `.DS_Store`.  Can be given multiple times and/or as a
+    /// comma-separated list.
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    fast_not_exist: Vec<String>,
+
+    /// Read additional names to treat as for `--fast-not-exist` from the
+    /// given file, one per line.  Blank lines and lines starting with `#` are
+    /// ignored.
+    #[arg(long, value_name = "FILE")]
+    fast_not_exist_file: Option<PathBuf>,
+
+    /// Display timestamps in HTML views in the given format, written in the
+    /// format description syntax of the `time` crate
+    /// (<https://time-rs.github.io/book/api/format-description.html>).  By
+    /// default, timestamps are displayed as, e.g., `2024-01-02 03:04:05Z`.
+    #[arg(long, value_parser = parse_timestamp_format, value_name = "FORMAT")]
+    html_timestamp_format: Option<OwnedFormatItem>,
+
+    /// Convert timestamps displayed in HTML views to the given UTC offset,
+    /// written as `+HH:MM` or `-HH:MM`
+    #[arg(long, default_value = "+00:00", value_parser = parse_utc_offset, value_name = "OFFSET")]
+    html_utc_offset: UtcOffset,
+
+    /// When serving HTTPS, also listen for plain HTTP on the given port (on
+    /// the same IP address) and redirect all requests received there to
+    /// HTTPS
+    #[arg(long, value_name = "PORT", requires = "tls_cert")]
+    http_redirect_port: Option<u16>,
+
+    /// Speak HTTP/2 to the Archive, S3, and the Zarr manifest tree without
+    /// first negotiating it.  Without this option, HTTP/2 is still used with
+    /// HTTPS servers that support it.
+    #[arg(long)]
+    http2_prior_knowledge: bool,
+
+    /// Serve the contents of the given file at `/humans.txt`
+    #[arg(long, value_name = "FILE")]
+    humans_txt: Option<PathBuf>,
+
+    /// Only serve Dandisets whose IDs match at least one of the given glob
+    /// patterns.  Can be given multiple times and/or as a comma-separated
+    /// list.
+    #[arg(long, value_name = "PATTERN", value_delimiter = ',')]
+    include_dandisets: Vec<glob::Pattern>,
+
+    /// Serve `.json`, `.tsv`, `.yaml`, and `.yml` assets & Zarr entries no
+    /// larger than this many bytes as plain text fetched by `dandidav` instead
+    /// of redirecting to their download URLs.  By default, all such files are
+    /// redirected.
+    #[arg(long, value_name = "INT")]
+    inline_preview_max_bytes: Option<u64>,
+
+    /// Also serve the Dandisets of the DANDI Archive instance with the given
+    /// API URL under `/{NAME}-dandisets/`.  Can be given multiple times.
+    #[arg(long, value_name = "NAME=URL")]
+    instance: Vec<InstanceSpec>,
+
     /// IP address to listen on
     #[arg(long, default_value = "127.0.0.1")]
     ip_addr: IpAddr,
 
+    /// When used with `--version`, output version & build information as a
+    /// JSON object
+    #[arg(long, requires = "version")]
+    json: bool,
+
+    /// Give up on `GET` requests for collections and finite-depth `PROPFIND`
+    /// requests that take longer than this many seconds to handle.  0
+    /// disables the limit.
+    #[arg(long, default_value_t = DEFAULT_LISTING_TIMEOUT_SECS, value_name = "SECONDS")]
+    listing_timeout: u64,
+
+    /// Serve the given local directory tree (read-only) under `/{NAME}/`.
+    /// Can be given multiple times.
+    #[arg(long, value_name = "NAME=DIR")]
+    local_mount: Vec<LocalMountSpec>,
+
+    /// Tell clients whose requests are denied by the `--maintenance-rules` to
+    /// try again after this many seconds
+    #[arg(long, default_value_t = DEFAULT_MAINTENANCE_RETRY_AFTER_SECS, value_name = "SECONDS")]
+    maintenance_retry_after: u64,
+
+    /// Read rules from the given file for allowing or denying requests based
+    /// on their `User-Agent` headers, e.g., in order to serve only
+    /// `dandi-cli` during an incident.  Each line of the file is either blank,
+    /// a comment starting with `#`, or a rule of the form `allow PATTERN` or
+    /// `deny PATTERN`, where `PATTERN` is `*` (matching all requests) or a
+    /// case-insensitive substring of the `User-Agent`.  Each request is
+    /// handled according to the first rule that matches it, and requests
+    /// matching no rules are allowed.  Denied requests receive a 503 response.
+    #[arg(long, value_name = "FILE")]
+    maintenance_rules: Option<PathBuf>,
+
+    /// Fail infinite-depth `PROPFIND` requests that would report on more than
+    /// this many resources
+    #[arg(long, default_value_t = DEFAULT_MAX_INFINITE_DEPTH_RESOURCES, value_name = "INT")]
+    max_infinite_depth_resources: usize,
+
+    /// Follow at most this many redirects when making requests to the
+    /// Archive, S3, and the Zarr manifest tree
+    #[arg(long, default_value_t = DEFAULT_MAX_REDIRECTS, value_name = "INT")]
+    max_redirects: usize,
+
+    /// Retry requests to the Archive and the Zarr manifest tree that fail due
+    /// to network errors or 408, 429, or 5xx responses at most this many
+    /// times
+    #[arg(long, default_value_t = DEFAULT_MAX_RETRIES, value_name = "INT")]
+    max_retries: u32,
+
+    /// When resolving a path under a Dandiset version, look up at most this
+    /// many leading path components with Zarr extensions (see
+    /// `--zarr-extension`) to determine whether any of them is a Zarr; paths
+    /// needing more lookups are treated as nonexistent
+    #[arg(long, default_value_t = DEFAULT_MAX_ZARR_CANDIDATES, value_name = "INT")]
+    max_zarr_candidates: usize,
+
+    /// Serve Prometheus metrics at `/metrics`
+    #[arg(long)]
+    metrics: bool,
+
+    /// When listing the results of paginated Archive API endpoints, fetch up
+    /// to this many pages in the background ahead of the page currently
+    /// being consumed
+    #[arg(long, default_value_t = DEFAULT_PAGE_PREFETCH, value_name = "INT")]
+    page_prefetch: usize,
+
+    /// Close idle outgoing HTTP connections after this many seconds.  0 keeps
+    /// idle connections open indefinitely.
+    #[arg(long, default_value_t = DEFAULT_POOL_IDLE_TIMEOUT_SECS, value_name = "SECONDS")]
+    pool_idle_timeout: u64,
+
+    /// Keep at most this many idle outgoing HTTP connections open per host.
+    /// If not given, there is no limit.
+    #[arg(long, value_name = "INT")]
+    pool_max_idle_per_host: Option<usize>,
+
     /// Port to listen on
     #[arg(short, long, default_value_t = 8080)]
     port: u16,
@@ -63,20 +362,250 @@ struct Arguments {
     #[arg(long)]
     prefer_s3_redirects: bool,
 
-    /// Site name to use in HTML collection pages
-    #[arg(short = 'T', long, default_value = env!("CARGO_PKG_NAME"))]
-    title: String,
+    /// Wait this many milliseconds before the first retry of a failed request
+    /// to the Archive or the Zarr manifest tree, doubling the wait before
+    /// each subsequent retry
+    #[arg(long, default_value_t = DEFAULT_RETRY_BACKOFF_MS, value_name = "MILLISECONDS")]
+    retry_backoff: u64,
+
+    /// Limit the retries of failed requests to the Archive or the Zarr
+    /// manifest tree to this percentage of all requests sent to it, beyond a
+    /// small reserve, so that retries do not pile up during outages
+    #[arg(long, default_value_t = DEFAULT_RETRY_BUDGET_PERCENT, value_name = "PERCENT")]
+    retry_budget: u32,
+
+    /// Cache up to this many resources from listings of published Dandiset
+    /// versions in memory.  0 disables caching.
+    #[arg(long, default_value_t = DEFAULT_PUBLISHED_CACHE_SIZE, value_name = "INT")]
+    published_cache_size: u64,
+
+    /// Limit each client IP address to an average of this many requests per
+    /// second.  By default, requests are not rate-limited.
+    #[arg(long, value_name = "INT", value_parser = clap::value_parser!(u32).range(1..))]
+    rate_limit: Option<u32>,
+
+    /// Allow each client IP address to make this many requests in a burst
+    /// before the `--rate-limit` applies
+    #[arg(long, default_value_t = DEFAULT_RATE_LIMIT_BURST, value_name = "INT", value_parser = clap::value_parser!(u32).range(1..))]
+    rate_limit_burst: u32,
+
+    /// Do not rate-limit clients whose IP addresses belong to the given
+    /// network (in CIDR notation, or a single IP address).  Can be given
+    /// multiple times.
+    #[arg(long, value_name = "CIDR", value_parser = parse_client_net)]
+    rate_limit_exempt: Vec<IpNet>,
+
+    /// Give up on `GET` requests for non-collection resources that take
+    /// longer than this many seconds to handle.  0 disables the limit.
+    #[arg(long, default_value_t = DEFAULT_REDIRECT_TIMEOUT_SECS, value_name = "SECONDS")]
+    redirect_timeout: u64,
+
+    /// Whether to "allow" or "disallow" web crawlers in `/robots.txt`
+    #[arg(long, default_value = "disallow", value_name = "allow|disallow")]
+    robots: RobotsPolicy,
+
+    /// How to respond to `GET` requests for `/`: "render" the root
+    /// collection, redirect to a given URL or absolute URL path, or respond
+    /// with "404"
+    #[arg(long, default_value = "render", value_name = "render|404|URL")]
+    root_redirect: RootBehavior,
+
+    /// Give up on connecting to S3 after this many seconds
+    #[arg(long, default_value_t = DEFAULT_S3_CONNECT_TIMEOUT_SECS, value_name = "SECONDS")]
+    s3_connect_timeout: u64,
+
+    /// Treat URLs for the S3-compatible object store (e.g., a MinIO server)
+    /// with the given base URL as S3 URLs, both in virtual-hosted style
+    /// (`{bucket}.{host}`) and path style (`{host}/{bucket}`), and list Zarrs
+    /// stored there via the store's S3 API.  Can be given multiple times.
+    #[arg(long, value_name = "URL")]
+    s3_endpoint: Vec<S3Endpoint>,
+
+    /// List up to this many ranges of the entries of each Zarr folder on S3
+    /// at once.  1 lists folders sequentially.
+    #[arg(long, default_value_t = DEFAULT_S3_LIST_CONCURRENCY, value_name = "INT")]
+    s3_list_concurrency: usize,
+
+    /// Make at most this many attempts (including the first) at each S3
+    /// request.  1 disables retries.
+    #[arg(long, default_value_t = DEFAULT_S3_MAX_ATTEMPTS, value_name = "INT", value_parser = clap::value_parser!(u32).range(1..))]
+    s3_max_attempts: u32,
+
+    /// Give up on an S3 request attempt if no data is received for this many
+    /// seconds
+    #[arg(long, default_value_t = DEFAULT_S3_READ_TIMEOUT_SECS, value_name = "SECONDS")]
+    s3_read_timeout: u64,
+
+    /// URI (or e-mail address) to list as a contact for reporting security
+    /// vulnerabilities in `/.well-known/security.txt`.  Can be given multiple
+    /// times.  If not given, no `security.txt` is served.
+    #[arg(long, requires = "security_expires", value_name = "URI")]
+    security_contact: Vec<SecurityContact>,
+
+    /// RFC 3339 timestamp after which the information in
+    /// `/.well-known/security.txt` should be considered stale
+    #[arg(long, requires = "security_contact", value_parser = parse_timestamp, value_name = "TIMESTAMP")]
+    security_expires: Option<OffsetDateTime>,
+
+    /// URL of a vulnerability disclosure policy to list in
+    /// `/.well-known/security.txt`
+    #[arg(long, requires = "security_contact", value_name = "URL")]
+    security_policy: Option<HttpUrl>,
+
+    /// Serve a sitemap at `/sitemap.xml` listing the primary Archive
+    /// instance's Dandisets & published versions, using the given URL as the
+    /// public URL of `dandidav`.  If `--robots allow` is also given, the
+    /// sitemap is advertised in `/robots.txt`.
+    #[arg(long, value_name = "URL")]
+    sitemap_base_url: Option<HttpUrl>,
+
+    /// Abort infinite-depth `PROPFIND` responses and `assets.jsonl` downloads
+    /// that are not completed within this many seconds.  0 disables the
+    /// limit.
+    #[arg(long, default_value_t = DEFAULT_STREAM_TIMEOUT_SECS, value_name = "SECONDS")]
+    stream_timeout: u64,
+
+    /// Send TCP keepalive probes on outgoing HTTP connections every this many
+    /// seconds.  0 disables TCP keepalive.
+    #[arg(long, default_value_t = DEFAULT_TCP_KEEPALIVE_SECS, value_name = "SECONDS")]
+    tcp_keepalive: u64,
+
+    /// Site name to use in HTML collection pages.  Defaults to the name
+    /// reported by the primary Archive instance, or "dandidav" if that cannot
+    /// be determined.
+    #[arg(short = 'T', long)]
+    title: Option<String>,
+
+    /// Serve HTTPS using the PEM-encoded certificate chain in the given file.
+    /// Requires `--tls-key`.
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "tls_key",
+        conflicts_with = "uds"
+    )]
+    tls_cert: Option<PathBuf>,
+
+    /// Serve HTTPS using the PEM-encoded private key in the given file.
+    /// Requires `--tls-cert`.
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// The number of trusted reverse proxies in front of `dandidav` that
+    /// append to the `X-Forwarded-For` header.  Rate limiting and usage
+    /// statistics identify clients by the address that many entries from
+    /// the right end of the header.  If this is 0, the header is ignored and
+    /// clients are identified by the peer address of the connection.
+    #[arg(long, default_value_t = 0, value_name = "INT")]
+    trusted_proxies: usize,
+
+    /// Listen on a Unix domain socket at the given path instead of on a TCP
+    /// port, e.g., for use behind a reverse proxy.  Any socket already at the
+    /// path is replaced.  As client addresses are then unknown, rate limiting
+    /// and usage statistics rely on the `X-Forwarded-For` header set by the
+    /// proxy (see `--trusted-proxies`).
+    ///
+    /// This option (along with `--ip-addr` and `--port`) is ignored when
+    /// `dandidav` is started via systemd socket activation.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["ip_addr", "port"])]
+    uds: Option<PathBuf>,
+
+    /// Aggregate anonymized usage statistics (unique clients, requests, and
+    /// bytes redirected per top-level hierarchy) for each UTC day and log
+    /// them as a JSON object once the day is over
+    #[arg(long)]
+    usage_stats: bool,
+
+    /// Print version information and exit
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// Treat path components under Dandiset versions with the given extension
+    /// (compared case-insensitively) as potential Zarrs.  Can be given
+    /// multiple times.  Defaults to `.zarr` and `.ngff`.
+    #[arg(long, value_name = "EXT")]
+    zarr_extension: Vec<ZarrExtension>,
 
     /// Limit the Zarr manifest cache to storing no more than this many
     /// megabytes of parsed manifests at once
     #[arg(short = 'Z', long, default_value_t = 100, value_name = "INT")]
     zarrman_cache_mb: u64,
+
+    /// Persist fetched Zarr manifests in the given directory and consult it
+    /// before fetching manifests from the manifest tree, so that manifests
+    /// survive restarts.  The directory is created if it does not exist.
+    #[arg(long, value_name = "DIR")]
+    zarrman_disk_cache: Option<PathBuf>,
+
+    /// Limit the on-disk Zarr manifest cache to storing no more than this
+    /// many megabytes of manifests
+    #[arg(long, default_value_t = 1000, value_name = "INT")]
+    zarrman_disk_cache_mb: u64,
+
+    /// Download & parse no more than this many Zarr manifests at once, so
+    /// that bursts of requests for different large Zarrs do not exhaust
+    /// memory.  Further fetches wait for a free slot, failing with a 503 if
+    /// none frees up within 30 seconds.  0 means no limit.
+    #[arg(long, default_value_t = 4, value_name = "INT")]
+    zarrman_max_concurrent_fetches: usize,
+
+    /// Read a list of Zarr manifest paths (one per line, relative to the
+    /// manifest root) from the given file and keep the manifests pinned in
+    /// memory, outside of the Zarr manifest cache.  The file is reread
+    /// periodically to pick up changes.
+    #[arg(long, value_name = "PATH")]
+    zarrman_pin_file: Option<PathBuf>,
+
+    /// Serve the contents of Zarr assets in Dandiset versions from the Zarrs'
+    /// manifests (as found via the Zarrs' checksums) rather than by listing
+    /// S3, falling back to S3 for Zarrs whose manifests are not available
+    #[arg(long)]
+    zarrs_via_manifests: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Alternative modes of operation to serving
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+enum Command {
+    /// Download a Dandiset version, Zarr, or part thereof to a local
+    /// directory.
+    ///
+    /// Files are verified against their checksums, interrupted mirrors can be
+    /// resumed by running the same command again, and a manifest of the
+    /// mirrored files is written to `.dandidav-mirror.jsonl` in the output
+    /// directory.  The options for connecting to the Archive (e.g.,
+    /// `--api-url` and `--api-token`) must be given before `mirror`.
+    Mirror(MirrorArguments),
+}
+
+#[derive(Args, Clone, Debug, Eq, PartialEq)]
+struct MirrorArguments {
+    /// Download up to this many files at once
+    #[arg(short = 'J', long, default_value_t = DEFAULT_MIRROR_JOBS, value_name = "INT")]
+    jobs: usize,
+
+    /// The path of the resource to mirror, as served by `dandidav` (e.g.,
+    /// `dandisets/000027/draft/` or
+    /// `dandisets/000027/releases/0.210831.2033/sub-RAT123/`)
+    path: String,
+
+    /// The local directory to mirror the resource into
+    outdir: PathBuf,
 }
 
 // See
 // <https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/time/struct.OffsetTime.html#method.local_rfc_3339>
 // for an explanation of the main + #[tokio::main]run thing
 fn main() -> anyhow::Result<()> {
+    // This must happen before the Tokio runtime starts any threads:
+    let systemd_fds = take_systemd_fds();
+    let args = Arguments::parse();
+    if args.version {
+        println!("{}", VersionInfo::current().render(args.json));
+        return Ok(());
+    }
     let timer =
         OffsetTime::local_rfc_3339().context("failed to determine local timezone offset")?;
     tracing_subscriber::registry()
@@ -91,67 +620,483 @@ fn main() -> anyhow::Result<()> {
                 .with_target(env!("CARGO_CRATE_NAME"), Level::TRACE)
                 .with_target("aws_config", Level::DEBUG)
                 .with_target("reqwest", Level::TRACE)
-                .with_target("reqwest_retry", Level::TRACE)
                 .with_target("tower_http", Level::TRACE)
                 .with_default(Level::INFO),
         )
         .init();
-    run()
+    run(args, systemd_fds)
 }
 
 #[tokio::main]
-async fn run() -> anyhow::Result<()> {
-    let args = Arguments::parse();
-    let dandi = DandiClient::new(args.api_url)?;
-    let zarrfetcher = ManifestFetcher::new(args.zarrman_cache_mb * 1_000_000)?;
+async fn run(args: Arguments, systemd_fds: Option<u32>) -> anyhow::Result<()> {
+    let retry = RetryPolicy {
+        max_retries: args.max_retries,
+        backoff: Duration::from_millis(args.retry_backoff),
+        budget_percent: args.retry_budget,
+        breaker_threshold: args.circuit_breaker_threshold,
+        breaker_cooldown: Duration::from_secs(args.circuit_breaker_cooldown),
+    };
+    let security_txt = args.security_expires.map(|expires| SecurityTxt {
+        contacts: args.security_contact,
+        expires,
+        policy: args.security_policy,
+    });
+    if security_txt.as_ref().is_some_and(SecurityTxt::is_expired) {
+        tracing::warn!("Expiration time given by --security-expires has already passed");
+    }
+    let humans_txt = args
+        .humans_txt
+        .map(|p| {
+            std::fs::read_to_string(&p).with_context(|| format!("failed to read {}", p.display()))
+        })
+        .transpose()?;
+    let maintenance_rules = args
+        .maintenance_rules
+        .map(|p| {
+            std::fs::read_to_string(&p)
+                .with_context(|| format!("failed to read {}", p.display()))?
+                .parse::<UserAgentRules>()
+                .with_context(|| format!("failed to parse {}", p.display()))
+        })
+        .transpose()?;
+    match maintenance_rules {
+        Some(ref rules) if rules.is_empty() => {
+            tracing::warn!("File given by --maintenance-rules contains no rules");
+        }
+        Some(ref rules) => tracing::info!(
+            rules = rules.len(),
+            "Applying maintenance rules to client User-Agents"
+        ),
+        None => (),
+    }
+    let cache_config = ApiCacheConfig {
+        max_entries: args.api_cache_size,
+        ttl: Duration::from_secs(args.api_cache_ttl),
+    };
+    set_custom_endpoints(args.s3_endpoint);
+    let mut fast_not_exist = args.fast_not_exist;
+    if let Some(p) = args.fast_not_exist_file {
+        let s = std::fs::read_to_string(&p)
+            .with_context(|| format!("failed to read {}", p.display()))?;
+        fast_not_exist.extend(
+            s.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+    let s3config = S3Config {
+        connect_timeout: Duration::from_secs(args.s3_connect_timeout),
+        read_timeout: Duration::from_secs(args.s3_read_timeout),
+        max_attempts: args.s3_max_attempts,
+        list_concurrency: args.s3_list_concurrency,
+    };
+    // The `--api-token` is only used for the primary instance, as tokens are
+    // specific to each instance.
+    let mut instances = vec![(Instance::Primary, args.api_url, args.api_token)];
+    for InstanceSpec { name, api_url } in args.instance {
+        let instance = Instance::Named(name);
+        if instances.iter().any(|(inst, _, _)| *inst == instance) {
+            anyhow::bail!("Archive instance {instance} was specified more than once");
+        }
+        instances.push((instance, api_url, None));
+    }
+    let mut local_mounts = Vec::new();
+    for spec in args.local_mount {
+        if local_mounts.iter().any(|(name, _)| *name == spec.name) {
+            anyhow::bail!("Local mount {} was specified more than once", spec.name);
+        }
+        let name = spec.name.clone();
+        let source = LocalSource::new(spec).await?;
+        local_mounts.push((name, source));
+    }
+    let (mount_names, local_mounts): (Vec<_>, Vec<_>) = local_mounts.into_iter().unzip();
+    let path_config = PathConfig {
+        local_mounts: mount_names,
+        ..PathConfig::default()
+    }
+    .with_fast_not_exist(fast_not_exist);
+    let zarr_lookup = ZarrLookup {
+        extensions: if args.zarr_extension.is_empty() {
+            ZarrExtensions::default()
+        } else {
+            ZarrExtensions::new(args.zarr_extension)
+        },
+        max_candidates: args.max_zarr_candidates,
+        exhaustive: args.exhaustive_zarr_lookup,
+    };
+    let http_config = HttpConfig {
+        max_redirects: args.max_redirects,
+        http2_prior_knowledge: args.http2_prior_knowledge,
+        pool_idle_timeout: nonzero_secs(args.pool_idle_timeout),
+        pool_max_idle_per_host: args.pool_max_idle_per_host,
+        tcp_keepalive: nonzero_secs(args.tcp_keepalive),
+    };
+    let mut archives = BTreeMap::new();
+    for (instance, api_url, token) in instances {
+        let dandi = DandiClient::new(
+            api_url,
+            cache_config,
+            retry,
+            s3config,
+            http_config,
+            zarr_lookup.clone(),
+            args.page_prefetch,
+            token,
+        )?
+        .with_instance(instance.clone());
+        dandi
+            .validate_api_url()
+            .await
+            .with_context(|| format!("invalid API URL for Archive instance {instance}"))?;
+        dandi.install_periodic_cache_stats(API_CACHE_STATS_PERIOD);
+        archives.insert(instance, dandi);
+    }
+    let mut zarrfetcher =
+        ManifestFetcher::new(args.zarrman_cache_mb * 1_000_000, retry, http_config)?;
+    if let Some(dir) = args.zarrman_disk_cache {
+        let disk = ManifestDiskCache::open(dir.clone(), args.zarrman_disk_cache_mb * 1_000_000)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to open Zarr manifest disk cache at {}",
+                    dir.display()
+                )
+            })?;
+        zarrfetcher = zarrfetcher.with_disk_cache(disk);
+    }
+    if args.zarrman_max_concurrent_fetches > 0 {
+        zarrfetcher = zarrfetcher.with_fetch_limit(
+            args.zarrman_max_concurrent_fetches,
+            ZARR_MANIFEST_FETCH_QUEUE_TIMEOUT,
+        );
+    }
     zarrfetcher.install_periodic_dump(ZARR_MANIFEST_CACHE_DUMP_PERIOD);
+    if let Some(file) = args.zarrman_pin_file {
+        let paths = read_pin_file(&file).await?;
+        zarrfetcher.install_pinning(paths, file, ZARR_MANIFEST_PIN_REFRESH_PERIOD);
+    }
     let zarrman = ZarrManClient::new(zarrfetcher);
-    let templater = Templater::new(args.title)?;
+    let mut labels = Vec::with_capacity(archives.len());
+    let mut primary_name = None;
+    let upstream = Arc::new(UpstreamVersions::new(archives[&Instance::Primary].clone()));
+    for (instance, dandi) in &archives {
+        match dandi.instance_info().await {
+            Ok(info) => {
+                if *instance == Instance::Primary {
+                    primary_name = info.name().map(ToOwned::to_owned);
+                    upstream.record(Ok(&info));
+                }
+                labels.push(ArchiveLabel::new(instance, &info));
+            }
+            Err(e) => {
+                tracing::warn!(
+                    error = ?e,
+                    "Failed to fetch information about Archive instance {instance}",
+                );
+                if *instance == Instance::Primary {
+                    upstream.record(Err(e));
+                }
+            }
+        }
+    }
+    upstream.install_periodic_probe(UPSTREAM_PROBE_PERIOD);
+    let title = args
+        .title
+        .or(primary_name)
+        .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_owned());
+    let templater = Templater::new(title, labels)?.with_timestamps(TimestampStyle::new(
+        args.html_timestamp_format,
+        args.html_utc_offset,
+    ));
+    let copy = if args.copy_destination.is_empty() {
+        None
+    } else {
+        Some(CopyConfig::new(args.copy_destination)?)
+    };
+    let dandiset_filter = Arc::new(DandisetFilter::new(
+        args.include_dandisets,
+        args.exclude_dandisets,
+    ));
+    let sitemap = args.sitemap_base_url.map(|base_url| {
+        Arc::new(Sitemap::new(
+            archives[&Instance::Primary].clone(),
+            Arc::clone(&dandiset_filter),
+            base_url,
+        ))
+    });
+    let robots_txt = RobotsTxt {
+        policy: args.robots,
+        sitemap: sitemap.as_deref().map(Sitemap::url),
+    };
     let dav = Arc::new(DandiDav {
-        dandi,
+        archives,
         zarrman,
-        templater,
+        templater: Arc::new(templater),
         prefer_s3_redirects: args.prefer_s3_redirects,
+        root_behavior: args.root_redirect,
+        asset_sidecars: args.asset_sidecars,
+        inline_preview_max_bytes: args.inline_preview_max_bytes,
+        infinite_depth_limit: args
+            .allow_infinite_depth
+            .then_some(args.max_infinite_depth_resources),
+        timeouts: RequestTimeouts {
+            listing: nonzero_secs(args.listing_timeout),
+            redirect: nonzero_secs(args.redirect_timeout),
+            stream: nonzero_secs(args.stream_timeout),
+        },
+        path_memo: PathMemo::default(),
+        published_cache: PublishedCache::new(args.published_cache_size),
+        copy,
+        sync_snapshots: SyncSnapshots::new(SYNC_SNAPSHOT_CACHE_SIZE),
+        zarrs_via_manifests: args.zarrs_via_manifests,
+        local_mounts,
+        path_config: Arc::new(path_config),
+        dandiset_filter: Arc::clone(&dandiset_filter),
     });
-    let app = Router::new()
+    if let Some(Command::Mirror(mirror)) = args.command {
+        let report = dav
+            .mirror(&mirror.path, &mirror.outdir, mirror.jobs)
+            .await
+            .with_context(|| format!("failed to mirror {}", mirror.path))?;
+        println!("{report}");
+        return Ok(());
+    }
+    let mut app = Router::new()
         .route(
-            "/.static/styles.css",
+            STYLESHEET_ROUTE,
             get(|| async {
                 // Note: This response should not have WebDAV headers (DAV, Allow)
                 ([(CONTENT_TYPE, CSS_CONTENT_TYPE)], STYLESHEET)
             }),
         )
-        .fallback_service(service_fn(move |req: Request| {
+        .route(
+            TABLE_SCRIPT_ROUTE,
+            get(|| async {
+                // Note: This response should not have WebDAV headers (DAV, Allow)
+                ([(CONTENT_TYPE, JAVASCRIPT_CONTENT_TYPE)], TABLE_SCRIPT)
+            }),
+        )
+        .fallback_service(service_fn({
             let dav = Arc::clone(&dav);
-            async move { dav.handle_request(req).await }
-        }))
-        .layer(middleware::from_fn(handle_head))
+            move |req: Request| {
+                let dav = Arc::clone(&dav);
+                async move { dav.handle_request(req).await }
+            }
+        }));
+    if let Some(security_txt) = security_txt {
+        let body = security_txt.render();
+        app = app.route(
+            SECURITY_TXT_ROUTE,
+            get(|| async {
+                // Note: This response should not have WebDAV headers (DAV, Allow)
+                ([(CONTENT_TYPE, TEXT_CONTENT_TYPE)], body)
+            }),
+        );
+    }
+    if let Some(body) = humans_txt {
+        app = app.route(
+            HUMANS_TXT_ROUTE,
+            get(|| async {
+                // Note: This response should not have WebDAV headers (DAV, Allow)
+                ([(CONTENT_TYPE, TEXT_CONTENT_TYPE)], body)
+            }),
+        );
+    }
+    let body = robots_txt.render();
+    app = app.route(
+        ROBOTS_TXT_ROUTE,
+        get(|| async {
+            // Note: This response should not have WebDAV headers (DAV, Allow)
+            ([(CONTENT_TYPE, TEXT_CONTENT_TYPE)], body)
+        }),
+    );
+    if let Some(sitemap) = sitemap {
+        app = app
+            .route(
+                SITEMAP_INDEX_ROUTE,
+                get({
+                    let sitemap = Arc::clone(&sitemap);
+                    move || async move { sitemap.index_response().await }
+                }),
+            )
+            .route(
+                SITEMAP_PAGE_ROUTE,
+                get(move |Path(page): Path<String>| async move {
+                    sitemap.page_response(&page).await
+                }),
+            );
+    }
+    if args.asset_redirects {
+        let handler = {
+            let dav = Arc::clone(&dav);
+            move |Path(asset_id): Path<String>, headers: HeaderMap| async move {
+                // Note: This response should not have WebDAV headers (DAV, Allow)
+                dav.redirect_asset(&asset_id, &headers).await
+            }
+        };
+        app = app
+            .route(ASSET_REDIRECT_ROUTE, get(handler.clone()))
+            .route(&format!("{ASSET_REDIRECT_ROUTE}/"), get(handler));
+    }
+    let class_limit = |per_second: Option<u32>, burst: Option<u32>| {
+        per_second.or(args.rate_limit).map(|per_second| RateLimit {
+            per_second,
+            burst: burst.unwrap_or(args.rate_limit_burst),
+        })
+    };
+    let client_ips = ClientIpSource {
+        trusted_proxies: args.trusted_proxies,
+    };
+    let rate_limits = RateLimits {
+        normal: class_limit(None, None),
+        expensive: class_limit(args.expensive_rate_limit, args.expensive_rate_limit_burst),
+        cheap: class_limit(args.cheap_rate_limit, args.cheap_rate_limit_burst),
+        exempt: args.rate_limit_exempt,
+        client_ips,
+    };
+    let limited = rate_limits.is_limited() || maintenance_rules.is_some();
+    if rate_limits.is_limited() {
+        app = limit_rate(app, rate_limits);
+    }
+    if let Some(rules) = maintenance_rules {
+        let maintenance = Maintenance {
+            rules,
+            retry_after: Duration::from_secs(args.maintenance_retry_after),
+        };
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(maintenance),
+            shed_agents,
+        ));
+    }
+    if limited {
+        app = app.layer(middleware::from_fn_with_state(client_ips, log_rejections));
+    }
+    // Routes added from here on are not subject to rate limiting or
+    // maintenance rules
+    app = app
+        .route(HEALTHZ_ROUTE, get(|| async { health::liveness() }))
+        .route(
+            READYZ_ROUTE,
+            get({
+                let upstream = Arc::clone(&upstream);
+                move || async move { health::readiness(&dav, &upstream).await }
+            }),
+        )
+        .route(
+            ABOUT_ROUTE,
+            get(move || async move { upstream.to_response() }),
+        );
+    let error_log = (args.error_log_size > 0).then(|| Arc::new(ErrorLog::new(args.error_log_size)));
+    if let Some(ref log) = error_log {
+        let log = Arc::clone(log);
+        app = app.route(
+            ADMIN_ERRORS_ROUTE,
+            get(move || async move { log.to_response() }),
+        );
+    }
+    if args.metrics {
+        app = app.route(
+            METRICS_ROUTE,
+            get(|| async {
+                // Note: This response should not have WebDAV headers (DAV, Allow)
+                ([(CONTENT_TYPE, METRICS_CONTENT_TYPE)], metrics().render())
+            }),
+        );
+    }
+    app = app.layer(middleware::from_fn(handle_head));
+    if let Some(log) = error_log {
+        app = app.layer(middleware::from_fn_with_state(log, record_errors));
+    }
+    if args.access_log {
+        app = app.layer(middleware::from_fn(log_access));
+    }
+    if args.usage_stats {
+        let stats = Arc::new(UsageStats::new(client_ips));
+        stats.install_periodic_report(USAGE_STATS_CHECK_PERIOD);
+        app = app.layer(middleware::from_fn_with_state(stats, record_usage));
+    }
+    let app = app
+        .layer(middleware::from_fn(count_in_flight))
         .layer(middleware::from_fn(log_memory))
         .layer(SetResponseHeaderLayer::if_not_present(
             SERVER,
             HeaderValue::from_static(SERVER_VALUE),
         ))
-        .layer(SetResponseHeaderLayer::if_not_present(
-            ACCESS_CONTROL_ALLOW_ORIGIN,
-            HeaderValue::from_static("*"),
-        ))
+        .layer(cors_layer(args.cors_origin))
         .layer(TraceLayer::new_for_http());
-    let listener = tokio::net::TcpListener::bind((args.ip_addr, args.port))
-        .await
-        .context("failed to bind listener")?;
-    axum::serve(listener, app)
-        .await
-        .context("failed to serve application")?;
+    let mut listener = ServerListener::open(
+        systemd_fds,
+        args.uds.as_deref(),
+        SocketAddr::from((args.ip_addr, args.port)),
+    )
+    .await
+    .context("failed to bind listener")?;
+    let mut redirector = None;
+    if let (Some(cert), Some(key)) = (args.tls_cert, args.tls_key) {
+        let acceptor =
+            load_tls_config(&cert, &key).context("failed to load TLS certificate & key")?;
+        listener = listener.with_tls(acceptor)?;
+        if let Some(port) = args.http_redirect_port {
+            let http_listener = tokio::net::TcpListener::bind((args.ip_addr, port))
+                .await
+                .context("failed to bind HTTP redirect listener")?;
+            redirector = Some((http_listener, listener.port().unwrap_or(args.port)));
+        }
+    }
+    let serving = async {
+        listener
+            .serve(app)
+            .await
+            .context("failed to serve application")
+    };
+    if let Some((http_listener, https_port)) = redirector {
+        tokio::try_join!(serving, async {
+            serve_https_redirects(http_listener, https_port)
+                .await
+                .context("failed to serve HTTP redirects")
+        })?;
+    } else {
+        serving.await?;
+    }
     Ok(())
 }
 
+/// Convert a number of seconds given on the command line to a `Duration`,
+/// with 0 meaning "no limit"
+fn nonzero_secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
 /// Handle `HEAD` requests by converting them to `GET` requests and discarding
-/// the resulting response body
+/// the resulting response body.
+///
+/// The `Content-Length` of the response is determined as follows:
+///
+/// - If the response is a redirect to a non-collection resource of known size
+///   (as indicated by a [`RedirectTargetSize`] extension), the size of the
+///   redirect target is reported along with an `Accept-Ranges: bytes` header.
+///
+/// - Otherwise, if the body size was recorded by the DAV layer in a
+///   [`BodySize`] extension, that size is used.
+///
+/// - Otherwise, if the body's size hint is exact, that is used.
+///
+/// - Otherwise, no `Content-Length` is reported.
 async fn handle_head(method: Method, mut request: Request<Body>, next: Next) -> Response<Body> {
     if method == Method::HEAD {
         *request.method_mut() = Method::GET;
         let mut resp = next.run(request).await;
-        if let Some(sz) = resp.body().size_hint().exact() {
+        if let Some(&RedirectTargetSize(sz)) = resp.extensions().get::<RedirectTargetSize>() {
+            resp.headers_mut().insert(CONTENT_LENGTH, sz.into());
+            resp.headers_mut()
+                .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        } else if let Some(sz) = resp
+            .extensions()
+            .get::<BodySize>()
+            .map(|&BodySize(sz)| sz)
+            .or_else(|| resp.body().size_hint().exact())
+        {
             resp.headers_mut().insert(CONTENT_LENGTH, sz.into());
         }
         *resp.body_mut() = Body::empty();
@@ -161,6 +1106,12 @@ async fn handle_head(method: Method, mut request: Request<Body>, next: Next) ->
     }
 }
 
+/// Keep track of the number of requests currently being handled
+async fn count_in_flight(request: Request<Body>, next: Next) -> Response<Body> {
+    let _guard = metrics().start_request();
+    next.run(request).await
+}
+
 async fn log_memory(request: Request<Body>, next: Next) -> Response<Body> {
     fn getmem(rel: &str) -> Option<memory_stats::MemoryStats> {
         if let Some(stats) = memory_stats::memory_stats() {
@@ -214,3 +1165,214 @@ impl fmt::Display for UsizeDiff {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header::LOCATION, StatusCode};
+    use axum::response::{IntoResponse, Redirect};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/blob",
+                get(|| async {
+                    let mut resp = Redirect::temporary("https://example.com/blob").into_response();
+                    resp.extensions_mut().insert(RedirectTargetSize(1234));
+                    resp
+                }),
+            )
+            .route("/text", get(|| async { "Hello!" }))
+            .route(
+                "/stream",
+                get(|| async {
+                    let body = Body::from_stream(futures_util::stream::iter([
+                        Ok::<_, std::convert::Infallible>("Hello, "),
+                        Ok("world!"),
+                    ]));
+                    let mut resp = body.into_response();
+                    resp.extensions_mut().insert(BodySize(13));
+                    resp
+                }),
+            )
+            .layer(middleware::from_fn(handle_head))
+    }
+
+    async fn request(method: Method, path: &str) -> Response<Body> {
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        app().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn head_redirect_with_size() {
+        let resp = request(Method::HEAD, "/blob").await;
+        assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(resp.headers()[LOCATION], "https://example.com/blob");
+        assert_eq!(resp.headers()[CONTENT_LENGTH], "1234");
+        assert_eq!(resp.headers()[ACCEPT_RANGES], "bytes");
+    }
+
+    #[tokio::test]
+    async fn get_redirect_with_size() {
+        let resp = request(Method::GET, "/blob").await;
+        assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert!(!resp.headers().contains_key(ACCEPT_RANGES));
+        assert_ne!(
+            resp.headers()
+                .get(CONTENT_LENGTH)
+                .map(HeaderValue::as_bytes),
+            Some(&b"1234"[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn head_plain() {
+        let resp = request(Method::HEAD, "/text").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_LENGTH], "6");
+        assert!(!resp.headers().contains_key(ACCEPT_RANGES));
+    }
+
+    #[tokio::test]
+    async fn head_streamed_with_body_size() {
+        let resp = request(Method::HEAD, "/stream").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_LENGTH], "13");
+    }
+
+    #[test]
+    fn version_json() {
+        let args = Arguments::try_parse_from(["dandidav", "--version", "--json"]).unwrap();
+        assert!(args.version);
+        assert!(args.json);
+        let args = Arguments::try_parse_from(["dandidav", "-V"]).unwrap();
+        assert!(args.version);
+        assert!(!args.json);
+    }
+
+    #[test]
+    fn json_requires_version() {
+        let e = Arguments::try_parse_from(["dandidav", "--json"]).unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+
+    #[rstest::rstest]
+    #[case("--include-dandisets")]
+    #[case("--exclude-dandisets")]
+    fn asset_redirects_conflict_with_dandiset_filter(#[case] opt: &str) {
+        let e = Arguments::try_parse_from(["dandidav", "--asset-redirects", opt, "000027"])
+            .unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn security_txt_args() {
+        let args = Arguments::try_parse_from([
+            "dandidav",
+            "--security-contact",
+            "security@example.com",
+            "--security-contact",
+            "https://example.com/report",
+            "--security-expires",
+            "2030-01-01T00:00:00Z",
+        ])
+        .unwrap();
+        assert_eq!(args.security_contact.len(), 2);
+        assert!(args.security_expires.is_some());
+        assert_eq!(args.security_policy, None);
+    }
+
+    #[rstest::rstest]
+    #[case(&["--security-contact", "security@example.com"])]
+    #[case(&["--security-expires", "2030-01-01T00:00:00Z"])]
+    #[case(&["--security-policy", "https://example.com/policy"])]
+    fn security_txt_args_incomplete(#[case] opts: &[&str]) {
+        let e = Arguments::try_parse_from(std::iter::once(&"dandidav").chain(opts)).unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn rate_limit_args() {
+        let args = Arguments::try_parse_from(["dandidav"]).unwrap();
+        assert_eq!(args.rate_limit, None);
+        assert_eq!(args.rate_limit_burst, DEFAULT_RATE_LIMIT_BURST);
+        let args = Arguments::try_parse_from(["dandidav", "--rate-limit", "10"]).unwrap();
+        assert_eq!(args.rate_limit, Some(10));
+        let e = Arguments::try_parse_from(["dandidav", "--rate-limit", "0"]).unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn rate_limit_class_args() {
+        let args = Arguments::try_parse_from(["dandidav"]).unwrap();
+        assert_eq!(args.expensive_rate_limit, None);
+        assert_eq!(args.cheap_rate_limit, None);
+        assert!(args.rate_limit_exempt.is_empty());
+        assert_eq!(args.trusted_proxies, 0);
+        let args = Arguments::try_parse_from([
+            "dandidav",
+            "--expensive-rate-limit",
+            "2",
+            "--expensive-rate-limit-burst",
+            "5",
+            "--cheap-rate-limit",
+            "100",
+            "--rate-limit-exempt",
+            "10.0.0.0/8",
+            "--rate-limit-exempt",
+            "2001:db8::1",
+            "--trusted-proxies",
+            "1",
+        ])
+        .unwrap();
+        assert_eq!(args.trusted_proxies, 1);
+        assert_eq!(args.expensive_rate_limit, Some(2));
+        assert_eq!(args.expensive_rate_limit_burst, Some(5));
+        assert_eq!(args.cheap_rate_limit, Some(100));
+        assert_eq!(args.cheap_rate_limit_burst, None);
+        assert_eq!(
+            args.rate_limit_exempt
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["10.0.0.0/8", "2001:db8::1/128"]
+        );
+        let e = Arguments::try_parse_from(["dandidav", "--rate-limit-exempt", "10.0.0.0/40"])
+            .unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn mirror_args() {
+        let args = Arguments::try_parse_from(["dandidav"]).unwrap();
+        assert_eq!(args.command, None);
+        let args = Arguments::try_parse_from([
+            "dandidav",
+            "--api-url",
+            "https://api.example.com/api",
+            "mirror",
+            "-J",
+            "8",
+            "dandisets/000027/draft/",
+            "out",
+        ])
+        .unwrap();
+        assert_eq!(args.api_url.as_str(), "https://api.example.com/api");
+        assert_eq!(
+            args.command,
+            Some(Command::Mirror(MirrorArguments {
+                jobs: 8,
+                path: "dandisets/000027/draft/".into(),
+                outdir: PathBuf::from("out"),
+            }))
+        );
+        let e = Arguments::try_parse_from(["dandidav", "mirror", "dandisets/000027/draft/"])
+            .unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+}
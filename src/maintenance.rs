@@ -0,0 +1,277 @@
+//! Shedding of traffic from selected clients during maintenance & incidents
+use crate::ratelimit::Rejected;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{
+        header::{HeaderValue, RETRY_AFTER, USER_AGENT},
+        response::Response,
+        StatusCode,
+    },
+    middleware::Next,
+    response::IntoResponse,
+};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The name under which rejections by the maintenance rules are logged &
+/// counted
+const MAINTENANCE: &str = "maintenance";
+
+/// An ordered list of rules for deciding whether to serve a request based on
+/// its `User-Agent` header, as read from the file given via
+/// `--maintenance-rules`.
+///
+/// Each line of the file is either blank, a comment (starting with `#`), or a
+/// rule of the form `allow PATTERN` or `deny PATTERN`.  A pattern of `*`
+/// matches every request; any other pattern matches requests whose
+/// `User-Agent` contains it as a case-insensitive substring.  Each request is
+/// allowed or denied according to the first rule that matches it; requests
+/// that match no rules are allowed.
+///
+/// For example, the following rules shed all traffic other than that from
+/// `dandi-cli`:
+///
+/// ```text
+/// # Incident in progress; only serve dandi-cli
+/// allow dandi-cli
+/// deny *
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct UserAgentRules(Vec<UserAgentRule>);
+
+impl UserAgentRules {
+    /// Returns `true` if a request with the given `User-Agent` (if any) is
+    /// allowed by the rules
+    fn allows(&self, user_agent: Option<&str>) -> bool {
+        let user_agent = user_agent.map(str::to_lowercase);
+        self.0
+            .iter()
+            .find(|rule| rule.matches(user_agent.as_deref()))
+            .map_or(true, |rule| rule.action == RuleAction::Allow)
+    }
+
+    /// Returns `true` if there are no rules
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Return the number of rules
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl FromStr for UserAgentRules {
+    type Err = ParseUserAgentRulesError;
+
+    fn from_str(s: &str) -> Result<UserAgentRules, ParseUserAgentRulesError> {
+        let mut rules = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |kind| ParseUserAgentRulesError { line: i + 1, kind };
+            let (action, pattern) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| err(RuleErrorKind::NoPattern))?;
+            let action = match action {
+                "allow" => RuleAction::Allow,
+                "deny" => RuleAction::Deny,
+                _ => return Err(err(RuleErrorKind::BadAction)),
+            };
+            let pattern = match pattern.trim() {
+                "*" => None,
+                p => Some(p.to_lowercase()),
+            };
+            rules.push(UserAgentRule { action, pattern });
+        }
+        Ok(UserAgentRules(rules))
+    }
+}
+
+/// A single rule in a [`UserAgentRules`] list
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct UserAgentRule {
+    /// Whether to serve requests that match the rule
+    action: RuleAction,
+
+    /// The lowercased substring that a request's `User-Agent` must contain
+    /// in order to match the rule, or `None` if the rule matches all requests
+    pattern: Option<String>,
+}
+
+impl UserAgentRule {
+    /// Returns `true` if the rule matches a request with the given lowercased
+    /// `User-Agent` (if any)
+    fn matches(&self, user_agent: Option<&str>) -> bool {
+        match (&self.pattern, user_agent) {
+            (None, _) => true,
+            (Some(pattern), Some(ua)) => ua.contains(pattern.as_str()),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RuleAction {
+    Allow,
+    Deny,
+}
+
+/// Error returned when parsing an invalid [`UserAgentRules`] file
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid rule on line {line}: {kind}")]
+pub(crate) struct ParseUserAgentRulesError {
+    line: usize,
+    kind: RuleErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+enum RuleErrorKind {
+    #[error("expected \"allow\" or \"deny\"")]
+    BadAction,
+    #[error("rule lacks pattern")]
+    NoPattern,
+}
+
+/// The state used by [`shed_agents()`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Maintenance {
+    /// The rules deciding which requests to serve
+    pub(crate) rules: UserAgentRules,
+
+    /// How long clients whose requests are denied should wait before trying
+    /// again
+    pub(crate) retry_after: Duration,
+}
+
+/// Respond to each request that is denied by the [`UserAgentRules`] with a
+/// 503 response with a `Retry-After` header instead of handling it.  Denied
+/// requests are marked with a [`Rejected`] extension so that they are logged
+/// & counted by [`log_rejections()`][crate::ratelimit::log_rejections].
+pub(crate) async fn shed_agents(
+    State(maintenance): State<Arc<Maintenance>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    if maintenance.rules.allows(user_agent) {
+        next.run(request).await
+    } else {
+        let mut resp = (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Service temporarily unavailable for this client due to maintenance; try again later\n",
+        )
+            .into_response();
+        resp.headers_mut().insert(
+            RETRY_AFTER,
+            HeaderValue::from(maintenance.retry_after.as_secs()),
+        );
+        resp.extensions_mut().insert(Rejected {
+            limiter: MAINTENANCE,
+        });
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use axum::{middleware, routing::get, Router};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    static RULES: &str = concat!(
+        "# Only serve dandi-cli and one particular browser\n",
+        "\n",
+        "allow dandi-cli\n",
+        "  allow  Firefox/130\n",
+        "deny Firefox\n",
+        "deny *\n",
+    );
+
+    #[rstest]
+    #[case(Some("dandi/0.63.0 requests/2.32.3 (dandi-cli)"), true)]
+    #[case(Some("DANDI-CLI"), true)]
+    #[case(Some("Mozilla/5.0 (X11; Linux x86_64) Firefox/130.0"), true)]
+    #[case(Some("Mozilla/5.0 (X11; Linux x86_64) Firefox/131.0"), false)]
+    #[case(Some("curl/8.5.0"), false)]
+    #[case(None, false)]
+    fn test_allows(#[case] user_agent: Option<&str>, #[case] allowed: bool) {
+        let rules = RULES.parse::<UserAgentRules>().unwrap();
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules.allows(user_agent), allowed);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("# Nothing to see here\n\n   \n")]
+    fn test_empty(#[case] s: &str) {
+        let rules = s.parse::<UserAgentRules>().unwrap();
+        assert!(rules.is_empty());
+        assert!(rules.allows(None));
+        assert!(rules.allows(Some("curl/8.5.0")));
+    }
+
+    #[test]
+    fn test_no_match_allowed() {
+        let rules = "deny curl".parse::<UserAgentRules>().unwrap();
+        assert!(rules.allows(None));
+        assert!(rules.allows(Some("dandi-cli")));
+        assert!(!rules.allows(Some("curl/8.5.0")));
+    }
+
+    #[rstest]
+    #[case("allow", 1, RuleErrorKind::NoPattern)]
+    #[case(
+        "# Comment\nallow dandi-cli\npermit curl\n",
+        3,
+        RuleErrorKind::BadAction
+    )]
+    #[case("Allow dandi-cli", 1, RuleErrorKind::BadAction)]
+    fn test_parse_err(#[case] s: &str, #[case] line: usize, #[case] kind: RuleErrorKind) {
+        assert_matches!(s.parse::<UserAgentRules>(), Err(e) => {
+            assert_eq!(e, ParseUserAgentRulesError { line, kind });
+        });
+    }
+
+    #[tokio::test]
+    async fn sheds_denied_agents() {
+        let maintenance = Maintenance {
+            rules: RULES.parse().unwrap(),
+            retry_after: Duration::from_secs(120),
+        };
+        let app = Router::new().route("/", get(|| async { "Hello" })).layer(
+            middleware::from_fn_with_state(Arc::new(maintenance), shed_agents),
+        );
+        let request = |ua: &str| {
+            Request::get("/")
+                .header(USER_AGENT, ua)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app.clone().oneshot(request("dandi-cli")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.extensions().get::<Rejected>().is_none());
+        let resp = app.oneshot(request("curl/8.5.0")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            resp.headers().get(RETRY_AFTER),
+            Some(&HeaderValue::from_static("120"))
+        );
+        assert_eq!(
+            resp.extensions().get::<Rejected>(),
+            Some(&Rejected {
+                limiter: MAINTENANCE
+            })
+        );
+    }
+}
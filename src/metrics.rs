@@ -0,0 +1,312 @@
+//! Prometheus metrics for monitoring `dandidav`
+//!
+//! Metrics are recorded in a process-wide [`Metrics`] instance (obtained via
+//! [`metrics()`]) regardless of configuration; they are only exposed over
+//! HTTP at `/metrics` when the `--metrics` option is given.
+use crate::accesslog::{note_cache_lookup, note_upstream_call};
+use prometheus_client::encoding::{text::encode, EncodeLabelSet};
+use prometheus_client::metrics::{
+    counter::Counter,
+    family::Family,
+    gauge::Gauge,
+    histogram::{exponential_buckets, Histogram},
+};
+use prometheus_client::registry::Registry;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Return the process-wide [`Metrics`] instance
+pub(crate) fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// A collection of metrics about the server and its requests to upstream
+/// services
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    /// The registry in which all of the metrics are registered
+    registry: Registry,
+
+    /// Number of WebDAV requests handled, by method, type of request path,
+    /// and response status
+    requests: Family<RequestLabels, Counter>,
+
+    /// Number of HTTP requests currently being handled
+    in_flight: Gauge,
+
+    /// Durations of individual HTTP requests made to upstream services (the
+    /// Archive API, the Zarr manifest tree, etc.), by host and method
+    upstream_duration: Family<UpstreamLabels, Histogram>,
+
+    /// Number of cache lookups, by cache and result (hit or miss)
+    cache_lookups: Family<CacheLabels, Counter>,
+
+    /// Durations of individual S3 `ListObjectsV2` requests
+    s3_list_duration: Histogram,
+
+    /// Number of requests rejected by limiters (e.g., the rate limiter), by
+    /// limiter
+    rejections: Family<RejectionLabels, Counter>,
+
+    /// Sizes of the bodies of WebDAV requests that declare a
+    /// `Content-Length`, by method and type of request path
+    request_size: Family<RequestSizeLabels, Histogram>,
+
+    /// Sizes of the bodies of WebDAV responses, by type of request path and
+    /// kind of response
+    response_size: Family<ResponseSizeLabels, Histogram>,
+}
+
+impl Metrics {
+    /// Create & register all metrics
+    fn new() -> Metrics {
+        let mut registry = Registry::with_prefix(env!("CARGO_CRATE_NAME"));
+        let requests = Family::<RequestLabels, Counter>::default();
+        registry.register(
+            "requests",
+            "Number of WebDAV requests handled",
+            requests.clone(),
+        );
+        let in_flight = Gauge::default();
+        registry.register(
+            "requests_in_flight",
+            "Number of HTTP requests currently being handled",
+            in_flight.clone(),
+        );
+        let upstream_duration: Family<UpstreamLabels, Histogram> =
+            Family::new_with_constructor(duration_histogram);
+        registry.register(
+            "upstream_request_duration_seconds",
+            "Durations of HTTP requests to upstream services",
+            upstream_duration.clone(),
+        );
+        let cache_lookups = Family::<CacheLabels, Counter>::default();
+        registry.register(
+            "cache_lookups",
+            "Number of cache lookups",
+            cache_lookups.clone(),
+        );
+        let s3_list_duration = duration_histogram();
+        registry.register(
+            "s3_list_duration_seconds",
+            "Durations of S3 ListObjectsV2 requests",
+            s3_list_duration.clone(),
+        );
+        let rejections = Family::<RejectionLabels, Counter>::default();
+        registry.register(
+            "rejected_requests",
+            "Number of requests rejected by limiters",
+            rejections.clone(),
+        );
+        let request_size: Family<RequestSizeLabels, Histogram> =
+            Family::new_with_constructor(size_histogram);
+        registry.register(
+            "request_size_bytes",
+            "Sizes of WebDAV request bodies",
+            request_size.clone(),
+        );
+        let response_size: Family<ResponseSizeLabels, Histogram> =
+            Family::new_with_constructor(size_histogram);
+        registry.register(
+            "response_size_bytes",
+            "Sizes of WebDAV response bodies",
+            response_size.clone(),
+        );
+        Metrics {
+            registry,
+            requests,
+            in_flight,
+            upstream_duration,
+            cache_lookups,
+            s3_list_duration,
+            rejections,
+            request_size,
+            response_size,
+        }
+    }
+
+    /// Render the current values of all metrics in the OpenMetrics text
+    /// format
+    pub(crate) fn render(&self) -> String {
+        let mut s = String::new();
+        encode(&mut s, &self.registry).expect("writing to a String should not fail");
+        s
+    }
+
+    /// Record the handling of a WebDAV request with the given method, type of
+    /// request path, and response status
+    pub(crate) fn record_request(&self, method: &'static str, path: &'static str, status: u16) {
+        self.requests
+            .get_or_create(&RequestLabels {
+                method,
+                path,
+                status,
+            })
+            .inc();
+    }
+
+    /// Record the start of the handling of an HTTP request.  The returned
+    /// guard records the end of the handling when dropped.
+    pub(crate) fn start_request(&self) -> InFlightGuard<'_> {
+        self.in_flight.inc();
+        InFlightGuard(&self.in_flight)
+    }
+
+    /// Record that an HTTP request with the given method to the given host
+    /// took `elapsed` to complete.  The request is also counted in the access
+    /// log record of the request being handled, if any.
+    pub(crate) fn record_upstream(&self, host: String, method: String, elapsed: Duration) {
+        note_upstream_call();
+        self.upstream_duration
+            .get_or_create(&UpstreamLabels { host, method })
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Record a lookup in the cache named `cache`.  The lookup is also counted
+    /// in the access log record of the request being handled, if any.
+    pub(crate) fn record_cache_lookup(&self, cache: &'static str, hit: bool) {
+        note_cache_lookup(hit);
+        self.cache_lookups
+            .get_or_create(&CacheLabels {
+                cache,
+                result: if hit { "hit" } else { "miss" },
+            })
+            .inc();
+    }
+
+    /// Record that an S3 `ListObjectsV2` request took `elapsed` to complete
+    pub(crate) fn record_s3_list(&self, elapsed: Duration) {
+        self.s3_list_duration.observe(elapsed.as_secs_f64());
+    }
+
+    /// Record that a request was rejected by the limiter named `limiter`
+    pub(crate) fn record_rejection(&self, limiter: &'static str) {
+        self.rejections
+            .get_or_create(&RejectionLabels { limiter })
+            .inc();
+    }
+
+    /// Record that a WebDAV request with the given method & type of request
+    /// path had a body of `size` bytes
+    pub(crate) fn record_request_size(&self, method: &'static str, path: &'static str, size: u64) {
+        #[allow(clippy::cast_precision_loss)]
+        self.request_size
+            .get_or_create(&RequestSizeLabels { method, path })
+            .observe(size as f64);
+    }
+
+    /// Record that the response to a WebDAV request for the given type of
+    /// request path was of the given kind (e.g., `"html"` or `"redirect"`)
+    /// and had a body of `size` bytes
+    pub(crate) fn record_response_size(&self, path: &'static str, kind: &'static str, size: u64) {
+        #[allow(clippy::cast_precision_loss)]
+        self.response_size
+            .get_or_create(&ResponseSizeLabels { path, kind })
+            .observe(size as f64);
+    }
+}
+
+/// A guard returned by [`Metrics::start_request()`] that decrements the
+/// in-flight request gauge when dropped
+#[derive(Debug)]
+pub(crate) struct InFlightGuard<'a>(&'a Gauge);
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Labels for [`Metrics::requests`]
+#[derive(Clone, Debug, EncodeLabelSet, Eq, Hash, PartialEq)]
+struct RequestLabels {
+    method: &'static str,
+    path: &'static str,
+    status: u16,
+}
+
+/// Labels for [`Metrics::upstream_duration`]
+#[derive(Clone, Debug, EncodeLabelSet, Eq, Hash, PartialEq)]
+struct UpstreamLabels {
+    host: String,
+    method: String,
+}
+
+/// Labels for [`Metrics::cache_lookups`]
+#[derive(Clone, Debug, EncodeLabelSet, Eq, Hash, PartialEq)]
+struct CacheLabels {
+    cache: &'static str,
+    result: &'static str,
+}
+
+/// Labels for [`Metrics::rejections`]
+#[derive(Clone, Debug, EncodeLabelSet, Eq, Hash, PartialEq)]
+struct RejectionLabels {
+    limiter: &'static str,
+}
+
+/// Labels for [`Metrics::request_size`]
+#[derive(Clone, Debug, EncodeLabelSet, Eq, Hash, PartialEq)]
+struct RequestSizeLabels {
+    method: &'static str,
+    path: &'static str,
+}
+
+/// Labels for [`Metrics::response_size`]
+#[derive(Clone, Debug, EncodeLabelSet, Eq, Hash, PartialEq)]
+struct ResponseSizeLabels {
+    path: &'static str,
+    kind: &'static str,
+}
+
+/// Construct a histogram for durations in seconds, with buckets ranging from
+/// 5 milliseconds to about 40 seconds
+fn duration_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.005, 2.0, 14))
+}
+
+/// Construct a histogram for sizes in bytes, with buckets ranging from 64
+/// bytes to 256 MiB
+fn size_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(64.0, 4.0, 12))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let m = Metrics::new();
+        m.record_request("GET", "dandiset", 200);
+        m.record_request("GET", "dandiset", 200);
+        m.record_cache_lookup("zarr-manifests", true);
+        m.record_rejection("rate-limit");
+        m.record_request_size("PROPFIND", "dandiset", 100);
+        m.record_response_size("dandiset", "html", 1000);
+        m.record_response_size("dandiset", "html", 5000);
+        {
+            let _guard = m.start_request();
+            let s = m.render();
+            assert!(s.contains("dandidav_requests_in_flight 1\n"));
+        }
+        let s = m.render();
+        assert!(
+            s.contains(r#"dandidav_requests_total{method="GET",path="dandiset",status="200"} 2"#)
+        );
+        assert!(
+            s.contains(r#"dandidav_cache_lookups_total{cache="zarr-manifests",result="hit"} 1"#)
+        );
+        assert!(s.contains(r#"dandidav_rejected_requests_total{limiter="rate-limit"} 1"#));
+        assert!(
+            s.contains(r#"dandidav_request_size_bytes_count{method="PROPFIND",path="dandiset"} 1"#)
+        );
+        assert!(s.contains(r#"dandidav_response_size_bytes_sum{path="dandiset",kind="html"} 6000"#));
+        assert!(s.contains(
+            r#"dandidav_response_size_bytes_bucket{le="1024.0",path="dandiset",kind="html"} 1"#
+        ));
+        assert!(s.contains("dandidav_requests_in_flight 0\n"));
+        assert!(s.ends_with("# EOF\n"));
+    }
+}
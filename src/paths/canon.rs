@@ -0,0 +1,221 @@
+//! Canonicalization rules shared by the parsers of request paths for the
+//! `/dandisets/` and `/zarrs/` hierarchies, so that the hierarchies agree on
+//! which paths they accept
+use super::{Component, ParseComponentError};
+use crate::consts::ZARR_EXTENSIONS;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Canonicalize a sequence of path segments (as obtained by splitting a path
+/// on forward slashes) into a sequence of [`Component`]s.
+///
+/// Single-dot segments are ignored.  Double-dot segments are discarded along
+/// with the immediately preceding component, if any; a double-dot segment at
+/// the root is simply discarded.
+///
+/// # Errors
+///
+/// Returns `Err` if any segment is empty, contains a forward slash, or
+/// contains NUL
+pub(crate) fn canonicalize<'a, I>(segments: I) -> Result<Vec<Component>, ParseComponentError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut parts = Vec::new();
+    for s in segments {
+        match s.parse::<Component>() {
+            Ok(c) => parts.push(c),
+            Err(ParseComponentError::CurDir) => (),
+            Err(ParseComponentError::ParentDir) => {
+                let _ = parts.pop();
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(parts)
+}
+
+/// The set of filename extensions that mark a path component in a Dandiset
+/// version as a potential Zarr asset.  Extensions are matched
+/// case-insensitively.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ZarrExtensions(Arc<[ZarrExtension]>);
+
+impl ZarrExtensions {
+    pub(crate) fn new<I: IntoIterator<Item = ZarrExtension>>(iter: I) -> ZarrExtensions {
+        ZarrExtensions(iter.into_iter().collect())
+    }
+
+    /// Returns `true` if the path component `name` has one of the extensions
+    /// (compared case-insensitively) preceded by a nonempty stem
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        self.0.iter().any(|ext| {
+            name.len()
+                .checked_sub(ext.0.len())
+                .filter(|&i| i > 0)
+                .and_then(|i| name.get(i..))
+                .is_some_and(|suffix| suffix.eq_ignore_ascii_case(&ext.0))
+        })
+    }
+}
+
+impl Default for ZarrExtensions {
+    /// The default extensions: [`ZARR_EXTENSIONS`]
+    fn default() -> ZarrExtensions {
+        ZarrExtensions::new(
+            ZARR_EXTENSIONS
+                .into_iter()
+                .map(|ext| ZarrExtension(ext.into())),
+        )
+    }
+}
+
+/// A filename extension, including the leading period, that marks a path
+/// component as a potential Zarr asset
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ZarrExtension(String);
+
+impl FromStr for ZarrExtension {
+    type Err = ParseZarrExtensionError;
+
+    /// Parse a filename extension.  The leading period is optional.
+    fn from_str(s: &str) -> Result<ZarrExtension, ParseZarrExtensionError> {
+        let ext = s.strip_prefix('.').unwrap_or(s);
+        if ext.is_empty() || ext.contains(['/', '\0']) {
+            Err(ParseZarrExtensionError)
+        } else {
+            Ok(ZarrExtension(format!(".{ext}")))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid Zarr extension: expected a nonempty extension without slashes, e.g., .zarr")]
+pub(crate) struct ParseZarrExtensionError;
+
+/// If the path component `c` consists of a Zarr checksum followed by the
+/// extension `ext` (including the leading period), return the checksum.
+///
+/// A checksum is a nonempty string that does not contain a period.  Returns
+/// `None` if `c` does not end with `ext` or if the remainder is not a
+/// checksum.
+pub(crate) fn strip_checksum_ext(c: &Component, ext: &str) -> Option<Component> {
+    c.strip_suffix(ext)
+        .filter(|checksum| !checksum.contains('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&[], &[])]
+    #[case(&["foo"], &["foo"])]
+    #[case(&["foo", "bar"], &["foo", "bar"])]
+    #[case(&["."], &[])]
+    #[case(&[".."], &[])]
+    #[case(&["..", "foo"], &["foo"])]
+    #[case(&["foo", "."], &["foo"])]
+    #[case(&[".", "foo", ".", "bar", "."], &["foo", "bar"])]
+    #[case(&["foo", ".."], &[])]
+    #[case(&["foo", "..", "bar"], &["bar"])]
+    #[case(&["foo", "bar", "..", ".."], &[])]
+    #[case(&["foo", "bar", "..", "..", ".."], &[])]
+    #[case(&["foo", "bar", "..", "baz"], &["foo", "baz"])]
+    #[case(&["foo", ".", ".."], &[])]
+    #[case(&["...", ".foo", "foo."], &["...", ".foo", "foo."])]
+    #[case(&["foo bar", "%2e"], &["foo bar", "%2e"])]
+    fn test_canonicalize(#[case] segments: &[&str], #[case] components: &[&str]) {
+        let parts = canonicalize(segments.iter().copied()).unwrap();
+        assert_eq!(parts, components);
+    }
+
+    #[rstest]
+    #[case(&[""], ParseComponentError::Empty)]
+    #[case(&["foo", "", "bar"], ParseComponentError::Empty)]
+    #[case(&["foo/bar"], ParseComponentError::Slash)]
+    #[case(&["foo\0bar"], ParseComponentError::Nul)]
+    #[case(&["foo", "..", "\0"], ParseComponentError::Nul)]
+    fn test_canonicalize_err(#[case] segments: &[&str], #[case] err: ParseComponentError) {
+        assert_matches!(canonicalize(segments.iter().copied()), Err(e) => {
+            assert_eq!(e, err);
+        });
+    }
+
+    #[rstest]
+    #[case("foo.zarr", true)]
+    #[case("foo.ngff", true)]
+    #[case("foo.ome.zarr", true)]
+    #[case("foo.zarr.ngff", true)]
+    #[case("..zarr", true)]
+    #[case(".zarr", false)]
+    #[case(".ngff", false)]
+    #[case("foo.ZARR", true)]
+    #[case("foo.Ngff", true)]
+    #[case(".ZARR", false)]
+    #[case("foo.zarr.json", false)]
+    #[case("foo.zar", false)]
+    #[case("foozarr", false)]
+    #[case("foo", false)]
+    #[case("fooé.zarr", true)]
+    #[case("é", false)]
+    fn test_default_zarr_extensions(#[case] name: &str, #[case] r: bool) {
+        assert_eq!(ZarrExtensions::default().matches(name), r);
+    }
+
+    #[rstest]
+    #[case("foo.N5", true)]
+    #[case("foo.n5", true)]
+    #[case("foo.zarr", false)]
+    #[case(".n5", false)]
+    fn test_custom_zarr_extensions(#[case] name: &str, #[case] r: bool) {
+        let exts = ZarrExtensions::new([".n5".parse().unwrap()]);
+        assert_eq!(exts.matches(name), r);
+    }
+
+    #[rstest]
+    #[case(".zarr", ".zarr")]
+    #[case("zarr", ".zarr")]
+    #[case(".ome.zarr", ".ome.zarr")]
+    #[case(".N5", ".N5")]
+    fn test_parse_zarr_extension(#[case] s: &str, #[case] ext: &str) {
+        assert_eq!(s.parse::<ZarrExtension>().unwrap().0, ext);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case(".")]
+    #[case(".foo/bar")]
+    #[case("foo\0")]
+    fn test_parse_bad_zarr_extension(#[case] s: &str) {
+        assert_eq!(s.parse::<ZarrExtension>(), Err(ParseZarrExtensionError));
+    }
+
+    #[rstest]
+    #[case(
+        "6ddc4625befef8d6f9796835648162be-509--710206390.zarr",
+        ".zarr",
+        Some("6ddc4625befef8d6f9796835648162be-509--710206390")
+    )]
+    #[case(
+        "6ddc4625befef8d6f9796835648162be-509--710206390.json",
+        ".json",
+        Some("6ddc4625befef8d6f9796835648162be-509--710206390")
+    )]
+    #[case("6ddc4625befef8d6f9796835648162be-509--710206390.json", ".zarr", None)]
+    #[case("6ddc4625befef8d6f9796835648162be-509--710206390", ".zarr", None)]
+    #[case(
+        "6ddc4625befef8d6f9796835648162be-509--710206390.versionid.zarr",
+        ".zarr",
+        None
+    )]
+    #[case(".zarr", ".zarr", None)]
+    #[case("..zarr", ".zarr", None)]
+    #[case("abc.ZARR", ".zarr", None)]
+    fn test_strip_checksum_ext(#[case] c: Component, #[case] ext: &str, #[case] r: Option<&str>) {
+        assert_eq!(strip_checksum_ext(&c, ext).as_deref(), r);
+    }
+}
@@ -79,6 +79,10 @@ impl PureDirPath {
     pub(crate) fn component_strs(&self) -> std::str::Split<'_, char> {
         self.0.trim_end_matches('/').split('/')
     }
+
+    pub(crate) fn components(&self) -> impl Iterator<Item = Component> + '_ {
+        self.component_strs().map(|c| Component(c.into()))
+    }
 }
 
 impl From<Component> for PureDirPath {
@@ -224,6 +228,13 @@ mod tests {
         assert_eq!(dirpath.component_strs().collect::<Vec<_>>(), comps);
     }
 
+    #[rstest]
+    #[case("foo/", vec!["foo"])]
+    #[case("foo/bar/quux/", vec!["foo", "bar", "quux"])]
+    fn test_components(#[case] dirpath: PureDirPath, #[case] comps: Vec<&str>) {
+        assert_eq!(dirpath.components().collect::<Vec<_>>(), comps);
+    }
+
     #[test]
     fn test_from_component() {
         let c = "foo".parse::<Component>().unwrap();
@@ -1,7 +1,9 @@
 //! Path types with restricted formats
+mod canon;
 mod component;
 mod dirpath;
 mod purepath;
+pub(crate) use self::canon::*;
 pub(crate) use self::component::*;
 pub(crate) use self::dirpath::*;
 pub(crate) use self::purepath::*;
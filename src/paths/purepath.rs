@@ -1,5 +1,4 @@
-use super::{Component, PureDirPath};
-use crate::consts::ZARR_EXTENSIONS;
+use super::{Component, PureDirPath, ZarrExtensions};
 use thiserror::Error;
 
 /// A nonempty, forward-slash-separated path that does not contain any of the
@@ -51,11 +50,20 @@ impl PurePath {
         self.0.starts_with(&other.0)
     }
 
-    /// For each non-final component in the path that has an extension of
-    /// `.zarr` or `.ngff` (case sensitive), yield the portion of the path up
-    /// through that component along with the rest of the path.
-    pub(crate) fn split_zarr_candidates(&self) -> SplitZarrCandidates<'_> {
-        SplitZarrCandidates::new(self)
+    /// For each non-final component in the path that has one of the given
+    /// extensions, yield the portion of the path up through that component
+    /// along with the rest of the path.
+    pub(crate) fn split_zarr_candidates<'a>(
+        &'a self,
+        extensions: &'a ZarrExtensions,
+    ) -> SplitZarrCandidates<'a> {
+        SplitZarrCandidates::new(self, Some(extensions))
+    }
+
+    /// For each non-final component in the path, yield the portion of the
+    /// path up through that component along with the rest of the path.
+    pub(crate) fn split_prefixes(&self) -> SplitZarrCandidates<'_> {
+        SplitZarrCandidates::new(self, None)
     }
 
     pub(crate) fn relative_to(&self, dirpath: &PureDirPath) -> Option<PurePath> {
@@ -67,6 +75,11 @@ impl PurePath {
         Some(PurePath(s.to_owned()))
     }
 
+    pub(crate) fn parent(&self) -> Option<PureDirPath> {
+        let i = self.0.rfind('/')?;
+        Some(PureDirPath(self.0[..=i].to_owned()))
+    }
+
     pub(crate) fn to_dir_path(&self) -> PureDirPath {
         PureDirPath(format!("{}/", self.0))
     }
@@ -118,13 +131,19 @@ pub(crate) enum ParsePurePathError {
 pub(crate) struct SplitZarrCandidates<'a> {
     s: &'a str,
     inner: std::str::MatchIndices<'a, char>,
+    /// If `None`, every non-final component is a candidate
+    extensions: Option<&'a ZarrExtensions>,
 }
 
 impl<'a> SplitZarrCandidates<'a> {
-    fn new(path: &'a PurePath) -> Self {
+    fn new(path: &'a PurePath, extensions: Option<&'a ZarrExtensions>) -> Self {
         let s = &path.0;
         let inner = s.match_indices('/');
-        SplitZarrCandidates { s, inner }
+        SplitZarrCandidates {
+            s,
+            inner,
+            extensions,
+        }
     }
 }
 
@@ -135,14 +154,11 @@ impl Iterator for SplitZarrCandidates<'_> {
         for (i, _) in self.inner.by_ref() {
             let zarrpath = &self.s[..i];
             let entrypath = &self.s[(i + 1)..];
-            for ext in ZARR_EXTENSIONS {
-                if let Some(pre) = zarrpath.strip_suffix(ext) {
-                    if !pre.is_empty() && !pre.ends_with('/') {
-                        let zarrpath = PurePath(zarrpath.into());
-                        let entrypath = PurePath(entrypath.into());
-                        return Some((zarrpath, entrypath));
-                    }
-                }
+            let name = zarrpath.rsplit('/').next().unwrap_or(zarrpath);
+            if self.extensions.map_or(true, |exts| exts.matches(name)) {
+                let zarrpath = PurePath(zarrpath.into());
+                let entrypath = PurePath(entrypath.into());
+                return Some((zarrpath, entrypath));
             }
         }
         None
@@ -261,7 +277,8 @@ mod tests {
         #[test]
         fn no_zarr() {
             let path = "foo/bar/baz".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
         }
@@ -269,7 +286,8 @@ mod tests {
         #[test]
         fn one_zarr() {
             let path = "foo/bar.zarr/baz".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_matches!(iter.next(), Some((zp, ep)) => {
                 assert_eq!(zp, "foo/bar.zarr");
                 assert_eq!(ep, "baz");
@@ -281,7 +299,8 @@ mod tests {
         #[test]
         fn one_ngff() {
             let path = "foo/bar.ngff/baz".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_matches!(iter.next(), Some((zp, ep)) => {
                 assert_eq!(zp, "foo/bar.ngff");
                 assert_eq!(ep, "baz");
@@ -295,7 +314,8 @@ mod tests {
             let path = "foo.zarr/bar/baz.zarr/quux/glarch/cleesh.zarr/gnusto"
                 .parse::<PurePath>()
                 .unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_matches!(iter.next(), Some((zp, ep)) => {
                 assert_eq!(zp, "foo.zarr");
                 assert_eq!(ep, "bar/baz.zarr/quux/glarch/cleesh.zarr/gnusto");
@@ -315,7 +335,8 @@ mod tests {
         #[test]
         fn consecutive_zarrs() {
             let path = "foo/bar.zarr/baz.zarr/quux".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_matches!(iter.next(), Some((zp, ep)) => {
                 assert_eq!(zp, "foo/bar.zarr");
                 assert_eq!(ep, "baz.zarr/quux");
@@ -331,7 +352,12 @@ mod tests {
         #[test]
         fn capital_zarr_ext() {
             let path = "foo/bar.Zarr/baz".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
+            assert_matches!(iter.next(), Some((zp, ep)) => {
+                assert_eq!(zp, "foo/bar.Zarr");
+                assert_eq!(ep, "baz");
+            });
             assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
         }
@@ -339,7 +365,12 @@ mod tests {
         #[test]
         fn capital_ngff_ext() {
             let path = "foo/bar.Ngff/baz".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
+            assert_matches!(iter.next(), Some((zp, ep)) => {
+                assert_eq!(zp, "foo/bar.Ngff");
+                assert_eq!(ep, "baz");
+            });
             assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
         }
@@ -347,7 +378,8 @@ mod tests {
         #[test]
         fn final_zarr() {
             let path = "foo/bar/baz.zarr".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
         }
@@ -355,7 +387,8 @@ mod tests {
         #[test]
         fn ext_component() {
             let path = "foo/.zarr/baz".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
         }
@@ -363,7 +396,8 @@ mod tests {
         #[test]
         fn ext_first_component() {
             let path = ".zarr/foo/baz".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
         }
@@ -371,7 +405,8 @@ mod tests {
         #[test]
         fn just_zarr() {
             let path = "foo.zarr".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
             assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
         }
@@ -379,8 +414,36 @@ mod tests {
         #[test]
         fn just_ext() {
             let path = ".zarr".parse::<PurePath>().unwrap();
-            let mut iter = path.split_zarr_candidates();
+            let exts = ZarrExtensions::default();
+            let mut iter = path.split_zarr_candidates(&exts);
+            assert_eq!(iter.next(), None);
             assert_eq!(iter.next(), None);
+        }
+
+        #[test]
+        fn custom_ext() {
+            let path = "foo.zarr/bar.n5/baz".parse::<PurePath>().unwrap();
+            let exts = ZarrExtensions::new([".n5".parse().unwrap()]);
+            let mut iter = path.split_zarr_candidates(&exts);
+            assert_matches!(iter.next(), Some((zp, ep)) => {
+                assert_eq!(zp, "foo.zarr/bar.n5");
+                assert_eq!(ep, "baz");
+            });
+            assert_eq!(iter.next(), None);
+        }
+
+        #[test]
+        fn prefixes() {
+            let path = "foo/bar.zarr/baz".parse::<PurePath>().unwrap();
+            let mut iter = path.split_prefixes();
+            assert_matches!(iter.next(), Some((zp, ep)) => {
+                assert_eq!(zp, "foo");
+                assert_eq!(ep, "bar.zarr/baz");
+            });
+            assert_matches!(iter.next(), Some((zp, ep)) => {
+                assert_eq!(zp, "foo/bar.zarr");
+                assert_eq!(ep, "baz");
+            });
             assert_eq!(iter.next(), None);
         }
     }
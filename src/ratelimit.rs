@@ -0,0 +1,710 @@
+//! Limiting of the rate at which individual clients may make requests
+use crate::consts::RATE_LIMITER_CLEANUP_PERIOD;
+use crate::metrics::metrics;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header::USER_AGENT, response::Response, Method, StatusCode},
+    middleware::{self, Next},
+    response::IntoResponse,
+    Router,
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    DefaultKeyedRateLimiter, Quota,
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The name under which rejections by the rate limiter for
+/// [`RequestClass::Normal`] requests are logged & counted
+const RATE_LIMITER: &str = "rate-limit";
+
+/// The name under which rejections by the rate limiter for
+/// [`RequestClass::Expensive`] requests are logged & counted
+const EXPENSIVE_RATE_LIMITER: &str = "expensive-rate-limit";
+
+/// The name under which rejections by the rate limiter for
+/// [`RequestClass::Cheap`] requests are logged & counted
+const CHEAP_RATE_LIMITER: &str = "cheap-rate-limit";
+
+/// Settings for a per-client rate limiter
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct RateLimit {
+    /// The average number of requests per second that each client IP address
+    /// is allowed to make.  This must be nonzero.
+    pub(crate) per_second: u32,
+
+    /// The maximum number of requests that a client IP address may make in a
+    /// burst before being limited to `per_second`.  This must be nonzero.
+    pub(crate) burst: u32,
+}
+
+/// Settings for the per-client rate limiters applied to each class of
+/// request.  Each class is limited separately, so that, e.g., a client
+/// performing many expensive requests is not thereby prevented from making
+/// cheap ones.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct RateLimits {
+    /// The limit for [`RequestClass::Normal`] requests, or `None` if they are
+    /// not limited
+    pub(crate) normal: Option<RateLimit>,
+
+    /// The limit for [`RequestClass::Expensive`] requests, or `None` if they
+    /// are not limited
+    pub(crate) expensive: Option<RateLimit>,
+
+    /// The limit for [`RequestClass::Cheap`] requests, or `None` if they are
+    /// not limited
+    pub(crate) cheap: Option<RateLimit>,
+
+    /// Networks whose clients are not subject to rate limiting
+    pub(crate) exempt: Vec<IpNet>,
+
+    /// How clients' IP addresses are determined
+    pub(crate) client_ips: ClientIpSource,
+}
+
+impl RateLimits {
+    /// Returns `true` if any class of request is limited
+    pub(crate) fn is_limited(&self) -> bool {
+        self.normal.is_some() || self.expensive.is_some() || self.cheap.is_some()
+    }
+}
+
+/// How to determine the IP address of the client that made a request
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ClientIpSource {
+    /// The number of trusted reverse proxies in front of `dandidav`, each of
+    /// which appends the address of its peer to the `X-Forwarded-For`
+    /// header.  If this is zero, `X-Forwarded-For` is ignored.
+    pub(crate) trusted_proxies: usize,
+}
+
+impl ClientIpSource {
+    /// Determine the IP address of the client that made `request`.
+    ///
+    /// If there are N trusted proxies, the client's address is the Nth entry
+    /// from the right end of the request's `X-Forwarded-For` header(s), as
+    /// that is the entry appended by the outermost trusted proxy; entries
+    /// further to the left are supplied by the client (or by untrusted
+    /// intermediaries) and may be forged.  If there are no trusted proxies,
+    /// if the header has fewer than N entries, or if the entry is not an IP
+    /// address, the peer address of the connection is used instead.  Returns
+    /// `None` if that is not available either (e.g., when listening on a Unix
+    /// domain socket).
+    pub(crate) fn client_ip<B>(self, request: &Request<B>) -> Option<IpAddr> {
+        self.forwarded_ip(request).or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        })
+    }
+
+    /// Return the client address given by the trusted proxies in the
+    /// `X-Forwarded-For` header(s) of `request`, if any
+    fn forwarded_ip<B>(self, request: &Request<B>) -> Option<IpAddr> {
+        if self.trusted_proxies == 0 {
+            return None;
+        }
+        let entries = request
+            .headers()
+            .get_all("X-Forwarded-For")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let entry = entries.get(entries.len().checked_sub(self.trusted_proxies)?)?;
+        entry
+            .parse::<IpAddr>()
+            .ok()
+            .or_else(|| entry.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+    }
+}
+
+/// Parse a network in CIDR notation (e.g., `192.0.2.0/24`) or a bare IP
+/// address, which is treated as a network containing just that address
+pub(crate) fn parse_client_net(s: &str) -> Result<IpNet, ipnet::AddrParseError> {
+    match s.parse::<IpAddr>() {
+        Ok(ip) => Ok(IpNet::from(ip)),
+        Err(_) => s.parse::<IpNet>(),
+    }
+}
+
+/// A classification of requests by how costly they are to handle, used to
+/// pick the rate limiter that applies to them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RequestClass {
+    /// Requests for static assets and `GET` requests for non-collection
+    /// resources (which are usually answered with redirects)
+    Cheap,
+
+    /// All requests that are neither cheap nor expensive
+    Normal,
+
+    /// `PROPFIND` requests with a `Depth` other than 0, requests for `tree`
+    /// listings, which may require fetching many pages of resources from
+    /// upstream, `COPY` requests, and `REPORT` requests
+    Expensive,
+}
+
+impl RequestClass {
+    /// Classify a request.
+    ///
+    /// As this is done before routing, the classification is based solely on
+    /// the request's method, path, query, and headers: `GET` requests for
+    /// paths not ending in a slash are assumed to be for non-collection
+    /// resources, and `PROPFIND` requests without a `Depth` header are
+    /// treated as having a `Depth` of `infinity`.  `COPY` requests, which
+    /// transfer entire resources, and `REPORT` requests, which list entire
+    /// Dandiset versions, are always expensive.
+    fn of<B>(request: &Request<B>) -> RequestClass {
+        let path = request.uri().path();
+        let method = request.method();
+        if path.starts_with("/.static/") {
+            RequestClass::Cheap
+        } else if method.as_str() == "PROPFIND" {
+            let depth = request
+                .headers()
+                .get("Depth")
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim);
+            if depth == Some("0") {
+                RequestClass::Normal
+            } else {
+                RequestClass::Expensive
+            }
+        } else if method.as_str() == "COPY" || method.as_str() == "REPORT" {
+            RequestClass::Expensive
+        } else if method == Method::GET || method == Method::HEAD {
+            let tree = request.uri().query().is_some_and(|q| {
+                q.split('&')
+                    .any(|param| param.split('=').next() == Some("tree"))
+            });
+            if tree {
+                RequestClass::Expensive
+            } else if path.ends_with('/') {
+                RequestClass::Normal
+            } else {
+                RequestClass::Cheap
+            }
+        } else {
+            RequestClass::Normal
+        }
+    }
+
+    /// Return the name under which rejections of requests of this class are
+    /// logged & counted
+    fn limiter_name(self) -> &'static str {
+        match self {
+            RequestClass::Cheap => CHEAP_RATE_LIMITER,
+            RequestClass::Normal => RATE_LIMITER,
+            RequestClass::Expensive => EXPENSIVE_RATE_LIMITER,
+        }
+    }
+}
+
+/// The state of the rate limiters for each class of request
+#[derive(Debug)]
+struct RateLimiters {
+    normal: Option<DefaultKeyedRateLimiter<IpAddr>>,
+    expensive: Option<DefaultKeyedRateLimiter<IpAddr>>,
+    cheap: Option<DefaultKeyedRateLimiter<IpAddr>>,
+    exempt: Vec<IpNet>,
+    client_ips: ClientIpSource,
+
+    /// Whether a request whose client could not be identified has been
+    /// logged yet
+    warned_unidentified: AtomicBool,
+}
+
+impl RateLimiters {
+    fn new(limits: RateLimits) -> RateLimiters {
+        fn mklimiter(limit: RateLimit) -> DefaultKeyedRateLimiter<IpAddr> {
+            let per_second = NonZeroU32::new(limit.per_second).unwrap_or(NonZeroU32::MIN);
+            let burst = NonZeroU32::new(limit.burst).unwrap_or(NonZeroU32::MIN);
+            DefaultKeyedRateLimiter::keyed(Quota::per_second(per_second).allow_burst(burst))
+        }
+
+        RateLimiters {
+            normal: limits.normal.map(mklimiter),
+            expensive: limits.expensive.map(mklimiter),
+            cheap: limits.cheap.map(mklimiter),
+            exempt: limits.exempt,
+            client_ips: limits.client_ips,
+            warned_unidentified: AtomicBool::new(false),
+        }
+    }
+
+    /// Return the limiter for requests of the given class, if they are
+    /// limited
+    fn get(&self, class: RequestClass) -> Option<&DefaultKeyedRateLimiter<IpAddr>> {
+        match class {
+            RequestClass::Cheap => self.cheap.as_ref(),
+            RequestClass::Normal => self.normal.as_ref(),
+            RequestClass::Expensive => self.expensive.as_ref(),
+        }
+    }
+
+    /// Returns `true` if `ip` belongs to an exempt network
+    fn is_exempt(&self, ip: IpAddr) -> bool {
+        self.exempt.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Discard the state for clients that have not made any requests recently
+    fn cleanup(&self) {
+        for limiter in [&self.normal, &self.expensive, &self.cheap]
+            .into_iter()
+            .flatten()
+        {
+            limiter.retain_recent();
+            limiter.shrink_to_fit();
+        }
+    }
+}
+
+/// Apply the given rate limits to all routes currently in `app`.
+///
+/// Clients are identified by IP address, as determined by
+/// [`ClientIpSource::client_ip()`].  Requests in excess of the limit for
+/// their class receive a 429 response with a `Retry-After` header and a
+/// [`Rejected`] extension; apply [`log_rejections()`] outside of the limiter
+/// in order to log & count them.
+/// Requests from clients in exempt networks are never limited, nor are
+/// requests whose client cannot be identified (e.g., when listening on a
+/// Unix domain socket without a trusted proxy setting `X-Forwarded-For`);
+/// the first such request is logged.
+///
+/// This also spawns a task that periodically discards the limiters' state
+/// for clients that have not made any requests recently.
+pub(crate) fn limit_rate(app: Router, limits: RateLimits) -> Router {
+    let limiters = Arc::new(RateLimiters::new(limits));
+    let cleaned = Arc::clone(&limiters);
+    let mut schedule = tokio::time::interval(RATE_LIMITER_CLEANUP_PERIOD);
+    schedule.reset(); // Don't tick immediately
+    schedule.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    tokio::spawn(async move {
+        loop {
+            schedule.tick().await;
+            cleaned.cleanup();
+        }
+    });
+    app.layer(middleware::from_fn_with_state(limiters, check_rate))
+}
+
+/// Middleware for rejecting requests in excess of the rate limit for their
+/// class
+async fn check_rate(
+    State(limiters): State<Arc<RateLimiters>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let class = RequestClass::of(&request);
+    let Some(limiter) = limiters.get(class) else {
+        return next.run(request).await;
+    };
+    let Some(ip) = limiters.client_ips.client_ip(&request) else {
+        if !limiters.warned_unidentified.swap(true, Ordering::Relaxed) {
+            tracing::warn!("Could not determine client IP address for rate limiting; requests from unidentified clients will not be rate-limited");
+        }
+        return next.run(request).await;
+    };
+    if limiters.is_exempt(ip) {
+        return next.run(request).await;
+    }
+    match limiter.check_key(&ip) {
+        Ok(()) => next.run(request).await,
+        Err(negative) => {
+            let wait_time = negative
+                .wait_time_from(DefaultClock::default().now())
+                .as_secs();
+            let mut resp = (
+                StatusCode::TOO_MANY_REQUESTS,
+                [
+                    ("retry-after", wait_time.to_string()),
+                    ("x-ratelimit-after", wait_time.to_string()),
+                ],
+                format!("Too many requests; try again in {wait_time} seconds\n"),
+            )
+                .into_response();
+            resp.extensions_mut().insert(Rejected {
+                limiter: class.limiter_name(),
+            });
+            resp
+        }
+    }
+}
+
+/// A response extension marking a response as the rejection of a request by
+/// a limiter.  [`log_rejections()`] logs and counts responses with this
+/// extension.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Rejected {
+    /// The name of the limiter that rejected the request
+    pub(crate) limiter: &'static str,
+}
+
+/// Log and count responses marked as rejections by a [`Rejected`] extension,
+/// recording the limiter involved, the request path, the client's
+/// `User-Agent`, and the network prefix of the client's IP address (so that
+/// abusive clients can be told apart from genuine load without logging full
+/// addresses)
+pub(crate) async fn log_rejections(
+    State(client_ips): State<ClientIpSource>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let path = request.uri().path().to_owned();
+    let client = client_ips.client_ip(&request).map(ip_prefix);
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(ToOwned::to_owned);
+    let resp = next.run(request).await;
+    if let Some(&Rejected { limiter }) = resp.extensions().get::<Rejected>() {
+        tracing::warn!(
+            limiter,
+            client = client.as_deref().unwrap_or("unknown"),
+            user_agent = user_agent.as_deref().unwrap_or("unknown"),
+            path,
+            status = resp.status().as_u16(),
+            "Request rejected by limiter",
+        );
+        metrics().record_rejection(limiter);
+    }
+    resp
+}
+
+/// Return the network prefix of `ip` in CIDR notation: the first 24 bits for
+/// IPv4 addresses and the first 48 bits for IPv6 addresses
+fn ip_prefix(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{a}.{b}.{c}.0/24")
+        }
+        IpAddr::V6(ip) => {
+            let masked = u128::from(ip) & !(u128::MAX >> 48);
+            format!("{}/48", std::net::Ipv6Addr::from(masked))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        http::header::RETRY_AFTER,
+        routing::{any, get},
+    };
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[case("203.0.113.42", "203.0.113.0/24")]
+    #[case("10.0.0.1", "10.0.0.0/24")]
+    #[case("2001:db8:abcd:12::1", "2001:db8:abcd::/48")]
+    #[case("::1", "::/48")]
+    fn test_ip_prefix(#[case] ip: IpAddr, #[case] prefix: &str) {
+        assert_eq!(ip_prefix(ip), prefix);
+    }
+
+    #[tokio::test]
+    async fn rejects_excess_requests() {
+        let app = limit_rate(
+            Router::new().route("/", get(|| async { "Hello" })),
+            RateLimits {
+                normal: Some(RateLimit {
+                    per_second: 1,
+                    burst: 2,
+                }),
+                client_ips: ClientIpSource { trusted_proxies: 1 },
+                ..RateLimits::default()
+            },
+        );
+        let request = |ip: &str| {
+            Request::get("/")
+                .header("X-Forwarded-For", ip)
+                .body(Body::empty())
+                .unwrap()
+        };
+        for _ in 0..2 {
+            let resp = app.clone().oneshot(request("192.0.2.1")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert!(resp.extensions().get::<Rejected>().is_none());
+        }
+        let resp = app.clone().oneshot(request("192.0.2.1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key(RETRY_AFTER));
+        assert_eq!(
+            resp.extensions().get::<Rejected>(),
+            Some(&Rejected {
+                limiter: RATE_LIMITER
+            })
+        );
+        let resp = app.oneshot(request("192.0.2.2")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[rstest]
+    #[case("GET", "/.static/styles.css", None, RequestClass::Cheap)]
+    #[case(
+        "GET",
+        "/dandisets/000027/draft/dandiset.yaml",
+        None,
+        RequestClass::Cheap
+    )]
+    #[case("HEAD", "/favicon.ico", None, RequestClass::Cheap)]
+    #[case("GET", "/", None, RequestClass::Normal)]
+    #[case("GET", "/dandisets/000027/draft/", None, RequestClass::Normal)]
+    #[case(
+        "GET",
+        "/dandisets/000027/draft/?format=json",
+        None,
+        RequestClass::Normal
+    )]
+    #[case(
+        "GET",
+        "/dandisets/000027/draft/?tree=json",
+        None,
+        RequestClass::Expensive
+    )]
+    #[case(
+        "GET",
+        "/dandisets/000027/draft/?page=2&tree",
+        None,
+        RequestClass::Expensive
+    )]
+    #[case(
+        "PROPFIND",
+        "/dandisets/000027/draft/",
+        Some("0"),
+        RequestClass::Normal
+    )]
+    #[case(
+        "PROPFIND",
+        "/dandisets/000027/draft/",
+        Some("1"),
+        RequestClass::Expensive
+    )]
+    #[case("PROPFIND", "/dandisets/", Some("infinity"), RequestClass::Expensive)]
+    #[case("PROPFIND", "/dandisets/", None, RequestClass::Expensive)]
+    #[case("PROPFIND", "/.static/styles.css", Some("1"), RequestClass::Cheap)]
+    #[case("OPTIONS", "/dandisets", None, RequestClass::Normal)]
+    #[case(
+        "COPY",
+        "/dandisets/000027/draft/sub-RAT123.nwb",
+        None,
+        RequestClass::Expensive
+    )]
+    #[case(
+        "REPORT",
+        "/dandisets/000027/draft/",
+        Some("0"),
+        RequestClass::Expensive
+    )]
+    fn test_request_class(
+        #[case] method: &str,
+        #[case] uri: &str,
+        #[case] depth: Option<&str>,
+        #[case] class: RequestClass,
+    ) {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(d) = depth {
+            builder = builder.header("Depth", d);
+        }
+        let request = builder.body(()).unwrap();
+        assert_eq!(RequestClass::of(&request), class);
+    }
+
+    #[rstest]
+    #[case("192.0.2.0/24", "192.0.2.0/24")]
+    #[case("192.0.2.7", "192.0.2.7/32")]
+    #[case("2001:db8::/32", "2001:db8::/32")]
+    #[case("2001:db8::1", "2001:db8::1/128")]
+    fn test_parse_client_net(#[case] s: &str, #[case] net: &str) {
+        assert_eq!(parse_client_net(s).unwrap().to_string(), net);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("192.0.2.0/33")]
+    #[case("example.com")]
+    fn test_parse_client_net_err(#[case] s: &str) {
+        assert!(parse_client_net(s).is_err());
+    }
+
+    #[tokio::test]
+    async fn limits_classes_separately() {
+        let limit = RateLimit {
+            per_second: 1,
+            burst: 1,
+        };
+        let app = limit_rate(
+            Router::new().route("/{*path}", any(|| async { "Hello" })),
+            RateLimits {
+                normal: Some(limit),
+                expensive: Some(limit),
+                cheap: None,
+                exempt: Vec::new(),
+                client_ips: ClientIpSource { trusted_proxies: 1 },
+            },
+        );
+        let request = |method: &str, path: &str| {
+            Request::builder()
+                .method(method)
+                .uri(path)
+                .header("X-Forwarded-For", "192.0.2.1")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app.clone().oneshot(request("GET", "/foo/")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = app.clone().oneshot(request("GET", "/foo/")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let resp = app
+            .clone()
+            .oneshot(request("PROPFIND", "/foo/"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = app
+            .clone()
+            .oneshot(request("PROPFIND", "/foo/"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            resp.extensions().get::<Rejected>(),
+            Some(&Rejected {
+                limiter: EXPENSIVE_RATE_LIMITER
+            })
+        );
+        for _ in 0..5 {
+            let resp = app.clone().oneshot(request("GET", "/foo")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn exempts_networks() {
+        let app = limit_rate(
+            Router::new().route("/", get(|| async { "Hello" })),
+            RateLimits {
+                normal: Some(RateLimit {
+                    per_second: 1,
+                    burst: 1,
+                }),
+                exempt: vec!["10.0.0.0/8".parse().unwrap()],
+                client_ips: ClientIpSource { trusted_proxies: 1 },
+                ..RateLimits::default()
+            },
+        );
+        let request = |ip: &str| {
+            Request::get("/")
+                .header("X-Forwarded-For", ip)
+                .body(Body::empty())
+                .unwrap()
+        };
+        for _ in 0..5 {
+            let resp = app.clone().oneshot(request("10.1.2.3")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let resp = app.clone().oneshot(request("192.0.2.1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = app.oneshot(request("192.0.2.1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn unidentified_not_limited() {
+        let app = limit_rate(
+            Router::new().route("/", get(|| async { "Hello" })),
+            RateLimits {
+                normal: Some(RateLimit {
+                    per_second: 1,
+                    burst: 1,
+                }),
+                client_ips: ClientIpSource { trusted_proxies: 1 },
+                ..RateLimits::default()
+            },
+        );
+        // No peer address (as over a Unix domain socket) and no
+        // `X-Forwarded-For` header:
+        for _ in 0..5 {
+            let resp = app
+                .clone()
+                .oneshot(Request::get("/").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn forged_forwarding_not_exempted() {
+        let app = limit_rate(
+            Router::new().route("/", get(|| async { "Hello" })),
+            RateLimits {
+                normal: Some(RateLimit {
+                    per_second: 1,
+                    burst: 1,
+                }),
+                exempt: vec!["10.0.0.0/8".parse().unwrap()],
+                client_ips: ClientIpSource { trusted_proxies: 1 },
+                ..RateLimits::default()
+            },
+        );
+        // The client sends "X-Forwarded-For: 10.1.2.3", and the trusted proxy
+        // appends the client's real address.
+        let request = || {
+            Request::get("/")
+                .header("X-Forwarded-For", "10.1.2.3, 192.0.2.1")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = app.oneshot(request()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[rstest]
+    #[case(0, &[], None)]
+    #[case(0, &["192.0.2.1"], None)]
+    #[case(1, &[], None)]
+    #[case(1, &["192.0.2.1"], Some("192.0.2.1"))]
+    #[case(1, &["10.1.2.3, 192.0.2.1"], Some("192.0.2.1"))]
+    #[case(1, &["10.1.2.3", "192.0.2.1"], Some("192.0.2.1"))]
+    #[case(2, &["10.1.2.3, 192.0.2.1, 198.51.100.7"], Some("192.0.2.1"))]
+    #[case(2, &["192.0.2.1"], None)]
+    #[case(1, &["192.0.2.1:54321"], Some("192.0.2.1"))]
+    #[case(1, &["[2001:db8::1]:54321"], Some("2001:db8::1"))]
+    #[case(1, &["192.0.2.1, unknown"], None)]
+    fn test_client_ip(
+        #[case] trusted_proxies: usize,
+        #[case] forwarded: &[&str],
+        #[case] ip: Option<&str>,
+    ) {
+        let ip = ip.map(|s| s.parse::<IpAddr>().unwrap());
+        let mut builder = Request::get("/");
+        for &value in forwarded {
+            builder = builder.header("X-Forwarded-For", value);
+        }
+        let source = ClientIpSource { trusted_proxies };
+        let mut request = builder.body(()).unwrap();
+        assert_eq!(source.client_ip(&request), ip);
+        let peer = "198.51.100.99:443".parse::<SocketAddr>().unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer));
+        assert_eq!(
+            source.client_ip(&request),
+            Some(ip.unwrap_or_else(|| peer.ip()))
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Support for S3-compatible object stores other than AWS
+use crate::httputil::{HttpUrl, ParseHttpUrlError};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use thiserror::Error;
+use url::Url;
+
+/// The custom endpoints configured via [`set_custom_endpoints()`]
+static CUSTOM_ENDPOINTS: OnceLock<Vec<S3Endpoint>> = OnceLock::new();
+
+/// Configure the custom S3-compatible endpoints whose URLs are recognized by
+/// [`S3Location::parse_url()`][super::S3Location::parse_url].  This should be
+/// called at most once, at program startup; later calls have no effect.
+pub(crate) fn set_custom_endpoints(endpoints: Vec<S3Endpoint>) {
+    if CUSTOM_ENDPOINTS.set(endpoints).is_err() {
+        tracing::warn!("Attempted to configure custom S3 endpoints more than once");
+    }
+}
+
+/// Return the custom S3-compatible endpoints configured via
+/// [`set_custom_endpoints()`]
+pub(super) fn custom_endpoints() -> &'static [S3Endpoint] {
+    CUSTOM_ENDPOINTS.get().map_or(&[], Vec::as_slice)
+}
+
+/// The base URL of an S3-compatible object store other than AWS (e.g., a
+/// MinIO server), as given on the command line via `--s3-endpoint`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct S3Endpoint {
+    /// The endpoint URL, which has an empty path
+    url: HttpUrl,
+
+    /// The host of `url`, lowercased
+    host: String,
+}
+
+impl S3Endpoint {
+    /// Return the endpoint URL
+    pub(super) fn url(&self) -> &HttpUrl {
+        &self.url
+    }
+
+    /// If `url` points to an object on this endpoint, return the name of the
+    /// object's bucket and the still-percent-encoded path of the object
+    /// within the bucket (without a leading slash).  Both virtual-hosted-style
+    /// URLs (`{scheme}://{bucket}.{host}/{key}`) and path-style URLs
+    /// (`{scheme}://{host}/{bucket}/{key}`) are recognized.
+    pub(super) fn locate<'a>(&self, url: &'a Url) -> Option<(&'a str, &'a str)> {
+        if url.scheme() != self.url.as_url().scheme()
+            || url.port_or_known_default() != self.url.as_url().port_or_known_default()
+        {
+            return None;
+        }
+        let host = url.host_str()?;
+        let path = url.path();
+        let path = path.strip_prefix('/').unwrap_or(path);
+        if host == self.host {
+            let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+            (!bucket.is_empty()).then_some((bucket, key))
+        } else {
+            let bucket = host
+                .strip_suffix(&*self.host)?
+                .strip_suffix('.')
+                .filter(|b| !b.is_empty())?;
+            Some((bucket, path))
+        }
+    }
+
+    /// Return the path-style URL for the root of the given bucket on this
+    /// endpoint
+    pub(super) fn bucket_url(&self, bucket: &str) -> HttpUrl {
+        let mut url = self.url.clone();
+        url.push(bucket);
+        url
+    }
+}
+
+impl FromStr for S3Endpoint {
+    type Err = ParseS3EndpointError;
+
+    fn from_str(s: &str) -> Result<S3Endpoint, ParseS3EndpointError> {
+        let url = s.parse::<HttpUrl>()?;
+        let host = url
+            .as_url()
+            .host_str()
+            .ok_or(ParseS3EndpointError::NoHost)?
+            .to_ascii_lowercase();
+        if !matches!(url.as_url().path(), "" | "/")
+            || url.as_url().query().is_some()
+            || url.as_url().fragment().is_some()
+        {
+            return Err(ParseS3EndpointError::HasPath);
+        }
+        Ok(S3Endpoint { url, host })
+    }
+}
+
+/// Error returned when parsing an invalid [`S3Endpoint`] string
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+pub(crate) enum ParseS3EndpointError {
+    #[error("invalid endpoint URL")]
+    Url(#[from] ParseHttpUrlError),
+    #[error("endpoint URL lacks host")]
+    NoHost,
+    #[error("endpoint URL must not have a path, query, or fragment")]
+    HasPath,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("https://minio.example.org/foo/bar.txt", Some(("foo", "bar.txt")))]
+    #[case("https://minio.example.org/foo/zarr/abc/", Some(("foo", "zarr/abc/")))]
+    #[case("https://minio.example.org/foo", Some(("foo", "")))]
+    #[case("https://foo.minio.example.org/bar.txt", Some(("foo", "bar.txt")))]
+    #[case("https://foo.bar.minio.example.org/baz.txt", Some(("foo.bar", "baz.txt")))]
+    #[case("https://minio.example.org:443/foo/bar.txt", Some(("foo", "bar.txt")))]
+    #[case("https://minio.example.org/", None)]
+    #[case("https://minio.example.org:9000/foo/bar.txt", None)]
+    #[case("http://minio.example.org/foo/bar.txt", None)]
+    #[case("https://xminio.example.org/foo/bar.txt", None)]
+    #[case("https://example.org/foo/bar.txt", None)]
+    fn test_locate(#[case] url: Url, #[case] location: Option<(&str, &str)>) {
+        let endpoint = "https://minio.example.org".parse::<S3Endpoint>().unwrap();
+        assert_eq!(endpoint.locate(&url), location);
+    }
+
+    #[test]
+    fn test_ip_endpoint() {
+        let endpoint = "http://127.0.0.1:9000".parse::<S3Endpoint>().unwrap();
+        let url = "http://127.0.0.1:9000/dandi/zarr/abc/"
+            .parse::<Url>()
+            .unwrap();
+        assert_eq!(endpoint.locate(&url), Some(("dandi", "zarr/abc/")));
+    }
+
+    #[test]
+    fn test_bucket_url() {
+        let endpoint = "http://localhost:9000/".parse::<S3Endpoint>().unwrap();
+        assert_eq!(
+            endpoint.bucket_url("dandi").as_str(),
+            "http://localhost:9000/dandi"
+        );
+    }
+
+    #[rstest]
+    #[case("minio.example.org")]
+    #[case("ftp://minio.example.org")]
+    #[case("https://minio.example.org/bucket")]
+    #[case("https://minio.example.org/?foo=bar")]
+    fn test_bad_endpoint(#[case] s: &str) {
+        assert!(s.parse::<S3Endpoint>().is_err());
+    }
+}
@@ -1,43 +1,122 @@
 //! Facilities for retrieving information from an S3 bucket
+mod endpoint;
 mod streams;
+use self::endpoint::custom_endpoints;
+pub(crate) use self::endpoint::{set_custom_endpoints, S3Endpoint};
 use self::streams::ListEntryPages;
+use crate::consts::CUSTOM_S3_ENDPOINT_REGION;
 use crate::dav::ErrorClass;
-use crate::httputil::{self, BuildClientError, HttpError, HttpUrl, ParseHttpUrlError};
+use crate::httputil::{self, HttpError, HttpUrl, ParseHttpUrlError};
 use crate::paths::{ParsePureDirPathError, ParsePurePathError, PureDirPath, PurePath};
 use crate::streamutil::TryStreamUtil;
 use crate::validstr::TryFromStringError;
+use aws_config::{retry::RetryConfig, timeout::TimeoutConfig};
 use aws_sdk_s3::{operation::list_objects_v2::ListObjectsV2Error, types::CommonPrefix, Client};
 use aws_smithy_runtime_api::client::{orchestrator::HttpResponse, result::SdkError};
 use aws_smithy_types_convert::date_time::DateTimeExt;
-use futures_util::{Stream, TryStreamExt};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
 use smartstring::alias::CompactString;
 use std::cmp::Ordering;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU32, Ordering as AtomicOrdering},
+    Arc,
+};
+use std::time::Duration;
 use thiserror::Error;
 use time::OffsetDateTime;
 use url::{Host, Url};
 
 type ListObjectsError = SdkError<ListObjectsV2Error, HttpResponse>;
 
+/// Settings for the requests made by [`S3Client`]s
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct S3Config {
+    /// How long to wait for a connection to S3 to be established
+    pub(crate) connect_timeout: Duration,
+
+    /// How long to wait for data to be read from S3 before giving up on a
+    /// request attempt
+    pub(crate) read_timeout: Duration,
+
+    /// The maximum number of attempts (including the initial attempt) to
+    /// make for each request.  A value of 1 disables retries.
+    pub(crate) max_attempts: u32,
+
+    /// The maximum number of ranges of a folder's entries to list at once
+    /// when listing the folder; see [`NameRange`].  A value of 1 lists
+    /// folders sequentially, one page at a time.
+    pub(crate) list_concurrency: usize,
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct S3Client {
     inner: Client,
     bucket: CompactString,
+
+    /// The URL of the root of the bucket, to which objects' keys are appended
+    /// to form their download URLs
+    bucket_url: HttpUrl,
+
+    /// The number of `ListObjectsV2` requests made with this client that
+    /// have failed since the last successful request
+    failures: Arc<AtomicU32>,
+
+    /// The maximum number of ranges of a folder's entries to list at once
+    list_concurrency: usize,
 }
 
 impl S3Client {
-    async fn new(bucket: CompactString, region: String) -> S3Client {
-        let config = aws_config::from_env()
+    /// Construct a client for the given bucket in the given region.  If
+    /// `endpoint` is non-`None`, requests are made to that endpoint using
+    /// path-style addressing instead of to AWS.
+    async fn new(
+        bucket: CompactString,
+        region: String,
+        endpoint: Option<&S3Endpoint>,
+        s3config: S3Config,
+    ) -> S3Client {
+        let timeout_config = TimeoutConfig::builder()
+            .connect_timeout(s3config.connect_timeout)
+            .read_timeout(s3config.read_timeout)
+            .build();
+        let retry_config = RetryConfig::standard().with_max_attempts(s3config.max_attempts.max(1));
+        let mut loader = aws_config::from_env()
             .app_name(
                 aws_config::AppName::new("dandidav")
                     .expect(r#""dandidav" should be a valid app name"#),
             )
             .no_credentials()
             .region(aws_config::Region::new(region))
-            .load()
-            .await;
-        let inner = Client::new(&config);
-        S3Client { inner, bucket }
+            .timeout_config(timeout_config)
+            .retry_config(retry_config);
+        if let Some(endpoint) = endpoint {
+            loader = loader.endpoint_url(endpoint.url().as_str());
+        }
+        let config = loader.load().await;
+        let inner = Client::from_conf(
+            aws_sdk_s3::config::Builder::from(&config)
+                .force_path_style(endpoint.is_some())
+                .build(),
+        );
+        let bucket_url = match endpoint {
+            Some(endpoint) => endpoint.bucket_url(&bucket),
+            None => format!("https://{bucket}.s3.amazonaws.com")
+                .parse::<HttpUrl>()
+                .expect("bucket should be a valid hostname component"),
+        };
+        S3Client {
+            inner,
+            bucket,
+            bucket_url,
+            failures: Arc::new(AtomicU32::new(0)),
+            list_concurrency: s3config.list_concurrency,
+        }
+    }
+
+    /// Return the number of requests made with this client that have failed
+    /// since the last successful request
+    pub(crate) fn consecutive_failures(&self) -> u32 {
+        self.failures.load(AtomicOrdering::Relaxed)
     }
 
     pub(crate) fn with_prefix(self: Arc<Self>, prefix: PureDirPath) -> PrefixedS3Client {
@@ -49,15 +128,42 @@ impl S3Client {
 
     // `key_prefix` may or may not end with `/`; it is used as-is
     fn list_entry_pages<S: Into<String>>(&self, key_prefix: S) -> ListEntryPages {
-        ListEntryPages::new(self, key_prefix)
+        ListEntryPages::new(self, key_prefix, None)
     }
 
     fn get_folder_entries(
         &self,
         key_prefix: &PureDirPath,
     ) -> impl Stream<Item = Result<S3Entry, S3Error>> {
-        self.list_entry_pages(key_prefix)
-            .try_flat_iter_map(|page| page)
+        if self.list_concurrency > 1 {
+            self.get_folder_entries_concurrently(key_prefix)
+                .left_stream()
+        } else {
+            self.list_entry_pages(key_prefix)
+                .try_flat_iter_map(|page| page)
+                .right_stream()
+        }
+    }
+
+    /// List the entries of the folder at `key_prefix` by splitting them into
+    /// up to `self.list_concurrency` [`NameRange`]s and listing the ranges
+    /// concurrently.  The entries in each range are yielded once the range
+    /// has been listed in full, with the ranges yielded in order.
+    fn get_folder_entries_concurrently(
+        &self,
+        key_prefix: &PureDirPath,
+    ) -> impl Stream<Item = Result<S3Entry, S3Error>> {
+        let ranges = NameRange::split(self.list_concurrency)
+            .into_iter()
+            .map(|range| {
+                let pages = ListEntryPages::new(self, key_prefix, range.start_after(key_prefix));
+                range.list(pages, key_prefix.to_string())
+            })
+            .collect::<Vec<_>>();
+        let concurrency = ranges.len();
+        stream::iter(ranges)
+            .buffered(concurrency)
+            .try_flat_iter_map(|entries| entries)
     }
 
     // Returns `None` if nothing found at path
@@ -138,19 +244,146 @@ impl PrefixedS3Client {
     }
 }
 
+/// The characters at which the entries of a folder are split into
+/// [`NameRange`]s for concurrent listing, chosen so that the (typically
+/// numeric) names of Zarr chunks are spread across the ranges
+static NAME_RANGE_BOUNDARIES: &[u8] = b"123456789A";
+
+/// A range of the names of the entries in a folder, delimited by their first
+/// characters.
+///
+/// As each `ListObjectsV2` request for a folder needs the continuation token
+/// returned by the previous request, the pages of a single listing can only
+/// be fetched one at a time.  Instead, large folders can be listed faster by
+/// splitting their entries into ranges and listing each range separately,
+/// starting at the range's start (via the `StartAfter` parameter) and
+/// stopping upon reaching the range's end.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct NameRange {
+    /// Entries whose names start with a character before this one are
+    /// excluded.  `None` means the range is unbounded below.
+    start: Option<u8>,
+
+    /// Entries whose names start with this character or a later one are
+    /// excluded.  `None` means the range is unbounded above.
+    end: Option<u8>,
+}
+
+impl NameRange {
+    /// Split the space of all entry names into up to `n` consecutive ranges
+    /// at characters in [`NAME_RANGE_BOUNDARIES`]
+    fn split(n: usize) -> Vec<NameRange> {
+        let n = n.clamp(1, NAME_RANGE_BOUNDARIES.len() + 1);
+        let cuts = (1..n)
+            .filter_map(|i| {
+                NAME_RANGE_BOUNDARIES
+                    .get((i * (NAME_RANGE_BOUNDARIES.len() + 1) / n).saturating_sub(1))
+                    .copied()
+            })
+            .collect::<Vec<_>>();
+        std::iter::once(None)
+            .chain(cuts.iter().copied().map(Some))
+            .zip(cuts.iter().copied().map(Some).chain(std::iter::once(None)))
+            .map(|(start, end)| NameRange { start, end })
+            .collect()
+    }
+
+    /// Return the `StartAfter` value to use when listing the range within
+    /// the folder at `key_prefix`.  This sorts after all names that start
+    /// with the character preceding `start`, so that they are skipped.
+    fn start_after(&self, key_prefix: &PureDirPath) -> Option<String> {
+        self.start.map(|c| {
+            format!(
+                "{key_prefix}{}{}",
+                char::from(c.saturating_sub(1)),
+                char::MAX
+            )
+        })
+    }
+
+    /// Returns `true` if `name` is within the range
+    fn contains(&self, name: &str) -> bool {
+        name.bytes().next().is_some_and(|c| {
+            self.start.map_or(true, |s| s <= c) && self.end.map_or(true, |e| c < e)
+        })
+    }
+
+    /// Returns `true` if `name` is past the end of the range
+    fn precedes(&self, name: &str) -> bool {
+        match (self.end, name.bytes().next()) {
+            (Some(e), Some(c)) => e <= c,
+            _ => false,
+        }
+    }
+
+    /// Return the entries in `page` (a page of the listing of the folder at
+    /// `key_prefix`) that are within the range, along with whether any
+    /// entries in the page were past the end of the range, in which case the
+    /// listing of the range is complete
+    fn filter_page(&self, key_prefix: &str, page: S3EntryPage) -> (Vec<S3Entry>, bool) {
+        let mut done = false;
+        let entries = page
+            .into_iter()
+            .filter(|entry| {
+                let key = match entry {
+                    S3Entry::Folder(folder) => &*folder.key_prefix,
+                    S3Entry::Object(obj) => &*obj.key,
+                };
+                let name = key.strip_prefix(key_prefix).unwrap_or(key);
+                if self.precedes(name) {
+                    done = true;
+                }
+                self.contains(name)
+            })
+            .collect();
+        (entries, done)
+    }
+
+    /// Collect the entries within the range from `pages`, a listing of the
+    /// folder at `key_prefix` starting at [`NameRange::start_after()`]
+    async fn list(
+        self,
+        mut pages: ListEntryPages,
+        key_prefix: String,
+    ) -> Result<Vec<S3Entry>, S3Error> {
+        let mut entries = Vec::new();
+        while let Some(page) = pages.try_next().await? {
+            let (page_entries, done) = self.filter_page(&key_prefix, page);
+            entries.extend(page_entries);
+            if done {
+                break;
+            }
+        }
+        Ok(entries)
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub(crate) struct BucketSpec {
     pub(crate) bucket: CompactString,
     pub(crate) region: Option<String>,
+
+    /// The custom S3-compatible endpoint on which the bucket is located, or
+    /// `None` if the bucket is on AWS
+    pub(crate) endpoint: Option<S3Endpoint>,
 }
 
 impl BucketSpec {
-    pub(crate) async fn into_s3client(self) -> Result<S3Client, GetBucketRegionError> {
-        let region = match self.region {
-            Some(region) => region,
-            None => get_bucket_region(&self.bucket).await?,
+    /// Construct an [`S3Client`] for the bucket with the given settings,
+    /// using `client` to look up the bucket's region if it is not already
+    /// known.  Buckets on custom endpoints whose regions are not known are
+    /// assumed to be in [`CUSTOM_S3_ENDPOINT_REGION`].
+    pub(crate) async fn into_s3client(
+        self,
+        client: &httputil::Client,
+        s3config: S3Config,
+    ) -> Result<S3Client, GetBucketRegionError> {
+        let region = match (self.region, &self.endpoint) {
+            (Some(region), _) => region,
+            (None, Some(_)) => CUSTOM_S3_ENDPOINT_REGION.to_owned(),
+            (None, None) => get_bucket_region(client, &self.bucket).await?,
         };
-        Ok(S3Client::new(self.bucket, region).await)
+        Ok(S3Client::new(self.bucket, region, self.endpoint.as_ref(), s3config).await)
     }
 }
 
@@ -162,18 +395,33 @@ pub(crate) struct S3Location {
 
 impl S3Location {
     /// Parse an S3 URL into an `S3Location`.  The URL must have a scheme of
-    /// "http" or "https" and have a domain in one of the following formats:
+    /// "http" or "https" and be in one of the following formats:
+    ///
+    /// - `{bucket}.s3.{region}.amazonaws.com/{key}`
+    /// - `{bucket}.s3-{region}.amazonaws.com/{key}`
+    /// - `{bucket}.s3.amazonaws.com/{key}`
+    /// - `s3.{region}.amazonaws.com/{bucket}/{key}`
+    /// - `s3-{region}.amazonaws.com/{bucket}/{key}`
+    /// - `s3.amazonaws.com/{bucket}/{key}`
     ///
-    /// - `{bucket}.s3.{region}.amazonaws.com`
-    /// - `{bucket}.s3-{region}.amazonaws.com`
-    /// - `{bucket}.s3.amazonaws.com`
+    /// or else be a virtual-hosted-style or path-style URL for one of the
+    /// custom endpoints configured via [`set_custom_endpoints()`].
     ///
-    /// The bucket and optional region are extracted from the domain and used
-    /// to construct the `bucket_spec` field of the resulting `S3Location`.
+    /// The bucket and optional region are extracted from the URL and used to
+    /// construct the `bucket_spec` field of the resulting `S3Location`.
     ///
-    /// The path component of the URL has its leading forward slash (if any)
-    /// stripped and is then percent-decoded to produce the `key` field.
+    /// The remainder of the path component of the URL is percent-decoded to
+    /// produce the `key` field.
     pub(crate) fn parse_url(url: &Url) -> Result<S3Location, S3UrlError> {
+        S3Location::parse_url_with_endpoints(url, custom_endpoints())
+    }
+
+    /// Like [`S3Location::parse_url()`], but recognizing URLs for the given
+    /// custom endpoints instead of the configured ones
+    fn parse_url_with_endpoints(
+        url: &Url,
+        endpoints: &[S3Endpoint],
+    ) -> Result<S3Location, S3UrlError> {
         // cf. <https://docs.aws.amazon.com/AmazonS3/latest/userguide/VirtualHosting.html>
         if !matches!(url.scheme(), "http" | "https") {
             return Err(S3UrlError::NotHttp);
@@ -181,30 +429,27 @@ impl S3Location {
         let Some(Host::Domain(fqdn)) = url.host() else {
             return Err(S3UrlError::NoDomain);
         };
-        // Possible domain formats (See link above):
-        // - {bucket}.s3.{region}.amazonaws.com
-        // - {bucket}.s3-{region}.amazonaws.com
-        // - {bucket}.s3.amazonaws.com
-        let e = S3UrlError::InvalidDomain;
-        let (bucket, s) = fqdn.split_once('.').ok_or(e)?;
-        let s = s
-            .strip_prefix("s3")
-            .ok_or(e)?
-            .strip_suffix(".amazonaws.com")
-            .ok_or(e)?;
-        let region = if s.is_empty() {
-            None
-        } else if let Some(region) = s.strip_prefix(['.', '-']) {
-            if !region.contains('.') {
-                Some(region)
-            } else {
-                return Err(e);
+        let path = url.path();
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let (bucket, region, endpoint, path) = if let Some(region) = parse_aws_s3_domain(fqdn) {
+            let (bucket, path) = path.split_once('/').unwrap_or((path, ""));
+            if bucket.is_empty() {
+                return Err(S3UrlError::NoBucket);
             }
+            (bucket, region, None, path)
+        } else if let Some((bucket, region)) = fqdn
+            .split_once('.')
+            .and_then(|(bucket, s)| Some((bucket, parse_aws_s3_domain(s)?)))
+        {
+            (bucket, region, None, path)
+        } else if let Some((endpoint, (bucket, path))) = endpoints
+            .iter()
+            .find_map(|ep| ep.locate(url).map(|loc| (ep, loc)))
+        {
+            (bucket, None, Some(endpoint.clone()), path)
         } else {
-            return Err(e);
+            return Err(S3UrlError::InvalidDomain);
         };
-        let path = url.path();
-        let path = path.strip_prefix('/').unwrap_or(path);
         let key = percent_encoding::percent_decode_str(path)
             .decode_utf8()
             .map_err(S3UrlError::BadPath)?
@@ -213,12 +458,27 @@ impl S3Location {
             bucket_spec: BucketSpec {
                 bucket: bucket.into(),
                 region: region.map(String::from),
+                endpoint,
             },
             key,
         })
     }
 }
 
+/// If `domain` is the domain of an AWS S3 endpoint, i.e., one of
+/// `s3.{region}.amazonaws.com`, `s3-{region}.amazonaws.com`, or
+/// `s3.amazonaws.com`, return the region (if any)
+fn parse_aws_s3_domain(domain: &str) -> Option<Option<&str>> {
+    let s = domain.strip_prefix("s3")?.strip_suffix(".amazonaws.com")?;
+    if s.is_empty() {
+        Some(None)
+    } else {
+        s.strip_prefix(['.', '-'])
+            .filter(|region| !region.is_empty() && !region.contains('.'))
+            .map(Some)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
 pub(crate) enum S3UrlError {
     #[error("URL is not HTTP(S)")]
@@ -227,6 +487,8 @@ pub(crate) enum S3UrlError {
     NoDomain,
     #[error("domain in URL is not S3")]
     InvalidDomain,
+    #[error("path-style S3 URL lacks bucket")]
+    NoBucket,
     #[error("URL path does not decode to UTF-8")]
     BadPath(#[source] std::str::Utf8Error),
 }
@@ -323,9 +585,11 @@ pub(crate) struct S3Object {
 }
 
 impl S3Object {
+    /// Convert an object returned by `ListObjectsV2` into an `S3Object`,
+    /// forming its download URL by appending its key to `bucket_url`
     fn try_from_aws_object(
         obj: aws_sdk_s3::types::Object,
-        bucket: &str,
+        bucket_url: &HttpUrl,
     ) -> Result<S3Object, TryFromAwsObjectError> {
         let Some(key) = obj.key else {
             return Err(TryFromAwsObjectError::NoKey);
@@ -340,9 +604,7 @@ impl S3Object {
             return Err(TryFromAwsObjectError::NoSize { key });
         };
         let keypath = PurePath::try_from(key.clone()).map_err(TryFromAwsObjectError::BadKey)?;
-        let mut download_url = format!("https://{bucket}.s3.amazonaws.com")
-            .parse::<HttpUrl>()
-            .expect("bucket should be a valid hostname component");
+        let mut download_url = bucket_url.clone();
         // Adding the key this way is necessary in order for URL-unsafe
         // characters to be percent-encoded:
         download_url.extend(key.split('/'));
@@ -401,7 +663,22 @@ pub(crate) enum S3Error {
 impl S3Error {
     /// Classify the general type of error
     pub(crate) fn class(&self) -> ErrorClass {
-        ErrorClass::BadGateway
+        match self {
+            S3Error::ListObjects { source, .. } if is_timeout(source) => ErrorClass::GatewayTimeout,
+            S3Error::ListObjects { .. } | S3Error::BadObject { .. } | S3Error::BadPrefix { .. } => {
+                ErrorClass::BadGateway
+            }
+        }
+    }
+}
+
+/// Returns `true` if `e` indicates that a request to S3 timed out, either
+/// while connecting or while waiting for a response
+fn is_timeout(e: &ListObjectsError) -> bool {
+    match e {
+        SdkError::TimeoutError(_) => true,
+        SdkError::DispatchFailure(failure) => failure.is_timeout(),
+        _ => false,
     }
 }
 
@@ -437,7 +714,10 @@ pub(crate) enum TryFromAwsObjectError {
 
 // The AWS SDK currently cannot be used for this:
 // <https://github.com/awslabs/aws-sdk-rust/issues/1052>
-pub(crate) async fn get_bucket_region(bucket: &str) -> Result<String, GetBucketRegionError> {
+pub(crate) async fn get_bucket_region(
+    client: &httputil::Client,
+    bucket: &str,
+) -> Result<String, GetBucketRegionError> {
     let url_str = format!("https://{bucket}.s3.amazonaws.com");
     let url = url_str
         .parse::<HttpUrl>()
@@ -445,9 +725,8 @@ pub(crate) async fn get_bucket_region(bucket: &str) -> Result<String, GetBucketR
             url: url_str,
             source,
         })?;
-    let client = httputil::Client::new()?;
     let r = client.head(url).await?;
-    match r.headers().get("x-amz-bucket-region").map(|hv| hv.to_str()) {
+    match r.headers.get("x-amz-bucket-region").map(|hv| hv.to_str()) {
         Some(Ok(region)) => Ok(region.to_owned()),
         Some(Err(e)) => Err(GetBucketRegionError::BadHeader(e)),
         None => Err(GetBucketRegionError::NoHeader),
@@ -456,8 +735,6 @@ pub(crate) async fn get_bucket_region(bucket: &str) -> Result<String, GetBucketR
 
 #[derive(Debug, Error)]
 pub(crate) enum GetBucketRegionError {
-    #[error(transparent)]
-    BuildClient(#[from] BuildClientError),
     #[error(transparent)]
     Http(#[from] HttpError),
     #[error("URL constructed for bucket is invalid: {url:?}")]
@@ -475,7 +752,6 @@ impl GetBucketRegionError {
     /// Classify the general type of error
     pub(crate) fn class(&self) -> ErrorClass {
         match self {
-            GetBucketRegionError::BuildClient(_) => ErrorClass::Internal,
             GetBucketRegionError::Http(source) => source.class(),
             GetBucketRegionError::BadUrl { .. } => ErrorClass::Internal,
             GetBucketRegionError::NoHeader => ErrorClass::BadGateway,
@@ -487,7 +763,146 @@ impl GetBucketRegionError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::httputil::FakeTransport;
+    use assert_matches::assert_matches;
+    use reqwest::StatusCode;
     use rstest::rstest;
+    use std::sync::Arc;
+
+    #[rstest]
+    #[case(0, vec![(None, None)])]
+    #[case(1, vec![(None, None)])]
+    #[case(2, vec![(None, Some(b'5')), (Some(b'5'), None)])]
+    #[case(3, vec![(None, Some(b'3')), (Some(b'3'), Some(b'7')), (Some(b'7'), None)])]
+    fn split_name_ranges(#[case] n: usize, #[case] expected: Vec<(Option<u8>, Option<u8>)>) {
+        let expected = expected
+            .into_iter()
+            .map(|(start, end)| NameRange { start, end })
+            .collect::<Vec<_>>();
+        assert_eq!(NameRange::split(n), expected);
+    }
+
+    #[test]
+    fn split_name_ranges_max() {
+        let ranges = NameRange::split(100);
+        assert_eq!(ranges.len(), NAME_RANGE_BOUNDARIES.len() + 1);
+        assert_eq!(ranges[0].end, Some(b'1'));
+        assert_eq!(ranges[10].start, Some(b'A'));
+        for (prev, next) in ranges.iter().zip(&ranges[1..]) {
+            assert_eq!(prev.end, next.start);
+        }
+    }
+
+    #[test]
+    fn name_range_start_after() {
+        let prefix = "zarr/abc/".parse::<PureDirPath>().unwrap();
+        let range = NameRange {
+            start: Some(b'5'),
+            end: None,
+        };
+        assert_eq!(
+            range.start_after(&prefix).as_deref(),
+            Some("zarr/abc/4\u{10FFFF}")
+        );
+        let range = NameRange {
+            start: None,
+            end: Some(b'5'),
+        };
+        assert_eq!(range.start_after(&prefix), None);
+    }
+
+    #[test]
+    fn name_range_filter_page() {
+        fn object(key: &str) -> S3Object {
+            S3Object {
+                key: key.parse().unwrap(),
+                modified: time::macros::datetime!(2024-01-01 00:00:00 UTC),
+                size: 1,
+                etag: "abc".into(),
+                download_url: "https://bucket.s3.amazonaws.com/x".parse().unwrap(),
+            }
+        }
+
+        let range = NameRange {
+            start: Some(b'3'),
+            end: Some(b'7'),
+        };
+        let page = S3EntryPage {
+            folders: vec![
+                S3Folder {
+                    key_prefix: "zarr/2/".parse().unwrap(),
+                },
+                S3Folder {
+                    key_prefix: "zarr/3/".parse().unwrap(),
+                },
+            ],
+            objects: vec![object("zarr/2.0"), object("zarr/3.0"), object("zarr/6.9")],
+        };
+        let (entries, done) = range.filter_page("zarr/", page.clone());
+        assert_eq!(
+            entries,
+            vec![
+                S3Entry::Folder(page.folders[1].clone()),
+                S3Entry::Object(page.objects[1].clone()),
+                S3Entry::Object(page.objects[2].clone()),
+            ]
+        );
+        assert!(!done);
+        let page = S3EntryPage {
+            folders: Vec::new(),
+            objects: vec![object("zarr/6.10"), object("zarr/7.0"), object("zarr/8.0")],
+        };
+        let (entries, done) = range.filter_page("zarr/", page.clone());
+        assert_eq!(entries, vec![S3Entry::Object(page.objects[0].clone())]);
+        assert!(done);
+    }
+
+    fn client(transport: FakeTransport) -> httputil::Client {
+        httputil::Client::new(
+            httputil::RetryPolicy::default(),
+            httputil::HttpConfig::default(),
+            None,
+        )
+        .unwrap()
+        .with_transport(Arc::new(transport))
+    }
+
+    #[tokio::test]
+    async fn bucket_region() {
+        let client = client(FakeTransport::new().respond(
+            "https://dandiarchive.s3.amazonaws.com/",
+            StatusCode::OK,
+            &[("x-amz-bucket-region", "us-east-2")],
+            "",
+        ));
+        assert_eq!(
+            get_bucket_region(&client, "dandiarchive").await.unwrap(),
+            "us-east-2"
+        );
+    }
+
+    #[tokio::test]
+    async fn bucket_region_missing() {
+        let client = client(FakeTransport::new().respond(
+            "https://dandiarchive.s3.amazonaws.com/",
+            StatusCode::OK,
+            &[],
+            "",
+        ));
+        assert_matches!(
+            get_bucket_region(&client, "dandiarchive").await,
+            Err(GetBucketRegionError::NoHeader)
+        );
+    }
+
+    #[tokio::test]
+    async fn bucket_region_no_such_bucket() {
+        let client = client(FakeTransport::new());
+        assert_matches!(
+            get_bucket_region(&client, "nonexistent").await,
+            Err(GetBucketRegionError::Http(HttpError::NotFound { .. }))
+        );
+    }
 
     #[rstest]
     #[case(
@@ -504,6 +919,24 @@ mod tests {
         None,
         "zarr/bf47be1a-4fed-4105-bcb4-c52534a45b82/foo bar.txt"
     )]
+    #[case(
+        "https://s3.amazonaws.com/dandiarchive/zarr/bf47be1a-4fed-4105-bcb4-c52534a45b82/",
+        "dandiarchive",
+        None,
+        "zarr/bf47be1a-4fed-4105-bcb4-c52534a45b82/"
+    )]
+    #[case(
+        "https://s3.us-west-2.amazonaws.com/dandiarchive/zarr/foo%20bar.txt",
+        "dandiarchive",
+        Some("us-west-2"),
+        "zarr/foo bar.txt"
+    )]
+    #[case(
+        "https://s3-us-west-2.amazonaws.com/dandiarchive",
+        "dandiarchive",
+        Some("us-west-2"),
+        ""
+    )]
     fn test_good_s3location_urls(
         #[case] url: Url,
         #[case] bucket: &str,
@@ -513,11 +946,36 @@ mod tests {
         let s3loc = S3Location::parse_url(&url).unwrap();
         assert_eq!(s3loc.bucket_spec.bucket, bucket);
         assert_eq!(s3loc.bucket_spec.region.as_deref(), region);
+        assert_eq!(s3loc.bucket_spec.endpoint, None);
         assert_eq!(s3loc.key, key);
     }
 
     #[rstest]
-    #[case("https://s3.amazonaws.com/dandiarchive/zarr/bf47be1a-4fed-4105-bcb4-c52534a45b82/")]
+    #[case("https://minio.example.org/dandi/zarr/abc/", "dandi", "zarr/abc/")]
+    #[case("https://dandi.minio.example.org/zarr/abc/", "dandi", "zarr/abc/")]
+    #[case(
+        "https://minio.example.org/dandi/foo%20bar.txt",
+        "dandi",
+        "foo bar.txt"
+    )]
+    fn test_custom_endpoint_s3location_urls(
+        #[case] url: Url,
+        #[case] bucket: &str,
+        #[case] key: &str,
+    ) {
+        let endpoint = "https://minio.example.org".parse::<S3Endpoint>().unwrap();
+        let s3loc =
+            S3Location::parse_url_with_endpoints(&url, std::slice::from_ref(&endpoint)).unwrap();
+        assert_eq!(s3loc.bucket_spec.bucket, bucket);
+        assert_eq!(s3loc.bucket_spec.region, None);
+        assert_eq!(s3loc.bucket_spec.endpoint, Some(endpoint));
+        assert_eq!(s3loc.key, key);
+        assert!(S3Location::parse_url_with_endpoints(&url, &[]).is_err());
+    }
+
+    #[rstest]
+    #[case("https://s3.amazonaws.com/")]
+    #[case("https://s3..amazonaws.com/dandiarchive/zarr/")]
     #[case("https://dandiarchive.amazonaws.com/zarr/bf47be1a-4fed-4105-bcb4-c52534a45b82/")]
     #[case(
         "https://dandiarchive.us-west-2.amazonaws.com/zarr/bf47be1a-4fed-4105-bcb4-c52534a45b82/"
@@ -526,4 +984,62 @@ mod tests {
         let r = S3Location::parse_url(&url);
         assert!(r.is_err());
     }
+
+    #[test]
+    fn test_s3_error_class() {
+        let cases = [
+            S3Error::ListObjects {
+                bucket: "dandiarchive".into(),
+                prefix: "zarr/".into(),
+                source: ListObjectsError::construction_failure("test"),
+            },
+            S3Error::BadObject {
+                bucket: "dandiarchive".into(),
+                prefix: "zarr/".into(),
+                source: TryFromAwsObjectError::NoKey,
+            },
+            S3Error::BadPrefix {
+                bucket: "dandiarchive".into(),
+                prefix: "zarr/".into(),
+                source: TryFromCommonPrefixError::NoPrefix,
+            },
+        ];
+        for e in cases {
+            assert_eq!(e.class(), ErrorClass::BadGateway, "wrong class for {e:?}");
+        }
+    }
+
+    #[test]
+    fn test_s3_timeout_error_class() {
+        let e = S3Error::ListObjects {
+            bucket: "dandiarchive".into(),
+            prefix: "zarr/".into(),
+            source: ListObjectsError::timeout_error("timed out"),
+        };
+        assert_eq!(e.class(), ErrorClass::GatewayTimeout);
+    }
+
+    #[test]
+    fn test_get_bucket_region_error_class() {
+        let url = "https://dandiarchive.s3.amazonaws.com"
+            .parse::<HttpUrl>()
+            .unwrap();
+        let cases = [
+            (
+                GetBucketRegionError::Http(HttpError::NotFound { url }),
+                ErrorClass::NotFound,
+            ),
+            (
+                GetBucketRegionError::BadUrl {
+                    url: "https://bad bucket.s3.amazonaws.com".into(),
+                    source: ParseHttpUrlError::BadScheme,
+                },
+                ErrorClass::Internal,
+            ),
+            (GetBucketRegionError::NoHeader, ErrorClass::BadGateway),
+        ];
+        for (e, class) in cases {
+            assert_eq!(e.class(), class, "wrong class for {e:?}");
+        }
+    }
 }
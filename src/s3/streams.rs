@@ -2,12 +2,19 @@ use super::{
     ListObjectsError, S3Client, S3EntryPage, S3Error, S3Folder, S3Object, TryFromAwsObjectError,
     TryFromCommonPrefixError,
 };
+use crate::httputil::HttpUrl;
+use crate::metrics::metrics;
 use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
 use aws_smithy_async::future::pagination_stream::PaginationStream;
 use futures_util::Stream;
 use smartstring::alias::CompactString;
 use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
 use std::task::{ready, Context, Poll};
+use std::time::Instant;
 
 // Implementing list_entry_pages() as a manually-implemented Stream instead of
 // via async_stream lets us save about 3500 bytes on dandidav's top-level
@@ -16,15 +23,28 @@ use std::task::{ready, Context, Poll};
 #[must_use = "streams do nothing unless polled"]
 pub(super) struct ListEntryPages {
     bucket: CompactString,
+    /// The URL of the root of the bucket, for forming objects' download URLs
+    bucket_url: HttpUrl,
     key_prefix: String,
     inner: Option<PaginationStream<Result<ListObjectsV2Output, ListObjectsError>>>,
+    /// The originating client's counter of consecutive failed requests
+    failures: Arc<AtomicU32>,
+    /// When the request for the page currently being fetched was started
+    page_start: Option<Instant>,
 }
 
 impl ListEntryPages {
-    pub(super) fn new<S: Into<String>>(client: &S3Client, key_prefix: S) -> Self {
+    /// List the keys under `key_prefix`, starting after the key
+    /// `start_after` if given
+    pub(super) fn new<S: Into<String>>(
+        client: &S3Client,
+        key_prefix: S,
+        start_after: Option<String>,
+    ) -> Self {
         let key_prefix = key_prefix.into();
         ListEntryPages {
             bucket: client.bucket.clone(),
+            bucket_url: client.bucket_url.clone(),
             key_prefix: key_prefix.clone(),
             inner: Some(
                 client
@@ -32,10 +52,13 @@ impl ListEntryPages {
                     .list_objects_v2()
                     .bucket(&*client.bucket)
                     .prefix(key_prefix)
+                    .set_start_after(start_after)
                     .delimiter("/")
                     .into_paginator()
                     .send(),
             ),
+            failures: Arc::clone(&client.failures),
+            page_start: None,
         }
     }
 
@@ -82,22 +105,32 @@ impl Stream for ListEntryPages {
     type Item = Result<S3EntryPage, S3Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let Some(inner) = self.inner.as_mut() else {
+        let this = &mut *self;
+        let Some(inner) = this.inner.as_mut() else {
             return None.into();
         };
+        let page_start = *this.page_start.get_or_insert_with(Instant::now);
         let Some(r) = ready!(inner.poll_next(cx)) else {
             self.inner = None;
             return None.into();
         };
+        self.page_start = None;
+        metrics().record_s3_list(page_start.elapsed());
         let page = match r {
-            Ok(page) => page,
-            Err(source) => return self.die_list_objects(source),
+            Ok(page) => {
+                self.failures.store(0, Ordering::Relaxed);
+                page
+            }
+            Err(source) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                return self.die_list_objects(source);
+            }
         };
         let objects = match page
             .contents
             .unwrap_or_default()
             .into_iter()
-            .map(|obj| S3Object::try_from_aws_object(obj, &self.bucket))
+            .map(|obj| S3Object::try_from_aws_object(obj, &self.bucket_url))
             .collect::<Result<Vec<_>, _>>()
         {
             Ok(objects) => objects,
@@ -0,0 +1,436 @@
+//! Generation of `/sitemap.xml` listing the Dandisets & published versions
+//! on the primary Archive instance, for use by search engines
+use crate::consts::{SITEMAP_CONCURRENCY, SITEMAP_CONTENT_TYPE, SITEMAP_MAX_URLS, SITEMAP_TTL};
+use crate::dandi::{DandiClient, DandiError, Dandiset, VersionId};
+use crate::dav::DandisetFilter;
+use crate::httputil::HttpUrl;
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, response::Response, StatusCode},
+    response::IntoResponse,
+};
+use futures_util::{StreamExt, TryStreamExt};
+use std::future::ready;
+use std::sync::Arc;
+use std::time::Instant;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::Mutex;
+use xml::writer::{events::XmlEvent, EmitterConfig, EventWriter};
+
+/// The XML namespace of sitemap documents
+static SITEMAP_XMLNS: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// A generator of sitemaps listing the URLs at which the primary Archive
+/// instance's Dandisets & their published versions are served.
+///
+/// If there are more URLs than can be listed in a single sitemap,
+/// `/sitemap.xml` is instead a sitemap index pointing to the pages
+/// `/sitemaps/1.xml`, `/sitemaps/2.xml`, etc.
+#[derive(Debug)]
+pub(crate) struct Sitemap {
+    dandi: DandiClient,
+    filter: Arc<DandisetFilter>,
+
+    /// The public URL at which `dandidav` is served, used as the base of all
+    /// URLs in the sitemap
+    base_url: HttpUrl,
+
+    /// The maximum number of URLs to list in a single sitemap
+    max_urls: usize,
+
+    /// The most recently generated list of URLs and the time at which it was
+    /// generated
+    cache: Mutex<Option<(Instant, Arc<[SitemapUrl]>)>>,
+}
+
+impl Sitemap {
+    pub(crate) fn new(
+        dandi: DandiClient,
+        filter: Arc<DandisetFilter>,
+        base_url: HttpUrl,
+    ) -> Sitemap {
+        Sitemap {
+            dandi,
+            filter,
+            base_url,
+            max_urls: SITEMAP_MAX_URLS,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Return the public URL of `/sitemap.xml`
+    pub(crate) fn url(&self) -> HttpUrl {
+        let mut url = self.base_url.clone();
+        url.push("sitemap.xml");
+        url
+    }
+
+    /// Respond to a request for `/sitemap.xml`
+    pub(crate) async fn index_response(&self) -> Response<Body> {
+        let urls = match self.urls().await {
+            Ok(urls) => urls,
+            Err(e) => return error_response(e),
+        };
+        let body = if urls.len() <= self.max_urls {
+            render_urlset(&urls)
+        } else {
+            let pages = urls.len().div_ceil(self.max_urls);
+            let locs = (1..=pages)
+                .map(|n| {
+                    let mut url = self.base_url.clone();
+                    url.extend(["sitemaps", &format!("{n}.xml")]);
+                    url
+                })
+                .collect::<Vec<_>>();
+            render_sitemapindex(&locs)
+        };
+        // Note: This response should not have WebDAV headers (DAV, Allow)
+        ([(CONTENT_TYPE, SITEMAP_CONTENT_TYPE)], body).into_response()
+    }
+
+    /// Respond to a request for `/sitemaps/{page}`, where `page` is of the
+    /// form `{n}.xml` for a 1-based page number `n`
+    pub(crate) async fn page_response(&self, page: &str) -> Response<Body> {
+        let Some(n) = page
+            .strip_suffix(".xml")
+            .and_then(|s| s.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(1))
+        else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let urls = match self.urls().await {
+            Ok(urls) => urls,
+            Err(e) => return error_response(e),
+        };
+        if urls.len() <= self.max_urls {
+            // Everything is listed in `/sitemap.xml` itself
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        let Some(chunk) = urls.chunks(self.max_urls).nth(n) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        // Note: This response should not have WebDAV headers (DAV, Allow)
+        ([(CONTENT_TYPE, SITEMAP_CONTENT_TYPE)], render_urlset(chunk)).into_response()
+    }
+
+    /// Return the URLs to list in the sitemap, regenerating them if the
+    /// cached list is missing or older than [`SITEMAP_TTL`]
+    async fn urls(&self) -> Result<Arc<[SitemapUrl]>, DandiError> {
+        let mut cache = self.cache.lock().await;
+        if let Some((generated, ref urls)) = *cache {
+            if generated.elapsed() < SITEMAP_TTL {
+                return Ok(Arc::clone(urls));
+            }
+        }
+        let urls = Arc::<[SitemapUrl]>::from(self.generate().await?);
+        *cache = Some((Instant::now(), Arc::clone(&urls)));
+        Ok(urls)
+    }
+
+    /// Fetch the Archive's Dandisets and the published versions of each one
+    /// and construct the sitemap URLs for them
+    async fn generate(&self) -> Result<Vec<SitemapUrl>, DandiError> {
+        let dandisets = self
+            .dandi
+            .get_all_dandisets()
+            .try_filter(|ds| ready(self.filter.allows(&ds.identifier)))
+            .try_collect::<Vec<_>>()
+            .await?;
+        let urls = futures_util::stream::iter(dandisets)
+            .map(|ds| self.dandiset_urls(ds))
+            .buffered(SITEMAP_CONCURRENCY)
+            .try_concat()
+            .await?;
+        Ok(urls)
+    }
+
+    /// Construct the sitemap URLs for `ds` and its published versions
+    async fn dandiset_urls(&self, ds: Dandiset) -> Result<Vec<SitemapUrl>, DandiError> {
+        let mut loc = self.base_url.clone();
+        loc.extend(["dandisets", ds.identifier.as_ref()])
+            .ensure_dirpath();
+        let mut urls = vec![SitemapUrl {
+            loc,
+            lastmod: ds.modified,
+        }];
+        if ds.most_recent_published_version.is_some() {
+            let endpoint = self.dandi.dandiset(ds.identifier.clone());
+            let mut versions = std::pin::pin!(endpoint.get_all_versions());
+            while let Some(v) = versions.try_next().await? {
+                if v.version == VersionId::Draft {
+                    continue;
+                }
+                let mut loc = self.base_url.clone();
+                loc.extend([
+                    "dandisets",
+                    ds.identifier.as_ref(),
+                    "releases",
+                    v.version.as_ref(),
+                ])
+                .ensure_dirpath();
+                urls.push(SitemapUrl {
+                    loc,
+                    lastmod: v.modified,
+                });
+            }
+        }
+        Ok(urls)
+    }
+}
+
+/// A URL listed in a sitemap
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct SitemapUrl {
+    loc: HttpUrl,
+    lastmod: OffsetDateTime,
+}
+
+/// Render a `urlset` sitemap document listing the given URLs
+fn render_urlset(urls: &[SitemapUrl]) -> Vec<u8> {
+    render(|writer| {
+        writer.write(XmlEvent::start_element("urlset").default_ns(SITEMAP_XMLNS))?;
+        for url in urls {
+            let lastmod = url
+                .lastmod
+                .format(&Rfc3339)
+                .expect("formatting a timestamp as RFC 3339 should not fail");
+            writer.write(XmlEvent::start_element("url"))?;
+            text_tag(writer, "loc", url.loc.as_str())?;
+            text_tag(writer, "lastmod", &lastmod)?;
+            writer.write(XmlEvent::end_element())?;
+        }
+        writer.write(XmlEvent::end_element())
+    })
+}
+
+/// Render a `sitemapindex` document pointing to the sitemaps at the given
+/// URLs
+fn render_sitemapindex(locs: &[HttpUrl]) -> Vec<u8> {
+    render(|writer| {
+        writer.write(XmlEvent::start_element("sitemapindex").default_ns(SITEMAP_XMLNS))?;
+        for loc in locs {
+            writer.write(XmlEvent::start_element("sitemap"))?;
+            text_tag(writer, "loc", loc.as_str())?;
+            writer.write(XmlEvent::end_element())?;
+        }
+        writer.write(XmlEvent::end_element())
+    })
+}
+
+fn render<F>(func: F) -> Vec<u8>
+where
+    F: FnOnce(&mut EventWriter<Vec<u8>>) -> xml::writer::Result<()>,
+{
+    let mut writer = EmitterConfig::new()
+        .indent_string("  ")
+        .perform_indent(true)
+        .write_document_declaration(true)
+        .create_writer(Vec::new());
+    func(&mut writer).expect("writing sitemap XML to a Vec should not fail");
+    let mut output = writer.into_inner();
+    output.push(b'\n');
+    output
+}
+
+fn text_tag(writer: &mut EventWriter<Vec<u8>>, name: &str, text: &str) -> xml::writer::Result<()> {
+    writer.write(XmlEvent::start_element(name))?;
+    writer.write(XmlEvent::characters(text))?;
+    writer.write(XmlEvent::end_element())
+}
+
+/// Log a failure to generate the sitemap and return a 502 response
+fn error_response(e: DandiError) -> Response<Body> {
+    let e = anyhow::Error::from(e);
+    tracing::warn!(error = ?e, "Failed to generate sitemap");
+    (StatusCode::BAD_GATEWAY, format!("{e:?}")).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dandi::{ApiCacheConfig, ZarrLookup};
+    use crate::httputil::{FakeTransport, HttpConfig, RetryPolicy};
+    use crate::paths::ZarrExtensions;
+    use crate::s3::S3Config;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn version(version: &str, modified: &str) -> serde_json::Value {
+        json!({
+            "version": version,
+            "name": "Test Dandiset",
+            "asset_count": 1,
+            "size": 42,
+            "status": "Valid",
+            "created": "2020-03-15T22:56:55.655000Z",
+            "modified": modified,
+        })
+    }
+
+    fn dandiset(id: &str, published: Option<&str>) -> serde_json::Value {
+        json!({
+            "identifier": id,
+            "created": "2020-03-15T22:56:55.655000Z",
+            "modified": "2021-01-01T00:00:00Z",
+            "draft_version": version("draft", "2021-01-01T00:00:00Z"),
+            "most_recent_published_version": published.map(|v| version(v, "2022-06-01T12:00:00Z")),
+        })
+    }
+
+    fn sitemap(max_urls: usize) -> Sitemap {
+        let transport = FakeTransport::new()
+            .json(
+                "https://api.example.com/api/dandisets/",
+                &json!({
+                    "next": null,
+                    "results": [
+                        dandiset("000001", Some("0.220601.1200")),
+                        dandiset("000002", None),
+                        dandiset("000003", None),
+                    ],
+                }),
+            )
+            .json(
+                "https://api.example.com/api/dandisets/000001/versions/",
+                &json!({
+                    "next": null,
+                    "results": [
+                        version("0.210101.0000", "2021-01-01T00:00:00Z"),
+                        version("0.220601.1200", "2022-06-01T12:00:00Z"),
+                        version("draft", "2021-01-01T00:00:00Z"),
+                    ],
+                }),
+            );
+        let dandi = DandiClient::new(
+            "https://api.example.com/api".parse().unwrap(),
+            ApiCacheConfig {
+                max_entries: 0,
+                ttl: Duration::from_secs(1),
+            },
+            RetryPolicy::default(),
+            S3Config {
+                connect_timeout: Duration::from_secs(1),
+                read_timeout: Duration::from_secs(1),
+                max_attempts: 1,
+                list_concurrency: 1,
+            },
+            HttpConfig::default(),
+            ZarrLookup {
+                extensions: ZarrExtensions::default(),
+                max_candidates: 1,
+                exhaustive: false,
+            },
+            0,
+            None,
+        )
+        .unwrap()
+        .with_transport(Arc::new(transport));
+        let filter = DandisetFilter::new(Vec::new(), vec![glob::Pattern::new("000003").unwrap()]);
+        let mut sitemap = Sitemap::new(
+            dandi,
+            Arc::new(filter),
+            "https://webdav.example.com".parse().unwrap(),
+        );
+        sitemap.max_urls = max_urls;
+        sitemap
+    }
+
+    async fn body(resp: Response<Body>) -> String {
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_TYPE], SITEMAP_CONTENT_TYPE);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn single_sitemap() {
+        let sitemap = sitemap(SITEMAP_MAX_URLS);
+        let body = body(sitemap.index_response().await).await;
+        assert_eq!(
+            body,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+                "  <url>\n",
+                "    <loc>https://webdav.example.com/dandisets/000001/</loc>\n",
+                "    <lastmod>2021-01-01T00:00:00Z</lastmod>\n",
+                "  </url>\n",
+                "  <url>\n",
+                "    <loc>https://webdav.example.com/dandisets/000001/releases/0.210101.0000/</loc>\n",
+                "    <lastmod>2021-01-01T00:00:00Z</lastmod>\n",
+                "  </url>\n",
+                "  <url>\n",
+                "    <loc>https://webdav.example.com/dandisets/000001/releases/0.220601.1200/</loc>\n",
+                "    <lastmod>2022-06-01T12:00:00Z</lastmod>\n",
+                "  </url>\n",
+                "  <url>\n",
+                "    <loc>https://webdav.example.com/dandisets/000002/</loc>\n",
+                "    <lastmod>2021-01-01T00:00:00Z</lastmod>\n",
+                "  </url>\n",
+                "</urlset>\n",
+            )
+        );
+        assert_eq!(
+            sitemap.page_response("1.xml").await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn paginated_sitemap() {
+        let sitemap = sitemap(3);
+        let index = body(sitemap.index_response().await).await;
+        assert_eq!(
+            index,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+                "  <sitemap>\n",
+                "    <loc>https://webdav.example.com/sitemaps/1.xml</loc>\n",
+                "  </sitemap>\n",
+                "  <sitemap>\n",
+                "    <loc>https://webdav.example.com/sitemaps/2.xml</loc>\n",
+                "  </sitemap>\n",
+                "</sitemapindex>\n",
+            )
+        );
+        let page2 = body(sitemap.page_response("2.xml").await).await;
+        assert_eq!(
+            page2,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+                "  <url>\n",
+                "    <loc>https://webdav.example.com/dandisets/000002/</loc>\n",
+                "    <lastmod>2021-01-01T00:00:00Z</lastmod>\n",
+                "  </url>\n",
+                "</urlset>\n",
+            )
+        );
+        let page1 = body(sitemap.page_response("1.xml").await).await;
+        assert_eq!(page1.matches("<url>").count(), 3);
+        for page in ["0.xml", "3.xml", "1", "one.xml"] {
+            assert_eq!(
+                sitemap.page_response(page).await.status(),
+                StatusCode::NOT_FOUND
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn archive_error() {
+        let sitemap = Sitemap::new(
+            sitemap(1)
+                .dandi
+                .with_transport(Arc::new(FakeTransport::new())),
+            Arc::default(),
+            "https://webdav.example.com".parse().unwrap(),
+        );
+        assert_eq!(
+            sitemap.index_response().await.status(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Native TLS termination, for serving HTTPS without a reverse proxy
+use crate::consts::{TLS_ACCEPT_QUEUE_SIZE, TLS_HANDSHAKE_TIMEOUT};
+use axum::{
+    extract::Request,
+    http::{
+        header::{HOST, LOCATION},
+        uri::{Authority, PathAndQuery},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+    serve::Listener,
+    Router,
+};
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ServerConfig,
+};
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+
+/// Load a PEM-encoded certificate chain and private key from the given files
+/// and construct a TLS acceptor that serves them
+pub(crate) fn load_tls_config(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor, TlsError> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_err(|source| TlsError::ReadCert {
+            path: cert_path.to_owned(),
+            source,
+        })?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCerts {
+            path: cert_path.to_owned(),
+        });
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|source| TlsError::ReadKey {
+        path: key_path.to_owned(),
+        source,
+    })?;
+    let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(TlsError::Config)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(TlsError::Config)?;
+    // Only HTTP/1 is supported by the server:
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// A [`Listener`] that accepts TCP connections and performs TLS handshakes on
+/// them.
+///
+/// Handshakes are performed concurrently in background tasks, so that a slow
+/// or stalled client cannot hold up the acceptance of other connections.
+#[derive(Debug)]
+pub(crate) struct TlsListener {
+    local_addr: SocketAddr,
+    receiver: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    /// Start accepting connections on `listener` and performing TLS
+    /// handshakes on them with `acceptor`.  Must be called within a Tokio
+    /// runtime.
+    pub(crate) fn new(listener: TcpListener, acceptor: TlsAcceptor) -> io::Result<TlsListener> {
+        let local_addr = listener.local_addr()?;
+        let (sender, receiver) = mpsc::channel(TLS_ACCEPT_QUEUE_SIZE);
+        tokio::spawn(accept_loop(listener, acceptor, sender));
+        Ok(TlsListener {
+            local_addr,
+            receiver,
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        self.receiver
+            .recv()
+            .await
+            .expect("TLS accept loop should run for as long as the listener exists")
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Accept connections on `listener`, perform TLS handshakes on them, and send
+/// the successfully-established streams to `sender` until its receiver is
+/// dropped
+async fn accept_loop(
+    mut listener: TcpListener,
+    acceptor: TlsAcceptor,
+    sender: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+) {
+    loop {
+        let (stream, addr) = tokio::select! {
+            conn = Listener::accept(&mut listener) => conn,
+            () = sender.closed() => return,
+        };
+        let acceptor = acceptor.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    let _ = sender.send((stream, addr)).await;
+                }
+                Ok(Err(e)) => tracing::debug!(%addr, error = %e, "TLS handshake failed"),
+                Err(_) => tracing::debug!(%addr, "TLS handshake timed out"),
+            }
+        });
+    }
+}
+
+/// Serve plain HTTP on `listener`, responding to every request with a
+/// redirect to the same URL over HTTPS on port `https_port`
+pub(crate) async fn serve_https_redirects(
+    listener: TcpListener,
+    https_port: u16,
+) -> io::Result<()> {
+    let app = Router::new()
+        .fallback(move |req: Request| async move { redirect_to_https(&req, https_port) });
+    axum::serve(listener, app).await
+}
+
+/// Construct a permanent redirect from the URL of `req` to the same URL with
+/// an `https` scheme and port `https_port`.  If the request lacks a valid
+/// `Host` header, a 400 response is returned instead.
+fn redirect_to_https(req: &Request, https_port: u16) -> Response {
+    let Some(authority) = req
+        .headers()
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<Authority>().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "Missing or invalid Host header\n").into_response();
+    };
+    let host = authority.host();
+    let path = req.uri().path_and_query().map_or("/", PathAndQuery::as_str);
+    let location = if https_port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{https_port}{path}")
+    };
+    (StatusCode::PERMANENT_REDIRECT, [(LOCATION, location)]).into_response()
+}
+
+/// Error returned by [`load_tls_config()`]
+#[derive(Debug, Error)]
+pub(crate) enum TlsError {
+    #[error("failed to read TLS certificates from {}", .path.display())]
+    ReadCert {
+        path: PathBuf,
+        source: rustls::pki_types::pem::Error,
+    },
+    #[error("no TLS certificates found in {}", .path.display())]
+    NoCerts { path: PathBuf },
+    #[error("failed to read TLS private key from {}", .path.display())]
+    ReadKey {
+        path: PathBuf,
+        source: rustls::pki_types::pem::Error,
+    },
+    #[error("invalid TLS configuration")]
+    Config(#[source] rustls::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use axum::body::Body;
+    use rstest::rstest;
+    use rustls::{pki_types::ServerName, ClientConfig, RootCertStore};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    fn testdata(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src")
+            .join("testdata")
+            .join("tls")
+            .join(name)
+    }
+
+    #[tokio::test]
+    async fn serve_over_tls() {
+        let acceptor = load_tls_config(&testdata("cert.pem"), &testdata("key.pem")).unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener = TlsListener::new(tcp, acceptor).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().fallback(|| async { "Hello over TLS\n" });
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(testdata("cert.pem")).unwrap() {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nHello over TLS\n"));
+    }
+
+    #[test]
+    fn load_missing_cert() {
+        assert_matches!(
+            load_tls_config(&testdata("nonexistent.pem"), &testdata("key.pem")).err(),
+            Some(TlsError::ReadCert { .. })
+        );
+    }
+
+    #[test]
+    fn load_no_certs() {
+        assert_matches!(
+            load_tls_config(&testdata("key.pem"), &testdata("key.pem")).err(),
+            Some(TlsError::NoCerts { .. })
+        );
+    }
+
+    #[test]
+    fn load_no_key() {
+        assert_matches!(
+            load_tls_config(&testdata("cert.pem"), &testdata("cert.pem")).err(),
+            Some(TlsError::ReadKey { .. })
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "example.com",
+        "/dandisets/?page=2",
+        443,
+        "https://example.com/dandisets/?page=2"
+    )]
+    #[case("example.com:8080", "/", 8443, "https://example.com:8443/")]
+    #[case("[::1]:8080", "/zarrs/", 8443, "https://[::1]:8443/zarrs/")]
+    fn test_redirect_to_https(
+        #[case] host: &str,
+        #[case] path: &str,
+        #[case] https_port: u16,
+        #[case] location: &str,
+    ) {
+        let req = Request::builder()
+            .uri(path)
+            .header(HOST, host)
+            .body(Body::empty())
+            .unwrap();
+        let resp = redirect_to_https(&req, https_port);
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()[LOCATION], location);
+    }
+
+    #[test]
+    fn redirect_to_https_no_host() {
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let resp = redirect_to_https(&req, 443);
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
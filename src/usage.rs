@@ -0,0 +1,311 @@
+//! Aggregation of anonymized usage statistics
+use crate::consts::USAGE_MAX_CLIENTS;
+use crate::dav::RedirectTargetSize;
+use crate::ratelimit::ClientIpSource;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{response::Response, Method},
+    middleware::Next,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{BuildHasher, RandomState};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::{Date, OffsetDateTime};
+
+/// A record of usage statistics for the current UTC day, which are logged as
+/// a single JSON object once the day is over.
+///
+/// For each day, the following are recorded:
+///
+/// - the number of unique client IP addresses (as determined by
+///   [`ClientIpSource::client_ip()`]) that made requests, up to
+///   [`USAGE_MAX_CLIENTS`]; if more clients than that are seen, the count is
+///   reported as capped
+/// - the total number of requests
+/// - the total size of the resources that `GET` requests were redirected to,
+///   broken down by the top-level component of the request path (e.g.,
+///   `dandisets` or `zarrs`)
+///
+/// Client IP addresses are not retained; they are only recorded as hashes
+/// keyed with a random seed that is discarded at the end of each day.
+#[derive(Debug)]
+pub(crate) struct UsageStats {
+    /// The statistics for the current day
+    usage: Mutex<DailyUsage>,
+
+    /// How clients' IP addresses are determined
+    client_ips: ClientIpSource,
+}
+
+impl UsageStats {
+    pub(crate) fn new(client_ips: ClientIpSource) -> UsageStats {
+        UsageStats {
+            usage: Mutex::new(DailyUsage::new(today())),
+            client_ips,
+        }
+    }
+
+    /// Spawn a task that checks every `period` whether the current UTC day
+    /// has ended and, if so, logs the statistics for the day and starts
+    /// aggregating anew
+    pub(crate) fn install_periodic_report(self: &Arc<Self>, period: Duration) {
+        let stats = Arc::clone(self);
+        let mut schedule = tokio::time::interval(period);
+        schedule.reset(); // Don't tick immediately
+        schedule.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        tokio::spawn(async move {
+            loop {
+                schedule.tick().await;
+                stats.rollover();
+            }
+        });
+    }
+
+    /// Record a request from `client`.  If the request was redirected to a
+    /// resource of known size, `redirected` gives the top-level component of
+    /// the request path and the size of the resource.
+    fn record(&self, client: Option<IpAddr>, redirected: Option<(String, u64)>) {
+        let report = {
+            let mut usage = self.lock();
+            let report = usage.rollover(today());
+            usage.record(client, redirected);
+            report
+        };
+        if let Some(report) = report {
+            report.log();
+        }
+    }
+
+    /// If the current UTC day has ended, log the statistics for the day and
+    /// reset them
+    fn rollover(&self) {
+        let report = self.lock().rollover(today());
+        if let Some(report) = report {
+            report.log();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DailyUsage> {
+        self.usage
+            .lock()
+            .expect("UsageStats mutex should not be poisoned")
+    }
+}
+
+/// Usage statistics aggregated over a single UTC day
+#[derive(Debug)]
+struct DailyUsage {
+    /// The day being aggregated
+    date: Date,
+
+    /// The hasher used to anonymize client IP addresses for this day
+    hasher: RandomState,
+
+    /// Hashes of the client IP addresses seen this day, up to
+    /// [`USAGE_MAX_CLIENTS`] of them
+    clients: HashSet<u64>,
+
+    /// Whether a client was seen this day after `clients` became full
+    clients_capped: bool,
+
+    /// The number of requests made this day
+    requests: u64,
+
+    /// The total sizes of redirect targets, keyed by top-level path component
+    bytes_redirected: BTreeMap<String, u64>,
+}
+
+impl DailyUsage {
+    fn new(date: Date) -> DailyUsage {
+        DailyUsage {
+            date,
+            hasher: RandomState::new(),
+            clients: HashSet::new(),
+            clients_capped: false,
+            requests: 0,
+            bytes_redirected: BTreeMap::new(),
+        }
+    }
+
+    fn record(&mut self, client: Option<IpAddr>, redirected: Option<(String, u64)>) {
+        if let Some(ip) = client {
+            let hash = self.hasher.hash_one(ip);
+            if self.clients.len() < USAGE_MAX_CLIENTS {
+                self.clients.insert(hash);
+            } else if !self.clients.contains(&hash) {
+                self.clients_capped = true;
+            }
+        }
+        self.requests += 1;
+        if let Some((hierarchy, size)) = redirected {
+            *self.bytes_redirected.entry(hierarchy).or_default() += size;
+        }
+    }
+
+    /// If `today` is after the day being aggregated, return a report of the
+    /// statistics so far and start aggregating for `today` with a fresh
+    /// hasher
+    fn rollover(&mut self, today: Date) -> Option<UsageReport> {
+        (today > self.date).then(|| std::mem::replace(self, DailyUsage::new(today)).report())
+    }
+
+    fn report(self) -> UsageReport {
+        UsageReport {
+            date: self.date.to_string(),
+            unique_clients: self.clients.len(),
+            unique_clients_capped: self.clients_capped,
+            requests: self.requests,
+            bytes_redirected: self.bytes_redirected,
+        }
+    }
+}
+
+/// The usage statistics for a single day, as logged
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct UsageReport {
+    date: String,
+    /// The number of unique clients seen, or a lower bound on it if
+    /// `unique_clients_capped` is true
+    unique_clients: usize,
+    unique_clients_capped: bool,
+    requests: u64,
+    bytes_redirected: BTreeMap<String, u64>,
+}
+
+impl UsageReport {
+    fn log(&self) {
+        match serde_json::to_string(self) {
+            Ok(usage_json) => tracing::info!(
+                usage_event = "daily",
+                %usage_json,
+                "Usage statistics for {}",
+                self.date,
+            ),
+            Err(e) => tracing::warn!(
+                error = %e,
+                "Failed to serialize usage statistics for {}",
+                self.date,
+            ),
+        }
+    }
+}
+
+/// Record each request and the size of its redirect target (if any) in the
+/// given [`UsageStats`].
+///
+/// This middleware must be applied outside of `handle_head` so that `HEAD`
+/// requests are not counted as downloads.
+pub(crate) async fn record_usage(
+    State(stats): State<Arc<UsageStats>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let client = stats.client_ips.client_ip(&request);
+    let is_get = request.method() == Method::GET;
+    let hierarchy = top_level_component(request.uri().path()).to_owned();
+    let resp = next.run(request).await;
+    let redirected = is_get
+        .then(|| resp.extensions().get::<RedirectTargetSize>())
+        .flatten()
+        .map(|&RedirectTargetSize(size)| (hierarchy, size));
+    stats.record(client, redirected);
+    resp
+}
+
+/// Return the first component of a request path
+fn top_level_component(path: &str) -> &str {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    path.split_once('/').map_or(path, |(first, _)| first)
+}
+
+fn today() -> Date {
+    OffsetDateTime::now_utc().date()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use time::macros::date;
+
+    #[rstest]
+    #[case("/", "")]
+    #[case("/dandisets", "dandisets")]
+    #[case("/dandisets/", "dandisets")]
+    #[case("/dandisets/000027/draft/foo.nwb", "dandisets")]
+    #[case("/zarrs/001/e3b/", "zarrs")]
+    fn test_top_level_component(#[case] path: &str, #[case] component: &str) {
+        assert_eq!(top_level_component(path), component);
+    }
+
+    #[test]
+    fn aggregate_and_rollover() {
+        let mut usage = DailyUsage::new(date!(2026 - 10 - 15));
+        let alice = "192.0.2.1".parse::<IpAddr>().unwrap();
+        let bob = "2001:db8::1".parse::<IpAddr>().unwrap();
+        usage.record(Some(alice), None);
+        usage.record(Some(alice), Some(("dandisets".into(), 1000)));
+        usage.record(Some(bob), Some(("zarrs".into(), 42)));
+        usage.record(None, Some(("dandisets".into(), 234)));
+        assert_eq!(usage.rollover(date!(2026 - 10 - 15)), None);
+        usage.record(Some(bob), None);
+        let report = usage.rollover(date!(2026 - 10 - 16)).unwrap();
+        assert_eq!(
+            report,
+            UsageReport {
+                date: "2026-10-15".into(),
+                unique_clients: 2,
+                unique_clients_capped: false,
+                requests: 5,
+                bytes_redirected: BTreeMap::from([
+                    ("dandisets".into(), 1234),
+                    ("zarrs".into(), 42),
+                ]),
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"date":"2026-10-15","unique_clients":2,"unique_clients_capped":false,"requests":5,"bytes_redirected":{"dandisets":1234,"zarrs":42}}"#
+        );
+        assert_eq!(usage.date, date!(2026 - 10 - 16));
+        assert!(usage.clients.is_empty());
+        assert!(!usage.clients_capped);
+        assert_eq!(usage.requests, 0);
+        assert!(usage.bytes_redirected.is_empty());
+        let report = usage.rollover(date!(2026 - 10 - 17)).unwrap();
+        assert_eq!(
+            report,
+            UsageReport {
+                date: "2026-10-16".into(),
+                unique_clients: 0,
+                unique_clients_capped: false,
+                requests: 0,
+                bytes_redirected: BTreeMap::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn clients_capped() {
+        let mut usage = DailyUsage::new(date!(2026 - 10 - 15));
+        for i in 0..USAGE_MAX_CLIENTS {
+            let ip = IpAddr::from(std::net::Ipv6Addr::from(u128::try_from(i).unwrap()));
+            usage.record(Some(ip), None);
+        }
+        // Repeat clients are still recognized once the set is full:
+        usage.record(Some(IpAddr::from(std::net::Ipv6Addr::from(0))), None);
+        assert!(!usage.clients_capped);
+        usage.record(Some("192.0.2.1".parse().unwrap()), None);
+        let report = usage.rollover(date!(2026 - 10 - 16)).unwrap();
+        assert_eq!(report.unique_clients, USAGE_MAX_CLIENTS);
+        assert!(report.unique_clients_capped);
+        assert_eq!(
+            report.requests,
+            u64::try_from(USAGE_MAX_CLIENTS).unwrap() + 2
+        );
+    }
+}
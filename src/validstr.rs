@@ -53,9 +53,8 @@ macro_rules! validstr {
         }
 
         impl From<&$t> for String {
-            #[allow(clippy::string_to_string)]
             fn from(value: &$t) -> String {
-                value.0.to_string()
+                value.0.as_str().to_owned()
             }
         }
 
@@ -0,0 +1,83 @@
+//! Reporting of version & build information
+use serde::Serialize;
+
+/// Version & build information about the running `dandidav` binary, as
+/// output by `dandidav --version --json`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct VersionInfo {
+    /// The name of the program
+    name: &'static str,
+
+    /// The program's semantic version
+    version: &'static str,
+
+    /// The abbreviated hash of the Git commit the program was built from, if
+    /// it was built from a Git repository
+    git_commit: Option<&'static str>,
+
+    /// The time at which the program was built, in RFC 3339 format
+    build_date: &'static str,
+
+    /// The output of `rustc --version` for the compiler used to build the
+    /// program
+    rustc_version: &'static str,
+
+    /// The Cargo features enabled for the build
+    features: Vec<&'static str>,
+}
+
+impl VersionInfo {
+    /// Return the version & build information for this binary
+    pub(crate) fn current() -> VersionInfo {
+        VersionInfo {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: option_env!("GIT_COMMIT"),
+            build_date: env!("BUILD_DATE"),
+            rustc_version: env!("RUSTC_VERSION"),
+            features: env!("BUILD_FEATURES")
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Render the version information for output by `--version`: either as a
+    /// single line of the form `dandidav {version} (commit: {commit})` (the
+    /// same as clap's default) or, if `json` is true, as a JSON object
+    pub(crate) fn render(&self, json: bool) -> String {
+        if json {
+            serde_json::to_string_pretty(self).expect("serializing VersionInfo should not fail")
+        } else {
+            format!("{} {}", self.name, env!("VERSION_WITH_GIT"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_json() {
+        let info = VersionInfo::current();
+        let value = serde_json::from_str::<serde_json::Value>(&info.render(true)).unwrap();
+        assert_eq!(value["name"], "dandidav");
+        assert_eq!(value["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(value["git_commit"].as_str(), option_env!("GIT_COMMIT"));
+        assert!(value["build_date"].as_str().is_some_and(|s| !s.is_empty()));
+        assert!(value["rustc_version"]
+            .as_str()
+            .is_some_and(|s| s.starts_with("rustc ")));
+        assert!(value["features"].is_array());
+    }
+
+    #[test]
+    fn render_plain() {
+        let info = VersionInfo::current();
+        assert_eq!(
+            info.render(false),
+            format!("dandidav {}", env!("VERSION_WITH_GIT"))
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Generation of informational files like `/.well-known/security.txt` and
+//! `/robots.txt`
+use crate::httputil::HttpUrl;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+use url::Url;
+
+/// The contents of a [`security.txt`](https://www.rfc-editor.org/rfc/rfc9116)
+/// file describing how to report security vulnerabilities in the service
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SecurityTxt {
+    /// Where to send reports of vulnerabilities, in order of preference.
+    /// This should not be empty.
+    pub(crate) contacts: Vec<SecurityContact>,
+
+    /// The time after which the file's contents should be considered stale
+    pub(crate) expires: OffsetDateTime,
+
+    /// The URL of the service's vulnerability disclosure policy, if any
+    pub(crate) policy: Option<HttpUrl>,
+}
+
+impl SecurityTxt {
+    /// Returns `true` if the file's expiration time has passed
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expires <= OffsetDateTime::now_utc()
+    }
+
+    /// Render the file's contents
+    pub(crate) fn render(&self) -> String {
+        fn writer(s: &mut String, sectxt: &SecurityTxt) -> fmt::Result {
+            for contact in &sectxt.contacts {
+                writeln!(s, "Contact: {contact}")?;
+            }
+            let expires = sectxt
+                .expires
+                .to_offset(UtcOffset::UTC)
+                .format(&Rfc3339)
+                .map_err(|_| fmt::Error)?;
+            writeln!(s, "Expires: {expires}")?;
+            if let Some(ref policy) = sectxt.policy {
+                writeln!(s, "Policy: {policy}")?;
+            }
+            Ok(())
+        }
+
+        let mut s = String::new();
+        writer(&mut s, self).expect("rendering security.txt should not fail");
+        s
+    }
+}
+
+/// A URI at which security vulnerabilities can be reported, as given in a
+/// "Contact" field of a `security.txt` file
+///
+/// When parsing, e-mail addresses without a URI scheme are converted to
+/// `mailto:` URIs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SecurityContact(Url);
+
+impl fmt::Display for SecurityContact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for SecurityContact {
+    type Err = ParseSecurityContactError;
+
+    fn from_str(s: &str) -> Result<SecurityContact, ParseSecurityContactError> {
+        let s = s.trim();
+        let url = if s.contains('@') && !s.contains(':') {
+            Url::parse(&format!("mailto:{s}"))?
+        } else {
+            Url::parse(s)?
+        };
+        Ok(SecurityContact(url))
+    }
+}
+
+/// Error returned when parsing an invalid [`SecurityContact`] string
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("expected an e-mail address or an absolute URI")]
+pub(crate) struct ParseSecurityContactError(#[from] url::ParseError);
+
+/// The contents of a [`robots.txt`](https://www.rfc-editor.org/rfc/rfc9309)
+/// file telling web crawlers what they may crawl
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RobotsTxt {
+    /// Whether crawlers are allowed to crawl the service
+    pub(crate) policy: RobotsPolicy,
+
+    /// The URL of the service's sitemap, if any.  This is only included in
+    /// the file when crawling is allowed.
+    pub(crate) sitemap: Option<HttpUrl>,
+}
+
+impl RobotsTxt {
+    /// Render the file's contents
+    pub(crate) fn render(&self) -> String {
+        match (self.policy, &self.sitemap) {
+            (RobotsPolicy::Disallow, _) => "User-agent: *\nDisallow: /\n".to_owned(),
+            (RobotsPolicy::Allow, None) => "User-agent: *\nDisallow:\n".to_owned(),
+            (RobotsPolicy::Allow, Some(sitemap)) => {
+                format!("User-agent: *\nDisallow:\n\nSitemap: {sitemap}\n")
+            }
+        }
+    }
+}
+
+/// Whether web crawlers are allowed to crawl the service, as given by the
+/// `--robots` option
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum RobotsPolicy {
+    /// Allow all crawling
+    Allow,
+
+    /// Disallow all crawling
+    #[default]
+    Disallow,
+}
+
+impl FromStr for RobotsPolicy {
+    type Err = ParseRobotsPolicyError;
+
+    fn from_str(s: &str) -> Result<RobotsPolicy, ParseRobotsPolicyError> {
+        match s {
+            "allow" => Ok(RobotsPolicy::Allow),
+            "disallow" => Ok(RobotsPolicy::Disallow),
+            _ => Err(ParseRobotsPolicyError),
+        }
+    }
+}
+
+/// Error returned when parsing an invalid [`RobotsPolicy`] string
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error(r#"expected "allow" or "disallow""#)]
+pub(crate) struct ParseRobotsPolicyError;
+
+/// Parse an RFC 3339 timestamp given as the value of a command-line option
+pub(crate) fn parse_timestamp(s: &str) -> Result<OffsetDateTime, time::error::Parse> {
+    OffsetDateTime::parse(s, &Rfc3339)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use time::macros::datetime;
+
+    #[rstest]
+    #[case("security@example.com", "mailto:security@example.com")]
+    #[case(" mailto:security@example.com ", "mailto:security@example.com")]
+    #[case(
+        "https://example.com/security/report",
+        "https://example.com/security/report"
+    )]
+    #[case("tel:+1-201-555-0123", "tel:+1-201-555-0123")]
+    fn parse_contact(#[case] s: &str, #[case] uri: &str) {
+        let contact = s.parse::<SecurityContact>().unwrap();
+        assert_eq!(contact.to_string(), uri);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("security")]
+    #[case("/security/report")]
+    fn parse_bad_contact(#[case] s: &str) {
+        assert!(s.parse::<SecurityContact>().is_err());
+    }
+
+    #[test]
+    fn render() {
+        let sectxt = SecurityTxt {
+            contacts: vec![
+                "security@example.com".parse().unwrap(),
+                "https://example.com/security/report".parse().unwrap(),
+            ],
+            expires: parse_timestamp("2030-06-30T12:00:00-04:00").unwrap(),
+            policy: Some("https://example.com/security/policy".parse().unwrap()),
+        };
+        assert_eq!(
+            sectxt.render(),
+            concat!(
+                "Contact: mailto:security@example.com\n",
+                "Contact: https://example.com/security/report\n",
+                "Expires: 2030-06-30T16:00:00Z\n",
+                "Policy: https://example.com/security/policy\n",
+            )
+        );
+        assert!(!sectxt.is_expired());
+    }
+
+    #[test]
+    fn render_minimal() {
+        let sectxt = SecurityTxt {
+            contacts: vec!["security@example.com".parse().unwrap()],
+            expires: datetime!(2020-01-01 00:00:00 UTC),
+            policy: None,
+        };
+        assert_eq!(
+            sectxt.render(),
+            "Contact: mailto:security@example.com\nExpires: 2020-01-01T00:00:00Z\n"
+        );
+        assert!(sectxt.is_expired());
+    }
+
+    #[rstest]
+    #[case(RobotsPolicy::Disallow, None, "User-agent: *\nDisallow: /\n")]
+    #[case(
+        RobotsPolicy::Disallow,
+        Some("https://webdav.example.com/sitemap.xml"),
+        "User-agent: *\nDisallow: /\n"
+    )]
+    #[case(RobotsPolicy::Allow, None, "User-agent: *\nDisallow:\n")]
+    #[case(
+        RobotsPolicy::Allow,
+        Some("https://webdav.example.com/sitemap.xml"),
+        "User-agent: *\nDisallow:\n\nSitemap: https://webdav.example.com/sitemap.xml\n"
+    )]
+    fn render_robots(
+        #[case] policy: RobotsPolicy,
+        #[case] sitemap: Option<&str>,
+        #[case] rendered: &str,
+    ) {
+        let robots = RobotsTxt {
+            policy,
+            sitemap: sitemap.map(|s| s.parse().unwrap()),
+        };
+        assert_eq!(robots.render(), rendered);
+    }
+}
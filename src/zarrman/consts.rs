@@ -19,5 +19,60 @@ pub(super) static MANIFEST_ROOT_URL: &str =
 /// `{ENTRY_DOWNLOAD_PREFIX}/{zarr_id}/{entry_path}`.
 pub(super) static ENTRY_DOWNLOAD_PREFIX: &str = "https://dandiarchive.s3.amazonaws.com/zarr/";
 
+/// The maximum number of pages of a paginated directory listing in the
+/// manifest tree to fetch
+pub(super) const MAX_INDEX_PAGES: usize = 1000;
+
+/// The `Accept-Encoding` value sent when fetching Zarr manifests, allowing
+/// the manifest tree to serve precompressed copies of them
+pub(super) static MANIFEST_ACCEPT_ENCODING: &str = "zstd, gzip";
+
+/// The maximum number of manifest tree index pages to cache
+pub(super) const INDEX_CACHE_SIZE: u64 = 10_000;
+
+/// Cached manifest tree index pages are used without revalidation for this
+/// long after they were last fetched or revalidated
+pub(super) const INDEX_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Expire any cached manifest tree index pages that haven't been accessed
+/// for this long
+pub(super) const INDEX_CACHE_IDLE_EXPIRY: Duration = Duration::from_secs(3600);
+
+/// Log statistics about one out of every this many manifest fetches at the
+/// INFO level
+pub(super) const MANIFEST_FETCH_LOG_SAMPLE_INTERVAL: u64 = 10;
+
 /// Expire any manifest cache entries that haven't been accessed for this long
 pub(super) const MANIFEST_CACHE_IDLE_EXPIRY: Duration = Duration::from_secs(300);
+
+/// The name under which the most recent manifest for each Zarr is served in
+/// the Zarr's directory in the `/zarrs/` hierarchy
+pub(super) static LATEST_MANIFEST_NAME: &str = "latest";
+
+/// Resolutions of Zarr IDs to their most recent manifests are cached for
+/// this long
+pub(super) const LATEST_MANIFEST_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The maximum number of resolutions of Zarr IDs to their most recent
+/// manifests to cache
+pub(super) const LATEST_MANIFEST_CACHE_SIZE: u64 = 10_000;
+
+/// The name of the consolidated metadata file that `dandidav` synthesizes at
+/// the root of each Zarr v2 hierarchy that lacks one
+pub(super) static ZMETADATA_NAME: &str = ".zmetadata";
+
+/// The names of the Zarr v2 metadata files whose contents are gathered into
+/// synthesized consolidated metadata files
+pub(super) static ZARR_METADATA_NAMES: [&str; 3] = [".zarray", ".zattrs", ".zgroup"];
+
+/// The maximum number of Zarr metadata files to fetch concurrently when
+/// synthesizing a consolidated metadata file
+pub(super) const ZMETADATA_FETCH_CONCURRENCY: usize = 8;
+
+/// The maximum total size in bytes of synthesized consolidated metadata files
+/// to keep in memory
+pub(super) const ZMETADATA_CACHE_SIZE: u64 = 50_000_000;
+
+/// Expire any cached consolidated metadata files that haven't been accessed
+/// for this long
+pub(super) const ZMETADATA_CACHE_IDLE_EXPIRY: Duration = Duration::from_secs(3600);
@@ -0,0 +1,415 @@
+//! An on-disk cache of Zarr manifest documents that persists across restarts
+use super::resources::ManifestPath;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The extension given to partially-written cache files before they are
+/// renamed into place
+const TEMP_EXTENSION: &str = "tmp";
+
+/// A size-limited directory of Zarr manifest documents, laid out like the
+/// manifest tree (i.e., the document for a manifest at path
+/// `128/4a1/{zarr_id}/{checksum}.json` is stored at that path relative to the
+/// cache directory).
+///
+/// Documents are stored as opaque byte strings; it is up to the caller to
+/// store them in a format that can be decoded later.  When adding a document
+/// would cause the total size of the cache to exceed its limit, the least
+/// recently used documents are deleted.  As a manifest's path includes its
+/// Zarr's checksum, the content at a given path never changes, and so cached
+/// documents never need to be invalidated.
+#[derive(Clone, Debug)]
+pub(crate) struct ManifestDiskCache {
+    inner: Arc<DiskCacheInner>,
+}
+
+#[derive(Debug)]
+struct DiskCacheInner {
+    /// The directory in which the documents are stored
+    dir: PathBuf,
+
+    /// The maximum total size in bytes of the stored documents
+    max_size: u64,
+
+    /// Bookkeeping for the documents currently in the cache
+    state: Mutex<DiskState>,
+}
+
+#[derive(Debug, Default)]
+struct DiskState {
+    /// The documents currently in the cache
+    entries: HashMap<ManifestPath, DiskEntry>,
+
+    /// The sum of the sizes of the documents in `entries`
+    total_size: u64,
+
+    /// A counter incremented on every access, used to order documents by
+    /// recency of use
+    clock: u64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct DiskEntry {
+    /// The size of the document in bytes
+    size: u64,
+
+    /// The value of [`DiskState::clock`] when the document was last read or
+    /// written
+    last_used: u64,
+}
+
+impl DiskState {
+    /// Record `path` as having been used just now
+    fn touch(&mut self, path: &ManifestPath) -> bool {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.last_used = self.clock;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record a document of `size` bytes as stored at `path`
+    fn insert(&mut self, path: ManifestPath, size: u64) {
+        self.clock += 1;
+        let entry = DiskEntry {
+            size,
+            last_used: self.clock,
+        };
+        if let Some(old) = self.entries.insert(path, entry) {
+            self.total_size -= old.size;
+        }
+        self.total_size += size;
+    }
+
+    /// Stop tracking the document at `path`
+    fn remove(&mut self, path: &ManifestPath) {
+        if let Some(old) = self.entries.remove(path) {
+            self.total_size -= old.size;
+        }
+    }
+
+    /// Remove the least recently used documents from the bookkeeping until
+    /// the total size is at most `max_size`, and return their paths
+    fn evict(&mut self, max_size: u64) -> Vec<ManifestPath> {
+        if self.total_size <= max_size {
+            return Vec::new();
+        }
+        let mut by_age = self
+            .entries
+            .iter()
+            .map(|(path, entry)| (entry.last_used, path.clone()))
+            .collect::<Vec<_>>();
+        by_age.sort_unstable_by_key(|&(last_used, _)| last_used);
+        let mut evicted = Vec::new();
+        for (_, path) in by_age {
+            if self.total_size <= max_size {
+                break;
+            }
+            self.remove(&path);
+            evicted.push(path);
+        }
+        evicted
+    }
+}
+
+impl ManifestDiskCache {
+    /// Open a cache in the directory `dir`, creating it if it does not exist,
+    /// that stores up to `max_size` bytes of documents.
+    ///
+    /// Any documents already in the directory (e.g., from a previous run) are
+    /// added to the cache, with the most recently modified treated as the
+    /// most recently used.  Leftover partially-written files are deleted, and
+    /// if the existing documents exceed `max_size`, the oldest are deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created or traversed
+    pub(crate) async fn open(dir: PathBuf, max_size: u64) -> std::io::Result<ManifestDiskCache> {
+        tokio::fs::create_dir_all(&dir).await?;
+        let mut found = Vec::new();
+        let mut stack = vec![dir.clone()];
+        while let Some(d) = stack.pop() {
+            let mut iter = tokio::fs::read_dir(&d).await?;
+            while let Some(entry) = iter.next_entry().await? {
+                let fpath = entry.path();
+                let ftype = entry.file_type().await?;
+                if ftype.is_dir() {
+                    stack.push(fpath);
+                    continue;
+                } else if !ftype.is_file() {
+                    continue;
+                }
+                if fpath.extension().is_some_and(|ext| ext == TEMP_EXTENSION) {
+                    remove_file(&fpath).await;
+                    continue;
+                }
+                let Some(path) = relative_manifest_path(&dir, &fpath) else {
+                    continue;
+                };
+                let md = entry.metadata().await?;
+                let mtime = md.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                found.push((mtime, path, md.len()));
+            }
+        }
+        found.sort_by_key(|&(mtime, _, _)| mtime);
+        let mut state = DiskState::default();
+        for (_, path, size) in found {
+            state.insert(path, size);
+        }
+        let evicted = state.evict(max_size);
+        tracing::info!(
+            cache = "zarr-manifests-disk",
+            dir = %dir.display(),
+            entries = state.entries.len(),
+            size = state.total_size,
+            evicted = evicted.len(),
+            "Opened on-disk Zarr manifest cache",
+        );
+        let cache = ManifestDiskCache {
+            inner: Arc::new(DiskCacheInner {
+                dir,
+                max_size,
+                state: Mutex::new(state),
+            }),
+        };
+        for path in evicted {
+            remove_file(&cache.file_path(&path)).await;
+        }
+        Ok(cache)
+    }
+
+    /// Return the location at which the document for `path` is stored
+    fn file_path(&self, path: &ManifestPath) -> PathBuf {
+        let mut fpath = self.inner.dir.clone();
+        fpath.extend(path.to_string().split('/'));
+        fpath
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, DiskState> {
+        self.inner
+            .state
+            .lock()
+            .expect("disk cache state lock should not be poisoned")
+    }
+
+    /// Return the stored document for `path`, if any.  If the document cannot
+    /// be read, the error is logged, the document is forgotten, and `None` is
+    /// returned.
+    pub(crate) async fn get(&self, path: &ManifestPath) -> Option<Vec<u8>> {
+        if !self.state().touch(path) {
+            return None;
+        }
+        match tokio::fs::read(self.file_path(path)).await {
+            Ok(body) => Some(body),
+            Err(e) => {
+                tracing::warn!(
+                    cache = "zarr-manifests-disk",
+                    manifest = %path,
+                    error = %e,
+                    "Failed to read Zarr manifest from disk cache; discarding",
+                );
+                self.state().remove(path);
+                None
+            }
+        }
+    }
+
+    /// Store `body` as the document for `path`, evicting the least recently
+    /// used documents as needed to stay within the size limit.  Documents
+    /// larger than the limit are not stored.
+    ///
+    /// The document is first written to a temporary file and then renamed
+    /// into place so that an interrupted write never leaves a truncated
+    /// document behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing the document fails
+    pub(crate) async fn put(&self, path: &ManifestPath, body: &[u8]) -> std::io::Result<()> {
+        let size = u64::try_from(body.len()).unwrap_or(u64::MAX);
+        if size > self.inner.max_size {
+            tracing::debug!(
+                cache = "zarr-manifests-disk",
+                manifest = %path,
+                size,
+                "Zarr manifest is too large for disk cache; not storing",
+            );
+            return Ok(());
+        }
+        let fpath = self.file_path(path);
+        if let Some(parent) = fpath.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmppath = fpath.with_extension(format!("{}.{TEMP_EXTENSION}", uuid::Uuid::new_v4()));
+        if let Err(e) = tokio::fs::write(&tmppath, body).await {
+            remove_file(&tmppath).await;
+            return Err(e);
+        }
+        if let Err(e) = tokio::fs::rename(&tmppath, &fpath).await {
+            remove_file(&tmppath).await;
+            return Err(e);
+        }
+        let evicted = {
+            let mut state = self.state();
+            state.insert(path.clone(), size);
+            state.evict(self.inner.max_size)
+        };
+        for p in evicted {
+            tracing::debug!(
+                cache_event = "evict",
+                cache = "zarr-manifests-disk",
+                manifest = %p,
+                "Zarr manifest evicted from disk cache",
+            );
+            remove_file(&self.file_path(&p)).await;
+        }
+        Ok(())
+    }
+
+    /// Delete the stored document for `path`, if any
+    pub(crate) async fn remove(&self, path: &ManifestPath) {
+        self.state().remove(path);
+        remove_file(&self.file_path(path)).await;
+    }
+}
+
+/// Parse the location of a file beneath `dir` as a [`ManifestPath`].
+/// Returns `None` if the file is not at a valid manifest path.
+fn relative_manifest_path(dir: &Path, fpath: &Path) -> Option<ManifestPath> {
+    let relpath = fpath.strip_prefix(dir).ok()?;
+    let components = relpath
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    components.join("/").parse::<ManifestPath>().ok()
+}
+
+/// Delete the file at `path`, logging any errors other than the file not
+/// existing
+async fn remove_file(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        if e.kind() != ErrorKind::NotFound {
+            tracing::warn!(
+                cache = "zarr-manifests-disk",
+                path = %path.display(),
+                error = %e,
+                "Failed to delete file from Zarr manifest disk cache",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest_path(checksum: &str) -> ManifestPath {
+        format!("128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/{checksum}-1--1.json")
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn put_get() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let cache = ManifestDiskCache::open(tmpdir.path().join("cache"), 100)
+            .await
+            .unwrap();
+        let path = manifest_path("6ddc4625befef8d6f9796835648162be");
+        assert_eq!(cache.get(&path).await, None);
+        cache.put(&path, b"manifest").await.unwrap();
+        assert_eq!(cache.get(&path).await.as_deref(), Some(&b"manifest"[..]));
+        assert!(tmpdir
+            .path()
+            .join("cache")
+            .join("128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-1--1.json")
+            .is_file());
+        cache.remove(&path).await;
+        assert_eq!(cache.get(&path).await, None);
+    }
+
+    #[tokio::test]
+    async fn evict_least_recently_used() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let cache = ManifestDiskCache::open(tmpdir.path().to_owned(), 25)
+            .await
+            .unwrap();
+        let p1 = manifest_path("00000000000000000000000000000001");
+        let p2 = manifest_path("00000000000000000000000000000002");
+        let p3 = manifest_path("00000000000000000000000000000003");
+        cache.put(&p1, &[1; 10]).await.unwrap();
+        cache.put(&p2, &[2; 10]).await.unwrap();
+        assert!(cache.get(&p1).await.is_some());
+        cache.put(&p3, &[3; 10]).await.unwrap();
+        assert!(cache.get(&p1).await.is_some());
+        assert_eq!(cache.get(&p2).await, None);
+        assert!(cache.get(&p3).await.is_some());
+        assert!(!cache.file_path(&p2).exists());
+    }
+
+    #[tokio::test]
+    async fn oversized_not_stored() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let cache = ManifestDiskCache::open(tmpdir.path().to_owned(), 5)
+            .await
+            .unwrap();
+        let path = manifest_path("6ddc4625befef8d6f9796835648162be");
+        cache.put(&path, b"manifest").await.unwrap();
+        assert_eq!(cache.get(&path).await, None);
+        assert!(!cache.file_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn reopen() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = manifest_path("6ddc4625befef8d6f9796835648162be");
+        let cache = ManifestDiskCache::open(tmpdir.path().to_owned(), 100)
+            .await
+            .unwrap();
+        cache.put(&path, b"manifest").await.unwrap();
+        let leftover = cache.file_path(&path).with_extension("1234.tmp");
+        std::fs::write(&leftover, b"partial").unwrap();
+        std::fs::write(tmpdir.path().join("README"), b"not a manifest").unwrap();
+        drop(cache);
+        let cache = ManifestDiskCache::open(tmpdir.path().to_owned(), 100)
+            .await
+            .unwrap();
+        assert_eq!(cache.get(&path).await.as_deref(), Some(&b"manifest"[..]));
+        assert_eq!(cache.state().entries.len(), 1);
+        assert!(!leftover.exists());
+    }
+
+    #[tokio::test]
+    async fn reopen_smaller() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let p1 = manifest_path("00000000000000000000000000000001");
+        let p2 = manifest_path("00000000000000000000000000000002");
+        let cache = ManifestDiskCache::open(tmpdir.path().to_owned(), 100)
+            .await
+            .unwrap();
+        cache.put(&p1, &[1; 10]).await.unwrap();
+        cache.put(&p2, &[2; 10]).await.unwrap();
+        let p1_file = cache.file_path(&p1);
+        let p2_file = cache.file_path(&p2);
+        drop(cache);
+        // Ensure p2 is newer than p1 regardless of filesystem timestamp
+        // granularity:
+        std::fs::File::options()
+            .write(true)
+            .open(&p1_file)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let cache = ManifestDiskCache::open(tmpdir.path().to_owned(), 15)
+            .await
+            .unwrap();
+        assert_eq!(cache.get(&p1).await, None);
+        assert!(!p1_file.exists());
+        assert!(p2_file.exists());
+    }
+}
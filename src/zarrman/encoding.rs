@@ -0,0 +1,196 @@
+//! Decoding of compressed Zarr manifests
+use axum::http::header::{HeaderMap, CONTENT_ENCODING};
+use flate2::read::MultiGzDecoder;
+use std::fmt;
+use std::io::Read;
+use thiserror::Error;
+
+/// The magic number at the start of every gzip stream
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+
+/// The magic number at the start of every Zstandard frame
+const ZSTD_MAGIC: &[u8] = b"\x28\xB5\x2F\xFD";
+
+/// A content coding in which the manifest tree may serve a Zarr manifest
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ContentCoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+impl ContentCoding {
+    /// Determine the coding of a response body with the given headers.
+    ///
+    /// The coding is normally taken from the `Content-Encoding` header.  As
+    /// precompressed `.json.gz` & `.json.zst` files may instead be served
+    /// as-is (e.g., with a `Content-Type` of `application/zstd`), a body
+    /// without a `Content-Encoding` is also recognized as compressed if it
+    /// begins with the magic number of a supported format; as JSON documents
+    /// cannot begin with either magic number, this is unambiguous.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the `Content-Encoding` header names an unsupported
+    /// coding or multiple codings
+    pub(super) fn detect(headers: &HeaderMap, body: &[u8]) -> Result<ContentCoding, DecodeError> {
+        let encoding = headers.get(CONTENT_ENCODING).map(|v| {
+            String::from_utf8_lossy(v.as_bytes())
+                .trim()
+                .to_ascii_lowercase()
+        });
+        match encoding.as_deref() {
+            None | Some("" | "identity") => {
+                if body.starts_with(GZIP_MAGIC) {
+                    Ok(ContentCoding::Gzip)
+                } else if body.starts_with(ZSTD_MAGIC) {
+                    Ok(ContentCoding::Zstd)
+                } else {
+                    Ok(ContentCoding::Identity)
+                }
+            }
+            Some("gzip" | "x-gzip") => Ok(ContentCoding::Gzip),
+            Some("zstd") => Ok(ContentCoding::Zstd),
+            Some(other) => Err(DecodeError::Unsupported(other.to_owned())),
+        }
+    }
+
+    /// Return the name of the coding as used in `Content-Encoding` headers
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            ContentCoding::Identity => "identity",
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Zstd => "zstd",
+        }
+    }
+
+    /// Return a reader that decompresses `body` according to the coding as it
+    /// is read.  Returns `None` for [`ContentCoding::Identity`], in which case
+    /// `body` should be used as-is.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the decompressor cannot be initialized.  Errors
+    /// encountered while decompressing are reported by the reader.
+    pub(super) fn decoder(self, body: &[u8]) -> std::io::Result<Option<Box<dyn Read + '_>>> {
+        Ok(match self {
+            ContentCoding::Identity => None,
+            ContentCoding::Gzip => Some(Box::new(MultiGzDecoder::new(body))),
+            ContentCoding::Zstd => Some(Box::new(zstd::Decoder::new(body)?)),
+        })
+    }
+}
+
+impl fmt::Display for ContentCoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned when a compressed Zarr manifest cannot be decoded
+#[derive(Debug, Error)]
+pub(crate) enum DecodeError {
+    #[error("unsupported content coding {0:?}")]
+    Unsupported(String),
+    #[error("failed to decompress {coding}-encoded data")]
+    Decompress {
+        coding: ContentCoding,
+        source: std::io::Error,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use axum::http::header::HeaderValue;
+    use flate2::{write::GzEncoder, Compression};
+    use rstest::rstest;
+    use std::io::Write;
+
+    static JSON: &[u8] = br#"{"schemaVersion": 2, "entries": {}}"#;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn headers(encoding: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(enc) = encoding {
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_str(enc).unwrap());
+        }
+        headers
+    }
+
+    #[rstest]
+    #[case(None, ContentCoding::Identity)]
+    #[case(Some("identity"), ContentCoding::Identity)]
+    #[case(Some("gzip"), ContentCoding::Gzip)]
+    #[case(Some("GZip"), ContentCoding::Gzip)]
+    #[case(Some("x-gzip"), ContentCoding::Gzip)]
+    #[case(Some("zstd"), ContentCoding::Zstd)]
+    fn test_detect_header(#[case] encoding: Option<&str>, #[case] coding: ContentCoding) {
+        assert_eq!(
+            ContentCoding::detect(&headers(encoding), JSON).unwrap(),
+            coding
+        );
+    }
+
+    #[test]
+    fn test_detect_magic() {
+        let gz = gzip(JSON);
+        assert_eq!(
+            ContentCoding::detect(&headers(None), &gz).unwrap(),
+            ContentCoding::Gzip
+        );
+        let zst = zstd::encode_all(JSON, 0).unwrap();
+        assert_eq!(
+            ContentCoding::detect(&headers(None), &zst).unwrap(),
+            ContentCoding::Zstd
+        );
+    }
+
+    #[rstest]
+    #[case("br")]
+    #[case("gzip, zstd")]
+    fn test_detect_unsupported(#[case] encoding: &str) {
+        assert_matches!(
+            ContentCoding::detect(&headers(Some(encoding)), JSON),
+            Err(DecodeError::Unsupported(enc)) => {
+                assert_eq!(enc, encoding);
+            }
+        );
+    }
+
+    fn decode(coding: ContentCoding, body: &[u8]) -> std::io::Result<Option<Vec<u8>>> {
+        let Some(mut decoder) = coding.decoder(body)? else {
+            return Ok(None);
+        };
+        let mut buf = Vec::new();
+        decoder.read_to_end(&mut buf)?;
+        Ok(Some(buf))
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(ContentCoding::Identity, JSON).unwrap(), None);
+        assert_eq!(
+            decode(ContentCoding::Gzip, &gzip(JSON)).unwrap().as_deref(),
+            Some(JSON)
+        );
+        let zst = zstd::encode_all(JSON, 0).unwrap();
+        assert_eq!(
+            decode(ContentCoding::Zstd, &zst).unwrap().as_deref(),
+            Some(JSON)
+        );
+    }
+
+    #[rstest]
+    #[case(ContentCoding::Gzip)]
+    #[case(ContentCoding::Zstd)]
+    fn test_decode_invalid(#[case] coding: ContentCoding) {
+        assert!(decode(coding, JSON).is_err());
+    }
+}
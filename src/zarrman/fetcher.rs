@@ -1,17 +1,37 @@
-use super::consts::{MANIFEST_CACHE_IDLE_EXPIRY, MANIFEST_ROOT_URL};
-use super::manifest::Manifest;
-use super::resources::ManifestPath;
+use super::consts::{
+    INDEX_CACHE_IDLE_EXPIRY, INDEX_CACHE_SIZE, INDEX_CACHE_TTL, MANIFEST_ACCEPT_ENCODING,
+    MANIFEST_CACHE_IDLE_EXPIRY, MANIFEST_FETCH_LOG_SAMPLE_INTERVAL, MANIFEST_ROOT_URL,
+    MAX_INDEX_PAGES,
+};
+use super::diskcache::ManifestDiskCache;
+use super::encoding::{ContentCoding, DecodeError};
+use super::manifest::{Manifest, ParseManifestError};
+use super::resources::{ManifestPath, ParseManifestPathError};
 use super::util::{Index, ZarrManError};
-use crate::httputil::{BuildClientError, Client, HttpError, HttpUrl};
+use crate::httputil::{BuildClientError, Client, HttpConfig, HttpError, HttpUrl, RetryPolicy};
+use crate::metrics::metrics;
 use crate::paths::PureDirPath;
+use axum::http::{
+    header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_LENGTH, ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
+use bytes::Bytes;
 use get_size::GetSize;
 use moka::{
     future::{Cache, CacheBuilder},
     ops::compute::{CompResult, Op},
 };
 use serde::Serialize;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 /// A client for fetching & caching data from the manifest tree
 #[derive(Clone, Debug)]
@@ -23,18 +43,74 @@ pub(crate) struct ManifestFetcher {
     /// `MANIFEST_ROOT_URL`
     cache: Cache<ManifestPath, Arc<Manifest>>,
 
+    /// A cache of index pages fetched from the manifest tree, keyed by URL
+    index_cache: Cache<HttpUrl, Arc<CachedIndexPage>>,
+
+    /// How long a cached index page is used before it is revalidated
+    index_ttl: Duration,
+
     /// [`MANIFEST_ROOT_URL`], parsed into an [`HttpUrl`]
     manifest_root_url: HttpUrl,
+
+    /// The number of manifests fetched from the manifest tree so far, used
+    /// for sampling the manifests to log fetch statistics for
+    fetch_count: Arc<AtomicU64>,
+
+    /// Parsed manifests that have been pinned in memory by
+    /// [`ManifestFetcher::pin_manifests()`].  These are consulted before
+    /// `cache`, are never evicted, and do not count towards the cache's size
+    /// limit.
+    pinned: Arc<RwLock<HashMap<ManifestPath, Arc<Manifest>>>>,
+
+    /// An optional on-disk cache of manifest documents, consulted before
+    /// fetching a manifest that is not in `cache` from the manifest tree
+    disk: Option<ManifestDiskCache>,
+
+    /// An optional limit on the number of manifests that may be downloaded &
+    /// parsed at once, so that a burst of requests for different large
+    /// Zarrs cannot exhaust memory
+    fetch_limit: Option<FetchLimit>,
+}
+
+/// An index page from the manifest tree along with the information needed to
+/// revalidate it
+#[derive(Clone, Debug)]
+struct CachedIndexPage {
+    /// The parsed page
+    page: Index,
+
+    /// The page's `ETag`, if the manifest tree supplied one
+    etag: Option<HeaderValue>,
+
+    /// When the page was last fetched or revalidated
+    validated: Instant,
+}
+
+/// A limit on the number of concurrent manifest fetches
+#[derive(Clone, Debug)]
+struct FetchLimit {
+    /// A semaphore with one permit per fetch allowed to run at once
+    slots: Arc<Semaphore>,
+
+    /// The maximum amount of time to wait for a permit before giving up
+    queue_timeout: Duration,
 }
 
 impl ManifestFetcher {
-    /// Construct a new client instance
+    /// Construct a new client instance that caches up to `cache_size` bytes
+    /// of parsed manifests, retries failed requests according to `retry`, and
+    /// whose HTTP connections & redirect handling are configured by
+    /// `http_config`
     ///
     /// # Errors
     ///
     /// Returns an error if construction of the inner `reqwest::Client` fails
-    pub(crate) fn new(cache_size: u64) -> Result<Self, BuildClientError> {
-        let inner = Client::new()?;
+    pub(crate) fn new(
+        cache_size: u64,
+        retry: RetryPolicy,
+        http_config: HttpConfig,
+    ) -> Result<Self, BuildClientError> {
+        let inner = Client::new(retry, http_config, None)?;
         let cache: Cache<ManifestPath, Arc<Manifest>> = CacheBuilder::new(cache_size)
             .name("zarr-manifests")
             .weigher(|_, manifest: &Arc<Manifest>| {
@@ -52,36 +128,229 @@ impl ManifestFetcher {
                 );
             })
             .build();
+        let index_cache = CacheBuilder::new(INDEX_CACHE_SIZE)
+            .name("zarr-index")
+            .time_to_idle(INDEX_CACHE_IDLE_EXPIRY)
+            .build();
         let manifest_root_url = MANIFEST_ROOT_URL
             .parse::<HttpUrl>()
             .expect("MANIFEST_ROOT_URL should be a valid HTTP URL");
         Ok(ManifestFetcher {
             inner,
             cache,
+            index_cache,
+            index_ttl: INDEX_CACHE_TTL,
             manifest_root_url,
+            fetch_count: Arc::new(AtomicU64::new(0)),
+            pinned: Arc::new(RwLock::new(HashMap::new())),
+            disk: None,
+            fetch_limit: None,
         })
     }
 
+    /// Return a copy of the fetcher that downloads & parses at most
+    /// `max_concurrent` manifests at once.  Further fetches wait in a queue
+    /// for up to `queue_timeout` for an earlier fetch to finish, after which
+    /// they fail with [`ZarrManError::FetchQueueTimeout`].
+    pub(crate) fn with_fetch_limit(
+        self,
+        max_concurrent: usize,
+        queue_timeout: Duration,
+    ) -> ManifestFetcher {
+        ManifestFetcher {
+            fetch_limit: Some(FetchLimit {
+                slots: Arc::new(Semaphore::new(max_concurrent)),
+                queue_timeout,
+            }),
+            ..self
+        }
+    }
+
+    /// Return a copy of the fetcher that persists fetched manifests in
+    /// `disk` and consults it before making requests to the manifest tree
+    pub(crate) fn with_disk_cache(self, disk: ManifestDiskCache) -> ManifestFetcher {
+        ManifestFetcher {
+            disk: Some(disk),
+            ..self
+        }
+    }
+
+    /// Return a copy of the fetcher that sends its HTTP requests via
+    /// `transport`
+    #[cfg(test)]
+    pub(crate) fn with_transport(
+        &self,
+        transport: Arc<dyn crate::httputil::HttpTransport>,
+    ) -> ManifestFetcher {
+        ManifestFetcher {
+            inner: self.inner.with_transport(transport),
+            ..self.clone()
+        }
+    }
+
+    /// Check that the manifest tree is reachable by making a `HEAD` request to
+    /// its root
+    pub(super) async fn check(&self) -> Result<(), HttpError> {
+        self.inner
+            .head(self.manifest_root_url.clone())
+            .await
+            .map(|_| ())
+    }
+
     /// Retrieve the manifest index in the given directory of the manifest
     /// tree.
     ///
     /// `path` must be relative to the manifest root.  A `path` of `None`
     /// denotes the manifest root itself.
+    ///
+    /// If the index is paginated, the `next` links are followed (up to
+    /// [`MAX_INDEX_PAGES`] pages, stopping early if a page repeats) and the
+    /// pages are merged.  The files & directories in the returned index are
+    /// sorted by name and deduplicated so that the order does not depend on
+    /// how the listing was paginated.
+    ///
+    /// Pages are fetched via [`ManifestFetcher::fetch_index_page()`] and thus
+    /// may be served from a short-lived cache.
     pub(super) async fn fetch_index(&self, path: Option<&PureDirPath>) -> Result<Index, HttpError> {
         let mut url = self.manifest_root_url.clone();
         if let Some(p) = path {
             url.extend(p.component_strs()).ensure_dirpath();
         }
-        self.inner.get_json::<Index>(url).await
+        let mut index = self.fetch_index_page(&url).await?;
+        let mut seen = HashSet::from([url.clone()]);
+        while let Some(next) = index.next.take() {
+            let Some(next_url) = url
+                .as_url()
+                .join(&next)
+                .ok()
+                .and_then(|u| u.as_str().parse::<HttpUrl>().ok())
+            else {
+                tracing::warn!(%url, next, "Manifest tree index has invalid next link; ignoring");
+                break;
+            };
+            if seen.len() >= MAX_INDEX_PAGES {
+                tracing::warn!(
+                    %url,
+                    max_pages = MAX_INDEX_PAGES,
+                    "Manifest tree index has too many pages; truncating",
+                );
+                break;
+            }
+            if !seen.insert(next_url.clone()) {
+                tracing::warn!(%url, next = %next_url, "Manifest tree index pagination loops; truncating");
+                break;
+            }
+            let page = self.fetch_index_page(&next_url).await?;
+            index.files.extend(page.files);
+            index.directories.extend(page.directories);
+            index.next = page.next;
+            url = next_url;
+        }
+        index.files.sort_unstable();
+        index.files.dedup();
+        index.directories.sort_unstable();
+        index.directories.dedup();
+        Ok(index)
+    }
+
+    /// Retrieve & parse the single manifest tree index page at `url`.
+    ///
+    /// Pages are cached for [`INDEX_CACHE_TTL`] after they are fetched.  Once
+    /// that time has passed, a cached page with an `ETag` is revalidated with
+    /// a conditional request, and if the manifest tree reports that the page
+    /// is unchanged, the cached page is used for another [`INDEX_CACHE_TTL`].
+    async fn fetch_index_page(&self, url: &HttpUrl) -> Result<Index, HttpError> {
+        let cached = self.index_cache.get(url).await;
+        if let Some(ref c) = cached {
+            if c.validated.elapsed() < self.index_ttl {
+                metrics().record_cache_lookup("zarr-index", true);
+                return Ok(c.page.clone());
+            }
+        }
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = cached.as_ref().and_then(|c| c.etag.clone()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        let r = self.inner.get_with_headers(url.clone(), headers).await?;
+        if r.status == StatusCode::NOT_MODIFIED {
+            let Some(c) = cached else {
+                return Err(HttpError::Status {
+                    url: url.clone(),
+                    status: r.status,
+                });
+            };
+            metrics().record_cache_lookup("zarr-index", true);
+            tracing::debug!(
+                cache_event = "revalidated",
+                cache = "zarr-index",
+                %url,
+                "Manifest tree index page is unchanged; reusing cached copy",
+            );
+            let page = c.page.clone();
+            self.index_cache
+                .insert(
+                    url.clone(),
+                    Arc::new(CachedIndexPage {
+                        validated: Instant::now(),
+                        ..(*c).clone()
+                    }),
+                )
+                .await;
+            return Ok(page);
+        }
+        metrics().record_cache_lookup("zarr-index", false);
+        let etag = r.headers.get(ETAG).cloned();
+        let body = r.bytes().await?;
+        let page =
+            serde_json::from_slice::<Index>(&body).map_err(|source| HttpError::ParseJson {
+                url: url.clone(),
+                source,
+            })?;
+        self.index_cache
+            .insert(
+                url.clone(),
+                Arc::new(CachedIndexPage {
+                    page: page.clone(),
+                    etag,
+                    validated: Instant::now(),
+                }),
+            )
+            .await;
+        Ok(page)
+    }
+
+    /// Retrieve the entry of a Zarr at the given download URL and parse it as
+    /// JSON
+    pub(super) async fn fetch_entry_json(
+        &self,
+        url: HttpUrl,
+    ) -> Result<serde_json::Value, HttpError> {
+        self.inner.get_json::<serde_json::Value>(url).await
     }
 
     /// Retrieve the Zarr manifest at the given [`ManifestPath`] in the
-    /// manifest tree, either via an HTTP request or from a cache
+    /// manifest tree, either via an HTTP request, from a cache, or from the
+    /// pinned manifests.
+    ///
+    /// The manifest tree may serve manifests compressed with gzip or
+    /// Zstandard, in which case they are decompressed before parsing.  Cache
+    /// entries are weighed by the size of the parsed manifests, regardless of
+    /// how they were transferred.
     #[tracing::instrument(skip_all, fields(id = %uuid::Uuid::new_v4(), manifest = %path))]
     pub(super) async fn fetch_manifest(
         &self,
         path: &ManifestPath,
     ) -> Result<Arc<Manifest>, ZarrManError> {
+        if let Some(zman) = self.get_pinned(path) {
+            metrics().record_cache_lookup("zarr-manifests", true);
+            tracing::debug!(
+                cache_event = "hit_pinned",
+                cache = "zarr-manifests",
+                manifest = %path,
+                "Fetched Zarr manifest from pinned manifests",
+            );
+            return Ok(zman);
+        }
         let result = self
             .cache
             .entry_by_ref(path)
@@ -95,10 +364,8 @@ impl ManifestFetcher {
                         approx_cache_size = self.cache.weighted_size(),
                         "Cache miss for Zarr manifest; about to fetch from repository",
                     );
-                    self.inner
-                        .get_json::<Manifest>(path.under_manifest_root(&self.manifest_root_url))
-                        .await
-                        .map(|zman| Op::Put(Arc::new(zman)))
+                    let zman = self.download_manifest(path).await?;
+                    Ok::<_, ZarrManError>(Op::Put(Arc::new(zman)))
                 } else {
                     Ok(Op::Nop)
                 }
@@ -106,6 +373,7 @@ impl ManifestFetcher {
             .await?;
         let entry = match result {
             CompResult::Inserted(entry) => {
+                metrics().record_cache_lookup("zarr-manifests", false);
                 tracing::debug!(
                     cache_event = "miss_post",
                     cache = "zarr-manifests",
@@ -118,6 +386,7 @@ impl ManifestFetcher {
                 entry
             }
             CompResult::Unchanged(entry) => {
+                metrics().record_cache_lookup("zarr-manifests", true);
                 tracing::debug!(
                     cache_event = "hit",
                     cache = "zarr-manifests",
@@ -136,6 +405,247 @@ impl ManifestFetcher {
         Ok(entry.into_value())
     }
 
+    /// Fetch & parse the Zarr manifest at the given [`ManifestPath`] in the
+    /// manifest tree, bypassing the in-memory cache.
+    ///
+    /// If an on-disk cache is configured, the manifest is read from there if
+    /// present, and a manifest fetched from the manifest tree is stored there
+    /// afterwards.
+    ///
+    /// If a fetch limit is configured, this waits for a fetch slot first.
+    async fn download_manifest(&self, path: &ManifestPath) -> Result<Manifest, ZarrManError> {
+        let _permit = self.acquire_fetch_slot(path).await?;
+        if let Some(zman) = self.load_from_disk(path).await {
+            return Ok(zman);
+        }
+        let url = path.under_manifest_root(&self.manifest_root_url);
+        let headers = HeaderMap::from_iter([(
+            ACCEPT_ENCODING,
+            HeaderValue::from_static(MANIFEST_ACCEPT_ENCODING),
+        )]);
+        let r = self.inner.get_with_headers(url.clone(), headers).await?;
+        let content_length = r
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|hv| hv.to_str().ok())
+            .and_then(|s| s.parse::<u64>().ok());
+        let resp_headers = r.headers.clone();
+        let body = r.bytes().await?;
+        let start = Instant::now();
+        let coding = ContentCoding::detect(&resp_headers, &body).map_err(|source| {
+            ZarrManError::DecodeManifest {
+                url: url.clone(),
+                source,
+            }
+        })?;
+        let (zman, decoded_size, body) = parse_manifest_blocking(coding, body)
+            .await
+            .map_err(|e| manifest_error(&url, coding, e))?;
+        let parse_duration = start.elapsed();
+        let n = self.fetch_count.fetch_add(1, Ordering::Relaxed);
+        if n % MANIFEST_FETCH_LOG_SAMPLE_INTERVAL == 0 {
+            tracing::info!(
+                manifest = %path,
+                manifest_url = %url,
+                schema_version = %zman.schema_version(),
+                content_length,
+                content_coding = %coding,
+                body_size = body.len(),
+                decoded_size,
+                parse_duration_secs = parse_duration.as_secs_f64(),
+                cache_weight = zman.get_size(),
+                sample_interval = MANIFEST_FETCH_LOG_SAMPLE_INTERVAL,
+                "Fetched & parsed Zarr manifest",
+            );
+        }
+        if let Some(ref disk) = self.disk {
+            if let Err(e) =
+                store_on_disk(disk, path, &body, coding != ContentCoding::Identity).await
+            {
+                tracing::warn!(
+                    cache = "zarr-manifests-disk",
+                    manifest = %path,
+                    error = %e,
+                    "Failed to store Zarr manifest in disk cache",
+                );
+            }
+        }
+        Ok(zman)
+    }
+
+    /// If a fetch limit is configured, wait for a slot to fetch the manifest
+    /// at `path`, returning a permit that frees the slot when dropped
+    async fn acquire_fetch_slot(
+        &self,
+        path: &ManifestPath,
+    ) -> Result<Option<SemaphorePermit<'_>>, ZarrManError> {
+        let Some(ref limit) = self.fetch_limit else {
+            return Ok(None);
+        };
+        if let Ok(permit) = limit.slots.try_acquire() {
+            return Ok(Some(permit));
+        }
+        tracing::debug!(
+            manifest = %path,
+            "All Zarr manifest fetch slots in use; waiting for one to free up",
+        );
+        let start = Instant::now();
+        let Ok(permit) = tokio::time::timeout(limit.queue_timeout, limit.slots.acquire()).await
+        else {
+            tracing::warn!(
+                manifest = %path,
+                timeout_secs = limit.queue_timeout.as_secs_f64(),
+                "Timed out waiting for a Zarr manifest fetch slot",
+            );
+            return Err(ZarrManError::FetchQueueTimeout {
+                manifest_path: path.clone(),
+                timeout: limit.queue_timeout,
+            });
+        };
+        tracing::debug!(
+            manifest = %path,
+            wait_secs = start.elapsed().as_secs_f64(),
+            "Acquired Zarr manifest fetch slot",
+        );
+        Ok(Some(
+            permit.expect("manifest fetch semaphore should never be closed"),
+        ))
+    }
+
+    /// Read & parse the Zarr manifest at `path` from the on-disk cache, if
+    /// one is configured and contains the manifest.  If the stored document
+    /// cannot be decoded or parsed, it is deleted and `None` is returned.
+    async fn load_from_disk(&self, path: &ManifestPath) -> Option<Manifest> {
+        let disk = self.disk.as_ref()?;
+        let Some(body) = disk.get(path).await else {
+            metrics().record_cache_lookup("zarr-manifests-disk", false);
+            return None;
+        };
+        let body_size = body.len();
+        let parsed = match ContentCoding::detect(&HeaderMap::new(), &body) {
+            Ok(coding) => parse_manifest_blocking(coding, Bytes::from(body))
+                .await
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        match parsed {
+            Ok((zman, _, _)) => {
+                metrics().record_cache_lookup("zarr-manifests-disk", true);
+                tracing::debug!(
+                    cache_event = "hit_disk",
+                    cache = "zarr-manifests-disk",
+                    manifest = %path,
+                    body_size,
+                    "Read Zarr manifest from disk cache",
+                );
+                Some(zman)
+            }
+            Err(e) => {
+                metrics().record_cache_lookup("zarr-manifests-disk", false);
+                tracing::warn!(
+                    cache = "zarr-manifests-disk",
+                    manifest = %path,
+                    error = ?e,
+                    "Zarr manifest in disk cache is invalid; discarding",
+                );
+                disk.remove(path).await;
+                None
+            }
+        }
+    }
+
+    /// Return the pinned manifest at `path`, if any
+    fn get_pinned(&self, path: &ManifestPath) -> Option<Arc<Manifest>> {
+        self.pinned
+            .read()
+            .expect("pinned manifests lock should not be poisoned")
+            .get(path)
+            .cloned()
+    }
+
+    /// Make the manifests at `paths` the set of pinned manifests, fetching
+    /// those that are not already pinned (taking them from the cache if
+    /// present) and unpinning any pinned manifests not in `paths`.  As a
+    /// manifest's path includes its Zarr's checksum, the content at a given
+    /// path never changes, and so manifests that are already pinned are not
+    /// refetched.
+    ///
+    /// Manifests that cannot be fetched are logged and skipped, to be tried
+    /// again on the next call.  Returns the number of manifests pinned
+    /// afterwards.
+    pub(crate) async fn pin_manifests(&self, paths: &[ManifestPath]) -> usize {
+        let mut fetched = HashMap::new();
+        for path in paths {
+            if fetched.contains_key(path) {
+                continue;
+            }
+            if let Some(zman) = self.get_pinned(path) {
+                fetched.insert(path.clone(), zman);
+                continue;
+            }
+            let zman = match self.cache.get(path).await {
+                Some(zman) => zman,
+                None => match self.download_manifest(path).await {
+                    Ok(zman) => Arc::new(zman),
+                    Err(e) => {
+                        tracing::warn!(
+                            manifest = %path,
+                            error = ?anyhow::Error::from(e),
+                            "Failed to fetch Zarr manifest for pinning",
+                        );
+                        continue;
+                    }
+                },
+            };
+            // The manifest no longer needs to take up space in the cache:
+            self.cache.invalidate(path).await;
+            fetched.insert(path.clone(), zman);
+        }
+        let count = fetched.len();
+        *self
+            .pinned
+            .write()
+            .expect("pinned manifests lock should not be poisoned") = fetched;
+        tracing::info!(
+            pinned = count,
+            requested = paths.len(),
+            "Updated pinned Zarr manifests"
+        );
+        count
+    }
+
+    /// Spawn a task that pins the manifests at `paths` and then, every
+    /// `period`, rereads the list of manifests to pin from `file` (as read
+    /// by [`read_pin_file()`]) and updates the pinned manifests to match.  If
+    /// rereading fails, the error is logged and the pinned manifests are left
+    /// as they are.
+    pub(crate) fn install_pinning(
+        &self,
+        paths: Vec<ManifestPath>,
+        file: PathBuf,
+        period: Duration,
+    ) {
+        let this = self.clone();
+        let mut schedule = tokio::time::interval(period);
+        schedule.reset(); // The initial paths are pinned before the first tick
+        schedule.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        tokio::spawn(async move {
+            this.pin_manifests(&paths).await;
+            loop {
+                schedule.tick().await;
+                match read_pin_file(&file).await {
+                    Ok(paths) => {
+                        this.pin_manifests(&paths).await;
+                    }
+                    Err(e) => tracing::warn!(
+                        error = ?anyhow::Error::from(e),
+                        "Failed to reread Zarr manifest pin file; keeping current pins",
+                    ),
+                }
+            }
+        });
+    }
+
     pub(crate) fn install_periodic_dump(&self, period: Duration) {
         let this = self.clone();
         let mut schedule = tokio::time::interval(period);
@@ -181,8 +691,651 @@ impl ManifestFetcher {
     }
 }
 
+/// Parse a manifest document that was transferred with the content coding
+/// `coding`.  Compressed documents are parsed as they are decompressed so
+/// that the decompressed document is never held in memory in its entirety.
+///
+/// On success, returns the manifest along with the size of the decompressed
+/// document.  Failures to decompress the document are reported as
+/// [`ParseManifestError::Io`].
+fn parse_manifest(
+    coding: ContentCoding,
+    body: &[u8],
+) -> Result<(Manifest, u64), ParseManifestError> {
+    match coding.decoder(body).map_err(ParseManifestError::Io)? {
+        None => Ok((
+            Manifest::from_json(body)?,
+            u64::try_from(body.len()).unwrap_or(u64::MAX),
+        )),
+        Some(decoder) => {
+            let mut reader = CountingReader {
+                inner: decoder,
+                count: 0,
+            };
+            let zman = Manifest::from_reader(&mut reader)?;
+            Ok((zman, reader.count))
+        }
+    }
+}
+
+/// Run [`parse_manifest()`] on `body` on a thread for blocking operations so
+/// that decompressing & parsing large manifests does not hold up the async
+/// runtime.  On success, `body` is returned along with the parse results so
+/// that the caller can make further use of it.
+async fn parse_manifest_blocking(
+    coding: ContentCoding,
+    body: Bytes,
+) -> Result<(Manifest, u64, Bytes), ParseManifestError> {
+    let task = tokio::task::spawn_blocking(move || {
+        parse_manifest(coding, &body).map(|(zman, size)| (zman, size, body))
+    });
+    match task.await {
+        Ok(r) => r,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Convert an error from [`parse_manifest()`] for the document at `url`,
+/// transferred with the content coding `coding`, into a [`ZarrManError`]
+fn manifest_error(url: &HttpUrl, coding: ContentCoding, e: ParseManifestError) -> ZarrManError {
+    match e {
+        ParseManifestError::Json(source) => ZarrManError::Http(HttpError::ParseJson {
+            url: url.clone(),
+            source,
+        }),
+        ParseManifestError::UnsupportedVersion(version) => {
+            ZarrManError::UnsupportedManifestVersion {
+                url: url.clone(),
+                version,
+            }
+        }
+        ParseManifestError::Io(source) => ZarrManError::DecodeManifest {
+            url: url.clone(),
+            source: DecodeError::Decompress { coding, source },
+        },
+    }
+}
+
+/// A reader that counts the number of bytes read through it
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count = self
+            .count
+            .saturating_add(u64::try_from(n).unwrap_or(u64::MAX));
+        Ok(n)
+    }
+}
+
+/// Store the manifest document `body` (as received from the manifest tree) at
+/// `path` in `disk`.  If the document was not `compressed` in transit, it is
+/// compressed with Zstandard first so that it takes up less space; compressed
+/// documents are recognized by their magic numbers when read back.
+async fn store_on_disk(
+    disk: &ManifestDiskCache,
+    path: &ManifestPath,
+    body: &[u8],
+    compressed: bool,
+) -> std::io::Result<()> {
+    if compressed {
+        disk.put(path, body).await
+    } else {
+        disk.put(path, &zstd::encode_all(body, 0)?).await
+    }
+}
+
+/// Read a list of Zarr manifest paths to pin in memory from the file at
+/// `path`.  The file must contain one manifest path (relative to the
+/// manifest root, e.g., `128/4a1/{zarr_id}/{checksum}.json`) per line.
+/// Blank lines and lines starting with `#` are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or if any line is not a valid
+/// manifest path
+pub(crate) async fn read_pin_file(path: &Path) -> Result<Vec<ManifestPath>, PinFileError> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|source| PinFileError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+    parse_pin_list(&content).map_err(|(line, source)| PinFileError::Parse {
+        path: path.to_owned(),
+        line,
+        source,
+    })
+}
+
+/// Parse the contents of a pin file as described in [`read_pin_file()`].  On
+/// failure, the (1-based) number of the invalid line is returned along with
+/// the error.
+fn parse_pin_list(content: &str) -> Result<Vec<ManifestPath>, (usize, ParseManifestPathError)> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(lineno, line)| line.parse::<ManifestPath>().map_err(|e| (lineno, e)))
+        .collect()
+}
+
+/// Error returned by [`read_pin_file()`]
+#[derive(Debug, Error)]
+pub(crate) enum PinFileError {
+    #[error("failed to read Zarr manifest pin file {}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid manifest path on line {line} of {}", path.display())]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        source: ParseManifestPathError,
+    },
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 struct EntryStat {
     manifest_path: String,
     size: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::httputil::{FakeTransport, FAKE_BODY_CHUNK_SIZE};
+    use assert_matches::assert_matches;
+    use axum::http::StatusCode;
+    use rstest::rstest;
+    use serde_json::json;
+    use std::io::Write;
+
+    fn fetcher(transport: FakeTransport) -> (ManifestFetcher, Arc<FakeTransport>) {
+        let transport = Arc::new(transport);
+        let fetcher =
+            ManifestFetcher::new(1_000_000, RetryPolicy::default(), HttpConfig::default())
+                .unwrap()
+                .with_transport(transport.clone());
+        (fetcher, transport)
+    }
+
+    #[tokio::test]
+    async fn fetch_index() {
+        let (fetcher, transport) = fetcher(FakeTransport::new().json(
+            &format!("{MANIFEST_ROOT_URL}128/4a1/"),
+            &json!({
+                "path": "128/4a1",
+                "files": [],
+                "directories": ["1284a14f-fe4f-4dc3-b10d-48e5db8bf18d"],
+            }),
+        ));
+        let index = fetcher
+            .fetch_index(Some(&"128/4a1/".parse().unwrap()))
+            .await
+            .unwrap();
+        assert!(index.files.is_empty());
+        assert_eq!(index.directories, ["1284a14f-fe4f-4dc3-b10d-48e5db8bf18d"]);
+        assert_eq!(
+            transport.requests(),
+            [(reqwest::Method::GET, format!("{MANIFEST_ROOT_URL}128/4a1/"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_index_sorted() {
+        let (fetcher, _) = fetcher(FakeTransport::new().json(
+            MANIFEST_ROOT_URL,
+            &json!({
+                "path": "",
+                "files": [],
+                "directories": ["e3b", "128", "a7f"],
+            }),
+        ));
+        let index = fetcher.fetch_index(None).await.unwrap();
+        assert_eq!(index.directories, ["128", "a7f", "e3b"]);
+    }
+
+    #[tokio::test]
+    async fn fetch_index_paginated() {
+        let (fetcher, transport) = fetcher(
+            FakeTransport::new()
+                .json(
+                    &format!("{MANIFEST_ROOT_URL}128/"),
+                    &json!({
+                        "path": "128",
+                        "files": [],
+                        "directories": ["a7f", "4a1"],
+                        "next": "?page=2",
+                    }),
+                )
+                .json(
+                    &format!("{MANIFEST_ROOT_URL}128/?page=2"),
+                    &json!({
+                        "path": "128",
+                        "files": [],
+                        "directories": ["e3b", "4a1"],
+                        "next": format!("{MANIFEST_ROOT_URL}128/?page=3"),
+                    }),
+                )
+                .json(
+                    &format!("{MANIFEST_ROOT_URL}128/?page=3"),
+                    &json!({
+                        "path": "128",
+                        "files": [],
+                        "directories": ["0ba"],
+                        "next": null,
+                    }),
+                ),
+        );
+        let index = fetcher
+            .fetch_index(Some(&"128/".parse().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(index.directories, ["0ba", "4a1", "a7f", "e3b"]);
+        assert_eq!(index.next, None);
+        assert_eq!(
+            transport.requests(),
+            [
+                (reqwest::Method::GET, format!("{MANIFEST_ROOT_URL}128/")),
+                (
+                    reqwest::Method::GET,
+                    format!("{MANIFEST_ROOT_URL}128/?page=2")
+                ),
+                (
+                    reqwest::Method::GET,
+                    format!("{MANIFEST_ROOT_URL}128/?page=3")
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_index_pagination_loop() {
+        let (fetcher, transport) = fetcher(
+            FakeTransport::new()
+                .json(
+                    &format!("{MANIFEST_ROOT_URL}128/"),
+                    &json!({
+                        "path": "128",
+                        "files": [],
+                        "directories": ["4a1"],
+                        "next": "?page=2",
+                    }),
+                )
+                .json(
+                    &format!("{MANIFEST_ROOT_URL}128/?page=2"),
+                    &json!({
+                        "path": "128",
+                        "files": [],
+                        "directories": ["e3b"],
+                        "next": "/dandi/zarr-manifests/zarr-manifests-v2-sorted/128/",
+                    }),
+                ),
+        );
+        let index = fetcher
+            .fetch_index(Some(&"128/".parse().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(index.directories, ["4a1", "e3b"]);
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fetch_index_page_error() {
+        let (fetcher, _) = fetcher(FakeTransport::new().json(
+            &format!("{MANIFEST_ROOT_URL}128/"),
+            &json!({
+                "path": "128",
+                "files": [],
+                "directories": ["4a1"],
+                "next": "?page=2",
+            }),
+        ));
+        assert_matches!(
+            fetcher.fetch_index(Some(&"128/".parse().unwrap())).await,
+            Err(HttpError::NotFound { .. })
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_index_cached() {
+        let (fetcher, transport) = fetcher(FakeTransport::new().json(
+            MANIFEST_ROOT_URL,
+            &json!({"path": "", "files": [], "directories": ["128"]}),
+        ));
+        let index1 = fetcher.fetch_index(None).await.unwrap();
+        let index2 = fetcher.fetch_index(None).await.unwrap();
+        assert_eq!(index1, index2);
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_index_revalidate() {
+        let (fetcher, transport) = fetcher(FakeTransport::new().respond(
+            MANIFEST_ROOT_URL,
+            StatusCode::OK,
+            &[("content-type", "application/json"), ("etag", "\"abc123\"")],
+            json!({"path": "", "files": [], "directories": ["128"]}).to_string(),
+        ));
+        let fetcher = ManifestFetcher {
+            index_ttl: Duration::ZERO,
+            ..fetcher
+        };
+        let index1 = fetcher.fetch_index(None).await.unwrap();
+        let index2 = fetcher.fetch_index(None).await.unwrap();
+        assert_eq!(index1, index2);
+        assert_eq!(index2.directories, ["128"]);
+        let headers = transport.request_headers();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].get(IF_NONE_MATCH), None);
+        assert_eq!(headers[1][IF_NONE_MATCH], "\"abc123\"");
+    }
+
+    #[tokio::test]
+    async fn fetch_index_no_etag_refetched() {
+        let (fetcher, transport) = fetcher(FakeTransport::new().json_sequence(
+            MANIFEST_ROOT_URL,
+            &[
+                json!({"path": "", "files": [], "directories": ["128"]}),
+                json!({"path": "", "files": [], "directories": ["128", "a7f"]}),
+            ],
+        ));
+        let fetcher = ManifestFetcher {
+            index_ttl: Duration::ZERO,
+            ..fetcher
+        };
+        assert_eq!(
+            fetcher.fetch_index(None).await.unwrap().directories,
+            ["128"]
+        );
+        assert_eq!(
+            fetcher.fetch_index(None).await.unwrap().directories,
+            ["128", "a7f"]
+        );
+        assert!(transport
+            .request_headers()
+            .iter()
+            .all(|h| h.get(IF_NONE_MATCH).is_none()));
+    }
+
+    #[tokio::test]
+    async fn check_unreachable() {
+        let (fetcher, transport) = fetcher(FakeTransport::new().respond(
+            MANIFEST_ROOT_URL,
+            StatusCode::FORBIDDEN,
+            &[],
+            "",
+        ));
+        assert_matches!(fetcher.check().await, Err(HttpError::Status { .. }));
+        assert_eq!(
+            transport.requests(),
+            [(reqwest::Method::HEAD, MANIFEST_ROOT_URL.to_owned())]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_manifest_unsupported_version() {
+        let path = ManifestPath {
+            prefix: "128/4a1/".parse().unwrap(),
+            zarr_id: "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d".parse().unwrap(),
+            checksum: "6ddc4625befef8d6f9796835648162be-509--710206390"
+                .parse()
+                .unwrap(),
+        };
+        let url = format!(
+            "{MANIFEST_ROOT_URL}128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json"
+        );
+        let (fetcher, _) =
+            fetcher(FakeTransport::new().json(&url, &json!({"schemaVersion": 99, "entries": {}})));
+        assert_matches!(
+            fetcher.fetch_manifest(&path).await,
+            Err(e @ ZarrManError::UnsupportedManifestVersion { .. }) => {
+                assert_eq!(e.to_string(), format!("manifest at {url} uses unsupported schema version 99"));
+            }
+        );
+    }
+
+    fn manifest_path() -> (ManifestPath, String) {
+        let path = ManifestPath {
+            prefix: "128/4a1/".parse().unwrap(),
+            zarr_id: "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d".parse().unwrap(),
+            checksum: "6ddc4625befef8d6f9796835648162be-509--710206390"
+                .parse()
+                .unwrap(),
+        };
+        let url = format!(
+            "{MANIFEST_ROOT_URL}128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json"
+        );
+        (path, url)
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[rstest]
+    #[case::zstd_encoded(&[("content-encoding", "zstd")], |b: &[u8]| zstd::encode_all(b, 0).unwrap())]
+    #[case::gzip_encoded(&[("content-encoding", "gzip")], gzip)]
+    #[case::zstd_file(&[("content-type", "application/zstd")], |b: &[u8]| zstd::encode_all(b, 0).unwrap())]
+    #[case::gzip_file(&[("content-type", "application/gzip")], gzip)]
+    #[tokio::test]
+    async fn fetch_manifest_compressed(
+        #[case] headers: &[(&'static str, &str)],
+        #[case] compress: fn(&[u8]) -> Vec<u8>,
+    ) {
+        let (path, url) = manifest_path();
+        let manifest = json!({
+            "schemaVersion": 2,
+            "entries": {
+                ".zgroup": [
+                    "VI0Gh7.WxqVr1gEf_Flr2ZKDuNfuEbsP",
+                    "2022-06-27T23:09:11+00:00",
+                    24,
+                    "e20297935e73dd0154104d4ea53040ab"
+                ],
+            },
+        });
+        let body = compress(manifest.to_string().as_bytes());
+        let (fetcher, transport) =
+            fetcher(FakeTransport::new().respond(&url, StatusCode::OK, headers, body));
+        let zman = fetcher.fetch_manifest(&path).await.unwrap();
+        assert_eq!(
+            *zman,
+            Manifest::from_json(manifest.to_string().as_bytes()).unwrap()
+        );
+        assert_eq!(
+            transport.request_headers()[0][ACCEPT_ENCODING],
+            MANIFEST_ACCEPT_ENCODING
+        );
+    }
+
+    #[rstest]
+    #[case::identity(&[], |b: &[u8]| b.to_vec())]
+    #[case::zstd_encoded(&[("content-encoding", "zstd")], |b: &[u8]| zstd::encode_all(b, 0).unwrap())]
+    #[case::gzip_encoded(&[("content-encoding", "gzip")], gzip)]
+    #[tokio::test]
+    async fn fetch_manifest_multiple_chunks(
+        #[case] headers: &[(&'static str, &str)],
+        #[case] compress: fn(&[u8]) -> Vec<u8>,
+    ) {
+        let (path, url) = manifest_path();
+        let entries = (0..2000u128)
+            .map(|i| {
+                let digest = format!(
+                    "{:032x}",
+                    i.wrapping_mul(0x9E37_79B9_7F4A_7C15_F39C_C060_5CED_C835)
+                );
+                (
+                    i.to_string(),
+                    json!([
+                        "VI0Gh7.WxqVr1gEf_Flr2ZKDuNfuEbsP",
+                        "2022-06-27T23:09:11+00:00",
+                        i,
+                        digest
+                    ]),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>();
+        let manifest = json!({
+            "schemaVersion": 2,
+            "entries": {
+                ".zgroup": [
+                    "VI0Gh7.WxqVr1gEf_Flr2ZKDuNfuEbsP",
+                    "2022-06-27T23:09:11+00:00",
+                    24,
+                    "e20297935e73dd0154104d4ea53040ab"
+                ],
+                "data": entries,
+            },
+        });
+        let body = compress(manifest.to_string().as_bytes());
+        assert!(body.len() > 4 * FAKE_BODY_CHUNK_SIZE);
+        let (fetcher, _) =
+            fetcher(FakeTransport::new().respond(&url, StatusCode::OK, headers, body));
+        let zman = fetcher.fetch_manifest(&path).await.unwrap();
+        assert_eq!(
+            *zman,
+            Manifest::from_json(manifest.to_string().as_bytes()).unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case(&[("content-encoding", "br")], "{}")]
+    #[case(&[("content-encoding", "zstd")], "{}")]
+    #[tokio::test]
+    async fn fetch_manifest_undecodable(
+        #[case] headers: &[(&'static str, &str)],
+        #[case] body: &'static str,
+    ) {
+        let (path, url) = manifest_path();
+        let (fetcher, _) =
+            fetcher(FakeTransport::new().respond(&url, StatusCode::OK, headers, body));
+        assert_matches!(
+            fetcher.fetch_manifest(&path).await,
+            Err(ZarrManError::DecodeManifest { url: u, .. }) => {
+                assert_eq!(u.as_str(), url);
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_manifest_disk_cache() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let (path, url) = manifest_path();
+        let manifest = json!({"schemaVersion": 2, "entries": {}});
+        let disk = ManifestDiskCache::open(tmpdir.path().to_owned(), 1_000_000)
+            .await
+            .unwrap();
+        let (fetcher1, transport) = fetcher(FakeTransport::new().json(&url, &manifest));
+        let zman = fetcher1
+            .with_disk_cache(disk)
+            .fetch_manifest(&path)
+            .await
+            .unwrap();
+        assert_eq!(transport.requests().len(), 1);
+        // A new fetcher with an empty in-memory cache reads the manifest back
+        // from disk without making any requests:
+        let disk = ManifestDiskCache::open(tmpdir.path().to_owned(), 1_000_000)
+            .await
+            .unwrap();
+        let (fetcher2, transport) = fetcher(FakeTransport::new());
+        let zman2 = fetcher2
+            .with_disk_cache(disk)
+            .fetch_manifest(&path)
+            .await
+            .unwrap();
+        assert_eq!(zman, zman2);
+        assert!(transport.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_manifest_disk_cache_corrupt() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let (path, url) = manifest_path();
+        let disk = ManifestDiskCache::open(tmpdir.path().to_owned(), 1_000_000)
+            .await
+            .unwrap();
+        disk.put(&path, b"not a manifest").await.unwrap();
+        let (fetcher, transport) =
+            fetcher(FakeTransport::new().json(&url, &json!({"schemaVersion": 2, "entries": {}})));
+        let fetcher = fetcher.with_disk_cache(disk.clone());
+        fetcher.fetch_manifest(&path).await.unwrap();
+        assert_eq!(transport.requests().len(), 1);
+        let stored = disk.get(&path).await.unwrap();
+        assert!(stored.starts_with(b"\x28\xB5\x2F\xFD"));
+    }
+
+    #[tokio::test]
+    async fn fetch_manifest_queue_timeout() {
+        let (path, url) = manifest_path();
+        let (fetcher, transport) =
+            fetcher(FakeTransport::new().json(&url, &json!({"schemaVersion": 2, "entries": {}})));
+        let fetcher = fetcher.with_fetch_limit(1, Duration::from_millis(50));
+        let slots = fetcher.fetch_limit.as_ref().unwrap().slots.clone();
+        let permit = slots.acquire().await.unwrap();
+        assert_matches!(
+            fetcher.fetch_manifest(&path).await,
+            Err(ZarrManError::FetchQueueTimeout { manifest_path, timeout }) => {
+                assert_eq!(manifest_path, path);
+                assert_eq!(timeout, Duration::from_millis(50));
+            }
+        );
+        assert!(transport.requests().is_empty());
+        drop(permit);
+        fetcher.fetch_manifest(&path).await.unwrap();
+        assert_eq!(transport.requests().len(), 1);
+        assert_eq!(slots.available_permits(), 1);
+    }
+
+    #[test]
+    fn test_parse_pin_list() {
+        let (path, _) = manifest_path();
+        let content = indoc::indoc! {"
+            # Popular Zarrs
+
+            128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json
+        "};
+        assert_eq!(parse_pin_list(content).unwrap(), [path]);
+    }
+
+    #[test]
+    fn test_parse_pin_list_invalid() {
+        let content = "# Comment\n128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d\n";
+        assert_eq!(parse_pin_list(content), Err((2, ParseManifestPathError)));
+    }
+
+    #[tokio::test]
+    async fn pin_manifests() {
+        let (path, url) = manifest_path();
+        let (fetcher, transport) =
+            fetcher(FakeTransport::new().json(&url, &json!({"schemaVersion": 2, "entries": {}})));
+        assert_eq!(
+            fetcher.pin_manifests(&[path.clone(), path.clone()]).await,
+            1
+        );
+        assert_eq!(transport.requests().len(), 1);
+        fetcher.fetch_manifest(&path).await.unwrap();
+        // Re-pinning an already-pinned manifest does not refetch it:
+        assert_eq!(fetcher.pin_manifests(std::slice::from_ref(&path)).await, 1);
+        assert_eq!(transport.requests().len(), 1);
+        assert_eq!(fetcher.pin_manifests(&[]).await, 0);
+        fetcher.fetch_manifest(&path).await.unwrap();
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pin_manifests_skips_failures() {
+        let (path, _) = manifest_path();
+        let (fetcher, _) = fetcher(FakeTransport::new());
+        assert_eq!(fetcher.pin_manifests(&[path]).await, 0);
+    }
+}
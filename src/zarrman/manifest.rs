@@ -1,18 +1,71 @@
+use super::consts::{ZARR_METADATA_NAMES, ZMETADATA_NAME};
 use crate::paths::{Component, PurePath};
 use get_size::GetSize;
 use itertools::{Itertools, Position};
-use serde::Deserialize;
+use serde::{
+    de::{self, value::SeqAccessDeserializer, Deserializer, IgnoredAny, MapAccess, SeqAccess},
+    Deserialize,
+};
 use std::collections::BTreeMap;
-use time::OffsetDateTime;
+use std::fmt;
+use std::io::{BufReader, Read};
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 /// A parsed Zarr manifest
-#[derive(Clone, Debug, Deserialize, Eq, GetSize, PartialEq)]
+#[derive(Clone, Debug, Eq, GetSize, PartialEq)]
 pub(super) struct Manifest {
+    /// The version of the manifest schema that the manifest was written in
+    pub(super) schema_version: SchemaVersion,
+
     /// A tree of the Zarr's entries
     pub(super) entries: ManifestFolder,
 }
 
 impl Manifest {
+    /// Parse a Zarr manifest from the given JSON document.
+    ///
+    /// The document is parsed in a single pass, with the entry tree built
+    /// directly as the document is read rather than via an intermediate
+    /// generic representation.  Documents lacking a `schemaVersion` field are
+    /// assumed to use [`SchemaVersion::V2`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document is not valid JSON, if its schema
+    /// version is not one that `dandidav` supports, or if the document does
+    /// not conform to its schema version
+    pub(super) fn from_json(bytes: &[u8]) -> Result<Manifest, ParseManifestError> {
+        serde_json::from_slice::<RawManifest>(bytes)?.into_manifest()
+    }
+
+    /// Parse a Zarr manifest from a JSON document read incrementally from
+    /// `reader` in the same manner as [`Manifest::from_json()`].  This allows
+    /// a compressed manifest to be parsed as it is decompressed, without the
+    /// decompressed document ever being held in memory in its entirety.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseManifestError::Io`] if reading from `reader` fails, and
+    /// the same errors as [`Manifest::from_json()`] otherwise
+    pub(super) fn from_reader<R: Read>(reader: R) -> Result<Manifest, ParseManifestError> {
+        serde_json::from_reader::<_, RawManifest>(BufReader::new(reader))
+            .map_err(|e| {
+                if e.is_io() {
+                    ParseManifestError::Io(e.into())
+                } else {
+                    ParseManifestError::Json(e)
+                }
+            })?
+            .into_manifest()
+    }
+
+    /// Return the version of the manifest schema that the manifest was
+    /// written in
+    pub(super) fn schema_version(&self) -> SchemaVersion {
+        self.schema_version
+    }
+
     /// Retrieve a reference to the folder or entry in the manifest at `path`,
     /// if any
     pub(super) fn get(&self, path: &PurePath) -> Option<EntryRef<'_>> {
@@ -28,6 +81,183 @@ impl Manifest {
         }
         Some(EntryRef::Folder(folder))
     }
+
+    /// Returns `true` if a consolidated metadata file should be synthesized
+    /// for the Zarr, i.e., if the Zarr is a Zarr v2 hierarchy (as indicated
+    /// by a `.zgroup` or `.zarray` entry at its root) that does not already
+    /// contain a `.zmetadata` entry
+    pub(super) fn synthesizes_zmetadata(&self) -> bool {
+        let mut is_v2 = false;
+        for name in self.entries.keys() {
+            if name == ZMETADATA_NAME {
+                return false;
+            } else if name == ".zgroup" || name == ".zarray" {
+                is_v2 = true;
+            }
+        }
+        is_v2
+    }
+
+    /// Return the latest modification time of any entry in the Zarr, or
+    /// `None` if the Zarr is empty
+    pub(super) fn last_modified(&self) -> Option<OffsetDateTime> {
+        fn walk(folder: &ManifestFolder) -> Option<OffsetDateTime> {
+            folder
+                .values()
+                .filter_map(|child| match child {
+                    FolderEntry::Folder(f) => walk(f),
+                    FolderEntry::Entry(e) => Some(e.modified),
+                })
+                .max()
+        }
+
+        walk(&self.entries)
+    }
+
+    /// Return the paths of & manifest entries for all of the Zarr v2
+    /// metadata files (`.zattrs`, `.zarray`, and `.zgroup`) anywhere in the
+    /// Zarr, in depth-first order with folders' contents sorted by name
+    pub(super) fn metadata_entries(&self) -> Vec<(PurePath, &ManifestEntry)> {
+        fn walk<'a>(
+            folder: &'a ManifestFolder,
+            prefix: Option<&PurePath>,
+            out: &mut Vec<(PurePath, &'a ManifestEntry)>,
+        ) {
+            for (name, child) in folder {
+                let path = match prefix {
+                    Some(p) => p.join_one(name),
+                    None => PurePath::from(name.clone()),
+                };
+                match child {
+                    FolderEntry::Folder(f) => walk(f, Some(&path), out),
+                    FolderEntry::Entry(e) if ZARR_METADATA_NAMES.contains(&&**name) => {
+                        out.push((path, e));
+                    }
+                    FolderEntry::Entry(_) => (),
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        walk(&self.entries, None, &mut out);
+        out
+    }
+}
+
+/// The top-level fields of a Zarr manifest document, as gathered in a single
+/// pass over the document
+#[derive(Debug, Default)]
+struct RawManifest {
+    /// The schema version declared by the document, or the declared version
+    /// as written in the document if it is not supported
+    schema_version: Option<Result<SchemaVersion, String>>,
+
+    /// The parsed entry tree.  This is `None` if the document lacks an
+    /// `entries` field or if it declared an unsupported schema version
+    /// before the field was encountered, in which case the field is skipped.
+    entries: Option<ManifestFolder>,
+}
+
+impl RawManifest {
+    fn into_manifest(self) -> Result<Manifest, ParseManifestError> {
+        let schema_version = match self.schema_version {
+            None => SchemaVersion::V2,
+            Some(Ok(v)) => v,
+            Some(Err(v)) => return Err(ParseManifestError::UnsupportedVersion(v)),
+        };
+        let Some(entries) = self.entries else {
+            return Err(ParseManifestError::Json(de::Error::missing_field(
+                "entries",
+            )));
+        };
+        Ok(Manifest {
+            schema_version,
+            entries,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for RawManifest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = RawManifest;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a Zarr manifest")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawManifest, A::Error> {
+                let mut raw = RawManifest::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "schemaVersion" => {
+                            let v = map.next_value::<serde_json::Value>()?;
+                            raw.schema_version = Some(match v.as_u64() {
+                                Some(2) => Ok(SchemaVersion::V2),
+                                Some(3) => Ok(SchemaVersion::V3),
+                                _ => Err(v.to_string()),
+                            });
+                        }
+                        "entries" if !matches!(raw.schema_version, Some(Err(_))) => {
+                            // Entries of both supported schema versions are
+                            // handled by the same `Deserialize` implementation
+                            // (which accepts both arrays and objects), so the
+                            // version need not be known at this point.
+                            raw.entries = Some(map.next_value::<ManifestFolder>()?);
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(raw)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+/// The versions of the Zarr manifest schema supported by `dandidav`
+#[derive(Clone, Copy, Debug, Default, Eq, GetSize, Hash, PartialEq)]
+pub(super) enum SchemaVersion {
+    /// Schema version 2, in which each entry is an array of the entry's
+    /// fields in the order `versionId`, `lastModified`, `size`, `ETag`
+    #[default]
+    V2,
+
+    /// Schema version 3, in which each entry is an object mapping the names
+    /// of the entry's fields to their values
+    V3,
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaVersion::V2 => write!(f, "2"),
+            SchemaVersion::V3 => write!(f, "3"),
+        }
+    }
+}
+
+/// Error returned by [`Manifest::from_json()`]
+#[derive(Debug, Error)]
+pub(super) enum ParseManifestError {
+    /// The document was not valid JSON or did not match the manifest schema
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The document's `schemaVersion` is not supported.  The field contains
+    /// the version as written in the document.
+    #[error("unsupported manifest schema version {0}")]
+    UnsupportedVersion(String),
+
+    /// Reading the document failed (e.g., because it could not be
+    /// decompressed)
+    #[error("failed to read manifest document")]
+    Io(#[source] std::io::Error),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -40,31 +270,194 @@ pub(super) enum EntryRef<'a> {
 /// subdirectory names to the entries & subdirectories
 pub(super) type ManifestFolder = BTreeMap<Component, FolderEntry>;
 
-#[derive(Clone, Debug, Deserialize, Eq, GetSize, PartialEq)]
-#[serde(untagged)]
+#[derive(Clone, Debug, Eq, GetSize, PartialEq)]
 pub(super) enum FolderEntry {
     Folder(ManifestFolder),
     Entry(ManifestEntry),
 }
 
+impl<'de> Deserialize<'de> for FolderEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match deserializer.deserialize_any(NodeVisitor)? {
+            Node::Entry(entry) => Ok(FolderEntry::Entry(entry)),
+            Node::Folder(folder) => Ok(FolderEntry::Folder(folder)),
+            Node::Str(s) => Err(de::Error::invalid_type(
+                de::Unexpected::Str(&s),
+                &NodeVisitor,
+            )),
+            Node::Int(i) => Err(de::Error::invalid_type(
+                de::Unexpected::Signed(i),
+                &NodeVisitor,
+            )),
+        }
+    }
+}
+
+/// A value encountered while parsing an entry tree: either a folder or entry,
+/// or a scalar that is a field of a schema version 3 entry.
+///
+/// As a JSON object in an entry tree may be either a folder or a schema
+/// version 3 entry, the values in an object are parsed as `Node`s, and the
+/// object is treated as an entry if any of its values are scalars.  This lets
+/// the tree be built in a single pass without buffering objects (which could
+/// contain large subtrees) in order to try parsing them both ways.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Node {
+    Folder(ManifestFolder),
+    Entry(ManifestEntry),
+    Str(String),
+    Int(i64),
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> de::Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a Zarr manifest folder or entry")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Node, E> {
+        Ok(Node::Str(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Node, E> {
+        Ok(Node::Str(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Node, E> {
+        Ok(Node::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Node, E> {
+        i64::try_from(v)
+            .map(Node::Int)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &"an i64"))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Node, A::Error> {
+        ManifestEntry::deserialize(SeqAccessDeserializer::new(seq)).map(Node::Entry)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut folder = ManifestFolder::new();
+        let mut fields = EntryFields::default();
+        while let Some(key) = map.next_key::<String>()? {
+            match map.next_value::<Node>()? {
+                Node::Folder(f) if !fields.seen => {
+                    folder.insert(parse_name::<A::Error>(&key)?, FolderEntry::Folder(f));
+                }
+                Node::Entry(e) if !fields.seen => {
+                    folder.insert(parse_name::<A::Error>(&key)?, FolderEntry::Entry(e));
+                }
+                Node::Folder(_) | Node::Entry(_) => (),
+                Node::Str(s) => fields.set_str(&key, s)?,
+                Node::Int(i) => fields.set_int(&key, i)?,
+            }
+        }
+        if fields.seen {
+            fields.finish().map(Node::Entry)
+        } else {
+            Ok(Node::Folder(folder))
+        }
+    }
+}
+
+/// Parse a key of a JSON object in an entry tree as the name of a folder or
+/// entry
+fn parse_name<E: de::Error>(key: &str) -> Result<Component, E> {
+    key.parse::<Component>()
+        .map_err(|_| E::invalid_value(de::Unexpected::Str(key), &"a path component"))
+}
+
+/// The fields of a schema version 3 entry gathered so far while parsing a
+/// JSON object.  Unknown fields are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct EntryFields {
+    /// Whether any scalar values (and thus possibly fields) have been
+    /// encountered in the object yet
+    seen: bool,
+    version_id: Option<String>,
+    modified: Option<String>,
+    size: Option<i64>,
+    etag: Option<String>,
+}
+
+impl EntryFields {
+    fn set_str<E: de::Error>(&mut self, key: &str, value: String) -> Result<(), E> {
+        self.seen = true;
+        let slot = match key {
+            "versionId" => &mut self.version_id,
+            "lastModified" => &mut self.modified,
+            "ETag" => &mut self.etag,
+            "size" => return Err(E::invalid_type(de::Unexpected::Str(&value), &"an integer")),
+            _ => return Ok(()),
+        };
+        *slot = Some(value);
+        Ok(())
+    }
+
+    fn set_int<E: de::Error>(&mut self, key: &str, value: i64) -> Result<(), E> {
+        self.seen = true;
+        match key {
+            "size" => {
+                self.size = Some(value);
+                Ok(())
+            }
+            "versionId" | "lastModified" | "ETag" => {
+                Err(E::invalid_type(de::Unexpected::Signed(value), &"a string"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn finish<E: de::Error>(self) -> Result<ManifestEntry, E> {
+        let version_id = self
+            .version_id
+            .ok_or_else(|| E::missing_field("versionId"))?;
+        let modified = self
+            .modified
+            .ok_or_else(|| E::missing_field("lastModified"))?;
+        let modified = OffsetDateTime::parse(&modified, &Rfc3339).map_err(E::custom)?;
+        let size = self.size.ok_or_else(|| E::missing_field("size"))?;
+        let etag = self.etag.ok_or_else(|| E::missing_field("ETag"))?;
+        Ok(ManifestEntry {
+            version_id,
+            modified,
+            size,
+            etag,
+        })
+    }
+}
+
 /// Information on a Zarr entry in a manifest as of the point in time
 /// represented by the manifest
 #[derive(Clone, Debug, Deserialize, Eq, GetSize, PartialEq)]
 pub(super) struct ManifestEntry {
-    // IMPORTANT: Keep these fields in this order so that deserialization will
-    // work properly!
+    // IMPORTANT: Keep these fields in this order so that deserialization of
+    // schema version 2 entries will work properly!  The renames are the field
+    // names used by schema version 3 entries.
     /// The S3 version ID of the entry's S3 object
+    #[serde(rename = "versionId")]
     pub(super) version_id: String,
 
     /// The entry's S3 object's modification time
     #[get_size(size = 0)] // Nothing on the heap
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(rename = "lastModified", with = "time::serde::rfc3339")]
     pub(super) modified: OffsetDateTime,
 
     /// The size of the entry in bytes
     pub(super) size: i64,
 
     /// The ETag of the entry's S3 object
+    #[serde(rename = "ETag")]
     pub(super) etag: String,
 }
 
@@ -73,6 +466,7 @@ mod tests {
     use super::*;
     use assert_matches::assert_matches;
     use indoc::indoc;
+    use rstest::rstest;
     use time::macros::datetime;
 
     #[test]
@@ -109,7 +503,7 @@ mod tests {
         }
         "#};
 
-        let manifest = serde_json::from_str::<Manifest>(s).unwrap();
+        let manifest = Manifest::from_json(s.as_bytes()).unwrap();
         let zattrs = ManifestEntry {
             version_id: "VwOSu7IVLAQcQHcqOesmlrEDm2sL_Tfs".into(),
             modified: datetime!(2022-06-27 23:07:47 UTC),
@@ -132,6 +526,7 @@ mod tests {
         assert_eq!(
             manifest,
             Manifest {
+                schema_version: SchemaVersion::V2,
                 entries: BTreeMap::from([
                     (
                         ".zattrs".parse().unwrap(),
@@ -229,4 +624,140 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_manifest_v3() {
+        let s = indoc! {r#"
+        {
+         "schemaVersion": 3,
+         "statistics": {
+          "entries": 2,
+          "depth": 2,
+          "totalSize": 470,
+          "lastModified": "2022-06-27T23:07:48+00:00",
+          "zarrChecksum": "0123456789abcdef0123456789abcdef-2--470"
+         },
+         "entries": {
+          ".zgroup": {"versionId": "7obAY5BUNOdI1Uch3RoI4oHuGXhW4h0R", "lastModified": "2022-06-27T23:07:47+00:00", "size": 24, "ETag": "e20297935e73dd0154104d4ea53040ab"},
+          "0": {
+           ".zarray": {"ETag": "5477ec3da352681e5ba6f6ea550ef740", "size": 446, "lastModified": "2022-06-27T23:07:48+00:00", "versionId": "Ou6TnKwWPmEJrL.0utCWLPxgfr_lA0I1"}
+          }
+         }
+        }
+        "#};
+        let manifest = Manifest::from_json(s.as_bytes()).unwrap();
+        assert_eq!(manifest.schema_version(), SchemaVersion::V3);
+        assert_eq!(
+            manifest.get(&".zgroup".parse::<PurePath>().unwrap()),
+            Some(EntryRef::Entry(&ManifestEntry {
+                version_id: "7obAY5BUNOdI1Uch3RoI4oHuGXhW4h0R".into(),
+                modified: datetime!(2022-06-27 23:07:47 UTC),
+                size: 24,
+                etag: "e20297935e73dd0154104d4ea53040ab".into(),
+            }))
+        );
+        assert_eq!(
+            manifest.get(&"0/.zarray".parse::<PurePath>().unwrap()),
+            Some(EntryRef::Entry(&ManifestEntry {
+                version_id: "Ou6TnKwWPmEJrL.0utCWLPxgfr_lA0I1".into(),
+                modified: datetime!(2022-06-27 23:07:48 UTC),
+                size: 446,
+                etag: "5477ec3da352681e5ba6f6ea550ef740".into(),
+            }))
+        );
+        assert!(manifest.synthesizes_zmetadata());
+    }
+
+    #[test]
+    fn test_manifest_no_version() {
+        let s = r#"{"entries": {"foo": ["abc", "2022-06-27T23:07:47+00:00", 24, "def"]}}"#;
+        let manifest = Manifest::from_json(s.as_bytes()).unwrap();
+        assert_eq!(manifest.schema_version(), SchemaVersion::V2);
+        assert_matches!(
+            manifest.get(&"foo".parse::<PurePath>().unwrap()),
+            Some(EntryRef::Entry(_))
+        );
+    }
+
+    #[rstest]
+    #[case(r#"{"schemaVersion": 4, "entries": {}}"#, "4")]
+    #[case(r#"{"entries": {"foo": {}}, "schemaVersion": 1}"#, "1")]
+    #[case(r#"{"schemaVersion": "3.0", "entries": {"foo": [1, 2]}}"#, r#""3.0""#)]
+    fn test_manifest_unsupported_version(#[case] s: &str, #[case] version: &str) {
+        assert_matches!(
+            Manifest::from_json(s.as_bytes()),
+            Err(ParseManifestError::UnsupportedVersion(v)) => {
+                assert_eq!(v, version);
+            }
+        );
+    }
+
+    #[test]
+    fn test_manifest_from_reader() {
+        let s = indoc! {r#"
+        {
+         "schemaVersion": 3,
+         "entries": {
+          ".zgroup": {"versionId": "7obAY5BUNOdI1Uch3RoI4oHuGXhW4h0R", "lastModified": "2022-06-27T23:07:47+00:00", "size": 24, "ETag": "e20297935e73dd0154104d4ea53040ab", "extra": "ignored"},
+          "size": {
+           "0": {"versionId": "Ou6TnKwWPmEJrL.0utCWLPxgfr_lA0I1", "lastModified": "2022-06-27T23:07:48+00:00", "size": 446, "ETag": "5477ec3da352681e5ba6f6ea550ef740"}
+          }
+         }
+        }
+        "#};
+        let manifest = Manifest::from_reader(s.as_bytes()).unwrap();
+        assert_eq!(manifest, Manifest::from_json(s.as_bytes()).unwrap());
+        assert_eq!(manifest.schema_version(), SchemaVersion::V3);
+        assert_matches!(
+            manifest.get(&".zgroup".parse::<PurePath>().unwrap()),
+            Some(EntryRef::Entry(e)) => {
+                assert_eq!(e.size, 24);
+            }
+        );
+        assert_matches!(
+            manifest.get(&"size/0".parse::<PurePath>().unwrap()),
+            Some(EntryRef::Entry(e)) => {
+                assert_eq!(e.etag, "5477ec3da352681e5ba6f6ea550ef740");
+            }
+        );
+    }
+
+    #[test]
+    fn test_manifest_from_reader_io_error() {
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("oops"))
+            }
+        }
+
+        assert_matches!(
+            Manifest::from_reader(FailingReader),
+            Err(ParseManifestError::Io(e)) => {
+                assert_eq!(e.to_string(), "oops");
+            }
+        );
+    }
+
+    #[rstest]
+    #[case(r#"{"schemaVersion": 2}"#)]
+    #[case(r#"{"schemaVersion": 3, "entries": {"foo": {"versionId": "abc", "size": 24, "ETag": "def"}}}"#)]
+    #[case(r#"{"schemaVersion": 3, "entries": {"foo": {"versionId": "abc", "lastModified": "2022-06-27T23:07:47+00:00", "size": "24", "ETag": "def"}}}"#)]
+    #[case(r#"{"schemaVersion": 2, "entries": {"..": ["abc", "2022-06-27T23:07:47+00:00", 24, "def"]}}"#)]
+    #[case(r#"{"schemaVersion": 2, "entries": {"foo": "bar"}}"#)]
+    fn test_manifest_invalid(#[case] s: &str) {
+        assert_matches!(
+            Manifest::from_json(s.as_bytes()),
+            Err(ParseManifestError::Json(_))
+        );
+    }
+
+    #[test]
+    fn test_manifest_bad_json() {
+        assert_matches!(
+            Manifest::from_json(br#"{"schemaVersion": 2, "entries": {"foo": 42}}"#),
+            Err(ParseManifestError::Json(_))
+        );
+    }
 }
@@ -13,18 +13,32 @@
 //! entry hierarchies.
 
 mod consts;
+mod diskcache;
+mod encoding;
 mod fetcher;
 mod manifest;
 mod path;
 mod resources;
 mod util;
-use self::consts::ENTRY_DOWNLOAD_PREFIX;
-pub(crate) use self::fetcher::ManifestFetcher;
+use self::consts::{
+    ENTRY_DOWNLOAD_PREFIX, LATEST_MANIFEST_CACHE_SIZE, LATEST_MANIFEST_CACHE_TTL,
+    ZMETADATA_CACHE_IDLE_EXPIRY, ZMETADATA_CACHE_SIZE, ZMETADATA_FETCH_CONCURRENCY, ZMETADATA_NAME,
+};
+pub(crate) use self::diskcache::ManifestDiskCache;
+pub(crate) use self::fetcher::{read_pin_file, ManifestFetcher};
 use self::path::ReqPath;
 pub(crate) use self::resources::*;
 pub(crate) use self::util::ZarrManError;
-use crate::httputil::HttpUrl;
-use crate::paths::{PureDirPath, PurePath};
+use crate::httputil::{HttpError, HttpUrl};
+use crate::metrics::metrics;
+use crate::paths::{strip_checksum_ext, Component, PureDirPath, PurePath};
+use bytes::Bytes;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use moka::{
+    future::{Cache, CacheBuilder},
+    ops::compute::{CompResult, Op},
+};
+use serde_json::{json, Map, Value};
 
 /// A client for fetching data about Zarrs via Zarr manifest files
 #[derive(Clone, Debug)]
@@ -38,6 +52,14 @@ pub(crate) struct ZarrManClient {
     /// The directory path `"zarrs/"`, used at various points in the code,
     /// pre-parsed for convenience
     web_path_prefix: PureDirPath,
+
+    /// A cache of the contents of synthesized consolidated metadata files,
+    /// keyed by the paths of the respective Zarrs' manifests
+    zmetadata: Cache<ManifestPath, Bytes>,
+
+    /// A short-lived cache of the paths to Zarrs' most recent manifests,
+    /// keyed by the Zarrs' directories in the manifest tree
+    latest: Cache<PureDirPath, ManifestPath>,
 }
 
 impl ZarrManClient {
@@ -49,19 +71,46 @@ impl ZarrManClient {
         let web_path_prefix = "zarrs/"
             .parse::<PureDirPath>()
             .expect(r#""zarrs/" should be a valid directory path"#);
+        let zmetadata = CacheBuilder::new(ZMETADATA_CACHE_SIZE)
+            .name("zarr-zmetadata")
+            .weigher(|_, blob: &Bytes| u32::try_from(blob.len()).unwrap_or(u32::MAX))
+            .time_to_idle(ZMETADATA_CACHE_IDLE_EXPIRY)
+            .build();
+        let latest = CacheBuilder::new(LATEST_MANIFEST_CACHE_SIZE)
+            .name("zarr-latest")
+            .time_to_live(LATEST_MANIFEST_CACHE_TTL)
+            .build();
         ZarrManClient {
             fetcher,
             entry_download_prefix,
             web_path_prefix,
+            zmetadata,
+            latest,
         }
     }
 
+    /// Check that the manifest tree is reachable
+    pub(crate) async fn check(&self) -> Result<(), HttpError> {
+        self.fetcher.check().await
+    }
+
     /// Retrieve the resources at the top level of `/zarrs/`, i.e., those
     /// matching the resources at the top level of the manifest tree
     pub(crate) async fn get_top_level_dirs(&self) -> Result<Vec<ZarrManResource>, ZarrManError> {
         self.get_index_entries(None).await
     }
 
+    /// Returns `true` if the given `path` (sans leading `zarrs/`) in the
+    /// `/zarrs/` hierarchy is a path to a Zarr manifest or to a resource
+    /// within one, in which case the contents of the resource (if it exists)
+    /// never change, as manifests are identified by checksum.
+    pub(crate) fn is_immutable(path: &PurePath) -> bool {
+        matches!(
+            ReqPath::parse_path(path),
+            Some(ReqPath::Manifest(_) | ReqPath::InManifest { .. })
+        )
+    }
+
     /// Get details on the resource at the given `path` (sans leading `zarrs/`)
     /// in the `/zarrs/` hierarchy
     ///
@@ -71,37 +120,116 @@ impl ZarrManClient {
         &self,
         path: &PurePath,
     ) -> Result<ZarrManResource, ZarrManError> {
-        let Some(rp) = ReqPath::parse_path(path) else {
-            return Err(ZarrManError::InvalidPath { path: path.clone() });
-        };
-        match rp {
-            ReqPath::Dir(p) => {
+        let target = self.resolve(path).await?;
+        self.get_target(target).await
+    }
+
+    /// Get details on the resource at the given `path` (sans leading `zarrs/`)
+    /// in the `/zarrs/` hierarchy along with its immediate child resources (if
+    /// any)
+    ///
+    /// Although `path` is a `PurePath`, the resulting resource may be a
+    /// collection.
+    pub(crate) async fn get_resource_with_children(
+        &self,
+        path: &PurePath,
+    ) -> Result<ZarrManResourceWithChildren, ZarrManError> {
+        let target = self.resolve(path).await?;
+        self.get_target_with_children(target).await
+    }
+
+    /// Returns `true` if the manifest tree contains a manifest at
+    /// `manifest_path`.  The manifest is fetched (if not already cached) in
+    /// order to determine this.
+    pub(crate) async fn has_manifest(
+        &self,
+        manifest_path: &ManifestPath,
+    ) -> Result<bool, ZarrManError> {
+        match self.fetcher.fetch_manifest(manifest_path).await {
+            Ok(_) => Ok(true),
+            Err(ZarrManError::Http(HttpError::NotFound { .. })) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get details on the resource at `entry_path` in the Zarr whose manifest
+    /// is at `manifest_path` (or on the Zarr itself, if `entry_path` is
+    /// `None`), with the Zarr served at `web_path` rather than at its usual
+    /// location in the `/zarrs/` hierarchy
+    pub(crate) async fn get_zarr_resource(
+        &self,
+        manifest_path: ManifestPath,
+        web_path: PureDirPath,
+        entry_path: Option<PurePath>,
+    ) -> Result<ZarrManResource, ZarrManError> {
+        self.get_target(Target::Zarr {
+            manifest_path,
+            web_path,
+            entry_path,
+        })
+        .await
+    }
+
+    /// Get details on the resource at `entry_path` in the Zarr whose manifest
+    /// is at `manifest_path` (or on the Zarr itself, if `entry_path` is
+    /// `None`) along with its immediate child resources (if any), with the
+    /// Zarr served at `web_path` rather than at its usual location in the
+    /// `/zarrs/` hierarchy
+    pub(crate) async fn get_zarr_resource_with_children(
+        &self,
+        manifest_path: ManifestPath,
+        web_path: PureDirPath,
+        entry_path: Option<PurePath>,
+    ) -> Result<ZarrManResourceWithChildren, ZarrManError> {
+        self.get_target_with_children(Target::Zarr {
+            manifest_path,
+            web_path,
+            entry_path,
+        })
+        .await
+    }
+
+    /// Get details on the resource identified by `target`
+    async fn get_target(&self, target: Target) -> Result<ZarrManResource, ZarrManError> {
+        match target {
+            Target::Dir(p) => {
                 // Make a request to confirm that directory exists
                 let _ = self.get_index_entries(Some(&p)).await?;
                 Ok(ZarrManResource::WebFolder(WebFolder {
                     web_path: self.web_path_prefix.join_dir(&p),
                 }))
             }
-            ReqPath::Manifest(path) => {
+            Target::Zarr {
+                manifest_path,
+                web_path,
+                entry_path: None,
+            } => {
                 // Make a request to confirm that manifest exists
-                let _ = self.fetcher.fetch_manifest(&path).await?;
-                Ok(ZarrManResource::Manifest(Manifest { path }))
+                let _ = self.fetcher.fetch_manifest(&manifest_path).await?;
+                Ok(ZarrManResource::Manifest(Manifest {
+                    path: manifest_path,
+                    web_path,
+                }))
             }
-            ReqPath::InManifest {
+            Target::Zarr {
                 manifest_path,
-                entry_path,
+                web_path,
+                entry_path: Some(entry_path),
             } => {
                 let man = self.fetcher.fetch_manifest(&manifest_path).await?;
                 match man.get(&entry_path) {
                     Some(manifest::EntryRef::Folder(_)) => {
-                        let web_path = manifest_path
-                            .to_web_path()
-                            .join_dir(&entry_path.to_dir_path());
+                        let web_path = web_path.join_dir(&entry_path.to_dir_path());
                         Ok(ZarrManResource::ManFolder(ManifestFolder { web_path }))
                     }
                     Some(manifest::EntryRef::Entry(entry)) => Ok(ZarrManResource::ManEntry(
-                        self.convert_manifest_entry(&manifest_path, &entry_path, entry),
+                        self.convert_manifest_entry(&manifest_path, &web_path, &entry_path, entry),
                     )),
+                    None if entry_path == ZMETADATA_NAME && man.synthesizes_zmetadata() => {
+                        Ok(ZarrManResource::ZMetadata(
+                            self.get_zmetadata(&manifest_path, &web_path, &man).await?,
+                        ))
+                    }
                     None => Err(ZarrManError::ManifestPathNotFound {
                         manifest_path,
                         entry_path,
@@ -111,54 +239,80 @@ impl ZarrManClient {
         }
     }
 
-    /// Get details on the resource at the given `path` (sans leading `zarrs/`)
-    /// in the `/zarrs/` hierarchy along with its immediate child resources (if
-    /// any)
-    ///
-    /// Although `path` is a `PurePath`, the resulting resource may be a
-    /// collection.
-    pub(crate) async fn get_resource_with_children(
+    /// Get details on the resource identified by `target` along with its
+    /// immediate child resources (if any)
+    async fn get_target_with_children(
         &self,
-        path: &PurePath,
+        target: Target,
     ) -> Result<ZarrManResourceWithChildren, ZarrManError> {
-        let Some(rp) = ReqPath::parse_path(path) else {
-            return Err(ZarrManError::InvalidPath { path: path.clone() });
-        };
-        match rp {
-            ReqPath::Dir(p) => {
+        match target {
+            Target::Dir(p) => {
                 let children = self.get_index_entries(Some(&p)).await?;
                 let folder = WebFolder {
                     web_path: self.web_path_prefix.join_dir(&p),
                 };
                 Ok(ZarrManResourceWithChildren::WebFolder { folder, children })
             }
-            ReqPath::Manifest(path) => {
-                let man = self.fetcher.fetch_manifest(&path).await?;
-                let children = self.convert_manifest_folder_children(&path, None, &man.entries);
-                let folder = Manifest { path };
+            Target::Zarr {
+                manifest_path,
+                web_path,
+                entry_path: None,
+            } => {
+                let man = self.fetcher.fetch_manifest(&manifest_path).await?;
+                let mut children = self.convert_manifest_folder_children(
+                    &manifest_path,
+                    &web_path,
+                    None,
+                    &man.entries,
+                );
+                if man.synthesizes_zmetadata() {
+                    // Keep the children sorted by name
+                    let pos = man
+                        .entries
+                        .keys()
+                        .take_while(|name| &***name < ZMETADATA_NAME)
+                        .count();
+                    children.insert(
+                        pos,
+                        ZarrManResource::ZMetadata(zmetadata_stub(&web_path, &man)),
+                    );
+                }
+                let folder = Manifest {
+                    path: manifest_path,
+                    web_path,
+                };
                 Ok(ZarrManResourceWithChildren::Manifest { folder, children })
             }
-            ReqPath::InManifest {
+            Target::Zarr {
                 manifest_path,
-                entry_path,
+                web_path,
+                entry_path: Some(entry_path),
             } => {
                 let man = self.fetcher.fetch_manifest(&manifest_path).await?;
                 match man.get(&entry_path) {
                     Some(manifest::EntryRef::Folder(folref)) => {
-                        let web_path = manifest_path
-                            .to_web_path()
-                            .join_dir(&entry_path.to_dir_path());
                         let children = self.convert_manifest_folder_children(
                             &manifest_path,
+                            &web_path,
                             Some(&entry_path),
                             folref,
                         );
-                        let folder = ManifestFolder { web_path };
+                        let folder = ManifestFolder {
+                            web_path: web_path.join_dir(&entry_path.to_dir_path()),
+                        };
                         Ok(ZarrManResourceWithChildren::ManFolder { folder, children })
                     }
-                    Some(manifest::EntryRef::Entry(entry)) => {
-                        Ok(ZarrManResourceWithChildren::ManEntry(
-                            self.convert_manifest_entry(&manifest_path, &entry_path, entry),
+                    Some(manifest::EntryRef::Entry(entry)) => Ok(
+                        ZarrManResourceWithChildren::ManEntry(self.convert_manifest_entry(
+                            &manifest_path,
+                            &web_path,
+                            &entry_path,
+                            entry,
+                        )),
+                    ),
+                    None if entry_path == ZMETADATA_NAME && man.synthesizes_zmetadata() => {
+                        Ok(ZarrManResourceWithChildren::ZMetadata(
+                            self.get_zmetadata(&manifest_path, &web_path, &man).await?,
                         ))
                     }
                     None => Err(ZarrManError::ManifestPathNotFound {
@@ -170,6 +324,111 @@ impl ZarrManClient {
         }
     }
 
+    /// Parse a path (sans leading `zarrs/`) in the `/zarrs/` hierarchy and,
+    /// if it refers to the most recent manifest for a Zarr or a resource
+    /// within it, determine the path to that manifest
+    async fn resolve(&self, path: &PurePath) -> Result<Target, ZarrManError> {
+        let Some(rp) = ReqPath::parse_path(path) else {
+            return Err(ZarrManError::InvalidPath { path: path.clone() });
+        };
+        Ok(match rp {
+            ReqPath::Dir(p) => Target::Dir(p),
+            ReqPath::Manifest(manifest_path) => Target::Zarr {
+                web_path: manifest_path.to_web_path(),
+                manifest_path,
+                entry_path: None,
+            },
+            ReqPath::InManifest {
+                manifest_path,
+                entry_path,
+            } => Target::Zarr {
+                web_path: manifest_path.to_web_path(),
+                manifest_path,
+                entry_path: Some(entry_path),
+            },
+            ReqPath::Latest {
+                prefix,
+                zarr_id,
+                zarr_path,
+                entry_path,
+            } => Target::Zarr {
+                manifest_path: self.latest_manifest(&prefix, &zarr_id).await?,
+                web_path: self.web_path_prefix.join_dir(&zarr_path),
+                entry_path,
+            },
+        })
+    }
+
+    /// Return the path to the most recent manifest for the Zarr with the
+    /// given ID, either from a cache or by listing the Zarr's manifests in
+    /// the manifest tree.
+    ///
+    /// As manifests are named by checksum, their names do not indicate their
+    /// order.  If a Zarr has more than one manifest, each one is fetched, and
+    /// the one with the latest entry modification time is taken to be the
+    /// most recent.
+    async fn latest_manifest(
+        &self,
+        prefix: &PureDirPath,
+        zarr_id: &Component,
+    ) -> Result<ManifestPath, ZarrManError> {
+        let zarr_dir = &prefix.join_one_dir(zarr_id);
+        let result = self
+            .latest
+            .entry_by_ref(zarr_dir)
+            .and_try_compute_with(|entry| async move {
+                if entry.is_some() {
+                    return Ok(Op::Nop);
+                }
+                let index = self.fetcher.fetch_index(Some(zarr_dir)).await?;
+                let mut candidates = index
+                    .files
+                    .iter()
+                    .filter_map(|f| strip_checksum_ext(f, ".json"))
+                    .map(|checksum| ManifestPath {
+                        prefix: prefix.clone(),
+                        zarr_id: zarr_id.clone(),
+                        checksum,
+                    })
+                    .collect::<Vec<_>>();
+                let latest = match candidates.len() {
+                    0 => {
+                        return Err(ZarrManError::NoManifests {
+                            zarr_id: zarr_id.clone(),
+                        })
+                    }
+                    1 => candidates.pop().expect("candidates should be nonempty"),
+                    _ => {
+                        let mut latest = None;
+                        for path in candidates {
+                            let man = self.fetcher.fetch_manifest(&path).await?;
+                            let key = (man.last_modified(), path.checksum.clone());
+                            if latest.as_ref().map_or(true, |(k, _)| key > *k) {
+                                latest = Some((key, path));
+                            }
+                        }
+                        latest.expect("candidates should be nonempty").1
+                    }
+                };
+                Ok(Op::Put(latest))
+            })
+            .await?;
+        let entry = match result {
+            CompResult::Inserted(entry) => {
+                metrics().record_cache_lookup("zarr-latest", false);
+                entry
+            }
+            CompResult::Unchanged(entry) => {
+                metrics().record_cache_lookup("zarr-latest", true);
+                entry
+            }
+            _ => unreachable!(
+                "Call to and_try_compute_with() should only ever return Inserted or Unchanged"
+            ),
+        };
+        Ok(entry.into_value())
+    }
+
     /// Retrieve the resources in the given directory of the manifest tree.
     ///
     /// `path` must be relative to the manifest root.  A `path` of `None`
@@ -185,22 +444,18 @@ impl ZarrManClient {
         if let Some(path) = path {
             if let Some(prefix) = path.parent() {
                 for f in index.files {
-                    // This calls Component::strip_suffix(), so `checksum` is
-                    // guaranteed to be non-empty.
-                    let Some(checksum) = f.strip_suffix(".json") else {
-                        // Ignore
-                        continue;
-                    };
-                    if !checksum.contains('.') {
+                    // Files that are not manifests are ignored.
+                    if let Some(checksum) = strip_checksum_ext(&f, ".json") {
+                        let path = ManifestPath {
+                            prefix: prefix.clone(),
+                            zarr_id: path.name(),
+                            checksum,
+                        };
                         entries.push(ZarrManResource::Manifest(Manifest {
-                            path: ManifestPath {
-                                prefix: prefix.clone(),
-                                zarr_id: path.name(),
-                                checksum,
-                            },
+                            web_path: path.to_web_path(),
+                            path,
                         }));
                     }
-                    // else: Ignore
                 }
             }
         }
@@ -217,42 +472,125 @@ impl ZarrManClient {
     }
 
     /// Convert the [`manifest::ManifestEntry`] `entry` with path `entry_path`
-    /// in the manifest at `manifest_path` to a [`ManifestEntry`].
+    /// in the manifest at `manifest_path` (served at `zarr_web_path`) to a
+    /// [`ManifestEntry`].
     ///
     /// This largely consists of calculating the `web_path` and `url` fields of
     /// the entry.
     fn convert_manifest_entry(
         &self,
         manifest_path: &ManifestPath,
+        zarr_web_path: &PureDirPath,
         entry_path: &PurePath,
         entry: &manifest::ManifestEntry,
     ) -> ManifestEntry {
-        let web_path = manifest_path.to_web_path().join(entry_path);
-        let mut url = self.entry_download_prefix.clone();
-        url.push(manifest_path.zarr_id());
-        url.extend(entry_path.component_strs());
-        url.append_query_param("versionId", &entry.version_id);
         ManifestEntry {
-            web_path,
+            web_path: zarr_web_path.join(entry_path),
             size: entry.size,
             modified: entry.modified,
             etag: entry.etag.clone(),
-            url,
+            url: self.entry_url(manifest_path, entry_path, entry),
         }
     }
 
+    /// Return the download URL for the [`manifest::ManifestEntry`] `entry`
+    /// with path `entry_path` in the manifest at `manifest_path`
+    fn entry_url(
+        &self,
+        manifest_path: &ManifestPath,
+        entry_path: &PurePath,
+        entry: &manifest::ManifestEntry,
+    ) -> HttpUrl {
+        let mut url = self.entry_download_prefix.clone();
+        url.push(manifest_path.zarr_id());
+        url.extend(entry_path.component_strs());
+        url.append_query_param("versionId", &entry.version_id);
+        url
+    }
+
+    /// Return the consolidated metadata file synthesized for the Zarr whose
+    /// manifest is `man` at `manifest_path` (served at `zarr_web_path`),
+    /// either from a cache or by fetching & merging the contents of the
+    /// Zarr's metadata entries.
+    ///
+    /// The file's content is a JSON object of the form written by
+    /// `zarr.consolidate_metadata()`: the `"metadata"` field maps the paths
+    /// of the `.zattrs`, `.zarray`, and `.zgroup` entries to their parsed
+    /// contents.
+    async fn get_zmetadata(
+        &self,
+        manifest_path: &ManifestPath,
+        zarr_web_path: &PureDirPath,
+        man: &manifest::Manifest,
+    ) -> Result<ConsolidatedMetadata, ZarrManError> {
+        let result = self
+            .zmetadata
+            .entry_by_ref(manifest_path)
+            .and_try_compute_with(|entry| async move {
+                if entry.is_none() {
+                    let targets = man
+                        .metadata_entries()
+                        .into_iter()
+                        .map(|(path, entry)| {
+                            let url = self.entry_url(manifest_path, &path, entry);
+                            (path.to_string(), url)
+                        })
+                        .collect::<Vec<_>>();
+                    let metadata = stream::iter(targets)
+                        .map(|(path, url)| {
+                            let fetcher = self.fetcher.clone();
+                            async move {
+                                let value = fetcher.fetch_entry_json(url).await?;
+                                Ok::<_, HttpError>((path, value))
+                            }
+                        })
+                        .buffered(ZMETADATA_FETCH_CONCURRENCY)
+                        .try_collect::<Map<String, Value>>()
+                        .await?;
+                    let blob = serde_json::to_vec_pretty(&json!({
+                        "metadata": metadata,
+                        "zarr_consolidated_format": 1,
+                    }))
+                    .expect("serializing consolidated metadata should not fail");
+                    Ok::<_, HttpError>(Op::Put(Bytes::from(blob)))
+                } else {
+                    Ok(Op::Nop)
+                }
+            })
+            .await?;
+        let entry = match result {
+            CompResult::Inserted(entry) => {
+                metrics().record_cache_lookup("zarr-zmetadata", false);
+                entry
+            }
+            CompResult::Unchanged(entry) => {
+                metrics().record_cache_lookup("zarr-zmetadata", true);
+                entry
+            }
+            _ => unreachable!(
+                "Call to and_try_compute_with() should only ever return Inserted or Unchanged"
+            ),
+        };
+        Ok(ConsolidatedMetadata {
+            content: Some(entry.into_value().to_vec()),
+            ..zmetadata_stub(zarr_web_path, man)
+        })
+    }
+
     /// Convert the entries in `folder` (a folder at path `folder_path` in the
-    /// manifest at `manifest_path`) to [`ZarrManResource`]s
+    /// manifest at `manifest_path`, served at `zarr_web_path`) to
+    /// [`ZarrManResource`]s
     fn convert_manifest_folder_children(
         &self,
         manifest_path: &ManifestPath,
+        zarr_web_path: &PureDirPath,
         folder_path: Option<&PurePath>,
         folder: &manifest::ManifestFolder,
     ) -> Vec<ZarrManResource> {
         let mut children = Vec::with_capacity(folder.len());
         let web_path_prefix = match folder_path {
-            Some(p) => manifest_path.to_web_path().join_dir(&p.to_dir_path()),
-            None => manifest_path.to_web_path(),
+            Some(p) => zarr_web_path.join_dir(&p.to_dir_path()),
+            None => zarr_web_path.clone(),
         };
         for (name, child) in folder {
             match child {
@@ -268,6 +606,7 @@ impl ZarrManClient {
                     };
                     children.push(ZarrManResource::ManEntry(self.convert_manifest_entry(
                         manifest_path,
+                        zarr_web_path,
                         &entry_path,
                         entry,
                     )));
@@ -277,3 +616,293 @@ impl ZarrManClient {
         children
     }
 }
+
+/// Return a [`ConsolidatedMetadata`] without content for the Zarr whose
+/// manifest is `man` and which is served at `zarr_web_path`
+fn zmetadata_stub(zarr_web_path: &PureDirPath, man: &manifest::Manifest) -> ConsolidatedMetadata {
+    let name = ZMETADATA_NAME
+        .parse::<PurePath>()
+        .expect("ZMETADATA_NAME should be a valid path");
+    ConsolidatedMetadata {
+        web_path: zarr_web_path.join(&name),
+        modified: man
+            .metadata_entries()
+            .into_iter()
+            .map(|(_, entry)| entry.modified)
+            .max(),
+        content: None,
+    }
+}
+
+/// A parsed path in the `/zarrs/` hierarchy with any references to a Zarr's
+/// most recent manifest resolved
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Target {
+    /// A directory path between the manifest root and the Zarr manifests
+    Dir(PureDirPath),
+
+    /// A Zarr or a resource within it
+    Zarr {
+        /// The path to the Zarr's manifest
+        manifest_path: ManifestPath,
+
+        /// The path to the Zarr as served by `dandidav`, including the leading
+        /// `zarrs/`
+        web_path: PureDirPath,
+
+        /// The portion of the path within the Zarr, if any
+        entry_path: Option<PurePath>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::consts::MANIFEST_ROOT_URL;
+    use super::*;
+    use crate::httputil::{FakeTransport, HttpConfig, RetryPolicy};
+    use assert_matches::assert_matches;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::sync::Arc;
+    use time::macros::datetime;
+
+    const ZARR_ID: &str = "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d";
+    const CHECKSUM: &str = "6ddc4625befef8d6f9796835648162be-509--710206390";
+
+    fn manifest_url() -> String {
+        format!("{MANIFEST_ROOT_URL}128/4a1/{ZARR_ID}/{CHECKSUM}.json")
+    }
+
+    fn entry_url(path: &str, version_id: &str) -> String {
+        format!("{ENTRY_DOWNLOAD_PREFIX}{ZARR_ID}/{path}?versionId={version_id}")
+    }
+
+    fn zarr_path(entry_path: Option<&str>) -> PurePath {
+        let mut s = format!("128/4a1/{ZARR_ID}/{CHECKSUM}.zarr");
+        if let Some(p) = entry_path {
+            s.push('/');
+            s.push_str(p);
+        }
+        s.parse().unwrap()
+    }
+
+    fn client(transport: FakeTransport) -> (ZarrManClient, Arc<FakeTransport>) {
+        let transport = Arc::new(transport);
+        let fetcher =
+            ManifestFetcher::new(1_000_000, RetryPolicy::default(), HttpConfig::default())
+                .unwrap()
+                .with_transport(transport.clone());
+        (ZarrManClient::new(fetcher), transport)
+    }
+
+    fn v2_transport(root_entries: Value) -> FakeTransport {
+        let mut entries = json!({
+            ".zattrs": ["v-root-attrs", "2022-06-27T23:07:47+00:00", 24, "e1"],
+            ".zgroup": ["v-root-group", "2022-06-27T23:07:48+00:00", 24, "e2"],
+            "0": {
+                ".zarray": ["v-0-array", "2022-06-27T23:09:00+00:00", 446, "e3"],
+                "0": ["v-0-0", "2022-06-27T23:10:00+00:00", 1793451, "e4"],
+            },
+        });
+        if let (Some(entries), Value::Object(extra)) = (entries.as_object_mut(), root_entries) {
+            entries.extend(extra);
+        }
+        FakeTransport::new()
+            .json(&manifest_url(), &json!({"entries": entries}))
+            .json(
+                &entry_url(".zattrs", "v-root-attrs"),
+                &json!({"multiscales": []}),
+            )
+            .json(
+                &entry_url(".zgroup", "v-root-group"),
+                &json!({"zarr_format": 2}),
+            )
+            .json(
+                &entry_url("0/.zarray", "v-0-array"),
+                &json!({"chunks": [64], "zarr_format": 2}),
+            )
+    }
+
+    #[tokio::test]
+    async fn get_zmetadata() {
+        let (client, transport) = client(v2_transport(json!({})));
+        let res = client
+            .get_resource(&zarr_path(Some(".zmetadata")))
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResource::ZMetadata(zmeta) => {
+            assert_eq!(zmeta.web_path.to_string(), format!("zarrs/128/4a1/{ZARR_ID}/{CHECKSUM}.zarr/.zmetadata"));
+            assert_eq!(zmeta.modified, Some(datetime!(2022-06-27 23:09:00 UTC)));
+            let content = serde_json::from_slice::<Value>(&zmeta.content.unwrap()).unwrap();
+            assert_eq!(
+                content,
+                json!({
+                    "metadata": {
+                        ".zattrs": {"multiscales": []},
+                        ".zgroup": {"zarr_format": 2},
+                        "0/.zarray": {"chunks": [64], "zarr_format": 2},
+                    },
+                    "zarr_consolidated_format": 1,
+                })
+            );
+        });
+        assert_eq!(transport.requests().len(), 4);
+        // The result is cached:
+        let res = client
+            .get_resource_with_children(&zarr_path(Some(".zmetadata")))
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResourceWithChildren::ZMetadata(zmeta) => {
+            assert!(zmeta.content.is_some());
+        });
+        assert_eq!(transport.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn list_zmetadata() {
+        let (client, transport) = client(v2_transport(json!({})));
+        let res = client
+            .get_resource_with_children(&zarr_path(None))
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResourceWithChildren::Manifest { children, .. } => {
+            assert_eq!(children.len(), 4);
+            assert_matches!(&children[2], ZarrManResource::ZMetadata(zmeta) => {
+                assert_eq!(zmeta.web_path.name_str(), ".zmetadata");
+                assert_eq!(zmeta.content, None);
+            });
+            assert_matches!(&children[3], ZarrManResource::ManFolder(_));
+        });
+        // Listing does not fetch the metadata entries:
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn real_zmetadata_wins() {
+        let (client, transport) = client(v2_transport(json!({
+            ".zmetadata": ["v-zmeta", "2022-06-27T23:07:47+00:00", 15191, "e5"],
+        })));
+        let res = client
+            .get_resource(&zarr_path(Some(".zmetadata")))
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResource::ManEntry(entry) => {
+            assert_eq!(entry.size, 15191);
+            assert_eq!(entry.url.as_str(), entry_url(".zmetadata", "v-zmeta"));
+        });
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn no_zmetadata_for_v3() {
+        let (client, _) = client(FakeTransport::new().json(
+            &manifest_url(),
+            &json!({
+                "entries": {
+                    "zarr.json": ["v-zarr-json", "2024-01-01T00:00:00+00:00", 100, "e1"],
+                },
+            }),
+        ));
+        let r = client.get_resource(&zarr_path(Some(".zmetadata"))).await;
+        assert_matches!(r, Err(ZarrManError::ManifestPathNotFound { .. }));
+        let res = client
+            .get_resource_with_children(&zarr_path(None))
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResourceWithChildren::Manifest { children, .. } => {
+            assert_eq!(children.len(), 1);
+        });
+    }
+
+    fn index_url() -> String {
+        format!("{MANIFEST_ROOT_URL}128/4a1/{ZARR_ID}/")
+    }
+
+    #[tokio::test]
+    async fn get_latest_single() {
+        let (client, transport) = client(v2_transport(json!({})).json(
+            &index_url(),
+            &json!({
+                "path": format!("128/4a1/{ZARR_ID}"),
+                "files": [format!("{CHECKSUM}.json")],
+                "directories": [],
+            }),
+        ));
+        let res = client
+            .get_resource(&format!("{ZARR_ID}/0/0").parse().unwrap())
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResource::ManEntry(entry) => {
+            assert_eq!(entry.web_path.to_string(), format!("zarrs/{ZARR_ID}/0/0"));
+            assert_eq!(entry.url.as_str(), entry_url("0/0", "v-0-0"));
+        });
+        let res = client
+            .get_resource_with_children(&format!("128/4a1/{ZARR_ID}/latest").parse().unwrap())
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResourceWithChildren::Manifest { folder, children } => {
+            assert_eq!(folder.path.checksum, CHECKSUM);
+            assert_eq!(folder.web_path.to_string(), format!("zarrs/128/4a1/{ZARR_ID}/latest/"));
+            assert_matches!(&children[0], ZarrManResource::ManEntry(entry) => {
+                assert_eq!(
+                    entry.web_path.to_string(),
+                    format!("zarrs/128/4a1/{ZARR_ID}/latest/.zattrs")
+                );
+            });
+        });
+        // The index and manifest are each only fetched once:
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_latest_multiple() {
+        const OLD_CHECKSUM: &str = "0a0b8e4dcfa7c5ab9b0a1bbc4ed9e1b4-1--100";
+        let (client, _) = client(
+            v2_transport(json!({}))
+                .json(
+                    &index_url(),
+                    &json!({
+                        "path": format!("128/4a1/{ZARR_ID}"),
+                        "files": [
+                            format!("{OLD_CHECKSUM}.json"),
+                            format!("{CHECKSUM}.json"),
+                            "README.txt",
+                        ],
+                        "directories": [],
+                    }),
+                )
+                .json(
+                    &format!("{MANIFEST_ROOT_URL}128/4a1/{ZARR_ID}/{OLD_CHECKSUM}.json"),
+                    &json!({
+                        "entries": {
+                            ".zgroup": ["v-old", "2021-01-01T00:00:00+00:00", 24, "e0"],
+                        },
+                    }),
+                ),
+        );
+        let res = client
+            .get_resource(&ZARR_ID.parse().unwrap())
+            .await
+            .unwrap();
+        assert_matches!(res, ZarrManResource::Manifest(man) => {
+            assert_eq!(man.path.checksum, CHECKSUM);
+            assert_eq!(man.web_path.to_string(), format!("zarrs/{ZARR_ID}/"));
+        });
+    }
+
+    #[tokio::test]
+    async fn get_latest_no_manifests() {
+        let (client, _) = client(FakeTransport::new().json(
+            &index_url(),
+            &json!({
+                "path": format!("128/4a1/{ZARR_ID}"),
+                "files": [],
+                "directories": [],
+            }),
+        ));
+        let r = client.get_resource(&ZARR_ID.parse().unwrap()).await;
+        assert_matches!(r, Err(ZarrManError::NoManifests { zarr_id }) => {
+            assert_eq!(zarr_id, ZARR_ID);
+        });
+    }
+}
@@ -1,5 +1,6 @@
+use super::consts::LATEST_MANIFEST_NAME;
 use super::resources::ManifestPath;
-use crate::paths::{PureDirPath, PurePath};
+use crate::paths::{strip_checksum_ext, Component, PureDirPath, PurePath};
 
 /// A parsed representation of a path under the `/zarrs/` hierarchy
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -22,6 +23,23 @@ pub(super) enum ReqPath {
         /// The portion of the path within the Zarr
         entry_path: PurePath,
     },
+
+    /// A path to the most recent manifest for a Zarr or to a resource inside
+    /// it, given either as `{zarr_id}/` or as
+    /// `{prefix1}/{prefix2}/{zarr_id}/latest/` (optionally followed by a path
+    /// within the Zarr)
+    Latest {
+        /// The portion of the manifest tree path between the manifest root
+        /// and the Zarr ID, of the form `{prefix1}/{prefix2}/`
+        prefix: PureDirPath,
+        /// The Zarr ID
+        zarr_id: Component,
+        /// The path (sans leading `zarrs/`) at which the Zarr was requested,
+        /// i.e., the path under which its contents are served
+        zarr_path: PureDirPath,
+        /// The portion of the path within the Zarr, if any
+        entry_path: Option<PurePath>,
+    },
 }
 
 impl ReqPath {
@@ -32,6 +50,17 @@ impl ReqPath {
         let Some(c1) = components.next() else {
             unreachable!("path should have at least one component");
         };
+        if is_zarr_id(&c1) {
+            let prefix = PureDirPath::try_from(format!("{}/{}/", &c1[..3], &c1[3..6]))
+                .expect("Zarr ID prefixes should be valid directory paths");
+            let zarr_path = PureDirPath::from(c1.clone());
+            return Some(ReqPath::Latest {
+                prefix,
+                zarr_id: c1,
+                zarr_path,
+                entry_path: collect_path(components),
+            });
+        }
         let mut prefix = PureDirPath::from(c1);
         let Some(c2) = components.next() else {
             return Some(ReqPath::Dir(prefix));
@@ -44,22 +73,25 @@ impl ReqPath {
             prefix.push(&zarr_id);
             return Some(ReqPath::Dir(prefix));
         };
-        let checksum = checksum.strip_suffix(".zarr")?;
-        if checksum.contains('.') {
-            return None;
+        if checksum == LATEST_MANIFEST_NAME {
+            let mut zarr_path = prefix.join_one_dir(&zarr_id);
+            zarr_path.push(&checksum);
+            return Some(ReqPath::Latest {
+                prefix,
+                zarr_id,
+                zarr_path,
+                entry_path: collect_path(components),
+            });
         }
+        let checksum = strip_checksum_ext(&checksum, ".zarr")?;
         let manifest_path = ManifestPath {
             prefix,
             zarr_id,
             checksum,
         };
-        let Some(e1) = components.next() else {
+        let Some(entry_path) = collect_path(components) else {
             return Some(ReqPath::Manifest(manifest_path));
         };
-        let mut entry_path = PurePath::from(e1);
-        for e in components {
-            entry_path.push(&e);
-        }
         Some(ReqPath::InManifest {
             manifest_path,
             entry_path,
@@ -67,6 +99,24 @@ impl ReqPath {
     }
 }
 
+/// Returns `true` if `c` is a Zarr ID, i.e., a UUID in lowercase hyphenated
+/// form.  Uppercase IDs are not accepted, as the manifest tree is keyed by
+/// lowercase IDs.
+fn is_zarr_id(c: &Component) -> bool {
+    c.len() == 36 && !c.bytes().any(|b| b.is_ascii_uppercase()) && uuid::Uuid::try_parse(c).is_ok()
+}
+
+/// Join the given path components into a `PurePath`, or return `None` if
+/// there are none
+fn collect_path<I: IntoIterator<Item = Component>>(components: I) -> Option<PurePath> {
+    let mut components = components.into_iter();
+    let mut path = PurePath::from(components.next()?);
+    for c in components {
+        path.push(&c);
+    }
+    Some(path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,6 +170,50 @@ mod tests {
         );
     }
 
+    #[rstest]
+    #[case(
+        "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d",
+        "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/",
+        None
+    )]
+    #[case(
+        "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/0/.zarray",
+        "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/",
+        Some("0/.zarray")
+    )]
+    #[case(
+        "128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/latest",
+        "128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/latest/",
+        None
+    )]
+    #[case(
+        "128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/latest/.zgroup",
+        "128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/latest/",
+        Some(".zgroup")
+    )]
+    fn test_parse_latest(
+        #[case] inpath: PurePath,
+        #[case] zpath: PureDirPath,
+        #[case] epath: Option<&str>,
+    ) {
+        assert_matches!(
+            ReqPath::parse_path(&inpath),
+            Some(ReqPath::Latest { prefix, zarr_id, zarr_path, entry_path }) => {
+                assert_eq!(prefix, "128/4a1/");
+                assert_eq!(zarr_id, "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d");
+                assert_eq!(zarr_path, zpath);
+                assert_eq!(entry_path.as_ref().map(PurePath::as_ref), epath);
+            }
+        );
+    }
+
+    #[rstest]
+    #[case("1284A14F-FE4F-4DC3-B10D-48E5DB8BF18D")]
+    #[case("1284a14f-fe4f-4dc3-b10d-48E5DB8BF18D")]
+    fn test_reject_uppercase_zarr_id(#[case] path: PurePath) {
+        assert_matches!(ReqPath::parse_path(&path), Some(ReqPath::Dir(_)));
+    }
+
     #[rstest]
     #[case("128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390")]
     #[case("128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json")]
@@ -1,6 +1,8 @@
 use crate::httputil::HttpUrl;
-use crate::paths::{Component, PureDirPath, PurePath};
+use crate::paths::{strip_checksum_ext, Component, PureDirPath, PurePath};
 use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
 use time::OffsetDateTime;
 
 /// A resource served under `dandidav`'s `/zarrs/` hierarchy, not including
@@ -11,6 +13,7 @@ pub(crate) enum ZarrManResource {
     Manifest(Manifest),
     ManFolder(ManifestFolder),
     ManEntry(ManifestEntry),
+    ZMetadata(ConsolidatedMetadata),
 }
 
 /// A collection between the root of the `/zarrs/` hierarchy and the Zarr
@@ -30,6 +33,13 @@ pub(crate) struct WebFolder {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) struct Manifest {
     pub(crate) path: ManifestPath,
+
+    /// The path to the Zarr as served by `dandidav`.  This is normally
+    /// `path.to_web_path()`, but differs when the Zarr's most recent manifest
+    /// is requested via its Zarr ID or when the Zarr is served as an asset
+    /// of a Dandiset version, in which case the path is relative to the
+    /// version.
+    pub(crate) web_path: PureDirPath,
 }
 
 /// A path to a Zarr manifest in the manifest tree or a Zarr collection in the
@@ -48,6 +58,19 @@ pub(crate) struct ManifestPath {
 }
 
 impl ManifestPath {
+    /// Returns the path to the manifest with checksum `checksum` for the Zarr
+    /// with ID `zarr_id`, or `None` if either value is not a valid path
+    /// component or the Zarr ID is too short to be split into a prefix
+    pub(crate) fn for_zarr(zarr_id: &str, checksum: &str) -> Option<ManifestPath> {
+        let prefix =
+            PureDirPath::try_from(format!("{}/{}/", zarr_id.get(..3)?, zarr_id.get(3..6)?)).ok()?;
+        Some(ManifestPath {
+            prefix,
+            zarr_id: zarr_id.parse().ok()?,
+            checksum: checksum.parse().ok()?,
+        })
+    }
+
     /// Returns the Zarr ID
     pub(super) fn zarr_id(&self) -> &str {
         self.zarr_id.as_ref()
@@ -79,12 +102,47 @@ impl fmt::Display for ManifestPath {
     }
 }
 
+impl FromStr for ManifestPath {
+    type Err = ParseManifestPathError;
+
+    /// Parse a path to a Zarr manifest relative to the manifest root, of the
+    /// form `{prefix1}/{prefix2}/{zarr_id}/{checksum}.json`
+    fn from_str(s: &str) -> Result<ManifestPath, ParseManifestPathError> {
+        let path = s.parse::<PurePath>().map_err(|_| ParseManifestPathError)?;
+        let mut components = path.components();
+        let (Some(c1), Some(c2), Some(zarr_id), Some(filename), None) = (
+            components.next(),
+            components.next(),
+            components.next(),
+            components.next(),
+            components.next(),
+        ) else {
+            return Err(ParseManifestPathError);
+        };
+        let checksum = strip_checksum_ext(&filename, ".json").ok_or(ParseManifestPathError)?;
+        let mut prefix = PureDirPath::from(c1);
+        prefix.push(&c2);
+        Ok(ManifestPath {
+            prefix,
+            zarr_id,
+            checksum,
+        })
+    }
+}
+
 impl fmt::Debug for ManifestPath {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self.to_string())
     }
 }
 
+/// Error returned when parsing an invalid path to a Zarr manifest
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error(
+    "expected a manifest path of the form {{prefix1}}/{{prefix2}}/{{zarr_id}}/{{checksum}}.json"
+)]
+pub(crate) struct ParseManifestPathError;
+
 /// A resource served under `dandidav`'s `/zarrs/` hierarchy, including
 /// information on child resources
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -102,6 +160,7 @@ pub(crate) enum ZarrManResourceWithChildren {
         children: Vec<ZarrManResource>,
     },
     ManEntry(ManifestEntry),
+    ZMetadata(ConsolidatedMetadata),
 }
 
 /// A folder within a Zarr
@@ -113,8 +172,9 @@ pub(crate) struct ManifestFolder {
 /// An entry within a Zarr
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) struct ManifestEntry {
-    /// The path to the entry as served by `dandidav`, i.e., a path of the form
-    /// `zarrs/{p1}/{p2}/{zarr_id}/{checksum}.zarr/{entry_path}`
+    /// The path to the entry as served by `dandidav`, i.e., the path to its
+    /// Zarr (normally of the form `zarrs/{p1}/{p2}/{zarr_id}/{checksum}.zarr/`)
+    /// followed by the entry's path within the Zarr
     pub(crate) web_path: PurePath,
 
     /// The size of the entry in bytes
@@ -130,9 +190,29 @@ pub(crate) struct ManifestEntry {
     pub(crate) url: HttpUrl,
 }
 
+/// A consolidated metadata file (`.zmetadata`) synthesized by `dandidav` at
+/// the root of a Zarr v2 hierarchy from the Zarr's `.zattrs`, `.zarray`, and
+/// `.zgroup` entries
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ConsolidatedMetadata {
+    /// The path to the file as served by `dandidav`, i.e., the path to its
+    /// Zarr (normally of the form `zarrs/{p1}/{p2}/{zarr_id}/{checksum}.zarr/`)
+    /// followed by `.zmetadata`
+    pub(crate) web_path: PurePath,
+
+    /// The latest modification time of the metadata entries, if there are
+    /// any
+    pub(crate) modified: Option<OffsetDateTime>,
+
+    /// The content of the file.  This is `None` when the file is listed as a
+    /// child of its Zarr, in which case the content is not computed.
+    pub(crate) content: Option<Vec<u8>>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
 
     #[test]
     fn manifest_path_to_urls() {
@@ -146,4 +226,43 @@ mod tests {
         assert_eq!(mp.to_web_path(), "zarrs/128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.zarr/");
         assert_eq!(mp.under_manifest_root(&"https://datasets.datalad.org/dandi/zarr-manifests/zarr-manifests-v2-sorted/".parse().unwrap()).as_str(), "https://datasets.datalad.org/dandi/zarr-manifests/zarr-manifests-v2-sorted/128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json");
     }
+
+    #[test]
+    fn manifest_path_for_zarr() {
+        let mp = ManifestPath::for_zarr(
+            "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d",
+            "6ddc4625befef8d6f9796835648162be-509--710206390",
+        )
+        .unwrap();
+        assert_eq!(mp.to_string(), "128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json");
+        assert_eq!(ManifestPath::for_zarr("12345", "abc"), None);
+        assert_eq!(ManifestPath::for_zarr("12/45678", "abc"), None);
+        assert_eq!(
+            ManifestPath::for_zarr("1284a14f-fe4f-4dc3-b10d-48e5db8bf18d", "a/b"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_manifest_path() {
+        let mp = "128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json"
+            .parse::<ManifestPath>()
+            .unwrap();
+        assert_eq!(mp.prefix, "128/4a1/");
+        assert_eq!(mp.zarr_id, "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d");
+        assert_eq!(
+            mp.checksum,
+            "6ddc4625befef8d6f9796835648162be-509--710206390"
+        );
+    }
+
+    #[rstest]
+    #[case("128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d")]
+    #[case("128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.zarr")]
+    #[case("128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json/0")]
+    #[case("/128/4a1/1284a14f-fe4f-4dc3-b10d-48e5db8bf18d/6ddc4625befef8d6f9796835648162be-509--710206390.json")]
+    #[case("")]
+    fn parse_bad_manifest_path(#[case] s: &str) {
+        assert_eq!(s.parse::<ManifestPath>(), Err(ParseManifestPathError));
+    }
 }
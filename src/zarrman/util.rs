@@ -1,8 +1,10 @@
+use super::encoding::DecodeError;
 use super::resources::ManifestPath;
 use crate::dav::ErrorClass;
-use crate::httputil::HttpError;
+use crate::httputil::{HttpError, HttpUrl};
 use crate::paths::{Component, PurePath};
 use serde::Deserialize;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -15,12 +17,35 @@ pub(crate) enum ZarrManError {
     #[error("invalid path requested: {path:?}")]
     InvalidPath { path: PurePath },
 
+    /// A manifest was served in a content coding that `dandidav` does not
+    /// support or could not be decompressed
+    #[error("failed to decode manifest at {url}")]
+    DecodeManifest { url: HttpUrl, source: DecodeError },
+
+    /// A manifest was written in a version of the manifest schema that
+    /// `dandidav` does not support
+    #[error("manifest at {url} uses unsupported schema version {version}")]
+    UnsupportedManifestVersion { url: HttpUrl, version: String },
+
     /// An request was made for a nonexistent path inside an extant Zarr
     #[error("path {entry_path:?} inside manifest at {manifest_path:?} does not exist")]
     ManifestPathNotFound {
         manifest_path: ManifestPath,
         entry_path: PurePath,
     },
+
+    /// A Zarr's most recent manifest was requested, but the manifest tree
+    /// contains no manifests for the Zarr
+    #[error("no manifests found for Zarr {zarr_id}")]
+    NoManifests { zarr_id: Component },
+
+    /// Too many other manifests were being fetched for the manifest to be
+    /// fetched within the queueing timeout
+    #[error("timed out after {timeout:?} waiting to fetch manifest {manifest_path:?}")]
+    FetchQueueTimeout {
+        manifest_path: ManifestPath,
+        timeout: Duration,
+    },
 }
 
 impl ZarrManError {
@@ -28,9 +53,12 @@ impl ZarrManError {
     pub(crate) fn class(&self) -> ErrorClass {
         match self {
             ZarrManError::Http(source) => source.class(),
-            ZarrManError::InvalidPath { .. } | ZarrManError::ManifestPathNotFound { .. } => {
-                ErrorClass::NotFound
-            }
+            ZarrManError::DecodeManifest { .. }
+            | ZarrManError::UnsupportedManifestVersion { .. } => ErrorClass::BadGateway,
+            ZarrManError::InvalidPath { .. }
+            | ZarrManError::ManifestPathNotFound { .. }
+            | ZarrManError::NoManifests { .. } => ErrorClass::NotFound,
+            ZarrManError::FetchQueueTimeout { .. } => ErrorClass::Unavailable,
         }
     }
 }
@@ -45,4 +73,70 @@ pub(super) struct Index {
     pub(super) files: Vec<Component>,
     /// The names of the subdirectories of the directory
     pub(super) directories: Vec<Component>,
+    /// If the listing is paginated, the (possibly relative) URL of the next
+    /// page of the listing
+    #[serde(default)]
+    pub(super) next: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zarrman_error_class() {
+        let manifest_path = ManifestPath {
+            prefix: "128/4a1/".parse().unwrap(),
+            zarr_id: "1284a14f-fe4f-4dc3-b10d-48e5db8bf18d".parse().unwrap(),
+            checksum: "6ddc4625befef8d6f9796835648162be-509--710206390"
+                .parse()
+                .unwrap(),
+        };
+        let url = "https://datasets.datalad.org/dandi/zarr-manifests/"
+            .parse::<HttpUrl>()
+            .unwrap();
+        let cases = [
+            (
+                ZarrManError::Http(HttpError::NotFound { url: url.clone() }),
+                ErrorClass::NotFound,
+            ),
+            (
+                ZarrManError::InvalidPath {
+                    path: "128/4a1/foo.json".parse().unwrap(),
+                },
+                ErrorClass::NotFound,
+            ),
+            (
+                ZarrManError::DecodeManifest {
+                    url: url.clone(),
+                    source: DecodeError::Unsupported("br".into()),
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                ZarrManError::UnsupportedManifestVersion {
+                    url,
+                    version: "4".into(),
+                },
+                ErrorClass::BadGateway,
+            ),
+            (
+                ZarrManError::ManifestPathNotFound {
+                    manifest_path: manifest_path.clone(),
+                    entry_path: "0/0/0".parse().unwrap(),
+                },
+                ErrorClass::NotFound,
+            ),
+            (
+                ZarrManError::FetchQueueTimeout {
+                    manifest_path,
+                    timeout: Duration::from_secs(30),
+                },
+                ErrorClass::Unavailable,
+            ),
+        ];
+        for (e, class) in cases {
+            assert_eq!(e.class(), class, "wrong class for {e:?}");
+        }
+    }
 }